@@ -85,6 +85,13 @@ pub struct CliOptionsRun {
     /// `Multiaddr` of an additional node to try to connect to on startup.
     #[arg(long, value_parser = parse_bootnode)]
     pub additional_bootnode: Vec<Bootnode>,
+    /// `Multiaddr` of a node to always stay connected to. Can be passed multiple times.
+    #[arg(long, value_parser = parse_bootnode)]
+    pub reserved_nodes: Vec<Bootnode>,
+    /// Only connect to the nodes passed through `--reserved-nodes` or added through the JSON-RPC
+    /// API, and refuse all other nodes.
+    #[arg(long)]
+    pub reserved_only: bool,
     /// Bind point of the JSON-RPC server ("none" or `<ip>:<port>`).
     #[arg(long, default_value = "127.0.0.1:9944", value_parser = parse_json_rpc_address)]
     pub json_rpc_address: JsonRpcAddress,
@@ -98,6 +105,10 @@ pub struct CliOptionsRun {
     /// Address of a Jaeger agent to send traces to (hint: port is typically 6831).
     #[arg(long)]
     pub jaeger: Option<SocketAddr>,
+    /// Bind point of the HTTP server exporting Prometheus metrics at `/metrics`
+    /// (`<ip>:<port>`). If not passed, the metrics aren't exported.
+    #[arg(long)]
+    pub prometheus_address: Option<SocketAddr>,
     /// Do not load or store anything on disk.
     #[arg(long)]
     pub tmp: bool,
@@ -108,6 +119,14 @@ pub struct CliOptionsRun {
     /// chain is not a parachain.
     #[arg(long, default_value = "256M", value_parser = parse_max_bytes)]
     pub relay_chain_database_cache_size: MaxBytes,
+    /// Author blocks on demand instead of following the consensus slots: instant, manual.
+    /// For local development only.
+    #[arg(long)]
+    pub dev_seal: Option<DevSeal>,
+    /// If the database is empty, warp sync to the head of the finalized chain and download its
+    /// storage instead of verifying all the blocks since the genesis.
+    #[arg(long)]
+    pub warp_sync: bool,
 }
 
 #[derive(Debug, clap::Parser)]
@@ -197,6 +216,12 @@ pub enum Output {
     LogsJson,
 }
 
+#[derive(Debug, Clone, clap::ValueEnum)]
+pub enum DevSeal {
+    Instant,
+    Manual,
+}
+
 #[derive(Debug, Clone)]
 pub struct JsonRpcAddress(pub Option<SocketAddr>);
 
@@ -223,6 +223,10 @@ async fn run(cli_options: cli::CliOptionsRun) {
     let keystore_path = base_storage_directory
         .as_ref()
         .map(|path| path.join(parsed_chain_spec.id()).join("keys"));
+    // File supposed to contain the list of known peers.
+    let address_book_path = base_storage_directory
+        .as_ref()
+        .map(|path| path.join(parsed_chain_spec.id()).join("peers.json"));
 
     // Build the relay chain information if relevant.
     let (relay_chain, relay_chain_name) =
@@ -251,6 +255,8 @@ async fn run(cli_options: cli::CliOptionsRun) {
             let cfg = smoldot_full_node::ChainConfig {
                 chain_spec: spec_json.into(),
                 additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
                 keystore_memory: Vec::new(),
                 sqlite_database_path: base_storage_directory.as_ref().map(|d| {
                     d.join(parsed_relay_spec.id())
@@ -261,7 +267,11 @@ async fn run(cli_options: cli::CliOptionsRun) {
                 keystore_path: base_storage_directory
                     .as_ref()
                     .map(|path| path.join(parsed_relay_spec.id()).join("keys")),
+                address_book_path: base_storage_directory
+                    .as_ref()
+                    .map(|path| path.join(parsed_relay_spec.id()).join("peers.json")),
                 json_rpc_listen: None,
+                warp_sync: cli_options.warp_sync,
             };
 
             (Some(cfg), Some(relay_chain_name.to_owned()))
@@ -367,10 +377,17 @@ async fn run(cli_options: cli::CliOptionsRun) {
                 .iter()
                 .map(|cli::Bootnode { address, peer_id }| (peer_id.clone(), address.clone()))
                 .collect(),
+            reserved_nodes: cli_options
+                .reserved_nodes
+                .iter()
+                .map(|cli::Bootnode { address, peer_id }| (peer_id.clone(), address.clone()))
+                .collect(),
+            reserved_only: cli_options.reserved_only,
             keystore_memory: cli_options.keystore_memory,
             sqlite_database_path,
             sqlite_cache_size: cli_options.database_cache_size.0,
             keystore_path,
+            address_book_path,
             json_rpc_listen: if let Some(address) = cli_options.json_rpc_address.0 {
                 Some(smoldot_full_node::JsonRpcListenConfig {
                     address,
@@ -379,6 +396,7 @@ async fn run(cli_options: cli::CliOptionsRun) {
             } else {
                 None
             },
+            warp_sync: cli_options.warp_sync,
         },
         relay_chain,
         libp2p_key,
@@ -388,7 +406,13 @@ async fn run(cli_options: cli::CliOptionsRun) {
             Arc::new(move |task| executor.spawn(task).detach())
         },
         log_callback: log_callback.clone(),
+        clock: smoldot_full_node::Clock::system(),
         jaeger_agent: cli_options.jaeger,
+        prometheus_address: cli_options.prometheus_address,
+        dev_seal: cli_options.dev_seal.map(|dev_seal| match dev_seal {
+            cli::DevSeal::Instant => smoldot_full_node::DevSeal::Instant,
+            cli::DevSeal::Manual => smoldot_full_node::DevSeal::Manual,
+        }),
     })
     .await;
 
@@ -414,6 +438,13 @@ async fn run(cli_options: cli::CliOptionsRun) {
         );
     }
 
+    if let Some(addr) = client.prometheus_server_addr() {
+        log_callback.log(
+            smoldot_full_node::LogLevel::Info,
+            format!("Prometheus metrics available at <http://{addr}/metrics>."),
+        );
+    }
+
     // Starting from here, a SIGINT (or equivalent) handler is set up. If the user does Ctrl+C,
     // an event will be triggered on `ctrlc_detected`.
     // This should be performed after all the expensive initialization is done, as otherwise these
@@ -0,0 +1,502 @@
+// Smoldot
+// Copyright (C) 2019-2022  Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Background authority discovery service.
+//!
+//! The [`AuthorityDiscoveryService`] periodically obtains the list of authorities of the
+//! finalized block of the chain, then publishes in the Kademlia DHT the addresses of the local
+//! node for each authority discovery key of the keystore that belongs to this list, and looks
+//! up the addresses of all the other authorities. The addresses that are found are reported to
+//! the network service.
+//!
+//! See also [`smoldot::network::authority_discovery`].
+
+use crate::{
+    compiled_runtimes_cache, database_thread, network_service, Clock, LogCallback, LogLevel,
+};
+
+use smol::future;
+use smoldot::{executor, identity::keystore, network::authority_discovery, trie};
+use std::{future::Future, iter, pin::Pin, sync::Arc, time::Duration};
+
+/// Configuration for an [`AuthorityDiscoveryService`].
+pub struct Config {
+    /// Closure that spawns background tasks.
+    pub tasks_executor: Box<dyn FnMut(Pin<Box<dyn Future<Output = ()> + Send>>) + Send>,
+
+    /// Function called in order to notify of something.
+    pub log_callback: Arc<dyn LogCallback + Send + Sync>,
+
+    /// Source of time of the node.
+    pub clock: Clock,
+
+    /// Database to use to obtain the list of authorities of the finalized block.
+    pub database: Arc<database_thread::DatabaseThread>,
+
+    /// Cache used when compiling the runtime of the finalized block.
+    pub compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
+    /// Keystore containing the authority discovery keys of the local node.
+    pub keystore: Arc<keystore::Keystore>,
+
+    /// Access to the network, and identifier of the chain from the point of view of the network
+    /// service.
+    pub network_service: (
+        Arc<network_service::NetworkService>,
+        network_service::ChainId,
+    ),
+
+    /// Record containing the addresses of the local node, to publish for each authority
+    /// discovery key of the keystore that belongs to the list of authorities.
+    pub local_record: authority_discovery::LocalRecord,
+}
+
+/// Running authority discovery service. Stops when destroyed.
+pub struct AuthorityDiscoveryService {
+    /// Notified when the service shuts down.
+    foreground_shutdown: event_listener::Event,
+}
+
+impl AuthorityDiscoveryService {
+    /// Starts a new authority discovery service.
+    pub fn new(mut config: Config) -> Self {
+        let foreground_shutdown = event_listener::Event::new();
+
+        let mut background = Background {
+            log_callback: config.log_callback,
+            database: config.database,
+            compiled_runtimes_cache: config.compiled_runtimes_cache,
+            keystore: config.keystore,
+            network_service: config.network_service.0,
+            network_chain_id: config.network_service.1,
+            local_record: config.local_record,
+            runtime: None,
+        };
+
+        let clock = config.clock;
+        let mut on_foreground_shutdown = foreground_shutdown.listen();
+        (config.tasks_executor)(Box::pin(async move {
+            // The first round is delayed in order to give some time to the networking to
+            // connect to peers.
+            let mut next_round = Duration::from_secs(30);
+
+            loop {
+                let still_alive = future::race(
+                    async {
+                        clock.sleep(next_round).await;
+                        true
+                    },
+                    async {
+                        (&mut on_foreground_shutdown).await;
+                        false
+                    },
+                )
+                .await;
+                if !still_alive {
+                    break;
+                }
+
+                background.round().await;
+                next_round = Duration::from_secs(10 * 60);
+            }
+        }));
+
+        AuthorityDiscoveryService {
+            foreground_shutdown,
+        }
+    }
+}
+
+impl Drop for AuthorityDiscoveryService {
+    fn drop(&mut self) {
+        self.foreground_shutdown.notify(usize::max_value());
+    }
+}
+
+struct Background {
+    /// See [`Config::log_callback`].
+    log_callback: Arc<dyn LogCallback + Send + Sync>,
+
+    /// See [`Config::database`].
+    database: Arc<database_thread::DatabaseThread>,
+
+    /// See [`Config::compiled_runtimes_cache`].
+    compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
+    /// See [`Config::keystore`].
+    keystore: Arc<keystore::Keystore>,
+
+    /// See [`Config::network_service`].
+    network_service: Arc<network_service::NetworkService>,
+
+    /// See [`Config::network_service`].
+    network_chain_id: network_service::ChainId,
+
+    /// See [`Config::local_record`].
+    local_record: authority_discovery::LocalRecord,
+
+    /// Runtime of the finalized block during the previous round, alongside with its code.
+    /// Used in order to avoid compiling the runtime at each round.
+    runtime: Option<(Vec<u8>, executor::host::HostVmPrototype)>,
+}
+
+impl Background {
+    /// Publishes the record of the local node and looks up the records of the other
+    /// authorities.
+    async fn round(&mut self) {
+        let authorities = match self.authorities().await {
+            Ok(authorities) => authorities,
+            Err(error) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!("authority-discovery-authorities-error; error={}", error),
+                );
+                return;
+            }
+        };
+
+        // Calling `keys()` on the keystore is racy, but that's considered acceptable and part
+        // of the design of the node.
+        let local_keys = self
+            .keystore
+            .keys()
+            .await
+            .filter(|(namespace, _)| *namespace == keystore::KeyNamespace::AuthorityDiscovery)
+            .map(|(_, public_key)| public_key)
+            .filter(|public_key| authorities.contains(public_key))
+            .collect::<Vec<_>>();
+
+        for public_key in &local_keys {
+            self.publish(public_key).await;
+        }
+
+        for authority in &authorities {
+            if local_keys.contains(authority) {
+                continue;
+            }
+
+            self.lookup(authority).await;
+        }
+    }
+
+    /// Publishes the record of the local node under the given authority discovery key.
+    async fn publish(&self, authority_public_key: &[u8; 32]) {
+        let authority_signature = match self
+            .keystore
+            .sign(
+                keystore::KeyNamespace::AuthorityDiscovery,
+                authority_public_key,
+                self.local_record.payload_to_sign(),
+            )
+            .await
+        {
+            Ok(signature) => signature,
+            Err(error) => {
+                self.log_callback.log(
+                    LogLevel::Warn,
+                    format!(
+                        "authority-discovery-sign-error; public_key={}; error={}",
+                        hex::encode(authority_public_key),
+                        error
+                    ),
+                );
+                return;
+            }
+        };
+
+        let key = authority_discovery::authority_dht_key(authority_public_key).to_vec();
+        let record = self.local_record.build_signed_record(&authority_signature);
+
+        // The record is stored on the peers of the network that are the closest to the key.
+        let closest_peers = self
+            .network_service
+            .clone()
+            .kademlia_lookup(self.network_chain_id, key.clone())
+            .await
+            .closest_peers;
+
+        let mut num_successes = 0;
+        for target in closest_peers {
+            if self
+                .network_service
+                .clone()
+                .kademlia_put_value_request(
+                    target,
+                    self.network_chain_id,
+                    key.clone(),
+                    record.clone(),
+                )
+                .await
+                .is_ok()
+            {
+                num_successes += 1;
+            }
+        }
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "authority-discovery-published; public_key={}; num_peers={}",
+                hex::encode(authority_public_key),
+                num_successes
+            ),
+        );
+    }
+
+    /// Looks up the record of the given authority and, if one is found, reports its addresses
+    /// to the network service.
+    async fn lookup(&self, authority_public_key: &[u8; 32]) {
+        let key = authority_discovery::authority_dht_key(authority_public_key).to_vec();
+
+        let records = self
+            .network_service
+            .clone()
+            .kademlia_lookup(self.network_chain_id, key)
+            .await
+            .records;
+
+        for (target, record) in records {
+            match authority_discovery::decode_and_verify_record(&record, authority_public_key) {
+                Ok(authority_discovery::VerifiedRecord { peer_id, addresses }) => {
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "authority-discovery-found; public_key={}; peer_id={}; \
+                            addresses={:?}",
+                            hex::encode(authority_public_key),
+                            peer_id,
+                            addresses
+                        ),
+                    );
+
+                    self.network_service
+                        .discovered_addresses(self.network_chain_id, peer_id, addresses)
+                        .await;
+                    return;
+                }
+                Err(error) => {
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "authority-discovery-bad-record; public_key={}; peer_id={}; \
+                            error={}",
+                            hex::encode(authority_public_key),
+                            target,
+                            error
+                        ),
+                    );
+                }
+            }
+        }
+    }
+
+    /// Calls the runtime of the finalized block in order to obtain the list of authorities.
+    async fn authorities(&mut self) -> Result<Vec<[u8; 32]>, AuthoritiesError> {
+        let (block_hash, code, heap_pages) = self
+            .database
+            .with_database(
+                |database| -> Result<_, database_thread::StorageAccessError> {
+                    let block_hash = database
+                        .finalized_block_hash()
+                        .map_err(database_thread::StorageAccessError::Corrupted)?;
+                    let code = database.block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        trie::bytes_to_nibbles(b":code".iter().copied()).map(u8::from),
+                    )?;
+                    let heap_pages = database.block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        trie::bytes_to_nibbles(b":heappages".iter().copied()).map(u8::from),
+                    )?;
+                    Ok((block_hash, code, heap_pages))
+                },
+            )
+            .await
+            .map_err(AuthoritiesError::Database)?;
+
+        let (code, _) = code.ok_or(AuthoritiesError::NoCode)?;
+
+        let runtime = match &self.runtime {
+            Some((runtime_code, runtime)) if *runtime_code == code => runtime.clone(),
+            _ => {
+                let heap_pages =
+                    executor::storage_heap_pages_to_value(heap_pages.as_ref().map(|(h, _)| &h[..]))
+                        .map_err(AuthoritiesError::InvalidHeapPages)?;
+                let runtime = self
+                    .compiled_runtimes_cache
+                    .build(&code, heap_pages, true)
+                    .map_err(AuthoritiesError::InvalidRuntime)?;
+                self.runtime = Some((code, runtime.clone()));
+                runtime
+            }
+        };
+
+        let mut call = executor::runtime_host::run(executor::runtime_host::Config {
+            virtual_machine: runtime,
+            function_to_call: authority_discovery::AUTHORITIES_FUNCTION_NAME,
+            parameter: iter::empty::<&'static [u8]>(),
+            max_log_level: 0,
+            storage_main_trie_changes: Default::default(),
+            calculate_trie_changes: false,
+            trace: false,
+        })
+        .map_err(|(error, _)| AuthoritiesError::StartCall(error))?;
+
+        loop {
+            match call {
+                executor::runtime_host::RuntimeHostVm::Finished(Ok(success)) => {
+                    return authority_discovery::decode_authorities(
+                        success.virtual_machine.value().as_ref(),
+                    )
+                    .map_err(AuthoritiesError::Decode);
+                }
+                executor::runtime_host::RuntimeHostVm::Finished(Err(error)) => {
+                    return Err(AuthoritiesError::Call(error.detail));
+                }
+                executor::runtime_host::RuntimeHostVm::StorageGet(req) => {
+                    let parent_paths = req.child_trie().map(|child_trie| {
+                        trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                            .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                            .map(u8::from)
+                            .collect::<Vec<_>>()
+                    });
+                    let key = trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
+                        .map(u8::from)
+                        .collect::<Vec<_>>();
+                    let value = self
+                        .database
+                        .with_database(move |db| {
+                            db.block_storage_get(
+                                &block_hash,
+                                parent_paths.into_iter().map(|p| p.into_iter()),
+                                key.iter().copied(),
+                            )
+                        })
+                        .await
+                        .map_err(AuthoritiesError::Database)?;
+                    let value = value.as_ref().map(|(val, vers)| {
+                        (
+                            iter::once(&val[..]),
+                            executor::runtime_host::TrieEntryVersion::try_from(*vers)
+                                .expect("corrupted database"),
+                        )
+                    });
+
+                    call = req.inject_value(value);
+                }
+                executor::runtime_host::RuntimeHostVm::ClosestDescendantMerkleValue(req) => {
+                    let parent_paths = req.child_trie().map(|child_trie| {
+                        trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                            .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                            .map(u8::from)
+                            .collect::<Vec<_>>()
+                    });
+                    let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
+
+                    let merkle_value = self
+                        .database
+                        .with_database(move |db| {
+                            db.block_storage_closest_descendant_merkle_value(
+                                &block_hash,
+                                parent_paths.into_iter().map(|p| p.into_iter()),
+                                key_nibbles.iter().copied(),
+                            )
+                        })
+                        .await
+                        .map_err(AuthoritiesError::Database)?;
+
+                    call = req.inject_merkle_value(merkle_value.as_ref().map(|v| &v[..]));
+                }
+                executor::runtime_host::RuntimeHostVm::NextKey(req) => {
+                    let parent_paths = req.child_trie().map(|child_trie| {
+                        trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                            .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                            .map(u8::from)
+                            .collect::<Vec<_>>()
+                    });
+                    let key_nibbles = req
+                        .key()
+                        .map(u8::from)
+                        .chain(if req.or_equal() { None } else { Some(0u8) })
+                        .collect::<Vec<_>>();
+                    let prefix_nibbles = req.prefix().map(u8::from).collect::<Vec<_>>();
+
+                    let branch_nodes = req.branch_nodes();
+                    let next_key = self
+                        .database
+                        .with_database(move |db| {
+                            db.block_storage_next_key(
+                                &block_hash,
+                                parent_paths.into_iter().map(|p| p.into_iter()),
+                                key_nibbles.iter().copied(),
+                                prefix_nibbles.iter().copied(),
+                                branch_nodes,
+                            )
+                        })
+                        .await
+                        .map_err(AuthoritiesError::Database)?;
+
+                    call = req.inject_key(
+                        next_key.map(|k| k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())),
+                    );
+                }
+                executor::runtime_host::RuntimeHostVm::OffchainStorageSet(req) => {
+                    call = req.resume();
+                }
+                executor::runtime_host::RuntimeHostVm::SignatureVerification(req) => {
+                    call = req.verify_and_resume();
+                }
+                executor::runtime_host::RuntimeHostVm::Offchain(_) => {
+                    return Err(AuthoritiesError::OffchainCall);
+                }
+                executor::runtime_host::RuntimeHostVm::LogEmit(req) => {
+                    // Logs are ignored.
+                    call = req.resume();
+                }
+            }
+        }
+    }
+}
+
+/// Error while obtaining the list of authorities.
+#[derive(Debug, derive_more::Display)]
+enum AuthoritiesError {
+    /// Error while accessing the database.
+    #[display(fmt = "Database access error: {_0}")]
+    Database(database_thread::StorageAccessError),
+    /// The finalized block doesn't have any runtime code.
+    NoCode,
+    /// The `:heappages` of the finalized block are invalid.
+    #[display(fmt = "Invalid heap pages: {_0}")]
+    InvalidHeapPages(executor::InvalidHeapPagesError),
+    /// Failed to compile the runtime of the finalized block.
+    #[display(fmt = "Invalid runtime: {_0}")]
+    InvalidRuntime(executor::host::NewErr),
+    /// Failed to start the runtime call. Typically happens if the runtime doesn't support
+    /// authority discovery.
+    #[display(fmt = "Failed to start runtime call: {_0}")]
+    StartCall(executor::host::StartErr),
+    /// Error during the runtime call.
+    #[display(fmt = "Error during runtime call: {_0}")]
+    Call(executor::runtime_host::ErrorDetail),
+    /// The runtime call has attempted to perform an offchain operation.
+    OffchainCall,
+    /// Failed to decode the output of the runtime call.
+    #[display(fmt = "{_0}")]
+    Decode(authority_discovery::DecodeAuthoritiesError),
+}
@@ -0,0 +1,187 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Source of time of the node.
+//!
+//! See [`Clock`].
+
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+/// Source of time of the node.
+///
+/// All the timers of the node that relate to the protocol (networking timeouts, slots of the
+/// consensus algorithm, periodic discoveries, etc.) are based on this clock.
+///
+/// The clock is either the clock of the operating system, or a clock that only advances when
+/// [`Clock::advance`] is called. The latter is meant to be used in tests.
+///
+/// > **Note**: The timers related to the TCP listeners of the JSON-RPC and Prometheus servers,
+/// >           and the measurements of how long an operation has taken, always use the clock of
+/// >           the operating system.
+#[derive(Clone)]
+pub struct Clock {
+    /// `None` if the clock of the operating system is used.
+    manual: Option<Arc<Manual>>,
+}
+
+impl Clock {
+    /// Returns a [`Clock`] that uses the clock of the operating system.
+    pub fn system() -> Self {
+        Clock { manual: None }
+    }
+
+    /// Returns a [`Clock`] that only advances when [`Clock::advance`] is called.
+    ///
+    /// Must be passed the value of the UNIX time that the clock starts at.
+    pub fn manual(unix_time_start: Duration) -> Self {
+        Clock {
+            manual: Some(Arc::new(Manual {
+                instant_start: Instant::now(),
+                unix_time_start,
+                state: Mutex::new(ManualState {
+                    elapsed: Duration::new(0, 0),
+                    sleeps: BTreeMap::new(),
+                }),
+                next_sleep_id: AtomicU64::new(0),
+            })),
+        }
+    }
+
+    /// Advances the clock by the given duration, and wakes up all the timers that have elapsed.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`Clock`] was created with [`Clock::system`].
+    ///
+    pub fn advance(&self, duration: Duration) {
+        let manual = self.manual.as_ref().unwrap();
+
+        let elapsed_sleeps = {
+            let mut state = manual.state.lock().unwrap();
+            state.elapsed += duration;
+            let now = manual.instant_start + state.elapsed;
+            let still_pending = state.sleeps.split_off(&(now + Duration::from_nanos(1), 0));
+            std::mem::replace(&mut state.sleeps, still_pending)
+        };
+
+        for (_, waker) in elapsed_sleeps {
+            waker.wake();
+        }
+    }
+
+    /// Returns the current time.
+    pub fn now(&self) -> Instant {
+        match &self.manual {
+            None => Instant::now(),
+            Some(manual) => manual.instant_start + manual.state.lock().unwrap().elapsed,
+        }
+    }
+
+    /// Returns the time elapsed since the UNIX epoch.
+    pub fn now_from_unix_epoch(&self) -> Duration {
+        match &self.manual {
+            // Panicking if the system clock is before the UNIX epoch is considered acceptable.
+            None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap(),
+            Some(manual) => manual.unix_time_start + manual.state.lock().unwrap().elapsed,
+        }
+    }
+
+    /// Returns a future that is ready after the given duration has elapsed.
+    pub fn sleep(&self, duration: Duration) -> Delay {
+        self.sleep_until(self.now() + duration)
+    }
+
+    /// Returns a future that is ready once the given moment has been reached.
+    pub fn sleep_until(&self, when: Instant) -> Delay {
+        match &self.manual {
+            None => Delay(DelayInner::System(smol::Timer::at(when))),
+            Some(manual) => Delay(DelayInner::Manual {
+                manual: manual.clone(),
+                when,
+                id: manual.next_sleep_id.fetch_add(1, Ordering::Relaxed),
+            }),
+        }
+    }
+}
+
+/// Future returned by [`Clock::sleep`] and [`Clock::sleep_until`].
+pub struct Delay(DelayInner);
+
+enum DelayInner {
+    System(smol::Timer),
+    Manual {
+        manual: Arc<Manual>,
+        when: Instant,
+        /// Identifier of this delay, used as a key in [`ManualState::sleeps`].
+        id: u64,
+    },
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        match &mut self.get_mut().0 {
+            DelayInner::System(timer) => Pin::new(timer).poll(cx).map(|_| ()),
+            DelayInner::Manual { manual, when, id } => {
+                let mut state = manual.state.lock().unwrap();
+                if manual.instant_start + state.elapsed >= *when {
+                    return Poll::Ready(());
+                }
+
+                state.sleeps.insert((*when, *id), cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        if let DelayInner::Manual { manual, when, id } = &self.0 {
+            manual.state.lock().unwrap().sleeps.remove(&(*when, *id));
+        }
+    }
+}
+
+/// Manually controlled clock shared between a [`Clock`] and its [`Delay`]s.
+struct Manual {
+    /// Value returned by [`Clock::now`] when the clock is at zero.
+    instant_start: Instant,
+    /// Value returned by [`Clock::now_from_unix_epoch`] when the clock is at zero.
+    unix_time_start: Duration,
+    state: Mutex<ManualState>,
+    /// Identifier to assign to the next [`Delay`].
+    next_sleep_id: AtomicU64,
+}
+
+struct ManualState {
+    /// Time elapsed since the creation of the [`Clock`].
+    elapsed: Duration,
+    /// List of [`Delay`]s that are waiting, indexed by when they finish and their identifier.
+    sleeps: BTreeMap<(Instant, u64), Waker>,
+}
@@ -0,0 +1,162 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! On-disk cache of compiled runtimes.
+//!
+//! Compiling a runtime ahead of time takes a noticeable amount of time. In order to not do this
+//! every time the node starts or every time an old runtime is needed, the compiled artifacts are
+//! saved in a directory, each in a file whose name is derived from the hash of the runtime code
+//! and from the version of the node. If the version of the node changes, the previously
+//! compiled artifacts are simply ignored.
+//!
+//! The files in this directory contain native machine code that is directly executed. The
+//! directory must therefore be trusted in the same way as the executable of the node itself.
+
+use crate::{LogCallback, LogLevel};
+
+use smoldot::executor::{host, vm};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+/// See [the module-level documentation](..).
+pub struct CompiledRuntimesCache {
+    /// Directory where the compiled artifacts are stored. If `None`, nothing is cached.
+    directory: Option<PathBuf>,
+
+    /// Function called in order to notify of something.
+    log_callback: Arc<dyn LogCallback + Send + Sync>,
+}
+
+impl CompiledRuntimesCache {
+    /// Creates a new [`CompiledRuntimesCache`].
+    ///
+    /// If `directory` is `None`, runtimes are always compiled and never saved. The directory
+    /// is automatically created if it doesn't exist.
+    pub fn new(
+        directory: Option<PathBuf>,
+        log_callback: Arc<dyn LogCallback + Send + Sync>,
+    ) -> Self {
+        CompiledRuntimesCache {
+            directory,
+            log_callback,
+        }
+    }
+
+    /// Builds a [`host::HostVmPrototype`] compiled ahead of time from the given runtime code,
+    /// loading it from the cache if possible and saving it to the cache otherwise.
+    pub fn build(
+        &self,
+        module: &[u8],
+        heap_pages: vm::HeapPages,
+        allow_unresolved_imports: bool,
+    ) -> Result<host::HostVmPrototype, host::NewErr> {
+        let config = || host::Config {
+            module,
+            heap_pages,
+            exec_hint: vm::ExecHint::CompileAheadOfTime,
+            allow_unresolved_imports,
+            fuel_budget: None,
+        };
+
+        let code_hash = blake2_rfc::blake2b::blake2b(32, &[], module);
+        let path = self.directory.as_ref().map(|directory| {
+            directory.join(format!(
+                "{}-{}.bin",
+                hex::encode(code_hash.as_bytes()),
+                env!("CARGO_PKG_VERSION")
+            ))
+        });
+
+        if let Some(path) = &path {
+            match fs::read(path) {
+                Ok(compiled) => {
+                    // Safety: the cache directory is trusted. See the module-level documentation.
+                    match unsafe { host::HostVmPrototype::from_compiled(config(), &compiled) } {
+                        Ok(runtime) => {
+                            self.log_callback.log(
+                                LogLevel::Debug,
+                                format!(
+                                    "compiled-runtime-cache-hit; code_hash={}",
+                                    hex::encode(code_hash.as_bytes())
+                                ),
+                            );
+                            return Ok(runtime);
+                        }
+                        Err(error) => {
+                            // The artifact might have been produced by a different version of
+                            // the execution engine. It gets overwritten below.
+                            self.log_callback.log(
+                                LogLevel::Debug,
+                                format!(
+                                    "compiled-runtime-cache-load-error; path={}; error={}",
+                                    path.display(),
+                                    error
+                                ),
+                            );
+                        }
+                    }
+                }
+                Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                Err(error) => {
+                    self.log_callback.log(
+                        LogLevel::Warn,
+                        format!(
+                            "compiled-runtime-cache-read-error; path={}; error={}",
+                            path.display(),
+                            error
+                        ),
+                    );
+                }
+            }
+        }
+
+        let runtime = host::HostVmPrototype::new(config())?;
+
+        if let (Some(path), Some(compiled)) = (path, runtime.serialize_compiled()) {
+            if let Err(error) = write_artifact(&path, &compiled) {
+                self.log_callback.log(
+                    LogLevel::Warn,
+                    format!(
+                        "compiled-runtime-cache-write-error; path={}; error={}",
+                        path.display(),
+                        error
+                    ),
+                );
+            }
+        }
+
+        Ok(runtime)
+    }
+}
+
+/// Writes a compiled artifact on disk. The content is first written to a temporary file which is
+/// then renamed, so that a half-written artifact is never loaded.
+///
+/// The name of the temporary file is randomized, as multiple tasks might save the same runtime
+/// at the same time.
+fn write_artifact(path: &Path, content: &[u8]) -> Result<(), io::Error> {
+    if let Some(directory) = path.parent() {
+        fs::create_dir_all(directory)?;
+    }
+
+    let tmp_path = path.with_extension(format!("{:016x}.tmp", rand::random::<u64>()));
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
@@ -24,13 +24,17 @@
 // TODO: doc
 // TODO: re-review this once finished
 
-use crate::{database_thread, jaeger_service, network_service, LogCallback, LogLevel};
+use crate::{
+    compiled_runtimes_cache, database_thread, jaeger_service, network_service, prometheus_service,
+    Clock, DevSeal, LogCallback, LogLevel,
+};
 
 use core::num::NonZeroU32;
 use futures_channel::{mpsc, oneshot};
 use futures_lite::FutureExt as _;
 use futures_util::{future, stream, SinkExt as _, StreamExt as _};
 use hashbrown::HashSet;
+use rand::seq::IteratorRandom as _;
 use smol::lock::Mutex;
 use smoldot::{
     author,
@@ -41,19 +45,28 @@ use smoldot::{
     informant::HashDisplay,
     libp2p,
     network::{self, codec::BlockData},
-    sync::all,
+    sync::{all, state_download},
     trie,
     verify::body_only::{self, StorageChanges, TrieEntryVersion},
 };
 use std::{
     array,
     borrow::Cow,
+    cmp,
+    collections::{BTreeSet, VecDeque},
     iter, mem,
     num::{NonZeroU64, NonZeroUsize},
     sync::Arc,
-    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+    time::{Duration, Instant},
 };
 
+/// Maximum number of block headers verified at once ahead of the execution of the blocks. See
+/// [`SyncBackground::headers_verify_ahead_in_progress`].
+const HEADERS_VERIFY_AHEAD_MAX: usize = 512;
+
+mod collator;
+mod parachain_inherent;
+
 /// Configuration for a [`ConsensusService`].
 pub struct Config {
     /// Closure that spawns background tasks.
@@ -62,9 +75,15 @@ pub struct Config {
     /// Function called in order to notify of something.
     pub log_callback: Arc<dyn LogCallback + Send + Sync>,
 
+    /// Source of time of the node.
+    pub clock: Clock,
+
     /// Database to use to read and write information about the chain.
     pub database: Arc<database_thread::DatabaseThread>,
 
+    /// Cache used when compiling the runtime of the finalized block.
+    pub compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
     /// Number of bytes of the block number in the networking protocol.
     pub block_number_bytes: usize,
 
@@ -91,6 +110,9 @@ pub struct Config {
     /// Service to use to report traces.
     pub jaeger_service: Arc<jaeger_service::JaegerService>,
 
+    /// Metrics of the chain to keep up to date.
+    pub metrics: Arc<prometheus_service::ChainMetrics>,
+
     /// A node has the authorization to author a block during a slot.
     ///
     /// In order for the network to perform well, a block should be authored and propagated
@@ -108,6 +130,49 @@ pub struct Config {
     /// Note that this value doesn't determine the moment when creating the block has ended, but
     /// the moment when creating the block should start its final phase.
     pub slot_duration_author_ratio: u16,
+
+    /// If `Some`, blocks are authored on demand rather than following the slots, and the
+    /// finality of the chain is decided locally. Networking peers are ignored.
+    ///
+    /// See [`ConsensusService::create_block`], [`ConsensusService::finalize_block`] and
+    /// [`ConsensusService::submit_transaction`].
+    pub dev_seal: Option<DevSeal>,
+
+    /// If `true`, the chain is first warp synced to the latest finalized block, whose storage
+    /// is then downloaded from the networking peers, instead of verifying every single block
+    /// since the finalized block of the database.
+    ///
+    /// Ignored if the finalized block of the database isn't the genesis block, or in dev seal
+    /// mode.
+    pub warp_sync: bool,
+
+    /// If `Some`, the chain is a parachain. The state of its relay chain is used in order to
+    /// build the parachain-related inherents of the blocks that are authored.
+    pub relay_chain: Option<RelayChainConfig>,
+}
+
+/// See [`Config::relay_chain`].
+pub struct RelayChainConfig {
+    /// Identifier of the parachain within the relay chain.
+    pub para_id: u32,
+
+    /// Database of the relay chain. The best block of this database is used as the relay parent
+    /// of the blocks that are authored.
+    pub database: Arc<database_thread::DatabaseThread>,
+
+    /// Cache used when compiling the runtime of the relay chain.
+    pub compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
+    /// Number of bytes of the block number of the relay chain.
+    pub block_number_bytes: usize,
+
+    /// Identifier of the relay chain within [`Config::network_service`]. Collations of the
+    /// blocks that are authored are announced on this chain.
+    pub network_chain_id: network_service::ChainId,
+
+    /// Public key of the collator, found in [`Config::keystore`] under the
+    /// [`keystore::KeyNamespace::Collator`] namespace.
+    pub collator_key: [u8; 32],
 }
 
 /// Identifier for a blocks request to be performed.
@@ -155,6 +220,47 @@ enum ToBackground {
     IsMajorSyncingHint {
         result_tx: oneshot::Sender<bool>,
     },
+    CreateBlock {
+        create_empty: bool,
+        finalize: bool,
+        parent_hash: Option<[u8; 32]>,
+        result_tx: oneshot::Sender<Result<[u8; 32], DevSealError>>,
+    },
+    FinalizeBlock {
+        block_hash: [u8; 32],
+        result_tx: oneshot::Sender<Result<(), DevSealError>>,
+    },
+    SubmitTransaction {
+        transaction: Vec<u8>,
+        result_tx: oneshot::Sender<Result<(), DevSealError>>,
+    },
+}
+
+/// Error potentially returned by [`ConsensusService::create_block`],
+/// [`ConsensusService::finalize_block`] and [`ConsensusService::submit_transaction`].
+#[derive(Debug, derive_more::Display)]
+pub enum DevSealError {
+    /// The node hasn't been started in dev seal mode. See [`Config::dev_seal`].
+    #[display(fmt = "Node isn't running in dev seal mode")]
+    Disabled,
+    /// Creating an empty block has been refused and no transaction is waiting to be included.
+    #[display(fmt = "No transaction to include in the block")]
+    NoTransaction,
+    /// Blocks can only be authored on top of the current best block.
+    #[display(fmt = "Parent block must be the current best block")]
+    ParentNotBest,
+    /// Authoring blocks is only supported for Aura chains.
+    #[display(fmt = "Consensus algorithm of the chain isn't supported")]
+    UnsupportedConsensus,
+    /// None of the keys in the keystore is allowed to author blocks.
+    #[display(fmt = "No Aura authority key in the keystore")]
+    NoLocalAuthority,
+    /// Error while authoring or importing the block. See the logs for more information.
+    #[display(fmt = "Failed to author block")]
+    AuthoringFailed,
+    /// Block to finalize isn't a non-finalized block.
+    #[display(fmt = "Unknown non-finalized block")]
+    UnknownBlock,
 }
 
 /// Potential error when calling [`ConsensusService::new`].
@@ -178,6 +284,7 @@ impl ConsensusService {
         // Perform the initial access to the database to load a bunch of information.
         let (
             finalized_block_number,
+            finalized_block_scale_encoded_header,
             finalized_heap_pages,
             finalized_code,
             best_block_hash,
@@ -197,15 +304,14 @@ impl ConsensusService {
                     let finalized_block_hash = database
                         .finalized_block_hash()
                         .map_err(InitError::DatabaseCorruption)?;
-                    let finalized_block_number = header::decode(
-                        &database
-                            .block_scale_encoded_header(&finalized_block_hash)
-                            .map_err(InitError::DatabaseCorruption)?
-                            .unwrap(), // A panic here would indicate a bug in the database code.
-                        block_number_bytes,
-                    )
-                    .map_err(InitError::InvalidHeader)?
-                    .number;
+                    let finalized_block_scale_encoded_header = database
+                        .block_scale_encoded_header(&finalized_block_hash)
+                        .map_err(InitError::DatabaseCorruption)?
+                        .unwrap(); // A panic here would indicate a bug in the database code.
+                    let finalized_block_number =
+                        header::decode(&finalized_block_scale_encoded_header, block_number_bytes)
+                            .map_err(InitError::InvalidHeader)?
+                            .number;
                     let best_block_hash = database.best_block_hash().unwrap();
                     let best_block_number = header::decode(
                         &database
@@ -253,6 +359,7 @@ impl ConsensusService {
                     };
                     Ok((
                         finalized_block_number,
+                        finalized_block_scale_encoded_header,
                         finalized_heap_pages,
                         finalized_code,
                         best_block_hash,
@@ -284,6 +391,23 @@ impl ConsensusService {
             );
         }
 
+        // In dev seal mode, the finality of the chain is decided locally rather than by, for
+        // example, GrandPa.
+        let finalized_chain_information = if config.dev_seal.is_some() {
+            let mut chain_information =
+                chain_information::ChainInformation::from(finalized_chain_information);
+            chain_information.finality = chain_information::ChainInformationFinality::Outsourced;
+            chain_information::ValidChainInformation::try_from(chain_information).unwrap()
+        } else {
+            finalized_chain_information
+        };
+
+        // Warp syncing replaces the content of the database with the storage of the block that
+        // has been warp synced to. This is only done if the database doesn't contain anything
+        // valuable, in other words if its finalized block is the genesis block.
+        let warp_sync =
+            config.warp_sync && config.dev_seal.is_none() && finalized_block_number == 0;
+
         let mut sync = all::AllSync::new(all::Config {
             chain_information: finalized_chain_information,
             block_number_bytes: config.block_number_bytes,
@@ -304,6 +428,7 @@ impl ConsensusService {
                 NonZeroU32::new(2000).unwrap()
             },
             full_mode: true,
+            full_mode_warp_sync: warp_sync,
             code_trie_node_hint: None,
         });
 
@@ -313,18 +438,18 @@ impl ConsensusService {
             // saved in the database, hence the large number of unwraps here.
             let heap_pages = executor::storage_heap_pages_to_value(finalized_heap_pages.as_deref())
                 .map_err(InitError::FinalizedHeapPagesInvalid)?;
-            executor::host::HostVmPrototype::new(executor::host::Config {
-                module: finalized_code,
-                heap_pages,
-                exec_hint: executor::vm::ExecHint::CompileAheadOfTime, // TODO: probably should be decided by the optimisticsync
-                allow_unresolved_imports: false,
-            })
-            .map_err(InitError::FinalizedRuntimeInit)?
+            // TODO: the execution engine should probably be decided by the optimisticsync
+            config
+                .compiled_runtimes_cache
+                .build(&finalized_code, heap_pages, false)
+                .map_err(InitError::FinalizedRuntimeInit)?
         };
 
         let block_author_sync_source = sync.add_source(None, best_block_number, best_block_hash);
 
-        let (block_requests_finished_tx, block_requests_finished_rx) = mpsc::channel(0);
+        let (requests_finished_tx, requests_finished_rx) = mpsc::channel(0);
+        let (state_requests_finished_tx, state_requests_finished_rx) = mpsc::channel(0);
+        let (headers_verified_ahead_tx, headers_verified_ahead_rx) = mpsc::channel(0);
         let (to_background_tx, to_background_rx) = mpsc::channel(4);
 
         let background_sync = SyncBackground {
@@ -333,6 +458,9 @@ impl ConsensusService {
             block_authoring: None,
             authored_block: None,
             slot_duration_author_ratio: config.slot_duration_author_ratio,
+            parachain_inherent_builder: config
+                .relay_chain
+                .map(parachain_inherent::ParachainInherentBuilder::new),
             keystore: config.keystore,
             finalized_runtime: Arc::new(Mutex::new(Some(finalized_runtime))),
             network_service: config.network_service.0,
@@ -344,9 +472,29 @@ impl ConsensusService {
             peers_source_id_map: Default::default(),
             tasks_executor: config.tasks_executor,
             log_callback: config.log_callback,
-            block_requests_finished_tx,
-            block_requests_finished_rx,
+            clock: config.clock,
+            requests_finished_tx,
+            requests_finished_rx,
+            warp_sync: if warp_sync {
+                Some(WarpSyncState {
+                    database_finalized_block_header: finalized_block_scale_encoded_header,
+                    state_download: None,
+                })
+            } else {
+                None
+            },
+            state_requests_finished_tx,
+            state_requests_finished_rx,
+            headers_verify_ahead_in_progress: false,
+            headers_verified_ahead_tx,
+            headers_verified_ahead_rx,
             jaeger_service: config.jaeger_service,
+            metrics: config.metrics,
+            dev_seal: config.dev_seal,
+            dev_seal_requests: VecDeque::new(),
+            dev_seal_pending_block: None,
+            dev_seal_transactions: Vec::new(),
+            dev_seal_clock: Duration::new(0, 0),
         };
 
         background_sync.start();
@@ -454,6 +602,71 @@ impl ConsensusService {
             .await;
         result_rx.await.unwrap()
     }
+
+    /// Authors a new block on top of the current best block, and optionally finalizes it.
+    /// Returns the hash of the new block once it has been imported.
+    ///
+    /// Only available in dev seal mode. See [`Config::dev_seal`].
+    ///
+    /// If `create_empty` is `false`, an error is returned if no transaction is waiting to be
+    /// included. If `parent_hash` is `Some`, it must be equal to the hash of the current best
+    /// block.
+    pub async fn create_block(
+        &self,
+        create_empty: bool,
+        finalize: bool,
+        parent_hash: Option<[u8; 32]>,
+    ) -> Result<[u8; 32], DevSealError> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::CreateBlock {
+                create_empty,
+                finalize,
+                parent_hash,
+                result_tx,
+            })
+            .await;
+        result_rx.await.unwrap()
+    }
+
+    /// Sets the given non-finalized block as the new finalized block.
+    ///
+    /// Only available in dev seal mode. See [`Config::dev_seal`].
+    pub async fn finalize_block(&self, block_hash: [u8; 32]) -> Result<(), DevSealError> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::FinalizeBlock {
+                block_hash,
+                result_tx,
+            })
+            .await;
+        result_rx.await.unwrap()
+    }
+
+    /// Queues a SCALE-encoded transaction for inclusion in the next locally-authored block. The
+    /// transaction isn't validated.
+    ///
+    /// Only available in dev seal mode. See [`Config::dev_seal`]. If the mode is
+    /// [`DevSeal::Instant`], a block is authored and finalized immediately afterwards.
+    pub async fn submit_transaction(&self, transaction: Vec<u8>) -> Result<(), DevSealError> {
+        let (result_tx, result_rx) = oneshot::channel();
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::SubmitTransaction {
+                transaction,
+                result_tx,
+            })
+            .await;
+        result_rx.await.unwrap()
+    }
 }
 
 /// Return value of [`ConsensusService::subscribe_all`].
@@ -568,6 +781,17 @@ pub struct BlockNotification {
     pub parent_hash: [u8; 32],
 }
 
+/// Request to author a block in dev seal mode.
+struct DevSealRequest {
+    /// See [`ConsensusService::create_block`].
+    create_empty: bool,
+    /// See [`ConsensusService::create_block`].
+    finalize: bool,
+    /// Sender to report the outcome to. `None` if the request doesn't come from
+    /// [`ConsensusService::create_block`].
+    result_tx: Option<oneshot::Sender<Result<[u8; 32], DevSealError>>>,
+}
+
 struct SyncBackground {
     /// State machine containing the list of all the peers, all the non-finalized blocks, and all
     /// the network requests in progress.
@@ -585,7 +809,7 @@ struct SyncBackground {
     /// against a peer when it disconnects and that might already have a response.
     ///
     /// Each on-going request has a corresponding background task that sends its result to
-    /// [`SyncBackground::block_requests_finished_rx`].
+    /// [`SyncBackground::requests_finished_rx`].
     sync: all::AllSync<(), Option<NetworkSourceInfo>, NonFinalizedBlock>,
 
     /// Source within the [`SyncBackground::sync`] to use to import locally-authored blocks.
@@ -606,6 +830,10 @@ struct SyncBackground {
     /// See [`Config::slot_duration_author_ratio`].
     slot_duration_author_ratio: u16,
 
+    /// Builds the parachain-related inherents of authored blocks. `None` if the chain isn't a
+    /// parachain. See [`Config::relay_chain`].
+    parachain_inherent_builder: Option<parachain_inherent::ParachainInherentBuilder>,
+
     /// After a block has been authored, it is inserted here while waiting for the `sync` to
     /// import it. Contains the block height, the block hash, the SCALE-encoded block header, and
     /// the list of SCALE-encoded extrinsics of the block.
@@ -652,26 +880,189 @@ struct SyncBackground {
     /// See [`Config::log_callback`].
     log_callback: Arc<dyn LogCallback + Send + Sync>,
 
-    /// Block requests that have been emitted on the networking service and that are still in
+    /// See [`Config::clock`].
+    clock: Clock,
+
+    /// Requests that have been emitted on the networking service and that are still in
     /// progress. Each entry in this field also has an entry in [`SyncBackground::sync`].
-    block_requests_finished_rx: mpsc::Receiver<(
-        all::RequestId,
-        all::SourceId,
-        Result<Vec<BlockData>, network_service::BlocksRequestError>,
+    requests_finished_rx: mpsc::Receiver<(all::RequestId, all::SourceId, RequestOutcome)>,
+
+    /// Sending side of [`SyncBackground::requests_finished_rx`].
+    requests_finished_tx: mpsc::Sender<(all::RequestId, all::SourceId, RequestOutcome)>,
+
+    /// If `Some`, the chain is being warp synced and the database doesn't contain the storage
+    /// of the latest finalized block yet. Set back to `None` once the database has been reset
+    /// with the downloaded storage.
+    ///
+    /// While this field is `Some`, no block is authored and no block is verified.
+    warp_sync: Option<WarpSyncState>,
+
+    /// State requests that have been emitted on the networking service as part of
+    /// [`StateDownload`] and that are still in progress.
+    state_requests_finished_rx: mpsc::Receiver<(
+        libp2p::PeerId,
+        Result<network::service::EncodedStateResponse, network_service::StateRequestError>,
     )>,
 
-    /// Sending side of [`SyncBackground::block_requests_finished_rx`].
-    block_requests_finished_tx: mpsc::Sender<(
-        all::RequestId,
-        all::SourceId,
-        Result<Vec<BlockData>, network_service::BlocksRequestError>,
+    /// Sending side of [`SyncBackground::state_requests_finished_rx`].
+    state_requests_finished_tx: mpsc::Sender<(
+        libp2p::PeerId,
+        Result<network::service::EncodedStateResponse, network_service::StateRequestError>,
     )>,
 
+    /// `true` if a background task is verifying the headers of the blocks queued for
+    /// verification, ahead of the execution of these blocks. Its outcome is later received on
+    /// [`SyncBackground::headers_verified_ahead_rx`].
+    ///
+    /// Verifying a header doesn't require executing the block, and thus doesn't need to wait
+    /// for the blocks before it to have been executed. Only one such task runs at any given
+    /// time, as the verification of each header depends on the outcome of the verification of
+    /// its parent.
+    headers_verify_ahead_in_progress: bool,
+
+    /// See [`SyncBackground::headers_verify_ahead_in_progress`].
+    headers_verified_ahead_rx: mpsc::Receiver<all::HeadersVerifiedAhead>,
+
+    /// Sending side of [`SyncBackground::headers_verified_ahead_rx`].
+    headers_verified_ahead_tx: mpsc::Sender<all::HeadersVerifiedAhead>,
+
     /// See [`Config::database`].
     database: Arc<database_thread::DatabaseThread>,
 
     /// How to report events about blocks.
     jaeger_service: Arc<jaeger_service::JaegerService>,
+
+    /// See [`Config::metrics`].
+    metrics: Arc<prometheus_service::ChainMetrics>,
+
+    /// See [`Config::dev_seal`].
+    dev_seal: Option<DevSeal>,
+
+    /// In dev seal mode, list of blocks to author, in order.
+    dev_seal_requests: VecDeque<DevSealRequest>,
+
+    /// In dev seal mode, hash of the block that has been authored and is waiting to be imported,
+    /// and the request it originates from. No other block is authored while this field is `Some`.
+    dev_seal_pending_block: Option<([u8; 32], DevSealRequest)>,
+
+    /// In dev seal mode, SCALE-encoded transactions waiting to be included in the next authored
+    /// block.
+    dev_seal_transactions: Vec<Vec<u8>>,
+
+    /// In dev seal mode, blocks are authored without waiting for their slot, and can thus be
+    /// ahead of the actual time. This field contains the start of the slot of the latest authored
+    /// block, and is used instead of the actual time when it is higher.
+    dev_seal_clock: Duration,
+}
+
+/// Outcome of a request started against a source of [`SyncBackground::sync`].
+enum RequestOutcome {
+    Blocks(Result<Vec<BlockData>, network_service::BlocksRequestError>),
+    WarpSync(
+        Result<
+            network::service::EncodedGrandpaWarpSyncResponse,
+            network_service::WarpSyncRequestError,
+        >,
+    ),
+    StorageProof(
+        Result<network::service::EncodedMerkleProof, network_service::StorageProofRequestError>,
+    ),
+    CallProof(Result<network::service::EncodedMerkleProof, network_service::CallProofRequestError>),
+}
+
+/// See [`SyncBackground::warp_sync`].
+struct WarpSyncState {
+    /// SCALE-encoded header of the finalized block of the database. Reported as the finalized
+    /// block to the frontend until the database has been reset.
+    database_finalized_block_header: Vec<u8>,
+
+    /// Download of the storage of the block that has been warp synced to. `None` if the warp
+    /// syncing itself isn't finished yet.
+    state_download: Option<StateDownload>,
+}
+
+/// Download of the storage of the block that has been warp synced to.
+///
+/// The storage is downloaded ordered by key, and the trie nodes are written to the database as
+/// soon as they are complete. Only the ancestors of the latest downloaded entries are kept in
+/// memory.
+struct StateDownload {
+    /// Information about the finalized chain, to write in the database.
+    chain_information: chain_information::ChainInformation,
+
+    /// Hash of the block whose storage is downloaded.
+    block_hash: [u8; 32],
+
+    /// Number of the block whose storage is downloaded.
+    block_number: u64,
+
+    /// State trie root found in the header of the block whose storage is downloaded.
+    state_root: [u8; 32],
+
+    /// Version of the trie of the block whose storage is downloaded.
+    state_version: u8,
+
+    /// Runtime of the block whose storage is downloaded.
+    runtime: executor::host::HostVmPrototype,
+
+    /// Download of the trie nodes.
+    state: state_download::StateDownload,
+
+    /// Peer the storage is downloaded from. Set to `None` if a request fails, in which case
+    /// a different peer is picked.
+    peer_id: Option<libp2p::PeerId>,
+
+    /// List of all the peers that have sent entries since the download was started. If the
+    /// downloaded storage turns out to be invalid, all these peers are reported.
+    contributors: Vec<libp2p::PeerId>,
+
+    /// `true` if a state request is in progress. Its outcome is later received on
+    /// [`SyncBackground::state_requests_finished_rx`].
+    request_in_progress: bool,
+}
+
+impl StateDownload {
+    /// Restarts the download from scratch. The trie nodes already written to the database are
+    /// removed when it is reset.
+    fn restart(&mut self) {
+        self.state =
+            state_download::StateDownload::new(state_version_to_trie_version(self.state_version));
+        self.peer_id = None;
+        self.contributors.clear();
+    }
+}
+
+/// Converts the state version found in the runtime version into a [`trie::TrieEntryVersion`].
+fn state_version_to_trie_version(state_version: u8) -> trie::TrieEntryVersion {
+    if state_version == 0 {
+        trie::TrieEntryVersion::V0
+    } else {
+        trie::TrieEntryVersion::V1
+    }
+}
+
+/// Converts a node yielded by a [`state_download::StateDownload`] into a node to insert in the
+/// database.
+fn state_download_trie_node(
+    trie_node: state_download::TrieNode,
+) -> full_sqlite::InsertTrieNode<'static> {
+    let node = trie_node.node;
+    full_sqlite::InsertTrieNode {
+        merkle_value: Cow::Owned(node.merkle_value.as_ref().to_vec()),
+        children_merkle_values: array::from_fn(|n| {
+            node.children_merkle_values[n]
+                .as_ref()
+                .map(|child| Cow::Owned(child.as_ref().to_vec()))
+        }),
+        storage_value: match node.storage_value {
+            Some(value) => full_sqlite::InsertTrieNodeStorageValue::Value {
+                value: Cow::Owned(value),
+                references_merkle_value: trie_node.references_child_trie,
+            },
+            None => full_sqlite::InsertTrieNodeStorageValue::NoValue,
+        },
+        partial_key_nibbles: Cow::Owned(node.partial_key.into_iter().map(u8::from).collect()),
+    }
 }
 
 #[derive(Clone)]
@@ -717,17 +1108,23 @@ impl SyncBackground {
 
         loop {
             self.start_network_requests().await;
+            self.start_headers_verify_ahead();
+            self.update_metrics();
 
             enum WhatHappened {
                 ReadyToAuthor,
                 FrontendEvent(ToBackground),
                 FrontendClosed,
                 NetworkEvent(network_service::Event),
-                RequestFinished(
-                    all::RequestId,
-                    all::SourceId,
-                    Result<Vec<BlockData>, network_service::BlocksRequestError>,
+                RequestFinished(all::RequestId, all::SourceId, RequestOutcome),
+                StateRequestFinished(
+                    libp2p::PeerId,
+                    Result<
+                        network::service::EncodedStateResponse,
+                        network_service::StateRequestError,
+                    >,
                 ),
+                HeadersVerifiedAhead(all::HeadersVerifiedAhead),
                 SyncProcess,
             }
 
@@ -735,7 +1132,19 @@ impl SyncBackground {
                 // Creating the block authoring state and prepare a future that is ready when something
                 // related to the block authoring is ready.
                 // TODO: refactor as a separate task?
-                let authoring_ready_future = {
+                let authoring_ready_future = if self.dev_seal.is_some() {
+                    // In dev seal mode, blocks are authored on demand rather than following the
+                    // slots, and one at a time.
+                    if self.dev_seal_pending_block.is_none() && !self.dev_seal_requests.is_empty() {
+                        future::Either::Left(future::Either::Left(future::ready(())))
+                    } else {
+                        future::Either::Left(future::Either::Right(future::pending()))
+                    }
+                } else if self.warp_sync.is_some() {
+                    // No block is authored while warp syncing, as the storage of the finalized
+                    // block isn't available.
+                    future::Either::Left(future::Either::Right(future::pending()))
+                } else {
                     // TODO: overhead to call best_block_consensus() multiple times
                     let local_authorities = {
                         let namespace_filter = match self.sync.best_block_consensus() {
@@ -745,6 +1154,12 @@ impl SyncBackground {
                             chain_information::ChainInformationConsensusRef::Babe { .. } => {
                                 Some(keystore::KeyNamespace::Babe)
                             }
+                            chain_information::ChainInformationConsensusRef::Sassafras {
+                                ..
+                            } => {
+                                // Authoring Sassafras blocks isn't supported.
+                                None
+                            }
                             chain_information::ChainInformationConsensusRef::Unknown => {
                                 // In `Unknown` mode, all keys are accepted and there is no
                                 // filter on the namespace, as we can't author blocks anyway.
@@ -774,21 +1189,17 @@ impl SyncBackground {
                                     finalized_authorities_list, // TODO: field name not appropriate; should probably change the chain_information module
                                     slot_duration,
                                 },
-                            ) => Some(
-                                block_authoring.insert((
-                                    author::build::Builder::new(author::build::Config {
-                                        consensus: author::build::ConfigConsensus::Aura {
-                                            current_authorities: finalized_authorities_list,
-                                            local_authorities: local_authorities.iter(),
-                                            now_from_unix_epoch: SystemTime::now()
-                                                .duration_since(SystemTime::UNIX_EPOCH)
-                                                .unwrap(),
-                                            slot_duration,
-                                        },
-                                    }),
-                                    local_authorities,
-                                )),
-                            ),
+                            ) => Some(block_authoring.insert((
+                                author::build::Builder::new(author::build::Config {
+                                    consensus: author::build::ConfigConsensus::Aura {
+                                        current_authorities: finalized_authorities_list,
+                                        local_authorities: local_authorities.iter(),
+                                        now_from_unix_epoch: self.clock.now_from_unix_epoch(),
+                                        slot_duration,
+                                    },
+                                }),
+                                local_authorities,
+                            ))),
                             (
                                 None,
                                 chain_information::ChainInformationConsensusRef::Babe { .. },
@@ -799,16 +1210,13 @@ impl SyncBackground {
                         };
 
                     match &block_authoring {
-                        Some((author::build::Builder::Ready(_), _)) => future::Either::Left(
-                            future::Either::Left(future::ready(Instant::now())),
-                        ),
+                        Some((author::build::Builder::Ready(_), _)) => {
+                            future::Either::Left(future::Either::Left(future::ready(())))
+                        }
                         Some((author::build::Builder::WaitSlot(when), _)) => {
-                            let delay = (UNIX_EPOCH + when.when())
-                                .duration_since(SystemTime::now())
-                                .unwrap_or_else(|_| Duration::new(0, 0));
-                            future::Either::Right(future::FutureExt::fuse(smol::Timer::after(
-                                delay,
-                            )))
+                            let delay =
+                                when.when().saturating_sub(self.clock.now_from_unix_epoch());
+                            future::Either::Right(future::FutureExt::fuse(self.clock.sleep(delay)))
                         }
                         None => future::Either::Left(future::Either::Right(future::pending())),
                         Some((author::build::Builder::Idle, _)) => {
@@ -817,9 +1225,7 @@ impl SyncBackground {
                             // This prevents the authoring from trying over and over again to generate
                             // a bad block.
                             let delay = Duration::from_secs(2);
-                            future::Either::Right(future::FutureExt::fuse(smol::Timer::after(
-                                delay,
-                            )))
+                            future::Either::Right(future::FutureExt::fuse(self.clock.sleep(delay)))
                         }
                     }
                 };
@@ -839,11 +1245,28 @@ impl SyncBackground {
                 })
                 .or(async {
                     let (request_id, source_id, result) =
-                        self.block_requests_finished_rx.select_next_some().await;
+                        self.requests_finished_rx.select_next_some().await;
                     WhatHappened::RequestFinished(request_id, source_id, result)
                 })
                 .or(async {
-                    if !process_sync {
+                    let (peer_id, result) =
+                        self.state_requests_finished_rx.select_next_some().await;
+                    WhatHappened::StateRequestFinished(peer_id, result)
+                })
+                .or(async {
+                    WhatHappened::HeadersVerifiedAhead(
+                        self.headers_verified_ahead_rx.select_next_some().await,
+                    )
+                })
+                .or(async {
+                    // Blocks can't be verified while the storage of the finalized block is
+                    // being downloaded.
+                    if !process_sync
+                        || self
+                            .warp_sync
+                            .as_ref()
+                            .map_or(false, |w| w.state_download.is_some())
+                    {
                         future::pending().await
                     }
                     WhatHappened::SyncProcess
@@ -852,6 +1275,10 @@ impl SyncBackground {
             };
 
             match what_happened {
+                WhatHappened::ReadyToAuthor if self.dev_seal.is_some() => {
+                    self.author_block_dev_seal().await;
+                    process_sync = true;
+                }
                 WhatHappened::ReadyToAuthor => {
                     // Ready to author a block. Call `author_block()`.
                     // While a block is being authored, the whole syncing state machine is
@@ -890,6 +1317,29 @@ impl SyncBackground {
                 }) => {
                     let (tx, new_blocks) = async_channel::bounded(buffer_size.saturating_sub(1));
 
+                    // While warp syncing, the blocks of the sync state machine can't be reported,
+                    // as their storage isn't available. The finalized block of the database is
+                    // reported instead. The subscription is later closed when the database gets
+                    // reset.
+                    if let Some(warp_sync) = &self.warp_sync {
+                        self.blocks_notifications.push(tx);
+                        let _ = result_tx.send(SubscribeAll {
+                            id: SubscriptionId(0), // TODO:
+                            finalized_block_hash: header::hash_from_scale_encoded_header(
+                                &warp_sync.database_finalized_block_header,
+                            ),
+                            finalized_block_scale_encoded_header: warp_sync
+                                .database_finalized_block_header
+                                .clone(),
+                            finalized_block_runtime: Arc::new(
+                                self.finalized_runtime.lock().await.clone().unwrap(),
+                            ),
+                            non_finalized_blocks_ancestry_order: Vec::new(),
+                            new_blocks,
+                        });
+                        continue;
+                    }
+
                     // TODO: this code below is a bit hacky due to the API of AllSync not being super convenient
                     let finalized_block_scale_encoded_header = self
                         .sync
@@ -949,6 +1399,25 @@ impl SyncBackground {
                         new_blocks,
                     });
                 }
+                WhatHappened::FrontendEvent(ToBackground::GetSyncState { result_tx })
+                    if self.warp_sync.is_some() =>
+                {
+                    let header = &self
+                        .warp_sync
+                        .as_ref()
+                        .unwrap()
+                        .database_finalized_block_header;
+                    let block_hash = header::hash_from_scale_encoded_header(header);
+                    let block_number = header::decode(header, self.sync.block_number_bytes())
+                        .unwrap()
+                        .number;
+                    let _ = result_tx.send(SyncState {
+                        best_block_hash: block_hash,
+                        best_block_number: block_number,
+                        finalized_block_hash: block_hash,
+                        finalized_block_number: block_number,
+                    });
+                }
                 WhatHappened::FrontendEvent(ToBackground::GetSyncState { result_tx }) => {
                     let _ = result_tx.send(SyncState {
                         best_block_hash: self.sync.best_block_hash(),
@@ -965,13 +1434,55 @@ impl SyncBackground {
                     let _ = result_tx.send(());
                 }
                 WhatHappened::FrontendEvent(ToBackground::IsMajorSyncingHint { result_tx }) => {
-                    // As documented, the value returned doesn't need to be precise.
-                    let result = match self.sync.status() {
-                        all::Status::Sync => false,
-                        all::Status::WarpSyncFragments { .. }
-                        | all::Status::WarpSyncChainInformation { .. } => true,
+                    let _ = result_tx.send(self.is_major_syncing_hint());
+                }
+                WhatHappened::FrontendEvent(ToBackground::CreateBlock {
+                    create_empty,
+                    finalize,
+                    parent_hash,
+                    result_tx,
+                }) => {
+                    if self.dev_seal.is_none() {
+                        let _ = result_tx.send(Err(DevSealError::Disabled));
+                    } else if parent_hash.is_some_and(|h| h != self.sync.best_block_hash()) {
+                        let _ = result_tx.send(Err(DevSealError::ParentNotBest));
+                    } else {
+                        self.dev_seal_requests.push_back(DevSealRequest {
+                            create_empty,
+                            finalize,
+                            result_tx: Some(result_tx),
+                        });
+                    }
+                }
+                WhatHappened::FrontendEvent(ToBackground::FinalizeBlock {
+                    block_hash,
+                    result_tx,
+                }) => {
+                    let result = if self.dev_seal.is_none() {
+                        Err(DevSealError::Disabled)
+                    } else {
+                        self.dev_seal_finalize(&block_hash).await
+                    };
+                    let _ = result_tx.send(result);
+                }
+                WhatHappened::FrontendEvent(ToBackground::SubmitTransaction {
+                    transaction,
+                    result_tx,
+                }) => {
+                    let result = match self.dev_seal {
+                        None => Err(DevSealError::Disabled),
+                        Some(mode) => {
+                            self.dev_seal_transactions.push(transaction);
+                            if mode == DevSeal::Instant {
+                                self.dev_seal_requests.push_back(DevSealRequest {
+                                    create_empty: false,
+                                    finalize: true,
+                                    result_tx: None,
+                                });
+                            }
+                            Ok(())
+                        }
                     };
-
                     let _ = result_tx.send(result);
                 }
 
@@ -980,7 +1491,7 @@ impl SyncBackground {
                     chain_id,
                     best_block_number,
                     best_block_hash,
-                }) if chain_id == self.network_chain_id => {
+                }) if chain_id == self.network_chain_id && self.dev_seal.is_none() => {
                     // Most of the time, we insert a new source in the state machine.
                     // However, a source of that `PeerId` might already exist but be considered as
                     // disconnected. If that is the case, we simply mark it as no
@@ -1009,7 +1520,7 @@ impl SyncBackground {
                 WhatHappened::NetworkEvent(network_service::Event::Disconnected {
                     peer_id,
                     chain_id,
-                }) if chain_id == self.network_chain_id => {
+                }) if chain_id == self.network_chain_id && self.dev_seal.is_none() => {
                     // Sources that disconnect are only immediately removed from the sync state
                     // machine if they have no request in progress. If that is not the case, they
                     // are instead only marked as disconnected.
@@ -1029,7 +1540,7 @@ impl SyncBackground {
                     peer_id,
                     scale_encoded_header,
                     is_best,
-                }) if chain_id == self.network_chain_id => {
+                }) if chain_id == self.network_chain_id && self.dev_seal.is_none() => {
                     let _jaeger_span = self.jaeger_service.block_announce_process_span(
                         &header::hash_from_scale_encoded_header(&scale_encoded_header),
                     );
@@ -1046,32 +1557,75 @@ impl SyncBackground {
                         all::BlockAnnounceOutcome::InvalidHeader(_) => unreachable!(),
                     }
                 }
+                WhatHappened::NetworkEvent(network_service::Event::GrandpaNeighborPacket {
+                    chain_id,
+                    peer_id,
+                    finalized_block_height,
+                }) if chain_id == self.network_chain_id && self.dev_seal.is_none() => {
+                    let id = *self.peers_source_id_map.get(&peer_id).unwrap();
+                    self.sync
+                        .update_source_finality_state(id, finalized_block_height);
+                }
                 WhatHappened::NetworkEvent(_) => {
-                    // Different chain index.
+                    // Different chain index, or networking peers are ignored because of the dev
+                    // seal mode.
                 }
 
                 WhatHappened::RequestFinished(request_id, source_id, result) => {
-                    // TODO: clarify this piece of code
-                    let result = result.map_err(|_| ());
-                    let (_, response_outcome) = self.sync.blocks_request_response(
-                        request_id,
-                        result.map(|v| {
-                            v.into_iter().map(|block| all::BlockRequestSuccessBlock {
-                                scale_encoded_header: block.header.unwrap(), // TODO: don't unwrap
-                                scale_encoded_extrinsics: block.body.unwrap(), // TODO: don't unwrap
-                                scale_encoded_justifications: block
-                                    .justifications
-                                    .unwrap_or_default()
-                                    .into_iter()
-                                    .map(|j| all::Justification {
-                                        engine_id: j.engine_id,
-                                        justification: j.justification,
+                    let (_, response_outcome) = match result {
+                        RequestOutcome::Blocks(result) => {
+                            // TODO: clarify this piece of code
+                            let result = result.map_err(|_| ());
+                            self.sync.blocks_request_response(
+                                request_id,
+                                result.map(|v| {
+                                    v.into_iter().map(|block| all::BlockRequestSuccessBlock {
+                                        scale_encoded_header: block.header.unwrap(), // TODO: don't unwrap
+                                        scale_encoded_extrinsics: block.body.unwrap(), // TODO: don't unwrap
+                                        scale_encoded_justifications: block
+                                            .justifications
+                                            .unwrap_or_default()
+                                            .into_iter()
+                                            .map(|j| all::Justification {
+                                                engine_id: j.engine_id,
+                                                justification: j.justification,
+                                            })
+                                            .collect(),
+                                        user_data: NonFinalizedBlock::NotVerified,
                                     })
-                                    .collect(),
-                                user_data: NonFinalizedBlock::NotVerified,
-                            })
-                        }),
-                    );
+                                }),
+                            )
+                        }
+                        RequestOutcome::WarpSync(Ok(response)) => {
+                            let decoded = response.decode();
+                            let fragments = decoded
+                                .fragments
+                                .into_iter()
+                                .map(|f| all::WarpSyncFragment {
+                                    scale_encoded_header: f.scale_encoded_header.to_vec(),
+                                    scale_encoded_justification: f
+                                        .scale_encoded_justification
+                                        .to_vec(),
+                                })
+                                .collect();
+                            self.sync.grandpa_warp_sync_response_ok(
+                                request_id,
+                                fragments,
+                                decoded.is_finished,
+                            )
+                        }
+                        RequestOutcome::WarpSync(Err(_)) => {
+                            self.sync.grandpa_warp_sync_response_err(request_id)
+                        }
+                        RequestOutcome::StorageProof(result) => self.sync.storage_get_response(
+                            request_id,
+                            result.map(|proof| proof.decode().to_vec()).map_err(|_| ()),
+                        ),
+                        RequestOutcome::CallProof(result) => self.sync.call_proof_response(
+                            request_id,
+                            result.map(|proof| proof.decode().to_vec()).map_err(|_| ()),
+                        ),
+                    };
 
                     match response_outcome {
                         all::ResponseOutcome::Outdated
@@ -1097,6 +1651,22 @@ impl SyncBackground {
                     process_sync = true;
                 }
 
+                WhatHappened::StateRequestFinished(peer_id, result) => {
+                    self.state_request_finished(peer_id, result).await;
+                    // The download of the storage might have finished, in which case blocks
+                    // can now be verified.
+                    process_sync = true;
+                }
+
+                WhatHappened::HeadersVerifiedAhead(verified) => {
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        format!("headers-verified-ahead; num_headers={}", verified.len()),
+                    );
+                    self.headers_verify_ahead_in_progress = false;
+                    self.sync.inject_headers_verified_ahead(verified);
+                }
+
                 WhatHappened::SyncProcess => {
                     let (new_self, maybe_more_to_process) = self.process_blocks().await;
                     process_sync = maybe_more_to_process;
@@ -1152,9 +1722,8 @@ impl SyncBackground {
             let start = authoring_start.slot_start_from_unix_epoch();
             let end = authoring_start.slot_end_from_unix_epoch();
             debug_assert!(start < end);
-            debug_assert!(SystemTime::now() >= SystemTime::UNIX_EPOCH + start);
-            SystemTime::UNIX_EPOCH
-                + start
+            debug_assert!(self.dev_seal.is_some() || self.clock.now_from_unix_epoch() >= start);
+            start
                 + (end - start) * u32::from(self.slot_duration_author_ratio)
                     / u32::from(u16::max_value())
         };
@@ -1176,19 +1745,56 @@ impl SyncBackground {
                 };
             let parent_runtime = parent_runtime_arc.try_lock().unwrap().take().unwrap();
 
+            // In dev seal mode, the block is authored without waiting for its slot to start. The
+            // timestamp of the block must nonetheless be within its slot.
+            let now_from_unix_epoch = if self.dev_seal.is_some() {
+                authoring_start.slot_start_from_unix_epoch()
+            } else {
+                self.clock.now_from_unix_epoch()
+            };
+
+            // Parachain blocks must include information about the state of the relay chain.
+            let (parachain_inherent_data, relay_parent) = match &mut self.parachain_inherent_builder
+            {
+                Some(builder) => match builder.build().await {
+                    Ok((data, relay_parent)) => (Some(data), Some(relay_parent)),
+                    Err(error) => {
+                        // Put back the parent runtime that we extracted.
+                        *parent_runtime_arc.try_lock().unwrap() = Some(parent_runtime);
+
+                        // See the error handling of the block authoring below.
+                        self.block_authoring = Some((author::build::Builder::Idle, Vec::new()));
+                        self.log_callback.log(
+                            LogLevel::Warn,
+                            format!("block-author-parachain-inherent-error; error={}", error),
+                        );
+                        return;
+                    }
+                },
+                None => (None, None),
+            };
+
+            // When authoring a parachain block, the keys of the storage of the parent that are
+            // accessed must be recorded in order to later build the proof sent to the
+            // validators.
+            // TODO: child tries aren't recorded
+            let mut accessed_main_trie_keys = BTreeSet::<Vec<u8>>::new();
+
+            // Transactions to include in the block. Only ever non-empty in dev seal mode.
+            let mut transactions = mem::take(&mut self.dev_seal_transactions).into_iter();
+
             // Start the block authoring process.
             let mut block_authoring = {
                 authoring_start.start(author::build::AuthoringStartConfig {
                     block_number_bytes: self.sync.block_number_bytes(),
                     parent_hash: &self.sync.best_block_hash(),
                     parent_number: self.sync.best_block_number(),
-                    now_from_unix_epoch: SystemTime::now()
-                        .duration_since(SystemTime::UNIX_EPOCH)
-                        .unwrap(),
+                    now_from_unix_epoch,
                     parent_runtime,
                     block_body_capacity: 0, // TODO: could be set to the size of the tx pool
                     max_log_level: 0,
                     calculate_trie_changes: true,
+                    parachain_inherent_data,
                 })
             };
 
@@ -1226,8 +1832,62 @@ impl SyncBackground {
                             }
                         };
 
+                        // When authoring a parachain block, the collation is built and sent to
+                        // the validators of the relay chain.
+                        let parent_runtime = if let (Some(builder), Some(relay_parent)) =
+                            (&self.parachain_inherent_builder, &relay_parent)
+                        {
+                            let (parent_runtime, collation) = collator::build_collation(
+                                collator::Config {
+                                    database: &self.database,
+                                    keystore: &self.keystore,
+                                    collator_key: &builder.config().collator_key,
+                                    local_peer_id: self.network_service.local_peer_id(),
+                                    para_id: builder.config().para_id,
+                                    relay_parent,
+                                    parent_hash,
+                                    scale_encoded_header: &success.scale_encoded_header,
+                                    body: &success.body,
+                                    accessed_main_trie_keys: &accessed_main_trie_keys,
+                                },
+                                success.parent_runtime,
+                                success.storage_changes,
+                            )
+                            .await;
+
+                            match collation {
+                                Ok(collation) => {
+                                    self.log_callback.log(
+                                        LogLevel::Debug,
+                                        format!(
+                                            "collation-generated; relay_parent={}; pov_hash={}; pov_size={}",
+                                            HashDisplay(&collation.relay_parent),
+                                            HashDisplay(&collation.pov_hash),
+                                            collation.scale_encoded_pov.len()
+                                        ),
+                                    );
+                                    self.network_service
+                                        .announce_collation(
+                                            builder.config().network_chain_id,
+                                            collation,
+                                        )
+                                        .await;
+                                }
+                                Err(error) => {
+                                    self.log_callback.log(
+                                        LogLevel::Warn,
+                                        format!("collation-generation-error; error={}", error),
+                                    );
+                                }
+                            }
+
+                            parent_runtime
+                        } else {
+                            success.parent_runtime
+                        };
+
                         // Put back the parent runtime that we extracted.
-                        *parent_runtime_arc.try_lock().unwrap() = Some(success.parent_runtime);
+                        *parent_runtime_arc.try_lock().unwrap() = Some(parent_runtime);
 
                         break (success.scale_encoded_header, success.body, success.logs);
                     }
@@ -1256,8 +1916,11 @@ impl SyncBackground {
                     // Part of the block production consists in adding transactions to the block.
                     // These transactions are extracted from the transactions pool.
                     author::build::BuilderAuthoring::ApplyExtrinsic(apply) => {
-                        // TODO: actually implement including transactions in the blocks
-                        block_authoring = apply.finish();
+                        // TODO: actually implement including transactions from a pool in the blocks
+                        block_authoring = match transactions.next() {
+                            Some(transaction) => apply.add_extrinsic(transaction),
+                            None => apply.finish(),
+                        };
                     }
                     author::build::BuilderAuthoring::ApplyExtrinsicResult { result, resume } => {
                         if let Err(error) = result {
@@ -1271,8 +1934,11 @@ impl SyncBackground {
                             );
                         }
 
-                        // TODO: actually implement including transactions in the blocks
-                        block_authoring = resume.finish();
+                        // TODO: actually implement including transactions from a pool in the blocks
+                        block_authoring = match transactions.next() {
+                            Some(transaction) => resume.add_extrinsic(transaction),
+                            None => resume.finish(),
+                        };
                     }
 
                     // Access to the best block storage.
@@ -1286,6 +1952,12 @@ impl SyncBackground {
                         let key = trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
                             .map(u8::from)
                             .collect::<Vec<_>>();
+                        if relay_parent.is_some() {
+                            accessed_main_trie_keys.insert(match &parent_paths {
+                                Some(path) => path.clone(),
+                                None => key.clone(),
+                            });
+                        }
                         let value = self
                             .database
                             .with_database(move |db| {
@@ -1313,6 +1985,12 @@ impl SyncBackground {
                                 .collect::<Vec<_>>()
                         });
                         let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
+                        if relay_parent.is_some() {
+                            accessed_main_trie_keys.insert(match &parent_paths {
+                                Some(path) => path.clone(),
+                                None => key_nibbles.clone(),
+                            });
+                        }
 
                         let merkle_value = self
                             .database
@@ -1342,6 +2020,8 @@ impl SyncBackground {
                             .chain(if req.or_equal() { None } else { Some(0u8) })
                             .collect::<Vec<_>>();
                         let prefix_nibbles = req.prefix().map(u8::from).collect::<Vec<_>>();
+                        let parent_paths_recorded = parent_paths.clone();
+                        let requested_key_nibbles = key_nibbles.clone();
 
                         let branch_nodes = req.branch_nodes();
                         let next_key = self
@@ -1358,6 +2038,20 @@ impl SyncBackground {
                             .await
                             .expect("database access error");
 
+                        if relay_parent.is_some() {
+                            match &parent_paths_recorded {
+                                Some(path) => {
+                                    accessed_main_trie_keys.insert(path.clone());
+                                }
+                                None => {
+                                    accessed_main_trie_keys.insert(requested_key_nibbles);
+                                    if let Some(next_key) = &next_key {
+                                        accessed_main_trie_keys.insert(next_key.clone());
+                                    }
+                                }
+                            }
+                        }
+
                         block_authoring = req
                             .inject_key(next_key.map(|k| {
                                 k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())
@@ -1391,8 +2085,10 @@ impl SyncBackground {
         // or if the runtime code being executed contains a very heavy operation.
         // In any case, there is not much that a node operator can do except try increase the
         // performance of their machine.
-        match authoring_end.elapsed() {
-            Ok(now_minus_end) if now_minus_end < Duration::from_millis(500) => {}
+        match self.clock.now_from_unix_epoch().checked_sub(authoring_end) {
+            Some(now_minus_end) if now_minus_end < Duration::from_millis(500) => {}
+            // In dev seal mode, blocks are authored independently of their slot.
+            _ if self.dev_seal.is_some() => {}
             _ => {
                 self.log_callback.log(
                     LogLevel::Warn,
@@ -1434,23 +2130,180 @@ impl SyncBackground {
         ));
     }
 
-    /// Starts all the new network requests that should be started.
-    // TODO: handle obsolete requests
-    async fn start_network_requests(&mut self) {
-        loop {
-            // `desired_requests()` returns, in decreasing order of priority, the requests
-            // that should be started in order for the syncing to proceed. We simply pick the
-            // first request, but enforce one ongoing request per source.
-            let (source_id, _, mut request_info) = match self.sync.desired_requests().find(
-                |(source_id, source_info, request_details)| {
-                    if source_info
-                        .as_ref()
-                        .map_or(false, |info| info.is_disconnected)
-                    {
-                        // Source is a networking source that has already been disconnected.
-                        false
-                    } else if *source_id != self.block_author_sync_source {
-                        // Remote source.
+    /// Authors a block in dev seal mode, following the first element of
+    /// [`SyncBackground::dev_seal_requests`].
+    ///
+    /// The block is authored on top of the current best block, using the first slot after the
+    /// one of the best block that can be claimed by a local authority. The block is then
+    /// imported like in [`SyncBackground::author_block`], and the request is answered once the
+    /// import is finished.
+    ///
+    /// # Panic
+    ///
+    /// [`SyncBackground::dev_seal_requests`] must not be empty.
+    ///
+    async fn author_block_dev_seal(&mut self) {
+        let request = self.dev_seal_requests.pop_front().unwrap();
+
+        if !request.create_empty && self.dev_seal_transactions.is_empty() {
+            if let Some(result_tx) = request.result_tx {
+                let _ = result_tx.send(Err(DevSealError::NoTransaction));
+            }
+            return;
+        }
+
+        // Calling `keys()` on the keystore is racy, but that's considered acceptable and part of
+        // the design of the node.
+        let local_authorities = self
+            .keystore
+            .keys()
+            .await
+            .filter(|(namespace, _)| *namespace == keystore::KeyNamespace::Aura)
+            .map(|(_, key)| key)
+            .collect::<Vec<_>>();
+
+        let parent_slot = self
+            .sync
+            .best_block_header()
+            .digest
+            .aura_pre_runtime()
+            .map_or(0, |pre_digest| pre_digest.slot_number);
+
+        let builder = match self.sync.best_block_consensus() {
+            chain_information::ChainInformationConsensusRef::Aura {
+                finalized_authorities_list,
+                slot_duration,
+            } => {
+                // The block must be in a slot strictly after the one of its parent, even if
+                // that slot is in the future.
+                let now_from_unix_epoch = cmp::max(
+                    self.clock.now_from_unix_epoch(),
+                    Duration::from_millis(
+                        parent_slot
+                            .saturating_add(1)
+                            .saturating_mul(slot_duration.get()),
+                    ),
+                );
+
+                author::build::Builder::new(author::build::Config {
+                    consensus: author::build::ConfigConsensus::Aura {
+                        current_authorities: finalized_authorities_list,
+                        local_authorities: local_authorities.iter(),
+                        now_from_unix_epoch,
+                        slot_duration,
+                    },
+                })
+            }
+            _ => {
+                if let Some(result_tx) = request.result_tx {
+                    let _ = result_tx.send(Err(DevSealError::UnsupportedConsensus));
+                }
+                return;
+            }
+        };
+
+        let authoring_start = match builder {
+            author::build::Builder::Ready(authoring_start) => authoring_start,
+            author::build::Builder::WaitSlot(when) => when.start(),
+            author::build::Builder::Idle => {
+                if let Some(result_tx) = request.result_tx {
+                    let _ = result_tx.send(Err(DevSealError::NoLocalAuthority));
+                }
+                return;
+            }
+        };
+
+        // The block is potentially ahead of the actual time. Pretend that the time is at least
+        // the start of its slot, otherwise its verification would fail.
+        self.dev_seal_clock = cmp::max(
+            self.dev_seal_clock,
+            authoring_start.slot_start_from_unix_epoch(),
+        );
+
+        self.block_authoring = Some((
+            author::build::Builder::Ready(authoring_start),
+            local_authorities,
+        ));
+        self.author_block().await;
+
+        match &self.authored_block {
+            Some((_, block_hash, _, _)) => {
+                self.dev_seal_pending_block = Some((*block_hash, request));
+            }
+            None => {
+                if let Some(result_tx) = request.result_tx {
+                    let _ = result_tx.send(Err(DevSealError::AuthoringFailed));
+                }
+            }
+        }
+    }
+
+    /// Must be called after the block with the given hash has been verified. If this block is
+    /// the one in [`SyncBackground::dev_seal_pending_block`], finalizes it if requested and
+    /// reports the outcome.
+    async fn dev_seal_block_verified(&mut self, block_hash: &[u8; 32], success: bool) {
+        if !matches!(&self.dev_seal_pending_block, Some((hash, ..)) if hash == block_hash) {
+            return;
+        }
+
+        let (_, request) = self.dev_seal_pending_block.take().unwrap();
+        let result = if !success {
+            Err(DevSealError::AuthoringFailed)
+        } else if request.finalize {
+            self.dev_seal_finalize(block_hash)
+                .await
+                .map(|()| *block_hash)
+        } else {
+            Ok(*block_hash)
+        };
+
+        if let Some(result_tx) = request.result_tx {
+            let _ = result_tx.send(result);
+        }
+    }
+
+    /// Sets the given non-finalized block as the new finalized block, in dev seal mode.
+    async fn dev_seal_finalize(&mut self, block_hash: &[u8; 32]) -> Result<(), DevSealError> {
+        match self.sync.set_finalized_block(block_hash) {
+            Ok(all::FinalityProofVerifyOutcome::NewFinalized {
+                finalized_blocks_newest_to_oldest,
+                pruned_blocks,
+                updates_best_block,
+            }) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!("dev-seal-finalized; hash={}", HashDisplay(block_hash)),
+                );
+                self.on_blocks_finalized(
+                    finalized_blocks_newest_to_oldest,
+                    pruned_blocks,
+                    updates_best_block,
+                )
+                .await;
+                Ok(())
+            }
+            Ok(_) => unreachable!(),
+            Err(_) => Err(DevSealError::UnknownBlock),
+        }
+    }
+
+    /// Starts all the new network requests that should be started.
+    // TODO: handle obsolete requests
+    async fn start_network_requests(&mut self) {
+        loop {
+            // `desired_requests()` returns, in decreasing order of priority, the requests
+            // that should be started in order for the syncing to proceed. We simply pick the
+            // first request, but enforce one ongoing request per source.
+            let (source_id, _, mut request_info) = match self.sync.desired_requests().find(
+                |(source_id, source_info, request_details)| {
+                    if source_info
+                        .as_ref()
+                        .map_or(false, |info| info.is_disconnected)
+                    {
+                        // Source is a networking source that has already been disconnected.
+                        false
+                    } else if *source_id != self.block_author_sync_source {
+                        // Remote source.
                         self.sync.source_num_ongoing_requests(*source_id) == 0
                     } else {
                         // Locally-authored blocks source.
@@ -1564,24 +2417,395 @@ impl SyncBackground {
                     let request_id = self.sync.add_request(source_id, request_info.into(), ());
 
                     (self.tasks_executor)(Box::pin({
-                        let mut block_requests_finished_tx =
-                            self.block_requests_finished_tx.clone();
+                        let mut requests_finished_tx = self.requests_finished_tx.clone();
                         async move {
-                            let result = request.await;
-                            let _ = block_requests_finished_tx
+                            let result = RequestOutcome::Blocks(request.await);
+                            let _ = requests_finished_tx
                                 .send((request_id, source_id, result))
                                 .await;
                         }
                     }));
                 }
-                all::DesiredRequest::WarpSync { .. }
-                | all::DesiredRequest::StorageGetMerkleProof { .. }
-                | all::DesiredRequest::RuntimeCallMerkleProof { .. } => {
-                    // Not used in "full" mode.
-                    unreachable!()
+
+                // The requests below are only desired while warp syncing.
+                all::DesiredRequest::WarpSync {
+                    sync_start_block_hash,
+                } => {
+                    let peer_id = self.sync[source_id].clone().unwrap().peer_id;
+
+                    let request = self.network_service.clone().grandpa_warp_sync_request(
+                        peer_id,
+                        self.network_chain_id,
+                        sync_start_block_hash,
+                    );
+
+                    let request_id = self.sync.add_request(source_id, request_info.into(), ());
+
+                    (self.tasks_executor)(Box::pin({
+                        let mut requests_finished_tx = self.requests_finished_tx.clone();
+                        async move {
+                            let result = RequestOutcome::WarpSync(request.await);
+                            let _ = requests_finished_tx
+                                .send((request_id, source_id, result))
+                                .await;
+                        }
+                    }));
+                }
+                all::DesiredRequest::StorageGetMerkleProof {
+                    block_hash,
+                    ref keys,
+                    ..
+                } => {
+                    let peer_id = self.sync[source_id].clone().unwrap().peer_id;
+
+                    let request = self.network_service.clone().storage_proof_request(
+                        peer_id,
+                        self.network_chain_id,
+                        network::codec::StorageProofRequestConfig {
+                            block_hash,
+                            keys: keys.clone().into_iter(),
+                        },
+                    );
+
+                    let request_id = self.sync.add_request(source_id, request_info.into(), ());
+
+                    (self.tasks_executor)(Box::pin({
+                        let mut requests_finished_tx = self.requests_finished_tx.clone();
+                        async move {
+                            let result = RequestOutcome::StorageProof(request.await);
+                            let _ = requests_finished_tx
+                                .send((request_id, source_id, result))
+                                .await;
+                        }
+                    }));
                 }
+                all::DesiredRequest::RuntimeCallMerkleProof {
+                    block_hash,
+                    ref function_name,
+                    ref parameter_vectored,
+                } => {
+                    let peer_id = self.sync[source_id].clone().unwrap().peer_id;
+
+                    let request = self.network_service.clone().call_proof_request(
+                        peer_id,
+                        self.network_chain_id,
+                        network::codec::CallProofRequestConfig {
+                            block_hash,
+                            method: function_name.clone(),
+                            parameter_vectored: iter::once(parameter_vectored.clone()),
+                        },
+                    );
+
+                    let request_id = self.sync.add_request(source_id, request_info.into(), ());
+
+                    (self.tasks_executor)(Box::pin({
+                        let mut requests_finished_tx = self.requests_finished_tx.clone();
+                        async move {
+                            let result = RequestOutcome::CallProof(request.await);
+                            let _ = requests_finished_tx
+                                .send((request_id, source_id, result))
+                                .await;
+                        }
+                    }));
+                }
+            }
+        }
+
+        // After the warp syncing, the storage of the finalized block is downloaded from a random
+        // peer, one state request at a time. The same peer is used for the entire download unless
+        // a request fails.
+        if let Some(download) = self
+            .warp_sync
+            .as_mut()
+            .and_then(|warp_sync| warp_sync.state_download.as_mut())
+            .filter(|download| !download.request_in_progress)
+        {
+            let is_connected = |peer_id: &libp2p::PeerId| {
+                self.peers_source_id_map
+                    .get(peer_id)
+                    .map_or(false, |source_id| {
+                        !self.sync[*source_id]
+                            .as_ref()
+                            .map_or(true, |info| info.is_disconnected)
+                    })
+            };
+
+            let peer_id = download
+                .peer_id
+                .clone()
+                .filter(|peer_id| is_connected(peer_id))
+                .or_else(|| {
+                    self.peers_source_id_map
+                        .keys()
+                        .filter(|peer_id| is_connected(peer_id))
+                        .cloned()
+                        .choose(&mut rand::thread_rng())
+                });
+
+            if let Some(peer_id) = peer_id {
+                let (child_trie, start_key) = match download.state.request_start() {
+                    network::codec::StateRequestStart::ChildTrieDefault { child_trie, key } => {
+                        (Some(child_trie.to_vec()), key.to_vec())
+                    }
+                    network::codec::StateRequestStart::MainTrie(key) => (None, key.to_vec()),
+                };
+
+                let network_service = self.network_service.clone();
+                let network_chain_id = self.network_chain_id;
+                let block_hash = download.block_hash;
+                download.peer_id = Some(peer_id.clone());
+                download.request_in_progress = true;
+
+                (self.tasks_executor)(Box::pin({
+                    let mut state_requests_finished_tx = self.state_requests_finished_tx.clone();
+                    async move {
+                        let result = network_service
+                            .state_request(
+                                peer_id.clone(),
+                                network_chain_id,
+                                block_hash,
+                                match &child_trie {
+                                    Some(child_trie) => {
+                                        network::codec::StateRequestStart::ChildTrieDefault {
+                                            child_trie,
+                                            key: &start_key,
+                                        }
+                                    }
+                                    None => network::codec::StateRequestStart::MainTrie(&start_key),
+                                },
+                            )
+                            .await;
+                        let _ = state_requests_finished_tx.send((peer_id, result)).await;
+                    }
+                }));
+            }
+        }
+    }
+
+    /// Returns `true` if the syncing is currently downloading blocks at a high rate in order to
+    /// catch up with the head of the chain.
+    ///
+    /// See [`ConsensusService::is_major_syncing_hint`].
+    fn is_major_syncing_hint(&self) -> bool {
+        // As documented, the value returned doesn't need to be precise.
+        match self.sync.status() {
+            _ if self.warp_sync.is_some() => true,
+            all::Status::Sync => false,
+            all::Status::WarpSyncFragments { .. }
+            | all::Status::WarpSyncChainInformation { .. } => true,
+        }
+    }
+
+    /// Updates the values in [`SyncBackground::metrics`] that reflect the current state.
+    fn update_metrics(&self) {
+        // While warp syncing, the finalized block of the database is reported as both the best
+        // and finalized block, similar to what `ConsensusService::sync_state` does.
+        if let Some(warp_sync) = &self.warp_sync {
+            let block_number = header::decode(
+                &warp_sync.database_finalized_block_header,
+                self.sync.block_number_bytes(),
+            )
+            .unwrap()
+            .number;
+            self.metrics.set_best_block_height(block_number);
+            self.metrics.set_finalized_block_height(block_number);
+        } else {
+            self.metrics
+                .set_best_block_height(self.sync.best_block_number());
+            self.metrics
+                .set_finalized_block_height(self.sync.finalized_block_header().number);
+        }
+
+        self.metrics
+            .set_is_major_syncing(self.is_major_syncing_hint());
+        self.metrics
+            .set_database_cache_statistics(self.database.cache_statistics());
+    }
+
+    /// Starts verifying in the background the headers of the blocks that are queued for
+    /// verification, if this isn't already in progress.
+    ///
+    /// See [`SyncBackground::headers_verify_ahead_in_progress`].
+    fn start_headers_verify_ahead(&mut self) {
+        if self.headers_verify_ahead_in_progress {
+            return;
+        }
+
+        let Some(verify) = self.sync.headers_verify_ahead(HEADERS_VERIFY_AHEAD_MAX) else {
+            return;
+        };
+
+        self.headers_verify_ahead_in_progress = true;
+
+        // In dev seal mode, locally-authored blocks can be ahead of the actual time.
+        let dev_seal_clock = self.dev_seal_clock;
+        let clock = self.clock.clone();
+
+        (self.tasks_executor)(Box::pin({
+            let mut headers_verified_ahead_tx = self.headers_verified_ahead_tx.clone();
+            async move {
+                let unix_time = cmp::max(clock.now_from_unix_epoch(), dev_seal_clock);
+                let verified = verify.perform(unix_time);
+                let _ = headers_verified_ahead_tx.send(verified).await;
+            }
+        }));
+    }
+
+    /// Updates the [`StateDownload`] with the outcome of a state request. Once the entire storage
+    /// has been downloaded, resets the database with it and leaves the warp syncing.
+    ///
+    /// # Panic
+    ///
+    /// Panics if no [`StateDownload`] is in progress.
+    ///
+    async fn state_request_finished(
+        &mut self,
+        peer_id: libp2p::PeerId,
+        result: Result<network::service::EncodedStateResponse, network_service::StateRequestError>,
+    ) {
+        let download = self
+            .warp_sync
+            .as_mut()
+            .and_then(|warp_sync| warp_sync.state_download.as_mut())
+            .unwrap();
+        download.request_in_progress = false;
+
+        // Failed requests are simply started again against a different peer.
+        let Ok(response) = result else {
+            download.peer_id = None;
+            return;
+        };
+
+        if !download.contributors.contains(&peer_id) {
+            download.contributors.push(peer_id.clone());
+        }
+
+        let (trie_nodes, outcome) = match download.state.process_response(response.decode()) {
+            Ok(outcome) => outcome,
+            Err(error) => {
+                self.log_callback.log(
+                    LogLevel::Warn,
+                    format!(
+                        "warp-sync-state-invalid-response; peer_id={}; hash={}; error={}",
+                        peer_id,
+                        HashDisplay(&download.block_hash),
+                        error
+                    ),
+                );
+                // Nodes that have been generated while processing the response are lost,
+                // so the download must be restarted.
+                download.restart();
+                self.network_service
+                    .report_peer(
+                        self.network_chain_id,
+                        peer_id,
+                        network::basic_peering_strategy::ReputationChange::BadResponse,
+                    )
+                    .await;
+                return;
+            }
+        };
+
+        // Write the nodes that are complete to the database, even if the download isn't
+        // finished. They are removed from the database if the download fails.
+        let state_version = download.state_version;
+        self.database
+            .with_database(move |database| {
+                database.insert_trie_nodes(
+                    trie_nodes.into_iter().map(state_download_trie_node),
+                    state_version,
+                )
+            })
+            .await
+            .expect("database access error");
+
+        let download = self
+            .warp_sync
+            .as_mut()
+            .and_then(|warp_sync| warp_sync.state_download.as_mut())
+            .unwrap();
+        let state_download::Progress::Finished { state_root } = outcome else {
+            return;
+        };
+
+        if state_root != download.state_root {
+            self.log_callback.log(
+                LogLevel::Warn,
+                format!(
+                    "warp-sync-state-mismatch; hash={}; number={}; num_entries={}; peers={}",
+                    HashDisplay(&download.block_hash),
+                    download.block_number,
+                    download.state.num_entries(),
+                    download
+                        .contributors
+                        .iter()
+                        .map(|peer_id| peer_id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(",")
+                ),
+            );
+
+            // It is unknown which peer has sent invalid entries, so all the peers that have
+            // contributed to the download are reported. The download then starts again from
+            // scratch.
+            let contributors = mem::take(&mut download.contributors);
+            download.restart();
+            for peer_id in contributors {
+                self.network_service
+                    .report_peer(
+                        self.network_chain_id,
+                        peer_id,
+                        network::basic_peering_strategy::ReputationChange::BadResponse,
+                    )
+                    .await;
             }
+            return;
         }
+
+        let StateDownload {
+            chain_information,
+            block_hash,
+            block_number,
+            state_version,
+            runtime,
+            state,
+            ..
+        } = self.warp_sync.take().unwrap().state_download.unwrap();
+
+        // All the trie nodes have already been written, and the ones that don't belong to the
+        // storage of the new finalized block are removed when resetting.
+        self.database
+            .with_database(move |database| {
+                database.reset(
+                    &chain_information,
+                    iter::empty(),
+                    None,
+                    iter::empty(),
+                    state_version,
+                )
+            })
+            .await
+            .expect("database access error");
+
+        self.log_callback.log(
+            LogLevel::Info,
+            format!(
+                "warp-sync-state-downloaded; hash={}; number={}; num_entries={}",
+                HashDisplay(&block_hash),
+                block_number,
+                state.num_entries()
+            ),
+        );
+
+        self.finalized_runtime = Arc::new(Mutex::new(Some(runtime)));
+
+        // The subscriptions have been reporting the finalized block of the database, which is
+        // now gone. Closing them forces the frontend to subscribe again.
+        self.blocks_notifications.clear();
+
+        self.network_service
+            .set_local_best_block(self.network_chain_id, block_hash, block_number)
+            .await;
     }
 
     async fn process_blocks(mut self) -> (Self, bool) {
@@ -1590,9 +2814,8 @@ impl SyncBackground {
         // verifying storage proof.
         // If the state is one of the "verifying" states, perform the actual verification and
         // loop again until the sync is in an idle state.
-        let unix_time = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap();
+        // In dev seal mode, locally-authored blocks can be ahead of the actual time.
+        let unix_time = cmp::max(self.clock.now_from_unix_epoch(), self.dev_seal_clock);
 
         // TODO: move this?
         let block_number_bytes = self.sync.block_number_bytes();
@@ -1602,10 +2825,128 @@ impl SyncBackground {
                 self.sync = idle;
                 (self, false)
             }
-            all::ProcessOne::VerifyWarpSyncFragment(_)
-            | all::ProcessOne::WarpSyncBuildRuntime(_)
-            | all::ProcessOne::WarpSyncBuildChainInformation(_)
-            | all::ProcessOne::WarpSyncFinished { .. } => unreachable!(),
+            all::ProcessOne::VerifyWarpSyncFragment(verify) => {
+                let sender_peer_id = verify
+                    .proof_sender()
+                    .and_then(|(_, info)| info.as_ref())
+                    .map(|info| Cow::Owned(info.peer_id.to_string()))
+                    .unwrap_or(Cow::Borrowed("<disconnected>"));
+
+                let (sync, result) = verify.perform(rand::random());
+                self.sync = sync;
+
+                match result {
+                    Ok((fragment_hash, fragment_number)) => {
+                        self.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "warp-sync-fragment-verified; sender={}; hash={}; number={}",
+                                sender_peer_id,
+                                HashDisplay(&fragment_hash),
+                                fragment_number
+                            ),
+                        );
+                    }
+                    Err(err) => {
+                        // A justification failing to verify might be caused by a forced
+                        // GrandPa authorities change, which warp syncing can't cross.
+                        let maybe_forced_change =
+                            matches!(err, all::VerifyFragmentError::JustificationVerify(_));
+                        self.log_callback.log(
+                            LogLevel::Warn,
+                            format!(
+                                "failed-warp-sync-fragment-verification; sender={}; \
+                                maybe_forced_change={:?}; error={}",
+                                sender_peer_id, maybe_forced_change, err
+                            ),
+                        );
+                    }
+                }
+
+                (self, true)
+            }
+            all::ProcessOne::WarpSyncBuildRuntime(req) => {
+                let (sync, result) = req.build(all::ExecHint::CompileAheadOfTime, true);
+                self.sync = sync;
+
+                if let Err(err) = result {
+                    if !matches!(err, all::WarpSyncBuildRuntimeError::SourceMisbehavior(_)) {
+                        self.log_callback.log(
+                            LogLevel::Warn,
+                            format!("failed-warp-sync-runtime-build; error={}", err),
+                        );
+                    }
+                }
+
+                (self, true)
+            }
+            all::ProcessOne::WarpSyncBuildChainInformation(req) => {
+                let (sync, result) = req.build();
+                self.sync = sync;
+
+                if let Err(err) = result {
+                    if !matches!(
+                        err,
+                        all::WarpSyncBuildChainInformationError::SourceMisbehavior(_)
+                    ) {
+                        self.log_callback.log(
+                            LogLevel::Warn,
+                            format!("failed-warp-sync-chain-information-build; error={}", err),
+                        );
+                    }
+                }
+
+                (self, true)
+            }
+            all::ProcessOne::WarpSyncFinished {
+                sync,
+                finalized_block_runtime,
+                ..
+            } => {
+                self.sync = sync;
+
+                let finalized_header = self.sync.finalized_block_header();
+                let block_hash = finalized_header.hash(block_number_bytes);
+                let block_number = finalized_header.number;
+                let state_root = *finalized_header.state_root;
+
+                self.log_callback.log(
+                    LogLevel::Info,
+                    format!(
+                        "warp-sync-finished; hash={}; number={}",
+                        HashDisplay(&block_hash),
+                        block_number
+                    ),
+                );
+
+                // The storage of the finalized block is now downloaded, and blocks are only
+                // verified after that.
+                let state_version = finalized_block_runtime
+                    .runtime_version()
+                    .decode()
+                    .state_version
+                    .map(u8::from)
+                    .unwrap_or(0);
+                self.warp_sync.as_mut().unwrap().state_download = Some(StateDownload {
+                    chain_information: chain_information::ValidChainInformation::from(
+                        self.sync.as_chain_information(),
+                    )
+                    .into(),
+                    block_hash,
+                    block_number,
+                    state_root,
+                    state_version,
+                    runtime: finalized_block_runtime,
+                    state: state_download::StateDownload::new(state_version_to_trie_version(
+                        state_version,
+                    )),
+                    peer_id: None,
+                    contributors: Vec::new(),
+                    request_in_progress: false,
+                });
+
+                (self, true)
+            }
             all::ProcessOne::VerifyBlock(verify) => {
                 let when_verification_started = Instant::now();
                 let mut database_accesses_duration = Duration::new(0, 0);
@@ -1633,6 +2974,7 @@ impl SyncBackground {
                                 ),
                             );
                             self.sync = sync;
+                            self.dev_seal_block_verified(&hash_to_verify, false).await;
                             return (self, true);
                         }
                     };
@@ -1694,6 +3036,7 @@ impl SyncBackground {
                             );
                             *parent_runtime_arc.try_lock().unwrap() = Some(parent_runtime);
                             self.sync = header_verification_success.reject_bad_block();
+                            self.dev_seal_block_verified(&hash_to_verify, false).await;
                             return (self, true);
                         }
                         body_only::Verify::Finished(Ok(body_only::Success {
@@ -1711,12 +3054,17 @@ impl SyncBackground {
                                 .with_database_detached({
                                     let storage_changes = storage_changes.clone();
                                     let scale_encoded_header = header_verification_success.scale_encoded_header().to_vec();
+                                    let body = header_verification_success
+                                        .scale_encoded_extrinsics()
+                                        .unwrap()
+                                        .map(|extrinsic| extrinsic.as_ref().to_vec())
+                                        .collect::<Vec<_>>();
                                     move |database| {
                                         // TODO: overhead for building the SCALE encoding of the header
                                         let result = database.insert(
                                             &scale_encoded_header,
                                             is_new_best,
-                                            iter::empty::<Vec<u8>>(), // TODO:,no /!\
+                                            body.into_iter(),
                                             storage_changes.trie_changes_iter_ordered().unwrap().filter_map(
                                                 |(_child_trie, key, change)| {
                                                     let body_only::TrieChange::InsertUpdate {
@@ -1771,7 +3119,8 @@ impl SyncBackground {
                                         }
                                     }
                                 }).await;
-                            database_accesses_duration += when_database_access_started.elapsed();
+                            let import_duration = when_database_access_started.elapsed();
+                            database_accesses_duration += import_duration;
 
                             let height = header_verification_success.height();
                             let scale_encoded_header =
@@ -1791,6 +3140,10 @@ impl SyncBackground {
                                     is_new_best
                                 ),
                             );
+                            self.metrics.observe_block_verification(
+                                when_verification_started.elapsed(),
+                                import_duration,
+                            );
 
                             // Notify the subscribers.
                             // Elements in `blocks_notifications` are removed one by one and
@@ -1903,6 +3256,8 @@ impl SyncBackground {
                                 }
                             }
 
+                            self.dev_seal_block_verified(&hash_to_verify, true).await;
+
                             return (self, true);
                         }
 
@@ -2025,112 +3380,130 @@ impl SyncBackground {
                 }
             }
 
-            all::ProcessOne::VerifyFinalityProof(verify) => {
-                match verify.perform(rand::random()) {
-                    (
-                        sync_out,
-                        all::FinalityProofVerifyOutcome::NewFinalized {
-                            finalized_blocks_newest_to_oldest,
-                            pruned_blocks,
-                            updates_best_block,
-                        },
-                    ) => {
-                        self.sync = sync_out;
-
-                        let new_finalized_hash = finalized_blocks_newest_to_oldest
-                            .first()
-                            .unwrap()
-                            .header
-                            .hash(self.sync.block_number_bytes());
-                        self.log_callback.log(
-                            LogLevel::Debug,
-                            format!(
-                                "finality-proof-verification; outcome=success; new-finalized={}",
-                                HashDisplay(&new_finalized_hash)
-                            ),
-                        );
+            all::ProcessOne::VerifyFinalityProof(verify) => match verify.perform(rand::random()) {
+                (
+                    sync_out,
+                    all::FinalityProofVerifyOutcome::NewFinalized {
+                        finalized_blocks_newest_to_oldest,
+                        pruned_blocks,
+                        updates_best_block,
+                    },
+                ) => {
+                    self.sync = sync_out;
+
+                    let new_finalized_hash = finalized_blocks_newest_to_oldest
+                        .first()
+                        .unwrap()
+                        .header
+                        .hash(self.sync.block_number_bytes());
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "finality-proof-verification; outcome=success; new-finalized={}",
+                            HashDisplay(&new_finalized_hash)
+                        ),
+                    );
 
-                        if updates_best_block {
-                            let fut = self.network_service.set_local_best_block(
-                                self.network_chain_id,
-                                self.sync.best_block_hash(),
-                                self.sync.best_block_number(),
-                            );
-                            fut.await;
+                    self.on_blocks_finalized(
+                        finalized_blocks_newest_to_oldest,
+                        pruned_blocks,
+                        updates_best_block,
+                    )
+                    .await;
+                    (self, true)
+                }
+                (sync_out, all::FinalityProofVerifyOutcome::GrandpaCommitPending) => {
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        "finality-proof-verification; outcome=pending".to_string(),
+                    );
+                    self.sync = sync_out;
+                    (self, true)
+                }
+                (sync_out, all::FinalityProofVerifyOutcome::AlreadyFinalized) => {
+                    self.log_callback.log(
+                        LogLevel::Debug,
+                        "finality-proof-verification; outcome=already-finalized".to_string(),
+                    );
+                    self.sync = sync_out;
+                    (self, true)
+                }
+                (sync_out, all::FinalityProofVerifyOutcome::GrandpaCommitError(error)) => {
+                    self.log_callback.log(
+                        LogLevel::Warn,
+                        format!("finality-proof-verification-failure; error={}", error),
+                    );
+                    self.sync = sync_out;
+                    (self, true)
+                }
+                (sync_out, all::FinalityProofVerifyOutcome::JustificationError(error)) => {
+                    self.log_callback.log(
+                        LogLevel::Warn,
+                        format!("finality-proof-verification-failure; error={}", error),
+                    );
+                    self.sync = sync_out;
+                    (self, true)
+                }
+            },
+        }
+    }
 
-                            // Reset the block authoring, in order to potentially build a
-                            // block on top of this new best.
-                            self.block_authoring = None;
-                        }
+    /// Updates the state of the service after blocks have been finalized in [`SyncBackground::sync`].
+    async fn on_blocks_finalized(
+        &mut self,
+        finalized_blocks_newest_to_oldest: Vec<all::Block<NonFinalizedBlock>>,
+        pruned_blocks: Vec<[u8; 32]>,
+        updates_best_block: bool,
+    ) {
+        let new_finalized_hash = finalized_blocks_newest_to_oldest
+            .first()
+            .unwrap()
+            .header
+            .hash(self.sync.block_number_bytes());
+
+        if updates_best_block {
+            let fut = self.network_service.set_local_best_block(
+                self.network_chain_id,
+                self.sync.best_block_hash(),
+                self.sync.best_block_number(),
+            );
+            fut.await;
 
-                        self.finalized_runtime =
-                            match &finalized_blocks_newest_to_oldest.first().unwrap().user_data {
-                                NonFinalizedBlock::Verified { runtime } => runtime.clone(),
-                                _ => unreachable!(),
-                            };
-                        // TODO: what if best block changed?
-                        self.database
-                            .with_database_detached(move |database| {
-                                database.set_finalized(&new_finalized_hash).unwrap();
-                            })
-                            .await;
-                        // Elements in `blocks_notifications` are removed one by one and inserted
-                        // back if the channel is still open.
-                        for index in (0..self.blocks_notifications.len()).rev() {
-                            let subscription = self.blocks_notifications.swap_remove(index);
-                            if subscription
-                                .try_send(Notification::Finalized {
-                                    finalized_blocks_newest_to_oldest:
-                                        finalized_blocks_newest_to_oldest
-                                            .iter()
-                                            .map(|b| b.header.hash(self.sync.block_number_bytes()))
-                                            .collect::<Vec<_>>(),
-                                    pruned_blocks_hashes: pruned_blocks.clone(),
-                                    best_block_hash: self.sync.best_block_hash(),
-                                })
-                                .is_err()
-                            {
-                                continue;
-                            }
+            // Reset the block authoring, in order to potentially build a
+            // block on top of this new best.
+            self.block_authoring = None;
+        }
 
-                            self.blocks_notifications.push(subscription);
-                        }
-                        (self, true)
-                    }
-                    (sync_out, all::FinalityProofVerifyOutcome::GrandpaCommitPending) => {
-                        self.log_callback.log(
-                            LogLevel::Debug,
-                            "finality-proof-verification; outcome=pending".to_string(),
-                        );
-                        self.sync = sync_out;
-                        (self, true)
-                    }
-                    (sync_out, all::FinalityProofVerifyOutcome::AlreadyFinalized) => {
-                        self.log_callback.log(
-                            LogLevel::Debug,
-                            "finality-proof-verification; outcome=already-finalized".to_string(),
-                        );
-                        self.sync = sync_out;
-                        (self, true)
-                    }
-                    (sync_out, all::FinalityProofVerifyOutcome::GrandpaCommitError(error)) => {
-                        self.log_callback.log(
-                            LogLevel::Warn,
-                            format!("finality-proof-verification-failure; error={}", error),
-                        );
-                        self.sync = sync_out;
-                        (self, true)
-                    }
-                    (sync_out, all::FinalityProofVerifyOutcome::JustificationError(error)) => {
-                        self.log_callback.log(
-                            LogLevel::Warn,
-                            format!("finality-proof-verification-failure; error={}", error),
-                        );
-                        self.sync = sync_out;
-                        (self, true)
-                    }
-                }
+        self.finalized_runtime = match &finalized_blocks_newest_to_oldest.first().unwrap().user_data
+        {
+            NonFinalizedBlock::Verified { runtime } => runtime.clone(),
+            _ => unreachable!(),
+        };
+        // TODO: what if best block changed?
+        self.database
+            .with_database_detached(move |database| {
+                database.set_finalized(&new_finalized_hash).unwrap();
+            })
+            .await;
+        // Elements in `blocks_notifications` are removed one by one and inserted
+        // back if the channel is still open.
+        for index in (0..self.blocks_notifications.len()).rev() {
+            let subscription = self.blocks_notifications.swap_remove(index);
+            if subscription
+                .try_send(Notification::Finalized {
+                    finalized_blocks_newest_to_oldest: finalized_blocks_newest_to_oldest
+                        .iter()
+                        .map(|b| b.header.hash(self.sync.block_number_bytes()))
+                        .collect::<Vec<_>>(),
+                    pruned_blocks_hashes: pruned_blocks.clone(),
+                    best_block_hash: self.sync.best_block_hash(),
+                })
+                .is_err()
+            {
+                continue;
             }
+
+            self.blocks_notifications.push(subscription);
         }
     }
 }
@@ -0,0 +1,215 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Turning a freshly-authored parachain block into a collation that can be sent to the
+//! validators of the relay chain.
+//!
+//! See the [`smoldot::author::pov`] module for more information.
+
+use super::parachain_inherent::RelayParentInfo;
+use crate::{database_thread, network_service};
+
+use smoldot::{
+    author::pov,
+    executor::{self, runtime_host},
+    header,
+    identity::keystore,
+    libp2p::PeerId,
+    network::codec,
+    trie::{self, proof_encode},
+};
+use std::{collections::BTreeSet, iter};
+
+/// Configuration for [`build_collation`].
+pub(super) struct Config<'a> {
+    /// Database of the parachain.
+    pub database: &'a database_thread::DatabaseThread,
+    /// Keystore containing [`Config::collator_key`].
+    pub keystore: &'a keystore::Keystore,
+    /// Public key of the collator, in the [`keystore::KeyNamespace::Collator`] namespace.
+    pub collator_key: &'a [u8; 32],
+    /// Identity of the local node in the peer-to-peer network.
+    pub local_peer_id: &'a PeerId,
+    /// Identifier of the parachain.
+    pub para_id: u32,
+    /// Relay chain block the parachain block has been built upon.
+    pub relay_parent: &'a RelayParentInfo,
+    /// Hash of the parent of the parachain block.
+    pub parent_hash: [u8; 32],
+    /// SCALE-encoded header of the parachain block.
+    pub scale_encoded_header: &'a [u8],
+    /// Body of the parachain block.
+    pub body: &'a [Vec<u8>],
+    /// Keys of the main trie of the parent block that have been accessed while authoring the
+    /// block, as nibbles.
+    pub accessed_main_trie_keys: &'a BTreeSet<Vec<u8>>,
+}
+
+/// Error potentially returned by [`build_collation`].
+#[derive(Debug, derive_more::Display)]
+pub(super) enum BuildError {
+    /// Error accessing the parachain database.
+    #[display(fmt = "{_0}")]
+    Database(database_thread::StorageAccessError),
+    /// Error while calling the `CollectCollationInfo_collect_collation_info` runtime function.
+    CollectCollationInfoCall,
+    /// Error while decoding the output of `CollectCollationInfo_collect_collation_info`.
+    #[display(fmt = "{_0}")]
+    CollectCollationInfoDecode(pov::DecodeCollationInfoError),
+    /// Error while signing using the collator key.
+    #[display(fmt = "{_0}")]
+    Signing(keystore::SignError),
+}
+
+/// Builds the collation of a parachain block that has just been authored.
+///
+/// `parent_runtime` must be the runtime of the parent of the parachain block, and
+/// `main_trie_changes` the changes to the main trie performed by the parachain block.
+///
+/// Returns the runtime that was passed as parameter.
+pub(super) async fn build_collation(
+    config: Config<'_>,
+    parent_runtime: executor::host::HostVmPrototype,
+    main_trie_changes: runtime_host::StorageChanges,
+) -> (
+    executor::host::HostVmPrototype,
+    Result<network_service::Collation, BuildError>,
+) {
+    // The candidate commitments are obtained by calling a runtime function on top of the new
+    // block, in other words on top of the storage of its parent plus its changes.
+    // TODO: if the block upgrades the runtime, the new runtime should be used
+    let collation_info = database_thread::run_runtime_call(
+        config.database,
+        config.parent_hash,
+        runtime_host::Config {
+            virtual_machine: parent_runtime,
+            function_to_call: pov::COLLECT_COLLATION_INFO_FUNCTION_NAME,
+            parameter: pov::collect_collation_info_parameters(config.scale_encoded_header),
+            max_log_level: 0,
+            storage_main_trie_changes: main_trie_changes.into_main_trie_diff(),
+            calculate_trie_changes: false,
+            trace: false,
+        },
+    )
+    .await;
+    let (runtime, collation_info) = match collation_info {
+        Ok(Ok(success)) => {
+            let output = success.virtual_machine.value().as_ref().to_vec();
+            (success.virtual_machine.into_prototype(), output)
+        }
+        Ok(Err(error)) => return (error.prototype, Err(BuildError::CollectCollationInfoCall)),
+        // `run_runtime_call` only fails if the runtime couldn't be started, in which case it
+        // has been destroyed. This can't happen as the same runtime has just successfully
+        // authored a block.
+        Err(()) => unreachable!(),
+    };
+
+    let result = build_collation_inner(&config, &collation_info).await;
+    (runtime, result)
+}
+
+async fn build_collation_inner(
+    config: &Config<'_>,
+    collation_info: &[u8],
+) -> Result<network_service::Collation, BuildError> {
+    let collation_info = pov::decode_collect_collation_info_return_value(collation_info)
+        .map_err(BuildError::CollectCollationInfoDecode)?;
+
+    // Build the proof of all the storage items of the parent that have been accessed.
+    // TODO: the nodes of the child tries aren't included in the proof; blocks that access child tries will be rejected by the validators
+    let storage_proof = {
+        let parent_hash = config.parent_hash;
+        let keys = config.accessed_main_trie_keys.clone();
+        config
+            .database
+            .with_database(
+                move |database| -> Result<_, database_thread::StorageAccessError> {
+                    let mut proof_builder = proof_encode::ProofBuilder::new();
+                    for key in keys {
+                        for node in database.block_storage_proof_nodes(
+                            &parent_hash,
+                            iter::empty::<iter::Empty<_>>(),
+                            key.into_iter(),
+                        )? {
+                            let key_nibbles = node
+                                .key_nibbles
+                                .iter()
+                                .map(|n| trie::Nibble::try_from(*n).unwrap())
+                                .collect::<Vec<_>>();
+                            proof_builder.set_node_value(
+                                &key_nibbles,
+                                &node.node_value,
+                                node.unhashed_storage_value.as_deref(),
+                            );
+                        }
+                    }
+                    proof_builder.make_coherent();
+                    Ok(proof_builder.build_compact_to_vec())
+                },
+            )
+            .await
+            .map_err(BuildError::Database)?
+    };
+
+    let scale_encoded_pov = pov::build_pov(pov::PovConfig {
+        scale_encoded_header: config.scale_encoded_header,
+        block_body: config.body.iter(),
+        storage_proof: &storage_proof,
+    });
+    let pov_hash = pov::pov_hash(&scale_encoded_pov);
+
+    let signature = config
+        .keystore
+        .sign(
+            keystore::KeyNamespace::Collator,
+            config.collator_key,
+            &codec::collator_signature_payload(
+                &config.relay_parent.hash,
+                config.para_id,
+                &config.relay_parent.persisted_validation_data_hash,
+                &pov_hash,
+                &config.relay_parent.validation_code_hash,
+            ),
+        )
+        .await
+        .map_err(BuildError::Signing)?;
+    let declare_signature = config
+        .keystore
+        .sign(
+            keystore::KeyNamespace::Collator,
+            config.collator_key,
+            &codec::declare_signature_payload(config.local_peer_id),
+        )
+        .await
+        .map_err(BuildError::Signing)?;
+
+    Ok(network_service::Collation {
+        para_id: config.para_id,
+        collator_id: *config.collator_key,
+        declare_signature,
+        relay_parent: config.relay_parent.hash,
+        persisted_validation_data_hash: config.relay_parent.persisted_validation_data_hash,
+        pov_hash,
+        // TODO: the erasure root requires erasure-coding the available data of the candidate, which isn't implemented; validators will reject the candidate until this is done
+        erasure_root: [0; 32],
+        signature,
+        para_head: header::hash_from_scale_encoded_header(collation_info.head_data),
+        validation_code_hash: config.relay_parent.validation_code_hash,
+        commitments_hash: collation_info.commitments_hash(),
+        scale_encoded_pov,
+    })
+}
@@ -0,0 +1,338 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Assembling the parachain inherent data of a parachain block from the state of the relay
+//! chain.
+//!
+//! The relay parent of the parachain block is the current best block of the relay chain found
+//! in the relay chain database. See the [`smoldot::sync::para`] module for more information.
+
+use super::RelayChainConfig;
+use crate::database_thread;
+
+use smoldot::{executor, header, sync::para, trie, verify::inherents};
+use std::{collections::BTreeMap, iter};
+
+/// Builds [`inherents::ParachainInherentData`] using the relay chain database.
+pub(super) struct ParachainInherentBuilder {
+    config: RelayChainConfig,
+
+    /// Runtime of the relay chain, alongside with the code it has been built from. Kept between
+    /// calls in order to avoid compiling the runtime every time.
+    runtime: Option<(Vec<u8>, executor::host::HostVmPrototype)>,
+}
+
+/// Information about the relay parent of a parachain block, returned by
+/// [`ParachainInherentBuilder::build`] and necessary in order to build a collation.
+#[derive(Debug, Clone)]
+pub(super) struct RelayParentInfo {
+    /// Hash of the relay chain block.
+    pub hash: [u8; 32],
+    /// Blake2 hash of the SCALE-encoded persisted validation data of the parachain.
+    pub persisted_validation_data_hash: [u8; 32],
+    /// Hash of the validation code of the parachain.
+    pub validation_code_hash: [u8; 32],
+}
+
+/// Error potentially returned by [`ParachainInherentBuilder::build`].
+#[derive(Debug, derive_more::Display)]
+pub(super) enum BuildError {
+    /// Error accessing the relay chain database.
+    #[display(fmt = "{_0}")]
+    Database(database_thread::StorageAccessError),
+    /// Header of the relay parent in the database is invalid.
+    #[display(fmt = "{_0}")]
+    InvalidHeader(header::Error),
+    /// `:code` key is missing from the storage of the relay parent.
+    NoCode,
+    /// Error parsing the `:heappages` of the relay parent.
+    #[display(fmt = "{_0}")]
+    InvalidHeapPages(executor::InvalidHeapPagesError),
+    /// Error compiling the runtime of the relay parent.
+    #[display(fmt = "{_0}")]
+    InvalidRuntime(executor::host::NewErr),
+    /// Error while calling the given runtime function.
+    #[display(fmt = "Error calling {_0}")]
+    Call(&'static str),
+    /// Error while decoding the output of the given runtime function.
+    #[display(fmt = "Error decoding output of {_0}")]
+    Decode(&'static str),
+    /// Relay chain doesn't know about the parachain.
+    UnknownParachain,
+}
+
+impl ParachainInherentBuilder {
+    pub(super) fn new(config: RelayChainConfig) -> Self {
+        ParachainInherentBuilder {
+            config,
+            runtime: None,
+        }
+    }
+
+    /// Returns the configuration that was passed to [`ParachainInherentBuilder::new`].
+    pub(super) fn config(&self) -> &RelayChainConfig {
+        &self.config
+    }
+
+    /// Builds the parachain inherent data of a parachain block whose relay parent is the
+    /// current best block of the relay chain.
+    pub(super) async fn build(
+        &mut self,
+    ) -> Result<(inherents::ParachainInherentData, RelayParentInfo), BuildError> {
+        let (relay_parent_hash, relay_parent_header, code, heap_pages) = self
+            .config
+            .database
+            .with_database(
+                |database| -> Result<_, database_thread::StorageAccessError> {
+                    let block_hash = database
+                        .best_block_hash()
+                        .map_err(database_thread::StorageAccessError::Corrupted)?;
+                    let header = database
+                        .block_scale_encoded_header(&block_hash)
+                        .map_err(database_thread::StorageAccessError::Corrupted)?
+                        .ok_or(database_thread::StorageAccessError::UnknownBlock)?;
+                    let code = database.block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        trie::bytes_to_nibbles(b":code".iter().copied()).map(u8::from),
+                    )?;
+                    let heap_pages = database.block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        trie::bytes_to_nibbles(b":heappages".iter().copied()).map(u8::from),
+                    )?;
+                    Ok((block_hash, header, code, heap_pages))
+                },
+            )
+            .await
+            .map_err(BuildError::Database)?;
+
+        let relay_parent_header =
+            header::decode(&relay_parent_header, self.config.block_number_bytes)
+                .map_err(BuildError::InvalidHeader)?;
+        let (code, _) = code.ok_or(BuildError::NoCode)?;
+
+        if !matches!(&self.runtime, Some((runtime_code, _)) if *runtime_code == code) {
+            let heap_pages =
+                executor::storage_heap_pages_to_value(heap_pages.as_ref().map(|(h, _)| &h[..]))
+                    .map_err(BuildError::InvalidHeapPages)?;
+            let runtime = self
+                .config
+                .compiled_runtimes_cache
+                .build(&code, heap_pages, true)
+                .map_err(BuildError::InvalidRuntime)?;
+            self.runtime = Some((code, runtime));
+        }
+
+        let para_id = self.config.para_id;
+        let block_number_bytes = self.config.block_number_bytes;
+
+        let persisted_validation_data = self
+            .runtime_call(
+                relay_parent_hash,
+                para::PERSISTED_VALIDATION_FUNCTION_NAME,
+                para::persisted_validation_data_parameters(
+                    para_id,
+                    para::OccupiedCoreAssumption::TimedOut,
+                ),
+            )
+            .await?;
+        let persisted_validation_data_hash = {
+            // The runtime returns an `Option`. The hash is calculated over the content of the
+            // `Option`, which is checked below to be `Some`.
+            let mut hasher = blake2_rfc::blake2b::Blake2b::with_key(32, &[]);
+            hasher.update(persisted_validation_data.get(1..).unwrap_or(&[]));
+            <[u8; 32]>::try_from(hasher.finalize().as_bytes()).unwrap()
+        };
+        let persisted_validation_data = para::decode_persisted_validation_data_return_value(
+            &persisted_validation_data,
+            block_number_bytes,
+        )
+        .map_err(|_| BuildError::Decode(para::PERSISTED_VALIDATION_FUNCTION_NAME))?
+        .ok_or(BuildError::UnknownParachain)?;
+
+        let validation_code_hash = self
+            .runtime_call(
+                relay_parent_hash,
+                para::VALIDATION_CODE_HASH_FUNCTION_NAME,
+                para::validation_code_hash_parameters(
+                    para_id,
+                    para::OccupiedCoreAssumption::TimedOut,
+                ),
+            )
+            .await?;
+        let validation_code_hash =
+            *para::decode_validation_code_hash_return_value(&validation_code_hash)
+                .map_err(|_| BuildError::Decode(para::VALIDATION_CODE_HASH_FUNCTION_NAME))?
+                .ok_or(BuildError::UnknownParachain)?;
+
+        let downward_messages = self
+            .runtime_call(
+                relay_parent_hash,
+                para::DMQ_CONTENTS_FUNCTION_NAME,
+                para::dmq_contents_parameters(para_id),
+            )
+            .await?;
+        let downward_messages =
+            para::decode_dmq_contents_return_value(&downward_messages, block_number_bytes)
+                .map_err(|_| BuildError::Decode(para::DMQ_CONTENTS_FUNCTION_NAME))?
+                .into_iter()
+                .map(inherents::InboundMessage::from)
+                .collect();
+
+        let horizontal_messages = self
+            .runtime_call(
+                relay_parent_hash,
+                para::INBOUND_HRMP_CHANNELS_CONTENTS_FUNCTION_NAME,
+                para::inbound_hrmp_channels_contents_parameters(para_id),
+            )
+            .await?;
+        let horizontal_messages = para::decode_inbound_hrmp_channels_contents_return_value(
+            &horizontal_messages,
+            block_number_bytes,
+        )
+        .map_err(|_| BuildError::Decode(para::INBOUND_HRMP_CHANNELS_CONTENTS_FUNCTION_NAME))?
+        .into_iter()
+        .map(|(sender, messages)| {
+            (
+                sender,
+                messages
+                    .into_iter()
+                    .map(inherents::InboundMessage::from)
+                    .collect(),
+            )
+        })
+        .collect::<BTreeMap<_, _>>();
+
+        let relay_chain_state = self
+            .config
+            .database
+            .with_database(
+                move |database| -> Result<_, database_thread::StorageAccessError> {
+                    let mut nodes = Vec::new();
+                    let mut add_key_proof = |key: &[u8]| {
+                        let proof_nodes = database.block_storage_proof_nodes(
+                            &relay_parent_hash,
+                            iter::empty::<iter::Empty<_>>(),
+                            trie::bytes_to_nibbles(key.iter().copied()).map(u8::from),
+                        )?;
+                        for node in proof_nodes {
+                            // Node values shorter than 32 bytes are inlined within their parent,
+                            // except for the root node.
+                            if node.node_value.len() >= 32 || node.key_nibbles.is_empty() {
+                                nodes.push(node.node_value);
+                            }
+                            nodes.extend(node.unhashed_storage_value);
+                        }
+                        Ok::<_, database_thread::StorageAccessError>(())
+                    };
+
+                    for key in para::relay_chain_state_proof_keys(para_id) {
+                        add_key_proof(&key)?;
+                    }
+
+                    // The proof must also contain the HRMP channels found in the ingress and
+                    // egress channel indices.
+                    for (index_key, is_ingress) in [
+                        (para::hrmp_ingress_channels_index_key(para_id), true),
+                        (para::hrmp_egress_channels_index_key(para_id), false),
+                    ] {
+                        let Some((index, _)) = database.block_storage_get(
+                            &relay_parent_hash,
+                            iter::empty::<iter::Empty<_>>(),
+                            trie::bytes_to_nibbles(index_key.iter().copied()).map(u8::from),
+                        )?
+                        else {
+                            continue;
+                        };
+
+                        // An invalid index is simply ignored. The runtime of the parachain will
+                        // reject the proof if it is incomplete.
+                        for other in para::decode_hrmp_channels_index(&index).unwrap_or_default() {
+                            add_key_proof(&if is_ingress {
+                                para::hrmp_channel_key(other, para_id)
+                            } else {
+                                para::hrmp_channel_key(para_id, other)
+                            })?;
+                        }
+                    }
+
+                    Ok(nodes)
+                },
+            )
+            .await
+            .map_err(BuildError::Database)?;
+
+        let inherent_data = inherents::ParachainInherentData {
+            relay_chain_block_number_bytes: block_number_bytes,
+            parent_head: persisted_validation_data.parent_head.to_vec(),
+            relay_parent_number: relay_parent_header.number,
+            relay_parent_storage_root: *relay_parent_header.state_root,
+            max_pov_size: persisted_validation_data.max_pov_size,
+            relay_chain_state,
+            downward_messages,
+            horizontal_messages,
+        };
+
+        let relay_parent = RelayParentInfo {
+            hash: relay_parent_hash,
+            persisted_validation_data_hash,
+            validation_code_hash,
+        };
+
+        Ok((inherent_data, relay_parent))
+    }
+
+    /// Calls the given function of the runtime of the relay chain, and returns its output.
+    async fn runtime_call(
+        &mut self,
+        relay_parent_hash: [u8; 32],
+        function_to_call: &'static str,
+        parameter: impl Iterator<Item = impl AsRef<[u8]>> + Clone,
+    ) -> Result<Vec<u8>, BuildError> {
+        let (code, runtime) = self.runtime.take().unwrap();
+
+        let result = database_thread::run_runtime_call(
+            &self.config.database,
+            relay_parent_hash,
+            executor::runtime_host::Config {
+                virtual_machine: runtime,
+                function_to_call,
+                parameter,
+                max_log_level: 0,
+                storage_main_trie_changes: Default::default(),
+                calculate_trie_changes: false,
+                trace: false,
+            },
+        )
+        .await;
+
+        // In case of error, the runtime is dropped and will be compiled again next time.
+        match result {
+            Ok(Ok(success)) => {
+                let output = success.virtual_machine.value().as_ref().to_vec();
+                self.runtime = Some((code, success.virtual_machine.into_prototype()));
+                Ok(output)
+            }
+            Ok(Err(error)) => {
+                self.runtime = Some((code, error.prototype));
+                Err(BuildError::Call(function_to_call))
+            }
+            Err(()) => Err(BuildError::Call(function_to_call)),
+        }
+    }
+}
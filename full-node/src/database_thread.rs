@@ -20,10 +20,17 @@
 
 use futures_channel::oneshot;
 use smol::{channel, lock::Mutex, stream::StreamExt as _};
-use smoldot::database::full_sqlite::SqliteFullDatabase;
-use std::thread;
+use smoldot::{database::full_sqlite::SqliteFullDatabase, executor, trie};
+use std::{
+    iter,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    thread,
+};
 
-pub use smoldot::database::full_sqlite::{CorruptedError, StorageAccessError};
+pub use smoldot::database::full_sqlite::{CacheStatistics, CorruptedError, StorageAccessError};
 
 /// Handle to the thread were the database accesses are performed.
 ///
@@ -32,6 +39,10 @@ pub use smoldot::database::full_sqlite::{CorruptedError, StorageAccessError};
 /// Use the `From` trait implementation to build a [`DatabaseThread`].
 pub struct DatabaseThread {
     sender: Mutex<channel::Sender<Exec>>,
+
+    /// Statistics of the cache of the database, updated by the database thread after each
+    /// access.
+    cache_statistics: Arc<[AtomicU64; 2]>,
 }
 
 type Exec = Box<dyn FnOnce(&SqliteFullDatabase) + Send>;
@@ -70,25 +81,167 @@ impl DatabaseThread {
             .await
             .unwrap();
     }
+
+    /// Returns the statistics of the cache of the database as of the latest access.
+    pub fn cache_statistics(&self) -> CacheStatistics {
+        CacheStatistics {
+            hits: self.cache_statistics[0].load(Ordering::Relaxed),
+            misses: self.cache_statistics[1].load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Runs the given runtime call to completion, using the storage of the given block.
+///
+/// Returns `Err` if the call couldn't start, if the database couldn't be accessed, or if the
+/// runtime has called an offchain-worker-only function. Logs are ignored.
+pub async fn run_runtime_call(
+    database: &DatabaseThread,
+    block_hash: [u8; 32],
+    config: executor::runtime_host::Config<'_, impl Iterator<Item = impl AsRef<[u8]>> + Clone>,
+) -> Result<Result<executor::runtime_host::Success, executor::runtime_host::Error>, ()> {
+    let mut call = executor::runtime_host::run(config).map_err(|_| ())?;
+
+    loop {
+        match call {
+            executor::runtime_host::RuntimeHostVm::Finished(result) => {
+                return Ok(result);
+            }
+            executor::runtime_host::RuntimeHostVm::StorageGet(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key = trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
+                    .map(u8::from)
+                    .collect::<Vec<_>>();
+                let value = database
+                    .with_database(move |db| {
+                        db.block_storage_get(
+                            &block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key.iter().copied(),
+                        )
+                    })
+                    .await;
+                let Ok(value) = value else {
+                    return Err(());
+                };
+                let value = value.as_ref().map(|(val, vers)| {
+                    (
+                        iter::once(&val[..]),
+                        executor::runtime_host::TrieEntryVersion::try_from(*vers)
+                            .expect("corrupted database"),
+                    )
+                });
+
+                call = req.inject_value(value);
+            }
+            executor::runtime_host::RuntimeHostVm::ClosestDescendantMerkleValue(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
+
+                let merkle_value = database
+                    .with_database(move |db| {
+                        db.block_storage_closest_descendant_merkle_value(
+                            &block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key_nibbles.iter().copied(),
+                        )
+                    })
+                    .await;
+
+                let Ok(merkle_value) = merkle_value else {
+                    return Err(());
+                };
+
+                call = req.inject_merkle_value(merkle_value.as_ref().map(|v| &v[..]));
+            }
+            executor::runtime_host::RuntimeHostVm::NextKey(req) => {
+                let parent_paths = req.child_trie().map(|child_trie| {
+                    trie::bytes_to_nibbles(b":child_storage:default:".iter().copied())
+                        .chain(trie::bytes_to_nibbles(child_trie.as_ref().iter().copied()))
+                        .map(u8::from)
+                        .collect::<Vec<_>>()
+                });
+                let key_nibbles = req
+                    .key()
+                    .map(u8::from)
+                    .chain(if req.or_equal() { None } else { Some(0u8) })
+                    .collect::<Vec<_>>();
+                let prefix_nibbles = req.prefix().map(u8::from).collect::<Vec<_>>();
+
+                let branch_nodes = req.branch_nodes();
+                let next_key = database
+                    .with_database(move |db| {
+                        db.block_storage_next_key(
+                            &block_hash,
+                            parent_paths.into_iter().map(|p| p.into_iter()),
+                            key_nibbles.iter().copied(),
+                            prefix_nibbles.iter().copied(),
+                            branch_nodes,
+                        )
+                    })
+                    .await;
+
+                let Ok(next_key) = next_key else {
+                    return Err(());
+                };
+
+                call = req.inject_key(
+                    next_key.map(|k| k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())),
+                );
+            }
+            executor::runtime_host::RuntimeHostVm::OffchainStorageSet(req) => {
+                call = req.resume();
+            }
+            executor::runtime_host::RuntimeHostVm::SignatureVerification(req) => {
+                call = req.verify_and_resume();
+            }
+            executor::runtime_host::RuntimeHostVm::Offchain(_) => {
+                return Err(());
+            }
+            executor::runtime_host::RuntimeHostVm::LogEmit(req) => {
+                // Logs are ignored.
+                call = req.resume();
+            }
+        }
+    }
 }
 
 impl From<SqliteFullDatabase> for DatabaseThread {
     fn from(db: SqliteFullDatabase) -> DatabaseThread {
         let (sender, mut rx) = channel::bounded::<Box<dyn FnOnce(&SqliteFullDatabase) + Send>>(256);
+        let cache_statistics = Arc::new([AtomicU64::new(0), AtomicU64::new(0)]);
 
         thread::Builder::new()
             .name("sqlite-database".into())
-            .spawn(move || {
-                // When the `DatabaseThread` is dropped, the sender will close, `rx.next()`
-                // will return `None`, and the closure here will finish, ending the thread.
-                while let Some(closure) = smol::block_on(rx.next()) {
-                    closure(&db)
+            .spawn({
+                let cache_statistics = cache_statistics.clone();
+                move || {
+                    // When the `DatabaseThread` is dropped, the sender will close, `rx.next()`
+                    // will return `None`, and the closure here will finish, ending the thread.
+                    while let Some(closure) = smol::block_on(rx.next()) {
+                        closure(&db);
+
+                        let statistics = db.cache_statistics();
+                        cache_statistics[0].store(statistics.hits, Ordering::Relaxed);
+                        cache_statistics[1].store(statistics.misses, Ordering::Relaxed);
+                    }
                 }
             })
             .unwrap();
 
         DatabaseThread {
             sender: Mutex::new(sender),
+            cache_statistics,
         }
     }
 }
@@ -15,7 +15,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{consensus_service, database_thread, network_service, LogCallback, LogLevel};
+use crate::{
+    compiled_runtimes_cache, consensus_service, database_thread, network_service,
+    prometheus_service, LogCallback, LogLevel,
+};
 use futures_channel::oneshot;
 use futures_util::FutureExt;
 use smol::{
@@ -54,6 +57,9 @@ pub struct Config {
     /// Database to access blocks.
     pub database: Arc<database_thread::DatabaseThread>,
 
+    /// Cache used when compiling runtimes.
+    pub compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
     /// Access to the network, and identifier of the chain from the point of view of the network
     /// service.
     pub network_service: (
@@ -61,6 +67,9 @@ pub struct Config {
         network_service::ChainId,
     ),
 
+    /// Metrics of the chain to keep up to date.
+    pub metrics: Arc<prometheus_service::ChainMetrics>,
+
     /// Where to bind the WebSocket server. If `None`, no TCP server is started.
     pub bind_address: Option<SocketAddr>,
 
@@ -157,6 +166,7 @@ impl JsonRpcService {
             config.log_callback.clone(),
             config.consensus_service.clone(),
             config.database.clone(),
+            config.metrics.clone(),
             to_requests_handlers.clone(),
             virtual_client_main_task,
         );
@@ -166,6 +176,7 @@ impl JsonRpcService {
                 tasks_executor: config.tasks_executor.clone(),
                 log_callback: config.log_callback.clone(),
                 database: config.database.clone(),
+                compiled_runtimes_cache: config.compiled_runtimes_cache.clone(),
                 num_cache_entries: NonZeroUsize::new(16).unwrap(), // TODO: configurable?
             },
         ));
@@ -195,6 +206,7 @@ impl JsonRpcService {
                 log_callback: config.log_callback,
                 consensus_service: config.consensus_service.clone(),
                 database: config.database.clone(),
+                metrics: config.metrics.clone(),
                 to_requests_handlers,
                 num_json_rpc_clients: Arc::new(AtomicU32::new(0)),
                 max_json_rpc_clients: config.max_json_rpc_clients,
@@ -276,6 +288,9 @@ struct JsonRpcBackground {
     /// Consensus service of the chain.
     consensus_service: Arc<consensus_service::ConsensusService>,
 
+    /// See [`Config::metrics`].
+    metrics: Arc<prometheus_service::ChainMetrics>,
+
     /// Channel used to send requests to the tasks that process said requests.
     to_requests_handlers: async_channel::Sender<requests_handler::Message>,
 
@@ -366,6 +381,7 @@ impl JsonRpcBackground {
                 self.log_callback.clone(),
                 self.consensus_service.clone(),
                 self.database.clone(),
+                self.metrics.clone(),
                 self.to_requests_handlers.clone(),
                 client_main_task,
             );
@@ -550,6 +566,7 @@ fn spawn_client_main_task(
     log_callback: Arc<dyn LogCallback + Send + Sync>,
     consensus_service: Arc<consensus_service::ConsensusService>,
     database: Arc<database_thread::DatabaseThread>,
+    metrics: Arc<prometheus_service::ChainMetrics>,
     to_requests_handlers: async_channel::Sender<requests_handler::Message>,
     mut client_main_task: service::ClientMainTask,
 ) {
@@ -569,7 +586,10 @@ fn spawn_client_main_task(
                 } => {
                     client_main_task = task;
 
-                    match request_process.request() {
+                    let request = request_process.request();
+                    metrics.json_rpc_request(request.name());
+
+                    match request {
                         methods::MethodCall::chainHead_unstable_header {
                             follow_subscription,
                             ..
@@ -643,7 +663,10 @@ fn spawn_client_main_task(
                 } => {
                     client_main_task = task;
 
-                    match subscription_start.request() {
+                    let request = subscription_start.request();
+                    metrics.json_rpc_request(request.name());
+
+                    match request {
                         // TODO: enforce limit to number of subscriptions
                         methods::MethodCall::chainHead_unstable_follow { with_runtime } => {
                             let (tx, rx) = async_channel::bounded(16);
@@ -18,11 +18,13 @@
 use futures_lite::future;
 use smol::stream::StreamExt as _;
 use smoldot::{
-    executor,
+    database::full_sqlite,
+    executor, header,
     json_rpc::{methods, parse, service},
-    trie,
+    libp2p::{multiaddr, Multiaddr, PeerId},
+    trie, verify,
 };
-use std::{future::Future, iter, pin::Pin, sync::Arc};
+use std::{collections::BTreeMap, future::Future, iter, pin::Pin, sync::Arc};
 
 use crate::{
     consensus_service, database_thread,
@@ -93,6 +95,27 @@ pub fn spawn_requests_handler(mut config: Config) {
                         }));
                     }
 
+                    methods::MethodCall::author_submitExtrinsic { transaction } => {
+                        // Transactions are only accepted in dev seal mode, as the full node
+                        // doesn't have a transactions pool.
+                        let hash = blake2_rfc::blake2b::blake2b(32, &[], &transaction.0);
+                        match config
+                            .consensus_service
+                            .submit_transaction(transaction.0)
+                            .await
+                        {
+                            Ok(()) => request.respond(methods::Response::author_submitExtrinsic(
+                                methods::HashHexString(
+                                    <[u8; 32]>::try_from(hash.as_bytes()).unwrap(),
+                                ),
+                            )),
+                            Err(error) => request.fail(service::ErrorResponse::ServerError(
+                                -32000,
+                                &error.to_string(),
+                            )),
+                        }
+                    }
+
                     methods::MethodCall::chainSpec_v1_chainName {} => {
                         request.respond(methods::Response::chainSpec_v1_chainName(
                             (&config.chain_name).into(),
@@ -181,6 +204,45 @@ pub fn spawn_requests_handler(mut config: Config) {
                             }
                         }
                     }
+                    methods::MethodCall::engine_createBlock {
+                        create_empty,
+                        finalize,
+                        parent_hash,
+                    } => {
+                        match config
+                            .consensus_service
+                            .create_block(create_empty, finalize, parent_hash.map(|h| h.0))
+                            .await
+                        {
+                            Ok(hash) => request.respond(methods::Response::engine_createBlock(
+                                methods::CreatedBlock {
+                                    hash: methods::HashHexString(hash),
+                                    aux: methods::CreatedBlockAux {
+                                        header_only: false,
+                                        clear_justification_requests: false,
+                                        needs_justification: false,
+                                        bad_justification: false,
+                                        // Blocks are always authored on top of the best block.
+                                        is_new_best: true,
+                                    },
+                                },
+                            )),
+                            Err(error) => request.fail(service::ErrorResponse::ServerError(
+                                -32000,
+                                &error.to_string(),
+                            )),
+                        }
+                    }
+                    methods::MethodCall::engine_finalizeBlock {
+                        hash,
+                        justification: _,
+                    } => match config.consensus_service.finalize_block(hash.0).await {
+                        Ok(()) => request.respond(methods::Response::engine_finalizeBlock(true)),
+                        Err(error) => request.fail(service::ErrorResponse::ServerError(
+                            -32000,
+                            &error.to_string(),
+                        )),
+                    },
                     methods::MethodCall::state_getKeysPaged {
                         prefix,
                         count,
@@ -316,165 +378,37 @@ pub fn spawn_requests_handler(mut config: Config) {
                             }
                         };
 
-                        let mut call =
-                            match executor::runtime_host::run(executor::runtime_host::Config {
+                        let call_result = database_thread::run_runtime_call(
+                            &config.database,
+                            hash,
+                            executor::runtime_host::Config {
                                 virtual_machine: runtime,
                                 function_to_call: "Metadata_metadata",
                                 parameter: iter::empty::<&'static [u8]>(),
                                 max_log_level: 0,
                                 storage_main_trie_changes: Default::default(),
                                 calculate_trie_changes: false,
-                            }) {
-                                Ok(c) => c,
-                                Err(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    continue;
-                                }
-                            };
-
-                        loop {
-                            match call {
-                                executor::runtime_host::RuntimeHostVm::Finished(Ok(success)) => {
-                                    match methods::remove_metadata_length_prefix(success.virtual_machine.value().as_ref()) {
-                                        Ok(m) => request.respond(methods::Response::state_getMetadata(methods::HexString(m.to_vec()))),
-                                        Err(_) => {
-                                            request.fail(service::ErrorResponse::InternalError);
-                                        }
-                                    }
-                                    break;
-                                }
-                                executor::runtime_host::RuntimeHostVm::Finished(Err(_)) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    break;
-                                }
-                                executor::runtime_host::RuntimeHostVm::StorageGet(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key =
-                                        trie::bytes_to_nibbles(req.key().as_ref().iter().copied())
-                                            .map(u8::from)
-                                            .collect::<Vec<_>>();
-                                    let value = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_get(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key.iter().copied(),
-                                            )
-                                        })
-                                        .await;
-                                    let Ok(value) = value else {
-                                        request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-                                    let value = value.as_ref().map(|(val, vers)| {
-                                        (
-                                            iter::once(&val[..]),
-                                            executor::runtime_host::TrieEntryVersion::try_from(*vers)
-                                                .expect("corrupted database"),
-                                        )
-                                    });
-
-                                    call = req.inject_value(value);
-                                }
-                                executor::runtime_host::RuntimeHostVm::ClosestDescendantMerkleValue(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key_nibbles = req.key().map(u8::from).collect::<Vec<_>>();
-
-                                    let merkle_value = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_closest_descendant_merkle_value(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key_nibbles.iter().copied(),
-                                            )
-                                        })
-                                        .await;
-
-                                    let Ok(merkle_value) = merkle_value else {
-                                        request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-
-                                    call = req
-                                        .inject_merkle_value(merkle_value.as_ref().map(|v| &v[..]));
-                                }
-                                executor::runtime_host::RuntimeHostVm::NextKey(req) => {
-                                    let parent_paths = req.child_trie().map(|child_trie| {
-                                        trie::bytes_to_nibbles(
-                                            b":child_storage:default:".iter().copied(),
-                                        )
-                                        .chain(trie::bytes_to_nibbles(
-                                            child_trie.as_ref().iter().copied(),
-                                        ))
-                                        .map(u8::from)
-                                        .collect::<Vec<_>>()
-                                    });
-                                    let key_nibbles = req
-                                        .key()
-                                        .map(u8::from)
-                                        .chain(if req.or_equal() { None } else { Some(0u8) })
-                                        .collect::<Vec<_>>();
-                                    let prefix_nibbles =
-                                        req.prefix().map(u8::from).collect::<Vec<_>>();
-
-                                    let branch_nodes = req.branch_nodes();
-                                    let next_key = config
-                                        .database
-                                        .with_database(move |db| {
-                                            db.block_storage_next_key(
-                                                &hash,
-                                                parent_paths.into_iter().map(|p| p.into_iter()),
-                                                key_nibbles.iter().copied(),
-                                                prefix_nibbles.iter().copied(),
-                                                branch_nodes,
-                                            )
-                                        })
-                                        .await;
+                                trace: false,
+                            },
+                        )
+                        .await;
 
-                                    let Ok(next_key) = next_key else {
+                        match call_result {
+                            Ok(Ok(success)) => {
+                                match methods::remove_metadata_length_prefix(
+                                    success.virtual_machine.value().as_ref(),
+                                ) {
+                                    Ok(m) => request.respond(methods::Response::state_getMetadata(
+                                        methods::HexString(m.to_vec()),
+                                    )),
+                                    Err(_) => {
                                         request.fail(service::ErrorResponse::InternalError);
-                                        break;
-                                    };
-
-                                    call = req.inject_key(next_key.map(|k| {
-                                        k.into_iter().map(|b| trie::Nibble::try_from(b).unwrap())
-                                    }));
-                                }
-                                executor::runtime_host::RuntimeHostVm::OffchainStorageSet(req) => {
-                                    call = req.resume();
-                                }
-                                executor::runtime_host::RuntimeHostVm::SignatureVerification(req) => {
-                                    call = req.verify_and_resume();
-                                }
-                                executor::runtime_host::RuntimeHostVm::Offchain(_) => {
-                                    request.fail(service::ErrorResponse::InternalError);
-                                    break;
-                                }
-                                executor::runtime_host::RuntimeHostVm::LogEmit(req) => {
-                                    // Logs are ignored.
-                                    call = req.resume();
+                                    }
                                 }
                             }
+                            Ok(Err(_)) | Err(()) => {
+                                request.fail(service::ErrorResponse::InternalError);
+                            }
                         }
                     }
                     methods::MethodCall::state_getRuntimeVersion { at } => {
@@ -594,6 +528,56 @@ pub fn spawn_requests_handler(mut config: Config) {
                             }
                         }
                     }
+                    methods::MethodCall::state_traceBlock {
+                        block,
+                        targets,
+                        storage_keys,
+                        methods: methods_filter,
+                    } => {
+                        let result = trace_block(
+                            &config,
+                            block.0,
+                            targets.as_deref(),
+                            storage_keys.as_deref(),
+                            methods_filter.as_deref(),
+                        )
+                        .await;
+
+                        match result {
+                            Ok(response) => {
+                                request.respond(methods::Response::state_traceBlock(response))
+                            }
+                            Err(error) => request.fail(error),
+                        }
+                    }
+                    methods::MethodCall::system_addReservedPeer { peer } => {
+                        // The address must end with `/p2p/...`, which is removed before being
+                        // passed to the network service.
+                        let parsed = peer.parse::<Multiaddr>().ok().and_then(|mut address| {
+                            let Some(multiaddr::ProtocolRef::P2p(peer_id)) = address.iter().last()
+                            else {
+                                return None;
+                            };
+                            let peer_id = PeerId::from_bytes(peer_id.to_vec()).ok()?;
+                            address.pop();
+                            Some((peer_id, address))
+                        });
+
+                        match parsed {
+                            Some((peer_id, address)) => {
+                                config
+                                    .network_service
+                                    .0
+                                    .add_reserved_peer(config.network_service.1, peer_id, address)
+                                    .await;
+                                request.respond(methods::Response::system_addReservedPeer(()));
+                            }
+                            None => request.fail(service::ErrorResponse::ServerError(
+                                -32000,
+                                "Peer address must be a multiaddress ending with /p2p/...",
+                            )),
+                        }
+                    }
                     methods::MethodCall::system_chain {} => {
                         request
                             .respond(methods::Response::system_chain((&config.chain_name).into()));
@@ -616,6 +600,17 @@ pub fn spawn_requests_handler(mut config: Config) {
                             should_have_peers: config.chain_is_live,
                         }));
                     }
+                    methods::MethodCall::system_localListenAddresses {} => {
+                        let addresses = config
+                            .network_service
+                            .0
+                            .local_listen_addresses()
+                            .await
+                            .into_iter()
+                            .map(|addr| addr.to_string())
+                            .collect();
+                        request.respond(methods::Response::system_localListenAddresses(addresses));
+                    }
                     methods::MethodCall::system_localPeerId {} => {
                         let peer_id = config.network_service.0.local_peer_id().to_base58();
                         request.respond(methods::Response::system_localPeerId(peer_id.into()));
@@ -630,6 +625,22 @@ pub fn spawn_requests_handler(mut config: Config) {
                             serde_json::from_str(&config.chain_properties_json).unwrap(),
                         ));
                     }
+                    methods::MethodCall::system_removeReservedPeer { peer_id } => {
+                        match peer_id.parse::<PeerId>() {
+                            Ok(peer_id) => {
+                                config
+                                    .network_service
+                                    .0
+                                    .remove_reserved_peer(config.network_service.1, peer_id)
+                                    .await;
+                                request.respond(methods::Response::system_removeReservedPeer(()));
+                            }
+                            Err(_) => request.fail(service::ErrorResponse::ServerError(
+                                -32000,
+                                "Invalid peer ID",
+                            )),
+                        }
+                    }
                     methods::MethodCall::system_version {} => {
                         request.respond(methods::Response::system_version(
                             env!("CARGO_PKG_VERSION").into(),
@@ -842,3 +853,266 @@ fn convert_runtime_version(runtime_spec: &executor::CoreVersion) -> methods::Run
             .collect(),
     }
 }
+
+/// Re-executes the given block on top of the storage of its parent with tracing enabled, and
+/// returns the list of events that have happened during the execution.
+///
+/// Each filter is a comma-separated list. If `targets` is provided, only the events whose
+/// target starts with one of the elements are kept. If `storage_keys` is provided, only the
+/// storage events whose key starts with one of the hexadecimal-encoded elements are kept.
+/// If `methods_filter` is provided, only the calls to the host functions of this list are kept.
+async fn trace_block(
+    config: &Config,
+    block_hash: [u8; 32],
+    targets: Option<&str>,
+    storage_keys: Option<&str>,
+    methods_filter: Option<&str>,
+) -> Result<methods::TraceBlockResponse, service::ErrorResponse<'static>> {
+    let targets_list = targets
+        .filter(|t| !t.is_empty())
+        .map(|t| t.split(',').map(|t| t.trim()).collect::<Vec<_>>());
+    let methods_list = methods_filter
+        .filter(|m| !m.is_empty())
+        .map(|m| m.split(',').map(|m| m.trim()).collect::<Vec<_>>());
+    let storage_keys_list = match storage_keys.filter(|k| !k.is_empty()) {
+        Some(storage_keys) => Some(
+            storage_keys
+                .split(',')
+                .map(|key| {
+                    let key = key.trim();
+                    hex::decode(key.strip_prefix("0x").unwrap_or(key))
+                })
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| service::ErrorResponse::InvalidParams)?,
+        ),
+        None => None,
+    };
+
+    let (encoded_header, parent_hash, body) = config
+        .database
+        .with_database(move |db| {
+            let Some(header) = db.block_scale_encoded_header(&block_hash)? else {
+                return Ok(None);
+            };
+            let parent_hash = db.block_parent(&block_hash)?;
+            let body = db.block_extrinsics(&block_hash)?;
+            Ok::<_, full_sqlite::CorruptedError>(
+                parent_hash
+                    .zip(body)
+                    .map(|(parent_hash, body)| (header, parent_hash, body.collect::<Vec<_>>())),
+            )
+        })
+        .await
+        .map_err(|_| service::ErrorResponse::InternalError)?
+        .ok_or(service::ErrorResponse::InvalidParams)?;
+
+    // The block is executed on top of the storage of its parent, using the runtime of its
+    // parent. The genesis block has no parent and thus can't be executed.
+    let runtime = match config.runtime_caches_service.get(parent_hash).await {
+        Ok(runtime) => (*runtime).clone(),
+        Err(runtime_caches_service::GetError::UnknownBlock)
+        | Err(runtime_caches_service::GetError::Pruned) => {
+            return Ok(methods::TraceBlockResponse::TraceError(
+                methods::TraceBlockError {
+                    error: "Storage of the parent block isn't available".to_owned(),
+                },
+            ));
+        }
+        Err(runtime_caches_service::GetError::InvalidRuntime(_))
+        | Err(runtime_caches_service::GetError::NoCode)
+        | Err(runtime_caches_service::GetError::InvalidHeapPages)
+        | Err(runtime_caches_service::GetError::CorruptedDatabase) => {
+            return Err(service::ErrorResponse::InternalError);
+        }
+    };
+
+    let execute_block_parameters = {
+        let block_number_bytes = config.consensus_service.block_number_bytes();
+        let header = header::decode(&encoded_header, block_number_bytes)
+            .map_err(|_| service::ErrorResponse::InternalError)?;
+        verify::body_only::execute_block_parameter(header, block_number_bytes, body.iter())
+    };
+
+    let call_result = database_thread::run_runtime_call(
+        &config.database,
+        parent_hash,
+        executor::runtime_host::Config {
+            virtual_machine: runtime,
+            function_to_call: "Core_execute_block",
+            parameter: iter::once(&execute_block_parameters),
+            max_log_level: 5,
+            storage_main_trie_changes: Default::default(),
+            calculate_trie_changes: false,
+            trace: true,
+        },
+    )
+    .await;
+
+    let trace = match call_result {
+        Ok(Ok(success)) => success.trace,
+        Ok(Err(error)) => {
+            return Ok(methods::TraceBlockResponse::TraceError(
+                methods::TraceBlockError {
+                    error: error.detail.to_string(),
+                },
+            ));
+        }
+        Err(()) => return Err(service::ErrorResponse::InternalError),
+    };
+
+    let events = trace
+        .into_iter()
+        .filter(|event| match (event, &methods_list) {
+            (executor::runtime_host::TraceEvent::HostFunctionCall { name }, Some(list)) => {
+                list.iter().any(|m| m == name)
+            }
+            _ => true,
+        })
+        .filter(|event| match (event.storage_key(), &storage_keys_list) {
+            (Some(key), Some(list)) => list.iter().any(|prefix| key.starts_with(prefix)),
+            _ => true,
+        })
+        .map(convert_trace_event)
+        .filter(|event| match &targets_list {
+            Some(list) => list.iter().any(|target| event.target.starts_with(target)),
+            None => true,
+        })
+        .collect();
+
+    Ok(methods::TraceBlockResponse::BlockTrace(
+        methods::BlockTrace {
+            block_hash: methods::HashHexString(block_hash),
+            parent_hash: methods::HashHexString(parent_hash),
+            tracing_targets: targets.unwrap_or_default().to_owned(),
+            storage_keys: storage_keys.unwrap_or_default().to_owned(),
+            methods: methods_filter.unwrap_or_default().to_owned(),
+            events,
+        },
+    ))
+}
+
+fn convert_trace_event(event: executor::runtime_host::TraceEvent) -> methods::TraceBlockEvent {
+    let mut string_values = BTreeMap::new();
+
+    let target = match event {
+        executor::runtime_host::TraceEvent::HostFunctionCall { name } => {
+            string_values.insert("method".to_owned(), name.to_owned());
+            "host"
+        }
+        executor::runtime_host::TraceEvent::StorageRead {
+            child_trie,
+            key,
+            value,
+        } => {
+            string_values.insert("method".to_owned(), "Get".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(key));
+            string_values.insert(
+                "result".to_owned(),
+                value.map_or("None".to_owned(), hex::encode),
+            );
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageWrite {
+            child_trie,
+            key,
+            value: Some(value),
+        } => {
+            string_values.insert("method".to_owned(), "Put".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(key));
+            string_values.insert("value".to_owned(), hex::encode(value));
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageWrite {
+            child_trie,
+            key,
+            value: None,
+        } => {
+            string_values.insert("method".to_owned(), "Clear".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(key));
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageAppend {
+            child_trie,
+            key,
+            value,
+        } => {
+            string_values.insert("method".to_owned(), "Append".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(key));
+            string_values.insert("value".to_owned(), hex::encode(value));
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageClearPrefix {
+            child_trie,
+            prefix,
+            max_keys_to_remove,
+        } => {
+            string_values.insert("method".to_owned(), "ClearPrefix".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(prefix));
+            if let Some(max_keys_to_remove) = max_keys_to_remove {
+                string_values.insert(
+                    "max_keys_to_remove".to_owned(),
+                    max_keys_to_remove.to_string(),
+                );
+            }
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageNextKey {
+            child_trie,
+            key,
+            next_key,
+        } => {
+            string_values.insert("method".to_owned(), "NextKey".to_owned());
+            string_values.insert("key".to_owned(), hex::encode(key));
+            string_values.insert(
+                "result".to_owned(),
+                next_key.map_or("None".to_owned(), hex::encode),
+            );
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::StorageRoot { child_trie, hash } => {
+            string_values.insert("method".to_owned(), "StorageRoot".to_owned());
+            string_values.insert("result".to_owned(), hex::encode(hash));
+            if let Some(child_trie) = child_trie {
+                string_values.insert("child_trie".to_owned(), hex::encode(child_trie));
+            }
+            "state"
+        }
+        executor::runtime_host::TraceEvent::Log {
+            log_level,
+            target,
+            message,
+        } => {
+            string_values.insert("message".to_owned(), message);
+            if let Some(log_level) = log_level {
+                string_values.insert("level".to_owned(), log_level.to_string());
+            }
+            if let Some(target) = target {
+                string_values.insert("target".to_owned(), target);
+            }
+            "log"
+        }
+    };
+
+    methods::TraceBlockEvent {
+        target: target.to_owned(),
+        data: methods::TraceBlockEventData { string_values },
+    }
+}
@@ -15,7 +15,7 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{database_thread, LogCallback};
+use crate::{compiled_runtimes_cache, database_thread, LogCallback};
 
 use futures_channel::oneshot;
 use futures_lite::{Future, StreamExt as _};
@@ -34,6 +34,9 @@ pub struct Config {
     /// Database to access blocks.
     pub database: Arc<database_thread::DatabaseThread>,
 
+    /// Cache used when compiling runtimes.
+    pub compiled_runtimes_cache: Arc<compiled_runtimes_cache::CompiledRuntimesCache>,
+
     /// Number of entries in the cache of runtimes.
     pub num_cache_entries: NonZeroUsize,
 }
@@ -95,15 +98,10 @@ impl RuntimeCachesService {
                                 match executor::storage_heap_pages_to_value(
                                     heap_pages.as_ref().map(|(h, _)| &h[..]),
                                 ) {
-                                    Ok(heap_pages) => executor::host::HostVmPrototype::new(
-                                        executor::host::Config {
-                                            module: &code,
-                                            heap_pages,
-                                            exec_hint: executor::vm::ExecHint::CompileAheadOfTime,
-                                            allow_unresolved_imports: true, // TODO: configurable? or if not, document
-                                        },
-                                    )
-                                    .map_err(GetError::InvalidRuntime),
+                                    Ok(heap_pages) => config
+                                        .compiled_runtimes_cache
+                                        .build(&code, heap_pages, true) // TODO: `allow_unresolved_imports` configurable? or if not, document
+                                        .map_err(GetError::InvalidRuntime),
                                     Err(_) => Err(GetError::InvalidHeapPages),
                                 }
                             }
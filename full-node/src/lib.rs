@@ -31,17 +31,24 @@ use smoldot::{
         connection, multiaddr,
         peer_id::{self, PeerId},
     },
+    network::authority_discovery,
     trie,
 };
 use std::{array, borrow::Cow, io, iter, mem, net::SocketAddr, path::PathBuf, sync::Arc};
 
+mod authority_discovery_service;
+mod clock;
+mod compiled_runtimes_cache;
 mod consensus_service;
 mod database_thread;
 mod jaeger_service;
 mod json_rpc_service;
 mod network_service;
+mod prometheus_service;
 mod util;
 
+pub use clock::{Clock, Delay};
+
 pub struct Config<'a> {
     /// Chain to connect to.
     pub chain: ChainConfig<'a>,
@@ -58,8 +65,29 @@ pub struct Config<'a> {
     pub tasks_executor: Arc<dyn Fn(future::BoxFuture<'static, ()>) + Send + Sync>,
     /// Function called whenever a part of the node wants to notify of something.
     pub log_callback: Arc<dyn LogCallback + Send + Sync>,
+    /// Source of time of the node. Typically [`Clock::system`].
+    pub clock: Clock,
     /// Address of a Jaeger agent to send traces to. If `None`, do not send Jaeger traces.
     pub jaeger_agent: Option<SocketAddr>,
+    /// Address to bind the HTTP server exporting Prometheus metrics to. If `None`, the metrics
+    /// aren't exported.
+    pub prometheus_address: Option<SocketAddr>,
+    /// If `Some`, the node authors the blocks of [`Config::chain`] on demand rather than
+    /// following the slots of the consensus algorithm, and ignores the networking when it comes
+    /// to syncing. Meant for local development.
+    pub dev_seal: Option<DevSeal>,
+}
+
+/// See [`Config::dev_seal`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DevSeal {
+    /// A block is authored and finalized as soon as a transaction is submitted. Blocks can also
+    /// be authored and finalized with the `engine_createBlock` and `engine_finalizeBlock`
+    /// JSON-RPC functions.
+    Instant,
+    /// Blocks are only authored and finalized with the `engine_createBlock` and
+    /// `engine_finalizeBlock` JSON-RPC functions.
+    Manual,
 }
 
 /// See [`ChainConfig::json_rpc_listen`].
@@ -103,6 +131,11 @@ pub struct ChainConfig<'a> {
     pub chain_spec: Cow<'a, [u8]>,
     /// Identity and address of nodes to try to connect to on startup.
     pub additional_bootnodes: Vec<(peer_id::PeerId, multiaddr::Multiaddr)>,
+    /// Identity and address of nodes that the node always tries to stay connected to.
+    pub reserved_nodes: Vec<(peer_id::PeerId, multiaddr::Multiaddr)>,
+    /// If `true`, only nodes in [`ChainConfig::reserved_nodes`] or added later through the
+    /// JSON-RPC API are connected to.
+    pub reserved_only: bool,
     /// List of secret phrases to insert in the keystore of the node. Used to author blocks.
     // TODO: also automatically add the same keys through ed25519?
     pub keystore_memory: Vec<Box<[u8; 64]>>,
@@ -114,8 +147,17 @@ pub struct ChainConfig<'a> {
     ///
     /// If `None`, no keys are stored in disk.
     pub keystore_path: Option<PathBuf>,
+    /// Path to the file where the list of known peers of the chain is saved, and reloaded from
+    /// when the node restarts.
+    ///
+    /// If `None`, the list of known peers isn't saved.
+    pub address_book_path: Option<PathBuf>,
     /// Configuration of the JSON-RPC server. If `None`, no TCP server is started.
     pub json_rpc_listen: Option<JsonRpcListenConfig>,
+    /// If `true` and the database is empty, the node warp syncs to the head of the finalized
+    /// chain and downloads its storage instead of verifying all the blocks since the genesis.
+    /// Has no effect if the chain doesn't use GrandPa.
+    pub warp_sync: bool,
 }
 
 /// Running client. As long as this object is alive, the client reads/writes the database and has
@@ -127,6 +169,11 @@ pub struct Client {
     relay_chain_consensus_service: Option<Arc<consensus_service::ConsensusService>>,
     network_service: Arc<network_service::NetworkService>,
     network_known_best: Arc<Mutex<Option<u64>>>,
+    prometheus_service: Arc<prometheus_service::PrometheusService>,
+    /// Kept alive in order to continue publishing and looking up authority records.
+    _authority_discovery_service: authority_discovery_service::AuthorityDiscoveryService,
+    _relay_chain_authority_discovery_service:
+        Option<authority_discovery_service::AuthorityDiscoveryService>,
 }
 
 impl Client {
@@ -148,6 +195,13 @@ impl Client {
             .and_then(|j| j.listen_addr())
     }
 
+    /// Returns the address the Prometheus HTTP server is listening on.
+    ///
+    /// Returns `None` if and only if [`Config::prometheus_address`] was `None`.
+    pub fn prometheus_server_addr(&self) -> Option<SocketAddr> {
+        self.prometheus_service.listen_addr()
+    }
+
     /// Returns the best block according to the networking.
     pub async fn network_known_best(&self) -> Option<u64> {
         *self.network_known_best.lock().await
@@ -227,6 +281,15 @@ impl Client {
     }
 }
 
+impl Drop for Client {
+    fn drop(&mut self) {
+        // The other services hold a reference to the network service and might take some time
+        // to shut down. The networking is stopped immediately, so that the address books are
+        // saved before the process likely exits.
+        self.network_service.shutdown();
+    }
+}
+
 /// Error potentially returned by [`start`].
 #[derive(Debug, derive_more::Display)]
 pub enum StartError {
@@ -252,6 +315,8 @@ pub enum StartError {
     RelayChainKeystoreInit(io::Error),
     /// Error initializing the Jaeger service.
     JaegerInit(io::Error),
+    /// Error initializing the Prometheus service.
+    PrometheusInit(io::Error),
 }
 
 /// Error potentially returned by [`Client::relay_chain_send_json_rpc_request`].
@@ -341,6 +406,27 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         format!("sqlite-version; version={}", full_sqlite::sqlite_version()),
     );
 
+    // The runtimes compiled ahead of time are saved next to the database.
+    let compiled_runtimes_cache = Arc::new(compiled_runtimes_cache::CompiledRuntimesCache::new(
+        config
+            .chain
+            .sqlite_database_path
+            .as_ref()
+            .and_then(|path| path.parent())
+            .map(|dir| dir.join("compiled-runtimes")),
+        config.log_callback.clone(),
+    ));
+    let relay_chain_compiled_runtimes_cache =
+        Arc::new(compiled_runtimes_cache::CompiledRuntimesCache::new(
+            config
+                .relay_chain
+                .as_ref()
+                .and_then(|relay_chain| relay_chain.sqlite_database_path.as_ref())
+                .and_then(|path| path.parent())
+                .map(|dir| dir.join("compiled-runtimes")),
+            config.log_callback.clone(),
+        ));
+
     let (database, database_existed) = {
         let (db, existed) = open_database(
             &chain_spec,
@@ -389,6 +475,13 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         rand::thread_rng().fill_bytes(&mut *noise_static_key);
         connection::NoiseKey::new(&config.libp2p_key, &noise_static_key)
     };
+    // The record published through the authority discovery mechanism is signed using the libp2p
+    // key, and must thus be built before this key is erased.
+    // TODO: the listen addresses aren't necessarily reachable by other nodes; publish the public addresses instead
+    let authority_discovery_record = authority_discovery::LocalRecord::new(
+        &config.libp2p_key,
+        config.listen_addresses.iter().cloned(),
+    );
     zeroize::Zeroize::zeroize(&mut *config.libp2p_key);
     let local_peer_id =
         peer_id::PublicKey::Ed25519(*noise_key.libp2p_public_ed25519_key()).into_peer_id();
@@ -406,8 +499,22 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
     .await
     .map_err(StartError::JaegerInit)?;
 
+    let prometheus_service =
+        prometheus_service::PrometheusService::new(prometheus_service::Config {
+            tasks_executor: &mut |task| (config.tasks_executor)(task),
+            log_callback: config.log_callback.clone(),
+            listen_address: config.prometheus_address,
+        })
+        .await
+        .map_err(StartError::PrometheusInit)?;
+    let chain_metrics = prometheus_service.add_chain(chain_spec.id().to_owned());
+    let relay_chain_metrics = relay_chain_spec
+        .as_ref()
+        .map(|spec| prometheus_service.add_chain(spec.id().to_owned()));
+
     let (network_service, network_service_chain_ids, network_events_receivers) =
         network_service::NetworkService::new(network_service::Config {
+            clock: config.clock.clone(),
             listen_addresses: config.listen_addresses,
             num_events_receivers: 2 + if relay_chain_database.is_some() { 1 } else { 0 },
             chains: iter::once(network_service::ChainConfig {
@@ -415,6 +522,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                 fork_id: chain_spec.fork_id().map(|n| n.to_owned()),
                 block_number_bytes: usize::from(chain_spec.block_number_bytes()),
                 database: database.clone(),
+                metrics: chain_metrics.clone(),
                 grandpa_protocol_finalized_block_height: if matches!(
                     genesis_chain_information.as_ref().finality,
                     chain::chain_information::ChainInformationFinalityRef::Grandpa { .. }
@@ -477,6 +585,10 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                     list.extend(config.chain.additional_bootnodes);
                     list
                 },
+                reserved_nodes: config.chain.reserved_nodes,
+                reserved_only: config.chain.reserved_only,
+                address_book_path: config.chain.address_book_path.clone(),
+                allow_collation_protocols: false,
             })
             .chain(
                 if let Some(relay_chains_specs) = &relay_chain_spec {
@@ -485,6 +597,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                         fork_id: relay_chains_specs.fork_id().map(|n| n.to_owned()),
                         block_number_bytes: usize::from(relay_chains_specs.block_number_bytes()),
                         database: relay_chain_database.clone().unwrap(),
+                        metrics: relay_chain_metrics.clone().unwrap(),
                         grandpa_protocol_finalized_block_height: if matches!(
                             genesis_chain_information.as_ref().finality,
                             chain::chain_information::ChainInformationFinalityRef::Grandpa { .. }
@@ -551,6 +664,22 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                             }
                             list
                         },
+                        reserved_nodes: config
+                            .relay_chain
+                            .as_ref()
+                            .map(|c| c.reserved_nodes.clone())
+                            .unwrap_or_default(),
+                        reserved_only: config
+                            .relay_chain
+                            .as_ref()
+                            .map_or(false, |c| c.reserved_only),
+                        address_book_path: config
+                            .relay_chain
+                            .as_ref()
+                            .and_then(|c| c.address_book_path.clone()),
+                        // The main chain is a parachain of this relay chain, and the blocks
+                        // that the local node authors must be sent to the validators.
+                        allow_collation_protocols: true,
                     })
                 } else {
                     None
@@ -566,6 +695,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
             },
             log_callback: config.log_callback.clone(),
             jaeger_service: jaeger_service.clone(),
+            prometheus_service: prometheus_service.clone(),
         })
         .await
         .map_err(StartError::NetworkInit)?;
@@ -589,18 +719,59 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
             Box::new(move |task| executor(task))
         },
         log_callback: config.log_callback.clone(),
+        clock: config.clock.clone(),
         genesis_block_hash,
         network_events_receiver: network_events_receivers.next().unwrap(),
         network_service: (network_service.clone(), network_service_chain_ids[0]),
         database: database.clone(),
+        compiled_runtimes_cache: compiled_runtimes_cache.clone(),
         block_number_bytes: usize::from(chain_spec.block_number_bytes()),
-        keystore,
+        keystore: keystore.clone(),
         jaeger_service: jaeger_service.clone(),
+        metrics: chain_metrics.clone(),
         slot_duration_author_ratio: 43691_u16,
+        dev_seal: config.dev_seal,
+        warp_sync: config.chain.warp_sync,
+        relay_chain: match (
+            chain_spec.relay_chain(),
+            &relay_chain_database,
+            &relay_chain_spec,
+        ) {
+            (Some((_, para_id)), Some(relay_chain_database), Some(relay_chain_spec)) => {
+                Some(consensus_service::RelayChainConfig {
+                    para_id,
+                    database: relay_chain_database.clone(),
+                    compiled_runtimes_cache: relay_chain_compiled_runtimes_cache.clone(),
+                    block_number_bytes: usize::from(relay_chain_spec.block_number_bytes()),
+                    network_chain_id: network_service_chain_ids[1],
+                    // Similar to what Cumulus does, the collator key is generated at startup and
+                    // isn't saved on disk.
+                    collator_key: keystore
+                        .generate_sr25519(keystore::KeyNamespace::Collator, false)
+                        .await
+                        .map_err(StartError::KeystoreInit)?,
+                })
+            }
+            _ => None,
+        },
     })
     .await
     .map_err(StartError::ConsensusServiceInit)?;
 
+    let relay_chain_keystore = if let Some(relay_chain_config) = config.relay_chain.as_mut() {
+        let mut keystore =
+            keystore::Keystore::new(relay_chain_config.keystore_path.clone(), rand::random())
+                .await
+                .map_err(StartError::RelayChainKeystoreInit)?;
+        for mut private_key in mem::take(&mut relay_chain_config.keystore_memory) {
+            keystore.insert_sr25519_memory(keystore::KeyNamespace::all(), &private_key);
+            zeroize::Zeroize::zeroize(&mut *private_key);
+        }
+        Some(Arc::new(keystore))
+    } else {
+        None
+    };
+
     let relay_chain_consensus_service = if let Some(relay_chain_database) = &relay_chain_database {
         Some(
             consensus_service::ConsensusService::new(consensus_service::Config {
@@ -609,6 +780,7 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                     Box::new(move |task| executor(task))
                 },
                 log_callback: config.log_callback.clone(),
+                clock: config.clock.clone(),
                 genesis_block_hash: relay_genesis_chain_information
                     .as_ref()
                     .unwrap()
@@ -620,26 +792,17 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                 network_events_receiver: network_events_receivers.next().unwrap(),
                 network_service: (network_service.clone(), network_service_chain_ids[1]),
                 database: relay_chain_database.clone(),
+                compiled_runtimes_cache: relay_chain_compiled_runtimes_cache.clone(),
                 block_number_bytes: usize::from(
                     relay_chain_spec.as_ref().unwrap().block_number_bytes(),
                 ),
-                keystore: Arc::new({
-                    let mut keystore = keystore::Keystore::new(
-                        config.relay_chain.as_ref().unwrap().keystore_path.clone(),
-                        rand::random(),
-                    )
-                    .await
-                    .map_err(StartError::RelayChainKeystoreInit)?;
-                    for mut private_key in
-                        mem::take(&mut config.relay_chain.as_mut().unwrap().keystore_memory)
-                    {
-                        keystore.insert_sr25519_memory(keystore::KeyNamespace::all(), &private_key);
-                        zeroize::Zeroize::zeroize(&mut *private_key);
-                    }
-                    keystore
-                }),
+                keystore: relay_chain_keystore.clone().unwrap(),
                 jaeger_service, // TODO: consider passing a different jaeger service with a different service name
+                metrics: relay_chain_metrics.clone().unwrap(),
                 slot_duration_author_ratio: 43691_u16,
+                dev_seal: None,
+                warp_sync: config.relay_chain.as_ref().unwrap().warp_sync,
+                relay_chain: None,
             })
             .await
             .map_err(StartError::RelayChainConsensusServiceInit)?,
@@ -648,6 +811,47 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         None
     };
 
+    // Start the authority discovery services.
+    // They only need to be kept alive in order to function.
+    let authority_discovery_service = authority_discovery_service::AuthorityDiscoveryService::new(
+        authority_discovery_service::Config {
+            tasks_executor: {
+                let executor = config.tasks_executor.clone();
+                Box::new(move |task| executor(task))
+            },
+            log_callback: config.log_callback.clone(),
+            clock: config.clock.clone(),
+            database: database.clone(),
+            compiled_runtimes_cache: compiled_runtimes_cache.clone(),
+            keystore,
+            network_service: (network_service.clone(), network_service_chain_ids[0]),
+            local_record: authority_discovery_record.clone(),
+        },
+    );
+
+    let relay_chain_authority_discovery_service =
+        if let (Some(relay_chain_database), Some(relay_chain_keystore)) =
+            (&relay_chain_database, relay_chain_keystore)
+        {
+            Some(authority_discovery_service::AuthorityDiscoveryService::new(
+                authority_discovery_service::Config {
+                    tasks_executor: {
+                        let executor = config.tasks_executor.clone();
+                        Box::new(move |task| executor(task))
+                    },
+                    log_callback: config.log_callback.clone(),
+                    clock: config.clock.clone(),
+                    database: relay_chain_database.clone(),
+                    compiled_runtimes_cache: relay_chain_compiled_runtimes_cache.clone(),
+                    keystore: relay_chain_keystore,
+                    network_service: (network_service.clone(), network_service_chain_ids[1]),
+                    local_record: authority_discovery_record,
+                },
+            ))
+        } else {
+            None
+        };
+
     // Start the JSON-RPC service.
     // It only needs to be kept alive in order to function.
     //
@@ -659,8 +863,10 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         tasks_executor: config.tasks_executor.clone(),
         log_callback: config.log_callback.clone(),
         database,
+        compiled_runtimes_cache,
         consensus_service: consensus_service.clone(),
         network_service: (network_service.clone(), network_service_chain_ids[0]),
+        metrics: chain_metrics,
         bind_address: config.chain.json_rpc_listen.as_ref().map(|cfg| cfg.address),
         max_parallel_requests: 32,
         max_json_rpc_clients: config
@@ -688,8 +894,10 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
                 tasks_executor: config.tasks_executor.clone(),
                 log_callback: config.log_callback.clone(),
                 database: relay_chain_database.clone().unwrap(),
+                compiled_runtimes_cache: relay_chain_compiled_runtimes_cache,
                 consensus_service: relay_chain_consensus_service.clone().unwrap(),
                 network_service: (network_service.clone(), network_service_chain_ids[1]),
+                metrics: relay_chain_metrics.unwrap(),
                 bind_address: relay_chain_cfg
                     .json_rpc_listen
                     .as_ref()
@@ -728,10 +936,13 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         let network_service_chain_id = network_service_chain_ids[0];
         let network_known_best = network_known_best.clone();
 
-        // TODO: shut down this task if the client stops?
         async move {
             loop {
-                let network_event = main_network_events_receiver.next().await.unwrap();
+                // The events receiver is closed when the network service shuts down, in other
+                // words when the client stops.
+                let Some(network_event) = main_network_events_receiver.next().await else {
+                    break;
+                };
                 let mut network_known_best = network_known_best.lock().await;
 
                 match network_event {
@@ -787,6 +998,9 @@ pub async fn start(mut config: Config<'_>) -> Result<Client, StartError> {
         relay_chain_json_rpc_service,
         network_service,
         network_known_best,
+        prometheus_service,
+        _authority_discovery_service: authority_discovery_service,
+        _relay_chain_authority_discovery_service: relay_chain_authority_discovery_service,
     })
 }
 
@@ -824,10 +1038,17 @@ async fn open_database(
     {
         // Database already exists and contains data.
         full_sqlite::DatabaseOpen::Open(database) => {
-            if database.block_hash_by_number(0).unwrap().next().unwrap()
-                != genesis_chain_information
-                    .finalized_block_header
-                    .hash(chain_spec.block_number_bytes().into())
+            // A database that has been warp synced doesn't contain the genesis block, in which
+            // case this check can't be performed.
+            if database
+                .block_hash_by_number(0)
+                .unwrap()
+                .next()
+                .is_some_and(|hash| {
+                    hash != genesis_chain_information
+                        .finalized_block_header
+                        .hash(chain_spec.block_number_bytes().into())
+                })
             {
                 panic!("Mismatch between database and chain specification. Shutting down node.");
             }
@@ -851,6 +1072,7 @@ async fn open_database(
                 .unwrap(),
                 exec_hint: executor::vm::ExecHint::Oneshot,
                 allow_unresolved_imports: true,
+                fuel_budget: None,
             })
             .unwrap()
             .runtime_version()
@@ -859,133 +1081,8 @@ async fn open_database(
             .map(u8::from)
             .unwrap_or(0);
 
-            // The chain specification only contains trie nodes that have a storage value attached
-            // to them, while the database needs to know all trie nodes (including branch nodes).
-            // The good news is that we can determine the latter from the former, which we do
-            // here.
-            // TODO: consider moving this block to the chain spec module
-            // TODO: poorly optimized
-            let mut trie_structure = {
-                let mut trie_structure = trie::trie_structure::TrieStructure::new();
-                for (key, value) in genesis_storage.iter() {
-                    match trie_structure.node(trie::bytes_to_nibbles(key.iter().copied())) {
-                        trie::trie_structure::Entry::Vacant(e) => {
-                            e.insert_storage_value().insert(
-                                (Some(value), None::<trie::trie_node::MerkleValueOutput>),
-                                (None, None),
-                            );
-                        }
-                        trie::trie_structure::Entry::Occupied(
-                            trie::trie_structure::NodeAccess::Branch(mut e),
-                        ) => {
-                            *e.user_data() = (Some(value), None);
-                            e.insert_storage_value();
-                        }
-                        trie::trie_structure::Entry::Occupied(
-                            trie::trie_structure::NodeAccess::Storage(_),
-                        ) => {
-                            // Duplicate entry.
-                            panic!() // TODO: don't panic?
-                        }
-                    }
-                }
-
-                // Calculate the Merkle values of the nodes.
-                for node_index in trie_structure
-                    .iter_ordered()
-                    .collect::<Vec<_>>()
-                    .into_iter()
-                    .rev()
-                {
-                    let mut node_access = trie_structure.node_by_index(node_index).unwrap();
-
-                    let children = core::array::from_fn::<_, 16, _>(|n| {
-                        node_access
-                            .child(trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap())
-                            .map(|mut child| child.user_data().1.as_ref().unwrap().clone())
-                    });
-
-                    let is_root_node = node_access.is_root_node();
-                    let partial_key = node_access.partial_key().collect::<Vec<_>>().into_iter();
-
-                    // We have to hash the storage value ahead of time if necessary due to borrow
-                    // checking difficulties.
-                    let storage_value_hashed =
-                        match (node_access.user_data().0.as_ref(), state_version) {
-                            (Some(v), 1) => {
-                                if v.len() >= 33 {
-                                    Some(blake2_rfc::blake2b::blake2b(32, &[], v))
-                                } else {
-                                    None
-                                }
-                            }
-                            _ => None,
-                        };
-                    let storage_value = match (
-                        node_access.user_data().0.as_ref(),
-                        storage_value_hashed.as_ref(),
-                    ) {
-                        (_, Some(storage_value_hashed)) => trie::trie_node::StorageValue::Hashed(
-                            <&[u8; 32]>::try_from(storage_value_hashed.as_bytes()).unwrap(),
-                        ),
-                        (Some(v), None) => trie::trie_node::StorageValue::Unhashed(&v[..]),
-                        (None, _) => trie::trie_node::StorageValue::None,
-                    };
-
-                    let merkle_value = trie::trie_node::calculate_merkle_value(
-                        trie::trie_node::Decoded {
-                            children,
-                            partial_key,
-                            storage_value,
-                        },
-                        trie::HashFunction::Blake2,
-                        is_root_node,
-                    )
-                    .unwrap();
-
-                    node_access.into_user_data().1 = Some(merkle_value);
-                }
-
-                trie_structure
-            };
-
-            // Build the iterator of trie nodes.
-            let genesis_storage_full_trie = trie_structure
-                .iter_unordered()
-                .collect::<Vec<_>>()
-                .into_iter()
-                .map(|node_index| {
-                    let (storage_value, Some(merkle_value)) = &trie_structure[node_index] else {
-                        unreachable!()
-                    };
-                    // Cloning to solve borrow checker restriction. // TODO: optimize?
-                    let storage_value = if let Some(storage_value) = storage_value {
-                        // TODO: child tries support?
-                        full_sqlite::InsertTrieNodeStorageValue::Value {
-                            value: Cow::Owned(storage_value.to_vec()),
-                            references_merkle_value: false,
-                        }
-                    } else {
-                        full_sqlite::InsertTrieNodeStorageValue::NoValue
-                    };
-                    let merkle_value = merkle_value.as_ref().to_owned();
-                    let mut node_access = trie_structure.node_by_index(node_index).unwrap();
-
-                    full_sqlite::InsertTrieNode {
-                        storage_value,
-                        merkle_value: Cow::Owned(merkle_value),
-                        children_merkle_values: array::from_fn::<_, 16, _>(|n| {
-                            let child_index =
-                                trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap();
-                            node_access.child(child_index).map(|mut child| {
-                                Cow::Owned(child.user_data().1.as_ref().unwrap().as_ref().to_vec())
-                            })
-                        }),
-                        partial_key_nibbles: Cow::Owned(
-                            node_access.partial_key().map(u8::from).collect::<Vec<_>>(),
-                        ),
-                    }
-                });
+            let (genesis_storage_full_trie, _) =
+                storage_trie_nodes(genesis_storage.iter(), state_version);
 
             // The finalized block is the genesis block. As such, it has an empty body and
             // no justification.
@@ -994,7 +1091,7 @@ async fn open_database(
                     genesis_chain_information,
                     iter::empty(),
                     None,
-                    genesis_storage_full_trie,
+                    genesis_storage_full_trie.into_iter(),
                     state_version,
                 )
                 .unwrap();
@@ -1002,3 +1099,151 @@ async fn open_database(
         }
     }
 }
+
+/// Builds the list of all the nodes of the trie containing the given storage entries, in the
+/// format expected by the database, alongside with the Merkle value of the root of the trie.
+///
+/// The list of storage entries only contains trie nodes that have a storage value attached to
+/// them, while the database needs to know all trie nodes (including branch nodes). The good news
+/// is that we can determine the latter from the former, which this function does.
+///
+/// # Panic
+///
+/// Panics if the same key is found multiple times.
+///
+// TODO: child tries support?
+// TODO: poorly optimized
+fn storage_trie_nodes(
+    entries: impl Iterator<Item = (impl AsRef<[u8]>, impl AsRef<[u8]>)>,
+    state_version: u8,
+) -> (Vec<full_sqlite::InsertTrieNode<'static>>, [u8; 32]) {
+    let mut trie_structure = {
+        let mut trie_structure = trie::trie_structure::TrieStructure::new();
+        for (key, value) in entries {
+            match trie_structure.node(trie::bytes_to_nibbles(key.as_ref().iter().copied())) {
+                trie::trie_structure::Entry::Vacant(e) => {
+                    e.insert_storage_value().insert(
+                        (Some(value), None::<trie::trie_node::MerkleValueOutput>),
+                        (None, None),
+                    );
+                }
+                trie::trie_structure::Entry::Occupied(
+                    trie::trie_structure::NodeAccess::Branch(mut e),
+                ) => {
+                    *e.user_data() = (Some(value), None);
+                    e.insert_storage_value();
+                }
+                trie::trie_structure::Entry::Occupied(
+                    trie::trie_structure::NodeAccess::Storage(_),
+                ) => {
+                    // Duplicate entry.
+                    panic!() // TODO: don't panic?
+                }
+            }
+        }
+
+        // Calculate the Merkle values of the nodes.
+        for node_index in trie_structure
+            .iter_ordered()
+            .collect::<Vec<_>>()
+            .into_iter()
+            .rev()
+        {
+            let mut node_access = trie_structure.node_by_index(node_index).unwrap();
+
+            let children = core::array::from_fn::<_, 16, _>(|n| {
+                node_access
+                    .child(trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap())
+                    .map(|mut child| child.user_data().1.as_ref().unwrap().clone())
+            });
+
+            let is_root_node = node_access.is_root_node();
+            let partial_key = node_access.partial_key().collect::<Vec<_>>().into_iter();
+
+            // We have to hash the storage value ahead of time if necessary due to borrow
+            // checking difficulties.
+            let storage_value_hashed = match (node_access.user_data().0.as_ref(), state_version) {
+                (Some(v), 1) => {
+                    if v.as_ref().len() >= 33 {
+                        Some(blake2_rfc::blake2b::blake2b(32, &[], v.as_ref()))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            };
+            let storage_value = match (
+                node_access.user_data().0.as_ref(),
+                storage_value_hashed.as_ref(),
+            ) {
+                (_, Some(storage_value_hashed)) => trie::trie_node::StorageValue::Hashed(
+                    <&[u8; 32]>::try_from(storage_value_hashed.as_bytes()).unwrap(),
+                ),
+                (Some(v), None) => trie::trie_node::StorageValue::Unhashed(v.as_ref()),
+                (None, _) => trie::trie_node::StorageValue::None,
+            };
+
+            let merkle_value = trie::trie_node::calculate_merkle_value(
+                trie::trie_node::Decoded {
+                    children,
+                    partial_key,
+                    storage_value,
+                },
+                trie::HashFunction::Blake2,
+                is_root_node,
+            )
+            .unwrap();
+
+            node_access.into_user_data().1 = Some(merkle_value);
+        }
+
+        trie_structure
+    };
+
+    let root_merkle_value = trie_structure
+        .root_user_data()
+        .map(|(_, merkle_value)| {
+            *<&[u8; 32]>::try_from(merkle_value.as_ref().unwrap().as_ref()).unwrap()
+        })
+        .unwrap_or(trie::EMPTY_BLAKE2_TRIE_MERKLE_VALUE);
+
+    // Build the list of trie nodes.
+    let nodes = trie_structure
+        .iter_unordered()
+        .collect::<Vec<_>>()
+        .into_iter()
+        .map(|node_index| {
+            let (storage_value, Some(merkle_value)) = &trie_structure[node_index] else {
+                unreachable!()
+            };
+            // Cloning to solve borrow checker restriction. // TODO: optimize?
+            let storage_value = if let Some(storage_value) = storage_value {
+                // TODO: child tries support?
+                full_sqlite::InsertTrieNodeStorageValue::Value {
+                    value: Cow::Owned(storage_value.as_ref().to_vec()),
+                    references_merkle_value: false,
+                }
+            } else {
+                full_sqlite::InsertTrieNodeStorageValue::NoValue
+            };
+            let merkle_value = merkle_value.as_ref().to_owned();
+            let mut node_access = trie_structure.node_by_index(node_index).unwrap();
+
+            full_sqlite::InsertTrieNode {
+                storage_value,
+                merkle_value: Cow::Owned(merkle_value),
+                children_merkle_values: array::from_fn::<_, 16, _>(|n| {
+                    let child_index = trie::Nibble::try_from(u8::try_from(n).unwrap()).unwrap();
+                    node_access.child(child_index).map(|mut child| {
+                        Cow::Owned(child.user_data().1.as_ref().unwrap().as_ref().to_vec())
+                    })
+                }),
+                partial_key_nibbles: Cow::Owned(
+                    node_access.partial_key().map(u8::from).collect::<Vec<_>>(),
+                ),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    (nodes, root_merkle_value)
+}
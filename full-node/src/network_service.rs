@@ -21,18 +21,21 @@
 //! Importantly, its design is oriented towards the particular use case of the full node.
 //!
 //! The [`NetworkService`] spawns one background task (using the [`Config::tasks_executor`]) for
-//! each active TCP socket, plus one for each TCP listening socket. Messages are exchanged between
-//! the service and these background tasks.
+//! each active socket, plus one for each listening socket. Messages are exchanged between the
+//! service and these background tasks.
+//!
+//! In addition to TCP, the service supports `/memory/<port>` addresses, both for listening and
+//! dialing, which makes it possible to run multiple nodes within the same process.
 
 // TODO: doc
 // TODO: re-review this once finished
 
-use crate::{database_thread, jaeger_service, LogCallback, LogLevel};
+use crate::{database_thread, jaeger_service, prometheus_service, Clock, LogCallback, LogLevel};
 
 use core::{cmp, future::Future, mem, pin::Pin, task::Poll, time::Duration};
 use futures_channel::oneshot;
 use futures_lite::FutureExt as _;
-use hashbrown::HashMap;
+use hashbrown::{HashMap, HashSet};
 use smol::{
     channel, future,
     lock::Mutex,
@@ -44,21 +47,26 @@ use smoldot::{
     header,
     informant::HashDisplay,
     libp2p::{
-        connection,
+        connection, memory,
         multiaddr::{self, Multiaddr, ProtocolRef},
         peer_id::{self, PeerId},
     },
-    network::{basic_peering_strategy, codec, service},
+    network::{basic_peering_strategy, codec, kademlia, observed_addresses, service},
+    trie,
 };
 use std::{
-    io,
+    collections::VecDeque,
+    fs, io, iter,
     net::{IpAddr, SocketAddr},
+    path::{Path, PathBuf},
     sync::Arc,
     time::Instant,
+    vec,
 };
 
 pub use smoldot::network::service::ChainId;
 
+mod address_book;
 mod tasks;
 
 /// Configuration for a [`NetworkService`].
@@ -69,6 +77,9 @@ pub struct Config {
     /// Function called in order to notify of something.
     pub log_callback: Arc<dyn LogCallback + Send + Sync>,
 
+    /// Source of time of the node.
+    pub clock: Clock,
+
     /// Number of event receivers returned by [`NetworkService::new`].
     pub num_events_receivers: usize,
 
@@ -88,6 +99,9 @@ pub struct Config {
 
     /// Service to use to report traces.
     pub jaeger_service: Arc<jaeger_service::JaegerService>,
+
+    /// Service to use to report metrics that aren't specific to a chain.
+    pub prometheus_service: Arc<prometheus_service::PrometheusService>,
 }
 
 /// Configuration for one chain.
@@ -99,9 +113,27 @@ pub struct ChainConfig {
     /// network.
     pub bootstrap_nodes: Vec<(PeerId, Multiaddr)>,
 
+    /// List of node identities and addresses that the node always maintains a gossip link with.
+    /// These nodes are redialed after a backoff delay if the connection fails, and are never
+    /// banned.
+    pub reserved_nodes: Vec<(PeerId, Multiaddr)>,
+
+    /// If `true`, gossip links are only ever opened with nodes in
+    /// [`ChainConfig::reserved_nodes`] or added with [`NetworkService::add_reserved_peer`]. All
+    /// other inbound and outbound gossip links are refused.
+    pub reserved_only: bool,
+
+    /// Path to the file where to load the known peers of the chain and their addresses from
+    /// when starting, and where to periodically save them. If `None`, the known peers are
+    /// never saved.
+    pub address_book_path: Option<PathBuf>,
+
     /// Database to use to read blocks from when answering requests.
     pub database: Arc<database_thread::DatabaseThread>,
 
+    /// Metrics of the chain to keep up to date.
+    pub metrics: Arc<prometheus_service::ChainMetrics>,
+
     /// Hash of the genesis block of the chain. Sent to other nodes in order to determine whether
     /// the chains match.
     pub genesis_block_hash: [u8; 32],
@@ -119,6 +151,40 @@ pub struct ChainConfig {
     /// Must be `Some` if and only if the chain uses the GrandPa networking protocol. Contains the
     /// number of the finalized block at the time of the initialization.
     pub grandpa_protocol_finalized_block_height: Option<u64>,
+
+    /// `true` if the local node is a collator of a parachain whose relay chain is this chain.
+    /// Enables the collation protocols. See [`NetworkService::announce_collation`].
+    pub allow_collation_protocols: bool,
+}
+
+/// Collation of a parachain block. See [`NetworkService::announce_collation`].
+#[derive(Debug, Clone)]
+pub struct Collation {
+    /// Identifier of the parachain the collation belongs to.
+    pub para_id: u32,
+    /// Sr25519 public key of the collator.
+    pub collator_id: [u8; 32],
+    /// Signature made using [`Collation::collator_id`] of the payload returned by
+    /// [`codec::declare_signature_payload`] for the local peer ID.
+    pub declare_signature: [u8; 64],
+    /// Hash of the relay chain block the collation is built upon.
+    pub relay_parent: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::persisted_validation_data_hash`].
+    pub persisted_validation_data_hash: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::pov_hash`].
+    pub pov_hash: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::erasure_root`].
+    pub erasure_root: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::signature`].
+    pub signature: [u8; 64],
+    /// See [`codec::CandidateReceiptRef::para_head`].
+    pub para_head: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::validation_code_hash`].
+    pub validation_code_hash: [u8; 32],
+    /// See [`codec::CandidateReceiptRef::commitments_hash`].
+    pub commitments_hash: [u8; 32],
+    /// SCALE-encoded Proof-of-Validity of the parachain block.
+    pub scale_encoded_pov: Vec<u8>,
 }
 
 /// Event generated by the events reporters returned by [`NetworkService::new`].
@@ -140,6 +206,11 @@ pub enum Event {
         scale_encoded_header: Vec<u8>,
         is_best: bool,
     },
+    GrandpaNeighborPacket {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        finalized_block_height: u64,
+    },
 }
 
 pub struct NetworkService {
@@ -169,13 +240,15 @@ enum ToBackground {
         connection_now_dead: bool,
     },
     IncomingConnection {
-        socket: TcpStream,
+        socket: futures_util::future::Either<TcpStream, memory::Stream>,
         multiaddr: Multiaddr,
         when_accepted: Instant,
     },
     StartKademliaDiscoveries {
         when_done: oneshot::Sender<()>,
     },
+    RedialReservedPeers,
+    SaveAddressBooks,
     ForegroundAnnounceBlock {
         target: PeerId,
         chain_id: ChainId,
@@ -183,6 +256,10 @@ enum ToBackground {
         is_best: bool,
         result_tx: oneshot::Sender<Result<(), service::QueueNotificationError>>,
     },
+    ForegroundAnnounceCollation {
+        chain_id: ChainId,
+        collation: Collation,
+    },
     ForegroundSetLocalBestBlock {
         chain_id: ChainId,
         best_hash: [u8; 32],
@@ -194,6 +271,77 @@ enum ToBackground {
         config: codec::BlocksRequestConfig,
         result_tx: oneshot::Sender<Result<Vec<codec::BlockData>, BlocksRequestError>>,
     },
+    ForegroundWarpSyncRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        begin_hash: [u8; 32],
+        result_tx:
+            oneshot::Sender<Result<service::EncodedGrandpaWarpSyncResponse, WarpSyncRequestError>>,
+    },
+    ForegroundStorageProofRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        config: codec::StorageProofRequestConfig<vec::IntoIter<Vec<u8>>>,
+        result_tx: oneshot::Sender<Result<service::EncodedMerkleProof, StorageProofRequestError>>,
+    },
+    ForegroundCallProofRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        config: codec::CallProofRequestConfig<'static, vec::IntoIter<Vec<u8>>>,
+        result_tx: oneshot::Sender<Result<service::EncodedMerkleProof, CallProofRequestError>>,
+    },
+    ForegroundStateRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        block_hash: [u8; 32],
+        child_trie: Option<Vec<u8>>,
+        start_key: Vec<u8>,
+        result_tx: oneshot::Sender<Result<service::EncodedStateResponse, StateRequestError>>,
+    },
+    ForegroundKademliaGetValueRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        key: Vec<u8>,
+        result_tx: oneshot::Sender<Result<codec::GetValueResponse, KademliaGetValueRequestError>>,
+    },
+    ForegroundKademliaPutValueRequest {
+        target: PeerId,
+        chain_id: ChainId,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        result_tx: oneshot::Sender<Result<(), KademliaPutValueRequestError>>,
+    },
+    ForegroundKademliaClosestPeers {
+        chain_id: ChainId,
+        key: Vec<u8>,
+        result_tx: oneshot::Sender<Vec<PeerId>>,
+    },
+    ForegroundConnectForRequests {
+        peer_id: PeerId,
+        result_tx: oneshot::Sender<bool>,
+    },
+    ForegroundDiscoveredAddresses {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+    },
+    ForegroundAddReservedPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        address: Multiaddr,
+    },
+    ForegroundRemoveReservedPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+    },
+    ForegroundReportPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        change: basic_peering_strategy::ReputationChange,
+    },
+    ForegroundGetLocalListenAddresses {
+        result_tx: oneshot::Sender<Vec<Multiaddr>>,
+    },
     ForegroundGetNumConnections {
         result_tx: oneshot::Sender<usize>,
     },
@@ -205,11 +353,22 @@ enum ToBackground {
         result_tx: oneshot::Sender<usize>,
     },
     ForegroundShutdown,
+    DecayReputations,
+    StateRequestInResponse {
+        substream_id: service::SubstreamId,
+        response: Option<Vec<StateResponseGroup>>,
+    },
 }
 struct Inner {
     /// Value provided through [`Config::identify_agent_version`].
     identify_agent_version: String,
 
+    /// Value provided through [`Config::listen_addresses`].
+    listen_addresses: Vec<Multiaddr>,
+
+    /// Addresses of the local node as reported by remotes in their identify responses.
+    observed_addresses: observed_addresses::ObservedAddresses,
+
     /// Sending events through the public API.
     ///
     /// Contains either senders, or a `Future` that is currently sending an event and will yield
@@ -228,12 +387,24 @@ struct Inner {
     /// Service to use to report traces.
     jaeger_service: Arc<jaeger_service::JaegerService>,
 
+    /// See [`Config::prometheus_service`].
+    prometheus_service: Arc<prometheus_service::PrometheusService>,
+
     /// Data structure holding the entire state of the networking.
     network: service::ChainNetwork<Chain, Instant>,
 
     /// Data structure holding the addresses and assigned slots.
     peering_strategy: basic_peering_strategy::BasicPeeringStrategy<ChainId, Instant>,
 
+    /// For each reserved peer whose gossip link has recently failed, the instant when to try
+    /// again and the delay to use in case of another failure. Entries are removed when a gossip
+    /// link is successfully opened.
+    reserved_peers_redial: HashMap<PeerId, ReservedPeerRedial, fnv::FnvBuildHasher>,
+
+    /// Peers that a connection must be opened with in order to send requests to them, as
+    /// requested through [`NetworkService::connect_for_requests`].
+    request_dials: HashMap<PeerId, RequestDial, fnv::FnvBuildHasher>,
+
     /// Current number of outgoing connection attempts.
     ///
     /// This counter is used to limit the number of simultaneous connection attempts, as some
@@ -246,6 +417,9 @@ struct Inner {
     /// See [`Config::log_callback`].
     log_callback: Arc<dyn LogCallback + Send + Sync>,
 
+    /// See [`Config::clock`].
+    clock: Clock,
+
     active_connections: HashMap<
         service::ConnectionId,
         channel::Sender<service::CoordinatorToConnection>,
@@ -267,8 +441,78 @@ struct Inner {
         fnv::FnvBuildHasher,
     >,
 
+    /// List of GrandPa warp sync requests that have been started but not finished yet.
+    warp_sync_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<service::EncodedGrandpaWarpSyncResponse, WarpSyncRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+
+    /// List of storage proof requests that have been started but not finished yet.
+    storage_proof_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<service::EncodedMerkleProof, StorageProofRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+
+    /// List of call proof requests that have been started but not finished yet.
+    call_proof_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<service::EncodedMerkleProof, CallProofRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+
+    /// List of state requests that have been started but not finished yet.
+    state_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<service::EncodedStateResponse, StateRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+
+    /// List of inbound state requests whose response is being built in a separate task. Entries
+    /// are removed if the request is cancelled by the remote.
+    state_requests_in: HashSet<service::SubstreamId, fnv::FnvBuildHasher>,
+
+    /// List of identify requests that have been started but not finished yet, and the peer they
+    /// target.
+    identify_requests: HashMap<service::SubstreamId, PeerId, fnv::FnvBuildHasher>,
+
     /// List of Kademlia discovery operations that have been started but not finished yet.
     kademlia_find_nodes_requests: HashMap<service::SubstreamId, ChainId, fnv::FnvBuildHasher>,
+
+    /// List of Kademlia `GET_VALUE` requests that have been started but not finished yet.
+    kademlia_get_value_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<codec::GetValueResponse, KademliaGetValueRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+
+    /// List of Kademlia `PUT_VALUE` requests that have been started but not finished yet.
+    kademlia_put_value_requests: HashMap<
+        service::SubstreamId,
+        oneshot::Sender<Result<(), KademliaPutValueRequestError>>,
+        fnv::FnvBuildHasher,
+    >,
+}
+
+/// See [`Inner::reserved_peers_redial`].
+struct ReservedPeerRedial {
+    /// When to insert the peer back in the list of desired gossip links. `None` if the peer has
+    /// already been inserted back.
+    when: Option<Instant>,
+
+    /// Delay to wait in case the next attempt fails as well.
+    next_backoff: Duration,
+}
+
+/// See [`Inner::request_dials`].
+struct RequestDial {
+    /// `true` if a connection attempt has been started.
+    dialing: bool,
+
+    /// Senders to notify with `true` once the connection is established, or `false` if it
+    /// fails.
+    waiters: Vec<oneshot::Sender<bool>>,
 }
 
 /// Extra information of a chain.
@@ -276,10 +520,53 @@ struct Chain {
     /// Name of the chain to use for logging purposes.
     log_name: String,
 
+    /// See [`ChainConfig::reserved_only`].
+    reserved_only: bool,
+
+    /// See [`ChainConfig::address_book_path`].
+    address_book_path: Option<PathBuf>,
+
+    /// `true` if all the un-assigned peers of this chain were banned because of their reputation
+    /// the last time a slot assignment was attempted. Used in order to avoid printing the same
+    /// log message repeatedly.
+    all_peers_reputation_banned: bool,
+
     /// How to access data to answer requests from the remotes.
     database: Arc<database_thread::DatabaseThread>,
+
+    /// See [`ChainConfig::metrics`].
+    metrics: Arc<prometheus_service::ChainMetrics>,
+
+    /// K-buckets of the Kademlia DHT of this chain. Used in order to answer inbound Kademlia
+    /// requests. The addresses of the peers are found in [`Inner::peering_strategy`].
+    kbuckets: kademlia::kbuckets::KBuckets<PeerId, (), Instant, 20>,
+
+    /// Records of the Kademlia DHT of this chain that remotes have asked the local node to store.
+    kademlia_records: kademlia::record_store::RecordStore<Instant>,
+
+    /// `Some` if and only if [`ChainConfig::allow_collation_protocols`] was `true`.
+    collator: Option<CollatorState>,
+}
+
+/// See [`Chain::collator`].
+struct CollatorState {
+    /// Peers that are gossip-connected and that report being authorities, in other words
+    /// validators. Collation substreams are opened with these peers once a collation is
+    /// available.
+    authorities: HashSet<PeerId, fnv::FnvBuildHasher>,
+
+    /// Peers with which a collation substream is open, and to which the declare message has
+    /// been sent.
+    connected: HashSet<PeerId, fnv::FnvBuildHasher>,
+
+    /// Most recent collations passed to [`NetworkService::announce_collation`], from the oldest
+    /// to the newest.
+    collations: VecDeque<Collation>,
 }
 
+/// Maximum number of collations kept in [`CollatorState::collations`].
+const MAX_KEPT_COLLATIONS: usize = 4;
+
 impl NetworkService {
     /// Initializes the network service with the given configuration.
     pub async fn new(
@@ -313,6 +600,10 @@ impl NetworkService {
         let mut chain_names =
             hashbrown::HashMap::with_capacity_and_hasher(config.chains.len(), Default::default());
 
+        let local_peer_id =
+            peer_id::PublicKey::Ed25519(*config.noise_key.libp2p_public_ed25519_key())
+                .into_peer_id();
+
         for chain in config.chains {
             let chain_id = network
                 .add_chain(service::ChainConfig {
@@ -331,9 +622,43 @@ impl NetworkService {
                         },
                     ),
                     allow_inbound_block_requests: true,
+                    allow_inbound_state_requests: true,
+                    allow_inbound_kademlia_requests: true,
+                    allow_collation_protocols: chain.allow_collation_protocols,
                     user_data: Chain {
                         log_name: chain.log_name.clone(),
+                        reserved_only: chain.reserved_only,
+                        address_book_path: chain.address_book_path.clone(),
+                        all_peers_reputation_banned: false,
                         database: chain.database,
+                        metrics: chain.metrics,
+                        kbuckets: kademlia::kbuckets::KBuckets::new(
+                            local_peer_id.clone(),
+                            Duration::from_secs(20),
+                        ),
+                        kademlia_records: kademlia::record_store::RecordStore::new(
+                            kademlia::record_store::Config {
+                                max_records: 1024,
+                                // Keys are normally hashes, but some margin is left.
+                                max_key_size: 128,
+                                max_record_size: 16 * 1024,
+                                // Same value as the default of the libp2p Kademlia
+                                // implementations.
+                                record_ttl: Duration::from_secs(36 * 60 * 60),
+                            },
+                        ),
+                        collator: if chain.allow_collation_protocols {
+                            Some(CollatorState {
+                                authorities: HashSet::with_capacity_and_hasher(
+                                    0,
+                                    Default::default(),
+                                ),
+                                connected: HashSet::with_capacity_and_hasher(0, Default::default()),
+                                collations: VecDeque::with_capacity(MAX_KEPT_COLLATIONS),
+                            })
+                        } else {
+                            None
+                        },
                     },
                 })
                 .unwrap(); // TODO: don't unwrap?
@@ -343,6 +668,73 @@ impl NetworkService {
                 // in `basic_peering_strategy`.
                 peering_strategy.insert_chain_peer(chain_id, peer_id.clone(), usize::max_value());
                 peering_strategy.insert_address(&peer_id, addr.into_vec(), usize::max_value());
+                let _ = network[chain_id].kbuckets.entry(&peer_id).or_insert(
+                    (),
+                    &config.clock.now(),
+                    kademlia::kbuckets::PeerState::Disconnected,
+                );
+            }
+
+            // Load the peers that were known the last time the node was running.
+            if let Some(address_book_path) = &chain.address_book_path {
+                match smol::fs::read(address_book_path).await {
+                    Ok(content) => match address_book::decode(&content) {
+                        Ok(peers) => {
+                            config.log_callback.log(
+                                LogLevel::Debug,
+                                format!(
+                                    "address-book-loaded; chain={}; num_peers={}",
+                                    chain.log_name,
+                                    peers.len()
+                                ),
+                            );
+
+                            for (peer_id, addresses, protocols) in peers {
+                                peering_strategy.insert_chain_peer(
+                                    chain_id,
+                                    peer_id.clone(),
+                                    100, // TODO: constant
+                                );
+                                peering_strategy.set_peer_protocols(
+                                    &peer_id,
+                                    protocols.into_iter(),
+                                    64, // TODO: constant
+                                );
+                                for addr in addresses {
+                                    peering_strategy.insert_address(
+                                        &peer_id,
+                                        addr.into_vec(),
+                                        10, // TODO: constant
+                                    );
+                                }
+                            }
+                        }
+                        Err(error) => {
+                            config.log_callback.log(
+                                LogLevel::Warn,
+                                format!(
+                                    "address-book-load-error; chain={}; error={}",
+                                    chain.log_name, error
+                                ),
+                            );
+                        }
+                    },
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => {}
+                    Err(error) => {
+                        config.log_callback.log(
+                            LogLevel::Warn,
+                            format!(
+                                "address-book-load-error; chain={}; error={}",
+                                chain.log_name, error
+                            ),
+                        );
+                    }
+                }
+            }
+
+            for (peer_id, addr) in chain.reserved_nodes {
+                peering_strategy.insert_reserved_chain_peer(chain_id, peer_id.clone());
+                peering_strategy.insert_address(&peer_id, addr.into_vec(), usize::max_value());
             }
 
             chain_names.insert(chain_id, chain.log_name);
@@ -351,21 +743,30 @@ impl NetworkService {
         let (to_background_tx, to_background_rx) = channel::bounded(64);
         let foreground_shutdown = event_listener::Event::new();
 
-        let local_peer_id =
-            peer_id::PublicKey::Ed25519(*config.noise_key.libp2p_public_ed25519_key())
-                .into_peer_id();
-
         // Initialize the inner network service.
         let mut inner = Inner {
             local_peer_id: local_peer_id.clone(),
             identify_agent_version: config.identify_agent_version,
+            listen_addresses: config.listen_addresses.clone(),
+            observed_addresses: observed_addresses::ObservedAddresses::new(
+                observed_addresses::Config {
+                    max_reporters: 64,         // TODO: constant
+                    confirmation_threshold: 3, // TODO: constant
+                },
+            ),
             event_senders: either::Left(event_senders),
             num_pending_out_attempts: 0,
+            reserved_peers_redial: hashbrown::HashMap::with_capacity_and_hasher(
+                0,
+                Default::default(),
+            ),
+            request_dials: hashbrown::HashMap::with_capacity_and_hasher(4, Default::default()),
             to_background_rx,
             to_background_tx: to_background_tx.clone(),
             process_network_service_events: true,
             tasks_executor: config.tasks_executor,
             log_callback: config.log_callback.clone(),
+            clock: config.clock.clone(),
             network,
             noise_key: config.noise_key,
             peering_strategy,
@@ -377,18 +778,39 @@ impl NetworkService {
                 50, // TODO: ?
                 Default::default(),
             ),
+            warp_sync_requests: hashbrown::HashMap::with_capacity_and_hasher(4, Default::default()),
+            storage_proof_requests: hashbrown::HashMap::with_capacity_and_hasher(
+                4,
+                Default::default(),
+            ),
+            call_proof_requests: hashbrown::HashMap::with_capacity_and_hasher(
+                4,
+                Default::default(),
+            ),
+            state_requests: hashbrown::HashMap::with_capacity_and_hasher(4, Default::default()),
+            state_requests_in: hashbrown::HashSet::with_capacity_and_hasher(4, Default::default()),
+            identify_requests: hashbrown::HashMap::with_capacity_and_hasher(8, Default::default()),
             kademlia_find_nodes_requests: hashbrown::HashMap::with_capacity_and_hasher(
                 4,
                 Default::default(),
             ),
+            kademlia_get_value_requests: hashbrown::HashMap::with_capacity_and_hasher(
+                4,
+                Default::default(),
+            ),
+            kademlia_put_value_requests: hashbrown::HashMap::with_capacity_and_hasher(
+                4,
+                Default::default(),
+            ),
             jaeger_service: config.jaeger_service.clone(),
+            prometheus_service: config.prometheus_service,
         };
 
         // For each listening address in the configuration, create a background task dedicated to
         // listening on that address.
         for listen_address in config.listen_addresses {
             // Try to parse the requested address and create the corresponding listening socket.
-            let tcp_listener: smol::net::TcpListener = {
+            let listener: either::Either<smol::net::TcpListener, memory::Listener> = {
                 let addr = {
                     let mut iter = listen_address.iter();
                     let proto1 = iter.next();
@@ -396,25 +818,36 @@ impl NetworkService {
                     let proto3 = iter.next();
                     match (proto1, proto2, proto3) {
                         (Some(ProtocolRef::Ip4(ip)), Some(ProtocolRef::Tcp(port)), None) => {
-                            Some(SocketAddr::from((ip, port)))
+                            Some(either::Left(SocketAddr::from((ip, port))))
                         }
                         (Some(ProtocolRef::Ip6(ip)), Some(ProtocolRef::Tcp(port)), None) => {
-                            Some(SocketAddr::from((ip, port)))
+                            Some(either::Left(SocketAddr::from((ip, port))))
                         }
+                        (Some(ProtocolRef::Memory(port)), None, None) => Some(either::Right(port)),
                         _ => None,
                     }
                 };
 
-                if let Some(addr) = addr {
-                    match smol::net::TcpListener::bind(addr).await {
-                        Ok(l) => l,
+                match addr {
+                    Some(either::Left(addr)) => match smol::net::TcpListener::bind(addr).await {
+                        Ok(l) => either::Left(l),
                         Err(err) => {
                             return Err(InitError::ListenerIo(listen_address, err));
                         }
+                    },
+                    Some(either::Right(port)) => match memory::listen(port) {
+                        Ok(l) => either::Right(l),
+                        Err(err) => {
+                            return Err(InitError::ListenerIo(
+                                listen_address,
+                                io::Error::new(io::ErrorKind::AddrInUse, err.to_string()),
+                            ));
+                        }
+                    },
+                    None => {
+                        // TODO: support WebSocket server
+                        return Err(InitError::BadListenMultiaddr(listen_address));
                     }
-                } else {
-                    // TODO: support WebSocket server
-                    return Err(InitError::BadListenMultiaddr(listen_address));
                 }
             };
 
@@ -422,6 +855,7 @@ impl NetworkService {
             (inner.tasks_executor)(Box::pin({
                 let to_background_tx = to_background_tx.clone();
                 let log_callback = config.log_callback.clone();
+                let clock = config.clock.clone();
                 let mut on_foreground_shutdown = foreground_shutdown.listen();
                 async move {
                     loop {
@@ -430,18 +864,49 @@ impl NetworkService {
                                 (&mut on_foreground_shutdown).await;
                                 None
                             },
-                            async { Some(tcp_listener.accept().await) },
+                            async {
+                                Some(match &listener {
+                                    either::Left(tcp_listener) => {
+                                        either::Left(tcp_listener.accept().await)
+                                    }
+                                    either::Right(memory_listener) => {
+                                        either::Right(memory_listener.accept().await)
+                                    }
+                                })
+                            },
                         )
                         .await
                         else {
                             break;
                         };
 
-                        let when_accepted = Instant::now();
-
-                        let (socket, addr) = match accept_result {
-                            Ok(v) => v,
-                            Err(error) => {
+                        let when_accepted = clock.now();
+
+                        let (socket, multiaddr) = match accept_result {
+                            either::Left(Ok((socket, addr))) => {
+                                // The Nagle algorithm, implemented in the kernel, consists in
+                                // buffering the data to be sent out and waiting a bit before
+                                // actually sending it out, in order to potentially merge multiple
+                                // writes in a row into one packet. In the implementation below,
+                                // it is guaranteed that the buffer in `WithBuffers` is filled
+                                // with as much data as possible before the operating system gets
+                                // involved. As such, we disable the Nagle algorithm, in order to
+                                // avoid adding an artificial delay to all sends.
+                                let _ = socket.set_nodelay(true);
+
+                                let multiaddr = [
+                                    match addr.ip() {
+                                        IpAddr::V4(ip) => ProtocolRef::Ip4(ip.octets()),
+                                        IpAddr::V6(ip) => ProtocolRef::Ip6(ip.octets()),
+                                    },
+                                    ProtocolRef::Tcp(addr.port()),
+                                ]
+                                .into_iter()
+                                .collect::<Multiaddr>();
+
+                                (futures_util::future::Either::Left(socket), multiaddr)
+                            }
+                            either::Left(Err(error)) => {
                                 // Errors here can happen if the accept failed, for example if no
                                 // file descriptor is available.
                                 // A wait is added in order to avoid having a busy-loop failing to
@@ -450,30 +915,17 @@ impl NetworkService {
                                     LogLevel::Warn,
                                     format!("tcp-accept-error; error={}", error),
                                 );
-                                smol::Timer::after(Duration::from_secs(2)).await;
+                                clock.sleep(Duration::from_secs(2)).await;
                                 continue;
                             }
+                            either::Right(socket) => {
+                                let multiaddr =
+                                    iter::once(ProtocolRef::Memory(socket.remote_port()))
+                                        .collect::<Multiaddr>();
+                                (futures_util::future::Either::Right(socket), multiaddr)
+                            }
                         };
 
-                        // The Nagle algorithm, implemented in the kernel, consists in buffering the
-                        // data to be sent out and waiting a bit before actually sending it out, in
-                        // order to potentially merge multiple writes in a row into one packet. In
-                        // the implementation below, it is guaranteed that the buffer in `WithBuffers`
-                        // is filled with as much data as possible before the operating system gets
-                        // involved. As such, we disable the Nagle algorithm, in order to avoid adding
-                        // an artificial delay to all sends.
-                        let _ = socket.set_nodelay(true);
-
-                        let multiaddr = [
-                            match addr.ip() {
-                                IpAddr::V4(ip) => ProtocolRef::Ip4(ip.octets()),
-                                IpAddr::V6(ip) => ProtocolRef::Ip6(ip.octets()),
-                            },
-                            ProtocolRef::Tcp(addr.port()),
-                        ]
-                        .into_iter()
-                        .collect::<Multiaddr>();
-
                         log_callback.log(
                             LogLevel::Debug,
                             format!("incoming-connection; multiaddr={}", multiaddr),
@@ -507,6 +959,7 @@ impl NetworkService {
         // This is done through a separate task due to ease of implementation.
         (inner.tasks_executor)(Box::pin({
             let to_background_tx = to_background_tx.clone();
+            let clock = inner.clock.clone();
             let mut on_foreground_shutdown = foreground_shutdown.listen();
             async move {
                 let mut next_discovery = Duration::from_secs(1);
@@ -514,7 +967,7 @@ impl NetworkService {
                 loop {
                     let still_alive = future::race(
                         async {
-                            smol::Timer::after(next_discovery).await;
+                            clock.sleep(next_discovery).await;
                             true
                         },
                         async {
@@ -537,6 +990,60 @@ impl NetworkService {
             }
         }));
 
+        // Spawn a task that makes the reputations of the peers decay at a periodic interval.
+        (inner.tasks_executor)(Box::pin({
+            let to_background_tx = to_background_tx.clone();
+            let clock = inner.clock.clone();
+            let mut on_foreground_shutdown = foreground_shutdown.listen();
+            async move {
+                loop {
+                    let still_alive = future::race(
+                        async {
+                            clock.sleep(Duration::from_secs(1)).await;
+                            true
+                        },
+                        async {
+                            (&mut on_foreground_shutdown).await;
+                            false
+                        },
+                    )
+                    .await;
+                    if !still_alive {
+                        break;
+                    }
+
+                    let _ = to_background_tx.send(ToBackground::DecayReputations).await;
+                }
+            }
+        }));
+
+        // Spawn a task that periodically saves the address books on disk.
+        (inner.tasks_executor)(Box::pin({
+            let to_background_tx = to_background_tx.clone();
+            let clock = inner.clock.clone();
+            let mut on_foreground_shutdown = foreground_shutdown.listen();
+            async move {
+                loop {
+                    let still_alive = future::race(
+                        async {
+                            clock.sleep(Duration::from_secs(60)).await;
+                            true
+                        },
+                        async {
+                            (&mut on_foreground_shutdown).await;
+                            false
+                        },
+                    )
+                    .await;
+                    if !still_alive {
+                        break;
+                    }
+
+                    let _ = to_background_tx.send(ToBackground::SaveAddressBooks).await;
+                }
+            }
+        }));
+
         // Build the final network service.
         let network_service = Arc::new(NetworkService {
             local_peer_id,
@@ -637,6 +1144,27 @@ impl NetworkService {
             .await;
     }
 
+    /// Makes the given collation available to the validators of the relay chain.
+    ///
+    /// The collation is advertised to all the validators with which a collation substream is
+    /// open, and collation substreams are opened with all the connected validators that the
+    /// local node isn't connected to yet. The validators can then request the collation.
+    ///
+    /// Only the most recent collations are kept.
+    ///
+    /// Has no effect if [`ChainConfig::allow_collation_protocols`] was `false` for this chain.
+    pub async fn announce_collation(&self, chain_id: ChainId, collation: Collation) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundAnnounceCollation {
+                chain_id,
+                collation,
+            })
+            .await;
+    }
+
     pub async fn send_block_announce(
         self: Arc<Self>,
         target: PeerId,
@@ -761,18 +1289,773 @@ impl NetworkService {
             }
         }
 
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(basic_peering_strategy::ReputationChange::GoodResponse),
+                Err(BlocksRequestError::NoConnection) => None,
+                Err(BlocksRequestError::Request(service::BlocksRequestError::Request(err))) => {
+                    request_error_reputation_change(err)
+                }
+                Err(BlocksRequestError::Request(service::BlocksRequestError::Decode(_))) => {
+                    Some(basic_peering_strategy::ReputationChange::BadResponse)
+                }
+            },
+        )
+        .await;
+
         result
     }
-}
 
-impl Drop for NetworkService {
-    fn drop(&mut self) {
-        self.foreground_shutdown.notify(usize::max_value());
-    }
-}
+    /// Sends a GrandPa warp sync request to the given peer, asking for the list of fragments
+    /// that follow the given block.
+    pub async fn grandpa_warp_sync_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        begin_hash: [u8; 32],
+    ) -> Result<service::EncodedGrandpaWarpSyncResponse, WarpSyncRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
 
-/// Error when initializing the network service.
-#[derive(Debug, derive_more::Display)]
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "warp-sync-request-start; peer_id={}; chain={}; start={}",
+                target,
+                chain_name,
+                HashDisplay(&begin_hash)
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundWarpSyncRequest {
+                target: target.clone(),
+                chain_id,
+                begin_hash,
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(response) => {
+                let decoded = response.decode();
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "warp-sync-request-ended; peer_id={}; chain={}; outcome=success; \
+                        num_fragments={}; finished={:?}",
+                        target,
+                        chain_name,
+                        decoded.fragments.len(),
+                        decoded.is_finished
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "warp-sync-request-ended; peer_id={}; chain={}; outcome=failure; error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(basic_peering_strategy::ReputationChange::GoodResponse),
+                Err(WarpSyncRequestError::NoConnection) => None,
+                Err(WarpSyncRequestError::Request(
+                    service::GrandpaWarpSyncRequestError::Request(err),
+                )) => request_error_reputation_change(err),
+                Err(WarpSyncRequestError::Request(
+                    service::GrandpaWarpSyncRequestError::Decode(_),
+                )) => Some(basic_peering_strategy::ReputationChange::BadResponse),
+            },
+        )
+        .await;
+
+        result
+    }
+
+    /// Sends a storage proof request to the given peer.
+    pub async fn storage_proof_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        config: codec::StorageProofRequestConfig<impl Iterator<Item = impl AsRef<[u8]> + Clone>>,
+    ) -> Result<service::EncodedMerkleProof, StorageProofRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "storage-proof-request-start; peer_id={}; chain={}; block={}",
+                target,
+                chain_name,
+                HashDisplay(&config.block_hash)
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundStorageProofRequest {
+                target: target.clone(),
+                chain_id,
+                config: codec::StorageProofRequestConfig {
+                    block_hash: config.block_hash,
+                    keys: config
+                        .keys
+                        .map(|key| key.as_ref().to_vec())
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                },
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(proof) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "storage-proof-request-ended; peer_id={}; chain={}; outcome=success; \
+                        proof_size={}",
+                        target,
+                        chain_name,
+                        proof.decode().len()
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "storage-proof-request-ended; peer_id={}; chain={}; outcome=failure; \
+                        error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(basic_peering_strategy::ReputationChange::GoodResponse),
+                Err(StorageProofRequestError::NoConnection)
+                | Err(StorageProofRequestError::RequestTooLarge)
+                | Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::RemoteCouldntAnswer,
+                )) => None,
+                Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::Request(err),
+                )) => request_error_reputation_change(err),
+                Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::Decode(_),
+                )) => Some(basic_peering_strategy::ReputationChange::BadResponse),
+            },
+        )
+        .await;
+
+        result
+    }
+
+    /// Sends a call proof request to the given peer.
+    pub async fn call_proof_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        config: codec::CallProofRequestConfig<'_, impl Iterator<Item = impl AsRef<[u8]>>>,
+    ) -> Result<service::EncodedMerkleProof, CallProofRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "call-proof-request-start; peer_id={}; chain={}; block={}; function={}",
+                target,
+                chain_name,
+                HashDisplay(&config.block_hash),
+                config.method
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundCallProofRequest {
+                target: target.clone(),
+                chain_id,
+                config: codec::CallProofRequestConfig {
+                    block_hash: config.block_hash,
+                    method: config.method.into_owned().into(),
+                    parameter_vectored: config
+                        .parameter_vectored
+                        .map(|v| v.as_ref().to_vec())
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                },
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(proof) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "call-proof-request-ended; peer_id={}; chain={}; outcome=success; \
+                        proof_size={}",
+                        target,
+                        chain_name,
+                        proof.decode().len()
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "call-proof-request-ended; peer_id={}; chain={}; outcome=failure; \
+                        error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(basic_peering_strategy::ReputationChange::GoodResponse),
+                Err(CallProofRequestError::NoConnection)
+                | Err(CallProofRequestError::RequestTooLarge)
+                | Err(CallProofRequestError::Request(
+                    service::CallProofRequestError::RemoteCouldntAnswer,
+                )) => None,
+                Err(CallProofRequestError::Request(service::CallProofRequestError::Request(
+                    err,
+                ))) => request_error_reputation_change(err),
+                Err(CallProofRequestError::Request(service::CallProofRequestError::Decode(_))) => {
+                    Some(basic_peering_strategy::ReputationChange::BadResponse)
+                }
+            },
+        )
+        .await;
+
+        result
+    }
+
+    /// Sends a state request to the given peer, asking for the storage entries of the given
+    /// block that follow `start_key`.
+    ///
+    /// The storage entries are requested without a Merkle proof. It is the responsibility of
+    /// the API user to verify them by calculating the state trie root hash once the entire
+    /// storage has been downloaded.
+    pub async fn state_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        block_hash: [u8; 32],
+        start_key: codec::StateRequestStart<'_>,
+    ) -> Result<service::EncodedStateResponse, StateRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
+
+        let (child_trie, start_key) = match start_key {
+            codec::StateRequestStart::MainTrie(key) => (None, key.to_vec()),
+            codec::StateRequestStart::ChildTrieDefault { child_trie, key } => {
+                (Some(child_trie.to_vec()), key.to_vec())
+            }
+        };
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "state-request-start; peer_id={}; chain={}; block={}; start_key={}",
+                target,
+                chain_name,
+                HashDisplay(&block_hash),
+                hex::encode(&start_key)
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundStateRequest {
+                target: target.clone(),
+                chain_id,
+                block_hash,
+                child_trie,
+                start_key,
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(response) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "state-request-ended; peer_id={}; chain={}; outcome=success; \
+                        num_entries={}",
+                        target,
+                        chain_name,
+                        response
+                            .decode()
+                            .entries
+                            .iter()
+                            .map(|trie| trie.entries.len())
+                            .sum::<usize>()
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "state-request-ended; peer_id={}; chain={}; outcome=failure; error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        // Note that the validity of the storage entries can't be verified here. Peers that
+        // send invalid entries are reported separately.
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(basic_peering_strategy::ReputationChange::GoodResponse),
+                Err(StateRequestError::NoConnection) => None,
+                Err(StateRequestError::Request(service::StateRequestError::Request(err))) => {
+                    request_error_reputation_change(err)
+                }
+                Err(StateRequestError::Request(service::StateRequestError::Decode(_))) => {
+                    Some(basic_peering_strategy::ReputationChange::BadResponse)
+                }
+            },
+        )
+        .await;
+
+        result
+    }
+
+    /// Sends a Kademlia `GET_VALUE` request to the given peer, asking for the record whose key
+    /// is `key`.
+    ///
+    /// Note that this doesn't perform an iterative Kademlia query. Only the given peer is asked.
+    /// Use [`NetworkService::kademlia_lookup`] in order to determine which peers to ask.
+    pub async fn kademlia_get_value_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        key: Vec<u8>,
+    ) -> Result<codec::GetValueResponse, KademliaGetValueRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "kademlia-get-value-request-start; peer_id={}; chain={}; key={}",
+                target,
+                chain_name,
+                hex::encode(&key)
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundKademliaGetValueRequest {
+                target: target.clone(),
+                chain_id,
+                key,
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(response) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "kademlia-get-value-request-ended; peer_id={}; chain={}; \
+                        outcome=success; found={:?}",
+                        target,
+                        chain_name,
+                        response.record.is_some()
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "kademlia-get-value-request-ended; peer_id={}; chain={}; \
+                        outcome=failure; error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Sends a Kademlia `PUT_VALUE` request to the given peer, asking it to store the given
+    /// record.
+    ///
+    /// Note that this doesn't perform an iterative Kademlia query. Only the given peer is asked.
+    /// Use [`NetworkService::kademlia_lookup`] in order to determine which peers to ask.
+    pub async fn kademlia_put_value_request(
+        self: Arc<Self>,
+        target: PeerId,
+        chain_id: ChainId,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    ) -> Result<(), KademliaPutValueRequestError> {
+        let chain_name = self.chain_names[&chain_id].clone();
+
+        self.log_callback.log(
+            LogLevel::Debug,
+            format!(
+                "kademlia-put-value-request-start; peer_id={}; chain={}; key={}",
+                target,
+                chain_name,
+                hex::encode(&key)
+            ),
+        );
+
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundKademliaPutValueRequest {
+                target: target.clone(),
+                chain_id,
+                key,
+                value,
+                result_tx,
+            })
+            .await;
+
+        let result = result_rx.await.unwrap();
+
+        match &result {
+            Ok(()) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "kademlia-put-value-request-ended; peer_id={}; chain={}; outcome=success",
+                        target, chain_name
+                    ),
+                );
+            }
+            Err(err) => {
+                self.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "kademlia-put-value-request-ended; peer_id={}; chain={}; \
+                        outcome=failure; error={}",
+                        target, chain_name, err
+                    ),
+                );
+            }
+        }
+
+        result
+    }
+
+    /// Returns the list of peers of the k-buckets of the given chain that are the closest to the
+    /// given key, in the Kademlia sense. The list is ordered by increasing distance to the key.
+    ///
+    /// The local node isn't necessarily connected to these peers. Use
+    /// [`NetworkService::kademlia_lookup`] in order to find the peers of the entire network that
+    /// are the closest to a key.
+    pub async fn kademlia_closest_peers(&self, chain_id: ChainId, key: Vec<u8>) -> Vec<PeerId> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundKademliaClosestPeers {
+                chain_id,
+                key,
+                result_tx,
+            })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
+    /// Performs an iterative Kademlia lookup of the given key: asks the peers of the k-buckets
+    /// that are the closest to the key, then the peers they return, and so on, until the peers
+    /// of the network that are the closest to the key have been found. Connections are opened
+    /// with the peers the local node isn't connected to.
+    ///
+    /// `GET_VALUE` requests are used, meaning that the records found along the way are returned
+    /// as well.
+    pub async fn kademlia_lookup(
+        self: Arc<Self>,
+        chain_id: ChainId,
+        key: Vec<u8>,
+    ) -> KademliaLookupOutcome {
+        let mut lookup = kademlia::closest_peers_lookup::ClosestPeersLookup::new(
+            kademlia::closest_peers_lookup::Config {
+                key: &key,
+                initial_peers: self
+                    .kademlia_closest_peers(chain_id, key.clone())
+                    .await
+                    .into_iter(),
+                num_closest: 20, // TODO: constant
+                parallelism: 3,  // TODO: constant
+            },
+        );
+
+        let mut records = Vec::new();
+        let mut in_progress = futures_util::stream::FuturesUnordered::new();
+
+        loop {
+            while let Some(target) = lookup.next_to_query() {
+                let network_service = self.clone();
+                let key = key.clone();
+                in_progress.push(async move {
+                    let response = if network_service.connect_for_requests(target.clone()).await {
+                        network_service
+                            .kademlia_get_value_request(target.clone(), chain_id, key)
+                            .await
+                            .ok()
+                    } else {
+                        None
+                    };
+                    (target, response)
+                });
+            }
+
+            let Some((target, response)) = in_progress.next().await else {
+                break;
+            };
+
+            let Some(response) = response else {
+                lookup.inject_failure(&target);
+                continue;
+            };
+
+            if let Some(record) = response.record {
+                records.push((target.clone(), record.value));
+            }
+
+            let mut closer_peers = Vec::with_capacity(response.closer_peers.len());
+            for (peer_id, addresses) in response.closer_peers {
+                if peer_id == self.local_peer_id {
+                    continue;
+                }
+
+                // Peers without any valid address can't be connected to anyway.
+                let addresses = addresses
+                    .into_iter()
+                    .filter_map(|addr| Multiaddr::try_from(addr).ok())
+                    .collect::<Vec<_>>();
+                if addresses.is_empty() {
+                    continue;
+                }
+
+                self.discovered_addresses(chain_id, peer_id.clone(), addresses)
+                    .await;
+                closer_peers.push(peer_id);
+            }
+
+            lookup.inject_response(&target, closer_peers.into_iter());
+        }
+
+        debug_assert!(lookup.is_finished());
+        KademliaLookupOutcome {
+            closest_peers: lookup.closest_peers().cloned().collect(),
+            records,
+        }
+    }
+
+    /// Makes sure that a connection with the given peer is established, so that requests can be
+    /// sent to it. Returns `false` if the connection couldn't be established.
+    ///
+    /// The addresses of the peer must be known, for example through
+    /// [`NetworkService::discovered_addresses`].
+    pub async fn connect_for_requests(&self, peer_id: PeerId) -> bool {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundConnectForRequests { peer_id, result_tx })
+            .await;
+
+        result_rx.await.unwrap_or(false)
+    }
+
+    /// Returns the addresses the local node is reachable at. This includes the addresses passed
+    /// through [`Config::listen_addresses`] and the public addresses that remotes have reported
+    /// observing the local node at.
+    pub async fn local_listen_addresses(&self) -> Vec<Multiaddr> {
+        let (result_tx, result_rx) = oneshot::channel();
+
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundGetLocalListenAddresses { result_tx })
+            .await;
+
+        result_rx.await.unwrap()
+    }
+
+    /// Reports to the network service addresses of a peer of the given chain that have been
+    /// discovered through means other than the networking itself, such as the authority
+    /// discovery mechanism.
+    ///
+    /// The addresses are used in order to potentially connect to this peer in the future.
+    pub async fn discovered_addresses(
+        &self,
+        chain_id: ChainId,
+        peer_id: PeerId,
+        addresses: Vec<Multiaddr>,
+    ) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundDiscoveredAddresses {
+                chain_id,
+                peer_id,
+                addresses,
+            })
+            .await;
+    }
+
+    /// Adds a reserved peer to the given chain. The network service permanently tries to
+    /// maintain a gossip link with this peer, and never bans it.
+    ///
+    /// Has no effect if the peer is already reserved, except for adding the address to the list
+    /// of known addresses of this peer.
+    pub async fn add_reserved_peer(&self, chain_id: ChainId, peer_id: PeerId, address: Multiaddr) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundAddReservedPeer {
+                chain_id,
+                peer_id,
+                address,
+            })
+            .await;
+    }
+
+    /// Removes a peer previously added with [`NetworkService::add_reserved_peer`] or passed
+    /// through [`ChainConfig::reserved_nodes`]. The peer is then treated like any other peer.
+    ///
+    /// Has no effect if the peer isn't reserved.
+    pub async fn remove_reserved_peer(&self, chain_id: ChainId, peer_id: PeerId) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundRemoveReservedPeer { chain_id, peer_id })
+            .await;
+    }
+
+    /// Adjusts the reputation of the given peer on the given chain.
+    ///
+    /// Must be used to report misbehaviours detected outside of the networking, such as invalid
+    /// storage entries. Peers whose reputation is too low are banned and their gossip link is
+    /// closed. Request failures and successes are already reported automatically.
+    pub async fn report_peer(
+        &self,
+        chain_id: ChainId,
+        peer_id: PeerId,
+        change: basic_peering_strategy::ReputationChange,
+    ) {
+        let _ = self
+            .to_background_tx
+            .lock()
+            .await
+            .send(ToBackground::ForegroundReportPeer {
+                chain_id,
+                peer_id,
+                change,
+            })
+            .await;
+    }
+
+    /// Reports the outcome of a request to the background task, if the outcome has an effect on
+    /// the reputation of the peer.
+    async fn report_request_outcome(
+        &self,
+        chain_id: ChainId,
+        peer_id: PeerId,
+        change: Option<basic_peering_strategy::ReputationChange>,
+    ) {
+        if let Some(change) = change {
+            self.report_peer(chain_id, peer_id, change).await;
+        }
+    }
+}
+
+impl NetworkService {
+    /// Shuts down the networking and saves the address books, without waiting for all the
+    /// other services holding a reference to this [`NetworkService`] to be destroyed.
+    pub fn shutdown(&self) {
+        self.foreground_shutdown.notify(usize::max_value());
+    }
+}
+
+impl Drop for NetworkService {
+    fn drop(&mut self) {
+        self.foreground_shutdown.notify(usize::max_value());
+    }
+}
+
+/// Error when initializing the network service.
+#[derive(Debug, derive_more::Display)]
 pub enum InitError {
     /// I/O error when initializing a listener.
     #[display(fmt = "I/O error when creating listener for {_0}: {_1}")]
@@ -792,6 +2075,79 @@ pub enum BlocksRequestError {
     Request(service::BlocksRequestError),
 }
 
+/// Error returned by [`NetworkService::grandpa_warp_sync_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum WarpSyncRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::GrandpaWarpSyncRequestError),
+}
+
+/// Error returned by [`NetworkService::storage_proof_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum StorageProofRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Storage proof request is too large and can't be sent.
+    RequestTooLarge,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::StorageProofRequestError),
+}
+
+/// Error returned by [`NetworkService::call_proof_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum CallProofRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Call proof request is too large and can't be sent.
+    RequestTooLarge,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::CallProofRequestError),
+}
+
+/// Error returned by [`NetworkService::state_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum StateRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::StateRequestError),
+}
+
+/// Outcome of [`NetworkService::kademlia_lookup`].
+#[derive(Debug, Clone)]
+pub struct KademliaLookupOutcome {
+    /// Peers of the network that are the closest to the key, ordered by increasing distance.
+    pub closest_peers: Vec<PeerId>,
+    /// Records found during the lookup, and the peer that has returned each of them.
+    pub records: Vec<(PeerId, Vec<u8>)>,
+}
+
+/// Error returned by [`NetworkService::kademlia_get_value_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum KademliaGetValueRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::KademliaGetValueError),
+}
+
+/// Error returned by [`NetworkService::kademlia_put_value_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum KademliaPutValueRequestError {
+    /// No established connection with the target.
+    NoConnection,
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    Request(service::KademliaPutValueError),
+}
+
 fn run(mut inner: Inner) {
     // This function is a small hack because I didn't find a better way to store the executor
     // within `Inner` while at the same time spawning the `Inner` using said executor.
@@ -839,7 +2195,10 @@ async fn background_task(mut inner: Inner) {
                         peer_id,
                         ..
                     } => {
-                        inner.num_pending_out_attempts -= 1;
+                        // Only outgoing connections have an expected peer ID.
+                        if expected_peer_id.is_some() {
+                            inner.num_pending_out_attempts -= 1;
+                        }
 
                         let remote_addr = Multiaddr::try_from(
                             inner.network.connection_remote_addr(id).to_owned(),
@@ -876,14 +2235,51 @@ async fn background_task(mut inner: Inner) {
                                 .log_callback
                                 .log(LogLevel::Debug, format!("connected; peer_id={}", peer_id));
                         }
+
+                        // Ask the remote for its listen addresses and for our address as seen
+                        // from its side.
+                        match inner
+                            .network
+                            .start_identify_request(&peer_id, Duration::from_secs(20))
+                        {
+                            Ok(substream_id) => {
+                                let _prev_value = inner
+                                    .identify_requests
+                                    .insert(substream_id, peer_id.clone());
+                                debug_assert!(_prev_value.is_none());
+                            }
+                            // Events are processed with a delay, and the connection might
+                            // already be shutting down.
+                            Err(service::StartRequestError::NoConnection) => {}
+                        }
+
+                        if let Some(dial) = inner.request_dials.remove(&peer_id) {
+                            for waiter in dial.waiters {
+                                let _ = waiter.send(true);
+                            }
+                        }
+                        if let Some(expected_peer_id) =
+                            expected_peer_id.as_ref().filter(|p| **p != peer_id)
+                        {
+                            if inner
+                                .request_dials
+                                .get(expected_peer_id)
+                                .map_or(false, |dial| dial.dialing)
+                            {
+                                let dial = inner.request_dials.remove(expected_peer_id).unwrap();
+                                for waiter in dial.waiters {
+                                    let _ = waiter.send(false);
+                                }
+                            }
+                        }
                     }
                     service::Event::PreHandshakeDisconnected {
                         address,
                         expected_peer_id,
                         ..
                     } => {
-                        inner.num_pending_out_attempts -= 1;
                         if let Some(expected_peer_id) = expected_peer_id {
+                            inner.num_pending_out_attempts -= 1;
                             inner
                                 .peering_strategy
                                 .disconnect_addr(&expected_peer_id, &address)
@@ -896,6 +2292,18 @@ async fn background_task(mut inner: Inner) {
                                     expected_peer_id, address
                                 ),
                             );
+                            schedule_reserved_peer_redial(&mut inner, &expected_peer_id);
+
+                            if inner
+                                .request_dials
+                                .get(&expected_peer_id)
+                                .map_or(false, |dial| dial.dialing)
+                            {
+                                let dial = inner.request_dials.remove(&expected_peer_id).unwrap();
+                                for waiter in dial.waiters {
+                                    let _ = waiter.send(false);
+                                }
+                            }
                         }
                     }
                     service::Event::Disconnected {
@@ -962,7 +2370,7 @@ async fn background_task(mut inner: Inner) {
                                     inner.peering_strategy.unassign_slot_and_ban(
                                         &chain_id,
                                         &peer_id,
-                                        Instant::now() + Duration::from_secs(10),
+                                        inner.clock.now() + Duration::from_secs(10),
                                     );
                                     inner.log_callback.log(
                                         LogLevel::Debug,
@@ -985,6 +2393,7 @@ async fn background_task(mut inner: Inner) {
                     service::Event::GossipConnected {
                         peer_id,
                         chain_id,
+                        role,
                         best_number,
                         best_hash,
                         ..
@@ -999,6 +2408,31 @@ async fn background_task(mut inner: Inner) {
                             HashDisplay(&best_hash),
                         ),
                         );
+
+                        let now = inner.clock.now();
+                        if let Ok(mut entry) = inner.network[chain_id]
+                            .kbuckets
+                            .entry(&peer_id)
+                            .or_insert((), &now, kademlia::kbuckets::PeerState::Connected)
+                        {
+                            entry.set_state(&now, kademlia::kbuckets::PeerState::Connected);
+                        }
+
+                        inner.reserved_peers_redial.remove(&peer_id);
+                        update_peers_metric(&inner, chain_id);
+
+                        // Validators are the only peers interested in collations.
+                        if matches!(role, service::Role::Authority) {
+                            if let Some(collator) = &mut inner.network[chain_id].collator {
+                                collator.authorities.insert(peer_id.clone());
+                                if !collator.collations.is_empty() {
+                                    // An error is returned if a substream is already open or
+                                    // opening, in which case there is nothing to do.
+                                    let _ = inner.network.collation_open(chain_id, &peer_id);
+                                }
+                            }
+                        }
+
                         break Some(Event::Connected {
                             peer_id,
                             chain_id,
@@ -1017,12 +2451,23 @@ async fn background_task(mut inner: Inner) {
                             ),
                         );
 
+                        if let Some(mut entry) = inner.network[chain_id]
+                            .kbuckets
+                            .entry(&peer_id)
+                            .into_occupied()
+                        {
+                            entry.set_state(
+                                &inner.clock.now(),
+                                kademlia::kbuckets::PeerState::Disconnected,
+                            );
+                        }
+
                         // Note that peer doesn't necessarily have an out slot, as this event
                         // might happen as a result of an inbound gossip connection.
                         inner.peering_strategy.unassign_slot_and_ban(
                             &chain_id,
                             &peer_id,
-                            Instant::now() + Duration::from_secs(10),
+                            inner.clock.now() + Duration::from_secs(10),
                         );
                         if inner.network.gossip_remove_desired(
                             chain_id,
@@ -1037,6 +2482,15 @@ async fn background_task(mut inner: Inner) {
                                 ),
                             );
                         }
+                        schedule_reserved_peer_redial(&mut inner, &peer_id);
+                        update_peers_metric(&inner, chain_id);
+
+                        if let Some(collator) = &mut inner.network[chain_id].collator {
+                            collator.authorities.remove(&peer_id);
+                            collator.connected.remove(&peer_id);
+                            // An error is returned if no substream is open or opening.
+                            let _ = inner.network.collation_close(chain_id, &peer_id);
+                        }
 
                         inner.process_network_service_events = true;
 
@@ -1080,9 +2534,10 @@ async fn background_task(mut inner: Inner) {
                             inner.peering_strategy.unassign_slot_and_ban(
                                 &chain_id,
                                 &peer_id,
-                                Instant::now() + Duration::from_secs(15),
+                                inner.clock.now() + Duration::from_secs(15),
                             );
                         }
+                        schedule_reserved_peer_redial(&mut inner, &peer_id);
 
                         inner.process_network_service_events = true;
                     }
@@ -1093,24 +2548,25 @@ async fn background_task(mut inner: Inner) {
                     } => {
                         // TODO: log this
                         // TODO: arbitrary constant
-                        // The networking state machine guarantees that `GossipInDesired`
-                        // can't happen if we are already opening an out slot, which we do
-                        // immediately.
-                        // TODO: add debug_assert! ^
-                        if inner
-                            .network
-                            .opened_gossip_undesired_by_chain(chain_id)
-                            .count()
-                            < 25
+                        // Reserved peers are always accepted, while other peers are always
+                        // refused if the chain is in reserved-only mode.
+                        if inner.peering_strategy.is_reserved(&chain_id, &peer_id)
+                            || (!inner.network[chain_id].reserved_only
+                                && inner
+                                    .network
+                                    .opened_gossip_undesired_by_chain(chain_id)
+                                    .count()
+                                    < 25)
                         {
-                            inner
-                                .network
-                                .gossip_open(
-                                    chain_id,
-                                    &peer_id,
-                                    service::GossipKind::ConsensusTransactions,
-                                )
-                                .unwrap();
+                            // Opening fails if an outgoing gossip substream is already being
+                            // opened, which can happen if both sides connected to each other
+                            // at the same time, or if the connection is shutting down. In both
+                            // situations, there is nothing more to do.
+                            let _ = inner.network.gossip_open(
+                                chain_id,
+                                &peer_id,
+                                service::GossipKind::ConsensusTransactions,
+                            );
                         } else {
                             inner
                                 .network
@@ -1123,9 +2579,8 @@ async fn background_task(mut inner: Inner) {
                         }
                     }
                     service::Event::GossipInDesiredCancel { .. } => {
-                        // All `GossipInDesired` are immediately accepted or rejected, meaning
-                        // that this event can't happen.
-                        unreachable!()
+                        // Happens if a `GossipInDesired` couldn't be accepted because an
+                        // outgoing gossip substream was already being opened. Nothing to do.
                     }
                     service::Event::RequestResult {
                         substream_id,
@@ -1137,6 +2592,46 @@ async fn background_task(mut inner: Inner) {
                             .unwrap()
                             .send(response.map_err(BlocksRequestError::Request));
                     }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::GrandpaWarpSync(response),
+                    } => {
+                        let _ = inner
+                            .warp_sync_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(WarpSyncRequestError::Request));
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::StorageProof(response),
+                    } => {
+                        let _ = inner
+                            .storage_proof_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(StorageProofRequestError::Request));
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::CallProof(response),
+                    } => {
+                        let _ = inner
+                            .call_proof_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(CallProofRequestError::Request));
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::State(response),
+                    } => {
+                        let _ = inner
+                            .state_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(StateRequestError::Request));
+                    }
                     service::Event::RequestResult {
                         substream_id,
                         response: service::RequestResult::KademliaFindNode(Ok(nodes)),
@@ -1173,41 +2668,7 @@ async fn background_task(mut inner: Inner) {
                                 }
                             }
 
-                            if !valid_addrs.is_empty() {
-                                // Note that we must call this function before `insert_address`,
-                                // as documented in `basic_peering_strategy`.
-                                if let basic_peering_strategy::InsertChainPeerResult::Inserted {
-                                    peer_removed: Some(peer_removed),
-                                } = inner.peering_strategy.insert_chain_peer(
-                                    chain_id,
-                                    peer_id.clone(),
-                                    100, // TODO: constant
-                                ) {
-                                    inner.log_callback.log(
-                                        LogLevel::Debug,
-                                        format!(
-                                            "peer-forgotten; peer_id={}; chain={}",
-                                            peer_removed, inner.network[chain_id].log_name
-                                        ),
-                                    );
-                                }
-                            }
-
-                            for addr in valid_addrs {
-                                match inner
-                                    .peering_strategy
-                                     .insert_address(&peer_id, addr.into_vec(), 10) // TODO: constant
-                                    {
-                                        basic_peering_strategy::InsertAddressResult::Inserted { address_removed: Some(addr_rm) } => {
-                                            let addr_rm = Multiaddr::try_from(addr_rm).unwrap();
-                                            inner
-                                                .log_callback
-                                                .log(LogLevel::Debug, format!("address-purged; peer_id={}; address={}", peer_id, addr_rm));
-                                        }
-                                        basic_peering_strategy::InsertAddressResult::UnknownPeer => unreachable!(),
-                                        _ => {}
-                                    }
-                            }
+                            insert_discovered_addresses(&mut inner, chain_id, peer_id, valid_addrs);
                         }
                     }
                     service::Event::RequestResult {
@@ -1226,13 +2687,215 @@ async fn background_task(mut inner: Inner) {
                             ),
                         );
                     }
-                    service::Event::RequestResult { .. } => {
-                        // We never start a request of any other kind.
-                        unreachable!()
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::Identify(Ok(response)),
+                    } => {
+                        let peer_id = inner.identify_requests.remove(&substream_id).unwrap();
+                        let decoded = response.decode();
+
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "identified; peer_id={}; agent_version={}; protocols={}",
+                                peer_id,
+                                decoded.agent_version,
+                                decoded.protocols.clone().collect::<Vec<_>>().join(",")
+                            ),
+                        );
+
+                        // Listen addresses and protocols are only inserted if the peer is
+                        // already known to belong to one of the chains.
+                        inner.peering_strategy.set_peer_protocols(
+                            &peer_id,
+                            decoded.protocols.map(|p| p.to_owned()),
+                            64, // TODO: constant
+                        );
+                        for addr in decoded.listen_addrs {
+                            let Ok(addr) = Multiaddr::try_from(addr.to_vec()) else {
+                                continue;
+                            };
+                            if let basic_peering_strategy::InsertAddressResult::Inserted {
+                                address_removed: Some(addr_rm),
+                            } = inner.peering_strategy.insert_address(
+                                &peer_id,
+                                addr.into_vec(),
+                                10, // TODO: constant
+                            ) {
+                                let addr_rm = Multiaddr::try_from(addr_rm).unwrap();
+                                inner.log_callback.log(
+                                    LogLevel::Debug,
+                                    format!(
+                                        "address-purged; peer_id={}; address={}",
+                                        peer_id, addr_rm
+                                    ),
+                                );
+                            }
+                        }
+
+                        // The observed address is ignored if it can't be translated into an
+                        // address that other nodes can connect to.
+                        if let Some(observed_addr) =
+                            Multiaddr::try_from(decoded.observed_addr.to_vec())
+                                .ok()
+                                .and_then(|addr| {
+                                    translate_observed_address(&addr, &inner.listen_addresses)
+                                })
+                        {
+                            if inner
+                                .observed_addresses
+                                .insert(peer_id, observed_addr.clone().into_vec())
+                            {
+                                inner.log_callback.log(
+                                    LogLevel::Info,
+                                    format!("public-address-discovered; address={}", observed_addr),
+                                );
+                            }
+                        }
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::Identify(Err(error)),
+                    } => {
+                        let peer_id = inner.identify_requests.remove(&substream_id).unwrap();
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!("identify-error; peer_id={}; error={}", peer_id, error),
+                        );
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::KademliaGetValue(response),
+                    } => {
+                        let _ = inner
+                            .kademlia_get_value_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(KademliaGetValueRequestError::Request));
+                    }
+                    service::Event::RequestResult {
+                        substream_id,
+                        response: service::RequestResult::KademliaPutValue(response),
+                    } => {
+                        let _ = inner
+                            .kademlia_put_value_requests
+                            .remove(&substream_id)
+                            .unwrap()
+                            .send(response.map_err(KademliaPutValueRequestError::Request));
+                    }
+                    service::Event::RequestInCancel { substream_id } => {
+                        // State requests are the only requests that aren't answered immediately.
+                        let _was_in = inner.state_requests_in.remove(&substream_id);
+                        debug_assert!(_was_in);
+                    }
+                    service::Event::CollationFetchingRequestIn {
+                        peer_id,
+                        chain_id,
+                        request,
+                        substream_id,
+                    } => {
+                        // Collation protocols are only enabled if `collator` is `Some`.
+                        let collator = inner.network[chain_id].collator.as_ref().unwrap();
+                        let collation = collator
+                            .collations
+                            .iter()
+                            .rev()
+                            .find(|c| {
+                                c.relay_parent == request.relay_parent
+                                    && c.para_id == request.para_id
+                            })
+                            .cloned();
+
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "collation-fetching-request; peer_id={}; chain={}; relay_parent={}; para_id={}; found={:?}",
+                                peer_id,
+                                inner.network[chain_id].log_name,
+                                HashDisplay(&request.relay_parent),
+                                request.para_id,
+                                collation.is_some()
+                            ),
+                        );
+
+                        inner.network.respond_collation_fetching(
+                            substream_id,
+                            collation
+                                .as_ref()
+                                .map(|c| codec::CollationFetchingResponse {
+                                    candidate_receipt: codec::CandidateReceiptRef {
+                                        para_id: c.para_id,
+                                        relay_parent: &c.relay_parent,
+                                        collator: &c.collator_id,
+                                        persisted_validation_data_hash: &c
+                                            .persisted_validation_data_hash,
+                                        pov_hash: &c.pov_hash,
+                                        erasure_root: &c.erasure_root,
+                                        signature: &c.signature,
+                                        para_head: &c.para_head,
+                                        validation_code_hash: &c.validation_code_hash,
+                                        commitments_hash: &c.commitments_hash,
+                                    },
+                                    scale_encoded_pov: &c.scale_encoded_pov,
+                                }),
+                        );
+                    }
+                    service::Event::CollationConnected { peer_id, chain_id } => {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "collation-connected; peer_id={}; chain={}",
+                                peer_id, inner.network[chain_id].log_name
+                            ),
+                        );
+
+                        // Substreams are only opened after a collation has been announced.
+                        let collator = inner.network[chain_id].collator.as_mut().unwrap();
+                        let latest = collator.collations.back().unwrap().clone();
+                        collator.connected.insert(peer_id.clone());
+
+                        let _ = inner.network.collation_send(
+                            &peer_id,
+                            chain_id,
+                            codec::CollationProtocolMessageRef::Declare {
+                                collator_id: &latest.collator_id,
+                                para_id: latest.para_id,
+                                signature: &latest.declare_signature,
+                            },
+                        );
+                        let _ = inner.network.collation_send(
+                            &peer_id,
+                            chain_id,
+                            codec::CollationProtocolMessageRef::AdvertiseCollation {
+                                relay_parent: &latest.relay_parent,
+                            },
+                        );
+                    }
+                    service::Event::CollationOpenFailed {
+                        peer_id,
+                        chain_id,
+                        error,
+                    } => {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "collation-connect-attempt-failed; peer_id={}; chain={}; error={}",
+                                peer_id, inner.network[chain_id].log_name, error
+                            ),
+                        );
                     }
-                    service::Event::RequestInCancel { .. } => {
-                        // Requests are answered immediately, and thus cancelling events can't happen.
-                        unreachable!()
+                    service::Event::CollationClosed { peer_id, chain_id } => {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "collation-disconnected; peer_id={}; chain={}",
+                                peer_id, inner.network[chain_id].log_name
+                            ),
+                        );
+
+                        if let Some(collator) = &mut inner.network[chain_id].collator {
+                            collator.connected.remove(&peer_id);
+                        }
                     }
                     service::Event::IdentifyRequestIn {
                         peer_id,
@@ -1242,9 +2905,12 @@ async fn background_task(mut inner: Inner) {
                             LogLevel::Debug,
                             format!("identify-request; peer_id={}", peer_id),
                         );
-                        inner
-                            .network
-                            .respond_identify(substream_id, &inner.identify_agent_version);
+                        let listen_addrs = local_listen_addresses(&inner);
+                        inner.network.respond_identify(
+                            substream_id,
+                            &inner.identify_agent_version,
+                            listen_addrs.iter().map(|a| a.as_ref()),
+                        );
                     }
                     service::Event::BlocksRequestIn {
                         peer_id,
@@ -1293,6 +2959,161 @@ async fn background_task(mut inner: Inner) {
                             },
                         );
                     }
+                    service::Event::StateRequestIn {
+                        peer_id,
+                        chain_id,
+                        block_hash,
+                        child_trie,
+                        start_key,
+                        no_proof,
+                        substream_id,
+                    } => {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "incoming-state-request; peer_id={}; chain={}; block={}; \
+                                no_proof={:?}",
+                                peer_id,
+                                inner.network[chain_id].log_name,
+                                HashDisplay(&block_hash),
+                                no_proof
+                            ),
+                        );
+
+                        // Substrate nodes expect the Merkle proof of a response to be a compact
+                        // proof, which would require accessing the node values of the trie.
+                        // Requests that ask for a proof are therefore explicitly rejected, in
+                        // which case Substrate nodes try another peer.
+                        if !no_proof {
+                            inner.log_callback.log(
+                                LogLevel::Debug,
+                                format!(
+                                    "incoming-state-request-rejected; peer_id={}; \
+                                    reason=proof-unsupported",
+                                    peer_id
+                                ),
+                            );
+                            inner.network.respond_state(substream_id, None);
+                            continue;
+                        }
+
+                        // Reading the storage can take a long time. It is done in a separate
+                        // task in order to not freeze the networking background task.
+                        inner.state_requests_in.insert(substream_id);
+                        (inner.tasks_executor)(Box::pin({
+                            let database = inner.network[chain_id].database.clone();
+                            let log_callback = inner.log_callback.clone();
+                            let to_background_tx = inner.to_background_tx.clone();
+                            async move {
+                                let response = match state_request_response(
+                                    &database, block_hash, child_trie, start_key,
+                                )
+                                .await
+                                {
+                                    Ok(response) => response,
+                                    Err(full_sqlite::StorageAccessError::StoragePruned)
+                                    | Err(full_sqlite::StorageAccessError::UnknownBlock) => None,
+                                    Err(full_sqlite::StorageAccessError::Corrupted(error)) => {
+                                        log_callback.log(
+                                            LogLevel::Warn,
+                                            format!(
+                                                "incoming-state-request-error; error={}",
+                                                error
+                                            ),
+                                        );
+                                        None
+                                    }
+                                };
+
+                                let _ = to_background_tx
+                                    .send(ToBackground::StateRequestInResponse {
+                                        substream_id,
+                                        response,
+                                    })
+                                    .await;
+                            }
+                        }));
+                    }
+                    service::Event::KademliaFindNodeRequestIn {
+                        peer_id,
+                        chain_id,
+                        key,
+                        substream_id,
+                    } => {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "incoming-kademlia-find-node; peer_id={}; chain={}",
+                                peer_id, inner.network[chain_id].log_name
+                            ),
+                        );
+
+                        let closer_peers = kademlia_closer_peers(&inner, chain_id, &key, &peer_id);
+                        inner
+                            .network
+                            .respond_kademlia_find_node(substream_id, &closer_peers);
+                    }
+                    service::Event::KademliaGetValueRequestIn {
+                        peer_id,
+                        chain_id,
+                        key,
+                        substream_id,
+                    } => {
+                        let value = inner.network[chain_id]
+                            .kademlia_records
+                            .get(&key, &inner.clock.now())
+                            .map(|value| value.to_vec());
+
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "incoming-kademlia-get-value; peer_id={}; chain={}; key={}; found={:?}",
+                                peer_id,
+                                inner.network[chain_id].log_name,
+                                hex::encode(&key),
+                                value.is_some()
+                            ),
+                        );
+
+                        let closer_peers = kademlia_closer_peers(&inner, chain_id, &key, &peer_id);
+                        inner.network.respond_kademlia_get_value(
+                            substream_id,
+                            &key,
+                            value.as_deref(),
+                            &closer_peers,
+                        );
+                    }
+                    service::Event::KademliaPutValueRequestIn {
+                        peer_id,
+                        chain_id,
+                        key,
+                        value,
+                        substream_id,
+                    } => {
+                        let now = inner.clock.now();
+                        let records = &mut inner.network[chain_id].kademlia_records;
+                        records.purge_expired(&now);
+                        let result = records.put(key.clone(), value.clone(), &now);
+
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "incoming-kademlia-put-value; peer_id={}; chain={}; key={}; result={:?}",
+                                peer_id,
+                                inner.network[chain_id].log_name,
+                                hex::encode(&key),
+                                result
+                            ),
+                        );
+
+                        if result.is_ok() {
+                            inner
+                                .network
+                                .respond_kademlia_put_value(substream_id, Some((&key, &value)));
+                        } else {
+                            inner.network.respond_kademlia_put_value(substream_id, None);
+                        }
+                    }
                     service::Event::GrandpaNeighborPacket {
                         chain_id,
                         peer_id,
@@ -1306,7 +3127,12 @@ async fn background_task(mut inner: Inner) {
                             state.set_id,
                             state.commit_finalized_height,
                         ));
-                        // TODO: report to the sync state machine
+
+                        break Some(Event::GrandpaNeighborPacket {
+                            chain_id,
+                            peer_id,
+                            finalized_block_height: state.commit_finalized_height,
+                        });
                     }
                     service::Event::GrandpaCommitMessage {
                         chain_id,
@@ -1330,7 +3156,7 @@ async fn background_task(mut inner: Inner) {
                         );
                         inner.peering_strategy.unassign_slots_and_ban(
                             &peer_id,
-                            Instant::now() + Duration::from_secs(5),
+                            inner.clock.now() + Duration::from_secs(5),
                         );
                         // TODO: log chain names?
                         inner.log_callback.log(
@@ -1375,6 +3201,41 @@ async fn background_task(mut inner: Inner) {
 
             // TODO: doc
             for chain_id in inner.network.chains().collect::<Vec<_>>() {
+                // Reserved peers always have a slot, and are inserted back in the list of
+                // desired gossip links once their redial delay has elapsed.
+                for peer_id in inner
+                    .peering_strategy
+                    .reserved_chain_peers(&chain_id)
+                    .cloned()
+                    .collect::<Vec<_>>()
+                {
+                    if inner
+                        .reserved_peers_redial
+                        .get(&peer_id)
+                        .map_or(false, |r| r.when.is_some())
+                    {
+                        continue;
+                    }
+
+                    if inner.network.gossip_insert_desired(
+                        chain_id,
+                        peer_id.clone(),
+                        service::GossipKind::ConsensusTransactions,
+                    ) {
+                        inner.log_callback.log(
+                            LogLevel::Debug,
+                            format!(
+                                "reserved-peer-desired; peer_id={}; chain={}",
+                                peer_id, inner.network[chain_id].log_name
+                            ),
+                        );
+                    }
+                }
+
+                if inner.network[chain_id].reserved_only {
+                    continue;
+                }
+
                 loop {
                     // TODO: 25 is an arbitrary constant, make configurable
                     if inner
@@ -1385,12 +3246,31 @@ async fn background_task(mut inner: Inner) {
                         break;
                     }
 
-                    let peer_id = match inner.peering_strategy.pick_assignable_peer(&chain_id, &Instant::now()) {
+                    let peer_id = match inner.peering_strategy.pick_assignable_peer(&chain_id, &inner.clock.now()) {
                         basic_peering_strategy::AssignablePeer::Assignable(peer_id) => {
+                            inner.network[chain_id].all_peers_reputation_banned = false;
                             peer_id.clone()
                         }
+                        basic_peering_strategy::AssignablePeer::AllPeersReputationBanned => {
+                            // Printed only once, as this code is reached every time the
+                            // background task wakes up.
+                            if !inner.network[chain_id].all_peers_reputation_banned {
+                                inner.network[chain_id].all_peers_reputation_banned = true;
+                                inner.log_callback.log(
+                                    LogLevel::Debug,
+                                    format!(
+                                        "no-assignable-peer; chain={}; reason=reputation",
+                                        inner.network[chain_id].log_name
+                                    ),
+                                );
+                            }
+                            break;
+                        }
                         basic_peering_strategy::AssignablePeer::AllPeersBanned { .. }  // TODO: handle `AllPeersBanned` by waking up when a ban expires
-                        | basic_peering_strategy::AssignablePeer::NoPeer => break,
+                        | basic_peering_strategy::AssignablePeer::NoPeer => {
+                            inner.network[chain_id].all_peers_reputation_banned = false;
+                            break;
+                        }
                     };
 
                     inner.peering_strategy.assign_slot(&chain_id, &peer_id);
@@ -1425,17 +3305,16 @@ async fn background_task(mut inner: Inner) {
                     None => break,
                 };
 
-                inner.num_pending_out_attempts += 1;
-
-                let Some(multiaddr) = inner.peering_strategy.addr_to_connected(&peer_id) else {
+                if !start_dial(&mut inner, &peer_id) {
                     // There is no address for that peer in the address book.
                     inner.network.gossip_remove_desired_all(
                         &peer_id,
                         service::GossipKind::ConsensusTransactions,
                     );
-                    inner
-                        .peering_strategy
-                        .unassign_slots_and_ban(&peer_id, Instant::now() + Duration::from_secs(10));
+                    inner.peering_strategy.unassign_slots_and_ban(
+                        &peer_id,
+                        inner.clock.now() + Duration::from_secs(10),
+                    );
                     // TODO: log chain names?
                     inner.log_callback.log(
                         LogLevel::Debug,
@@ -1444,69 +3323,33 @@ async fn background_task(mut inner: Inner) {
                             peer_id
                         ),
                     );
-                    continue;
-                };
+                    schedule_reserved_peer_redial(&mut inner, &peer_id);
+                }
+            }
 
-                let multiaddr = match multiaddr::Multiaddr::try_from(multiaddr.to_owned()) {
-                    Ok(a) => a,
-                    Err(multiaddr::FromVecError { addr }) => {
-                        // Address is in an invalid format.
-                        inner.log_callback.log(
-                            LogLevel::Debug,
-                            format!("invalid-address; peer_id={}; address={:?}", peer_id, addr),
-                        );
-                        let _was_in = inner.peering_strategy.remove_address(&peer_id, &addr);
-                        debug_assert!(_was_in);
-                        continue;
-                    }
-                };
+            // Open the connections requested through `NetworkService::connect_for_requests`.
+            loop {
+                if inner.num_pending_out_attempts >= 16 {
+                    // TODO: constant
+                    break;
+                }
 
-                // Convert the `multiaddr` (typically of the form `/ip4/a.b.c.d/tcp/d`) into
-                // a `Future<dyn Output = Result<TcpStream, ...>>`.
-                let socket = match tasks::multiaddr_to_socket(&multiaddr) {
-                    Ok(socket) => socket,
-                    Err(_) => {
-                        // Address is in an invalid format or isn't supported.
-                        inner.log_callback.log(
-                            LogLevel::Debug,
-                            format!(
-                                "invalid-address; peer_id={}; address={}",
-                                peer_id, multiaddr
-                            ),
-                        );
-                        let _was_in = inner
-                            .peering_strategy
-                            .remove_address(&peer_id, multiaddr.as_ref());
-                        debug_assert!(_was_in);
-                        continue;
-                    }
+                let Some(peer_id) = inner
+                    .request_dials
+                    .iter()
+                    .find(|(_, dial)| !dial.dialing)
+                    .map(|(peer_id, _)| peer_id.clone())
+                else {
+                    break;
                 };
 
-                let (connection_id, connection_task) = inner.network.add_single_stream_connection(
-                    Instant::now(),
-                    service::SingleStreamHandshakeKind::MultistreamSelectNoiseYamux {
-                        is_initiator: true,
-                        noise_key: &inner.noise_key,
-                    },
-                    multiaddr.clone().into_vec(),
-                    Some(peer_id.clone()),
-                );
-
-                let (tx, rx) = channel::bounded(16); // TODO: ?!
-                inner.active_connections.insert(connection_id, tx);
-
-                // Handle the connection in a separate task.
-                (inner.tasks_executor)(Box::pin(tasks::connection_task(
-                    inner.log_callback.clone(),
-                    multiaddr.to_string(),
-                    socket,
-                    connection_id,
-                    connection_task,
-                    rx,
-                    inner.to_background_tx.clone(),
-                )));
-
-                inner.process_network_service_events = true;
+                if start_dial(&mut inner, &peer_id) {
+                    inner.request_dials.get_mut(&peer_id).unwrap().dialing = true;
+                } else {
+                    for waiter in inner.request_dials.remove(&peer_id).unwrap().waiters {
+                        let _ = waiter.send(false);
+                    }
+                }
             }
         }
 
@@ -1540,6 +3383,12 @@ async fn background_task(mut inner: Inner) {
         }
 
         let message = {
+            let next_reserved_redial = inner
+                .reserved_peers_redial
+                .values()
+                .filter_map(|r| r.when)
+                .min();
+
             let foreground_msg = async { Some(inner.to_background_rx.next().await) };
             let sending_done = async {
                 if let either::Right(sending) = &mut inner.event_senders {
@@ -1551,7 +3400,17 @@ async fn background_task(mut inner: Inner) {
                 }
             };
 
-            match foreground_msg.or(sending_done).await {
+            let clock = inner.clock.clone();
+            let reserved_redial = async move {
+                if let Some(when) = next_reserved_redial {
+                    clock.sleep_until(when).await;
+                    Some(Some(ToBackground::RedialReservedPeers))
+                } else {
+                    future::pending().await
+                }
+            };
+
+            match foreground_msg.or(sending_done).or(reserved_redial).await {
                 Some(msg) => msg.unwrap(),
                 None => continue,
             }
@@ -1573,6 +3432,9 @@ async fn background_task(mut inner: Inner) {
                 if connection_now_dead {
                     let _was_in = inner.active_connections.remove(&connection_id);
                     debug_assert!(_was_in.is_some());
+                    inner
+                        .prometheus_service
+                        .set_network_connections(inner.network.num_connections());
                 }
 
                 inner.process_network_service_events = true;
@@ -1595,9 +3457,14 @@ async fn background_task(mut inner: Inner) {
 
                 let (tx, rx) = channel::bounded(16); // TODO: ?!
                 inner.active_connections.insert(connection_id, tx);
+                inner
+                    .prometheus_service
+                    .set_network_connections(inner.network.num_connections());
 
                 (inner.tasks_executor)(Box::pin(tasks::connection_task(
                     inner.log_callback.clone(),
+                    inner.clock.clone(),
+                    inner.prometheus_service.connection_bandwidth(&multiaddr),
                     multiaddr.to_string(),
                     async move { Ok(socket) },
                     connection_id,
@@ -1643,59 +3510,454 @@ async fn background_task(mut inner: Inner) {
                         // TODO: log message
                     }
                 }
-
-                let _ = when_done.send(());
-
-                inner.process_network_service_events = true;
-            }
-
-            ToBackground::ForegroundShutdown => {
-                // TODO: do a clean shutdown of all the connections
-                return;
+
+                let _ = when_done.send(());
+
+                inner.process_network_service_events = true;
+            }
+
+            ToBackground::ForegroundShutdown => {
+                // The address books are written synchronously, as the process is likely about
+                // to exit.
+                for (path, content) in address_books_to_save(&inner) {
+                    if let Err(error) = write_address_book(&path, &content) {
+                        inner.log_callback.log(
+                            LogLevel::Warn,
+                            format!(
+                                "address-book-save-error; path={}; error={}",
+                                path.display(),
+                                error
+                            ),
+                        );
+                    }
+                }
+
+                // TODO: do a clean shutdown of all the connections
+                return;
+            }
+            ToBackground::StateRequestInResponse {
+                substream_id,
+                response,
+            } => {
+                // The request might have been cancelled by the remote in the meantime.
+                if !inner.state_requests_in.remove(&substream_id) {
+                    continue;
+                }
+
+                inner.network.respond_state(
+                    substream_id,
+                    response.as_ref().map(|groups| codec::StateResponse {
+                        entries: groups
+                            .iter()
+                            .map(
+                                |(state_root, entries, complete)| codec::StateResponseEntries {
+                                    state_root,
+                                    entries: entries
+                                        .iter()
+                                        .map(|(key, value)| (&key[..], &value[..]))
+                                        .collect(),
+                                    complete: *complete,
+                                },
+                            )
+                            .collect(),
+                        proof: &[],
+                    }),
+                );
+            }
+            ToBackground::DecayReputations => {
+                inner.peering_strategy.decay_reputations();
+            }
+            ToBackground::SaveAddressBooks => {
+                for (path, content) in address_books_to_save(&inner) {
+                    let log_callback = inner.log_callback.clone();
+                    (inner.tasks_executor)(Box::pin(async move {
+                        let result = smol::unblock({
+                            let path = path.clone();
+                            move || write_address_book(&path, &content)
+                        })
+                        .await;
+                        if let Err(error) = result {
+                            log_callback.log(
+                                LogLevel::Warn,
+                                format!(
+                                    "address-book-save-error; path={}; error={}",
+                                    path.display(),
+                                    error
+                                ),
+                            );
+                        }
+                    }));
+                }
+            }
+
+            ToBackground::ForegroundAnnounceBlock {
+                target,
+                chain_id,
+                scale_encoded_header,
+                is_best,
+                result_tx,
+            } => {
+                let _ = result_tx.send(inner.network.gossip_send_block_announce(
+                    &target,
+                    chain_id,
+                    &scale_encoded_header,
+                    is_best,
+                ));
+            }
+            ToBackground::ForegroundAnnounceCollation {
+                chain_id,
+                collation,
+            } => {
+                let Some(collator) = &mut inner.network[chain_id].collator else {
+                    continue;
+                };
+
+                if collator.collations.len() >= MAX_KEPT_COLLATIONS {
+                    collator.collations.pop_front();
+                }
+                let relay_parent = collation.relay_parent;
+                collator.collations.push_back(collation);
+
+                let authorities = collator.authorities.iter().cloned().collect::<Vec<_>>();
+                let connected = collator.connected.iter().cloned().collect::<Vec<_>>();
+
+                for peer_id in authorities {
+                    if !connected.contains(&peer_id) {
+                        // An error is returned if the substream is already opening, in which
+                        // case the collation will be advertised once it is open.
+                        let _ = inner.network.collation_open(chain_id, &peer_id);
+                    }
+                }
+
+                for peer_id in connected {
+                    let _ = inner.network.collation_send(
+                        &peer_id,
+                        chain_id,
+                        codec::CollationProtocolMessageRef::AdvertiseCollation {
+                            relay_parent: &relay_parent,
+                        },
+                    );
+                }
+            }
+            ToBackground::ForegroundSetLocalBestBlock {
+                chain_id,
+                best_hash,
+                best_number,
+            } => {
+                inner
+                    .network
+                    .set_chain_local_best_block(chain_id, best_hash, best_number);
+            }
+            ToBackground::ForegroundBlocksRequest {
+                target,
+                chain_id,
+                config,
+                result_tx,
+            } => {
+                match inner.network.start_blocks_request(
+                    &target,
+                    chain_id,
+                    config,
+                    Duration::from_secs(12),
+                ) {
+                    Ok(request_id) => {
+                        // TODO: somehow cancel the request if the `rx` is dropped?
+                        inner.blocks_requests.insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestError::NoConnection) => {
+                        let _ = result_tx.send(Err(BlocksRequestError::NoConnection));
+                    }
+                }
+            }
+            ToBackground::ForegroundWarpSyncRequest {
+                target,
+                chain_id,
+                begin_hash,
+                result_tx,
+            } => {
+                // The timeout needs to be long enough to potentially download the maximum
+                // response size of 16 MiB.
+                match inner.network.start_grandpa_warp_sync_request(
+                    &target,
+                    chain_id,
+                    begin_hash,
+                    Duration::from_secs(24),
+                ) {
+                    Ok(request_id) => {
+                        inner.warp_sync_requests.insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestError::NoConnection) => {
+                        let _ = result_tx.send(Err(WarpSyncRequestError::NoConnection));
+                    }
+                }
+            }
+            ToBackground::ForegroundStorageProofRequest {
+                target,
+                chain_id,
+                config,
+                result_tx,
+            } => {
+                match inner.network.start_storage_proof_request(
+                    &target,
+                    chain_id,
+                    config,
+                    Duration::from_secs(12),
+                ) {
+                    Ok(request_id) => {
+                        inner.storage_proof_requests.insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestMaybeTooLargeError::NoConnection) => {
+                        let _ = result_tx.send(Err(StorageProofRequestError::NoConnection));
+                    }
+                    Err(service::StartRequestMaybeTooLargeError::RequestTooLarge) => {
+                        let _ = result_tx.send(Err(StorageProofRequestError::RequestTooLarge));
+                    }
+                }
+            }
+            ToBackground::ForegroundCallProofRequest {
+                target,
+                chain_id,
+                config,
+                result_tx,
+            } => {
+                match inner.network.start_call_proof_request(
+                    &target,
+                    chain_id,
+                    config,
+                    Duration::from_secs(12),
+                ) {
+                    Ok(request_id) => {
+                        inner.call_proof_requests.insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestMaybeTooLargeError::NoConnection) => {
+                        let _ = result_tx.send(Err(CallProofRequestError::NoConnection));
+                    }
+                    Err(service::StartRequestMaybeTooLargeError::RequestTooLarge) => {
+                        let _ = result_tx.send(Err(CallProofRequestError::RequestTooLarge));
+                    }
+                }
             }
-
-            ToBackground::ForegroundAnnounceBlock {
+            ToBackground::ForegroundStateRequest {
                 target,
                 chain_id,
-                scale_encoded_header,
-                is_best,
+                block_hash,
+                child_trie,
+                start_key,
                 result_tx,
             } => {
-                let _ = result_tx.send(inner.network.gossip_send_block_announce(
+                let start_key = match &child_trie {
+                    None => codec::StateRequestStart::MainTrie(&start_key),
+                    Some(child_trie) => codec::StateRequestStart::ChildTrieDefault {
+                        child_trie,
+                        key: &start_key,
+                    },
+                };
+
+                match inner.network.start_state_request(
                     &target,
                     chain_id,
-                    &scale_encoded_header,
-                    is_best,
-                ));
+                    &block_hash,
+                    start_key,
+                    true,
+                    Duration::from_secs(24),
+                ) {
+                    Ok(request_id) => {
+                        inner.state_requests.insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestError::NoConnection) => {
+                        let _ = result_tx.send(Err(StateRequestError::NoConnection));
+                    }
+                }
             }
-            ToBackground::ForegroundSetLocalBestBlock {
+            ToBackground::ForegroundKademliaGetValueRequest {
+                target,
                 chain_id,
-                best_hash,
-                best_number,
+                key,
+                result_tx,
             } => {
-                inner
-                    .network
-                    .set_chain_local_best_block(chain_id, best_hash, best_number);
+                match inner.network.start_kademlia_get_value_request(
+                    &target,
+                    chain_id,
+                    &key,
+                    Duration::from_secs(20),
+                ) {
+                    Ok(request_id) => {
+                        inner
+                            .kademlia_get_value_requests
+                            .insert(request_id, result_tx);
+                    }
+                    Err(service::StartRequestError::NoConnection) => {
+                        let _ = result_tx.send(Err(KademliaGetValueRequestError::NoConnection));
+                    }
+                }
             }
-            ToBackground::ForegroundBlocksRequest {
+            ToBackground::ForegroundKademliaPutValueRequest {
                 target,
                 chain_id,
-                config,
+                key,
+                value,
                 result_tx,
             } => {
-                match inner.network.start_blocks_request(
+                match inner.network.start_kademlia_put_value_request(
                     &target,
                     chain_id,
-                    config,
-                    Duration::from_secs(12),
+                    &key,
+                    &value,
+                    Duration::from_secs(20),
                 ) {
                     Ok(request_id) => {
-                        // TODO: somehow cancel the request if the `rx` is dropped?
-                        inner.blocks_requests.insert(request_id, result_tx);
+                        inner
+                            .kademlia_put_value_requests
+                            .insert(request_id, result_tx);
                     }
                     Err(service::StartRequestError::NoConnection) => {
-                        let _ = result_tx.send(Err(BlocksRequestError::NoConnection));
+                        let _ = result_tx.send(Err(KademliaPutValueRequestError::NoConnection));
+                    }
+                }
+            }
+            ToBackground::ForegroundKademliaClosestPeers {
+                chain_id,
+                key,
+                result_tx,
+            } => {
+                let _ = result_tx.send(
+                    inner.network[chain_id]
+                        .kbuckets
+                        .closest_entries(&key)
+                        .map(|(peer_id, ())| peer_id.clone())
+                        .take(20) // TODO: constant
+                        .collect(),
+                );
+            }
+            ToBackground::ForegroundConnectForRequests { peer_id, result_tx } => {
+                if inner.network.can_start_requests(&peer_id) {
+                    let _ = result_tx.send(true);
+                    continue;
+                }
+
+                inner
+                    .request_dials
+                    .entry(peer_id)
+                    .or_insert(RequestDial {
+                        dialing: false,
+                        waiters: Vec::new(),
+                    })
+                    .waiters
+                    .push(result_tx);
+                inner.process_network_service_events = true;
+            }
+            ToBackground::ForegroundGetLocalListenAddresses { result_tx } => {
+                let _ = result_tx.send(local_listen_addresses(&inner));
+            }
+            ToBackground::ForegroundDiscoveredAddresses {
+                chain_id,
+                peer_id,
+                addresses,
+            } => {
+                insert_discovered_addresses(&mut inner, chain_id, peer_id, addresses);
+            }
+            ToBackground::RedialReservedPeers => {
+                let now = inner.clock.now();
+                for redial in inner.reserved_peers_redial.values_mut() {
+                    if redial.when.map_or(false, |when| when <= now) {
+                        redial.when = None;
+                    }
+                }
+
+                inner.process_network_service_events = true;
+            }
+            ToBackground::ForegroundAddReservedPeer {
+                chain_id,
+                peer_id,
+                address,
+            } => {
+                if inner
+                    .peering_strategy
+                    .insert_reserved_chain_peer(chain_id, peer_id.clone())
+                {
+                    inner.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "reserved-peer-added; peer_id={}; chain={}",
+                            peer_id, inner.network[chain_id].log_name
+                        ),
+                    );
+                }
+
+                inner
+                    .peering_strategy
+                    .insert_address(&peer_id, address.into_vec(), 10); // TODO: constant
+                inner.process_network_service_events = true;
+            }
+            ToBackground::ForegroundReportPeer {
+                chain_id,
+                peer_id,
+                change,
+            } => match inner.peering_strategy.report(&chain_id, &peer_id, change) {
+                basic_peering_strategy::ReportResult::Banned { had_slot } => {
+                    inner.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "peer-banned; peer_id={}; chain={}; reason=reputation; had_slot={:?}",
+                            peer_id, inner.network[chain_id].log_name, had_slot
+                        ),
+                    );
+                    inner.network.gossip_remove_desired(
+                        chain_id,
+                        &peer_id,
+                        service::GossipKind::ConsensusTransactions,
+                    );
+                    let _ = inner.network.gossip_close(
+                        chain_id,
+                        &peer_id,
+                        service::GossipKind::ConsensusTransactions,
+                    );
+                    inner.process_network_service_events = true;
+                }
+                basic_peering_strategy::ReportResult::Adjusted
+                | basic_peering_strategy::ReportResult::UnknownPeerChain => {}
+            },
+            ToBackground::ForegroundRemoveReservedPeer { chain_id, peer_id } => {
+                if inner
+                    .peering_strategy
+                    .remove_reserved_chain_peer(&chain_id, &peer_id)
+                {
+                    inner.log_callback.log(
+                        LogLevel::Debug,
+                        format!(
+                            "reserved-peer-removed; peer_id={}; chain={}",
+                            peer_id, inner.network[chain_id].log_name
+                        ),
+                    );
+
+                    // The peer now goes through the normal slots assignment process.
+                    inner.peering_strategy.unassign_slot_and_ban(
+                        &chain_id,
+                        &peer_id,
+                        inner.clock.now(),
+                    );
+                    inner.network.gossip_remove_desired(
+                        chain_id,
+                        &peer_id,
+                        service::GossipKind::ConsensusTransactions,
+                    );
+                    if inner.network[chain_id].reserved_only {
+                        let _ = inner.network.gossip_close(
+                            chain_id,
+                            &peer_id,
+                            service::GossipKind::ConsensusTransactions,
+                        );
+                    }
+
+                    if !inner
+                        .network
+                        .chains()
+                        .any(|c| inner.peering_strategy.is_reserved(&c, &peer_id))
+                    {
+                        inner.reserved_peers_redial.remove(&peer_id);
                     }
+
+                    inner.process_network_service_events = true;
                 }
             }
             ToBackground::ForegroundGetNumConnections { result_tx } => {
@@ -1737,6 +3999,345 @@ async fn background_task(mut inner: Inner) {
     }
 }
 
+/// Inserts in the k-buckets and in the peering strategy addresses of a peer of the given chain
+/// that have been discovered.
+/// Returns the reputation change to apply to a peer that has answered a request with the given
+/// error, or `None` if the error isn't the fault of the peer.
+fn request_error_reputation_change(
+    error: &service::RequestError,
+) -> Option<basic_peering_strategy::ReputationChange> {
+    match error {
+        service::RequestError::Substream(connection::established::RequestError::Timeout) => {
+            Some(basic_peering_strategy::ReputationChange::Timeout)
+        }
+        err if err.is_protocol_error() => {
+            Some(basic_peering_strategy::ReputationChange::BadResponse)
+        }
+        _ => None,
+    }
+}
+
+/// Returns, for each chain that has a [`ChainConfig::address_book_path`], the path and the
+/// content of the address book to write on disk.
+fn address_books_to_save(inner: &Inner) -> Vec<(PathBuf, Vec<u8>)> {
+    inner
+        .network
+        .chains()
+        .filter_map(|chain_id| {
+            let path = inner.network[chain_id].address_book_path.clone()?;
+            let content =
+                address_book::encode(inner.peering_strategy.chain_peers_unordered(&chain_id).map(
+                    |peer_id| {
+                        let addresses = inner
+                            .peering_strategy
+                            .peer_addresses(peer_id)
+                            .filter_map(|addr| Multiaddr::try_from(addr.to_vec()).ok())
+                            .collect();
+                        let protocols = inner
+                            .peering_strategy
+                            .peer_protocols(peer_id)
+                            .map(|p| p.to_owned())
+                            .collect();
+                        (peer_id, addresses, protocols)
+                    },
+                ));
+            Some((path, content))
+        })
+        .collect()
+}
+
+/// Writes an address book on disk. The content is first written to a temporary file which is
+/// then renamed, so that the address book is never left half-written.
+fn write_address_book(path: &Path, content: &[u8]) -> Result<(), io::Error> {
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, content)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Starts opening a connection to the given peer, using one of its addresses found in the
+/// address book. Returns `false` if the address book doesn't contain any valid address for this
+/// peer.
+fn start_dial(inner: &mut Inner, peer_id: &PeerId) -> bool {
+    loop {
+        let Some(multiaddr) = inner.peering_strategy.addr_to_connected(peer_id) else {
+            return false;
+        };
+
+        let multiaddr = match multiaddr::Multiaddr::try_from(multiaddr.to_owned()) {
+            Ok(a) => a,
+            Err(multiaddr::FromVecError { addr }) => {
+                // Address is in an invalid format.
+                inner.log_callback.log(
+                    LogLevel::Debug,
+                    format!("invalid-address; peer_id={}; address={:?}", peer_id, addr),
+                );
+                let _was_in = inner.peering_strategy.remove_address(peer_id, &addr);
+                debug_assert!(_was_in);
+                continue;
+            }
+        };
+
+        // Convert the `multiaddr` (typically of the form `/ip4/a.b.c.d/tcp/d`) into
+        // a `Future<dyn Output = Result<TcpStream, ...>>`.
+        let socket = match tasks::multiaddr_to_socket(&multiaddr) {
+            Ok(socket) => socket,
+            Err(_) => {
+                // Address is in an invalid format or isn't supported.
+                inner.log_callback.log(
+                    LogLevel::Debug,
+                    format!(
+                        "invalid-address; peer_id={}; address={}",
+                        peer_id, multiaddr
+                    ),
+                );
+                let _was_in = inner
+                    .peering_strategy
+                    .remove_address(peer_id, multiaddr.as_ref());
+                debug_assert!(_was_in);
+                continue;
+            }
+        };
+
+        inner.num_pending_out_attempts += 1;
+        let (connection_id, connection_task) = inner.network.add_single_stream_connection(
+            inner.clock.now(),
+            service::SingleStreamHandshakeKind::MultistreamSelectNoiseYamux {
+                is_initiator: true,
+                noise_key: &inner.noise_key,
+            },
+            multiaddr.clone().into_vec(),
+            Some(peer_id.clone()),
+        );
+
+        let (tx, rx) = channel::bounded(16); // TODO: ?!
+        inner.active_connections.insert(connection_id, tx);
+        inner
+            .prometheus_service
+            .set_network_connections(inner.network.num_connections());
+
+        // Handle the connection in a separate task.
+        (inner.tasks_executor)(Box::pin(tasks::connection_task(
+            inner.log_callback.clone(),
+            inner.clock.clone(),
+            inner.prometheus_service.connection_bandwidth(&multiaddr),
+            multiaddr.to_string(),
+            socket,
+            connection_id,
+            connection_task,
+            rx,
+            inner.to_background_tx.clone(),
+        )));
+
+        inner.process_network_service_events = true;
+        return true;
+    }
+}
+
+/// If the given peer is reserved on any chain, removes it from the list of desired gossip links
+/// of these chains and schedules inserting it back after a backoff delay.
+///
+/// Must be called after a connection or gossip link attempt with the given peer has failed.
+fn schedule_reserved_peer_redial(inner: &mut Inner, peer_id: &PeerId) {
+    let mut is_reserved = false;
+    for chain_id in inner.network.chains().collect::<Vec<_>>() {
+        if !inner.peering_strategy.is_reserved(&chain_id, peer_id) {
+            continue;
+        }
+
+        is_reserved = true;
+        inner.network.gossip_remove_desired(
+            chain_id,
+            peer_id,
+            service::GossipKind::ConsensusTransactions,
+        );
+    }
+
+    if !is_reserved {
+        return;
+    }
+
+    let now = inner.clock.now();
+    let redial = inner
+        .reserved_peers_redial
+        .entry(peer_id.clone())
+        .or_insert(ReservedPeerRedial {
+            when: None,
+            next_backoff: Duration::from_secs(1),
+        });
+
+    if redial.when.is_some() {
+        // Redial already scheduled.
+        return;
+    }
+
+    let backoff = redial.next_backoff;
+    redial.when = Some(now + backoff);
+    redial.next_backoff = cmp::min(backoff * 2, Duration::from_secs(60));
+
+    inner.log_callback.log(
+        LogLevel::Debug,
+        format!(
+            "reserved-peer-redial-scheduled; peer_id={}; delay={:?}",
+            peer_id, backoff
+        ),
+    );
+}
+
+/// Updates the number of peers reported in the metrics of the given chain.
+///
+/// Must be called after a gossip link with a peer has been opened or closed.
+fn update_peers_metric(inner: &Inner, chain_id: ChainId) {
+    let num_peers = inner
+        .network
+        .gossip_connected_peers(chain_id, service::GossipKind::ConsensusTransactions)
+        .count();
+    inner.network[chain_id].metrics.set_peers(num_peers);
+}
+
+fn insert_discovered_addresses(
+    inner: &mut Inner,
+    chain_id: ChainId,
+    peer_id: PeerId,
+    addresses: Vec<Multiaddr>,
+) {
+    if !addresses.is_empty() {
+        let _ = inner.network[chain_id].kbuckets.entry(&peer_id).or_insert(
+            (),
+            &inner.clock.now(),
+            kademlia::kbuckets::PeerState::Disconnected,
+        );
+
+        // Note that we must call this function before `insert_address`, as documented in
+        // `basic_peering_strategy`.
+        if let basic_peering_strategy::InsertChainPeerResult::Inserted {
+            peer_removed: Some(peer_removed),
+        } = inner.peering_strategy.insert_chain_peer(
+            chain_id,
+            peer_id.clone(),
+            100, // TODO: constant
+        ) {
+            inner.log_callback.log(
+                LogLevel::Debug,
+                format!(
+                    "peer-forgotten; peer_id={}; chain={}",
+                    peer_removed, inner.network[chain_id].log_name
+                ),
+            );
+        }
+    }
+
+    for addr in addresses {
+        match inner.peering_strategy.insert_address(&peer_id, addr.into_vec(), 10) // TODO: constant
+        {
+            basic_peering_strategy::InsertAddressResult::Inserted {
+                address_removed: Some(addr_rm),
+            } => {
+                let addr_rm = Multiaddr::try_from(addr_rm).unwrap();
+                inner.log_callback.log(
+                    LogLevel::Debug,
+                    format!("address-purged; peer_id={}; address={}", peer_id, addr_rm),
+                );
+            }
+            basic_peering_strategy::InsertAddressResult::UnknownPeer => unreachable!(),
+            _ => {}
+        }
+    }
+}
+
+/// Returns the list of addresses the local node is reachable at, in order to report them to
+/// other nodes.
+///
+/// Listen addresses whose IP address is unspecified (i.e. `0.0.0.0` or `::`) are filtered out,
+/// as they are meaningless for remotes.
+fn local_listen_addresses(inner: &Inner) -> Vec<Multiaddr> {
+    let mut addresses = inner
+        .listen_addresses
+        .iter()
+        .filter(|addr| match addr.iter().next() {
+            Some(ProtocolRef::Ip4(ip)) => !IpAddr::from(ip).is_unspecified(),
+            Some(ProtocolRef::Ip6(ip)) => !IpAddr::from(ip).is_unspecified(),
+            _ => true,
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    for addr in inner.observed_addresses.confirmed_addresses() {
+        let addr = Multiaddr::try_from(addr.to_vec()).unwrap();
+        if !addresses.contains(&addr) {
+            addresses.push(addr);
+        }
+    }
+
+    addresses
+}
+
+/// Translates an address of the local node as observed by a remote into an address that other
+/// nodes can connect to.
+///
+/// If the connection was opened by the local node, the TCP port observed by the remote is the
+/// ephemeral port of the outgoing connection rather than a port the local node listens on. The
+/// port of the observed address is thus replaced with the one of the listen address that uses the
+/// same protocols. Returns `None` if there isn't any such listen address, in which case the local
+/// node can't be reached through the observed address anyway.
+fn translate_observed_address(
+    observed: &Multiaddr,
+    listen_addresses: &[Multiaddr],
+) -> Option<Multiaddr> {
+    let mut observed_iter = observed.iter();
+    let observed_ip = observed_iter.next()?;
+    let Some(ProtocolRef::Tcp(_)) = observed_iter.next() else {
+        return None;
+    };
+    let observed_rest = observed_iter.collect::<Vec<_>>();
+
+    listen_addresses.iter().find_map(|listen_addr| {
+        let mut listen_iter = listen_addr.iter();
+        match (&observed_ip, listen_iter.next()?) {
+            (ProtocolRef::Ip4(_), ProtocolRef::Ip4(_))
+            | (ProtocolRef::Ip6(_), ProtocolRef::Ip6(_)) => {}
+            _ => return None,
+        }
+        let Some(ProtocolRef::Tcp(listen_port)) = listen_iter.next() else {
+            return None;
+        };
+        if listen_iter.collect::<Vec<_>>() != observed_rest {
+            return None;
+        }
+
+        Some(
+            [observed_ip.clone(), ProtocolRef::Tcp(listen_port)]
+                .into_iter()
+                .chain(observed_rest.iter().cloned())
+                .collect(),
+        )
+    })
+}
+
+/// Builds the list of nodes closest to the given key that the local node knows about, in order
+/// to answer an inbound Kademlia request. The remote that has sent the request is excluded.
+fn kademlia_closer_peers(
+    inner: &Inner,
+    chain_id: ChainId,
+    key: &[u8],
+    requester: &PeerId,
+) -> Vec<(PeerId, Vec<Vec<u8>>)> {
+    inner.network[chain_id]
+        .kbuckets
+        .closest_entries(key)
+        .filter(|(peer_id, _)| *peer_id != requester)
+        .map(|(peer_id, ())| {
+            let addresses = inner
+                .peering_strategy
+                .peer_addresses(peer_id)
+                .map(|addr| addr.to_vec())
+                .collect::<Vec<_>>();
+            (peer_id.clone(), addresses)
+        })
+        .filter(|(_, addresses)| !addresses.is_empty())
+        .take(20) // TODO: constant
+        .collect()
+}
+
 /// Builds the response to a block request by reading from the given database.
 async fn blocks_request_response(
     database: &database_thread::DatabaseThread,
@@ -1815,3 +4416,199 @@ async fn blocks_request_response(
         })
         .await
 }
+
+/// Builds the response to a state request by reading from the given database.
+///
+/// Returns a list of groups of storage entries, each made of the Merkle value of the root of
+/// the trie (empty for the main trie), the storage entries, and whether no storage entry can be
+/// found after the last one. Like in Substrate, the content of a child trie immediately follows
+/// the entry of the main trie that contains its root.
+///
+/// Returns `Ok(None)` if the request targets a child trie that doesn't exist.
+async fn state_request_response(
+    database: &database_thread::DatabaseThread,
+    block_hash: [u8; 32],
+    child_trie: Option<Vec<u8>>,
+    start_key: Vec<u8>,
+) -> Result<Option<Vec<StateResponseGroup>>, full_sqlite::StorageAccessError> {
+    database
+        .with_database(move |database| {
+            let mut groups = vec![(Vec::new(), Vec::new(), false)];
+            let mut response_size = 0;
+
+            let main_trie_start = match child_trie {
+                Some(child_trie) => {
+                    let child_trie_key = [CHILD_TRIE_PREFIX, &child_trie].concat();
+                    let Some((state_root, _)) = database.block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        trie::bytes_to_nibbles(child_trie_key.iter().copied()).map(u8::from),
+                    )?
+                    else {
+                        return Ok(None);
+                    };
+
+                    let complete = state_response_child_trie(
+                        database,
+                        &block_hash,
+                        &child_trie_key,
+                        state_root,
+                        &start_key,
+                        &mut groups,
+                        &mut response_size,
+                    )?;
+                    if !complete {
+                        return Ok(Some(groups));
+                    }
+
+                    child_trie_key
+                }
+                None => start_key,
+            };
+
+            let mut key_nibbles = state_response_start_nibbles(&main_trie_start);
+
+            loop {
+                if response_size >= STATE_RESPONSE_MAX_SIZE {
+                    return Ok(Some(groups));
+                }
+
+                let Some(next_key) = database.block_storage_next_key(
+                    &block_hash,
+                    iter::empty::<iter::Empty<_>>(),
+                    key_nibbles.iter().copied(),
+                    iter::empty(),
+                    false,
+                )?
+                else {
+                    groups[0].2 = true;
+                    return Ok(Some(groups));
+                };
+
+                let (value, _) = database
+                    .block_storage_get(
+                        &block_hash,
+                        iter::empty::<iter::Empty<_>>(),
+                        next_key.iter().copied(),
+                    )?
+                    .unwrap(); // A panic here would indicate a bug in the database code.
+
+                let key = nibbles_to_key(&next_key);
+                response_size += key.len() + value.len();
+
+                if key.starts_with(CHILD_TRIE_PREFIX) {
+                    groups[0].1.push((key.clone(), value.clone()));
+                    let complete = state_response_child_trie(
+                        database,
+                        &block_hash,
+                        &key,
+                        value,
+                        &[],
+                        &mut groups,
+                        &mut response_size,
+                    )?;
+                    if !complete {
+                        return Ok(Some(groups));
+                    }
+                } else {
+                    groups[0].1.push((key, value));
+                }
+
+                key_nibbles = next_key;
+                key_nibbles.push(0);
+            }
+        })
+        .await
+}
+
+/// Group of storage entries of a response to a state request. See [`state_request_response`].
+type StateResponseGroup = (Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>, bool);
+
+/// Responses to state requests are limited to 2 MiB of keys and values, like in Substrate.
+const STATE_RESPONSE_MAX_SIZE: usize = 2 * 1024 * 1024;
+
+/// Prefix of the keys of the main trie that contain the root of a default child trie.
+const CHILD_TRIE_PREFIX: &[u8] = b":child_storage:default:";
+
+/// Adds to `groups` the entries of the given child trie that follow `start_key`. Returns `true`
+/// if the child trie is complete.
+///
+/// Nothing is added if the size limit of the response is already reached.
+fn state_response_child_trie(
+    database: &full_sqlite::SqliteFullDatabase,
+    block_hash: &[u8; 32],
+    child_trie_key: &[u8],
+    state_root: Vec<u8>,
+    start_key: &[u8],
+    groups: &mut Vec<StateResponseGroup>,
+    response_size: &mut usize,
+) -> Result<bool, full_sqlite::StorageAccessError> {
+    if *response_size >= STATE_RESPONSE_MAX_SIZE {
+        return Ok(false);
+    }
+
+    let trie_path = trie::bytes_to_nibbles(child_trie_key.iter().copied())
+        .map(u8::from)
+        .collect::<Vec<_>>();
+    let mut key_nibbles = state_response_start_nibbles(start_key);
+    let mut entries = Vec::new();
+
+    let complete = loop {
+        if *response_size >= STATE_RESPONSE_MAX_SIZE {
+            break false;
+        }
+
+        let Some(next_key) = database.block_storage_next_key(
+            block_hash,
+            iter::once(trie_path.iter().copied()),
+            key_nibbles.iter().copied(),
+            iter::empty(),
+            false,
+        )?
+        else {
+            break true;
+        };
+
+        let (value, _) = database
+            .block_storage_get(
+                block_hash,
+                iter::once(trie_path.iter().copied()),
+                next_key.iter().copied(),
+            )?
+            .unwrap(); // A panic here would indicate a bug in the database code.
+
+        let key = nibbles_to_key(&next_key);
+        *response_size += key.len() + value.len();
+        entries.push((key, value));
+
+        key_nibbles = next_key;
+        key_nibbles.push(0);
+    };
+
+    groups.push((state_root, entries, complete));
+    Ok(complete)
+}
+
+/// Returns the nibbles to pass to `block_storage_next_key` in order to find the first key
+/// strictly after `start_key`, or the first key of the trie if `start_key` is empty.
+fn state_response_start_nibbles(start_key: &[u8]) -> Vec<u8> {
+    let mut key_nibbles = trie::bytes_to_nibbles(start_key.iter().copied())
+        .map(u8::from)
+        .collect::<Vec<_>>();
+    // Pushing an extra nibble prevents `block_storage_next_key` from returning the start key.
+    if !key_nibbles.is_empty() {
+        key_nibbles.push(0);
+    }
+    key_nibbles
+}
+
+/// Converts a key returned by `block_storage_next_key` to bytes.
+fn nibbles_to_key(key_nibbles: &[u8]) -> Vec<u8> {
+    trie::nibbles_to_bytes_truncate(
+        key_nibbles
+            .iter()
+            .copied()
+            .map(|n| trie::Nibble::try_from(n).unwrap()),
+    )
+    .collect()
+}
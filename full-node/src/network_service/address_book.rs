@@ -0,0 +1,86 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encoding and decoding of the address book of a chain, in order to store it on disk and load
+//! it back when the node restarts.
+//!
+//! The address book is stored as a JSON document listing the known peers of the chain, their
+//! addresses, and the protocols they have reported supporting.
+
+use smoldot::libp2p::{Multiaddr, PeerId};
+
+/// Encodes the given list of peers, their addresses and their protocols into the format of the
+/// file on disk.
+pub fn encode<'a>(
+    peers: impl Iterator<Item = (&'a PeerId, Vec<Multiaddr>, Vec<String>)>,
+) -> Vec<u8> {
+    let address_book = AddressBook {
+        peers: peers
+            .map(|(peer_id, addresses, protocols)| Peer {
+                peer_id: peer_id.to_base58(),
+                addresses: addresses.iter().map(|a| a.to_string()).collect(),
+                protocols,
+            })
+            .collect(),
+    };
+
+    serde_json::to_vec_pretty(&address_book).unwrap()
+}
+
+/// Decodes a file previously generated with [`encode`].
+///
+/// Peers or addresses that fail to parse are silently ignored, in order to be forward
+/// compatible with addresses that this version of the node doesn't support.
+pub fn decode(
+    file_content: &[u8],
+) -> Result<Vec<(PeerId, Vec<Multiaddr>, Vec<String>)>, DecodeError> {
+    let address_book: AddressBook = serde_json::from_slice(file_content).map_err(DecodeError)?;
+
+    Ok(address_book
+        .peers
+        .into_iter()
+        .filter_map(|peer| {
+            let peer_id = peer.peer_id.parse::<PeerId>().ok()?;
+            let addresses = peer
+                .addresses
+                .iter()
+                .filter_map(|a| a.parse::<Multiaddr>().ok())
+                .collect::<Vec<_>>();
+            Some((peer_id, addresses, peer.protocols))
+        })
+        .collect())
+}
+
+/// Error potentially returned by [`decode`].
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "Failed to parse address book: {_0}")]
+pub struct DecodeError(serde_json::Error);
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct AddressBook {
+    peers: Vec<Peer>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Peer {
+    #[serde(rename = "peerId")]
+    peer_id: String,
+    addresses: Vec<String>,
+    /// Absent from files written by older versions of the node.
+    #[serde(default)]
+    protocols: Vec<String>,
+}
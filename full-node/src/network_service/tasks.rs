@@ -15,10 +15,10 @@
 // You should have received a copy of the GNU General Public License
 // along with this program.  If not, see <http://www.gnu.org/licenses/>.
 
-use crate::{LogCallback, LogLevel};
+use crate::{prometheus_service, Clock, LogCallback, LogLevel};
 use core::future::Future;
 use futures_lite::future;
-use futures_util::StreamExt as _;
+use futures_util::{future::Either, StreamExt as _};
 use smol::{
     channel,
     future::FutureExt as _,
@@ -26,6 +26,7 @@ use smol::{
 };
 use smoldot::{
     libp2p::{
+        memory,
         multiaddr::{Multiaddr, ProtocolRef},
         websocket, with_buffers,
     },
@@ -43,8 +44,11 @@ pub(super) trait AsyncReadWrite: AsyncRead + AsyncWrite {}
 impl<T> AsyncReadWrite for T where T: AsyncRead + AsyncWrite {}
 
 /// Asynchronous task managing a specific connection.
+#[allow(clippy::too_many_arguments)] // TODO: group some of these parameters
 pub(super) async fn connection_task(
     log_callback: Arc<dyn LogCallback + Send + Sync>,
+    clock: Clock,
+    bandwidth: prometheus_service::ConnectionBandwidth,
     address: String,
     socket: impl Future<Output = Result<impl AsyncReadWrite, io::Error>>,
     connection_id: service::ConnectionId,
@@ -65,7 +69,7 @@ pub(super) async fn connection_task(
         // processing the socket might generate a message, we only process the socket if no
         // message is currently being sent.
         if message_sending.is_none() {
-            if let Ok(mut socket_read_write) = socket.as_mut().read_write_access(Instant::now()) {
+            if let Ok(mut socket_read_write) = socket.as_mut().read_write_access(clock.now()) {
                 let read_bytes_before = socket_read_write.read_bytes;
                 let written_bytes_before = socket_read_write.write_bytes_queued;
                 let write_closed = socket_read_write.write_bytes_queueable.is_none();
@@ -76,6 +80,11 @@ pub(super) async fn connection_task(
                     || socket_read_write.write_bytes_queued != written_bytes_before
                     || (!write_closed && socket_read_write.write_bytes_queueable.is_none())
                 {
+                    bandwidth.add(
+                        socket_read_write.read_bytes - read_bytes_before,
+                        socket_read_write.write_bytes_queued - written_bytes_before,
+                    );
+
                     log_callback.log(
                         LogLevel::Trace,
                         format!(
@@ -154,9 +163,11 @@ pub(super) async fn connection_task(
                 // is `None`, we also call `wait_read_write_again` only when `message_sending` is
                 // `None`.
                 let fut = if message_sending.is_none() {
-                    Some(socket.as_mut().wait_read_write_again(|when| async move {
-                        smol::Timer::at(when).await;
-                    }))
+                    Some(
+                        socket
+                            .as_mut()
+                            .wait_read_write_again(|when| clock.sleep_until(when)),
+                    )
                 } else {
                     None
                 };
@@ -189,7 +200,7 @@ pub(super) async fn connection_task(
 
         match what_happened {
             WhatHappened::CoordinatorMessage(message) => {
-                connection_task.inject_coordinator_message(&Instant::now(), message);
+                connection_task.inject_coordinator_message(&clock.now(), message);
             }
             WhatHappened::CoordinatorDead => return,
             WhatHappened::SocketEvent => {}
@@ -205,13 +216,25 @@ pub(super) fn multiaddr_to_socket(
 ) -> Result<impl Future<Output = Result<impl AsyncReadWrite, io::Error>>, ()> {
     let mut iter = addr.iter().fuse();
     let proto1 = iter.next().ok_or(())?;
-    let proto2 = iter.next().ok_or(())?;
+    let proto2 = iter.next();
     let proto3 = iter.next();
 
     if iter.next().is_some() {
         return Err(());
     }
 
+    // In-process connections don't involve any socket.
+    if let (ProtocolRef::Memory(port), None) = (&proto1, &proto2) {
+        let port = *port;
+        return Ok(Either::Right(async move {
+            memory::connect(port)
+                .map(Either::Right)
+                .map_err(|err| io::Error::new(io::ErrorKind::ConnectionRefused, err.to_string()))
+        }));
+    }
+
+    let proto2 = proto2.ok_or(())?;
+
     // TODO: doesn't support WebSocket secure connections
 
     // Ensure ahead of time that the multiaddress is supported.
@@ -251,7 +274,7 @@ pub(super) fn multiaddr_to_socket(
         _ => return Err(()),
     };
 
-    Ok(async move {
+    Ok(Either::Left(async move {
         let tcp_socket = match addr {
             either::Left(socket_addr) => smol::net::TcpStream::connect(socket_addr).await,
             either::Right((dns, port)) => smol::net::TcpStream::connect((&dns[..], port)).await,
@@ -276,10 +299,10 @@ pub(super) fn multiaddr_to_socket(
                     url: "/",
                 })
                 .await
-                .map(futures_util::future::Either::Right)
+                .map(|socket| Either::Left(Either::Right(socket)))
             }
-            (Ok(tcp_socket), None) => Ok(futures_util::future::Either::Left(tcp_socket)),
+            (Ok(tcp_socket), None) => Ok(Either::Left(Either::Left(tcp_socket))),
             (Err(err), _) => Err(err),
         }
-    })
+    }))
 }
@@ -0,0 +1,551 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus integration.
+//!
+//! See <https://prometheus.io/> for an introduction.
+//!
+//! The [`PrometheusService`] holds the values of the metrics of the node. The other services
+//! update these values as things happen. If [`Config::listen_address`] is `Some`, an HTTP server
+//! is started and exports these values at `/metrics` using the Prometheus text format.
+//!
+//! Metrics that concern a specific chain are found in [`ChainMetrics`], obtained by calling
+//! [`PrometheusService::add_chain`].
+
+use crate::{database_thread, LogCallback, LogLevel};
+
+use smol::{
+    future,
+    io::{AsyncReadExt as _, AsyncWriteExt as _},
+    net::{TcpListener, TcpStream},
+};
+use smoldot::libp2p::multiaddr::{Multiaddr, ProtocolRef};
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    future::Future,
+    io,
+    net::SocketAddr,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Maximum size of the HTTP request headers sent by a client.
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// Maximum time a client has to send its request and read the response before the connection
+/// is closed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Configuration for a [`PrometheusService`].
+pub struct Config<'a> {
+    /// Closure that spawns background tasks.
+    pub tasks_executor: &'a mut dyn FnMut(Pin<Box<dyn Future<Output = ()> + Send>>),
+
+    /// Function called in order to notify of something.
+    pub log_callback: Arc<dyn LogCallback + Send + Sync>,
+
+    /// Where to bind the HTTP server exporting the metrics.
+    ///
+    /// If this is `None`, the service will still be created and the metrics updated, but they
+    /// aren't exported.
+    pub listen_address: Option<SocketAddr>,
+}
+
+pub struct PrometheusService {
+    /// Values of the metrics. Shared with the HTTP server.
+    metrics: Arc<Metrics>,
+
+    /// Address the HTTP server is listening on. Not necessarily equal to
+    /// [`Config::listen_address`].
+    listen_addr: Option<SocketAddr>,
+
+    /// Notified when the service is destroyed.
+    shutdown_notify: event_listener::Event,
+}
+
+impl PrometheusService {
+    pub async fn new(config: Config<'_>) -> Result<Arc<Self>, io::Error> {
+        let metrics = Arc::new(Metrics {
+            network_connections: AtomicU64::new(0),
+            network_bytes: Default::default(),
+            chains: Mutex::new(Vec::new()),
+        });
+
+        let shutdown_notify = event_listener::Event::new();
+
+        let listen_addr = if let Some(listen_address) = config.listen_address {
+            let tcp_listener = TcpListener::bind(listen_address).await?;
+            let listen_addr = tcp_listener.local_addr()?;
+            let mut on_shutdown = shutdown_notify.listen();
+            let metrics = metrics.clone();
+            let log_callback = config.log_callback;
+
+            // Spawn a background task that answers the HTTP requests.
+            (config.tasks_executor)(Box::pin(async move {
+                loop {
+                    let Some(accept_result) = future::or(
+                        async {
+                            (&mut on_shutdown).await;
+                            None
+                        },
+                        async { Some(tcp_listener.accept().await) },
+                    )
+                    .await
+                    else {
+                        break;
+                    };
+
+                    let (socket, address) = match accept_result {
+                        Ok(v) => v,
+                        Err(error) => {
+                            // Failing to accept an incoming TCP connection generally happens due
+                            // to the limit of file descriptors being reached.
+                            // Sleep a little bit and try again.
+                            log_callback.log(
+                                LogLevel::Warn,
+                                format!("prometheus-tcp-listener-error; error={error}"),
+                            );
+                            smol::Timer::after(Duration::from_millis(50)).await;
+                            continue;
+                        }
+                    };
+
+                    // Requests are answered one at a time, as scrapers typically only send a
+                    // request every few seconds.
+                    let result = future::or(answer_http_request(&metrics, socket), async {
+                        smol::Timer::after(REQUEST_TIMEOUT).await;
+                        Err(io::Error::from(io::ErrorKind::TimedOut))
+                    })
+                    .await;
+
+                    if let Err(error) = result {
+                        log_callback.log(
+                            LogLevel::Debug,
+                            format!("prometheus-request-error; address={address}; error={error}"),
+                        );
+                    }
+                }
+            }));
+
+            Some(listen_addr)
+        } else {
+            None
+        };
+
+        Ok(Arc::new(PrometheusService {
+            metrics,
+            listen_addr,
+            shutdown_notify,
+        }))
+    }
+
+    /// Returns the address the HTTP server is listening on.
+    ///
+    /// Returns `None` if and only if [`Config::listen_address`] was `None`.
+    pub fn listen_addr(&self) -> Option<SocketAddr> {
+        self.listen_addr
+    }
+
+    /// Registers a new chain whose metrics are exported, and returns the object to use to
+    /// update these metrics.
+    ///
+    /// The `log_name` is used as the value of the `chain` label of the metrics.
+    pub fn add_chain(&self, log_name: String) -> Arc<ChainMetrics> {
+        let chain = Arc::new(ChainMetrics {
+            log_name,
+            peers: AtomicU64::new(0),
+            best_block_height: AtomicU64::new(0),
+            finalized_block_height: AtomicU64::new(0),
+            is_major_syncing: AtomicBool::new(false),
+            blocks_verified: AtomicU64::new(0),
+            block_verification_micros: AtomicU64::new(0),
+            block_import_micros: AtomicU64::new(0),
+            database_cache_hits: AtomicU64::new(0),
+            database_cache_misses: AtomicU64::new(0),
+            json_rpc_requests: Mutex::new(BTreeMap::new()),
+        });
+
+        self.metrics.chains.lock().unwrap().push(chain.clone());
+        chain
+    }
+
+    /// Updates the number of connections with other nodes, including the connections that are
+    /// still being established.
+    pub fn set_network_connections(&self, num: usize) {
+        self.metrics.network_connections.store(
+            u64::try_from(num).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Returns an object to use to report the number of bytes received and sent over a
+    /// connection to the given address.
+    pub fn connection_bandwidth(&self, multiaddr: &Multiaddr) -> ConnectionBandwidth {
+        ConnectionBandwidth {
+            metrics: self.metrics.clone(),
+            transport: Transport::from_multiaddr(multiaddr),
+        }
+    }
+}
+
+impl Drop for PrometheusService {
+    fn drop(&mut self) {
+        self.shutdown_notify.notify(usize::max_value());
+    }
+}
+
+/// Metrics concerning a specific chain.
+///
+/// See [`PrometheusService::add_chain`].
+pub struct ChainMetrics {
+    /// Value of the `chain` label.
+    log_name: String,
+    peers: AtomicU64,
+    best_block_height: AtomicU64,
+    finalized_block_height: AtomicU64,
+    is_major_syncing: AtomicBool,
+    blocks_verified: AtomicU64,
+    block_verification_micros: AtomicU64,
+    block_import_micros: AtomicU64,
+    database_cache_hits: AtomicU64,
+    database_cache_misses: AtomicU64,
+    /// Number of JSON-RPC requests received, per method name.
+    json_rpc_requests: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+impl ChainMetrics {
+    /// Updates the number of peers with which a gossip link is open.
+    pub fn set_peers(&self, num: usize) {
+        self.peers.store(
+            u64::try_from(num).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Updates the height of the current best block.
+    pub fn set_best_block_height(&self, height: u64) {
+        self.best_block_height.store(height, Ordering::Relaxed);
+    }
+
+    /// Updates the height of the current finalized block.
+    pub fn set_finalized_block_height(&self, height: u64) {
+        self.finalized_block_height.store(height, Ordering::Relaxed);
+    }
+
+    /// Updates whether the node is currently catching up with the head of the chain.
+    pub fn set_is_major_syncing(&self, is_major_syncing: bool) {
+        self.is_major_syncing
+            .store(is_major_syncing, Ordering::Relaxed);
+    }
+
+    /// Reports that a block has been successfully verified and imported.
+    ///
+    /// `verification` is the total time spent verifying the block, and `import` the part of
+    /// this time spent writing the block in the database.
+    pub fn observe_block_verification(&self, verification: Duration, import: Duration) {
+        self.blocks_verified.fetch_add(1, Ordering::Relaxed);
+        self.block_verification_micros.fetch_add(
+            u64::try_from(verification.as_micros()).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+        self.block_import_micros.fetch_add(
+            u64::try_from(import.as_micros()).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+    }
+
+    /// Updates the statistics of the cache of the database of the chain.
+    pub fn set_database_cache_statistics(&self, statistics: database_thread::CacheStatistics) {
+        self.database_cache_hits
+            .store(statistics.hits, Ordering::Relaxed);
+        self.database_cache_misses
+            .store(statistics.misses, Ordering::Relaxed);
+    }
+
+    /// Reports that a JSON-RPC request has been received.
+    pub fn json_rpc_request(&self, method: &'static str) {
+        *self
+            .json_rpc_requests
+            .lock()
+            .unwrap()
+            .entry(method)
+            .or_insert(0) += 1;
+    }
+}
+
+/// See [`PrometheusService::connection_bandwidth`].
+pub struct ConnectionBandwidth {
+    metrics: Arc<Metrics>,
+    transport: Transport,
+}
+
+impl ConnectionBandwidth {
+    /// Adds to the number of bytes that have been received and sent over the connection.
+    pub fn add(&self, read: usize, written: usize) {
+        let [bytes_in, bytes_out] = &self.metrics.network_bytes[self.transport as usize];
+        bytes_in.fetch_add(
+            u64::try_from(read).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+        bytes_out.fetch_add(
+            u64::try_from(written).unwrap_or(u64::max_value()),
+            Ordering::Relaxed,
+        );
+    }
+}
+
+/// Transport protocol of a connection, used as the value of the `transport` label.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Transport {
+    Tcp = 0,
+    WebSocket = 1,
+    Memory = 2,
+}
+
+impl Transport {
+    /// Determines the transport protocol used by a connection to the given address.
+    fn from_multiaddr(multiaddr: &Multiaddr) -> Self {
+        let mut transport = Transport::Tcp;
+        for protocol in multiaddr.iter() {
+            match protocol {
+                ProtocolRef::Memory(_) => return Transport::Memory,
+                ProtocolRef::Ws | ProtocolRef::Wss => transport = Transport::WebSocket,
+                _ => {}
+            }
+        }
+        transport
+    }
+
+    /// Returns the value of the `transport` label.
+    fn label(&self) -> &'static str {
+        match self {
+            Transport::Tcp => "tcp",
+            Transport::WebSocket => "websocket",
+            Transport::Memory => "memory",
+        }
+    }
+}
+
+/// See [`PrometheusService::metrics`].
+struct Metrics {
+    network_connections: AtomicU64,
+    /// Number of bytes received and sent, indexed by [`Transport`].
+    network_bytes: [[AtomicU64; 2]; 3],
+    chains: Mutex<Vec<Arc<ChainMetrics>>>,
+}
+
+impl Metrics {
+    /// Builds the body of the response to a scrape, using the Prometheus text format.
+    fn render(&self) -> String {
+        let mut out = String::with_capacity(4096);
+
+        write_header(
+            &mut out,
+            "smoldot_network_connections",
+            "gauge",
+            "Number of connections with other nodes, including connections being established.",
+        );
+        let _ = writeln!(
+            out,
+            "smoldot_network_connections {}",
+            self.network_connections.load(Ordering::Relaxed)
+        );
+
+        write_header(
+            &mut out,
+            "smoldot_network_bytes_total",
+            "counter",
+            "Number of bytes received and sent over the network.",
+        );
+        for transport in [Transport::Tcp, Transport::WebSocket, Transport::Memory] {
+            let [bytes_in, bytes_out] = &self.network_bytes[transport as usize];
+            for (direction, bytes) in [("in", bytes_in), ("out", bytes_out)] {
+                let _ = writeln!(
+                    out,
+                    "smoldot_network_bytes_total{{transport=\"{}\",direction=\"{direction}\"}} {}",
+                    transport.label(),
+                    bytes.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        let chains = self.chains.lock().unwrap();
+
+        // Metrics with one value per chain.
+        let per_chain: [(&str, &str, &str, fn(&ChainMetrics) -> u64); 6] = [
+            (
+                "smoldot_network_peers",
+                "gauge",
+                "Number of peers with which a gossip link is open.",
+                |c| c.peers.load(Ordering::Relaxed),
+            ),
+            (
+                "smoldot_best_block_height",
+                "gauge",
+                "Height of the current best block.",
+                |c| c.best_block_height.load(Ordering::Relaxed),
+            ),
+            (
+                "smoldot_finalized_block_height",
+                "gauge",
+                "Height of the current finalized block.",
+                |c| c.finalized_block_height.load(Ordering::Relaxed),
+            ),
+            (
+                "smoldot_is_major_syncing",
+                "gauge",
+                "1 if the node is catching up with the head of the chain, 0 otherwise.",
+                |c| u64::from(c.is_major_syncing.load(Ordering::Relaxed)),
+            ),
+            (
+                "smoldot_database_cache_hits_total",
+                "counter",
+                "Number of times a page was found in the cache of the database.",
+                |c| c.database_cache_hits.load(Ordering::Relaxed),
+            ),
+            (
+                "smoldot_database_cache_misses_total",
+                "counter",
+                "Number of times a page had to be read from the disk by the database.",
+                |c| c.database_cache_misses.load(Ordering::Relaxed),
+            ),
+        ];
+        for (name, ty, help, value) in per_chain {
+            write_header(&mut out, name, ty, help);
+            for chain in chains.iter() {
+                let _ = writeln!(
+                    out,
+                    "{name}{{chain=\"{}\"}} {}",
+                    escape_label_value(&chain.log_name),
+                    value(chain)
+                );
+            }
+        }
+
+        // Durations, in microseconds, summed over all the verified blocks.
+        let durations: [(&str, &str, fn(&ChainMetrics) -> u64); 2] = [
+            (
+                "smoldot_block_verification_duration_seconds",
+                "Time spent verifying blocks, including their import in the database.",
+                |c| c.block_verification_micros.load(Ordering::Relaxed),
+            ),
+            (
+                "smoldot_block_import_duration_seconds",
+                "Time spent importing verified blocks in the database.",
+                |c| c.block_import_micros.load(Ordering::Relaxed),
+            ),
+        ];
+        for (name, help, value) in durations {
+            write_header(&mut out, name, "summary", help);
+            for chain in chains.iter() {
+                let chain_label = escape_label_value(&chain.log_name);
+                // Precision is lost for very large values, which is acceptable here.
+                let _ = writeln!(
+                    out,
+                    "{name}_sum{{chain=\"{chain_label}\"}} {}",
+                    value(chain) as f64 / 1_000_000.0
+                );
+                let _ = writeln!(
+                    out,
+                    "{name}_count{{chain=\"{chain_label}\"}} {}",
+                    chain.blocks_verified.load(Ordering::Relaxed)
+                );
+            }
+        }
+
+        write_header(
+            &mut out,
+            "smoldot_json_rpc_requests_total",
+            "counter",
+            "Number of JSON-RPC requests received, per method.",
+        );
+        for chain in chains.iter() {
+            let chain_label = escape_label_value(&chain.log_name);
+            for (method, num) in chain.json_rpc_requests.lock().unwrap().iter() {
+                let _ = writeln!(
+                    out,
+                    "smoldot_json_rpc_requests_total{{chain=\"{chain_label}\",method=\"{method}\"}} {num}"
+                );
+            }
+        }
+
+        out
+    }
+}
+
+/// Writes the `HELP` and `TYPE` lines that precede the values of a metric.
+fn write_header(out: &mut String, name: &str, ty: &str, help: &str) {
+    let _ = writeln!(out, "# HELP {name} {help}");
+    let _ = writeln!(out, "# TYPE {name} {ty}");
+}
+
+/// Escapes a string so that it can be used as the value of a label.
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Reads an HTTP request from the socket and sends back the response.
+async fn answer_http_request(metrics: &Metrics, mut socket: TcpStream) -> Result<(), io::Error> {
+    // Read until the end of the headers. The body of the request, if any, is ignored.
+    let mut request = Vec::with_capacity(1024);
+    loop {
+        let mut buffer = [0; 1024];
+        let num_read = socket.read(&mut buffer).await?;
+        if num_read == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+
+        request.extend_from_slice(&buffer[..num_read]);
+        if request.windows(4).any(|w| w == b"\r\n\r\n") {
+            break;
+        }
+        if request.len() >= MAX_REQUEST_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "HTTP request too large",
+            ));
+        }
+    }
+
+    let (status, body) =
+        if request.starts_with(b"GET /metrics ") || request.starts_with(b"GET /metrics?") {
+            ("200 OK", metrics.render())
+        } else {
+            ("404 Not Found", String::new())
+        };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+        Content-Type: text/plain; version=0.0.4\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\
+        \r\n\
+        {body}",
+        body.len()
+    );
+    socket.write_all(response.as_bytes()).await?;
+    socket.flush().await?;
+    Ok(())
+}
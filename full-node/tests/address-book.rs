@@ -0,0 +1,153 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests that the list of known peers is saved to and loaded from
+//! [`smoldot_full_node::ChainConfig::address_book_path`].
+
+use core::time::Duration;
+use smoldot::libp2p::{connection::NoiseKey, peer_id::PublicKey, PeerId};
+use std::{fs, sync::Arc, time::Instant};
+
+fn peer_id(libp2p_key: &[u8; 32]) -> PeerId {
+    let noise_key = NoiseKey::new(libp2p_key, &[0; 32]);
+    PublicKey::Ed25519(*noise_key.libp2p_public_ed25519_key()).into_peer_id()
+}
+
+fn chain_config(
+    address_book_path: Option<std::path::PathBuf>,
+) -> smoldot_full_node::ChainConfig<'static> {
+    smoldot_full_node::ChainConfig {
+        chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+        additional_bootnodes: Vec::new(),
+        reserved_nodes: Vec::new(),
+        reserved_only: false,
+        keystore_memory: Vec::new(),
+        sqlite_database_path: None,
+        sqlite_cache_size: 256 * 1024 * 1024,
+        keystore_path: None,
+        address_book_path,
+        json_rpc_listen: None,
+        warp_sync: false,
+    }
+}
+
+#[test]
+fn address_book_loaded_and_saved() {
+    smol::block_on(async move {
+        let directory = std::env::temp_dir().join(format!(
+            "smoldot-full-node-address-book-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&directory).unwrap();
+        let address_book_path = directory.join("peers.json");
+
+        // The address book contains the listening node, plus an entry with an invalid peer id
+        // that must be discarded when the file is rewritten.
+        fs::write(
+            &address_book_path,
+            serde_json::to_vec(&serde_json::json!({
+                "peers": [
+                    { "peerId": peer_id(&[1; 32]).to_base58(), "addresses": ["/memory/10"] },
+                    { "peerId": "invalid", "addresses": ["/memory/11"] },
+                ]
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        let _listener = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: chain_config(None),
+            relay_chain: None,
+            libp2p_key: Box::new([1; 32]),
+            listen_addresses: vec!["/memory/10".parse().unwrap()],
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        // This node has no bootnode and can only know about the other node through its
+        // address book.
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: chain_config(Some(address_book_path.clone())),
+            relay_chain: None,
+            libp2p_key: Box::new([2; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        while client.num_peers().await == 0 {
+            assert!(
+                Instant::now() < deadline,
+                "no connection to the address book peer"
+            );
+            smol::Timer::after(Duration::from_millis(100)).await;
+        }
+
+        // Leave some time for the identify request, sent in parallel of the gossip substreams
+        // being opened, to finish.
+        smol::Timer::after(Duration::from_secs(1)).await;
+
+        // The address book is saved when the node shuts down.
+        // The deadline is shorter than the period at which the address book is saved while the
+        // node is running.
+        drop(client);
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let saved = loop {
+            let content = fs::read(&address_book_path).unwrap();
+            let parsed = serde_json::from_slice::<serde_json::Value>(&content).ok();
+            if let Some(parsed) = parsed.filter(|p| p["peers"].as_array().unwrap().len() == 1) {
+                break parsed;
+            }
+            assert!(
+                Instant::now() < deadline,
+                "address book not saved at shutdown"
+            );
+            smol::Timer::after(Duration::from_millis(100)).await;
+        };
+
+        assert_eq!(
+            saved["peers"][0]["peerId"],
+            serde_json::Value::from(peer_id(&[1; 32]).to_base58())
+        );
+        assert_eq!(
+            saved["peers"][0]["addresses"],
+            serde_json::json!(["/memory/10"])
+        );
+
+        // The protocols are learned through the identify protocol.
+        assert!(saved["peers"][0]["protocols"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|p| p.as_str().unwrap().ends_with("/block-announces/1")));
+
+        let _ = fs::remove_dir_all(&directory);
+    });
+}
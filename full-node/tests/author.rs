@@ -25,6 +25,8 @@ fn basic_block_generated() {
             chain: smoldot_full_node::ChainConfig {
                 chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
                 additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
                 keystore_memory: vec![smoldot::identity::seed_phrase::decode_sr25519_private_key(
                     "//Alice",
                 )
@@ -32,14 +34,19 @@ fn basic_block_generated() {
                 sqlite_database_path: None,
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
+                address_book_path: None,
                 json_rpc_listen: None,
+                warp_sync: false,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
             listen_addresses: Vec::new(),
             tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
             log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
             jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
         })
         .await
         .unwrap();
@@ -73,3 +80,92 @@ fn basic_block_generated() {
         }
     });
 }
+
+#[test]
+fn dev_seal_manual() {
+    smol::block_on(async move {
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
+                keystore_memory: vec![smoldot::identity::seed_phrase::decode_sr25519_private_key(
+                    "//Alice",
+                )
+                .unwrap()],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                address_book_path: None,
+                json_rpc_listen: None,
+                warp_sync: false,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: Some(smoldot_full_node::DevSeal::Manual),
+        })
+        .await
+        .unwrap();
+
+        // Refusing to create an empty block fails, as no transaction has been submitted.
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"engine_createBlock","params":[false,false,null]}"#
+                .to_owned(),
+        );
+        assert!(
+            json_rpc::parse::parse_response(&client.next_json_rpc_response().await)
+                .unwrap()
+                .into_success()
+                .is_none()
+        );
+
+        // Create two blocks in a row, the first one without finalizing it.
+        let mut created_hashes = Vec::new();
+        for _ in 0..2 {
+            client.send_json_rpc_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"engine_createBlock","params":[true,false,null]}"#
+                    .to_owned(),
+            );
+            let response = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            let created: json_rpc::methods::CreatedBlock =
+                serde_json::from_str(result_json).unwrap();
+            assert!(created.aux.is_new_best);
+            created_hashes.push(created.hash.0);
+        }
+        assert_ne!(created_hashes[0], created_hashes[1]);
+
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"engine_finalizeBlock","params":["0x{}",null]}}"#,
+            hex::encode(created_hashes[1])
+        ));
+        let response = client.next_json_rpc_response().await;
+        let (_, result_json) = json_rpc::parse::parse_response(&response)
+            .unwrap()
+            .into_success()
+            .unwrap();
+        assert_eq!(result_json, "true");
+
+        // The first block is now an ancestor of the finalized block.
+        client.send_json_rpc_request(format!(
+            r#"{{"jsonrpc":"2.0","id":1,"method":"engine_finalizeBlock","params":["0x{}",null]}}"#,
+            hex::encode(created_hashes[0])
+        ));
+        assert!(
+            json_rpc::parse::parse_response(&client.next_json_rpc_response().await)
+                .unwrap()
+                .into_success()
+                .is_none()
+        );
+    });
+}
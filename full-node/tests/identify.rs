@@ -0,0 +1,105 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests about the identify protocol, which nodes use to learn about their own public address.
+//!
+//! These tests use TCP, as the address observed by remotes is the point of the tests.
+
+use core::time::Duration;
+use smoldot::{
+    json_rpc,
+    libp2p::{connection::NoiseKey, peer_id::PublicKey, PeerId},
+};
+use std::{sync::Arc, time::Instant};
+
+fn peer_id(libp2p_key: &[u8; 32]) -> PeerId {
+    let noise_key = NoiseKey::new(libp2p_key, &[0; 32]);
+    PublicKey::Ed25519(*noise_key.libp2p_public_ed25519_key()).into_peer_id()
+}
+
+async fn start_node(
+    libp2p_key: [u8; 32],
+    listen_address: &str,
+    additional_bootnodes: Vec<(PeerId, smoldot::libp2p::Multiaddr)>,
+) -> smoldot_full_node::Client {
+    smoldot_full_node::start(smoldot_full_node::Config {
+        chain: smoldot_full_node::ChainConfig {
+            chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+            additional_bootnodes,
+            reserved_nodes: Vec::new(),
+            reserved_only: false,
+            keystore_memory: Vec::new(),
+            sqlite_database_path: None,
+            sqlite_cache_size: 256 * 1024 * 1024,
+            keystore_path: None,
+            address_book_path: None,
+            json_rpc_listen: None,
+            warp_sync: false,
+        },
+        relay_chain: None,
+        libp2p_key: Box::new(libp2p_key),
+        listen_addresses: vec![listen_address.parse().unwrap()],
+        tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+        log_callback: Arc::new(move |_, _| {}),
+        clock: smoldot_full_node::Clock::system(),
+        jaeger_agent: None,
+        prometheus_address: None,
+        dev_seal: None,
+    })
+    .await
+    .unwrap()
+}
+
+#[test]
+fn observed_address_uses_listen_port() {
+    smol::block_on(async move {
+        // Three remotes must report the same address for it to be trusted.
+        let mut remotes = Vec::new();
+        let mut bootnodes = Vec::new();
+        for n in 1..=3u8 {
+            let address = format!("/ip4/127.0.0.1/tcp/{}", 30600 + u16::from(n));
+            remotes.push(start_node([n; 32], &address, Vec::new()).await);
+            bootnodes.push((peer_id(&[n; 32]), address.parse().unwrap()));
+        }
+
+        // Listen addresses whose IP address is unspecified aren't reported, so the only way for
+        // this node to know its address is through the remotes. The remotes observe the
+        // ephemeral port of the connections opened by this node, which must be replaced with
+        // its listen port.
+        let client = start_node([10; 32], "/ip4/0.0.0.0/tcp/30600", bootnodes).await;
+
+        let deadline = Instant::now() + Duration::from_secs(60);
+        loop {
+            client.send_json_rpc_request(
+                r#"{"jsonrpc":"2.0","id":1,"method":"system_localListenAddresses","params":[]}"#
+                    .to_owned(),
+            );
+            let response_raw = client.next_json_rpc_response().await;
+            let (_, result_json) = json_rpc::parse::parse_response(&response_raw)
+                .unwrap()
+                .into_success()
+                .unwrap();
+            let addresses = serde_json::from_str::<Vec<String>>(result_json).unwrap();
+            if addresses.iter().any(|a| a == "/ip4/127.0.0.1/tcp/30600") {
+                break;
+            }
+
+            assert!(Instant::now() < deadline, "{addresses:?}");
+            smol::Timer::after(Duration::from_millis(100)).await;
+        }
+    });
+}
@@ -25,18 +25,25 @@ fn send_request_errs_if_malformed() {
             chain: smoldot_full_node::ChainConfig {
                 chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
                 additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
                 keystore_memory: vec![],
                 sqlite_database_path: None,
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
+                address_book_path: None,
                 json_rpc_listen: None,
+                warp_sync: false,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
             listen_addresses: Vec::new(),
             tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
             log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
             jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
         })
         .await
         .unwrap();
@@ -57,18 +64,25 @@ fn send_request_works_if_unknown_request() {
             chain: smoldot_full_node::ChainConfig {
                 chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
                 additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
                 keystore_memory: vec![],
                 sqlite_database_path: None,
                 sqlite_cache_size: 256 * 1024 * 1024,
                 keystore_path: None,
+                address_book_path: None,
                 json_rpc_listen: None,
+                warp_sync: false,
             },
             relay_chain: None,
             libp2p_key: Box::new([0; 32]),
             listen_addresses: Vec::new(),
             tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
             log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
             jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
         })
         .await
         .unwrap();
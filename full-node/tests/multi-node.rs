@@ -0,0 +1,265 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tests that run multiple nodes within the same process and connect them through
+//! `/memory/<port>` multiaddresses.
+//!
+//! Each test uses different memory ports, as tests run in parallel within the same process.
+//!
+//! The clocks of the nodes only advance when asked, by a fixed amount at each step, which makes
+//! the tests independent of how fast the machine running them is.
+
+use core::{future::Future, iter, num::NonZeroU32, time::Duration};
+use smoldot::{
+    json_rpc,
+    libp2p::{connection::NoiseKey, peer_id::PublicKey, PeerId},
+};
+use std::sync::Arc;
+
+/// UNIX time the clocks of the nodes start at.
+const UNIX_TIME_START: Duration = Duration::from_secs(1_700_000_000);
+
+/// Amount of simulated time that passes at each step.
+const SIMULATED_STEP: Duration = Duration::from_millis(100);
+
+/// Maximum amount of real time a test is allowed to take.
+const REAL_TIME_LIMIT: Duration = Duration::from_secs(120);
+
+fn peer_id(libp2p_key: &[u8; 32]) -> PeerId {
+    let noise_key = NoiseKey::new(libp2p_key, &[0; 32]);
+    PublicKey::Ed25519(*noise_key.libp2p_public_ed25519_key()).into_peer_id()
+}
+
+/// Runs the given future while regularly calling `advance` with [`SIMULATED_STEP`].
+///
+/// # Panic
+///
+/// Panics if the future hasn't finished after [`REAL_TIME_LIMIT`].
+///
+async fn run_with_simulated_time<T>(
+    future: impl Future<Output = T>,
+    advance: impl Fn(Duration),
+) -> T {
+    let drive_clocks = async {
+        loop {
+            // Give some real time to the nodes in order to process what happened during the
+            // previous step.
+            smol::Timer::after(Duration::from_millis(10)).await;
+            advance(SIMULATED_STEP);
+        }
+    };
+
+    let timeout = async {
+        smol::Timer::after(REAL_TIME_LIMIT).await;
+        panic!("test timed out")
+    };
+
+    smol::future::or(future, smol::future::or(drive_clocks, timeout)).await
+}
+
+#[test]
+fn full_node_syncs_from_authoring_full_node() {
+    smol::block_on(async move {
+        let clock = smoldot_full_node::Clock::manual(UNIX_TIME_START);
+
+        let _author = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
+                keystore_memory: vec![smoldot::identity::seed_phrase::decode_sr25519_private_key(
+                    "//Alice",
+                )
+                .unwrap()],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                address_book_path: None,
+                json_rpc_listen: None,
+                warp_sync: false,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([1; 32]),
+            listen_addresses: vec!["/memory/1".parse().unwrap()],
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: clock.clone(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: vec![(peer_id(&[1; 32]), "/memory/1".parse().unwrap())],
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                address_book_path: None,
+                json_rpc_listen: None,
+                warp_sync: false,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([2; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: clock.clone(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"chainHead_unstable_follow","params":[false]}"#
+                .to_owned(),
+        );
+
+        run_with_simulated_time(
+            async {
+                let _ = json_rpc::parse::parse_response(&client.next_json_rpc_response().await)
+                    .unwrap()
+                    .into_success()
+                    .unwrap();
+
+                // The non-authoring node can only learn about new blocks from the authoring node.
+                loop {
+                    if let json_rpc::methods::ServerToClient::chainHead_unstable_followEvent {
+                        result: json_rpc::methods::FollowEvent::NewBlock { .. },
+                        ..
+                    } = json_rpc::methods::parse_notification(
+                        &client.next_json_rpc_response().await,
+                    )
+                    .unwrap()
+                    {
+                        break;
+                    }
+                }
+            },
+            |step| clock.advance(step),
+        )
+        .await;
+
+        assert_eq!(client.num_peers().await, 1);
+    });
+}
+
+#[test]
+fn light_client_connects_to_full_node() {
+    smol::block_on(async move {
+        let clock = smoldot_full_node::Clock::manual(UNIX_TIME_START);
+
+        let _full_node = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                address_book_path: None,
+                json_rpc_listen: None,
+                warp_sync: false,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([3; 32]),
+            listen_addresses: vec!["/memory/2".parse().unwrap()],
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: clock.clone(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        // The light client only knows about the nodes found in the chain specification.
+        let chain_spec = {
+            let mut chain_spec: serde_json::Value =
+                serde_json::from_slice(include_bytes!("./substrate-node-template.json")).unwrap();
+            chain_spec["bootNodes"] =
+                serde_json::json!([format!("/memory/2/p2p/{}", peer_id(&[3; 32]))]);
+            serde_json::to_string(&chain_spec).unwrap()
+        };
+
+        let platform = smoldot_light::platform::MemoryPlatform::new(
+            "light-client".into(),
+            "1.0".into(),
+            UNIX_TIME_START,
+        );
+        let mut light_client = smoldot_light::Client::new(platform.clone());
+        let chain = light_client
+            .add_chain(smoldot_light::AddChainConfig {
+                user_data: (),
+                specification: &chain_spec,
+                database_content: "",
+                potential_relay_chains: iter::empty(),
+                json_rpc: smoldot_light::AddChainConfigJsonRpc::Enabled {
+                    max_pending_requests: NonZeroU32::new(16).unwrap(),
+                    max_subscriptions: 16,
+                },
+            })
+            .unwrap();
+        let mut json_rpc_responses = chain.json_rpc_responses.unwrap();
+
+        run_with_simulated_time(
+            async {
+                loop {
+                    light_client
+                        .json_rpc_request(
+                            r#"{"jsonrpc":"2.0","id":1,"method":"system_health","params":[]}"#,
+                            chain.chain_id,
+                        )
+                        .unwrap();
+                    let response = json_rpc_responses.next().await.unwrap();
+                    let health = serde_json::from_str::<json_rpc::methods::SystemHealth>(
+                        json_rpc::parse::parse_response(&response)
+                            .unwrap()
+                            .into_success()
+                            .unwrap()
+                            .1,
+                    )
+                    .unwrap();
+
+                    if health.peers >= 1 {
+                        break;
+                    }
+
+                    // Leave some time for the light client to connect before asking again.
+                    smol::Timer::after(Duration::from_millis(50)).await;
+                }
+            },
+            |step| {
+                clock.advance(step);
+                platform.advance_time(step);
+            },
+        )
+        .await;
+    });
+}
@@ -0,0 +1,125 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use smol::io::{AsyncReadExt as _, AsyncWriteExt as _};
+use std::sync::Arc;
+
+async fn start_client() -> smoldot_full_node::Client {
+    smoldot_full_node::start(smoldot_full_node::Config {
+        chain: smoldot_full_node::ChainConfig {
+            chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+            additional_bootnodes: Vec::new(),
+            reserved_nodes: Vec::new(),
+            reserved_only: false,
+            keystore_memory: vec![],
+            sqlite_database_path: None,
+            sqlite_cache_size: 256 * 1024 * 1024,
+            keystore_path: None,
+            address_book_path: None,
+            json_rpc_listen: None,
+            warp_sync: false,
+        },
+        relay_chain: None,
+        libp2p_key: Box::new([0; 32]),
+        listen_addresses: Vec::new(),
+        tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+        log_callback: Arc::new(move |_, _| {}),
+        clock: smoldot_full_node::Clock::system(),
+        jaeger_agent: None,
+        prometheus_address: Some("127.0.0.1:0".parse().unwrap()),
+        dev_seal: None,
+    })
+    .await
+    .unwrap()
+}
+
+async fn http_get(client: &smoldot_full_node::Client, path: &str) -> String {
+    let mut socket = smol::net::TcpStream::connect(client.prometheus_server_addr().unwrap())
+        .await
+        .unwrap();
+    socket
+        .write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n").as_bytes())
+        .await
+        .unwrap();
+
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+#[test]
+fn metrics_exported() {
+    smol::block_on(async move {
+        let client = start_client().await;
+
+        client.send_json_rpc_request(
+            r#"{"jsonrpc":"2.0","id":1,"method":"system_name","params":[]}"#.to_owned(),
+        );
+        let _ = client.next_json_rpc_response().await;
+
+        let response = http_get(&client, "/metrics").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(response.contains("\nsmoldot_best_block_height{chain=\"local_testnet\"} 0\n"));
+        assert!(response.contains("\nsmoldot_network_peers{chain=\"local_testnet\"} 0\n"));
+        assert!(response.contains(
+            "\nsmoldot_json_rpc_requests_total{chain=\"local_testnet\",method=\"system_name\"} 1\n"
+        ));
+    });
+}
+
+#[test]
+fn unknown_path() {
+    smol::block_on(async move {
+        let client = start_client().await;
+        let response = http_get(&client, "/foo").await;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+    });
+}
+
+#[test]
+fn no_server_by_default() {
+    smol::block_on(async move {
+        let client = smoldot_full_node::start(smoldot_full_node::Config {
+            chain: smoldot_full_node::ChainConfig {
+                chain_spec: (&include_bytes!("./substrate-node-template.json")[..]).into(),
+                additional_bootnodes: Vec::new(),
+                reserved_nodes: Vec::new(),
+                reserved_only: false,
+                keystore_memory: vec![],
+                sqlite_database_path: None,
+                sqlite_cache_size: 256 * 1024 * 1024,
+                keystore_path: None,
+                address_book_path: None,
+                json_rpc_listen: None,
+                warp_sync: false,
+            },
+            relay_chain: None,
+            libp2p_key: Box::new([0; 32]),
+            listen_addresses: Vec::new(),
+            tasks_executor: Arc::new(|task| smol::spawn(task).detach()),
+            log_callback: Arc::new(move |_, _| {}),
+            clock: smoldot_full_node::Clock::system(),
+            jaeger_agent: None,
+            prometheus_address: None,
+            dev_seal: None,
+        })
+        .await
+        .unwrap();
+
+        assert!(client.prometheus_server_addr().is_none());
+    });
+}
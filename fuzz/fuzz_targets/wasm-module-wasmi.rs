@@ -23,5 +23,6 @@ libfuzzer_sys::fuzz_target!(|data: &[u8]| {
         heap_pages: smoldot::executor::DEFAULT_HEAP_PAGES,
         exec_hint: smoldot::executor::vm::ExecHint::ForceWasmi,
         allow_unresolved_imports: true,
+        fuel_budget: None,
     });
 });
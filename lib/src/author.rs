@@ -19,4 +19,5 @@
 
 pub mod aura;
 pub mod build;
+pub mod pov;
 pub mod runtime;
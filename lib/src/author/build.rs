@@ -242,6 +242,18 @@ impl AuthoringStart {
         let inherent_data = inherents::InherentData {
             timestamp: u64::try_from(config.now_from_unix_epoch.as_millis())
                 .unwrap_or(u64::max_value()),
+            // The slot number is only passed to parachain runtimes, as they are the only ones
+            // still making use of it. See the documentation of `InherentData`.
+            consensus: if config.parachain_inherent_data.is_some() {
+                Some(match self.consensus {
+                    WaitSlotConsensus::Aura(slot) => inherents::InherentDataConsensus::Aura {
+                        slot_number: slot.slot_number,
+                    },
+                })
+            } else {
+                None
+            },
+            parachain: config.parachain_inherent_data,
         };
 
         (Shared {
@@ -291,6 +303,15 @@ pub struct AuthoringStartConfig<'a> {
     /// If `true`, then [`StorageChanges::trie_changes_iter_ordered`] will return `Some`.
     /// Passing `None` requires fewer calculation and fewer storage accesses.
     pub calculate_trie_changes: bool,
+
+    /// Parachain-related inherents to pass to the runtime. Must be `Some` if and only if the
+    /// chain is a parachain.
+    ///
+    /// If `Some`, the slot number is also passed to the runtime as an inherent, as Cumulus-based
+    /// runtimes make use of it.
+    ///
+    /// See the [`crate::sync::para`] module for how to build this value.
+    pub parachain_inherent_data: Option<inherents::ParachainInherentData>,
 }
 
 /// More transactions can be added.
@@ -0,0 +1,292 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Proof-of-Validity of parachain blocks.
+//!
+//! # Overview
+//!
+//! In order for a parachain block to be included in the relay chain, the collator that has
+//! authored it must provide to the relay chain validators a *Proof-of-Validity* (PoV). The PoV
+//! contains the parachain block (header and body) and a Merkle proof of all the storage items
+//! of the parent block that have been accessed while executing the block. The validators then
+//! execute the block using the parachain validation function, which uses the storage proof
+//! rather than a database.
+//!
+//! The storage proof is expected to have been built using a
+//! [`crate::trie::proof_encode::ProofBuilder`] filled with all the storage items that have been
+//! read while authoring the block (see [`crate::author::build`]), and encoded in the "compact"
+//! format expected by the validators (see
+//! [`crate::trie::proof_encode::ProofBuilder::build_compact`]).
+//!
+//! The PoV is sent to the validators over the collation fetching protocol. See
+//! [`crate::network::codec::build_collation_fetching_response`].
+//!
+//! # Candidate commitments
+//!
+//! Alongside with the PoV, the collator must provide to the validators the hash of the so-called
+//! *commitments* of the parachain block, containing for example the messages sent by the
+//! parachain to the relay chain and to the other parachains. These commitments are obtained by
+//! calling the [`COLLECT_COLLATION_INFO_FUNCTION_NAME`] runtime function of the parachain on top
+//! of the newly-authored block. See [`collect_collation_info_parameters`],
+//! [`decode_collect_collation_info_return_value`] and [`CollationInfoRef::commitments_hash`].
+
+use crate::util;
+
+use alloc::vec::Vec;
+use core::iter;
+
+/// Configuration for [`build_pov`].
+#[derive(Debug, Clone)]
+pub struct PovConfig<'a, TExIter> {
+    /// SCALE-encoded header of the parachain block.
+    pub scale_encoded_header: &'a [u8],
+
+    /// List of SCALE-encoded extrinsics of the body of the parachain block.
+    pub block_body: TExIter,
+
+    /// Merkle proof of the storage of the parent of the parachain block, in the compact format
+    /// returned by [`crate::trie::proof_encode::ProofBuilder::build_compact_to_vec`].
+    ///
+    /// This proof must contain all the storage items accessed while executing the block.
+    pub storage_proof: &'a [u8],
+}
+
+/// Builds the SCALE-encoded Proof-of-Validity of a parachain block.
+pub fn build_pov<TExIter>(config: PovConfig<TExIter>) -> Vec<u8>
+where
+    TExIter: ExactSizeIterator,
+    TExIter::Item: AsRef<[u8]>,
+{
+    // The block data consists in the header, followed with the body, followed with the storage
+    // proof.
+    let mut block_data =
+        Vec::with_capacity(config.scale_encoded_header.len() + config.storage_proof.len() + 1024);
+    block_data.extend_from_slice(config.scale_encoded_header);
+    block_data
+        .extend_from_slice(util::encode_scale_compact_usize(config.block_body.len()).as_ref());
+    for extrinsic in config.block_body {
+        let extrinsic = extrinsic.as_ref();
+        block_data.extend_from_slice(util::encode_scale_compact_usize(extrinsic.len()).as_ref());
+        block_data.extend_from_slice(extrinsic);
+    }
+    block_data.extend_from_slice(config.storage_proof);
+
+    // The PoV is a structure containing a single field: the block data, as a SCALE-encoded
+    // `Vec<u8>`.
+    let mut out = Vec::with_capacity(block_data.len() + 5);
+    out.extend_from_slice(util::encode_scale_compact_usize(block_data.len()).as_ref());
+    out.extend_from_slice(&block_data);
+    out
+}
+
+/// Returns the hash of a SCALE-encoded Proof-of-Validity, as found in the candidate receipt
+/// sent to the validators. See [`crate::network::codec::CandidateReceiptRef::pov_hash`].
+pub fn pov_hash(scale_encoded_pov: &[u8]) -> [u8; 32] {
+    let mut hasher = blake2_rfc::blake2b::Blake2b::with_key(32, &[]);
+    hasher.update(scale_encoded_pov);
+    let mut out = [0; 32];
+    out.copy_from_slice(hasher.finalize().as_bytes());
+    out
+}
+
+/// Name of the runtime function of the parachain to call in order to obtain the information
+/// necessary to build the candidate commitments of a block.
+pub const COLLECT_COLLATION_INFO_FUNCTION_NAME: &str =
+    "CollectCollationInfo_collect_collation_info";
+
+/// Produces the input to pass to the `CollectCollationInfo_collect_collation_info` runtime call.
+///
+/// The runtime function must be called on top of the block whose header is passed as parameter.
+pub fn collect_collation_info_parameters(
+    scale_encoded_header: &[u8],
+) -> impl Iterator<Item = impl AsRef<[u8]> + '_> + Clone + '_ {
+    iter::once(scale_encoded_header)
+}
+
+/// Attempt to decode the return value of the `CollectCollationInfo_collect_collation_info`
+/// runtime call.
+pub fn decode_collect_collation_info_return_value(
+    scale_encoded: &[u8],
+) -> Result<CollationInfoRef, DecodeCollationInfoError> {
+    let res: Result<_, nom::Err<nom::error::Error<_>>> =
+        nom::combinator::all_consuming(nom::combinator::complete(nom::combinator::map(
+            nom::sequence::tuple((
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_elems| {
+                    nom::multi::many_m_n(num_elems, num_elems, util::nom_bytes_decode)
+                }),
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_elems| {
+                    nom::multi::many_m_n(
+                        num_elems,
+                        num_elems,
+                        nom::sequence::tuple((
+                            nom::number::streaming::le_u32,
+                            util::nom_bytes_decode,
+                        )),
+                    )
+                }),
+                util::nom_option_decode(util::nom_bytes_decode),
+                nom::number::streaming::le_u32,
+                nom::number::streaming::le_u32,
+                util::nom_bytes_decode,
+            )),
+            |(
+                upward_messages,
+                horizontal_messages,
+                new_validation_code,
+                processed_downward_messages,
+                hrmp_watermark,
+                head_data,
+            )| CollationInfoRef {
+                upward_messages,
+                horizontal_messages,
+                new_validation_code,
+                processed_downward_messages,
+                hrmp_watermark,
+                head_data,
+            },
+        )))(scale_encoded);
+    match res {
+        Ok((_, info)) => Ok(info),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+            Err(DecodeCollationInfoError(err.code))
+        }
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Error potentially returned by [`decode_collect_collation_info_return_value`].
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "Failed to decode the collation info")]
+pub struct DecodeCollationInfoError(nom::error::ErrorKind);
+
+/// Information about a parachain block, as returned by
+/// [`decode_collect_collation_info_return_value`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollationInfoRef<'a> {
+    /// Messages sent by the parachain to the relay chain.
+    pub upward_messages: Vec<&'a [u8]>,
+    /// Messages sent by the parachain to other parachains. Contains the parachain id of the
+    /// recipient and the message.
+    pub horizontal_messages: Vec<(u32, &'a [u8])>,
+    /// New Wasm validation code of the parachain, if it is being upgraded.
+    pub new_validation_code: Option<&'a [u8]>,
+    /// Number of downward messages that have been processed by the block.
+    pub processed_downward_messages: u32,
+    /// Relay chain block number up to which the horizontal messages have been processed.
+    pub hrmp_watermark: u32,
+    /// Head data of the block. For Cumulus-based chains, this is the SCALE-encoded header.
+    pub head_data: &'a [u8],
+}
+
+impl<'a> CollationInfoRef<'a> {
+    /// Returns the hash of the SCALE-encoded candidate commitments of the block. See
+    /// [`crate::network::codec::CandidateReceiptRef::commitments_hash`].
+    ///
+    /// The candidate commitments contain the same fields as [`CollationInfoRef`], but in a
+    /// different order.
+    pub fn commitments_hash(&self) -> [u8; 32] {
+        let mut hasher = blake2_rfc::blake2b::Blake2b::with_key(32, &[]);
+
+        hasher.update(util::encode_scale_compact_usize(self.upward_messages.len()).as_ref());
+        for message in &self.upward_messages {
+            hasher.update(util::encode_scale_compact_usize(message.len()).as_ref());
+            hasher.update(message);
+        }
+
+        hasher.update(util::encode_scale_compact_usize(self.horizontal_messages.len()).as_ref());
+        for (recipient, message) in &self.horizontal_messages {
+            hasher.update(&recipient.to_le_bytes());
+            hasher.update(util::encode_scale_compact_usize(message.len()).as_ref());
+            hasher.update(message);
+        }
+
+        match self.new_validation_code {
+            Some(code) => {
+                hasher.update(&[1]);
+                hasher.update(util::encode_scale_compact_usize(code.len()).as_ref());
+                hasher.update(code);
+            }
+            None => hasher.update(&[0]),
+        }
+
+        hasher.update(util::encode_scale_compact_usize(self.head_data.len()).as_ref());
+        hasher.update(self.head_data);
+        hasher.update(&self.processed_downward_messages.to_le_bytes());
+        hasher.update(&self.hrmp_watermark.to_le_bytes());
+
+        let mut out = [0; 32];
+        out.copy_from_slice(hasher.finalize().as_bytes());
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn basic_pov() {
+        let pov = super::build_pov(super::PovConfig {
+            scale_encoded_header: &[1, 2, 3],
+            block_body: [&[4u8, 5][..], &[6][..]].into_iter(),
+            storage_proof: &[4, 7],
+        });
+
+        assert_eq!(pov, &[44, 1, 2, 3, 8, 8, 4, 5, 4, 6, 4, 7]);
+    }
+
+    #[test]
+    fn collation_info_decode_and_commitments() {
+        let encoded = [
+            4, 8, 1, 2, // One upward message.
+            4, 0xe8, 0x3, 0, 0, 4, 3, // One horizontal message to 1000.
+            0, // No new validation code.
+            5, 0, 0, 0, // Processed downward messages.
+            9, 0, 0, 0, // HRMP watermark.
+            12, 4, 5, 6, // Head data.
+        ];
+
+        let info = super::decode_collect_collation_info_return_value(&encoded).unwrap();
+        assert_eq!(
+            info,
+            super::CollationInfoRef {
+                upward_messages: vec![&[1, 2]],
+                horizontal_messages: vec![(1000, &[3])],
+                new_validation_code: None,
+                processed_downward_messages: 5,
+                hrmp_watermark: 9,
+                head_data: &[4, 5, 6],
+            }
+        );
+
+        // The commitments contain the head data before the processed downward messages and
+        // the HRMP watermark.
+        let commitments = [
+            4, 8, 1, 2, 4, 0xe8, 0x3, 0, 0, 4, 3, 0, 12, 4, 5, 6, 5, 0, 0, 0, 9, 0, 0, 0,
+        ];
+        assert_eq!(info.commitments_hash(), super::pov_hash(&commitments));
+    }
+
+    #[test]
+    fn pov_hash_matches_blake2() {
+        assert_eq!(
+            super::pov_hash(&[]),
+            [
+                14, 87, 81, 192, 38, 229, 67, 178, 232, 171, 46, 176, 96, 153, 218, 161, 209, 229,
+                223, 71, 119, 143, 119, 135, 250, 171, 69, 205, 241, 47, 227, 168
+            ]
+        );
+    }
+}
@@ -160,6 +160,8 @@ pub enum Error {
         /// Error returned by the runtime.
         error: TransactionValidityError,
     },
+    /// Runtime has tried to call a host function that is only available to offchain workers.
+    ForbiddenHostCall,
 }
 
 /// Start a block building process.
@@ -194,6 +196,7 @@ pub fn build_block(config: Config) -> BlockBuild {
         storage_main_trie_changes: Default::default(),
         max_log_level: config.max_log_level,
         calculate_trie_changes: config.calculate_trie_changes,
+        trace: false,
     });
 
     let vm = match init_result {
@@ -294,6 +297,19 @@ impl BlockBuild {
                 (Inner::Runtime(runtime_host::RuntimeHostVm::OffchainStorageSet(inner)), _) => {
                     return BlockBuild::OffchainStorageSet(OffchainStorageSet(inner, shared))
                 }
+                (Inner::Runtime(runtime_host::RuntimeHostVm::SignatureVerification(sig)), _) => {
+                    inner = Inner::Runtime(sig.verify_and_resume());
+                }
+                (Inner::Runtime(runtime_host::RuntimeHostVm::LogEmit(log)), _) => {
+                    // Logs emitted by the runtime are ignored.
+                    inner = Inner::Runtime(log.resume());
+                }
+                (Inner::Runtime(runtime_host::RuntimeHostVm::Offchain(ctx)), _) => {
+                    return BlockBuild::Finished(Err((
+                        Error::ForbiddenHostCall,
+                        ctx.into_prototype(),
+                    )))
+                }
 
                 (
                     Inner::Runtime(runtime_host::RuntimeHostVm::Finished(Ok(success))),
@@ -350,6 +366,7 @@ impl BlockBuild {
                         storage_main_trie_changes: success.storage_changes.into_main_trie_diff(),
                         max_log_level: shared.max_log_level,
                         calculate_trie_changes: shared.calculate_trie_changes,
+                        trace: false,
                     });
 
                     inner = Inner::Runtime(match init_result {
@@ -552,6 +569,7 @@ impl InherentExtrinsics {
             storage_main_trie_changes: self.storage_changes.into_main_trie_diff(),
             max_log_level: self.shared.max_log_level,
             calculate_trie_changes: self.shared.calculate_trie_changes,
+            trace: false,
         });
 
         let vm = match init_result {
@@ -583,6 +601,7 @@ impl ApplyExtrinsic {
             storage_main_trie_changes: self.storage_changes.into_main_trie_diff(),
             max_log_level: self.shared.max_log_level,
             calculate_trie_changes: self.shared.calculate_trie_changes,
+            trace: false,
         });
 
         self.shared.stage = Stage::ApplyExtrinsic(extrinsic);
@@ -606,6 +625,7 @@ impl ApplyExtrinsic {
             storage_main_trie_changes: self.storage_changes.into_main_trie_diff(),
             max_log_level: self.shared.max_log_level,
             calculate_trie_changes: self.shared.calculate_trie_changes,
+            trace: false,
         });
 
         let vm = match init_result {
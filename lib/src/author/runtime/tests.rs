@@ -56,7 +56,11 @@ fn block_building_works() {
             super::BlockBuild::ApplyExtrinsic(ext) => builder = ext.finish(),
             super::BlockBuild::ApplyExtrinsicResult { .. } => unreachable!(),
             super::BlockBuild::InherentExtrinsics(ext) => {
-                builder = ext.inject_inherents(inherents::InherentData { timestamp: 1234 });
+                builder = ext.inject_inherents(inherents::InherentData {
+                    timestamp: 1234,
+                    consensus: None,
+                    parachain: None,
+                });
             }
             super::BlockBuild::StorageGet(get) => {
                 let value = genesis_storage
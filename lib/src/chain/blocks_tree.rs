@@ -207,6 +207,15 @@ impl<T> NonFinalizedTree<T> {
                     block_epoch_information: finalized_block_epoch_information.map(Arc::from),
                     next_epoch_transition: Arc::from(finalized_next_epoch_transition),
                 },
+                chain_information::ChainInformationConsensus::Sassafras {
+                    finalized_block_epoch_information,
+                    finalized_next_epoch_transition,
+                    slots_per_epoch,
+                } => FinalizedConsensus::Sassafras {
+                    slots_per_epoch,
+                    block_epoch_information: finalized_block_epoch_information.map(Arc::from),
+                    next_epoch_transition: Arc::from(finalized_next_epoch_transition),
+                },
             },
             finalized_best_score: BestScore {
                 num_primary_slots: 0,
@@ -274,6 +283,37 @@ impl<T> NonFinalizedTree<T> {
         self.blocks.shrink_to_fit();
     }
 
+    /// Builds a copy of this tree where the user data of every block is replaced with `()`.
+    ///
+    /// This makes it possible to verify headers in the background, for example on a different
+    /// thread, while `self` continues to be used. The headers verified against the copy can
+    /// then be inserted in `self` with [`NonFinalizedTree::revalidate_verified_header`].
+    pub fn clone_without_user_data(&self) -> NonFinalizedTree<()> {
+        NonFinalizedTree {
+            finalized_block_header: self.finalized_block_header.clone(),
+            finalized_block_hash: self.finalized_block_hash,
+            finalized_block_number: self.finalized_block_number,
+            finality: self.finality.clone(),
+            finalized_consensus: self.finalized_consensus.clone(),
+            finalized_best_score: self.finalized_best_score,
+            blocks: self.blocks.map_ref(|block| Block {
+                header: block.header.clone(),
+                hash: block.hash,
+                number: block.number,
+                consensus: block.consensus.clone(),
+                finality: block.finality.clone(),
+                best_score: block.best_score,
+                user_data: (),
+            }),
+            blocks_insertion_counter: self.blocks_insertion_counter,
+            blocks_by_hash: self.blocks_by_hash.clone(),
+            blocks_by_best_score: self.blocks_by_best_score.clone(),
+            blocks_trigger_gp_change: self.blocks_trigger_gp_change.clone(),
+            block_number_bytes: self.block_number_bytes,
+            allow_unknown_consensus_engines: self.allow_unknown_consensus_engines,
+        }
+    }
+
     /// Returns the value that was initially passed in [`Config::block_number_bytes`].
     pub fn block_number_bytes(&self) -> usize {
         self.block_number_bytes
@@ -312,6 +352,17 @@ impl<T> NonFinalizedTree<T> {
                         .map(|info| From::from(&**info)),
                     finalized_next_epoch_transition: next_epoch_transition.as_ref().into(),
                 },
+                FinalizedConsensus::Sassafras {
+                    block_epoch_information,
+                    next_epoch_transition,
+                    slots_per_epoch,
+                } => chain_information::ChainInformationConsensusRef::Sassafras {
+                    slots_per_epoch: *slots_per_epoch,
+                    finalized_block_epoch_information: block_epoch_information
+                        .as_ref()
+                        .map(|info| From::from(&**info)),
+                    finalized_next_epoch_transition: next_epoch_transition.as_ref().into(),
+                },
             },
             finality: match &self.finality {
                 Finality::Outsourced => chain_information::ChainInformationFinalityRef::Outsourced,
@@ -421,6 +472,35 @@ impl<T> NonFinalizedTree<T> {
                     .map(|info| From::from(&**info)),
                 finalized_next_epoch_transition: next_epoch.as_ref().into(),
             },
+            (
+                FinalizedConsensus::Sassafras {
+                    block_epoch_information,
+                    next_epoch_transition,
+                    slots_per_epoch,
+                },
+                None,
+            ) => chain_information::ChainInformationConsensusRef::Sassafras {
+                slots_per_epoch: *slots_per_epoch,
+                finalized_block_epoch_information: block_epoch_information
+                    .as_ref()
+                    .map(|info| From::from(&**info)),
+                finalized_next_epoch_transition: next_epoch_transition.as_ref().into(),
+            },
+            (
+                FinalizedConsensus::Sassafras {
+                    slots_per_epoch, ..
+                },
+                Some(BlockConsensus::Sassafras {
+                    current_epoch,
+                    next_epoch,
+                }),
+            ) => chain_information::ChainInformationConsensusRef::Sassafras {
+                slots_per_epoch: *slots_per_epoch,
+                finalized_block_epoch_information: current_epoch
+                    .as_ref()
+                    .map(|info| From::from(&**info)),
+                finalized_next_epoch_transition: next_epoch.as_ref().into(),
+            },
 
             // Any mismatch of consensus engine between the finalized and best block is not
             // supported at the moment.
@@ -542,6 +622,16 @@ enum FinalizedConsensus {
         /// See [`chain_information::ChainInformationConsensus::Babe::slots_per_epoch`].
         slots_per_epoch: NonZeroU64,
     },
+    Sassafras {
+        /// See [`chain_information::ChainInformationConsensus::Sassafras::finalized_block_epoch_information`].
+        block_epoch_information: Option<Arc<chain_information::SassafrasEpochInformation>>,
+
+        /// See [`chain_information::ChainInformationConsensus::Sassafras::finalized_next_epoch_transition`].
+        next_epoch_transition: Arc<chain_information::SassafrasEpochInformation>,
+
+        /// See [`chain_information::ChainInformationConsensus::Sassafras::slots_per_epoch`].
+        slots_per_epoch: NonZeroU64,
+    },
 }
 
 /// State of the chain finality engine.
@@ -631,6 +721,13 @@ enum BlockConsensus {
         /// Information about the Babe epoch the block belongs to.
         next_epoch: Arc<chain_information::BabeEpochInformation>,
     },
+    Sassafras {
+        /// Information about the Sassafras epoch the block belongs to. `None` if the block
+        /// belongs to epoch #0.
+        current_epoch: Option<Arc<chain_information::SassafrasEpochInformation>>,
+        /// Information about the Sassafras epoch that follows the one the block belongs to.
+        next_epoch: Arc<chain_information::SassafrasEpochInformation>,
+    },
 }
 
 /// Information about finality attached to each block.
@@ -225,15 +225,33 @@ fn polkadot_blocks_0_to_2() {
         _ => panic!(),
     };
 
+    let tree_without_block1 = tree.clone_without_user_data();
     tree.insert_verified_header(verified_header1, ());
 
-    let verified_header2 = match tree.verify_header(block2, Duration::new(0, 0)).unwrap() {
+    // Block 2 is verified against a copy of the tree, then inserted in the original tree.
+    let verified_header2 = match tree
+        .clone_without_user_data()
+        .verify_header(block2, Duration::new(0, 0))
+        .unwrap()
+    {
         HeaderVerifySuccess::Verified {
             verified_header, ..
         } => verified_header,
         _ => panic!(),
     };
 
+    assert!(tree_without_block1
+        .revalidate_verified_header(verified_header2.clone())
+        .is_err());
+
+    let verified_header2 = match tree.revalidate_verified_header(verified_header2).unwrap() {
+        HeaderVerifySuccess::Verified {
+            verified_header,
+            is_new_best: true,
+        } => verified_header,
+        _ => panic!(),
+    };
+
     tree.insert_verified_header(verified_header2, ());
 }
 
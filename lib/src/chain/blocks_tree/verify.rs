@@ -97,6 +97,14 @@ impl<T> NonFinalizedTree<T> {
                         current_epoch: block_epoch_information.clone(),
                         next_epoch: next_epoch_transition.clone(),
                     }),
+                    FinalizedConsensus::Sassafras {
+                        block_epoch_information,
+                        next_epoch_transition,
+                        ..
+                    } => Some(BlockConsensus::Sassafras {
+                        current_epoch: block_epoch_information.clone(),
+                        next_epoch: next_epoch_transition.clone(),
+                    }),
                 };
 
                 let finality = match self.finality {
@@ -158,6 +166,19 @@ impl<T> NonFinalizedTree<T> {
                     slots_per_epoch: *slots_per_epoch,
                     now_from_unix_epoch,
                 },
+                (
+                    FinalizedConsensus::Sassafras {
+                        slots_per_epoch, ..
+                    },
+                    Some(BlockConsensus::Sassafras {
+                        current_epoch,
+                        next_epoch,
+                    }),
+                ) => verify::header_only::ConfigConsensus::Sassafras {
+                    parent_block_epoch: current_epoch.as_ref().map(|v| (&**v).into()),
+                    parent_block_next_epoch: (&**next_epoch).into(),
+                    slots_per_epoch: *slots_per_epoch,
+                },
                 (FinalizedConsensus::Unknown, None) => {
                     return Err(HeaderVerifyError::UnknownConsensusEngine)
                 }
@@ -345,6 +366,120 @@ impl<T> NonFinalizedTree<T> {
                     )
                 }
 
+                // No Sassafras epoch transition. Just a regular block.
+                (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: None,
+                        is_ticket_claim,
+                        ..
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras { .. },
+                    Some(BlockConsensus::Sassafras {
+                        current_epoch,
+                        next_epoch,
+                    }),
+                )
+                | (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: None,
+                        is_ticket_claim,
+                        ..
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras {
+                        block_epoch_information: current_epoch,
+                        next_epoch_transition: next_epoch,
+                        ..
+                    },
+                    None,
+                ) => (
+                    parent_best_score.num_primary_slots + if is_ticket_claim { 1 } else { 0 },
+                    parent_best_score.num_secondary_slots + if is_ticket_claim { 0 } else { 1 },
+                    BlockConsensus::Sassafras {
+                        current_epoch,
+                        next_epoch,
+                    },
+                ),
+
+                // Sassafras epoch transition.
+                (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: Some(epoch_transition_target),
+                        is_ticket_claim,
+                        ..
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras { .. },
+                    Some(BlockConsensus::Sassafras {
+                        next_epoch: next_epoch_transition,
+                        ..
+                    }),
+                )
+                | (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: Some(epoch_transition_target),
+                        is_ticket_claim,
+                        ..
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras {
+                        next_epoch_transition,
+                        ..
+                    },
+                    None,
+                ) if next_epoch_transition.start_slot_number.is_some() => (
+                    parent_best_score.num_primary_slots + if is_ticket_claim { 1 } else { 0 },
+                    parent_best_score.num_secondary_slots + if is_ticket_claim { 0 } else { 1 },
+                    BlockConsensus::Sassafras {
+                        current_epoch: Some(next_epoch_transition),
+                        next_epoch: Arc::new(epoch_transition_target),
+                    },
+                ),
+
+                // Sassafras epoch transition to first epoch.
+                // Should only ever happen when the verified block is block 1.
+                (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: Some(epoch_transition_target),
+                        slot_number,
+                        is_ticket_claim,
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras { .. },
+                    Some(BlockConsensus::Sassafras { next_epoch, .. }),
+                )
+                | (
+                    verify::header_only::Success::Sassafras {
+                        epoch_transition_target: Some(epoch_transition_target),
+                        slot_number,
+                        is_ticket_claim,
+                    },
+                    Some(BlockConsensus::Sassafras { .. }),
+                    FinalizedConsensus::Sassafras {
+                        next_epoch_transition: next_epoch,
+                        ..
+                    },
+                    None,
+                ) => {
+                    debug_assert_eq!(decoded_header.number, 1);
+                    (
+                        parent_best_score.num_primary_slots + if is_ticket_claim { 1 } else { 0 },
+                        parent_best_score.num_secondary_slots + if is_ticket_claim { 0 } else { 1 },
+                        BlockConsensus::Sassafras {
+                            current_epoch: Some(Arc::new(
+                                chain_information::SassafrasEpochInformation {
+                                    start_slot_number: Some(slot_number),
+                                    epoch_index: next_epoch.epoch_index,
+                                    authorities: next_epoch.authorities.clone(),
+                                    randomness: next_epoch.randomness,
+                                },
+                            )),
+                            next_epoch: Arc::new(epoch_transition_target),
+                        },
+                    )
+                }
+
                 // Any mismatch between consensus algorithms should have been detected by the
                 // block verification.
                 _ => unreachable!(),
@@ -437,22 +572,8 @@ impl<T> NonFinalizedTree<T> {
         };
 
         // Determine whether this block would be the new best.
-        let is_new_best = {
-            let current_best_score = self
-                .blocks_by_best_score
-                .last_key_value()
-                .map(|(s, _)| s)
-                .unwrap_or(&self.finalized_best_score);
-
-            let new_block_best_score = BestScore {
-                num_primary_slots: best_score_num_primary_slots,
-                num_secondary_slots: best_score_num_secondary_slots,
-                insertion_counter: self.blocks_insertion_counter,
-            };
-
-            debug_assert_ne!(new_block_best_score, *current_best_score);
-            new_block_best_score > *current_best_score
-        };
+        let is_new_best =
+            self.is_new_best(best_score_num_primary_slots, best_score_num_secondary_slots);
 
         Ok(HeaderVerifySuccess::Verified {
             verified_header: VerifiedHeader {
@@ -463,11 +584,84 @@ impl<T> NonFinalizedTree<T> {
                 best_score_num_primary_slots,
                 best_score_num_secondary_slots,
                 hash,
+                finalized_block_hash: self.finalized_block_hash,
             },
             is_new_best,
         })
     }
 
+    /// Checks whether a [`VerifiedHeader`] that has been obtained by verifying a header against
+    /// another [`NonFinalizedTree`] can be inserted in this one, and if so returns the same value
+    /// as [`NonFinalizedTree::verify_header`] would.
+    ///
+    /// The outcome of the verification of a header only depends on the ancestry of this header.
+    /// A header that has been verified against a copy of this tree (see
+    /// [`NonFinalizedTree::clone_without_user_data`]) can thus be inserted in this tree without
+    /// being verified again, provided that its parent is in this tree and that both trees have
+    /// the same finalized block.
+    ///
+    /// Returns back the [`VerifiedHeader`] if that isn't the case. The header should then be
+    /// verified with [`NonFinalizedTree::verify_header`].
+    pub fn revalidate_verified_header(
+        &self,
+        verified_header: VerifiedHeader,
+    ) -> Result<HeaderVerifySuccess, VerifiedHeader> {
+        if verified_header.finalized_block_hash != self.finalized_block_hash {
+            return Err(verified_header);
+        }
+
+        if self.blocks_by_hash.contains_key(&verified_header.hash) {
+            return Ok(HeaderVerifySuccess::Duplicate);
+        }
+
+        let parent_is_known = match header::decode(
+            &verified_header.scale_encoded_header,
+            self.block_number_bytes,
+        ) {
+            Ok(decoded_header) => {
+                *decoded_header.parent_hash == self.finalized_block_hash
+                    || self.blocks_by_hash.contains_key(decoded_header.parent_hash)
+            }
+            Err(_) => false,
+        };
+        if !parent_is_known {
+            return Err(verified_header);
+        }
+
+        let is_new_best = self.is_new_best(
+            verified_header.best_score_num_primary_slots,
+            verified_header.best_score_num_secondary_slots,
+        );
+
+        Ok(HeaderVerifySuccess::Verified {
+            verified_header,
+            is_new_best,
+        })
+    }
+
+    /// Returns `true` if a block with the given score, if it was inserted now, would become the
+    /// new best block.
+    fn is_new_best(
+        &self,
+        best_score_num_primary_slots: u64,
+        best_score_num_secondary_slots: u64,
+    ) -> bool {
+        let current_best_score = self
+            .blocks_by_best_score
+            .last_key_value()
+            .map(|(s, _)| s)
+            .unwrap_or(&self.finalized_best_score);
+
+        let new_block_best_score = BestScore {
+            num_primary_slots: best_score_num_primary_slots,
+            num_secondary_slots: best_score_num_secondary_slots,
+            insertion_counter: self.blocks_insertion_counter,
+        };
+
+        debug_assert_ne!(new_block_best_score, *current_best_score);
+        new_block_best_score > *current_best_score
+    }
+
     /// Insert a header that has already been verified to be valid.
     ///
     /// # Panic
@@ -544,6 +738,7 @@ impl<T> NonFinalizedTree<T> {
 }
 
 /// Successfully-verified block header that can be inserted into the chain.
+#[derive(Clone)]
 pub struct VerifiedHeader {
     scale_encoded_header: Vec<u8>,
     consensus_update: BlockConsensus,
@@ -552,9 +747,16 @@ pub struct VerifiedHeader {
     best_score_num_secondary_slots: u64,
     hash: [u8; 32],
     number: u64,
+    /// Hash of the finalized block of the tree the header has been verified against.
+    finalized_block_hash: [u8; 32],
 }
 
 impl VerifiedHeader {
+    /// Returns the hash of the block header.
+    pub fn hash(&self) -> &[u8; 32] {
+        &self.hash
+    }
+
     /// Returns the block header.
     pub fn scale_encoded_header(&self) -> &[u8] {
         &self.scale_encoded_header
@@ -155,6 +155,18 @@ impl<'a> From<ChainInformationRef<'a>> for ChainInformation {
                         finalized_next_epoch_transition.into(),
                     ),
                 },
+                ChainInformationConsensusRef::Sassafras {
+                    slots_per_epoch,
+                    finalized_next_epoch_transition,
+                    finalized_block_epoch_information,
+                } => ChainInformationConsensus::Sassafras {
+                    slots_per_epoch,
+                    finalized_block_epoch_information: finalized_block_epoch_information
+                        .map(|i| Box::new(i.into())),
+                    finalized_next_epoch_transition: Box::new(
+                        finalized_next_epoch_transition.into(),
+                    ),
+                },
             },
             finality: info.finality.into(),
         }
@@ -212,6 +224,28 @@ pub enum ChainInformationConsensus {
         /// epoch #0, which can be found by calling the `BabeApi_configuration` runtime function.
         finalized_next_epoch_transition: Box<BabeEpochInformation>,
     },
+
+    /// Chain is using the Sassafras consensus engine.
+    Sassafras {
+        /// Number of slots per epoch. Configured at the genesis block and never touched later.
+        slots_per_epoch: NonZeroU64,
+
+        /// Sassafras epoch information about the epoch the finalized block belongs to.
+        ///
+        /// Must be `None` if and only if the finalized block is block #0.
+        ///
+        /// See [`ChainInformationConsensus::Babe::finalized_block_epoch_information`] for more
+        /// details.
+        finalized_block_epoch_information: Option<Box<SassafrasEpochInformation>>,
+
+        /// Sassafras epoch information about the epoch right after the one the finalized block
+        /// belongs to.
+        ///
+        /// If the finalized block is block #0, then this must contain the information about the
+        /// epoch #0, which can be found by calling the `SassafrasApi_current_epoch` runtime
+        /// function on the genesis block.
+        finalized_next_epoch_transition: Box<SassafrasEpochInformation>,
+    },
 }
 
 /// Information about a Babe epoch.
@@ -269,6 +303,39 @@ impl<'a> From<BabeEpochInformationRef<'a>> for BabeEpochInformation {
     }
 }
 
+/// Information about a Sassafras epoch.
+#[derive(Debug, Clone)]
+pub struct SassafrasEpochInformation {
+    /// Index of the epoch.
+    ///
+    /// Epoch number 0 starts at the slot number of block 1. Epoch indices increase one by one.
+    pub epoch_index: u64,
+
+    /// Slot at which the epoch starts.
+    ///
+    /// Must be `None` if and only if the context is
+    /// [`ChainInformationConsensus::Sassafras::finalized_next_epoch_transition`] and
+    /// [`SassafrasEpochInformation::epoch_index`] is 0.
+    pub start_slot_number: Option<u64>,
+
+    /// Bandersnatch public keys of the authorities allowed to author blocks during this epoch.
+    pub authorities: Vec<[u8; 32]>,
+
+    /// Randomness value for this epoch.
+    pub randomness: [u8; 32],
+}
+
+impl<'a> From<SassafrasEpochInformationRef<'a>> for SassafrasEpochInformation {
+    fn from(info: SassafrasEpochInformationRef<'a>) -> SassafrasEpochInformation {
+        SassafrasEpochInformation {
+            epoch_index: info.epoch_index,
+            start_slot_number: info.start_slot_number,
+            authorities: info.authorities.copied().collect(),
+            randomness: *info.randomness,
+        }
+    }
+}
+
 /// Extra items that depend on the finality engine.
 #[derive(Debug, Clone)]
 pub enum ChainInformationFinality {
@@ -395,6 +462,43 @@ impl<'a> ChainInformationRef<'a> {
             }
         }
 
+        if let ChainInformationConsensusRef::Sassafras {
+            finalized_next_epoch_transition,
+            finalized_block_epoch_information,
+            ..
+        } = &self.consensus
+        {
+            if finalized_next_epoch_transition.start_slot_number.is_some()
+                && (finalized_next_epoch_transition.epoch_index == 0)
+            {
+                return Err(ValidityError::UnexpectedSassafrasSlotStartNumber);
+            }
+            if finalized_next_epoch_transition.start_slot_number.is_none()
+                && (finalized_next_epoch_transition.epoch_index != 0)
+            {
+                return Err(ValidityError::MissingSassafrasSlotStartNumber);
+            }
+
+            if let Some(finalized_block_epoch_information) = &finalized_block_epoch_information {
+                if self.finalized_block_header.number == 0 {
+                    return Err(ValidityError::UnexpectedSassafrasFinalizedEpoch);
+                }
+                if finalized_block_epoch_information
+                    .start_slot_number
+                    .is_none()
+                {
+                    return Err(ValidityError::MissingSassafrasSlotStartNumber);
+                }
+                if finalized_block_epoch_information.epoch_index + 1
+                    != finalized_next_epoch_transition.epoch_index
+                {
+                    return Err(ValidityError::NonLinearSassafrasEpochs);
+                }
+            } else if self.finalized_block_header.number != 0 {
+                return Err(ValidityError::NoSassafrasFinalizedEpoch);
+            }
+        }
+
         if let ChainInformationFinalityRef::Grandpa {
             after_finalized_block_authorities_set_id,
             finalized_scheduled_change,
@@ -443,6 +547,17 @@ impl<'a> From<&'a ChainInformation> for ChainInformationRef<'a> {
                         .map(|i| (&**i).into()),
                     finalized_next_epoch_transition: (&**finalized_next_epoch_transition).into(),
                 },
+                ChainInformationConsensus::Sassafras {
+                    slots_per_epoch,
+                    finalized_block_epoch_information,
+                    finalized_next_epoch_transition,
+                } => ChainInformationConsensusRef::Sassafras {
+                    slots_per_epoch: *slots_per_epoch,
+                    finalized_block_epoch_information: finalized_block_epoch_information
+                        .as_ref()
+                        .map(|i| (&**i).into()),
+                    finalized_next_epoch_transition: (&**finalized_next_epoch_transition).into(),
+                },
             },
             finality: (&info.finality).into(),
         }
@@ -475,6 +590,18 @@ pub enum ChainInformationConsensusRef<'a> {
         /// See equivalent field in [`ChainInformationConsensus`].
         finalized_next_epoch_transition: BabeEpochInformationRef<'a>,
     },
+
+    /// Chain is using the Sassafras consensus engine.
+    Sassafras {
+        /// See equivalent field in [`ChainInformationConsensus`].
+        slots_per_epoch: NonZeroU64,
+
+        /// See equivalent field in [`ChainInformationConsensus`].
+        finalized_block_epoch_information: Option<SassafrasEpochInformationRef<'a>>,
+
+        /// See equivalent field in [`ChainInformationConsensus`].
+        finalized_next_epoch_transition: SassafrasEpochInformationRef<'a>,
+    },
 }
 
 /// Information about a Babe epoch.
@@ -523,6 +650,33 @@ impl<'a> From<&'a BabeEpochInformation> for BabeEpochInformationRef<'a> {
     }
 }
 
+/// Information about a Sassafras epoch.
+#[derive(Debug, Clone)]
+pub struct SassafrasEpochInformationRef<'a> {
+    /// See equivalent field in [`SassafrasEpochInformation`].
+    pub epoch_index: u64,
+
+    /// See equivalent field in [`SassafrasEpochInformation`].
+    pub start_slot_number: Option<u64>,
+
+    /// See equivalent field in [`SassafrasEpochInformation`].
+    pub authorities: header::SassafrasAuthoritiesIter<'a>,
+
+    /// See equivalent field in [`SassafrasEpochInformation`].
+    pub randomness: &'a [u8; 32],
+}
+
+impl<'a> From<&'a SassafrasEpochInformation> for SassafrasEpochInformationRef<'a> {
+    fn from(info: &'a SassafrasEpochInformation) -> SassafrasEpochInformationRef<'a> {
+        SassafrasEpochInformationRef {
+            epoch_index: info.epoch_index,
+            start_slot_number: info.start_slot_number,
+            authorities: header::SassafrasAuthoritiesIter::from_slice(&info.authorities),
+            randomness: &info.randomness,
+        }
+    }
+}
+
 /// Extra items that depend on the finality engine.
 #[derive(Debug, Clone)]
 pub enum ChainInformationFinalityRef<'a> {
@@ -576,6 +730,16 @@ pub enum ValidityError {
     NonLinearBabeEpochs,
     /// Finalized block is not number 0, but no Babe epoch information has been provided.
     NoBabeFinalizedEpoch,
+    /// Found a Sassafras slot start number for future Sassafras epoch number 0.
+    UnexpectedSassafrasSlotStartNumber,
+    /// Missing Sassafras slot start number for Sassafras epoch number other than future epoch 0.
+    MissingSassafrasSlotStartNumber,
+    /// Finalized block is block number 0, and a Sassafras epoch information has been provided.
+    UnexpectedSassafrasFinalizedEpoch,
+    /// Next Sassafras epoch number does not immediately follow current Sassafras epoch number.
+    NonLinearSassafrasEpochs,
+    /// Finalized block is not number 0, but no Sassafras epoch information has been provided.
+    NoSassafrasFinalizedEpoch,
     /// Scheduled GrandPa authorities change is before finalized block.
     ScheduledGrandPaChangeBeforeFinalized,
     /// The finalized block is block number 0, but the GrandPa authorities set id is not 0.
@@ -118,6 +118,10 @@ pub enum Error {
     BabeNextEpochOutputDecode,
     /// Failed to decode the output of the `BabeApi_configuration` runtime call.
     BabeConfigurationOutputDecode,
+    /// Failed to decode the output of the `SassafrasApi_current_epoch` runtime call.
+    SassafrasCurrentEpochOutputDecode,
+    /// Failed to decode the output of the `SassafrasApi_next_epoch` runtime call.
+    SassafrasNextEpochOutputDecode,
     /// The version of `GrandaApi` is too old to be able to build the chain information.
     GrandpaApiTooOld,
     /// Failed to decode the output of the `GrandpaApi_authorities` runtime call.
@@ -139,6 +143,8 @@ pub enum RuntimeCall {
     BabeApiCurrentEpoch,
     BabeApiNextEpoch,
     BabeApiConfiguration,
+    SassafrasApiCurrentEpoch,
+    SassafrasApiNextEpoch,
     GrandpaApiAuthorities,
     GrandpaApiCurrentSetId,
 }
@@ -152,6 +158,8 @@ impl RuntimeCall {
             RuntimeCall::BabeApiCurrentEpoch => "BabeApi_current_epoch",
             RuntimeCall::BabeApiNextEpoch => "BabeApi_next_epoch",
             RuntimeCall::BabeApiConfiguration => "BabeApi_configuration",
+            RuntimeCall::SassafrasApiCurrentEpoch => "SassafrasApi_current_epoch",
+            RuntimeCall::SassafrasApiNextEpoch => "SassafrasApi_next_epoch",
             RuntimeCall::GrandpaApiAuthorities => "GrandpaApi_grandpa_authorities",
             RuntimeCall::GrandpaApiCurrentSetId => "GrandpaApi_current_set_id",
         }
@@ -189,13 +197,14 @@ impl ChainInformationBuild {
     /// decoded.
     ///
     pub fn new(config: Config) -> Self {
-        let [aura_version, babe_version, grandpa_version] = config
+        let [aura_version, babe_version, sassafras_version, grandpa_version] = config
             .runtime
             .runtime_version()
             .decode()
             .apis
-            .find_versions(["AuraApi", "BabeApi", "GrandpaApi"]);
+            .find_versions(["AuraApi", "BabeApi", "SassafrasApi", "GrandpaApi"]);
         let runtime_has_aura = aura_version.map_or(false, |version_number| version_number == 1);
+        let runtime_has_sassafras = sassafras_version == Some(1);
         let runtime_babeapi_is_v1 = babe_version.and_then(|version_number| match version_number {
             1 => Some(true),
             2 => Some(false),
@@ -217,12 +226,15 @@ impl ChainInformationBuild {
             virtual_machine: Some(config.runtime),
             runtime_has_aura,
             runtime_babeapi_is_v1,
+            runtime_has_sassafras,
             runtime_grandpa_supports_currentsetid,
             aura_autorities_call_output: None,
             aura_slot_duration_call_output: None,
             babe_current_epoch_call_output: None,
             babe_next_epoch_call_output: None,
             babe_configuration_call_output: None,
+            sassafras_current_epoch_call_output: None,
+            sassafras_next_epoch_call_output: None,
             grandpa_autorities_call_output: None,
             grandpa_current_set_id_call_output: None,
         };
@@ -438,6 +450,29 @@ impl ChainInformationBuild {
             None
         };
 
+        // The current epoch is also retrieved for the genesis block, as it contains the
+        // information about epoch #0.
+        let sassafras_current_epoch =
+            if inner.runtime_has_sassafras && inner.sassafras_current_epoch_call_output.is_none() {
+                Some(RuntimeCall::SassafrasApiCurrentEpoch)
+            } else {
+                None
+            };
+
+        let sassafras_next_epoch = if matches!(
+            inner.finalized_block_header,
+            ConfigFinalizedBlockHeader::Any {
+                ref scale_encoded_header,
+                ..
+            } if header::decode(scale_encoded_header, inner.block_number_bytes).unwrap().number != 0
+        ) && inner.runtime_has_sassafras
+            && inner.sassafras_next_epoch_call_output.is_none()
+        {
+            Some(RuntimeCall::SassafrasApiNextEpoch)
+        } else {
+            None
+        };
+
         let grandpa_authorities = if !matches!(
             inner.finalized_block_header,
             ConfigFinalizedBlockHeader::Any {
@@ -477,6 +512,8 @@ impl ChainInformationBuild {
             babe_current_epoch,
             babe_next_epoch,
             babe_configuration,
+            sassafras_current_epoch,
+            sassafras_next_epoch,
             grandpa_authorities,
             grandpa_current_set_id,
         ]
@@ -496,6 +533,7 @@ impl ChainInformationBuild {
                 max_log_level: 0,
                 storage_main_trie_changes: Default::default(),
                 calculate_trie_changes: false,
+                trace: false,
             });
 
             let vm = match vm_start_result {
@@ -525,6 +563,36 @@ impl ChainInformationBuild {
                         virtual_machine: inner.virtual_machine.take().unwrap(),
                     }
                 }
+                (true, None, _) | (false, Some(_), _) if inner.runtime_has_sassafras => {
+                    return ChainInformationBuild::Finished {
+                        result: Err(Error::MultipleConsensusAlgorithms),
+                        virtual_machine: inner.virtual_machine.take().unwrap(),
+                    }
+                }
+                (false, None, _) if inner.runtime_has_sassafras => {
+                    let (current_epoch, slots_per_epoch) =
+                        inner.sassafras_current_epoch_call_output.take().unwrap();
+                    if let Some((next_epoch, _)) = inner.sassafras_next_epoch_call_output.take() {
+                        chain_information::ChainInformationConsensus::Sassafras {
+                            slots_per_epoch,
+                            finalized_block_epoch_information: Some(Box::new(current_epoch)),
+                            finalized_next_epoch_transition: Box::new(next_epoch),
+                        }
+                    } else {
+                        // The finalized block is the genesis block, in which case the "current
+                        // epoch" according to the runtime is epoch #0.
+                        chain_information::ChainInformationConsensus::Sassafras {
+                            slots_per_epoch,
+                            finalized_block_epoch_information: None,
+                            finalized_next_epoch_transition: Box::new(
+                                chain_information::SassafrasEpochInformation {
+                                    start_slot_number: None,
+                                    ..current_epoch
+                                },
+                            ),
+                        }
+                    }
+                }
                 (false, None, _) => chain_information::ChainInformationConsensus::Unknown,
                 (
                     false,
@@ -766,6 +834,42 @@ impl ChainInformationBuild {
                             }
                             virtual_machine
                         }
+                        Some(RuntimeCall::SassafrasApiCurrentEpoch) => {
+                            let result = decode_sassafras_epoch_output(
+                                success.virtual_machine.value().as_ref(),
+                                false,
+                            );
+                            let virtual_machine = success.virtual_machine.into_prototype();
+                            match result {
+                                Ok(output) => {
+                                    inner.sassafras_current_epoch_call_output = Some(output)
+                                }
+                                Err(err) => {
+                                    return ChainInformationBuild::Finished {
+                                        result: Err(err),
+                                        virtual_machine,
+                                    };
+                                }
+                            }
+                            virtual_machine
+                        }
+                        Some(RuntimeCall::SassafrasApiNextEpoch) => {
+                            let result = decode_sassafras_epoch_output(
+                                success.virtual_machine.value().as_ref(),
+                                true,
+                            );
+                            let virtual_machine = success.virtual_machine.into_prototype();
+                            match result {
+                                Ok(output) => inner.sassafras_next_epoch_call_output = Some(output),
+                                Err(err) => {
+                                    return ChainInformationBuild::Finished {
+                                        result: Err(err),
+                                        virtual_machine,
+                                    };
+                                }
+                            }
+                            virtual_machine
+                        }
                         Some(RuntimeCall::GrandpaApiAuthorities) => {
                             let result = decode_grandpa_authorities_output(
                                 success.virtual_machine.value().as_ref(),
@@ -877,6 +981,8 @@ struct ChainInformationBuildInner {
     /// If `Some`, the runtime supports `BabeApi` functions. If `true`, the version is 1 (the old
     /// version). If `false`, the version is 2.
     runtime_babeapi_is_v1: Option<bool>,
+    /// If `true`, the runtime supports `SassafrasApi` functions.
+    runtime_has_sassafras: bool,
     /// If `Some`, the runtime supports `GrandpaApi` functions. If `true`, the API supports the
     /// `GrandpaApi_current_set_id` runtime call.
     runtime_grandpa_supports_currentsetid: Option<bool>,
@@ -891,6 +997,14 @@ struct ChainInformationBuildInner {
     babe_next_epoch_call_output: Option<chain_information::BabeEpochInformation>,
     /// Output of the call to `BabeApi_configuration`, if it was already made.
     babe_configuration_call_output: Option<BabeGenesisConfiguration>,
+    /// Output of the call to `SassafrasApi_current_epoch`, if it was already made, alongside
+    /// with the number of slots per epoch.
+    sassafras_current_epoch_call_output:
+        Option<(chain_information::SassafrasEpochInformation, NonZeroU64)>,
+    /// Output of the call to `SassafrasApi_next_epoch`, if it was already made, alongside with
+    /// the number of slots per epoch.
+    sassafras_next_epoch_call_output:
+        Option<(chain_information::SassafrasEpochInformation, NonZeroU64)>,
     /// Output of the call to `GrandpaApi_grandpa_authorities`, if it was already made.
     grandpa_autorities_call_output: Option<Vec<header::GrandpaAuthority>>,
     /// Output of the call to `GrandpaApi_current_set_id`, if it was already made.
@@ -1081,6 +1195,66 @@ fn decode_babe_epoch_output(
     }
 }
 
+/// Decodes the output of a call to `SassafrasApi_current_epoch` (`is_next_epoch` is `false`) or
+/// `SassafrasApi_next_epoch` (`is_next_epoch` is `true`).
+///
+/// The output consists of the index of the epoch, its start slot, its length in slots, its list
+/// of authorities, its randomness, and its tickets configuration. Returns the epoch information
+/// alongside with the length of the epoch.
+fn decode_sassafras_epoch_output(
+    scale_encoded: &'_ [u8],
+    is_next_epoch: bool,
+) -> Result<(chain_information::SassafrasEpochInformation, NonZeroU64), Error> {
+    let mut combinator = nom::combinator::all_consuming(nom::combinator::map(
+        nom::sequence::tuple((
+            nom::number::streaming::le_u64,
+            nom::number::streaming::le_u64,
+            nom::combinator::map_opt(nom::number::streaming::le_u32, |n| {
+                NonZeroU64::new(u64::from(n))
+            }),
+            nom::combinator::flat_map(crate::util::nom_scale_compact_usize, |num_elems| {
+                nom::multi::many_m_n(
+                    num_elems,
+                    num_elems,
+                    nom::combinator::map(nom::bytes::streaming::take(32u32), |public_key| {
+                        <[u8; 32]>::try_from(public_key).unwrap()
+                    }),
+                )
+            }),
+            nom::combinator::map(nom::bytes::streaming::take(32u32), |b| {
+                <[u8; 32]>::try_from(b).unwrap()
+            }),
+            // Tickets configuration, not used at the moment.
+            nom::number::streaming::le_u32,
+            nom::number::streaming::le_u32,
+        )),
+        |(epoch_index, start_slot_number, slots_per_epoch, authorities, randomness, _, _)| {
+            let info = chain_information::SassafrasEpochInformation {
+                epoch_index,
+                // See the equivalent comment in `decode_babe_epoch_output`.
+                start_slot_number: if !is_next_epoch || epoch_index != 0 {
+                    Some(start_slot_number)
+                } else {
+                    None
+                },
+                authorities,
+                randomness,
+            };
+            (info, slots_per_epoch)
+        },
+    ));
+
+    let result: Result<_, nom::Err<nom::error::Error<&'_ [u8]>>> = combinator(scale_encoded);
+    match result {
+        Ok((_, info)) => Ok(info),
+        Err(_) => Err(if is_next_epoch {
+            Error::SassafrasNextEpochOutputDecode
+        } else {
+            Error::SassafrasCurrentEpochOutputDecode
+        }),
+    }
+}
+
 /// Decodes the output of a call to `GrandpaApi_grandpa_authorities`, or the content of the
 /// `:grandpa_authorities` storage item.
 fn decode_grandpa_authorities_output(
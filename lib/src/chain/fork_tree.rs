@@ -197,6 +197,32 @@ impl<T> ForkTree<T> {
         }
     }
 
+    /// Same as [`ForkTree::map`], except that `self` isn't consumed and that the closure is
+    /// passed a reference to each user data.
+    ///
+    /// The returned tree keeps the same [`NodeIndex`]es as `self`.
+    pub fn map_ref<U>(&self, mut map: impl FnMut(&T) -> U) -> ForkTree<U> {
+        ForkTree {
+            nodes: self
+                .nodes
+                .iter()
+                .map(|(index, node)| {
+                    let node = Node {
+                        parent: node.parent,
+                        first_child: node.first_child,
+                        next_sibling: node.next_sibling,
+                        previous_sibling: node.previous_sibling,
+                        is_prune_target_ancestor: node.is_prune_target_ancestor,
+                        data: map(&node.data),
+                    };
+
+                    (index, node)
+                })
+                .collect(),
+            first_root: self.first_root,
+        }
+    }
+
     /// Returns the ancestors of the given node. The iterator is empty if the node doesn't have
     /// any parent.
     ///
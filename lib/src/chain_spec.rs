@@ -118,6 +118,7 @@ impl ChainSpec {
             heap_pages,
             exec_hint: executor::vm::ExecHint::Oneshot,
             allow_unresolved_imports: true,
+            fuel_budget: None,
         })
         .map_err(FromGenesisStorageError::VmInitialization)?;
 
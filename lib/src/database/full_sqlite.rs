@@ -71,10 +71,10 @@
 #![cfg(feature = "database-sqlite")]
 #![cfg_attr(docsrs, doc(cfg(feature = "database-sqlite")))]
 
-use crate::{chain::chain_information, header, util};
+use crate::{chain::chain_information, header, trie, util};
 
 use alloc::borrow::Cow;
-use core::{fmt, iter, num::NonZeroU64};
+use core::{array, fmt, iter, num::NonZeroU64};
 use parking_lot::Mutex;
 use rusqlite::OptionalExtension as _;
 
@@ -277,8 +277,10 @@ impl SqliteFullDatabase {
             meta_get_number(&connection, "aura_slot_duration")?,
             meta_get_number(&connection, "babe_slots_per_epoch")?,
             meta_get_blob(&connection, "babe_finalized_next_epoch")?,
+            meta_get_number(&connection, "sassafras_slots_per_epoch")?,
+            meta_get_blob(&connection, "sassafras_finalized_next_epoch")?,
         ) {
-            (None, Some(slots_per_epoch), Some(finalized_next_epoch)) => {
+            (None, Some(slots_per_epoch), Some(finalized_next_epoch), None, None) => {
                 let slots_per_epoch = expect_nz_u64(slots_per_epoch)?;
                 let finalized_next_epoch_transition =
                     Box::new(decode_babe_epoch_information(&finalized_next_epoch)?);
@@ -293,7 +295,22 @@ impl SqliteFullDatabase {
                     slots_per_epoch,
                 }
             }
-            (Some(slot_duration), None, None) => {
+            (None, None, None, Some(slots_per_epoch), Some(finalized_next_epoch)) => {
+                let slots_per_epoch = expect_nz_u64(slots_per_epoch)?;
+                let finalized_next_epoch_transition =
+                    Box::new(decode_sassafras_epoch_information(&finalized_next_epoch)?);
+                let finalized_block_epoch_information =
+                    meta_get_blob(&connection, "sassafras_finalized_epoch")?
+                        .map(|v| decode_sassafras_epoch_information(&v))
+                        .transpose()?
+                        .map(Box::new);
+                chain_information::ChainInformationConsensus::Sassafras {
+                    finalized_block_epoch_information,
+                    finalized_next_epoch_transition,
+                    slots_per_epoch,
+                }
+            }
+            (Some(slot_duration), None, None, None, None) => {
                 let slot_duration = expect_nz_u64(slot_duration)?;
                 let finalized_authorities_list = aura_finalized_authorities(&connection)?;
                 chain_information::ChainInformationConsensus::Aura {
@@ -301,7 +318,7 @@ impl SqliteFullDatabase {
                     slot_duration,
                 }
             }
-            (None, None, None) => chain_information::ChainInformationConsensus::Unknown,
+            (None, None, None, None, None) => chain_information::ChainInformationConsensus::Unknown,
             _ => {
                 return Err(StorageAccessError::Corrupted(
                     CorruptedError::ConsensusAlgorithmMix,
@@ -565,6 +582,40 @@ impl SqliteFullDatabase {
                 )?;
             }
 
+            if let Some(new_epoch) = block_header.digest.sassafras_epoch_information() {
+                let epoch = meta_get_blob(&transaction, "sassafras_finalized_next_epoch")?.unwrap(); // TODO: don't unwrap
+                let decoded_epoch = decode_sassafras_epoch_information(&epoch)?;
+                transaction.execute(r#"INSERT OR REPLACE INTO meta(key, value_blob) SELECT "sassafras_finalized_epoch", value_blob FROM meta WHERE key = "sassafras_finalized_next_epoch""#, ()).unwrap();
+
+                let slot_number = block_header
+                    .digest
+                    .sassafras_pre_runtime()
+                    .unwrap()
+                    .slot_number;
+                let slots_per_epoch = expect_nz_u64(
+                    meta_get_number(&transaction, "sassafras_slots_per_epoch")?.unwrap(),
+                )?; // TODO: don't unwrap
+
+                let new_epoch = chain_information::SassafrasEpochInformation {
+                    epoch_index: decoded_epoch.epoch_index.checked_add(1).unwrap(),
+                    start_slot_number: Some(
+                        decoded_epoch
+                            .start_slot_number
+                            .unwrap_or(slot_number)
+                            .checked_add(slots_per_epoch.get())
+                            .unwrap(),
+                    ),
+                    authorities: new_epoch.authorities.copied().collect(),
+                    randomness: *new_epoch.randomness,
+                };
+
+                meta_set_blob(
+                    &transaction,
+                    "sassafras_finalized_next_epoch",
+                    &encode_sassafras_epoch_information(From::from(&new_epoch)),
+                )?;
+            }
+
             // TODO: implement Aura
 
             if grandpa_authorities_set_id(&transaction)?.is_some() {
@@ -653,6 +704,150 @@ impl SqliteFullDatabase {
         Ok(())
     }
 
+    /// Inserts trie nodes in the database without associating them to any block.
+    ///
+    /// This is typically used in order to write in the database the storage of a block
+    /// downloaded from the network bit by bit, before calling [`SqliteFullDatabase::reset`].
+    /// Nodes must be inserted after their children and, if they reference the root of a child
+    /// trie, after the nodes of this child trie.
+    ///
+    /// The nodes inserted with this function are removed from the database by the next call to
+    /// [`SqliteFullDatabase::reset`] if they aren't part of the storage of the new finalized
+    /// block.
+    ///
+    /// # Panic
+    ///
+    /// Panics if any of the nodes uses [`InsertTrieNodeStorageValue::SameAsParent`], as there is
+    /// no parent block to copy the storage value from.
+    ///
+    pub fn insert_trie_nodes<'a>(
+        &self,
+        new_trie_nodes: impl Iterator<Item = InsertTrieNode<'a>>,
+        trie_entries_version: u8,
+    ) -> Result<(), CorruptedError> {
+        let mut database = self.database.lock();
+
+        let transaction = database
+            .transaction()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        insert_storage(
+            &transaction,
+            None,
+            new_trie_nodes.inspect(|node| {
+                assert!(!matches!(
+                    node.storage_value,
+                    InsertTrieNodeStorageValue::SameAsParent
+                ));
+            }),
+            trie_entries_version,
+        )?;
+
+        transaction
+            .commit()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(())
+    }
+
+    /// Removes all the blocks and storage from the database and replaces them with the given
+    /// finalized block.
+    ///
+    /// This is typically used after a warp sync, in order to replace the existing chain with
+    /// a finalized block whose ancestry is unknown. The parameters are the same as for
+    /// [`DatabaseEmpty::initialize`].
+    ///
+    /// The storage of the finalized block consists in the nodes passed as parameter plus the
+    /// nodes previously inserted with [`SqliteFullDatabase::insert_trie_nodes`]. All the other
+    /// trie nodes are removed from the database.
+    ///
+    /// # Panic
+    ///
+    /// Panics if any of the nodes uses [`InsertTrieNodeStorageValue::SameAsParent`], as the
+    /// finalized block has no parent in the database.
+    ///
+    pub fn reset<'a>(
+        &self,
+        chain_information: impl Into<chain_information::ChainInformationRef<'a>>,
+        finalized_block_body: impl ExactSizeIterator<Item = &'a [u8]>,
+        finalized_block_justification: Option<Vec<u8>>,
+        finalized_block_storage_entries: impl Iterator<Item = InsertTrieNode<'a>>,
+        finalized_block_state_version: u8,
+    ) -> Result<(), CorruptedError> {
+        let mut database = self.database.lock();
+
+        let transaction = database
+            .transaction()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        // Temporarily disable foreign key checks, as tables are cleared in an arbitrary order.
+        // Note that this is immediately disabled again when we `COMMIT` later down below.
+        transaction
+            .execute("PRAGMA defer_foreign_keys = ON", ())
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        // Trie nodes are removed at the end, as some of them might belong to the storage of the
+        // new finalized block.
+        transaction
+            .execute_batch(
+                r#"
+DELETE FROM meta;
+DELETE FROM blocks_body;
+DELETE FROM blocks;
+DELETE FROM grandpa_triggered_authorities;
+DELETE FROM grandpa_scheduled_authorities;
+DELETE FROM aura_finalized_authorities;
+            "#,
+            )
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        open::insert_finalized_block(
+            &transaction,
+            self.block_number_bytes,
+            chain_information.into(),
+            finalized_block_body,
+            finalized_block_justification,
+            finalized_block_storage_entries.inspect(|node| {
+                assert!(!matches!(
+                    node.storage_value,
+                    InsertTrieNodeStorageValue::SameAsParent
+                ));
+            }),
+            finalized_block_state_version,
+        )?;
+
+        // Remove all the trie nodes that aren't reachable from the storage of the new
+        // finalized block.
+        transaction
+            .execute(
+                r#"
+            WITH RECURSIVE
+                reachable(node_hash) AS (
+                    SELECT state_trie_root_hash FROM blocks WHERE state_trie_root_hash IS NOT NULL
+                    UNION
+                    SELECT trie_node_child.child_hash
+                        FROM reachable
+                        JOIN trie_node_child ON trie_node_child.hash = reachable.node_hash
+                    UNION
+                    SELECT trie_node_storage.trie_root_ref
+                        FROM reachable
+                        JOIN trie_node_storage ON trie_node_storage.node_hash = reachable.node_hash
+                        WHERE trie_node_storage.trie_root_ref IS NOT NULL
+                )
+            DELETE FROM trie_node WHERE hash NOT IN (SELECT node_hash FROM reachable)
+            "#,
+                (),
+            )
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        // If everything went well up to this point, commit the transaction.
+        transaction
+            .commit()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(())
+    }
+
     /// Returns the value associated with a node of the trie of the given block.
     ///
     /// `parent_tries_paths_nibbles` is a list of keys to follow in order to find the root of the
@@ -1012,6 +1207,157 @@ impl SqliteFullDatabase {
 
         Ok(merkle_value)
     }
+
+    /// Returns the trie nodes of the storage of the given block that are necessary in order to
+    /// prove the storage value associated with the given key, or the absence of storage value.
+    ///
+    /// `key_nibbles` must be an iterator to the **nibbles** of the key.
+    ///
+    /// `parent_tries_paths_nibbles` is a list of keys to follow in order to find the root of the
+    /// trie into which `key_nibbles` should be searched. Only the nodes of this trie are returned.
+    /// In order to also prove the Merkle value of the root of a child trie, call this function a
+    /// second time with the path to this child trie passed as `key_nibbles`.
+    ///
+    /// The returned nodes can be passed to
+    /// [`crate::trie::proof_encode::ProofBuilder::set_node_value`].
+    ///
+    /// Returns an empty list if `parent_tries_paths_nibbles` didn't lead to any trie.
+    ///
+    /// # Panics
+    ///
+    /// Panics if any of the values yielded by `parent_tries_paths_nibbles` or `key_nibbles` is
+    /// superior or equal to 16.
+    ///
+    pub fn block_storage_proof_nodes(
+        &self,
+        block_hash: &[u8; 32],
+        parent_tries_paths_nibbles: impl Iterator<Item = impl Iterator<Item = u8>>,
+        key_nibbles: impl Iterator<Item = u8>,
+    ) -> Result<Vec<ProofNode>, StorageAccessError> {
+        let connection = self.database.lock();
+
+        let (state_trie_root_hash, block_has_storage) = connection
+            .prepare_cached(
+                r#"SELECT blocks.state_trie_root_hash, trie_node.hash IS NOT NULL FROM blocks LEFT JOIN trie_node ON trie_node.hash = blocks.state_trie_root_hash WHERE blocks.hash = ?"#,
+            )
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .query_row((&block_hash[..],), |row| {
+                Ok((
+                    row.get::<_, Option<Vec<u8>>>(0)?,
+                    row.get::<_, i64>(1)? != 0,
+                ))
+            })
+            .optional()
+            .map_err(|err| {
+                StorageAccessError::Corrupted(CorruptedError::Internal(InternalError(err)))
+            })?
+            .ok_or(StorageAccessError::UnknownBlock)?;
+
+        let Some(mut trie_root) = state_trie_root_hash.filter(|_| block_has_storage) else {
+            return Err(StorageAccessError::StoragePruned);
+        };
+
+        // Follow the parent tries in order to find the root of the trie to prove.
+        for parent_trie_path in parent_tries_paths_nibbles {
+            let parent_trie_path = parent_trie_path
+                .inspect(|n| assert!(*n < 16))
+                .collect::<Vec<_>>();
+            let path_nodes = trie_path_nodes(&connection, &trie_root, &parent_trie_path)
+                .map_err(StorageAccessError::Corrupted)?;
+            match path_nodes.last() {
+                Some((full_key, node)) if *full_key == parent_trie_path => {
+                    match &node.storage_value {
+                        Some((child_trie_root, true, _)) => trie_root = child_trie_root.clone(),
+                        _ => return Ok(Vec::new()),
+                    }
+                }
+                _ => return Ok(Vec::new()),
+            }
+        }
+
+        let key_nibbles = key_nibbles
+            .inspect(|n| assert!(*n < 16))
+            .collect::<Vec<_>>();
+        let path_nodes = trie_path_nodes(&connection, &trie_root, &key_nibbles)
+            .map_err(StorageAccessError::Corrupted)?;
+
+        path_nodes
+            .into_iter()
+            .map(|(full_key, node)| {
+                // Storage values of version 1 that are 33 bytes or more are hashed in the node
+                // value.
+                let storage_value_hash = match &node.storage_value {
+                    Some((value, _, 1)) if value.len() >= 33 => {
+                        let mut hash = [0; 32];
+                        hash.copy_from_slice(
+                            blake2_rfc::blake2b::blake2b(32, &[], value).as_bytes(),
+                        );
+                        Some(hash)
+                    }
+                    _ => None,
+                };
+
+                let node_value = trie::trie_node::encode_to_vec(trie::trie_node::Decoded {
+                    children: array::from_fn::<_, 16, _>(|n| node.children[n].as_deref()),
+                    partial_key: node
+                        .partial_key
+                        .iter()
+                        .map(|n| trie::Nibble::try_from(*n).unwrap()),
+                    storage_value: match (&node.storage_value, &storage_value_hash) {
+                        (None, _) => trie::trie_node::StorageValue::None,
+                        (Some(_), Some(hash)) => trie::trie_node::StorageValue::Hashed(hash),
+                        (Some((value, _, _)), None) => {
+                            trie::trie_node::StorageValue::Unhashed(value)
+                        }
+                    },
+                })
+                .map_err(|_| StorageAccessError::Corrupted(CorruptedError::InvalidTrieNode))?;
+
+                let unhashed_storage_value = storage_value_hash
+                    .and(node.storage_value)
+                    .map(|(value, _, _)| value);
+
+                Ok(ProofNode {
+                    key_nibbles: full_key,
+                    node_value,
+                    unhashed_storage_value,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the number of times the page cache of SQLite has been hit or missed since the
+    /// database has been opened.
+    ///
+    /// See also [`Config::cache_size`].
+    pub fn cache_statistics(&self) -> CacheStatistics {
+        let connection = self.database.lock();
+
+        let status = |op| {
+            let mut current = 0;
+            let mut highwater = 0;
+            // SAFETY: the connection handle is valid for as long as the connection is alive,
+            // and the lock guarantees that no other thread accesses it at the same time.
+            let result = unsafe {
+                rusqlite::ffi::sqlite3_db_status(
+                    connection.handle(),
+                    op,
+                    &mut current,
+                    &mut highwater,
+                    0,
+                )
+            };
+            debug_assert_eq!(result, rusqlite::ffi::SQLITE_OK);
+            u64::try_from(current).unwrap_or(0)
+        };
+
+        CacheStatistics {
+            hits: status(rusqlite::ffi::SQLITE_DBSTATUS_CACHE_HIT),
+            misses: status(rusqlite::ffi::SQLITE_DBSTATUS_CACHE_MISS),
+        }
+    }
 }
 
 impl fmt::Debug for SqliteFullDatabase {
@@ -1031,6 +1377,27 @@ impl Drop for SqliteFullDatabase {
     }
 }
 
+/// See [`SqliteFullDatabase::cache_statistics`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct CacheStatistics {
+    /// Number of times a page was found in the cache.
+    pub hits: u64,
+    /// Number of times a page had to be read from the disk.
+    pub misses: u64,
+}
+
+/// Trie node returned by [`SqliteFullDatabase::block_storage_proof_nodes`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProofNode {
+    /// Full key of the node within its trie. Each byte is a nibble, in other words is strictly
+    /// inferior to 16.
+    pub key_nibbles: Vec<u8>,
+    /// Node value of the node.
+    pub node_value: Vec<u8>,
+    /// If the node value contains the hash of the storage value, contains the storage value.
+    pub unhashed_storage_value: Option<Vec<u8>>,
+}
+
 pub struct InsertTrieNode<'a> {
     pub merkle_value: Cow<'a, [u8]>,
     pub partial_key_nibbles: Cow<'a, [u8]>,
@@ -1115,8 +1482,12 @@ pub enum CorruptedError {
     ConsensusAlgorithmMix,
     /// The information about a Babe epoch found in the database has failed to decode.
     InvalidBabeEpochInformation,
+    /// The information about a Sassafras epoch found in the database has failed to decode.
+    InvalidSassafrasEpochInformation,
     /// The version information about a storage entry has failed to decode.
     InvalidTrieEntryVersion,
+    /// A trie node references a child that couldn't be found, or can't be encoded.
+    InvalidTrieNode,
     #[display(fmt = "Internal error: {_0}")]
     Internal(InternalError),
 }
@@ -1241,6 +1612,110 @@ fn block_header(
         .map_err(|err| CorruptedError::Internal(InternalError(err)))
 }
 
+/// Content of a trie node loaded by [`trie_path_nodes`].
+struct LoadedTrieNode {
+    partial_key: Vec<u8>,
+    /// Storage value, whether the value is the Merkle value of a child trie, and version.
+    storage_value: Option<(Vec<u8>, bool, u8)>,
+    children: [Option<Vec<u8>>; 16],
+}
+
+/// Walks down the trie whose root has the given Merkle value, following the given key. Returns
+/// the list of nodes that have been traversed, each with its full key within the trie.
+///
+/// The last node of the list is either the node whose full key is equal to `key_nibbles`, or the
+/// node after which the trie diverges from `key_nibbles`.
+fn trie_path_nodes(
+    database: &rusqlite::Connection,
+    trie_root_merkle_value: &[u8],
+    key_nibbles: &[u8],
+) -> Result<Vec<(Vec<u8>, LoadedTrieNode)>, CorruptedError> {
+    let mut node_statement = database
+        .prepare_cached(
+            r#"SELECT trie_node.partial_key, COALESCE(trie_node_storage.value, trie_node_storage.trie_root_ref), trie_node_storage.trie_root_ref IS NOT NULL, trie_node_storage.trie_entry_version FROM trie_node LEFT JOIN trie_node_storage ON trie_node_storage.node_hash = trie_node.hash WHERE trie_node.hash = ?"#,
+        )
+        .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+    let mut children_statement = database
+        .prepare_cached(r#"SELECT child_num, child_hash FROM trie_node_child WHERE hash = ?"#)
+        .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+    let mut out = Vec::new();
+    let mut node_merkle_value = trie_root_merkle_value.to_vec();
+    let mut node_key_prefix = Vec::new();
+
+    loop {
+        let (partial_key, storage_value, is_trie_root_ref, trie_entry_version) = node_statement
+            .query_row((&node_merkle_value,), |row| {
+                Ok((
+                    row.get::<_, Vec<u8>>(0)?,
+                    row.get::<_, Option<Vec<u8>>>(1)?,
+                    row.get::<_, Option<i64>>(2)?.unwrap_or(0) != 0,
+                    row.get::<_, Option<i64>>(3)?,
+                ))
+            })
+            .optional()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+            .ok_or(CorruptedError::InvalidTrieNode)?;
+
+        let storage_value = match (storage_value, trie_entry_version) {
+            (Some(value), Some(version)) => Some((
+                value,
+                is_trie_root_ref,
+                u8::try_from(version).map_err(|_| CorruptedError::InvalidTrieEntryVersion)?,
+            )),
+            _ => None,
+        };
+
+        let mut children: [Option<Vec<u8>>; 16] = Default::default();
+        for child in children_statement
+            .query_map((&node_merkle_value,), |row| {
+                Ok((row.get::<_, Vec<u8>>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?
+        {
+            let (child_num, child_merkle_value) =
+                child.map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+            let slot = child_num
+                .first()
+                .and_then(|n| children.get_mut(usize::from(*n)))
+                .ok_or(CorruptedError::InvalidTrieNode)?;
+            *slot = Some(child_merkle_value);
+        }
+
+        let mut full_key = node_key_prefix;
+        full_key.extend_from_slice(&partial_key);
+
+        let next_child = if key_nibbles.len() > full_key.len() && key_nibbles.starts_with(&full_key)
+        {
+            let child_num = key_nibbles[full_key.len()];
+            children[usize::from(child_num)]
+                .clone()
+                .map(|child| (child_num, child))
+        } else {
+            None
+        };
+
+        out.push((
+            full_key.clone(),
+            LoadedTrieNode {
+                partial_key,
+                storage_value,
+                children,
+            },
+        ));
+
+        let Some((child_num, child_merkle_value)) = next_child else {
+            break;
+        };
+
+        node_key_prefix = full_key;
+        node_key_prefix.push(child_num);
+        node_merkle_value = child_merkle_value;
+    }
+
+    Ok(out)
+}
+
 fn set_best_chain(
     database: &rusqlite::Connection,
     new_best_block_hash: &[u8],
@@ -1686,3 +2161,55 @@ fn decode_babe_epoch_information(
 
     result.map_err(|()| CorruptedError::InvalidBabeEpochInformation)
 }
+
+fn encode_sassafras_epoch_information(
+    info: chain_information::SassafrasEpochInformationRef,
+) -> Vec<u8> {
+    let mut out = Vec::with_capacity(53 + info.authorities.len() * 32);
+    out.extend_from_slice(&info.epoch_index.to_le_bytes());
+    if let Some(start_slot_number) = info.start_slot_number {
+        out.extend_from_slice(&[1]);
+        out.extend_from_slice(&start_slot_number.to_le_bytes());
+    } else {
+        out.extend_from_slice(&[0]);
+    }
+    out.extend_from_slice(util::encode_scale_compact_usize(info.authorities.len()).as_ref());
+    for authority in info.authorities {
+        out.extend_from_slice(authority);
+    }
+    out.extend_from_slice(info.randomness);
+    out
+}
+
+fn decode_sassafras_epoch_information(
+    value: &[u8],
+) -> Result<chain_information::SassafrasEpochInformation, CorruptedError> {
+    nom::combinator::all_consuming(nom::combinator::map(
+        nom::sequence::tuple((
+            nom::number::streaming::le_u64,
+            util::nom_option_decode(nom::number::streaming::le_u64),
+            nom::combinator::flat_map(crate::util::nom_scale_compact_usize, |num_elems| {
+                nom::multi::many_m_n(
+                    num_elems,
+                    num_elems,
+                    nom::combinator::map(nom::bytes::streaming::take(32u32), |public_key| {
+                        <[u8; 32]>::try_from(public_key).unwrap()
+                    }),
+                )
+            }),
+            nom::bytes::streaming::take(32u32),
+        )),
+        |(epoch_index, start_slot_number, authorities, randomness)| {
+            chain_information::SassafrasEpochInformation {
+                epoch_index,
+                start_slot_number,
+                authorities,
+                randomness: TryFrom::try_from(randomness).unwrap(),
+            }
+        },
+    ))(value)
+    .map(|(_, v)| v)
+    .map_err(|_: nom::Err<nom::error::Error<&[u8]>>| {
+        CorruptedError::InvalidSassafrasEpochInformation
+    })
+}
@@ -22,7 +22,8 @@
 // TODO:remove all the unwraps in this module that shouldn't be there
 
 use super::{
-    encode_babe_epoch_information, insert_storage, CorruptedError, InsertTrieNode, InternalError,
+    encode_babe_epoch_information, encode_sassafras_epoch_information, insert_storage,
+    meta_set_blob, meta_set_number, CorruptedError, InsertTrieNode, InternalError,
     SqliteFullDatabase,
 };
 use crate::chain::chain_information;
@@ -144,6 +145,18 @@ Keys in that table:
  finalized block is block #0, then this contains information about epoch #0. Missing if and
  only if the chain doesn't use Babe.
 
+ - `sassafras_slots_per_epoch` (number): Number of slots per Sassafras epoch. Missing if and
+ only if the chain doesn't use Sassafras.
+
+ - `sassafras_finalized_epoch` (blob): Encoding of a structure that contains the information
+ about the Sassafras epoch used for the finalized block. Missing if and only if the finalized
+ block is block #0 or the chain doesn't use Sassafras.
+
+ - `sassafras_finalized_next_epoch` (blob): Encoding of a structure that contains the information
+ about the Sassafras epoch that follows the one described by `sassafras_finalized_epoch`. If the
+ finalized block is block #0, then this contains information about epoch #0. Missing if and
+ only if the chain doesn't use Sassafras.
+
 */
 CREATE TABLE meta(
     key STRING NOT NULL PRIMARY KEY,
@@ -197,7 +210,7 @@ List of all known blocks, indexed by their hash or number.
 */
 CREATE TABLE blocks(
     hash BLOB NOT NULL PRIMARY KEY,
-    parent_hash BLOB,  -- NULL only for the genesis block
+    parent_hash BLOB,  -- NULL only for the block the database has been initialized with, as its parent is unknown
     state_trie_root_hash BLOB,  -- NULL if and only if the trie is empty or if the trie storage has been pruned from the database
     number INTEGER NOT NULL,
     header BLOB NOT NULL,
@@ -363,83 +376,129 @@ impl DatabaseEmpty {
             .execute("PRAGMA defer_foreign_keys = ON", ())
             .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
 
-        let chain_information = chain_information.into();
-
-        let finalized_block_hash = chain_information
-            .finalized_block_header
-            .hash(self.block_number_bytes);
-
-        let scale_encoded_finalized_block_header = chain_information
-            .finalized_block_header
-            .scale_encoding(self.block_number_bytes)
-            .fold(Vec::new(), |mut a, b| {
-                a.extend_from_slice(b.as_ref());
-                a
-            });
-
-        insert_storage(
+        insert_finalized_block(
             &transaction,
-            None,
+            self.block_number_bytes,
+            chain_information.into(),
+            finalized_block_body,
+            finalized_block_justification,
             finalized_block_storage_entries,
             finalized_block_state_version,
         )?;
 
         transaction
-            .prepare_cached(
-                "INSERT INTO blocks(hash, parent_hash, state_trie_root_hash, number, header, is_best_chain, justification) VALUES(?, ?, ?, ?, ?, TRUE, ?)",
-            )
-            .unwrap()
-            .execute((
-                &finalized_block_hash[..],
-                if chain_information.finalized_block_header.number != 0 {
-                    Some(&chain_information.finalized_block_header.parent_hash[..])
-                } else { None },
-                &chain_information.finalized_block_header.state_root[..],
-                i64::try_from(chain_information.finalized_block_header.number).unwrap(),
-                &scale_encoded_finalized_block_header[..],
-                finalized_block_justification.as_deref(),
-            ))
+            .commit()
+            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
+
+        Ok(SqliteFullDatabase {
+            database: parking_lot::Mutex::new(self.database),
+            block_number_bytes: self.block_number_bytes,
+        })
+    }
+}
+
+/// Inserts the given finalized block and its storage in the database.
+///
+/// The database is expected to be empty and foreign key checks to be deferred.
+pub(super) fn insert_finalized_block<'a>(
+    transaction: &rusqlite::Transaction,
+    block_number_bytes: usize,
+    chain_information: chain_information::ChainInformationRef<'a>,
+    finalized_block_body: impl ExactSizeIterator<Item = &'a [u8]>,
+    finalized_block_justification: Option<Vec<u8>>,
+    finalized_block_storage_entries: impl Iterator<Item = InsertTrieNode<'a>>,
+    finalized_block_state_version: u8,
+) -> Result<(), CorruptedError> {
+    let finalized_block_hash = chain_information
+        .finalized_block_header
+        .hash(block_number_bytes);
+
+    let scale_encoded_finalized_block_header = chain_information
+        .finalized_block_header
+        .scale_encoding(block_number_bytes)
+        .fold(Vec::new(), |mut a, b| {
+            a.extend_from_slice(b.as_ref());
+            a
+        });
+
+    insert_storage(
+        transaction,
+        None,
+        finalized_block_storage_entries,
+        finalized_block_state_version,
+    )?;
+
+    // The parent of the finalized block is never in the database, and `parent_hash` is thus
+    // always `NULL` in order to satisfy the foreign key constraint.
+    transaction
+        .prepare_cached(
+            "INSERT INTO blocks(hash, parent_hash, state_trie_root_hash, number, header, is_best_chain, justification) VALUES(?, NULL, ?, ?, ?, TRUE, ?)",
+        )
+        .unwrap()
+        .execute((
+            &finalized_block_hash[..],
+            &chain_information.finalized_block_header.state_root[..],
+            i64::try_from(chain_information.finalized_block_header.number).unwrap(),
+            &scale_encoded_finalized_block_header[..],
+            finalized_block_justification.as_deref(),
+        ))
+        .unwrap();
+
+    {
+        let mut statement = transaction
+            .prepare_cached("INSERT INTO blocks_body(hash, idx, extrinsic) VALUES(?, ?, ?)")
             .unwrap();
+        for (index, item) in finalized_block_body.enumerate() {
+            statement
+                .execute((
+                    &finalized_block_hash[..],
+                    i64::try_from(index).unwrap(),
+                    item,
+                ))
+                .unwrap();
+        }
+    }
+
+    meta_set_blob(transaction, "best", &finalized_block_hash[..]).unwrap();
+    meta_set_number(
+        transaction,
+        "finalized",
+        chain_information.finalized_block_header.number,
+    )?;
+
+    match &chain_information.finality {
+        chain_information::ChainInformationFinalityRef::Outsourced => {}
+        chain_information::ChainInformationFinalityRef::Grandpa {
+            finalized_triggered_authorities,
+            after_finalized_block_authorities_set_id,
+            finalized_scheduled_change,
+        } => {
+            meta_set_number(
+                transaction,
+                "grandpa_authorities_set_id",
+                *after_finalized_block_authorities_set_id,
+            )?;
 
-        {
             let mut statement = transaction
-                .prepare_cached("INSERT INTO blocks_body(hash, idx, extrinsic) VALUES(?, ?, ?)")
+                .prepare_cached("INSERT INTO grandpa_triggered_authorities(idx, public_key, weight) VALUES(?, ?, ?)")
                 .unwrap();
-            for (index, item) in finalized_block_body.enumerate() {
+            for (index, item) in finalized_triggered_authorities.iter().enumerate() {
                 statement
                     .execute((
-                        &finalized_block_hash[..],
                         i64::try_from(index).unwrap(),
-                        item,
+                        &item.public_key[..],
+                        i64::from_ne_bytes(item.weight.get().to_ne_bytes()),
                     ))
                     .unwrap();
             }
-        }
-
-        super::meta_set_blob(&transaction, "best", &finalized_block_hash[..]).unwrap();
-        super::meta_set_number(
-            &transaction,
-            "finalized",
-            chain_information.finalized_block_header.number,
-        )?;
 
-        match &chain_information.finality {
-            chain_information::ChainInformationFinalityRef::Outsourced => {}
-            chain_information::ChainInformationFinalityRef::Grandpa {
-                finalized_triggered_authorities,
-                after_finalized_block_authorities_set_id,
-                finalized_scheduled_change,
-            } => {
-                super::meta_set_number(
-                    &transaction,
-                    "grandpa_authorities_set_id",
-                    *after_finalized_block_authorities_set_id,
-                )?;
+            if let Some((height, list)) = finalized_scheduled_change {
+                meta_set_number(transaction, "grandpa_scheduled_target", *height)?;
 
                 let mut statement = transaction
-                    .prepare_cached("INSERT INTO grandpa_triggered_authorities(idx, public_key, weight) VALUES(?, ?, ?)")
+                    .prepare_cached("INSERT INTO grandpa_scheduled_authorities(idx, public_key, weight) VALUES(?, ?, ?)")
                     .unwrap();
-                for (index, item) in finalized_triggered_authorities.iter().enumerate() {
+                for (index, item) in list.iter().enumerate() {
                     statement
                         .execute((
                             i64::try_from(index).unwrap(),
@@ -448,75 +507,80 @@ impl DatabaseEmpty {
                         ))
                         .unwrap();
                 }
-
-                if let Some((height, list)) = finalized_scheduled_change {
-                    super::meta_set_number(&transaction, "grandpa_scheduled_target", *height)?;
-
-                    let mut statement = transaction
-                        .prepare_cached("INSERT INTO grandpa_scheduled_authorities(idx, public_key, weight) VALUES(?, ?, ?)")
-                        .unwrap();
-                    for (index, item) in list.iter().enumerate() {
-                        statement
-                            .execute((
-                                i64::try_from(index).unwrap(),
-                                &item.public_key[..],
-                                i64::from_ne_bytes(item.weight.get().to_ne_bytes()),
-                            ))
-                            .unwrap();
-                    }
-                }
             }
         }
+    }
 
-        match &chain_information.consensus {
-            chain_information::ChainInformationConsensusRef::Unknown => {}
-            chain_information::ChainInformationConsensusRef::Aura {
-                finalized_authorities_list,
-                slot_duration,
-            } => {
-                super::meta_set_number(&transaction, "aura_slot_duration", slot_duration.get())
-                    .unwrap();
+    match &chain_information.consensus {
+        chain_information::ChainInformationConsensusRef::Unknown => {}
+        chain_information::ChainInformationConsensusRef::Aura {
+            finalized_authorities_list,
+            slot_duration,
+        } => {
+            meta_set_number(transaction, "aura_slot_duration", slot_duration.get()).unwrap();
 
-                let mut statement = transaction
-                    .prepare_cached(
-                        "INSERT INTO aura_finalized_authorities(idx, public_key) VALUES(?, ?)",
-                    )
+            let mut statement = transaction
+                .prepare_cached(
+                    "INSERT INTO aura_finalized_authorities(idx, public_key) VALUES(?, ?)",
+                )
+                .unwrap();
+            for (index, item) in finalized_authorities_list.clone().enumerate() {
+                statement
+                    .execute((i64::try_from(index).unwrap(), &item.public_key[..]))
                     .unwrap();
-                for (index, item) in finalized_authorities_list.clone().enumerate() {
-                    statement
-                        .execute((i64::try_from(index).unwrap(), &item.public_key[..]))
-                        .unwrap();
-                }
             }
-            chain_information::ChainInformationConsensusRef::Babe {
-                slots_per_epoch,
-                finalized_next_epoch_transition,
-                finalized_block_epoch_information,
-            } => {
-                super::meta_set_number(&transaction, "babe_slots_per_epoch", slots_per_epoch.get())
-                    .unwrap();
-                super::meta_set_blob(
-                    &transaction,
-                    "babe_finalized_next_epoch",
-                    &encode_babe_epoch_information(finalized_next_epoch_transition.clone())[..],
+        }
+        chain_information::ChainInformationConsensusRef::Babe {
+            slots_per_epoch,
+            finalized_next_epoch_transition,
+            finalized_block_epoch_information,
+        } => {
+            meta_set_number(transaction, "babe_slots_per_epoch", slots_per_epoch.get()).unwrap();
+            meta_set_blob(
+                transaction,
+                "babe_finalized_next_epoch",
+                &encode_babe_epoch_information(finalized_next_epoch_transition.clone())[..],
+            )
+            .unwrap();
+
+            if let Some(finalized_block_epoch_information) = finalized_block_epoch_information {
+                meta_set_blob(
+                    transaction,
+                    "babe_finalized_epoch",
+                    &encode_babe_epoch_information(finalized_block_epoch_information.clone())[..],
                 )
                 .unwrap();
-
-                if let Some(finalized_block_epoch_information) = finalized_block_epoch_information {
-                    super::meta_set_blob(&transaction, "babe_finalized_epoch", &encode_babe_epoch_information(
-            finalized_block_epoch_information.clone(),
-        )[..]).unwrap();
-                }
             }
         }
+        chain_information::ChainInformationConsensusRef::Sassafras {
+            slots_per_epoch,
+            finalized_next_epoch_transition,
+            finalized_block_epoch_information,
+        } => {
+            meta_set_number(
+                transaction,
+                "sassafras_slots_per_epoch",
+                slots_per_epoch.get(),
+            )
+            .unwrap();
+            meta_set_blob(
+                transaction,
+                "sassafras_finalized_next_epoch",
+                &encode_sassafras_epoch_information(finalized_next_epoch_transition.clone())[..],
+            )
+            .unwrap();
 
-        transaction
-            .commit()
-            .map_err(|err| CorruptedError::Internal(InternalError(err)))?;
-
-        Ok(SqliteFullDatabase {
-            database: parking_lot::Mutex::new(self.database),
-            block_number_bytes: self.block_number_bytes,
-        })
+            if let Some(finalized_block_epoch_information) = finalized_block_epoch_information {
+                meta_set_blob(
+                    transaction,
+                    "sassafras_finalized_epoch",
+                    &encode_sassafras_epoch_information(finalized_block_epoch_information.clone())
+                        [..],
+                )
+                .unwrap();
+            }
+        }
     }
+
+    Ok(())
 }
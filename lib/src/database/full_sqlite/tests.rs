@@ -115,12 +115,11 @@ fn empty_database_fill_then_query() {
         }
 
         // Store the trie in the database.
+        let state_root = trie
+            .root_user_data()
+            .map(|n| *<&[u8; 32]>::try_from(n.1.as_ref().unwrap().as_ref()).unwrap())
+            .unwrap_or(trie::EMPTY_BLAKE2_TRIE_MERKLE_VALUE);
         let open_db = {
-            let state_root = &trie
-                .root_user_data()
-                .map(|n| *<&[u8; 32]>::try_from(n.1.as_ref().unwrap().as_ref()).unwrap())
-                .unwrap_or(trie::EMPTY_BLAKE2_TRIE_MERKLE_VALUE);
-
             let trie_entries_linear =
                 trie.iter_unordered()
                     .collect::<Vec<_>>()
@@ -165,7 +164,7 @@ fn empty_database_fill_then_query() {
                         number: 0,
                         extrinsics_root: &[0; 32],
                         parent_hash: &[0; 32],
-                        state_root,
+                        state_root: &state_root,
                         digest: header::DigestRef::empty(),
                     },
                     consensus: chain_information::ChainInformationConsensusRef::Unknown,
@@ -243,6 +242,49 @@ fn empty_database_fill_then_query() {
             );
         }
 
+        // Ask random storage proofs.
+        for _ in 0..1024 {
+            let key = (0..uniform_sample(0, 4))
+                .map(|_| uniform_sample(0, 255))
+                .collect::<Vec<_>>();
+            let proof_nodes = open_db
+                .block_storage_proof_nodes(
+                    &block0_hash,
+                    iter::empty::<iter::Empty<_>>(),
+                    trie::bytes_to_nibbles(key.iter().copied()).map(u8::from),
+                )
+                .unwrap();
+            let mut proof = trie::proof_encode::ProofBuilder::new();
+            for node in proof_nodes {
+                proof.set_node_value(
+                    &node
+                        .key_nibbles
+                        .iter()
+                        .map(|n| trie::Nibble::try_from(*n).unwrap())
+                        .collect::<Vec<_>>(),
+                    &node.node_value,
+                    node.unhashed_storage_value.as_deref(),
+                );
+            }
+            let proof = trie::proof_decode::decode_and_verify_proof(trie::proof_decode::Config {
+                proof: proof.build_to_vec(),
+            })
+            .unwrap();
+            let expected = trie
+                .node_by_full_key(trie::bytes_to_nibbles(key.iter().copied()))
+                .and_then(|n| trie[n].0.clone());
+            assert_eq!(
+                proof
+                    .storage_value(&state_root, &key)
+                    .unwrap()
+                    .map(|(v, _)| v.to_vec()),
+                expected,
+                "\nkey = {:?}\ntrie = {:?}",
+                key.iter().map(|n| format!("{:x}", n)).collect::<String>(),
+                trie
+            );
+        }
+
         // Ask random closest descendant Merkle values.
         for _ in 0..1024 {
             let key = (0..uniform_sample(0, 8))
@@ -272,3 +314,234 @@ fn empty_database_fill_then_query() {
         }
     }
 }
+
+#[test]
+fn reset_replaces_content() {
+    // Builds a trie containing a single storage item at the root, and returns its Merkle value.
+    fn single_node_trie(value: &[u8]) -> ([u8; 32], InsertTrieNode) {
+        let merkle_value = trie::trie_node::calculate_merkle_value(
+            trie::trie_node::Decoded {
+                children: [None::<&[u8]>; 16],
+                partial_key: iter::empty(),
+                storage_value: trie::trie_node::StorageValue::Unhashed(value),
+            },
+            trie::HashFunction::Blake2,
+            true,
+        )
+        .unwrap();
+
+        let root = *<&[u8; 32]>::try_from(merkle_value.as_ref()).unwrap();
+        let node = InsertTrieNode {
+            storage_value: InsertTrieNodeStorageValue::Value {
+                value: Cow::Owned(value.to_vec()),
+                references_merkle_value: false,
+            },
+            merkle_value: Cow::Owned(root.to_vec()),
+            children_merkle_values: array::from_fn(|_| None),
+            partial_key_nibbles: Cow::Owned(Vec::new()),
+        };
+        (root, node)
+    }
+
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let (genesis_state_root, genesis_node) = single_node_trie(b"genesis");
+    let open_db = empty_db
+        .initialize(
+            chain_information::ChainInformationRef {
+                finalized_block_header: header::HeaderRef {
+                    number: 0,
+                    extrinsics_root: &[0; 32],
+                    parent_hash: &[0; 32],
+                    state_root: &genesis_state_root,
+                    digest: header::DigestRef::empty(),
+                },
+                consensus: chain_information::ChainInformationConsensusRef::Unknown,
+                finality: chain_information::ChainInformationFinalityRef::Outsourced,
+            },
+            iter::empty(),
+            None,
+            iter::once(genesis_node),
+            0,
+        )
+        .unwrap();
+    let genesis_hash = open_db.finalized_block_hash().unwrap();
+
+    let (new_state_root, new_node) = single_node_trie(b"warp synced");
+    open_db
+        .reset(
+            chain_information::ChainInformationRef {
+                finalized_block_header: header::HeaderRef {
+                    number: 5,
+                    extrinsics_root: &[0; 32],
+                    parent_hash: &[1; 32],
+                    state_root: &new_state_root,
+                    digest: header::DigestRef::empty(),
+                },
+                consensus: chain_information::ChainInformationConsensusRef::Unknown,
+                finality: chain_information::ChainInformationFinalityRef::Outsourced,
+            },
+            iter::empty(),
+            None,
+            iter::once(new_node),
+            0,
+        )
+        .unwrap();
+
+    let new_hash = open_db.finalized_block_hash().unwrap();
+    assert_ne!(new_hash, genesis_hash);
+    assert_eq!(open_db.best_block_hash().unwrap(), new_hash);
+    assert!(open_db.block_hash_by_number(0).unwrap().next().is_none());
+    assert!(open_db
+        .block_scale_encoded_header(&genesis_hash)
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        open_db
+            .block_storage_get(
+                &new_hash,
+                iter::empty::<iter::Empty<_>>(),
+                iter::empty::<u8>(),
+            )
+            .unwrap(),
+        Some((b"warp synced".to_vec(), 0))
+    );
+}
+
+#[test]
+fn reset_with_inserted_trie_nodes() {
+    let DatabaseOpen::Empty(empty_db) = open(Config {
+        block_number_bytes: 4,
+        cache_size: 2 * 1024 * 1024,
+        ty: ConfigTy::Memory,
+    })
+    .unwrap() else {
+        panic!()
+    };
+
+    let genesis_state_root = *<&[u8; 32]>::try_from(
+        trie::trie_node::calculate_merkle_value(
+            trie::trie_node::Decoded {
+                children: [None::<&[u8]>; 16],
+                partial_key: iter::empty(),
+                storage_value: trie::trie_node::StorageValue::Unhashed(b"genesis"),
+            },
+            trie::HashFunction::Blake2,
+            true,
+        )
+        .unwrap()
+        .as_ref(),
+    )
+    .unwrap();
+    let open_db = empty_db
+        .initialize(
+            chain_information::ChainInformationRef {
+                finalized_block_header: header::HeaderRef {
+                    number: 0,
+                    extrinsics_root: &[0; 32],
+                    parent_hash: &[0; 32],
+                    state_root: &genesis_state_root,
+                    digest: header::DigestRef::empty(),
+                },
+                consensus: chain_information::ChainInformationConsensusRef::Unknown,
+                finality: chain_information::ChainInformationFinalityRef::Outsourced,
+            },
+            iter::empty(),
+            None,
+            iter::once(InsertTrieNode {
+                storage_value: InsertTrieNodeStorageValue::Value {
+                    value: Cow::Borrowed(&b"genesis"[..]),
+                    references_merkle_value: false,
+                },
+                merkle_value: Cow::Borrowed(&genesis_state_root[..]),
+                children_merkle_values: array::from_fn(|_| None),
+                partial_key_nibbles: Cow::Borrowed(&[][..]),
+            }),
+            0,
+        )
+        .unwrap();
+    let genesis_hash = open_db.finalized_block_hash().unwrap();
+
+    // Insert the storage of the new block in several steps, as if it was downloaded from the
+    // network.
+    let entries: [(&[u8], &[u8]); 4] = [(b"a", b"1"), (b"ab", b"2"), (b"abc", b"3"), (b"b", b"4")];
+    let mut builder = trie::ordered_builder::OrderedTrieBuilder::new(
+        trie::HashFunction::Blake2,
+        trie::TrieEntryVersion::V0,
+    );
+    let to_insert_node = |node: &trie::ordered_builder::Node| InsertTrieNode {
+        merkle_value: Cow::Owned(node.merkle_value.as_ref().to_vec()),
+        partial_key_nibbles: Cow::Owned(node.partial_key.iter().map(|n| u8::from(*n)).collect()),
+        children_merkle_values: array::from_fn(|n| {
+            node.children_merkle_values[n]
+                .as_ref()
+                .map(|child| Cow::Owned(child.as_ref().to_vec()))
+        }),
+        storage_value: match &node.storage_value {
+            Some(value) => InsertTrieNodeStorageValue::Value {
+                value: Cow::Owned(value.clone()),
+                references_merkle_value: false,
+            },
+            None => InsertTrieNodeStorageValue::NoValue,
+        },
+    };
+    for (key, value) in entries {
+        let nodes = builder.push(key, value).unwrap();
+        open_db
+            .insert_trie_nodes(nodes.iter().map(to_insert_node), 0)
+            .unwrap();
+    }
+    let (last_nodes, state_root) = builder.finish();
+    open_db
+        .insert_trie_nodes(last_nodes.iter().map(to_insert_node), 0)
+        .unwrap();
+
+    open_db
+        .reset(
+            chain_information::ChainInformationRef {
+                finalized_block_header: header::HeaderRef {
+                    number: 5,
+                    extrinsics_root: &[0; 32],
+                    parent_hash: &[1; 32],
+                    state_root: &state_root,
+                    digest: header::DigestRef::empty(),
+                },
+                consensus: chain_information::ChainInformationConsensusRef::Unknown,
+                finality: chain_information::ChainInformationFinalityRef::Outsourced,
+            },
+            iter::empty(),
+            None,
+            iter::empty(),
+            0,
+        )
+        .unwrap();
+
+    let new_hash = open_db.finalized_block_hash().unwrap();
+    for (key, value) in entries {
+        assert_eq!(
+            open_db
+                .block_storage_get(
+                    &new_hash,
+                    iter::empty::<iter::Empty<_>>(),
+                    trie::bytes_to_nibbles(key.iter().copied()).map(u8::from),
+                )
+                .unwrap(),
+            Some((value.to_vec(), 0))
+        );
+    }
+
+    assert!(open_db
+        .block_storage_get(
+            &genesis_hash,
+            iter::empty::<iter::Empty<_>>(),
+            iter::empty()
+        )
+        .is_err());
+}
@@ -140,6 +140,18 @@
 //! The Wasm execution is fully deterministic, and the outcome of the execution only depends on
 //! the inputs. There is, for example, no implicit injection of randomness or of the current time.
 //!
+//! ## Fuel budget
+//!
+//! Nothing guarantees that a call to the runtime ever finishes. When executing a runtime that
+//! can't be trusted, [`Config::fuel_budget`] can be used in order to bound the amount of
+//! computation that each call is allowed to perform. Each WebAssembly instruction consumes a
+//! certain amount of "fuel", and an [`Error::OutOfFuel`] is generated once the budget has been
+//! exhausted. Since the fuel consumption is deterministic, the same call with the same inputs
+//! always either succeeds or runs out of fuel.
+//!
+//! When a budget is set, [`ReadyToRun::run`] also periodically returns a [`HostVm::ReadyToRun`],
+//! giving the opportunity to the caller to yield to other tasks before continuing the execution.
+//!
 //! ## Example
 //!
 //! ```
@@ -153,7 +165,8 @@
 //!         module: &wasm_binary_code,
 //!         heap_pages: HeapPages::from(2048),
 //!         exec_hint: smoldot::executor::vm::ExecHint::Oneshot,
-//!         allow_unresolved_imports: false
+//!         allow_unresolved_imports: false,
+//!         fuel_budget: None,
 //!     }).unwrap();
 //!     prototype.run_no_param("Core_version").unwrap().into()
 //! };
@@ -197,6 +210,7 @@ use crate::{trie, util};
 use alloc::{borrow::ToOwned as _, boxed::Box, string::String, sync::Arc, vec, vec::Vec};
 use core::{fmt, hash::Hasher as _, iter, str};
 use functions::HostFunction;
+use rand_chacha::rand_core::SeedableRng as _;
 
 pub mod runtime_version;
 
@@ -232,6 +246,15 @@ pub struct Config<TModule> {
     /// a [`Error::UnresolvedFunctionCalled`] error will be generated if the module tries to call
     /// an unresolved function.
     pub allow_unresolved_imports: bool,
+
+    /// If `Some`, maximum amount of fuel that each call to the runtime is allowed to consume,
+    /// after which an [`Error::OutOfFuel`] is generated. If `None`, the execution isn't bounded.
+    ///
+    /// The amount of fuel consumed is roughly equal to the number of WebAssembly instructions
+    /// that have been executed. Setting a budget slows down the execution.
+    ///
+    /// See [the module-level documentation](..).
+    pub fuel_budget: Option<u64>,
 }
 
 /// Prototype for an [`HostVm`].
@@ -272,11 +295,55 @@ struct VmCommon {
     /// Total number of pages of Wasm memory. This is equal to `heap_base / 64k` (rounded up) plus
     /// `heap_pages`.
     memory_total_pages: HeapPages,
+
+    /// Value of [`Config::fuel_budget`] passed to [`HostVmPrototype::new`].
+    fuel_budget: Option<u64>,
 }
 
 impl HostVmPrototype {
     /// Creates a new [`HostVmPrototype`]. Parses and potentially JITs the module.
     pub fn new(config: Config<impl AsRef<[u8]>>) -> Result<Self, NewErr> {
+        // Safety: no compiled artifact is passed.
+        unsafe { Self::new_inner(config, None) }
+    }
+
+    /// Similar to [`HostVmPrototype::new`], but loads the module from an artifact previously
+    /// returned by [`HostVmPrototype::serialize_compiled`] rather than compiling it.
+    ///
+    /// [`Config::module`] must still contain the code of the runtime, as it is used in order to
+    /// determine the runtime version. See [`vm::VirtualMachinePrototype::from_compiled`] for more
+    /// details.
+    ///
+    /// # Safety
+    ///
+    /// See [`vm::VirtualMachinePrototype::from_compiled`].
+    ///
+    pub unsafe fn from_compiled(
+        config: Config<impl AsRef<[u8]>>,
+        compiled: &[u8],
+    ) -> Result<Self, NewErr> {
+        Self::new_inner(config, Some(compiled))
+    }
+
+    /// Returns an artifact containing the compiled version of the runtime, that can later be
+    /// passed to [`HostVmPrototype::from_compiled`].
+    ///
+    /// Returns `None` if the runtime isn't compiled, for example if it is executed with an
+    /// interpreter.
+    pub fn serialize_compiled(&self) -> Option<Vec<u8>> {
+        self.vm_proto.serialize_compiled()
+    }
+
+    /// Implementation of [`HostVmPrototype::new`] and [`HostVmPrototype::from_compiled`].
+    ///
+    /// # Safety
+    ///
+    /// See [`vm::VirtualMachinePrototype::from_compiled`] if `compiled` is `Some`.
+    ///
+    unsafe fn new_inner(
+        config: Config<impl AsRef<[u8]>>,
+        compiled: Option<&[u8]>,
+    ) -> Result<Self, NewErr> {
         // The maximum allowed size for the decompressed Wasm code needs to be the same amongst
         // all implementations.
         // See <https://github.com/paritytech/substrate/blob/f9d10fabe04d598d68f8b097cc4905adbb1ad630/primitives/maybe-compressed-blob/src/lib.rs#L37>.
@@ -321,9 +388,18 @@ impl HostVmPrototype {
         // array.
         let (mut vm_proto, registered_functions) = {
             let mut registered_functions = Vec::new();
-            let vm_proto = vm::VirtualMachinePrototype::new(vm::Config {
+            let vm_config = vm::Config {
                 module_bytes: &module_bytes[..],
                 exec_hint: config.exec_hint,
+                // Substrate runtimes are nowadays compiled with the sign-extension operators and
+                // the bulk memory operations enabled. Enabling these features never causes a
+                // runtime that would be valid without them to be rejected.
+                features: vm::WasmFeatures {
+                    sign_extension: true,
+                    bulk_memory: true,
+                    ..vm::WasmFeatures::mvp()
+                },
+                fuel_metering: config.fuel_budget.is_some(),
                 // This closure is called back for each function that the runtime imports.
                 symbols: &mut |mod_name, f_name, signature| {
                     if mod_name != "env" {
@@ -344,7 +420,11 @@ impl HostVmPrototype {
                     });
                     Ok(id)
                 },
-            })?;
+            };
+            let vm_proto = match compiled {
+                Some(compiled) => vm::VirtualMachinePrototype::from_compiled(vm_config, compiled)?,
+                None => vm::VirtualMachinePrototype::new(vm_config)?,
+            };
             (vm_proto, registered_functions.into())
         };
 
@@ -375,6 +455,7 @@ impl HostVmPrototype {
                 registered_functions,
                 heap_pages: config.heap_pages,
                 memory_total_pages,
+                fuel_budget: config.fuel_budget,
             }),
         };
 
@@ -429,6 +510,11 @@ impl HostVmPrototype {
         self.common.heap_pages
     }
 
+    /// Returns the value of [`Config::fuel_budget`] that was passed to [`HostVmPrototype::new`].
+    pub fn fuel_budget(&self) -> Option<u64> {
+        self.common.fuel_budget
+    }
+
     /// Returns the runtime version found in the module.
     pub fn runtime_version(&self) -> &CoreVersion {
         self.common
@@ -474,6 +560,9 @@ impl HostVmPrototype {
 
         // Prepare the virtual machine for execution.
         let mut vm = self.vm_proto.prepare();
+        if let Some(fuel_budget) = self.common.fuel_budget {
+            vm.add_fuel(fuel_budget);
+        }
 
         // Write the input data in the VM's memory using the allocator.
         let data_ptr = match allocator.allocate(
@@ -664,6 +753,10 @@ impl HostVm {
     }
 }
 
+/// Amount of fuel after which [`ReadyToRun::run`] returns in order to give the opportunity to
+/// the caller to yield.
+const FUEL_YIELD_INTERVAL: u64 = 50_000_000;
+
 /// Virtual machine is ready to run.
 pub struct ReadyToRun {
     inner: Box<Inner>,
@@ -673,17 +766,48 @@ pub struct ReadyToRun {
 impl ReadyToRun {
     /// Runs the virtual machine until something important happens.
     ///
+    /// If a [`Config::fuel_budget`] has been set, this function might also return a
+    /// [`HostVm::ReadyToRun`] after a certain amount of fuel has been consumed, so that the
+    /// caller can yield to other tasks before continuing.
+    ///
     /// > **Note**: This is when the actual CPU-heavy computation happens.
-    pub fn run(mut self) -> HostVm {
+    pub fn run(self) -> HostVm {
+        self.run_inner(None)
+    }
+
+    /// Similar to [`ReadyToRun::run`], but additionally pushes on `host_functions_called` the
+    /// name of every host function that the runtime calls, in the order in which they are called.
+    ///
+    /// If the returned [`HostVm`] is the result of a host function call, this host function is
+    /// the last element that has been pushed.
+    pub fn run_traced(self, host_functions_called: &mut Vec<&'static str>) -> HostVm {
+        self.run_inner(Some(host_functions_called))
+    }
+
+    fn run_inner(mut self, mut host_functions_called: Option<&mut Vec<&'static str>>) -> HostVm {
+        let fuel_consumed_start = self.inner.vm.fuel_consumed();
+
         loop {
-            match self.run_once() {
-                HostVm::ReadyToRun(r) => self = r,
+            match self.run_once(host_functions_called.as_deref_mut()) {
+                HostVm::ReadyToRun(r) => {
+                    // Note that the check is only performed in-between host function calls,
+                    // meaning that more fuel than `FUEL_YIELD_INTERVAL` might have been consumed.
+                    if let (Some(start), Some(now)) =
+                        (fuel_consumed_start, r.inner.vm.fuel_consumed())
+                    {
+                        if now.saturating_sub(start) >= FUEL_YIELD_INTERVAL {
+                            return HostVm::ReadyToRun(r);
+                        }
+                    }
+
+                    self = r;
+                }
                 other => return other,
             }
         }
     }
 
-    fn run_once(mut self) -> HostVm {
+    fn run_once(mut self, host_functions_called: Option<&mut Vec<&'static str>>) -> HostVm {
         // `vm::ExecOutcome::Interrupted` is by far the variant that requires the most
         // handling code. As such, special-case all other variants before.
         let (id, params) = match self.inner.vm.run(self.resume_value) {
@@ -753,6 +877,13 @@ impl ReadyToRun {
                 }
             }
 
+            Err(vm::RunErr::OutOfFuel) => {
+                return HostVm::Error {
+                    error: Error::OutOfFuel,
+                    prototype: self.inner.into_prototype(),
+                }
+            }
+
             Err(vm::RunErr::BadValueTy { .. }) => {
                 // Tried to inject back the value returned by a host function, but it doesn't
                 // match what the Wasm code expects. Given that we check the host function
@@ -782,6 +913,10 @@ impl ReadyToRun {
             None => unreachable!(),
         };
 
+        if let Some(host_functions_called) = host_functions_called {
+            host_functions_called.push(host_fn.name());
+        }
+
         // Passed a parameter index. Produces an `impl AsRef<[u8]>`.
         macro_rules! expect_pointer_size {
             ($num:expr) => {{
@@ -1657,7 +1792,7 @@ impl ReadyToRun {
                     };
                 }
 
-                self.inner.signatures_batch_verification = Some(true);
+                self.inner.signatures_batch_verification = Some(SignaturesBatch::new());
 
                 HostVm::ReadyToRun(ReadyToRun {
                     resume_value: None,
@@ -1665,13 +1800,15 @@ impl ReadyToRun {
                 })
             }
             HostFunction::ext_crypto_finish_batch_verify_version_1 => {
-                let Some(outcome) = self.inner.signatures_batch_verification.take() else {
+                let Some(batch) = self.inner.signatures_batch_verification.take() else {
                     return HostVm::Error {
                         error: Error::NoBatchVerify,
                         prototype: self.inner.into_prototype(),
                     };
                 };
 
+                let outcome = batch.verify();
+
                 HostVm::ReadyToRun(ReadyToRun {
                     resume_value: Some(vm::WasmValue::I32(if outcome { 1 } else { 0 })),
                     inner: self.inner,
@@ -3030,11 +3167,77 @@ impl SignatureVerification {
     }
 
     /// Verify the signature and resume execution.
-    pub fn verify_and_resume(self) -> HostVm {
+    ///
+    /// If the signature is part of a batch verification, it is instead added to the batch and
+    /// only verified when the runtime finishes the batch, all the signatures of the batch being
+    /// verified at once.
+    pub fn verify_and_resume(mut self) -> HostVm {
+        if self.is_batch_verification {
+            self.queue_in_batch();
+            return self.resume(true);
+        }
+
         let success = self.is_valid();
         self.resume(success)
     }
 
+    /// Adds the signature to the batch of signatures being verified.
+    fn queue_in_batch(&mut self) {
+        debug_assert!(self.is_batch_verification);
+
+        let message = self.message().as_ref().to_vec();
+        let signature = self.signature().as_ref().to_vec();
+        let public_key = self.public_key().as_ref().to_vec();
+
+        let queued = match self.algorithm {
+            SignatureVerificationAlgorithm::Ed25519 => {
+                let batch = self.inner.signatures_batch_verification.as_mut().unwrap();
+                batch.ed25519.push(ed25519_zebra::batch::Item::from((
+                    ed25519_zebra::VerificationKeyBytes::from(
+                        <[u8; 32]>::try_from(&public_key[..]).unwrap_or_else(|_| unreachable!()),
+                    ),
+                    ed25519_zebra::Signature::from(
+                        <[u8; 64]>::try_from(&signature[..]).unwrap_or_else(|_| unreachable!()),
+                    ),
+                    &message,
+                )));
+                true
+            }
+            SignatureVerificationAlgorithm::Sr25519V1 => {
+                // Signatures that don't have the schnorrkel marker bit are only accepted for
+                // backwards compatibility reasons and can't be batched. They are verified
+                // immediately.
+                match (
+                    schnorrkel::PublicKey::from_bytes(&public_key),
+                    schnorrkel::Signature::from_bytes(&signature),
+                ) {
+                    (Ok(public_key), Ok(signature)) => {
+                        let batch = self.inner.signatures_batch_verification.as_mut().unwrap();
+                        batch.sr25519.push((message.clone(), signature, public_key));
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            SignatureVerificationAlgorithm::Sr25519V2
+            | SignatureVerificationAlgorithm::Ecdsa
+            | SignatureVerificationAlgorithm::EcdsaPrehashed => false,
+        };
+
+        if !queued && !self.is_valid() {
+            self.inner
+                .signatures_batch_verification
+                .as_mut()
+                .unwrap()
+                .all_valid = false;
+        }
+
+        let batch = self.inner.signatures_batch_verification.as_mut().unwrap();
+        batch.seed.update(&public_key);
+        batch.seed.update(&signature);
+        batch.seed.update(&message);
+    }
+
     /// Resume the execution assuming that the signature is valid.
     ///
     /// > **Note**: You are strongly encouraged to call
@@ -3058,7 +3261,9 @@ impl SignatureVerification {
             !self.is_batch_verification || self.inner.signatures_batch_verification.is_some()
         );
         if self.is_batch_verification && !success {
-            self.inner.signatures_batch_verification = Some(false);
+            if let Some(batch) = self.inner.signatures_batch_verification.as_mut() {
+                batch.all_valid = false;
+            }
         }
 
         // All signature-related host functions work the same way in terms of return value.
@@ -3099,6 +3304,13 @@ impl CallRuntimeVersion {
             .unwrap_or_else(|_| unreachable!())
     }
 
+    /// Returns the [`Config::fuel_budget`] of the virtual machine being executed. Since the Wasm
+    /// code returned by [`CallRuntimeVersion::wasm_code`] is provided by this virtual machine,
+    /// it should be subject to the same budget.
+    pub fn fuel_budget(&self) -> Option<u64> {
+        self.inner.common.fuel_budget
+    }
+
     /// Writes the SCALE-encoded runtime version to the memory and prepares for execution.
     ///
     /// If an error happened during the execution (such as an invalid Wasm binary code), pass
@@ -3668,13 +3880,11 @@ struct Inner {
     storage_transaction_depth: u32,
 
     /// The host provides a "batch signature verification" mechanism, where the runtime can start
-    /// verifying multiple signatures at once. This mechanism is deprecated, but is emulated
-    /// through a simple field.
+    /// verifying multiple signatures at once.
     ///
     /// Contains `Some` if and only if the runtime is currently within a batch signatures
-    /// verification. If `Some`, contains `true` if all the signatures have been verified
-    /// successfully so far.
-    signatures_batch_verification: Option<bool>,
+    /// verification.
+    signatures_batch_verification: Option<SignaturesBatch>,
 
     /// Memory allocator in order to answer the calls to `malloc` and `free`.
     allocator: allocator::FreeingBumpHeapAllocator,
@@ -3683,6 +3893,93 @@ struct Inner {
     common: Box<VmCommon>,
 }
 
+/// Signatures whose verification has been requested by the runtime within a batch signatures
+/// verification. See [`Inner::signatures_batch_verification`].
+///
+/// The Ed25519 and Sr25519 signatures are verified all at once when the batch is finished, which
+/// is considerably faster than verifying them one by one. Other signatures are verified
+/// immediately.
+struct SignaturesBatch {
+    /// `false` if a signature that has been verified immediately was invalid.
+    all_valid: bool,
+
+    /// Ed25519 signatures to verify.
+    ed25519: Vec<ed25519_zebra::batch::Item>,
+
+    /// Sr25519 signatures to verify, as message, signature, and public key.
+    sr25519: Vec<(Vec<u8>, schnorrkel::Signature, schnorrkel::PublicKey)>,
+
+    /// Hash of all the signatures of the batch, used to seed the randomness used by the batch
+    /// verification algorithms.
+    ///
+    /// The randomness used by the batch verification algorithms must not be predictable by
+    /// whoever has generated the signatures. Deriving it from the signatures themselves achieves
+    /// this while keeping the execution of the runtime deterministic.
+    seed: blake2_rfc::blake2b::Blake2b,
+}
+
+impl SignaturesBatch {
+    fn new() -> Self {
+        SignaturesBatch {
+            all_valid: true,
+            ed25519: Vec::new(),
+            sr25519: Vec::new(),
+            seed: blake2_rfc::blake2b::Blake2b::new(32),
+        }
+    }
+
+    /// Verifies all the signatures of the batch. Returns `true` if they are all valid.
+    fn verify(self) -> bool {
+        if !self.all_valid {
+            return false;
+        }
+
+        let mut randomness = rand_chacha::ChaCha20Rng::from_seed(
+            <[u8; 32]>::try_from(self.seed.finalize().as_bytes())
+                .unwrap_or_else(|_| unreachable!()),
+        );
+
+        if !self.ed25519.is_empty() {
+            // Note that batched Ed25519 verification uses a special flavour of Ed25519 where
+            // ambiguities are removed, and that this flavour is also used when verifying
+            // signatures one by one.
+            // See <https://docs.rs/ed25519-zebra/2.2.0/ed25519_zebra/batch/index.html> and
+            // <https://github.com/zcash/zips/blob/master/zip-0215.rst>
+            let mut verifier = ed25519_zebra::batch::Verifier::new();
+            for item in self.ed25519 {
+                verifier.queue(item);
+            }
+            if verifier.verify(&mut randomness).is_err() {
+                return false;
+            }
+        }
+
+        if !self.sr25519.is_empty() {
+            let context = schnorrkel::signing_context(b"substrate");
+            let (transcripts, (signatures, public_keys)): (Vec<_>, (Vec<_>, Vec<_>)) = self
+                .sr25519
+                .into_iter()
+                .map(|(message, signature, public_key)| {
+                    (context.bytes(&message), (signature, public_key))
+                })
+                .unzip();
+            if schnorrkel::verify_batch_rng(
+                transcripts,
+                &signatures,
+                &public_keys,
+                false,
+                &mut randomness,
+            )
+            .is_err()
+            {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
 impl Inner {
     /// Uses the memory allocator to allocate some memory for the given data, writes the data in
     /// memory, and returns an [`HostVm`] ready for the Wasm `host_fn` return.
@@ -3901,6 +4198,9 @@ pub enum Error {
     /// Error in the Wasm code execution.
     #[display(fmt = "{_0}")]
     Trap(vm::Trap),
+    /// The call has consumed all the fuel of its [`Config::fuel_budget`].
+    #[display(fmt = "Execution has exceeded its fuel budget")]
+    OutOfFuel,
     /// A non-`i64` value has been returned by the Wasm entry point.
     #[display(fmt = "A non-I64 value has been returned: {actual:?}")]
     BadReturnValue {
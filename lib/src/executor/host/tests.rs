@@ -94,6 +94,7 @@ fn basic_core_version() {
             heap_pages: HeapPages::new(2048),
             exec_hint,
             allow_unresolved_imports: true,
+            fuel_budget: None,
         })
         .unwrap();
 
@@ -132,6 +133,7 @@ fn min_requirements() {
     for exec_hint in ExecHint::available_engines() {
         HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -215,6 +215,7 @@ macro_rules! gen_test {
             for exec_hint in ExecHint::available_engines() {
                 let proto = HostVmPrototype::new(Config {
                     allow_unresolved_imports: false,
+                    fuel_budget: None,
                     exec_hint,
                     heap_pages: HeapPages::new(1024),
                     module: &module_bytes,
@@ -27,6 +27,7 @@ fn invalid_wasm() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -45,6 +46,7 @@ fn invalid_zstd() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -71,6 +73,7 @@ fn valid_zstd() {
     for exec_hint in ExecHint::available_engines() {
         HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -95,6 +98,7 @@ fn no_heap_base() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -122,6 +126,7 @@ fn memory_max_size_too_low() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -150,6 +155,7 @@ fn unresolved_host_functions_setting() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: true,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -160,6 +166,7 @@ fn unresolved_host_functions_setting() {
 
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -190,6 +197,7 @@ fn host_function_bad_signature() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: true,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -200,6 +208,7 @@ fn host_function_bad_signature() {
 
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -219,6 +228,7 @@ fn rococo_genesis_works() {
     for exec_hint in ExecHint::available_engines() {
         assert!(HostVmPrototype::new(Config {
             allow_unresolved_imports: true,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
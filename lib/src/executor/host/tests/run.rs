@@ -37,6 +37,7 @@ fn function_to_run_doesnt_exist() {
     for exec_hint in ExecHint::available_engines() {
         let host_vm = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -68,6 +69,7 @@ fn function_to_run_invalid_params() {
     for exec_hint in ExecHint::available_engines() {
         let host_vm = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -197,6 +199,7 @@ fn input_provided_correctly() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -219,6 +222,7 @@ fn input_provided_correctly() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -246,6 +250,7 @@ fn input_provided_correctly() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -352,6 +357,7 @@ fn large_input_provided_correctly() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -415,6 +421,7 @@ fn return_value_works() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -469,6 +476,7 @@ fn bad_return_value() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -528,6 +536,7 @@ fn returned_ptr_out_of_range() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -587,6 +596,7 @@ fn returned_size_out_of_range() {
     for exec_hint in ExecHint::available_engines() {
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -653,6 +663,7 @@ fn unresolved_host_function_called() {
     for exec_hint in ExecHint::available_engines() {
         match HostVmPrototype::new(Config {
             allow_unresolved_imports: false,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -663,6 +674,7 @@ fn unresolved_host_function_called() {
 
         let proto = HostVmPrototype::new(Config {
             allow_unresolved_imports: true,
+            fuel_budget: None,
             exec_hint,
             heap_pages: HeapPages::new(1024),
             module: &module_bytes,
@@ -685,4 +697,48 @@ fn unresolved_host_function_called() {
     }
 }
 
+#[test]
+fn fuel_budget_exceeded() {
+    let module_bytes = with_core_version_custom_sections(
+        wat::parse_str(
+            r#"
+    (module
+        (import "env" "memory" (memory 0))
+        (global (export "__heap_base") i32 (i32.const 0))
+        (func (export "test") (param i32 i32) (result i64)
+            (loop $l (br $l))
+            i64.const 0)
+    )
+    "#,
+        )
+        .unwrap(),
+    );
+
+    for exec_hint in ExecHint::available_engines() {
+        let mut proto = HostVmPrototype::new(Config {
+            allow_unresolved_imports: false,
+            fuel_budget: Some(1_000_000),
+            exec_hint,
+            heap_pages: HeapPages::new(1024),
+            module: &module_bytes,
+        })
+        .unwrap();
+
+        // Each call has its own budget, meaning that the prototype can be used again.
+        for _ in 0..2 {
+            let mut vm = HostVm::from(proto.run("test", &[]).unwrap());
+            proto = loop {
+                match vm {
+                    HostVm::ReadyToRun(r) => vm = r.run(),
+                    HostVm::Error {
+                        error: Error::OutOfFuel,
+                        prototype,
+                    } => break prototype,
+                    _ => unreachable!(),
+                }
+            };
+        }
+    }
+}
+
 // TODO: consider more tests for the other errors here, or add them on a host-function case-by-case basis
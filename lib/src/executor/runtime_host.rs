@@ -31,6 +31,8 @@
 //! - Keeps track of the logs generated by the call and concatenates them into a [`String`].
 //! - Automatically handles some externalities, such as calculating the Merkle root or storage
 //!   transactions.
+//! - Optionally, if [`Config::trace`] is `true`, records every storage access, host function
+//!   call and log emitted by the runtime into a list of [`TraceEvent`]s.
 //!
 //! These additional features considerably reduces the number of externals concepts to plug to
 //! the virtual machine.
@@ -60,6 +62,9 @@ mod tests;
 /// Configuration for [`run`].
 pub struct Config<'a, TParams> {
     /// Virtual machine to be run.
+    ///
+    /// If a [`host::Config::fuel_budget`] was passed when creating this virtual machine, the
+    /// call fails with an [`ErrorDetail::OutOfFuel`] if it exceeds this budget.
     pub virtual_machine: host::HostVmPrototype,
 
     /// Name of the function to be called.
@@ -86,6 +91,12 @@ pub struct Config<'a, TParams> {
     /// If `true`, then [`StorageChanges::trie_changes_iter_ordered`] will return `Some`.
     /// Passing `None` requires fewer calculation and fewer storage accesses.
     pub calculate_trie_changes: bool,
+
+    /// If `true`, then [`Success::trace`] and [`Error::trace`] contain the list of everything
+    /// that the runtime has done during the execution. If `false`, they are always empty.
+    ///
+    /// Tracing slows down the execution and is meant to be used for debugging purposes.
+    pub trace: bool,
 }
 
 /// Start running the WebAssembly virtual machine.
@@ -124,6 +135,7 @@ pub fn run(
         logs: String::new(),
         max_log_level: config.max_log_level,
         calculate_trie_changes: config.calculate_trie_changes,
+        trace: if config.trace { Some(Vec::new()) } else { None },
     }
     .run())
 }
@@ -141,6 +153,9 @@ pub struct Success {
     pub state_trie_version: TrieEntryVersion,
     /// Concatenation of all the log messages printed by the runtime.
     pub logs: String,
+    /// List of everything that the runtime has done during the execution, in chronological
+    /// order. Always empty if [`Config::trace`] was `false`.
+    pub trace: Vec<TraceEvent>,
 }
 
 /// See [`Success::storage_changes`].
@@ -471,6 +486,106 @@ pub struct Error {
     pub detail: ErrorDetail,
     /// Prototype of the virtual machine that was passed through [`Config::virtual_machine`].
     pub prototype: host::HostVmPrototype,
+    /// List of everything that the runtime has done during the execution before the error
+    /// happened, in chronological order. Always empty if [`Config::trace`] was `false`.
+    pub trace: Vec<TraceEvent>,
+}
+
+/// Event that happened during the execution. See [`Config::trace`].
+///
+/// Storage accesses that are performed internally, such as the ones necessary in order to
+/// calculate the trie root hash, are not reported.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TraceEvent {
+    /// The runtime has called a host function. The events that directly follow this one, up
+    /// until the next [`TraceEvent::HostFunctionCall`], have been caused by this call.
+    HostFunctionCall {
+        /// Name of the host function, for example `ext_storage_get_version_1`.
+        name: &'static str,
+    },
+    /// The runtime has read a storage value.
+    StorageRead {
+        /// Child trie the value was read from, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Key that was read.
+        key: Vec<u8>,
+        /// Value that was read, or `None` if there is no storage value at this key.
+        value: Option<Vec<u8>>,
+    },
+    /// The runtime has written or removed a storage value.
+    ///
+    /// Keys removed as part of a [`TraceEvent::StorageClearPrefix`] are also reported as
+    /// removals.
+    StorageWrite {
+        /// Child trie the value was written to, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Key that was written.
+        key: Vec<u8>,
+        /// New value, or `None` if the value was removed.
+        value: Option<Vec<u8>>,
+    },
+    /// The runtime has appended an item to a storage value.
+    StorageAppend {
+        /// Child trie the value belongs to, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Key that was appended to.
+        key: Vec<u8>,
+        /// Item that was appended.
+        value: Vec<u8>,
+    },
+    /// The runtime has started removing all the keys that start with a certain prefix. The
+    /// keys that are removed are reported as [`TraceEvent::StorageWrite`]s.
+    StorageClearPrefix {
+        /// Child trie the keys belong to, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Prefix of the keys to remove.
+        prefix: Vec<u8>,
+        /// Maximum number of keys to remove requested by the runtime.
+        max_keys_to_remove: Option<u32>,
+    },
+    /// The runtime has requested the key that follows another one.
+    StorageNextKey {
+        /// Child trie that was searched, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Key whose next key was requested.
+        key: Vec<u8>,
+        /// Key that follows [`TraceEvent::StorageNextKey::key`], if any.
+        next_key: Option<Vec<u8>>,
+    },
+    /// The runtime has requested the root hash of a trie.
+    StorageRoot {
+        /// Child trie whose root hash was requested, or `None` for the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Root hash that was calculated.
+        hash: [u8; 32],
+    },
+    /// The runtime has emitted a log.
+    Log {
+        /// Log level indicated by the runtime, if any. See [`LogEmitInfo::Log`].
+        log_level: Option<u32>,
+        /// Target indicated by the runtime, if any. See [`LogEmitInfo::Log`].
+        target: Option<String>,
+        /// Message of the log.
+        message: String,
+    },
+}
+
+impl TraceEvent {
+    /// Returns the storage key concerned by this event, if any.
+    ///
+    /// For [`TraceEvent::StorageClearPrefix`], returns the prefix.
+    pub fn storage_key(&self) -> Option<&[u8]> {
+        match self {
+            TraceEvent::StorageRead { key, .. }
+            | TraceEvent::StorageWrite { key, .. }
+            | TraceEvent::StorageAppend { key, .. }
+            | TraceEvent::StorageNextKey { key, .. } => Some(key),
+            TraceEvent::StorageClearPrefix { prefix, .. } => Some(prefix),
+            TraceEvent::HostFunctionCall { .. }
+            | TraceEvent::StorageRoot { .. }
+            | TraceEvent::Log { .. } => None,
+        }
+    }
 }
 
 /// Current state of the execution.
@@ -634,6 +749,11 @@ impl StorageGet {
 
         match (self.inner.vm, self.inner.root_calculation.take()) {
             (host::HostVm::ExternalStorageGet(req), None) => {
+                push_trace(&mut self.inner.trace, || TraceEvent::StorageRead {
+                    child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                    key: req.key().as_ref().to_vec(),
+                    value: value.as_ref().map(|(v, _)| v.clone()),
+                });
                 // TODO: should actually report the offset and max_size in the API
                 self.inner.vm = req.resume_full_value(value.as_ref().map(|(v, _)| &v[..]));
             }
@@ -791,6 +911,11 @@ impl NextKey {
 
                 match search {
                     storage_diff::StorageNextKey::Found(k) => {
+                        push_trace(&mut self.inner.trace, || TraceEvent::StorageNextKey {
+                            child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                            key: req.key().as_ref().to_vec(),
+                            next_key: k.map(|k| k.to_vec()),
+                        });
                         self.inner.vm = req.resume(k);
                     }
                     storage_diff::StorageNextKey::NextOf(next) => {
@@ -828,6 +953,11 @@ impl NextKey {
                             .entry(req.child_trie().map(|ct| ct.as_ref().to_vec()))
                             .or_insert(storage_diff::TrieDiff::empty());
 
+                        push_trace(&mut self.inner.trace, || TraceEvent::StorageWrite {
+                            child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                            key: key.clone(),
+                            value: None,
+                        });
                         trie.diff_insert_erase(key.clone(), ());
                         self.keys_removed_so_far += 1;
                         self.key_overwrite = Some(key); // TODO: might be expensive if lots of keys
@@ -1254,6 +1384,32 @@ impl LogEmit {
     pub fn resume(mut self) -> RuntimeHostVm {
         match self.inner.vm {
             host::HostVm::LogEmit(req) => {
+                push_trace(&mut self.inner.trace, || match req.info() {
+                    LogEmitInfo::Num(n) => TraceEvent::Log {
+                        log_level: None,
+                        target: None,
+                        message: n.to_string(),
+                    },
+                    LogEmitInfo::Utf8(s) => TraceEvent::Log {
+                        log_level: None,
+                        target: None,
+                        message: s.to_string(),
+                    },
+                    LogEmitInfo::Hex(s) => TraceEvent::Log {
+                        log_level: None,
+                        target: None,
+                        message: s.to_string(),
+                    },
+                    LogEmitInfo::Log {
+                        log_level,
+                        target,
+                        message,
+                    } => TraceEvent::Log {
+                        log_level: Some(log_level),
+                        target: Some(target.to_string()),
+                        message: message.to_string(),
+                    },
+                });
                 self.inner.vm = req.resume();
             }
             // We only create a `LogEmit` if the inner state is `LogEmit`.
@@ -1299,6 +1455,9 @@ struct Inner {
 
     /// See [`Config::calculate_trie_changes`].
     calculate_trie_changes: bool,
+
+    /// Events that have happened so far. `None` if [`Config::trace`] is `false`.
+    trace: Option<Vec<TraceEvent>>,
 }
 
 /// See [`Inner::pending_storage_changes`].
@@ -1454,6 +1613,10 @@ impl Inner {
                             _ => false,
                         };
                         if trie_match {
+                            push_trace(&mut self.trace, || TraceEvent::StorageRoot {
+                                child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                                hash: trie_root_hash,
+                            });
                             self.vm = req.resume(&trie_root_hash);
                         } else {
                             self.vm = host::HostVm::ExternalStorageRoot(req);
@@ -1568,12 +1731,24 @@ impl Inner {
             }
 
             match self.vm {
-                host::HostVm::ReadyToRun(r) => self.vm = r.run(),
+                host::HostVm::ReadyToRun(r) => match &mut self.trace {
+                    Some(trace) => {
+                        let mut host_functions_called = Vec::new();
+                        self.vm = r.run_traced(&mut host_functions_called);
+                        trace.extend(
+                            host_functions_called
+                                .into_iter()
+                                .map(|name| TraceEvent::HostFunctionCall { name }),
+                        );
+                    }
+                    None => self.vm = r.run(),
+                },
 
                 host::HostVm::Error { error, prototype } => {
                     return RuntimeHostVm::Finished(Err(Error {
                         detail: error,
                         prototype,
+                        trace: self.trace.unwrap_or_default(),
                     }));
                 }
 
@@ -1604,6 +1779,7 @@ impl Inner {
                         },
                         state_trie_version: self.state_trie_version,
                         logs: self.logs,
+                        trace: self.trace.unwrap_or_default(),
                     }));
                 }
 
@@ -1615,6 +1791,11 @@ impl Inner {
                         .and_then(|diff| diff.diff_get(req.key().as_ref()));
 
                     if let Some((value_in_diff, _)) = diff_search {
+                        push_trace(&mut self.trace, || TraceEvent::StorageRead {
+                            child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                            key: req.key().as_ref().to_vec(),
+                            value: value_in_diff.map(|v| v.to_vec()),
+                        });
                         self.vm = req.resume_full_value(value_in_diff);
                     } else {
                         self.vm = req.into();
@@ -1623,6 +1804,12 @@ impl Inner {
                 }
 
                 host::HostVm::ExternalStorageSet(req) => {
+                    push_trace(&mut self.trace, || TraceEvent::StorageWrite {
+                        child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                        key: req.key().as_ref().to_vec(),
+                        value: req.value().map(|v| v.as_ref().to_vec()),
+                    });
+
                     // Any attempt at writing a key that starts with `CHILD_STORAGE_SPECIAL_PREFIX`
                     // is silently ignored, as per spec.
                     if req.child_trie().is_none()
@@ -1653,6 +1840,12 @@ impl Inner {
                 }
 
                 host::HostVm::ExternalStorageAppend(req) => {
+                    push_trace(&mut self.trace, || TraceEvent::StorageAppend {
+                        child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                        key: req.key().as_ref().to_vec(),
+                        value: req.value().as_ref().to_vec(),
+                    });
+
                     // Any attempt at writing a key that starts with `CHILD_STORAGE_SPECIAL_PREFIX`
                     // is silently ignored, as per spec.
                     if req.child_trie().is_none()
@@ -1687,6 +1880,12 @@ impl Inner {
                 }
 
                 host::HostVm::ExternalStorageClearPrefix(req) => {
+                    push_trace(&mut self.trace, || TraceEvent::StorageClearPrefix {
+                        child_trie: req.child_trie().map(|ct| ct.as_ref().to_vec()),
+                        prefix: req.prefix().as_ref().to_vec(),
+                        max_keys_to_remove: req.max_keys_to_remove(),
+                    });
+
                     // Any attempt at clear a prefix that "intersects" (see code) with
                     // `CHILD_STORAGE_SPECIAL_PREFIX` is silently ignored, as per spec.
                     if req.child_trie().is_none()
@@ -1777,6 +1976,7 @@ impl Inner {
                         heap_pages: executor::DEFAULT_HEAP_PAGES,
                         exec_hint: vm::ExecHint::Oneshot,
                         allow_unresolved_imports: false, // TODO: what is a correct value here?
+                        fuel_budget: req.fuel_budget(),
                     }) {
                         Ok(w) => w,
                         Err(_) => {
@@ -1839,6 +2039,15 @@ impl Inner {
     }
 }
 
+/// Pushes the event returned by `event` on `trace`, if tracing is enabled.
+///
+/// The event is built lazily in order to avoid any overhead when tracing is disabled.
+fn push_trace(trace: &mut Option<Vec<TraceEvent>>, event: impl FnOnce() -> TraceEvent) {
+    if let Some(trace) = trace {
+        trace.push(event());
+    }
+}
+
 /// Performs the action described by [`host::HostVm::ExternalStorageAppend`] on an
 /// encoded storage value.
 fn append_to_storage_value(value: &mut Vec<u8>, to_add: &[u8]) {
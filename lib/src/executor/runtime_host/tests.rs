@@ -25,7 +25,7 @@
 
 use core::{iter, ops};
 
-use super::{run, Config, RuntimeHostVm};
+use super::{run, Config, RuntimeHostVm, TraceEvent};
 use crate::{executor::host, trie};
 use alloc::collections::BTreeMap;
 
@@ -76,6 +76,7 @@ fn execute_blocks() {
                 heap_pages,
                 exec_hint: crate::executor::vm::ExecHint::Oneshot,
                 allow_unresolved_imports: false,
+                fuel_budget: None,
             })
             .unwrap()
         };
@@ -89,99 +90,132 @@ fn execute_blocks() {
             .state_version
             .unwrap_or(host::TrieEntryVersion::V0);
 
-        // Start executing `Core_execute_block`. This runtime call will verify at the end whether
-        // the trie root hash of the block matches the one calculated by smoldot.
-        let mut execution = run(Config {
-            virtual_machine,
-            function_to_call: "Core_execute_block",
-            max_log_level: 3,
-            storage_main_trie_changes: Default::default(),
-            calculate_trie_changes: false,
-            parameter: {
-                // Block header + number of extrinsics + extrinsics
-                let encoded_body_len =
-                    crate::util::encode_scale_compact_usize(test_data.block.body.len());
-                iter::once(either::Right(either::Left(&test_data.block.header.0)))
-                    .chain(iter::once(either::Right(either::Right(encoded_body_len))))
-                    .chain(test_data.block.body.iter().map(|b| either::Left(&b.0)))
-            },
-        })
-        .unwrap();
-
-        loop {
-            match execution {
-                RuntimeHostVm::Finished(Ok(_)) => break, // Test successful!
-                RuntimeHostVm::Finished(Err(err)) => {
-                    panic!("Error during test #{}: {:?}", test_num, err)
-                }
-                RuntimeHostVm::SignatureVerification(sig) => execution = sig.verify_and_resume(),
-                RuntimeHostVm::ClosestDescendantMerkleValue(req) => {
-                    execution = req.resume_unknown()
-                }
-                RuntimeHostVm::StorageGet(get) => {
-                    let value = storage
-                        .get(&(
-                            get.child_trie().map(|c| c.as_ref().to_owned()),
-                            get.key().as_ref().to_owned(),
-                        ))
-                        .map(|v| (iter::once(&v[..]), state_version));
-                    execution = get.inject_value(value);
-                }
-                RuntimeHostVm::NextKey(req) => {
-                    // Because `NextKey` might ask for branch nodes, and that we don't build the
-                    // trie in its entirety, we have to use an algorithm that finds the branch
-                    // nodes for us.
-                    let next_key = {
-                        let mut search = trie::branch_search::BranchSearch::NextKey(
-                            trie::branch_search::start_branch_search(trie::branch_search::Config {
-                                key_before: req.key().collect::<Vec<_>>().into_iter(),
-                                or_equal: req.or_equal(),
-                                prefix: req.prefix().collect::<Vec<_>>().into_iter(),
-                                no_branch_search: !req.branch_nodes(),
-                            }),
-                        );
-
-                        loop {
-                            match search {
-                                trie::branch_search::BranchSearch::Found {
-                                    branch_trie_node_key,
-                                } => break branch_trie_node_key,
-                                trie::branch_search::BranchSearch::NextKey(bs_req) => {
-                                    let result = storage
-                                        .range((
-                                            if bs_req.or_equal() {
-                                                ops::Bound::Included((
-                                                    req.child_trie().map(|c| c.as_ref().to_owned()),
-                                                    bs_req.key_before().collect::<Vec<_>>(),
-                                                ))
-                                            } else {
-                                                ops::Bound::Excluded((
-                                                    req.child_trie().map(|c| c.as_ref().to_owned()),
-                                                    bs_req.key_before().collect::<Vec<_>>(),
-                                                ))
-                                            },
-                                            ops::Bound::Unbounded,
-                                        ))
-                                        .next()
-                                        .filter(|((trie, key), _)| {
-                                            *trie == req.child_trie().map(|c| c.as_ref().to_owned())
-                                                && key.starts_with(
-                                                    &bs_req.prefix().collect::<Vec<_>>(),
-                                                )
-                                        })
-                                        .map(|((_, k), _)| k);
-
-                                    search = bs_req.inject(result.map(|k| k.iter().copied()));
+        // Execute the block both with and without tracing enabled, as tracing must not have
+        // any influence on the outcome.
+        for trace in [false, true] {
+            // Start executing `Core_execute_block`. This runtime call will verify at the end
+            // whether the trie root hash of the block matches the one calculated by smoldot.
+            let mut execution = run(Config {
+                virtual_machine: virtual_machine.clone(),
+                function_to_call: "Core_execute_block",
+                max_log_level: 3,
+                storage_main_trie_changes: Default::default(),
+                calculate_trie_changes: false,
+                trace,
+                parameter: {
+                    // Block header + number of extrinsics + extrinsics
+                    let encoded_body_len =
+                        crate::util::encode_scale_compact_usize(test_data.block.body.len());
+                    iter::once(either::Right(either::Left(&test_data.block.header.0)))
+                        .chain(iter::once(either::Right(either::Right(encoded_body_len))))
+                        .chain(test_data.block.body.iter().map(|b| either::Left(&b.0)))
+                },
+            })
+            .unwrap();
+
+            loop {
+                match execution {
+                    RuntimeHostVm::Finished(Ok(success)) => {
+                        // Test successful! Also check that the trace is coherent.
+                        if trace {
+                            assert!(matches!(
+                                success.trace.first(),
+                                Some(TraceEvent::HostFunctionCall { .. })
+                            ));
+                            assert!(success
+                                .trace
+                                .iter()
+                                .any(|ev| matches!(ev, TraceEvent::StorageRead { .. })));
+                            assert!(success
+                                .trace
+                                .iter()
+                                .any(|ev| matches!(ev, TraceEvent::StorageWrite { .. })));
+                        } else {
+                            assert!(success.trace.is_empty());
+                        }
+                        break;
+                    }
+                    RuntimeHostVm::Finished(Err(err)) => {
+                        panic!("Error during test #{}: {:?}", test_num, err)
+                    }
+                    RuntimeHostVm::SignatureVerification(sig) => {
+                        execution = sig.verify_and_resume()
+                    }
+                    RuntimeHostVm::ClosestDescendantMerkleValue(req) => {
+                        execution = req.resume_unknown()
+                    }
+                    RuntimeHostVm::StorageGet(get) => {
+                        let value = storage
+                            .get(&(
+                                get.child_trie().map(|c| c.as_ref().to_owned()),
+                                get.key().as_ref().to_owned(),
+                            ))
+                            .map(|v| (iter::once(&v[..]), state_version));
+                        execution = get.inject_value(value);
+                    }
+                    RuntimeHostVm::NextKey(req) => {
+                        // Because `NextKey` might ask for branch nodes, and that we don't build the
+                        // trie in its entirety, we have to use an algorithm that finds the branch
+                        // nodes for us.
+                        let next_key = {
+                            let mut search = trie::branch_search::BranchSearch::NextKey(
+                                trie::branch_search::start_branch_search(
+                                    trie::branch_search::Config {
+                                        key_before: req.key().collect::<Vec<_>>().into_iter(),
+                                        or_equal: req.or_equal(),
+                                        prefix: req.prefix().collect::<Vec<_>>().into_iter(),
+                                        no_branch_search: !req.branch_nodes(),
+                                    },
+                                ),
+                            );
+
+                            loop {
+                                match search {
+                                    trie::branch_search::BranchSearch::Found {
+                                        branch_trie_node_key,
+                                    } => break branch_trie_node_key,
+                                    trie::branch_search::BranchSearch::NextKey(bs_req) => {
+                                        let result = storage
+                                            .range((
+                                                if bs_req.or_equal() {
+                                                    ops::Bound::Included((
+                                                        req.child_trie()
+                                                            .map(|c| c.as_ref().to_owned()),
+                                                        bs_req.key_before().collect::<Vec<_>>(),
+                                                    ))
+                                                } else {
+                                                    ops::Bound::Excluded((
+                                                        req.child_trie()
+                                                            .map(|c| c.as_ref().to_owned()),
+                                                        bs_req.key_before().collect::<Vec<_>>(),
+                                                    ))
+                                                },
+                                                ops::Bound::Unbounded,
+                                            ))
+                                            .next()
+                                            .filter(|((trie, key), _)| {
+                                                *trie
+                                                    == req
+                                                        .child_trie()
+                                                        .map(|c| c.as_ref().to_owned())
+                                                    && key.starts_with(
+                                                        &bs_req.prefix().collect::<Vec<_>>(),
+                                                    )
+                                            })
+                                            .map(|((_, k), _)| k);
+
+                                        search = bs_req.inject(result.map(|k| k.iter().copied()));
+                                    }
                                 }
                             }
-                        }
-                    };
+                        };
 
-                    execution = req.inject_key(next_key.map(|nk| nk.into_iter()));
-                }
-                RuntimeHostVm::LogEmit(log) => execution = log.resume(),
-                RuntimeHostVm::OffchainStorageSet(_) | RuntimeHostVm::Offchain(_) => {
-                    unimplemented!()
+                        execution = req.inject_key(next_key.map(|nk| nk.into_iter()));
+                    }
+                    RuntimeHostVm::LogEmit(log) => execution = log.resume(),
+                    RuntimeHostVm::OffchainStorageSet(_) | RuntimeHostVm::Offchain(_) => {
+                        unimplemented!()
+                    }
                 }
             }
         }
@@ -39,6 +39,17 @@
 //! is returned and the virtual machine is now paused. Once the logic of the host function has
 //! been executed, call `run` again, passing the return value of that host function.
 //!
+//! # Fuel metering
+//!
+//! Nothing in the WebAssembly format guarantees that a function eventually returns. When
+//! executing code that can't be trusted, it is possible to enable fuel metering through
+//! [`Config::fuel_metering`]. Each WebAssembly instruction that is executed then consumes a
+//! certain amount of fuel, and the execution stops with a [`RunErr::OutOfFuel`] once all the
+//! fuel that was provided through [`Prepare::add_fuel`] has been consumed.
+//!
+//! The amount of fuel consumed by each instruction is an implementation detail, but is the same
+//! no matter how many times the same code is executed.
+//!
 //! # About imported vs exported memory
 //!
 //! WebAssembly supports, in theory, addressing multiple different memory objects. The WebAssembly
@@ -59,8 +70,15 @@
 //! in 2017 is commonly referred to as "the MVP" (minimum viable product). Since then, various
 //! extensions have been added to the WebAssembly format.
 //!
-//! The code in this module, however, doesn't allow any of the feature that were added post-MVP.
-//! Trying to use WebAssembly code that uses one of these features will result in an error.
+//! By default, the code in this module doesn't allow any of the feature that were added
+//! post-MVP. Trying to use WebAssembly code that uses one of these features will result in an
+//! error.
+//!
+//! Some of these features can be opted-in to through [`Config::features`]. See [`WasmFeatures`].
+//! All the execution engines behave identically with regards to these features, with the
+//! exception that the sign-extension operators, mutable globals, and saturating float-to-int
+//! conversions are always accepted by the `wasmtime`-based engine, as it is not possible to
+//! disable them.
 //!
 
 mod interpreter;
@@ -93,12 +111,71 @@ pub struct Config<'a> {
     /// Hint about how to execute the WebAssembly code.
     pub exec_hint: ExecHint,
 
+    /// Post-MVP WebAssembly features that the module is allowed to use.
+    pub features: WasmFeatures,
+
+    /// If `true`, the execution consumes "fuel", and stops with a [`RunErr::OutOfFuel`] once
+    /// there isn't any fuel left. See [`Prepare::add_fuel`].
+    ///
+    /// Enabling fuel metering slows down the execution.
+    pub fuel_metering: bool,
+
     /// Called for each import that the module has. It must assign a number to each import, or
     /// return an error if the import can't be resolved. When the VM calls one of these functions,
     /// this number will be returned back in order for the user to know how to handle the call.
     pub symbols: &'a mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
 }
 
+/// Set of post-MVP WebAssembly features that can be enabled.
+///
+/// The [`Default`] implementation of this struct disables all features, in other words only
+/// allows the WebAssembly MVP.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct WasmFeatures {
+    /// Allows the sign-extension operators, such as `i32.extend8_s`.
+    ///
+    /// See <https://github.com/WebAssembly/sign-extension-ops>.
+    pub sign_extension: bool,
+
+    /// Allows the bulk memory operations, such as `memory.copy` or `memory.fill`.
+    ///
+    /// See <https://github.com/WebAssembly/bulk-memory-operations>.
+    pub bulk_memory: bool,
+
+    /// Allows functions and blocks to return multiple values.
+    ///
+    /// > **Note**: Functions that return multiple values can't be called with
+    /// >           [`Prepare::start`], and imported functions can't return multiple values.
+    ///
+    /// See <https://github.com/WebAssembly/multi-value>.
+    pub multi_value: bool,
+
+    /// Allows the `externref` type and the table manipulation instructions.
+    ///
+    /// Because the reference types proposal depends on the bulk memory operations proposal,
+    /// enabling this feature also enables [`WasmFeatures::bulk_memory`].
+    ///
+    /// See <https://github.com/WebAssembly/reference-types>.
+    pub reference_types: bool,
+}
+
+impl WasmFeatures {
+    /// Returns a [`WasmFeatures`] with all the features disabled.
+    pub fn mvp() -> Self {
+        WasmFeatures::default()
+    }
+
+    /// Returns a [`WasmFeatures`] with all the features enabled.
+    pub fn all() -> Self {
+        WasmFeatures {
+            sign_extension: true,
+            bulk_memory: true,
+            multi_value: true,
+            reference_types: true,
+        }
+    }
+}
+
 /// Virtual machine ready to start executing a function.
 ///
 /// > **Note**: This struct implements `Clone`. Cloning a [`VirtualMachinePrototype`] allocates
@@ -145,9 +222,14 @@ impl VirtualMachinePrototype {
                     ),
                     feature = "wasmtime"
                 ))]
-                ExecHint::CompileAheadOfTime => VirtualMachinePrototypeInner::Jit(
-                    jit::JitPrototype::new(config.module_bytes, config.symbols)?,
-                ),
+                ExecHint::CompileAheadOfTime => {
+                    VirtualMachinePrototypeInner::Jit(jit::JitPrototype::new(
+                        config.module_bytes,
+                        config.features,
+                        config.fuel_metering,
+                        config.symbols,
+                    )?)
+                }
                 #[cfg(not(all(
                     any(
                         all(
@@ -160,12 +242,19 @@ impl VirtualMachinePrototype {
                     feature = "wasmtime"
                 )))]
                 ExecHint::CompileAheadOfTime => VirtualMachinePrototypeInner::Interpreter(
-                    interpreter::InterpreterPrototype::new(config.module_bytes, config.symbols)?,
+                    interpreter::InterpreterPrototype::new(
+                        config.module_bytes,
+                        config.features,
+                        config.fuel_metering,
+                        config.symbols,
+                    )?,
                 ),
                 ExecHint::Oneshot | ExecHint::Untrusted | ExecHint::ForceWasmi => {
                     VirtualMachinePrototypeInner::Interpreter(
                         interpreter::InterpreterPrototype::new(
                             config.module_bytes,
+                            config.features,
+                            config.fuel_metering,
                             config.symbols,
                         )?,
                     )
@@ -182,13 +271,102 @@ impl VirtualMachinePrototype {
                     ),
                     feature = "wasmtime"
                 ))]
-                ExecHint::ForceWasmtime => VirtualMachinePrototypeInner::Jit(
-                    jit::JitPrototype::new(config.module_bytes, config.symbols)?,
-                ),
+                ExecHint::ForceWasmtime => {
+                    VirtualMachinePrototypeInner::Jit(jit::JitPrototype::new(
+                        config.module_bytes,
+                        config.features,
+                        config.fuel_metering,
+                        config.symbols,
+                    )?)
+                }
             },
         })
     }
 
+    /// Similar to [`VirtualMachinePrototype::new`], but loads the module from an artifact that
+    /// was previously returned by [`VirtualMachinePrototype::serialize_compiled`], which is
+    /// considerably faster than compiling it again.
+    ///
+    /// If the execution engine selected by [`Config::exec_hint`] doesn't support compiled
+    /// artifacts, `compiled` is ignored and [`Config::module_bytes`] is used instead, exactly as
+    /// if [`VirtualMachinePrototype::new`] had been called. Otherwise, [`Config::module_bytes`] is
+    /// ignored.
+    ///
+    /// An error is returned if the artifact was produced with a different version of the engine,
+    /// or with a different [`Config::features`] or [`Config::fuel_metering`]. The API user is
+    /// encouraged to fall back to [`VirtualMachinePrototype::new`] in that situation.
+    ///
+    /// # Safety
+    ///
+    /// Compiled artifacts contain native machine code. The `compiled` parameter must have been
+    /// returned by [`VirtualMachinePrototype::serialize_compiled`] and not modified since, as
+    /// loading any other data can result in arbitrary code execution.
+    ///
+    pub unsafe fn from_compiled(config: Config, compiled: &[u8]) -> Result<Self, NewErr> {
+        #[cfg(all(
+            any(
+                all(
+                    target_arch = "x86_64",
+                    any(target_os = "windows", target_os = "linux", target_os = "macos")
+                ),
+                all(target_arch = "aarch64", target_os = "linux"),
+                all(target_arch = "s390x", target_os = "linux")
+            ),
+            feature = "wasmtime"
+        ))]
+        if matches!(
+            config.exec_hint,
+            ExecHint::CompileAheadOfTime | ExecHint::ForceWasmtime
+        ) {
+            return Ok(VirtualMachinePrototype {
+                inner: VirtualMachinePrototypeInner::Jit(jit::JitPrototype::from_compiled(
+                    compiled,
+                    config.features,
+                    config.fuel_metering,
+                    config.symbols,
+                )?),
+            });
+        }
+
+        #[cfg(not(all(
+            any(
+                all(
+                    target_arch = "x86_64",
+                    any(target_os = "windows", target_os = "linux", target_os = "macos")
+                ),
+                all(target_arch = "aarch64", target_os = "linux"),
+                all(target_arch = "s390x", target_os = "linux")
+            ),
+            feature = "wasmtime"
+        )))]
+        let _ = compiled;
+
+        Self::new(config)
+    }
+
+    /// Returns an artifact containing the compiled version of the module, that can later be
+    /// passed to [`VirtualMachinePrototype::from_compiled`].
+    ///
+    /// Returns `None` if the execution engine being used doesn't compile the module, in which
+    /// case there is nothing to save.
+    pub fn serialize_compiled(&self) -> Option<Vec<u8>> {
+        match &self.inner {
+            #[cfg(all(
+                any(
+                    all(
+                        target_arch = "x86_64",
+                        any(target_os = "windows", target_os = "linux", target_os = "macos")
+                    ),
+                    all(target_arch = "aarch64", target_os = "linux"),
+                    all(target_arch = "s390x", target_os = "linux")
+                ),
+                feature = "wasmtime"
+            ))]
+            VirtualMachinePrototypeInner::Jit(inner) => inner.serialize_compiled(),
+            VirtualMachinePrototypeInner::Interpreter(_) => None,
+        }
+    }
+
     /// Returns the value of a global that the module exports.
     ///
     /// The global variable must be a `i32`, otherwise an error is returned. Negative values are
@@ -435,6 +613,30 @@ impl Prepare {
         }
     }
 
+    /// Adds the given amount of fuel to the amount available to the execution. The amount of
+    /// fuel available is initially 0.
+    ///
+    /// Has no effect if [`Config::fuel_metering`] was `false`.
+    ///
+    /// See [the module-level documentation](..).
+    pub fn add_fuel(&mut self, fuel: u64) {
+        match &mut self.inner {
+            #[cfg(all(
+                any(
+                    all(
+                        target_arch = "x86_64",
+                        any(target_os = "windows", target_os = "linux", target_os = "macos")
+                    ),
+                    all(target_arch = "aarch64", target_os = "linux"),
+                    all(target_arch = "s390x", target_os = "linux")
+                ),
+                feature = "wasmtime"
+            ))]
+            PrepareInner::Jit(inner) => inner.add_fuel(fuel),
+            PrepareInner::Interpreter(inner) => inner.add_fuel(fuel),
+        }
+    }
+
     /// Turns this prototype into an actual virtual machine. This requires choosing which function
     /// to execute.
     pub fn start(
@@ -548,6 +750,29 @@ impl VirtualMachine {
         }
     }
 
+    /// Returns the total amount of fuel that the execution has consumed so far, or `None` if
+    /// [`Config::fuel_metering`] was `false`.
+    ///
+    /// While the execution is paused due to a call to a host function, the value returned is the
+    /// amount of fuel consumed by the time the host function was called.
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        match &self.inner {
+            #[cfg(all(
+                any(
+                    all(
+                        target_arch = "x86_64",
+                        any(target_os = "windows", target_os = "linux", target_os = "macos")
+                    ),
+                    all(target_arch = "aarch64", target_os = "linux"),
+                    all(target_arch = "s390x", target_os = "linux")
+                ),
+                feature = "wasmtime"
+            ))]
+            VirtualMachineInner::Jit(inner) => inner.fuel_consumed(),
+            VirtualMachineInner::Interpreter(inner) => inner.fuel_consumed(),
+        }
+    }
+
     /// Returns the size of the memory, in bytes.
     ///
     /// > **Note**: This can change over time if the Wasm code uses the `grow` opcode.
@@ -1202,6 +1427,12 @@ pub enum NewErr {
     CouldntAllocateMemory,
     /// The Wasm module requires importing a global or a table, which isn't supported.
     ImportTypeNotSupported,
+    /// Error while loading a compiled artifact passed to
+    /// [`VirtualMachinePrototype::from_compiled`].
+    ///
+    /// Contains an opaque error message.
+    #[display(fmt = "{_0}")]
+    InvalidCompiled(String),
 }
 
 // TODO: an implementation of the `Error` trait is required in order to interact with wasmtime, but it's not possible to implement this trait on non-std yet
@@ -1245,6 +1476,10 @@ pub enum RunErr {
         /// Type of the value that was actually passed.
         obtained: Option<ValueType>,
     },
+    /// All the fuel that was provided through [`Prepare::add_fuel`] has been consumed. The state
+    /// machine is now poisoned.
+    #[display(fmt = "Execution has run out of fuel")]
+    OutOfFuel,
 }
 
 /// Error that can happen when calling [`VirtualMachinePrototype::global_value`].
@@ -19,7 +19,7 @@
 
 use super::{
     ExecOutcome, GlobalValueErr, HeapPages, NewErr, OutOfBoundsError, RunErr, Signature, StartErr,
-    Trap, ValueType, WasmValue,
+    Trap, ValueType, WasmFeatures, WasmValue,
 };
 
 use alloc::{borrow::ToOwned as _, string::ToString as _, sync::Arc, vec::Vec};
@@ -52,21 +52,26 @@ impl InterpreterPrototype {
     /// See [`super::VirtualMachinePrototype::new`].
     pub fn new(
         module_bytes: &[u8],
+        features: WasmFeatures,
+        fuel_metering: bool,
         symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
     ) -> Result<Self, NewErr> {
         let engine = {
             let mut config = wasmi::Config::default();
 
-            // Disable all the post-MVP wasm features.
-            config.wasm_sign_extension(false);
-            config.wasm_reference_types(false);
-            config.wasm_bulk_memory(false);
-            config.wasm_multi_value(false);
+            // Enable the post-MVP wasm features that have been requested, and disable all the
+            // other ones.
+            config.wasm_sign_extension(features.sign_extension);
+            config.wasm_reference_types(features.reference_types);
+            config.wasm_bulk_memory(features.bulk_memory || features.reference_types);
+            config.wasm_multi_value(features.multi_value);
             config.wasm_extended_const(false);
             config.wasm_mutable_global(false);
             config.wasm_saturating_float_to_int(false);
             config.wasm_tail_call(false);
 
+            config.consume_fuel(fuel_metering);
+
             wasmi::Engine::new(&config)
         };
 
@@ -338,6 +343,13 @@ impl Prepare {
         Ok(())
     }
 
+    /// See [`super::Prepare::add_fuel`].
+    pub fn add_fuel(&mut self, fuel: u64) {
+        // An error is returned if fuel metering is disabled, in which case we silently ignore
+        // the call.
+        let _ = self.inner.store.add_fuel(fuel);
+    }
+
     /// See [`super::Prepare::start`].
     pub fn start(
         self,
@@ -526,12 +538,24 @@ impl Interpreter {
                 self.execution = Some(Execution::Started(next));
                 Ok(outcome)
             }
+            Err(wasmi::Error::Trap(trap))
+                if matches!(trap.trap_code(), Some(wasmi::core::TrapCode::OutOfFuel)) =>
+            {
+                // `self.execution` has been extracted above and is left to `None`, meaning that
+                // the virtual machine is now poisoned.
+                Err(RunErr::OutOfFuel)
+            }
             Err(err) => Ok(ExecOutcome::Finished {
                 return_value: Err(Trap(err.to_string())),
             }),
         }
     }
 
+    /// See [`super::VirtualMachine::fuel_consumed`].
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        self.store.fuel_consumed()
+    }
+
     /// See [`super::VirtualMachine::memory_size`].
     pub fn memory_size(&self) -> HeapPages {
         HeapPages(u32::from(self.memory.current_pages(&self.store)))
@@ -19,7 +19,7 @@
 
 use super::{
     ExecOutcome, GlobalValueErr, HeapPages, NewErr, OutOfBoundsError, RunErr, Signature, StartErr,
-    Trap, ValueType, WasmValue,
+    Trap, ValueType, WasmFeatures, WasmValue,
 };
 
 use alloc::{boxed::Box, sync::Arc, vec::Vec};
@@ -59,8 +59,56 @@ impl JitPrototype {
     /// See [`super::VirtualMachinePrototype::new`].
     pub fn new(
         module_bytes: &[u8],
+        features: WasmFeatures,
+        fuel_metering: bool,
         symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
     ) -> Result<Self, NewErr> {
+        let engine = Self::engine(features, fuel_metering)?;
+
+        let module = wasmtime::Module::from_binary(&engine, module_bytes)
+            .map_err(|err| NewErr::InvalidWasm(err.to_string()))?;
+
+        let resolved_imports = Self::resolve_imports(&module, symbols)?;
+
+        Self::from_base_components(BaseComponents {
+            module,
+            resolved_imports,
+        })
+    }
+
+    /// See [`super::VirtualMachinePrototype::from_compiled`].
+    ///
+    /// # Safety
+    ///
+    /// See [`super::VirtualMachinePrototype::from_compiled`].
+    pub unsafe fn from_compiled(
+        compiled: &[u8],
+        features: WasmFeatures,
+        fuel_metering: bool,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<Self, NewErr> {
+        // Because the engine is configured identically as in `new`, `wasmtime` accepts
+        // artifacts that were compiled with the same features and the same version of `wasmtime`,
+        // and returns an error otherwise.
+        let engine = Self::engine(features, fuel_metering)?;
+
+        let module = wasmtime::Module::deserialize(&engine, compiled)
+            .map_err(|err| NewErr::InvalidCompiled(err.to_string()))?;
+
+        let resolved_imports = Self::resolve_imports(&module, symbols)?;
+
+        Self::from_base_components(BaseComponents {
+            module,
+            resolved_imports,
+        })
+    }
+
+    /// See [`super::VirtualMachinePrototype::serialize_compiled`].
+    pub fn serialize_compiled(&self) -> Option<Vec<u8>> {
+        self.base_components.module.serialize().ok()
+    }
+
+    fn engine(features: WasmFeatures, fuel_metering: bool) -> Result<wasmtime::Engine, NewErr> {
         let mut config = wasmtime::Config::new();
         config.cranelift_nan_canonicalization(true);
         config.cranelift_opt_level(wasmtime::OptLevel::Speed);
@@ -72,64 +120,63 @@ impl JitPrototype {
         // very important, so long as it is not `Environment`.
         config.wasm_backtrace_details(wasmtime::WasmBacktraceDetails::Enable);
 
-        // Disable all post-MVP wasm features.
+        // Enable the post-MVP wasm features that have been requested, and disable all the other
+        // ones.
         // Some of these configuration options are `true` by default while some others are `false`
-        // by default, but we just disable them all to be sure.
+        // by default, but we just set them all to be sure.
+        // Note that wasmtime doesn't allow disabling the sign-extension operators.
         config.wasm_threads(false);
-        config.wasm_reference_types(false);
+        config.wasm_reference_types(features.reference_types);
         config.wasm_simd(false);
-        config.wasm_bulk_memory(false);
-        config.wasm_multi_value(false);
+        config.wasm_bulk_memory(features.bulk_memory || features.reference_types);
+        config.wasm_multi_value(features.multi_value);
         config.wasm_multi_memory(false);
         config.wasm_memory64(false);
 
-        let engine =
-            wasmtime::Engine::new(&config).map_err(|err| NewErr::InvalidWasm(err.to_string()))?;
-
-        let module = wasmtime::Module::from_binary(&engine, module_bytes)
-            .map_err(|err| NewErr::InvalidWasm(err.to_string()))?;
+        // When fuel is exhausted, `wasmtime` by default traps, which is what we want.
+        config.consume_fuel(fuel_metering);
 
-        // Building the list of imports that the Wasm VM is able to use.
-        let resolved_imports = {
-            let mut imports = Vec::with_capacity(module.imports().len());
-            for import in module.imports() {
-                match import.ty() {
-                    wasmtime::ExternType::Func(func_type) => {
-                        // Note that if `Signature::try_from` fails, a `UnresolvedFunctionImport` is
-                        // also returned. This is because it is not possible for the function to
-                        // resolve anyway if its signature can't be represented.
-                        let function_index =
-                            match Signature::try_from(&func_type)
-                                .ok()
-                                .and_then(|conv_signature| {
-                                    symbols(import.module(), import.name(), &conv_signature).ok()
-                                }) {
-                                Some(i) => i,
-                                None => {
-                                    return Err(NewErr::UnresolvedFunctionImport {
-                                        module_name: import.module().to_owned(),
-                                        function: import.name().to_owned(),
-                                    })
-                                }
-                            };
+        wasmtime::Engine::new(&config).map_err(|err| NewErr::InvalidWasm(err.to_string()))
+    }
 
-                        imports.push(Some(function_index));
-                    }
-                    wasmtime::ExternType::Global(_) | wasmtime::ExternType::Table(_) => {
-                        return Err(NewErr::ImportTypeNotSupported);
-                    }
-                    wasmtime::ExternType::Memory(_) => {
-                        imports.push(None);
-                    }
-                };
-            }
-            imports
-        };
+    /// Builds the list of imports that the Wasm VM is able to use.
+    fn resolve_imports(
+        module: &wasmtime::Module,
+        symbols: &mut dyn FnMut(&str, &str, &Signature) -> Result<usize, ()>,
+    ) -> Result<Vec<Option<usize>>, NewErr> {
+        let mut imports = Vec::with_capacity(module.imports().len());
+        for import in module.imports() {
+            match import.ty() {
+                wasmtime::ExternType::Func(func_type) => {
+                    // Note that if `Signature::try_from` fails, a `UnresolvedFunctionImport` is
+                    // also returned. This is because it is not possible for the function to
+                    // resolve anyway if its signature can't be represented.
+                    let function_index =
+                        match Signature::try_from(&func_type)
+                            .ok()
+                            .and_then(|conv_signature| {
+                                symbols(import.module(), import.name(), &conv_signature).ok()
+                            }) {
+                            Some(i) => i,
+                            None => {
+                                return Err(NewErr::UnresolvedFunctionImport {
+                                    module_name: import.module().to_owned(),
+                                    function: import.name().to_owned(),
+                                })
+                            }
+                        };
 
-        Self::from_base_components(BaseComponents {
-            module,
-            resolved_imports,
-        })
+                    imports.push(Some(function_index));
+                }
+                wasmtime::ExternType::Global(_) | wasmtime::ExternType::Table(_) => {
+                    return Err(NewErr::ImportTypeNotSupported);
+                }
+                wasmtime::ExternType::Memory(_) => {
+                    imports.push(None);
+                }
+            };
+        }
+        Ok(imports)
     }
 
     fn from_base_components(base_components: BaseComponents) -> Result<Self, NewErr> {
@@ -186,6 +233,7 @@ impl JitPrototype {
                                                     .unwrap(),
                                                 expected_return_ty,
                                                 in_interrupted_waker: None, // Filled below
+                                                fuel_consumed: caller.fuel_consumed(),
                                                 memory: SliceRawParts(
                                                     memory.data_ptr(&caller),
                                                     memory.data_size(&caller),
@@ -448,6 +496,13 @@ impl Prepare {
         Ok(())
     }
 
+    /// See [`super::Prepare::add_fuel`].
+    pub fn add_fuel(&mut self, fuel: u64) {
+        // An error is returned if fuel metering is disabled, in which case we silently ignore
+        // the call.
+        let _ = self.inner.store.add_fuel(fuel);
+    }
+
     /// See [`super::Prepare::start`].
     pub fn start(
         mut self,
@@ -496,6 +551,7 @@ impl Prepare {
             shared: self.inner.shared,
             memory: self.inner.memory,
             memory_type: self.inner.memory_type,
+            interrupted_fuel_consumed: None,
         })
     }
 }
@@ -545,6 +601,8 @@ enum Shared {
         expected_return_ty: Option<ValueType>,
         /// See [`Shared::WithinFunctionCall::in_interrupted_waker`].
         in_interrupted_waker: Option<task::Waker>,
+        /// Value of `fuel_consumed` of the store when the function was called.
+        fuel_consumed: Option<u64>,
     },
     WithinFunctionCall {
         /// Pointer and size of the location where the virtual machine memory is located in the
@@ -597,6 +655,11 @@ pub struct Jit {
 
     /// See [`JitPrototype::memory_type`].
     memory_type: wasmtime::MemoryType,
+
+    /// While the execution is interrupted by a call to a host function, contains the amount of
+    /// fuel consumed when this host function was called. Necessary because the `store` can't be
+    /// accessed during a call.
+    interrupted_fuel_consumed: Option<u64>,
 }
 
 enum JitInner {
@@ -740,6 +803,12 @@ impl Jit {
                     return_value: Ok(val),
                 })
             }
+            task::Poll::Ready((store, Err(err)))
+                if err.downcast_ref::<wasmtime::Trap>() == Some(&wasmtime::Trap::OutOfFuel) =>
+            {
+                self.inner = JitInner::Done(store);
+                Err(RunErr::OutOfFuel)
+            }
             task::Poll::Ready((store, Err(err))) => {
                 self.inner = JitInner::Done(store);
                 Ok(ExecOutcome::Finished {
@@ -755,12 +824,14 @@ impl Jit {
                         memory,
                         expected_return_ty,
                         in_interrupted_waker,
+                        fuel_consumed,
                     } => {
                         *shared_lock = Shared::WithinFunctionCall {
                             memory,
                             expected_return_ty,
                             in_interrupted_waker,
                         };
+                        self.interrupted_fuel_consumed = fuel_consumed;
 
                         Ok(ExecOutcome::Interrupted {
                             id: function_index,
@@ -938,6 +1009,15 @@ impl Jit {
         Ok(())
     }
 
+    /// See [`super::VirtualMachine::fuel_consumed`].
+    pub fn fuel_consumed(&self) -> Option<u64> {
+        match &self.inner {
+            JitInner::NotStarted { store, .. } | JitInner::Done(store) => store.fuel_consumed(),
+            JitInner::Executing(_) => self.interrupted_fuel_consumed,
+            JitInner::Poisoned => unreachable!(),
+        }
+    }
+
     /// See [`super::VirtualMachine::into_prototype`].
     pub fn into_prototype(self) -> JitPrototype {
         // Since the creation has succeeded before, there's no reason why it would fail now.
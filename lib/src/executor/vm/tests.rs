@@ -34,6 +34,8 @@ fn basic_seems_to_work() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &include_bytes!("./test-polkadot-runtime-v9160.wasm")[..],
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -72,6 +74,8 @@ fn wat_not_accepted() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: b"(module)",
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::InvalidWasm(_))
@@ -90,6 +94,8 @@ fn out_of_memory_access() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes[..],
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0)
         })
         .is_err());
@@ -107,6 +113,8 @@ fn has_start_function() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes[..],
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0)
         })
         .is_err());
@@ -125,6 +133,8 @@ fn unsupported_type() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes[..],
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0)
         })
         .is_err());
@@ -152,6 +162,8 @@ fn basic_host_function_return_value_works() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -200,6 +212,8 @@ fn no_memory() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::NoMemory)
@@ -227,6 +241,8 @@ fn memory_misnamed() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::MemoryNotNamedMemory)
@@ -250,6 +266,8 @@ fn memory_isnt_memory() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::MemoryIsntMemory)
@@ -281,6 +299,8 @@ fn two_memories() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::InvalidWasm(_) | super::NewErr::TwoMemories)
@@ -307,6 +327,8 @@ fn exported_memory_works() {
         super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -333,6 +355,8 @@ fn unresolved_function() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Err(())
             }),
             Err(super::NewErr::UnresolvedFunctionImport { .. })
@@ -358,6 +382,8 @@ fn unsupported_signature() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::UnresolvedFunctionImport { .. })
@@ -383,6 +409,8 @@ fn unsupported_import_type() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::ImportTypeNotSupported)
@@ -409,6 +437,8 @@ fn start_function_forbidden() {
             super::VirtualMachinePrototype::new(super::Config {
                 module_bytes: &module_bytes,
                 exec_hint,
+                features: super::WasmFeatures::mvp(),
+                fuel_metering: false,
                 symbols: &mut |_, _, _| Ok(0)
             }),
             Err(super::NewErr::StartFunctionNotSupported) | Ok(_)
@@ -432,6 +462,8 @@ fn max_memory_pages() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -458,6 +490,8 @@ fn get_global() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -480,6 +514,8 @@ fn global_doesntexist() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -506,6 +542,8 @@ fn global_wrong_type() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -532,6 +570,8 @@ fn global_isnt_global() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -558,6 +598,8 @@ fn global_negative_value_works() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -581,6 +623,8 @@ fn call_non_existing_function() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -607,6 +651,8 @@ fn call_signature_not_supported() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -633,6 +679,8 @@ fn bad_params_types() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -659,6 +707,8 @@ fn try_to_call_global() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -688,6 +738,8 @@ fn wrong_type_provided_initially() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -720,6 +772,8 @@ fn wrong_type_returned_by_host_function_call() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -752,6 +806,8 @@ fn memory_min_specified_in_wasm() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -776,6 +832,8 @@ fn memory_grow_works() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -802,6 +860,8 @@ fn memory_grow_detects_limit() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -830,6 +890,8 @@ fn memory_grow_detects_limit_within_host_function() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -882,6 +944,8 @@ fn globals_reinitialized_after_reset() {
         let mut prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -916,6 +980,8 @@ fn memory_zeroed_after_reset() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -964,6 +1030,8 @@ fn memory_zeroed_after_prepare() {
         let prototype = super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .unwrap();
@@ -1002,6 +1070,8 @@ fn feature_disabled_signext() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1033,6 +1103,8 @@ fn feature_disabled_saturated_float_to_int() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1058,6 +1130,8 @@ fn feature_disabled_threads() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1080,6 +1154,8 @@ fn feature_disabled_reference_type() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1105,6 +1181,8 @@ fn feature_disabled_bulk_memory() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1129,6 +1207,8 @@ fn feature_disabled_multi_value() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1150,6 +1230,8 @@ fn feature_disabled_memory64() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1178,6 +1260,8 @@ fn feature_disabled_mutable_globals() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1213,6 +1297,8 @@ fn feature_disabled_tail_call() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
@@ -1272,12 +1358,445 @@ fn feature_disabled_simd128() {
         assert!(super::VirtualMachinePrototype::new(super::Config {
             module_bytes: &module_bytes,
             exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
             symbols: &mut |_, _, _| Ok(0),
         })
         .is_err());
     }
 }
 
+#[test]
+fn feature_disabled_sign_extension() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello") (result i32)
+            (i32.extend8_s (i32.const 255))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        // TODO: wasmtime doesn't allow disabling this feature /!\ test is faulty /!\ figure out what to do
+        // TODO: see https://github.com/paritytech/substrate/issues/10707#issuecomment-1494081313
+        if Some(exec_hint) == super::ExecHint::force_wasmtime_if_available() {
+            continue;
+        }
+
+        assert!(super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .is_err());
+    }
+}
+
+#[test]
+fn feature_enabled_sign_extension() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello") (result i32)
+            (i32.extend8_s (i32.const 255))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures {
+                sign_extension: true,
+                ..super::WasmFeatures::mvp()
+            },
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut vm = prototype.prepare().start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(-1))),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn feature_enabled_bulk_memory() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 1 4096))
+        (func (export "hello") (result i32)
+            (memory.fill (i32.const 4) (i32.const 7) (i32.const 4))
+            (memory.copy (i32.const 0) (i32.const 4) (i32.const 2))
+            (i32.load (i32.const 0))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures {
+                bulk_memory: true,
+                ..super::WasmFeatures::mvp()
+            },
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut vm = prototype.prepare().start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(0x0707))),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn feature_enabled_multi_value() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func $pair (result i64 i64)
+            (i64.const 5) (i64.const 3)
+        )
+        (func (export "hello") (result i64)
+            (call $pair)
+            i64.sub
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures {
+                multi_value: true,
+                ..super::WasmFeatures::mvp()
+            },
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut vm = prototype.prepare().start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I64(2))),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn feature_enabled_reference_types() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello") (result i32)
+            (ref.is_null (ref.null extern))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        // Reference types must be rejected if the feature isn't enabled, even if bulk memory
+        // operations are enabled.
+        assert!(super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures {
+                bulk_memory: true,
+                ..super::WasmFeatures::mvp()
+            },
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .is_err());
+
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures {
+                reference_types: true,
+                ..super::WasmFeatures::mvp()
+            },
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut vm = prototype.prepare().start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(1))),
+            _ => panic!(),
+        }
+    }
+}
+
+#[test]
+fn feature_enabled_all_still_rejects_unsupported() {
+    // Enabling all the features must not enable the features that aren't covered
+    // by `WasmFeatures`.
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello") (result i64)
+            (atomic.fence)
+            i64.const 2
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        assert!(super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::all(),
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .is_err());
+    }
+}
+
+#[test]
+fn fuel_metering_infinite_loop() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello")
+            (loop $l (br $l))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: true,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut prepare = prototype.prepare();
+        prepare.add_fuel(100_000);
+        let mut vm = prepare.start("hello", &[]).unwrap();
+        assert!(matches!(vm.run(None), Err(super::RunErr::OutOfFuel)));
+        assert!(matches!(vm.run(None), Err(super::RunErr::Poisoned)));
+    }
+}
+
+#[test]
+fn fuel_metering_enough_fuel() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (import "env" "host" (func $host))
+        (func (export "hello") (result i32)
+            (local $i i32)
+            (loop $l
+                (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                (br_if $l (i32.lt_u (local.get $i) (i32.const 1000)))
+            )
+            (call $host)
+            (local.get $i)
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: true,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        let mut prepare = prototype.prepare();
+        prepare.add_fuel(1_000_000);
+        let mut vm = prepare.start("hello", &[]).unwrap();
+        assert_eq!(vm.fuel_consumed(), Some(0));
+
+        // The amount of fuel consumed by the loop is known while the host function is being
+        // called.
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Interrupted { id: 0, .. }) => {}
+            _ => panic!(),
+        }
+        let fuel_in_host_function = vm.fuel_consumed().unwrap();
+        assert!(fuel_in_host_function >= 1000);
+
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(1000))),
+            _ => panic!(),
+        }
+        assert!(vm.fuel_consumed().unwrap() >= fuel_in_host_function);
+    }
+}
+
+#[test]
+fn fuel_metering_disabled() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (func (export "hello") (result i32)
+            (i32.const 5)
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(0),
+        })
+        .unwrap();
+
+        // Adding fuel has no effect.
+        let mut prepare = prototype.prepare();
+        prepare.add_fuel(0);
+        let mut vm = prepare.start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(5))),
+            _ => panic!(),
+        }
+        assert_eq!(vm.fuel_consumed(), None);
+    }
+}
+
+#[test]
+fn compiled_artifact_roundtrip() {
+    let module_bytes = wat::parse_str(
+        r#"
+    (module
+        (import "env" "memory" (memory $mem 0 4096))
+        (import "env" "host" (func $host (param i32) (result i32)))
+        (func (export "hello") (result i32)
+            (call $host (i32.const 3))
+        )
+    )
+    "#,
+    )
+    .unwrap();
+
+    for exec_hint in super::ExecHint::available_engines() {
+        let prototype = super::VirtualMachinePrototype::new(super::Config {
+            module_bytes: &module_bytes,
+            exec_hint,
+            features: super::WasmFeatures::mvp(),
+            fuel_metering: false,
+            symbols: &mut |_, _, _| Ok(9),
+        })
+        .unwrap();
+
+        // Not all engines produce a compiled artifact.
+        let Some(compiled) = prototype.serialize_compiled() else {
+            continue;
+        };
+
+        // Safety: the artifact has just been produced by `serialize_compiled`.
+        let prototype = unsafe {
+            super::VirtualMachinePrototype::from_compiled(
+                super::Config {
+                    module_bytes: &[],
+                    exec_hint,
+                    features: super::WasmFeatures::mvp(),
+                    fuel_metering: false,
+                    symbols: &mut |_, _, _| Ok(9),
+                },
+                &compiled,
+            )
+        }
+        .unwrap();
+
+        let mut vm = prototype.prepare().start("hello", &[]).unwrap();
+        match vm.run(None) {
+            Ok(super::ExecOutcome::Interrupted { id: 9, params }) => {
+                assert_eq!(params, vec![super::WasmValue::I32(3)])
+            }
+            _ => panic!(),
+        }
+        match vm.run(Some(super::WasmValue::I32(4))) {
+            Ok(super::ExecOutcome::Finished {
+                return_value: Ok(value),
+            }) => assert_eq!(value, Some(super::WasmValue::I32(4))),
+            _ => panic!(),
+        }
+
+        // Loading the artifact with a different configuration must fail.
+        // Safety: the artifact has been produced by `serialize_compiled`.
+        let result = unsafe {
+            super::VirtualMachinePrototype::from_compiled(
+                super::Config {
+                    module_bytes: &[],
+                    exec_hint,
+                    features: super::WasmFeatures::mvp(),
+                    fuel_metering: true,
+                    symbols: &mut |_, _, _| Ok(9),
+                },
+                &compiled,
+            )
+        };
+        assert!(matches!(result, Err(super::NewErr::InvalidCompiled(_))));
+    }
+}
+
 // TODO: check that the extended-const feature is disabled: https://github.com/WebAssembly/extended-const/blob/master/proposals/extended-const/Overview.md
 
 // TODO: test for memory reads and writes, including within host functions
@@ -0,0 +1,112 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! BEEFY (Bridge Efficiency Enabling Finality Yielder) is a secondary finality protocol that
+//! runs on top of GrandPa.
+//!
+//! # Overview
+//!
+//! Once a block has been finalized by GrandPa, the BEEFY validators sign a *commitment*
+//! containing, most notably, the root of the Merkle Mountain Range (MMR) of the chain at this
+//! block. Contrary to GrandPa, BEEFY uses ECDSA signatures, which makes these commitments cheap
+//! to verify on other chains, such as Ethereum.
+//!
+//! A commitment signed by at least two thirds of the validators of the current set (see
+//! [`commitment`]) proves that the block has been finalized. The MMR root found in the
+//! commitment can then be used to verify that any earlier block is part of the chain (see
+//! [`mmr`]).
+//!
+//! The validator set changes over time. Each change is announced through a digest log item of
+//! the header of the first block signed by the new set (see [`validator_set_change`]).
+
+use crate::header;
+
+pub mod commitment;
+pub mod mmr;
+
+/// Identifier of the BEEFY consensus engine, as found in block justifications.
+pub const ENGINE_ID: [u8; 4] = *b"BEEF";
+
+/// Name of the runtime function that returns the current BEEFY validator set.
+pub const VALIDATOR_SET_FUNCTION_NAME: &str = "BeefyApi_validator_set";
+
+/// Produces the input to pass to the [`VALIDATOR_SET_FUNCTION_NAME`] runtime call.
+pub fn validator_set_parameters() -> impl Iterator<Item = impl AsRef<[u8]>> + Clone {
+    core::iter::empty::<[u8; 0]>()
+}
+
+/// Attempt to decode the return value of the [`VALIDATOR_SET_FUNCTION_NAME`] runtime call.
+///
+/// Returns `None` if BEEFY isn't enabled yet on the chain.
+pub fn decode_validator_set_return_value(
+    scale_encoded: &[u8],
+) -> Result<Option<header::BeefyValidatorSetRef>, header::Error> {
+    match scale_encoded.split_first() {
+        Some((0, [])) => Ok(None),
+        Some((1, set)) => Ok(Some(header::BeefyValidatorSetRef::from_slice(set)?)),
+        _ => Err(header::Error::BeefyConsensusLogDecodeError),
+    }
+}
+
+/// If the given digest announces a change of the BEEFY validator set, returns the new set.
+///
+/// The BEEFY commitments of the block and its descendants must then be signed by this new set.
+pub fn validator_set_change<'a>(
+    digest: &header::DigestRef<'a>,
+) -> Option<header::BeefyValidatorSetRef<'a>> {
+    digest.logs().find_map(|item| match item {
+        header::DigestItemRef::BeefyConsensus(header::BeefyConsensusLogRef::AuthoritiesChange(
+            set,
+        )) => Some(set),
+        _ => None,
+    })
+}
+
+/// If the given digest contains the root of the Merkle Mountain Range of the chain, returns it.
+pub fn mmr_root<'a>(digest: &header::DigestRef<'a>) -> Option<&'a [u8; 32]> {
+    digest.logs().find_map(|item| match item {
+        header::DigestItemRef::BeefyConsensus(header::BeefyConsensusLogRef::MmrRoot(root)) => {
+            Some(root)
+        }
+        _ => None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn decode_validator_set() {
+        assert!(super::decode_validator_set_return_value(&[0])
+            .unwrap()
+            .is_none());
+
+        let mut encoded = alloc::vec![1, 8];
+        encoded.extend_from_slice(&[2; 33]);
+        encoded.extend_from_slice(&[3; 33]);
+        encoded.extend_from_slice(&5u64.to_le_bytes());
+        let set = super::decode_validator_set_return_value(&encoded)
+            .unwrap()
+            .unwrap();
+        assert_eq!(set.id, 5);
+        assert_eq!(
+            set.validators.collect::<alloc::vec::Vec<_>>(),
+            [&[2; 33], &[3; 33]]
+        );
+
+        assert!(super::decode_validator_set_return_value(&[1, 4]).is_err());
+    }
+}
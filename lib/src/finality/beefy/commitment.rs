@@ -0,0 +1,489 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! BEEFY signed commitments.
+//!
+//! A BEEFY justification, found in the justifications of a block under the
+//! [`super::ENGINE_ID`] engine, contains a *signed commitment*. Use [`decode_signed_commitment`]
+//! to decode it and [`verify`] to verify its signatures.
+
+use crate::util;
+
+use alloc::vec::Vec;
+use core::iter;
+
+/// Identifier of the payload item containing the root of the Merkle Mountain Range.
+pub const MMR_ROOT_PAYLOAD_ID: [u8; 2] = *b"mh";
+
+/// Attempt to decode the given SCALE-encoded BEEFY justification.
+pub fn decode_signed_commitment(
+    scale_encoded: &[u8],
+    block_number_bytes: usize,
+) -> Result<SignedCommitmentRef<'_>, DecodeError> {
+    match nom::combinator::complete(nom::combinator::all_consuming(nom::sequence::preceded(
+        // Only version 1 of the justifications format exists at the moment.
+        nom::bytes::streaming::tag(&[1]),
+        signed_commitment(block_number_bytes),
+    )))(scale_encoded)
+    {
+        Ok((_, commitment)) => Ok(commitment),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(DecodeError(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Potential error when decoding a BEEFY justification.
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "BEEFY justification parsing error: {_0:?}")]
+pub struct DecodeError(nom::error::ErrorKind);
+
+/// Commitment signed by the BEEFY validators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommitmentRef<'a> {
+    /// List of payload items, each identified by a two bytes identifier. Items are ordered by
+    /// identifier.
+    pub payload: Vec<(&'a [u8; 2], &'a [u8])>,
+    /// Number of the block the commitment relates to.
+    pub block_number: u64,
+    /// Identifier of the validator set that has signed the commitment.
+    pub validator_set_id: u64,
+}
+
+impl<'a> CommitmentRef<'a> {
+    /// Returns the root of the Merkle Mountain Range of the chain at the block the commitment
+    /// relates to, if any is present in the payload.
+    pub fn mmr_root(&self) -> Option<&'a [u8; 32]> {
+        self.payload
+            .iter()
+            .find(|(id, _)| **id == MMR_ROOT_PAYLOAD_ID)
+            .and_then(|(_, value)| <&[u8; 32]>::try_from(*value).ok())
+    }
+
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object. This is the message that the validators sign.
+    ///
+    /// # Panic
+    ///
+    /// Panics if [`CommitmentRef::block_number`] doesn't fit in `block_number_bytes` bytes. This
+    /// can't happen if the commitment has been decoded with the same `block_number_bytes`.
+    ///
+    pub fn scale_encoding(
+        &self,
+        block_number_bytes: usize,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        assert!(block_number_fits(self.block_number, block_number_bytes));
+        let mut block_number = Vec::with_capacity(block_number_bytes);
+        block_number.extend_from_slice(&self.block_number.to_le_bytes());
+        block_number.resize(block_number_bytes, 0);
+
+        iter::once(either::Left(either::Left(
+            util::encode_scale_compact_usize(self.payload.len()),
+        )))
+        .chain(self.payload.clone().into_iter().flat_map(|(id, value)| {
+            [
+                either::Right(&id[..]),
+                either::Left(either::Left(util::encode_scale_compact_usize(value.len()))),
+                either::Right(value),
+            ]
+        }))
+        .chain(iter::once(either::Left(either::Right(block_number))))
+        .chain(iter::once(either::Left(either::Right(
+            self.validator_set_id.to_le_bytes().to_vec(),
+        ))))
+    }
+}
+
+/// Commitment and the signatures of the validators.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedCommitmentRef<'a> {
+    /// The commitment that is signed.
+    pub commitment: CommitmentRef<'a>,
+    /// Bitfield indicating which validators have provided a signature. The most significant bit
+    /// of the first byte corresponds to the first validator.
+    signatures_from: &'a [u8],
+    /// Number of validators in the set that has signed the commitment.
+    validator_set_len: u32,
+    /// Concatenation of all the 65 bytes signatures.
+    signatures: &'a [u8],
+}
+
+impl<'a> SignedCommitmentRef<'a> {
+    /// Returns the number of validators in the set that has signed the commitment.
+    pub fn validator_set_len(&self) -> usize {
+        usize::try_from(self.validator_set_len).unwrap_or(usize::MAX)
+    }
+
+    /// Returns the number of signatures in the commitment.
+    pub fn num_signatures(&self) -> usize {
+        self.signatures.len() / 65
+    }
+
+    /// Returns, for each validator of the set, the signature of this validator if any.
+    pub fn signatures(&self) -> impl ExactSizeIterator<Item = Option<&'a [u8; 65]>> + 'a {
+        let signatures_from = self.signatures_from;
+        let mut signatures = self.signatures.chunks(65);
+        (0..self.validator_set_len()).map(move |index| {
+            if bitfield_bit(signatures_from, index) {
+                Some(<&[u8; 65]>::try_from(signatures.next().unwrap()).unwrap())
+            } else {
+                None
+            }
+        })
+    }
+}
+
+/// Configuration for a signed commitment verification process.
+#[derive(Debug)]
+pub struct Config<'a, I> {
+    /// Signed commitment to verify.
+    pub signed_commitment: SignedCommitmentRef<'a>,
+
+    /// Number of bytes used to encode block numbers in the chain.
+    pub block_number_bytes: usize,
+
+    /// Identifier of the validator set expected to have signed the commitment.
+    pub validator_set_id: u64,
+
+    /// List of validators expected to have signed the commitment, in order. Each item is a
+    /// compressed ECDSA public key.
+    pub validators: I,
+}
+
+/// Verifies that a signed commitment is valid.
+pub fn verify<'a>(
+    config: Config<impl ExactSizeIterator<Item = &'a [u8; 33]>>,
+) -> Result<(), VerifyError> {
+    if config.signed_commitment.commitment.validator_set_id != config.validator_set_id {
+        return Err(VerifyError::BadValidatorSetId {
+            expected: config.validator_set_id,
+            obtained: config.signed_commitment.commitment.validator_set_id,
+        });
+    }
+
+    // A commitment whose block number doesn't fit in the size of block numbers of the chain
+    // can't have been signed.
+    if !block_number_fits(
+        config.signed_commitment.commitment.block_number,
+        config.block_number_bytes,
+    ) {
+        return Err(VerifyError::BlockNumberOverflow);
+    }
+
+    let num_validators = config.validators.len();
+    if config.signed_commitment.validator_set_len() != num_validators {
+        return Err(VerifyError::ValidatorSetLenMismatch);
+    }
+
+    // The logic of the check is `actual >= expected - (expected - 1) / 3`, in other words
+    // strictly more than two thirds.
+    if num_validators == 0
+        || config.signed_commitment.num_signatures() < num_validators - (num_validators - 1) / 3
+    {
+        return Err(VerifyError::NotEnoughSignatures);
+    }
+
+    let message = {
+        let mut hasher = <sha3::Keccak256 as sha3::Digest>::new();
+        for buffer in config
+            .signed_commitment
+            .commitment
+            .scale_encoding(config.block_number_bytes)
+        {
+            sha3::Digest::update(&mut hasher, buffer.as_ref());
+        }
+        libsecp256k1::Message::parse(&sha3::Digest::finalize(hasher).into())
+    };
+
+    for (validator_index, (validator, signature)) in config
+        .validators
+        .zip(config.signed_commitment.signatures())
+        .enumerate()
+    {
+        let Some(signature) = signature else {
+            continue;
+        };
+
+        let recovered = libsecp256k1::Signature::parse_standard_slice(&signature[..64])
+            .ok()
+            .and_then(|rs| {
+                let v = libsecp256k1::RecoveryId::parse(if signature[64] > 26 {
+                    signature[64] - 27
+                } else {
+                    signature[64]
+                })
+                .ok()?;
+                libsecp256k1::recover(&message, &rs, &v).ok()
+            });
+
+        match recovered {
+            Some(public_key) if public_key.serialize_compressed() == *validator => {}
+            _ => return Err(VerifyError::BadSignature { validator_index }),
+        }
+    }
+
+    Ok(())
+}
+
+/// Error that can happen while verifying a signed commitment.
+#[derive(Debug, derive_more::Display)]
+pub enum VerifyError {
+    /// Commitment has been signed by an unexpected validator set.
+    #[display(fmt = "Expected validator set {expected}, obtained {obtained}")]
+    BadValidatorSetId { expected: u64, obtained: u64 },
+    /// Block number of the commitment doesn't fit in the number of bytes used to encode block
+    /// numbers in the chain.
+    BlockNumberOverflow,
+    /// Number of validators indicated in the commitment doesn't match the validator set.
+    ValidatorSetLenMismatch,
+    /// Commitment doesn't contain enough validator signatures to be valid.
+    NotEnoughSignatures,
+    /// One of the signatures can't be verified.
+    #[display(fmt = "Bad signature of validator #{validator_index}")]
+    BadSignature { validator_index: usize },
+}
+
+/// Returns `true` if the given block number can be encoded in `block_number_bytes` bytes.
+fn block_number_fits(block_number: u64, block_number_bytes: usize) -> bool {
+    block_number_bytes >= 8 || block_number >> (block_number_bytes * 8) == 0
+}
+
+/// Returns the value of the bit of the given index in a bitfield where the most significant
+/// bit of each byte comes first.
+fn bitfield_bit(bitfield: &[u8], index: usize) -> bool {
+    bitfield
+        .get(index / 8)
+        .is_some_and(|byte| (byte & (0x80 >> (index % 8))) != 0)
+}
+
+/// `Nom` combinator that parses a signed commitment.
+///
+/// The signatures are encoded in a compact way: a bitfield indicates which validators have
+/// provided a signature, followed with the number of validators and the list of signatures.
+fn signed_commitment<'a>(
+    block_number_bytes: usize,
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], SignedCommitmentRef<'a>> {
+    nom::error::context(
+        "signed_commitment",
+        nom::combinator::map_opt(
+            nom::sequence::tuple((
+                commitment(block_number_bytes),
+                crate::util::nom_bytes_decode,
+                nom::number::streaming::le_u32,
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_signatures| {
+                    nom::bytes::streaming::take(num_signatures.saturating_mul(65))
+                }),
+            )),
+            |(commitment, signatures_from, validator_set_len, signatures)| {
+                // Make sure that the bitfield is consistent with the list of signatures.
+                let num_set_bits = (0..usize::try_from(validator_set_len).ok()?)
+                    .filter(|index| bitfield_bit(signatures_from, *index))
+                    .count();
+                if num_set_bits != signatures.len() / 65 {
+                    return None;
+                }
+
+                Some(SignedCommitmentRef {
+                    commitment,
+                    signatures_from,
+                    validator_set_len,
+                    signatures,
+                })
+            },
+        ),
+    )
+}
+
+/// `Nom` combinator that parses a commitment.
+fn commitment<'a>(
+    block_number_bytes: usize,
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&'a [u8], CommitmentRef<'a>> {
+    nom::error::context(
+        "commitment",
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_items| {
+                    nom::multi::many_m_n(
+                        num_items,
+                        num_items,
+                        nom::sequence::tuple((
+                            nom::combinator::map(nom::bytes::streaming::take(2u32), |id| {
+                                <&[u8; 2]>::try_from(id).unwrap()
+                            }),
+                            crate::util::nom_bytes_decode,
+                        )),
+                    )
+                }),
+                crate::util::nom_varsize_number_decode_u64(block_number_bytes),
+                nom::number::streaming::le_u64,
+            )),
+            |(payload, block_number, validator_set_id)| CommitmentRef {
+                payload,
+                block_number,
+                validator_set_id,
+            },
+        ),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    fn secret_key(seed: u8) -> libsecp256k1::SecretKey {
+        libsecp256k1::SecretKey::parse(&[seed; 32]).unwrap()
+    }
+
+    fn public_key(seed: u8) -> [u8; 33] {
+        libsecp256k1::PublicKey::from_secret_key(&secret_key(seed)).serialize_compressed()
+    }
+
+    /// Builds a SCALE-encoded justification where the validators whose seed is in `signers`
+    /// have signed.
+    fn build_justification(num_validators: u8, signers: &[u8], set_id: u64) -> Vec<u8> {
+        let mut commitment = Vec::new();
+        commitment.push(4); // One payload item.
+        commitment.extend_from_slice(b"mh");
+        commitment.push(128); // 32 bytes.
+        commitment.extend_from_slice(&[0xaa; 32]);
+        commitment.extend_from_slice(&1234u32.to_le_bytes());
+        commitment.extend_from_slice(&set_id.to_le_bytes());
+
+        let message = {
+            let hash = <sha3::Keccak256 as sha3::Digest>::digest(&commitment);
+            libsecp256k1::Message::parse(&hash.into())
+        };
+
+        let mut bitfield = alloc::vec![0u8; usize::from(num_validators / 8 + 1)];
+        let mut signatures = Vec::new();
+        for validator in 0..num_validators {
+            if !signers.contains(&validator) {
+                continue;
+            }
+            bitfield[usize::from(validator / 8)] |= 0x80 >> (validator % 8);
+            let (signature, recovery_id) = libsecp256k1::sign(&message, &secret_key(validator + 1));
+            signatures.extend_from_slice(&signature.serialize());
+            signatures.push(recovery_id.serialize());
+        }
+
+        let mut out = Vec::new();
+        out.push(1);
+        out.extend_from_slice(&commitment);
+        out.extend_from_slice(crate::util::encode_scale_compact_usize(bitfield.len()).as_ref());
+        out.extend_from_slice(&bitfield);
+        out.extend_from_slice(&u32::from(num_validators).to_le_bytes());
+        out.extend_from_slice(
+            crate::util::encode_scale_compact_usize(signatures.len() / 65).as_ref(),
+        );
+        out.extend_from_slice(&signatures);
+        out
+    }
+
+    #[test]
+    fn decode_and_verify() {
+        let validators = (1..=4).map(public_key).collect::<Vec<_>>();
+        let justification = build_justification(4, &[0, 1, 3], 7);
+
+        let decoded = super::decode_signed_commitment(&justification, 4).unwrap();
+        assert_eq!(decoded.commitment.block_number, 1234);
+        assert_eq!(decoded.commitment.validator_set_id, 7);
+        assert_eq!(decoded.commitment.mmr_root(), Some(&[0xaa; 32]));
+        assert_eq!(
+            decoded
+                .signatures()
+                .map(|s| s.is_some())
+                .collect::<Vec<_>>(),
+            [true, true, false, true]
+        );
+
+        super::verify(super::Config {
+            signed_commitment: decoded,
+            block_number_bytes: 4,
+            validator_set_id: 7,
+            validators: validators.iter(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn not_enough_signatures() {
+        let validators = (1..=4).map(public_key).collect::<Vec<_>>();
+        let justification = build_justification(4, &[0, 3], 7);
+        let decoded = super::decode_signed_commitment(&justification, 4).unwrap();
+
+        assert!(matches!(
+            super::verify(super::Config {
+                signed_commitment: decoded,
+                block_number_bytes: 4,
+                validator_set_id: 7,
+                validators: validators.iter(),
+            }),
+            Err(super::VerifyError::NotEnoughSignatures)
+        ));
+    }
+
+    #[test]
+    fn block_number_overflow() {
+        let validators = (1..=4).map(public_key).collect::<Vec<_>>();
+        let justification = build_justification(4, &[0, 1, 2, 3], 7);
+        let decoded = super::decode_signed_commitment(&justification, 4).unwrap();
+
+        // Block number 1234 can't be encoded on a single byte.
+        assert!(matches!(
+            super::verify(super::Config {
+                signed_commitment: decoded,
+                block_number_bytes: 1,
+                validator_set_id: 7,
+                validators: validators.iter(),
+            }),
+            Err(super::VerifyError::BlockNumberOverflow)
+        ));
+    }
+
+    #[test]
+    fn wrong_validators() {
+        let validators = (2..=5).map(public_key).collect::<Vec<_>>();
+        let justification = build_justification(4, &[0, 1, 2, 3], 7);
+        let decoded = super::decode_signed_commitment(&justification, 4).unwrap();
+
+        assert!(matches!(
+            super::verify(super::Config {
+                signed_commitment: decoded,
+                block_number_bytes: 4,
+                validator_set_id: 7,
+                validators: validators.iter(),
+            }),
+            Err(super::VerifyError::BadSignature { validator_index: 0 })
+        ));
+    }
+
+    #[test]
+    fn wrong_set_id() {
+        let validators = (1..=4).map(public_key).collect::<Vec<_>>();
+        let justification = build_justification(4, &[0, 1, 2, 3], 8);
+        let decoded = super::decode_signed_commitment(&justification, 4).unwrap();
+
+        assert!(matches!(
+            super::verify(super::Config {
+                signed_commitment: decoded,
+                block_number_bytes: 4,
+                validator_set_id: 7,
+                validators: validators.iter(),
+            }),
+            Err(super::VerifyError::BadValidatorSetId { .. })
+        ));
+    }
+}
@@ -0,0 +1,423 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Merkle Mountain Range proofs.
+//!
+//! # Overview
+//!
+//! The Merkle Mountain Range (MMR) is an append-only Merkle structure whose leaves are, in the
+//! context of BEEFY, one item per block of the chain. The root of the MMR is found in BEEFY
+//! commitments (see [`super::commitment::CommitmentRef::mmr_root`]).
+//!
+//! The MMR consists in a list of perfect binary trees of decreasing height, called *peaks*. The
+//! root of the MMR is obtained by "bagging" the peaks from right to left: the two right-most
+//! peaks are replaced with the hash of the concatenation of the right-most peak followed with
+//! the one on its left, and so on until only one hash remains. All the hashes use Keccak-256.
+//!
+//! A *leaf proof* contains the hashes necessary to recalculate the root of the MMR from a leaf.
+//! Use [`decode_leaf_proof`] to decode a proof returned by the `MmrApi_generate_proof` runtime
+//! function, and [`verify_leaf_proof`] to verify it.
+
+use alloc::vec::Vec;
+
+/// Attempt to decode the given SCALE-encoded leaf proof.
+pub fn decode_leaf_proof(scale_encoded: &[u8]) -> Result<LeafProofRef<'_>, DecodeError> {
+    match nom::combinator::complete(nom::combinator::all_consuming(leaf_proof))(scale_encoded) {
+        Ok((_, proof)) => Ok(proof),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(DecodeError(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Potential error when decoding a leaf proof.
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "MMR leaf proof parsing error: {_0:?}")]
+pub struct DecodeError(nom::error::ErrorKind);
+
+/// Decoded leaf proof.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeafProofRef<'a> {
+    /// Indices of the leaves the proof is for.
+    pub leaf_indices: Vec<u64>,
+    /// Number of leaves in the MMR.
+    pub leaf_count: u64,
+    /// Hashes of the nodes of the proof.
+    pub items: Vec<&'a [u8; 32]>,
+}
+
+/// Configuration for a leaf proof verification process.
+#[derive(Debug)]
+pub struct Config<'a, I> {
+    /// Root of the Merkle Mountain Range the leaf is expected to belong to.
+    pub mmr_root: &'a [u8; 32],
+
+    /// SCALE-encoded leaf whose presence to verify.
+    pub leaf_scale_encoded: &'a [u8],
+
+    /// Index of the leaf within the MMR.
+    pub leaf_index: u64,
+
+    /// Number of leaves in the MMR. See [`LeafProofRef::leaf_count`].
+    pub leaf_count: u64,
+
+    /// Hashes of the nodes of the proof. See [`LeafProofRef::items`].
+    pub proof_items: I,
+}
+
+/// Verifies that a leaf belongs to a Merkle Mountain Range.
+///
+/// Only proofs concerning a single leaf are supported.
+pub fn verify_leaf_proof<'a>(
+    config: Config<impl Iterator<Item = &'a [u8; 32]>>,
+) -> Result<(), VerifyError> {
+    if config.leaf_index >= config.leaf_count {
+        return Err(VerifyError::LeafIndexOutOfRange);
+    }
+
+    let mut proof_items = config.proof_items;
+    let mmr_size = leaf_index_to_mmr_size(config.leaf_count - 1);
+    let mut leaf = Some((leaf_index_to_pos(config.leaf_index), {
+        let mut out = [0; 32];
+        out.copy_from_slice(&<sha3::Keccak256 as sha3::Digest>::digest(
+            config.leaf_scale_encoded,
+        ));
+        out
+    }));
+
+    // Calculate the hash of each peak. The peaks on the left of the leaf and the peak
+    // containing the leaf are each found in the proof or calculated, while the peaks on the
+    // right of the leaf are found in the proof already bagged together as a single item.
+    let mut peaks_hashes = Vec::new();
+    for peak_pos in peaks(mmr_size) {
+        let peak_hash = match leaf {
+            Some((pos, hash)) if pos <= peak_pos => {
+                leaf = None;
+                peak_hash_from_leaf(pos, hash, peak_pos, &mut proof_items)?
+            }
+            _ => match proof_items.next() {
+                Some(hash) => *hash,
+                None => break,
+            },
+        };
+        peaks_hashes.push(peak_hash);
+    }
+
+    if let Some(right_peaks) = proof_items.next() {
+        peaks_hashes.push(*right_peaks);
+    }
+    if proof_items.next().is_some() {
+        return Err(VerifyError::ProofTooLong);
+    }
+
+    // Bag the peaks together, from right to left. Note that, contrary to the nodes within a
+    // peak, the right-hand side comes first when hashing.
+    let mut root = peaks_hashes.pop().ok_or(VerifyError::ProofTooShort)?;
+    while let Some(left_peak) = peaks_hashes.pop() {
+        root = merge(&root, &left_peak);
+    }
+
+    if root != *config.mmr_root {
+        return Err(VerifyError::RootMismatch);
+    }
+
+    Ok(())
+}
+
+/// Error that can happen while verifying a leaf proof.
+#[derive(Debug, derive_more::Display)]
+pub enum VerifyError {
+    /// Leaf index is superior or equal to the number of leaves.
+    LeafIndexOutOfRange,
+    /// Proof is missing some hashes.
+    ProofTooShort,
+    /// Proof contains more hashes than necessary.
+    ProofTooLong,
+    /// Proof is inconsistent with the structure of the MMR.
+    BadProof,
+    /// Root calculated from the proof doesn't match the expected root.
+    RootMismatch,
+}
+
+/// Climbs from the leaf at the given position to the peak at the given position.
+fn peak_hash_from_leaf<'a>(
+    mut pos: u64,
+    mut hash: [u8; 32],
+    peak_pos: u64,
+    proof_items: &mut impl Iterator<Item = &'a [u8; 32]>,
+) -> Result<[u8; 32], VerifyError> {
+    let mut height = 0;
+    while pos != peak_pos {
+        let sibling = proof_items.next().ok_or(VerifyError::ProofTooShort)?;
+
+        // If the next node is higher than the current one, then the current node is a right
+        // child and its parent is the next node. Otherwise, it is a left child.
+        if pos_height_in_tree(pos + 1) > height {
+            pos += 1;
+            hash = merge(sibling, &hash);
+        } else {
+            pos += 2 << height;
+            hash = merge(&hash, sibling);
+        }
+
+        if pos > peak_pos {
+            return Err(VerifyError::BadProof);
+        }
+
+        height += 1;
+    }
+
+    Ok(hash)
+}
+
+/// Returns the hash of a parent node given its two children.
+fn merge(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut hasher = <sha3::Keccak256 as sha3::Digest>::new();
+    sha3::Digest::update(&mut hasher, left);
+    sha3::Digest::update(&mut hasher, right);
+    sha3::Digest::finalize(hasher).into()
+}
+
+/// Returns the number of nodes in an MMR whose last leaf has the given index.
+fn leaf_index_to_mmr_size(index: u64) -> u64 {
+    let leaves_count = index + 1;
+    2 * leaves_count - u64::from(leaves_count.count_ones())
+}
+
+/// Returns the position of a leaf within the list of nodes.
+fn leaf_index_to_pos(index: u64) -> u64 {
+    leaf_index_to_mmr_size(index) - u64::from((index + 1).trailing_zeros()) - 1
+}
+
+/// Returns the height of the node at the given position, where leaves have a height of 0.
+fn pos_height_in_tree(pos: u64) -> u32 {
+    let mut pos = pos + 1;
+    // Jump to the left-most node of the same height until reaching a node whose position,
+    // plus one, only contains ones.
+    while pos.count_zeros() != pos.leading_zeros() {
+        pos -= (1 << (63 - pos.leading_zeros())) - 1;
+    }
+    63 - pos.leading_zeros()
+}
+
+/// Returns the positions of the peaks of an MMR of the given size, from left to right.
+fn peaks(mmr_size: u64) -> Vec<u64> {
+    let mut out = Vec::new();
+    if mmr_size == 0 {
+        return out;
+    }
+
+    // Find the highest peak, which is the left-most one.
+    let (mut height, mut pos) = {
+        let mut height = 0;
+        while (2u64 << (height + 1)) - 2 < mmr_size {
+            height += 1;
+        }
+        (height, (2u64 << height) - 2)
+    };
+    out.push(pos);
+
+    // Find the other peaks by moving to the right sibling then down to the left child until
+    // reaching a node that exists.
+    while height > 0 {
+        pos += (2 << height) - 1;
+        while pos > mmr_size - 1 {
+            if height == 0 {
+                return out;
+            }
+            height -= 1;
+            pos -= 2 << height;
+        }
+        out.push(pos);
+    }
+
+    out
+}
+
+/// `Nom` combinator that parses a leaf proof.
+fn leaf_proof(bytes: &[u8]) -> nom::IResult<&[u8], LeafProofRef<'_>> {
+    nom::error::context(
+        "leaf_proof",
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::combinator::flat_map(crate::util::nom_scale_compact_usize, |num| {
+                    nom::multi::many_m_n(num, num, nom::number::streaming::le_u64)
+                }),
+                nom::number::streaming::le_u64,
+                nom::combinator::flat_map(crate::util::nom_scale_compact_usize, |num| {
+                    nom::multi::many_m_n(
+                        num,
+                        num,
+                        nom::combinator::map(nom::bytes::streaming::take(32u32), |h| {
+                            <&[u8; 32]>::try_from(h).unwrap()
+                        }),
+                    )
+                }),
+            )),
+            |(leaf_indices, leaf_count, items)| LeafProofRef {
+                leaf_indices,
+                leaf_count,
+                items,
+            },
+        ),
+    )(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    #[test]
+    fn peaks() {
+        assert_eq!(super::peaks(1), [0]);
+        assert_eq!(super::peaks(4), [2, 3]);
+        assert_eq!(super::peaks(8), [6, 7]);
+        assert_eq!(super::peaks(11), [6, 9, 10]);
+    }
+
+    // The test vectors below have been generated using an implementation of the MMR that is
+    // independent from the one in this module and that follows the algorithm of the
+    // `ckb-merkle-mountain-range` library used by Substrate. Each leaf is a single byte,
+    // starting with `a`.
+
+    fn check_proofs(leaf_count: u64, root: &str, proofs: &[(u64, &[&str])]) {
+        let root = <[u8; 32]>::try_from(hex::decode(root).unwrap()).unwrap();
+
+        for (leaf_index, proof) in proofs {
+            let proof = proof
+                .iter()
+                .map(|item| <[u8; 32]>::try_from(hex::decode(item).unwrap()).unwrap())
+                .collect::<Vec<_>>();
+            let leaf = [b'a' + u8::try_from(*leaf_index).unwrap()];
+
+            super::verify_leaf_proof(super::Config {
+                mmr_root: &root,
+                leaf_scale_encoded: &leaf,
+                leaf_index: *leaf_index,
+                leaf_count,
+                proof_items: proof.iter(),
+            })
+            .unwrap();
+
+            // Verifying a different leaf must fail.
+            assert!(matches!(
+                super::verify_leaf_proof(super::Config {
+                    mmr_root: &root,
+                    leaf_scale_encoded: b"z",
+                    leaf_index: *leaf_index,
+                    leaf_count,
+                    proof_items: proof.iter(),
+                }),
+                Err(super::VerifyError::RootMismatch)
+            ));
+        }
+    }
+
+    #[test]
+    fn seven_leaves() {
+        check_proofs(
+            7,
+            "329bcb82b465308e4d3445408c794db388e401855b1fe6f2981c93ca34ce516b",
+            &[
+                (
+                    0,
+                    &[
+                        "b5553de315e0edf504d9150af82dafa5c4667fa618ed0a6f19c69b41166c5510",
+                        "d253a52d4cb00de2895e85f2529e2976e6aaaa5c18106b68ab66813e14415669",
+                        "4389279b24da389afe91da3f4baeabbfeda9c9906f22d79370eab048bfbf0d19",
+                    ],
+                ),
+                (
+                    4,
+                    &[
+                        "68203f90e9d07dc5859259d7536e87a6ba9d345f2552b5b9de2999ddce9ce1bf",
+                        "d1e8aeb79500496ef3dc2e57ba746a8315d048b7a664a2bf948db4fa91960483",
+                        "14bcc435f49d130d189737f9762feb25c44ef5b886bef833e31a702af6be4748",
+                    ],
+                ),
+                (
+                    6,
+                    &[
+                        "68203f90e9d07dc5859259d7536e87a6ba9d345f2552b5b9de2999ddce9ce1bf",
+                        "f0b49bb4b0d9396e0315755ceafaa280707b32e75e6c9053f5cdf2679dcd5c6a",
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn eleven_leaves() {
+        check_proofs(
+            11,
+            "cd3bf86c1e524f7e51f479feff23c576b23f5615746b94339421f8ec9967b3d1",
+            &[
+                (
+                    2,
+                    &[
+                        "f1918e8562236eb17adc8502332f4c9c82bc14e19bfc0aa10ab674ff75b3d2f3",
+                        "805b21d846b189efaeb0377d6bb0d201b3872a363e607c25088f025b0c6ae1f8",
+                        "f313fc9eb1c4864b1b8e78296656fb7831cc0ed46361bf3452db1c4cec430050",
+                        "d8e984af4b9387de00d91ccff0ebf603fe7ecd6f2cd34092845ccfa7ad056b7b",
+                    ],
+                ),
+                (
+                    9,
+                    &[
+                        "cd07272f4955ddcfdac38ff36dff9d3e4353498923679ab548ba87e34648e4a3",
+                        "ea00237ef11bd9615a3b6d2629f2c6259d67b19bb94947a1bd739bae3415141c",
+                        "f3d0adcb6a1c70832365e9da0a6b2f5199422f6a53c67cfad171114e3442aa0f",
+                    ],
+                ),
+                (
+                    10,
+                    &[
+                        "cd07272f4955ddcfdac38ff36dff9d3e4353498923679ab548ba87e34648e4a3",
+                        "00f1ab17c0a22cac8888dcacf2506f283715df19c6155fecd32865fa76fe0b4c",
+                    ],
+                ),
+            ],
+        );
+    }
+
+    #[test]
+    fn single_leaf() {
+        // The root of an MMR containing a single leaf is the hash of this leaf.
+        let root = <[u8; 32]>::from(<sha3::Keccak256 as sha3::Digest>::digest(b"a"));
+        super::verify_leaf_proof(super::Config {
+            mmr_root: &root,
+            leaf_scale_encoded: b"a",
+            leaf_index: 0,
+            leaf_count: 1,
+            proof_items: [].iter(),
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn decode_proof() {
+        let mut encoded = alloc::vec![4];
+        encoded.extend_from_slice(&3u64.to_le_bytes());
+        encoded.extend_from_slice(&5u64.to_le_bytes());
+        encoded.push(4);
+        encoded.extend_from_slice(&[7; 32]);
+
+        let proof = super::decode_leaf_proof(&encoded).unwrap();
+        assert_eq!(proof.leaf_indices, [3]);
+        assert_eq!(proof.leaf_count, 5);
+        assert_eq!(proof.items, [&[7; 32]]);
+    }
+}
@@ -80,12 +80,16 @@ use core::{fmt, iter, slice};
 
 mod aura;
 mod babe;
+mod beefy;
 mod grandpa;
+mod sassafras;
 mod tests;
 
 pub use aura::*;
 pub use babe::*;
+pub use beefy::*;
 pub use grandpa::*;
+pub use sassafras::*;
 
 /// Returns a hash of a SCALE-encoded header.
 ///
@@ -218,7 +222,16 @@ pub enum Error {
     MutipleRuntimeEnvironmentUpdated,
     /// Found a Babe configuration change digest without an epoch change digest.
     UnexpectedBabeConfigDescriptor,
+    /// Bad length of a Sassafras seal.
+    BadSassafrasSealLength,
+    SassafrasPreDigestDecodeError,
+    SassafrasConsensusLogDecodeError,
+    /// There are multiple Sassafras pre-runtime digests in the block header.
+    MultipleSassafrasPreRuntimeDigests,
+    /// There are multiple Sassafras epoch descriptor digests in the block header.
+    MultipleSassafrasEpochDescriptors,
     GrandpaConsensusLogDecodeError,
+    BeefyConsensusLogDecodeError,
     /// Proof-of-work consensus algorithm is intentionally not supported for ideological reasons.
     PowIdeologicallyNotSupported,
 }
@@ -391,6 +404,13 @@ pub struct DigestRef<'a> {
     /// Index of the [`DigestItemRef::BabeConsensus`] item containing a
     /// [`BabeConsensusLogRef::NextConfigData`], if any.
     babe_next_config_data_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasSeal`] item, if any.
+    sassafras_seal_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasPreDigest`] item, if any.
+    sassafras_predigest_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasConsensus`] item containing a
+    /// [`SassafrasConsensusLogRef::NextEpochData`], if any.
+    sassafras_next_epoch_data_index: Option<usize>,
     /// `true` if there is a [`DigestItemRef::RuntimeEnvironmentUpdated`] item.
     has_runtime_environment_updated: bool,
 }
@@ -423,6 +443,9 @@ impl<'a> DigestRef<'a> {
             babe_predigest_index: None,
             babe_next_epoch_data_index: None,
             babe_next_config_data_index: None,
+            sassafras_seal_index: None,
+            sassafras_predigest_index: None,
+            sassafras_next_epoch_data_index: None,
             has_runtime_environment_updated: false,
         }
     }
@@ -441,6 +464,13 @@ impl<'a> DigestRef<'a> {
         self.logs().any(|l| l.is_babe())
     }
 
+    /// Returns true if the list has any item that belong to the Sassafras consensus engine.
+    ///
+    /// This function is `O(n)` over the number of log items.
+    pub fn has_any_sassafras(&self) -> bool {
+        self.logs().any(|l| l.is_sassafras())
+    }
+
     /// Returns true if the list has any item that belong to the Grandpa finality engine.
     ///
     /// This function is `O(n)` over the number of log items.
@@ -534,6 +564,52 @@ impl<'a> DigestRef<'a> {
         }
     }
 
+    /// Returns the Sassafras seal digest item, if any.
+    pub fn sassafras_seal(&self) -> Option<&'a [u8; 65]> {
+        if let Some(sassafras_seal_index) = self.sassafras_seal_index {
+            if let DigestItemRef::SassafrasSeal(seal) =
+                self.logs().nth(sassafras_seal_index).unwrap()
+            {
+                Some(seal)
+            } else {
+                unreachable!()
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Returns the Sassafras pre-runtime digest item, if any.
+    pub fn sassafras_pre_runtime(&self) -> Option<SassafrasPreDigestRef<'a>> {
+        if let Some(sassafras_predigest_index) = self.sassafras_predigest_index {
+            if let DigestItemRef::SassafrasPreDigest(item) =
+                self.logs().nth(sassafras_predigest_index).unwrap()
+            {
+                Some(item)
+            } else {
+                unreachable!()
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Returns the Sassafras epoch information stored in the header, if any.
+    pub fn sassafras_epoch_information(&self) -> Option<SassafrasNextEpochRef<'a>> {
+        if let Some(sassafras_next_epoch_data_index) = self.sassafras_next_epoch_data_index {
+            if let DigestItemRef::SassafrasConsensus(SassafrasConsensusLogRef::NextEpochData(
+                epoch,
+            )) = self.logs().nth(sassafras_next_epoch_data_index).unwrap()
+            {
+                Some(epoch)
+            } else {
+                unreachable!()
+            }
+        } else {
+            None
+        }
+    }
+
     /// Returns `true` if there is a [`DigestItemRef::RuntimeEnvironmentUpdated`] item.
     pub fn has_runtime_environment_updated(&self) -> bool {
         self.has_runtime_environment_updated
@@ -541,7 +617,10 @@ impl<'a> DigestRef<'a> {
 
     /// If the last element of the list is a seal, removes it from the [`DigestRef`].
     pub fn pop_seal(&mut self) -> Option<Seal<'a>> {
-        let seal_pos = self.babe_seal_index.or(self.aura_seal_index)?;
+        let seal_pos = self
+            .babe_seal_index
+            .or(self.aura_seal_index)
+            .or(self.sassafras_seal_index)?;
 
         match &mut self.inner {
             DigestRefInner::Parsed(list) => {
@@ -554,6 +633,7 @@ impl<'a> DigestRef<'a> {
                 match item {
                     DigestItem::AuraSeal(seal) => Some(Seal::Aura(seal)),
                     DigestItem::BabeSeal(seal) => Some(Seal::Babe(seal)),
+                    DigestItem::SassafrasSeal(seal) => Some(Seal::Sassafras(seal)),
                     _ => unreachable!(),
                 }
             }
@@ -586,6 +666,7 @@ impl<'a> DigestRef<'a> {
                     *digest_logs_len -= 1;
                     *digest = &digest[..digest.len() - pointer.len()];
                     self.babe_seal_index = None;
+                    self.sassafras_seal_index = None;
                     debug_assert_eq!(remaining_len, 1);
                 } else {
                     unreachable!()
@@ -594,6 +675,7 @@ impl<'a> DigestRef<'a> {
                 match iter.next() {
                     Some(DigestItemRef::AuraSeal(seal)) => Some(Seal::Aura(seal)),
                     Some(DigestItemRef::BabeSeal(seal)) => Some(Seal::Babe(seal)),
+                    Some(DigestItemRef::SassafrasSeal(seal)) => Some(Seal::Sassafras(seal)),
                     _ => unreachable!(),
                 }
             }
@@ -662,6 +744,9 @@ impl<'a> DigestRef<'a> {
         let mut babe_predigest_index = None;
         let mut babe_next_epoch_data_index = None;
         let mut babe_next_config_data_index = None;
+        let mut sassafras_seal_index = None;
+        let mut sassafras_predigest_index = None;
+        let mut sassafras_next_epoch_data_index = None;
         let mut has_runtime_environment_updated = false;
 
         // Iterate through the log items to see if anything is wrong.
@@ -694,6 +779,28 @@ impl<'a> DigestRef<'a> {
                 }
                 DigestItem::BabeConsensus(BabeConsensusLog::OnDisabled(_)) => {}
                 DigestItem::GrandpaConsensus(_) => {}
+                DigestItem::BeefyConsensus(_) => {}
+                DigestItem::SassafrasPreDigest(_) if sassafras_predigest_index.is_none() => {
+                    sassafras_predigest_index = Some(item_num);
+                }
+                DigestItem::SassafrasPreDigest(_) => {
+                    return Err(Error::MultipleSassafrasPreRuntimeDigests)
+                }
+                DigestItem::SassafrasConsensus(SassafrasConsensusLog::NextEpochData(_))
+                    if sassafras_next_epoch_data_index.is_none() =>
+                {
+                    sassafras_next_epoch_data_index = Some(item_num);
+                }
+                DigestItem::SassafrasConsensus(SassafrasConsensusLog::NextEpochData(_)) => {
+                    return Err(Error::MultipleSassafrasEpochDescriptors);
+                }
+                DigestItem::SassafrasConsensus(SassafrasConsensusLog::OnDisabled(_)) => {}
+                DigestItem::SassafrasSeal(_) if item_num == slice.len() - 1 => {
+                    debug_assert!(aura_seal_index.is_none());
+                    debug_assert!(babe_seal_index.is_none());
+                    sassafras_seal_index = Some(item_num);
+                }
+                DigestItem::SassafrasSeal(_) => return Err(Error::SealIsntLastItem),
                 DigestItem::AuraSeal(_) if item_num == slice.len() - 1 => {
                     debug_assert!(aura_seal_index.is_none());
                     debug_assert!(babe_seal_index.is_none());
@@ -735,6 +842,9 @@ impl<'a> DigestRef<'a> {
             babe_predigest_index,
             babe_next_epoch_data_index,
             babe_next_config_data_index,
+            sassafras_seal_index,
+            sassafras_predigest_index,
+            sassafras_next_epoch_data_index,
             has_runtime_environment_updated,
         })
     }
@@ -754,6 +864,9 @@ impl<'a> DigestRef<'a> {
         let mut babe_predigest_index = None;
         let mut babe_next_epoch_data_index = None;
         let mut babe_next_config_data_index = None;
+        let mut sassafras_seal_index = None;
+        let mut sassafras_predigest_index = None;
+        let mut sassafras_next_epoch_data_index = None;
         let mut has_runtime_environment_updated = false;
 
         // Iterate through the log items to see if anything is wrong.
@@ -794,6 +907,28 @@ impl<'a> DigestRef<'a> {
                 }
                 DigestItemRef::BabeConsensus(BabeConsensusLogRef::OnDisabled(_)) => {}
                 DigestItemRef::GrandpaConsensus(_) => {}
+                DigestItemRef::BeefyConsensus(_) => {}
+                DigestItemRef::SassafrasPreDigest(_) if sassafras_predigest_index.is_none() => {
+                    sassafras_predigest_index = Some(item_num);
+                }
+                DigestItemRef::SassafrasPreDigest(_) => {
+                    return Err(Error::MultipleSassafrasPreRuntimeDigests)
+                }
+                DigestItemRef::SassafrasConsensus(SassafrasConsensusLogRef::NextEpochData(_))
+                    if sassafras_next_epoch_data_index.is_none() =>
+                {
+                    sassafras_next_epoch_data_index = Some(item_num);
+                }
+                DigestItemRef::SassafrasConsensus(SassafrasConsensusLogRef::NextEpochData(_)) => {
+                    return Err(Error::MultipleSassafrasEpochDescriptors);
+                }
+                DigestItemRef::SassafrasConsensus(SassafrasConsensusLogRef::OnDisabled(_)) => {}
+                DigestItemRef::SassafrasSeal(_) if item_num == digest_logs_len - 1 => {
+                    debug_assert!(aura_seal_index.is_none());
+                    debug_assert!(babe_seal_index.is_none());
+                    sassafras_seal_index = Some(item_num);
+                }
+                DigestItemRef::SassafrasSeal(_) => return Err(Error::SealIsntLastItem),
                 DigestItemRef::AuraSeal(_) if item_num == digest_logs_len - 1 => {
                     debug_assert!(aura_seal_index.is_none());
                     debug_assert!(babe_seal_index.is_none());
@@ -839,6 +974,9 @@ impl<'a> DigestRef<'a> {
             babe_predigest_index,
             babe_next_epoch_data_index,
             babe_next_config_data_index,
+            sassafras_seal_index,
+            sassafras_predigest_index,
+            sassafras_next_epoch_data_index,
             has_runtime_environment_updated,
         };
 
@@ -862,6 +1000,9 @@ impl<'a> From<&'a Digest> for DigestRef<'a> {
             babe_predigest_index: digest.babe_predigest_index,
             babe_next_epoch_data_index: digest.babe_next_epoch_data_index,
             babe_next_config_data_index: digest.babe_next_config_data_index,
+            sassafras_seal_index: digest.sassafras_seal_index,
+            sassafras_predigest_index: digest.sassafras_predigest_index,
+            sassafras_next_epoch_data_index: digest.sassafras_next_epoch_data_index,
             has_runtime_environment_updated: digest.has_runtime_environment_updated,
         }
     }
@@ -871,6 +1012,7 @@ impl<'a> From<&'a Digest> for DigestRef<'a> {
 pub enum Seal<'a> {
     Aura(&'a [u8; 64]),
     Babe(&'a [u8; 64]),
+    Sassafras(&'a [u8; 65]),
 }
 
 /// Generic header digest.
@@ -892,6 +1034,13 @@ pub struct Digest {
     /// Index of the [`DigestItemRef::BabeConsensus`] item containing a
     /// [`BabeConsensusLogRef::NextConfigData`], if any.
     babe_next_config_data_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasSeal`] item, if any.
+    sassafras_seal_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasPreDigest`] item, if any.
+    sassafras_predigest_index: Option<usize>,
+    /// Index of the [`DigestItemRef::SassafrasConsensus`] item containing a
+    /// [`SassafrasConsensusLogRef::NextEpochData`], if any.
+    sassafras_next_epoch_data_index: Option<usize>,
     /// `true` if there is a [`DigestItemRef::RuntimeEnvironmentUpdated`] item.
     has_runtime_environment_updated: bool,
 }
@@ -925,6 +1074,21 @@ impl Digest {
         DigestRef::from(self).babe_epoch_information()
     }
 
+    /// Returns the Sassafras seal digest item, if any.
+    pub fn sassafras_seal(&self) -> Option<&[u8; 65]> {
+        DigestRef::from(self).sassafras_seal()
+    }
+
+    /// Returns the Sassafras pre-runtime digest item, if any.
+    pub fn sassafras_pre_runtime(&self) -> Option<SassafrasPreDigestRef<'_>> {
+        DigestRef::from(self).sassafras_pre_runtime()
+    }
+
+    /// Returns the Sassafras epoch information stored in the header, if any.
+    pub fn sassafras_epoch_information(&self) -> Option<SassafrasNextEpochRef<'_>> {
+        DigestRef::from(self).sassafras_epoch_information()
+    }
+
     /// Returns `true` if there is a [`DigestItemRef::RuntimeEnvironmentUpdated`] item.
     pub fn has_runtime_environment_updated(&self) -> bool {
         self.has_runtime_environment_updated
@@ -949,6 +1113,9 @@ impl<'a> From<DigestRef<'a>> for Digest {
             babe_predigest_index: digest.babe_predigest_index,
             babe_next_epoch_data_index: digest.babe_next_epoch_data_index,
             babe_next_config_data_index: digest.babe_next_config_data_index,
+            sassafras_seal_index: digest.sassafras_seal_index,
+            sassafras_predigest_index: digest.sassafras_predigest_index,
+            sassafras_next_epoch_data_index: digest.sassafras_next_epoch_data_index,
             has_runtime_environment_updated: digest.has_runtime_environment_updated,
         }
     }
@@ -1023,8 +1190,15 @@ pub enum DigestItemRef<'a> {
     /// Block signature made using the BABE consensus engine.
     BabeSeal(&'a [u8; 64]),
 
+    SassafrasPreDigest(SassafrasPreDigestRef<'a>),
+    SassafrasConsensus(SassafrasConsensusLogRef<'a>),
+    /// Block signature made using the Sassafras consensus engine.
+    SassafrasSeal(&'a [u8; 65]),
+
     GrandpaConsensus(GrandpaConsensusLogRef<'a>),
 
+    BeefyConsensus(BeefyConsensusLogRef<'a>),
+
     /// Consensus item with an engine that hasn't been recognized.
     UnknownConsensus {
         /// Name of the consensus engine.
@@ -1079,11 +1253,26 @@ impl<'a> DigestItemRef<'a> {
         )
     }
 
+    /// True if the item is relevant to the Sassafras consensus engine.
+    pub fn is_sassafras(&self) -> bool {
+        matches!(
+            self,
+            DigestItemRef::SassafrasPreDigest(_)
+                | DigestItemRef::SassafrasConsensus(_)
+                | DigestItemRef::SassafrasSeal(_)
+        )
+    }
+
     /// True if the item is relevant to the Grandpa finality engine.
     pub fn is_grandpa(&self) -> bool {
         matches!(self, DigestItemRef::GrandpaConsensus(_))
     }
 
+    /// True if the item is relevant to the BEEFY finality engine.
+    pub fn is_beefy(&self) -> bool {
+        matches!(self, DigestItemRef::BeefyConsensus(_))
+    }
+
     /// Decodes a SCALE-encoded digest item.
     pub fn from_scale_encoded(bytes: &'a [u8], block_number_bytes: usize) -> Result<Self, Error> {
         let (item, remain) = decode_item(bytes, block_number_bytes)?;
@@ -1163,6 +1352,41 @@ impl<'a> DigestItemRef<'a> {
                 ret.extend_from_slice(util::encode_scale_compact_usize(encoded.len()).as_ref());
                 (ret, either::Left(encoded))
             }
+            DigestItemRef::SassafrasPreDigest(ref sassafras_pre_digest) => {
+                let encoded = sassafras_pre_digest
+                    .scale_encoding()
+                    .fold(Vec::new(), |mut a, b| {
+                        a.extend_from_slice(b.as_ref());
+                        a
+                    });
+
+                let mut ret = Vec::with_capacity(12);
+                ret.push(6);
+                ret.extend_from_slice(b"SASS");
+                ret.extend_from_slice(util::encode_scale_compact_usize(encoded.len()).as_ref());
+                (ret, either::Left(encoded))
+            }
+            DigestItemRef::SassafrasConsensus(ref sassafras_consensus) => {
+                let encoded = sassafras_consensus
+                    .scale_encoding()
+                    .fold(Vec::new(), |mut a, b| {
+                        a.extend_from_slice(b.as_ref());
+                        a
+                    });
+
+                let mut ret = Vec::with_capacity(12);
+                ret.push(4);
+                ret.extend_from_slice(b"SASS");
+                ret.extend_from_slice(util::encode_scale_compact_usize(encoded.len()).as_ref());
+                (ret, either::Left(encoded))
+            }
+            DigestItemRef::SassafrasSeal(seal) => {
+                let mut ret = Vec::with_capacity(12);
+                ret.push(5);
+                ret.extend_from_slice(b"SASS");
+                ret.extend_from_slice(util::encode_scale_compact_usize(65).as_ref());
+                (ret, either::Right(&seal[..]))
+            }
             DigestItemRef::GrandpaConsensus(ref gp_consensus) => {
                 let encoded =
                     gp_consensus
@@ -1178,6 +1402,20 @@ impl<'a> DigestItemRef<'a> {
                 ret.extend_from_slice(util::encode_scale_compact_usize(encoded.len()).as_ref());
                 (ret, either::Left(encoded))
             }
+            DigestItemRef::BeefyConsensus(ref beefy_consensus) => {
+                let encoded = beefy_consensus
+                    .scale_encoding()
+                    .fold(Vec::new(), |mut a, b| {
+                        a.extend_from_slice(b.as_ref());
+                        a
+                    });
+
+                let mut ret = Vec::with_capacity(12);
+                ret.push(4);
+                ret.extend_from_slice(b"BEEF");
+                ret.extend_from_slice(util::encode_scale_compact_usize(encoded.len()).as_ref());
+                (ret, either::Left(encoded))
+            }
             DigestItemRef::BabeSeal(seal) => {
                 let mut ret = Vec::with_capacity(12);
                 ret.push(5);
@@ -1228,7 +1466,11 @@ impl<'a> From<&'a DigestItem> for DigestItemRef<'a> {
             DigestItem::BabePreDigest(v) => DigestItemRef::BabePreDigest(v.into()),
             DigestItem::BabeConsensus(v) => DigestItemRef::BabeConsensus(v.into()),
             DigestItem::BabeSeal(v) => DigestItemRef::BabeSeal(v),
+            DigestItem::SassafrasPreDigest(v) => DigestItemRef::SassafrasPreDigest(v.into()),
+            DigestItem::SassafrasConsensus(v) => DigestItemRef::SassafrasConsensus(v.into()),
+            DigestItem::SassafrasSeal(v) => DigestItemRef::SassafrasSeal(v),
             DigestItem::GrandpaConsensus(v) => DigestItemRef::GrandpaConsensus(v.into()),
+            DigestItem::BeefyConsensus(v) => DigestItemRef::BeefyConsensus(v.into()),
             DigestItem::UnknownConsensus { engine, opaque } => DigestItemRef::UnknownConsensus {
                 engine: *engine,
                 opaque,
@@ -1260,8 +1502,15 @@ pub enum DigestItem {
     /// Block signature made using the BABE consensus engine.
     BabeSeal([u8; 64]),
 
+    SassafrasPreDigest(SassafrasPreDigest),
+    SassafrasConsensus(SassafrasConsensusLog),
+    /// Block signature made using the Sassafras consensus engine.
+    SassafrasSeal([u8; 65]),
+
     GrandpaConsensus(GrandpaConsensusLog),
 
+    BeefyConsensus(BeefyConsensusLog),
+
     /// See [`DigestItemRef::UnknownConsensus`].
     UnknownConsensus {
         /// Name of the consensus engine.
@@ -1309,7 +1558,11 @@ impl<'a> From<DigestItemRef<'a>> for DigestItem {
                 seal.copy_from_slice(v);
                 DigestItem::BabeSeal(seal)
             }
+            DigestItemRef::SassafrasPreDigest(v) => DigestItem::SassafrasPreDigest(v.into()),
+            DigestItemRef::SassafrasConsensus(v) => DigestItem::SassafrasConsensus(v.into()),
+            DigestItemRef::SassafrasSeal(v) => DigestItem::SassafrasSeal(*v),
             DigestItemRef::GrandpaConsensus(v) => DigestItem::GrandpaConsensus(v.into()),
+            DigestItemRef::BeefyConsensus(v) => DigestItem::BeefyConsensus(v.into()),
             DigestItemRef::UnknownConsensus { engine, opaque } => DigestItem::UnknownConsensus {
                 opaque: opaque.to_vec(),
                 engine,
@@ -1397,6 +1650,10 @@ fn decode_item_from_parts<'a>(
             content,
             block_number_bytes,
         )?),
+        (4, b"SASS") => {
+            DigestItemRef::SassafrasConsensus(SassafrasConsensusLogRef::from_slice(content)?)
+        }
+        (4, b"BEEF") => DigestItemRef::BeefyConsensus(BeefyConsensusLogRef::from_slice(content)?),
         (4, engine) => DigestItemRef::UnknownConsensus {
             engine: *engine,
             opaque: content,
@@ -1408,6 +1665,9 @@ fn decode_item_from_parts<'a>(
         (5, b"BABE") => DigestItemRef::BabeSeal({
             TryFrom::try_from(content).map_err(|_| Error::BadBabeSealLength)?
         }),
+        (5, b"SASS") => DigestItemRef::SassafrasSeal({
+            TryFrom::try_from(content).map_err(|_| Error::BadSassafrasSealLength)?
+        }),
         (5, engine) => DigestItemRef::UnknownSeal {
             engine: *engine,
             opaque: content,
@@ -1415,6 +1675,9 @@ fn decode_item_from_parts<'a>(
         // 6 = PreRuntime
         (6, b"aura") => DigestItemRef::AuraPreDigest(AuraPreDigest::from_slice(content)?),
         (6, b"BABE") => DigestItemRef::BabePreDigest(BabePreDigestRef::from_slice(content)?),
+        (6, b"SASS") => {
+            DigestItemRef::SassafrasPreDigest(SassafrasPreDigestRef::from_slice(content)?)
+        }
         (6, engine) => DigestItemRef::UnknownPreRuntime {
             engine: *engine,
             opaque: content,
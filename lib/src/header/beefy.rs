@@ -0,0 +1,288 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::Error;
+use crate::util;
+
+use alloc::vec::Vec;
+use core::{cmp, fmt, iter, slice};
+
+/// A consensus log item for BEEFY.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BeefyConsensusLogRef<'a> {
+    /// The validator set has changed. The BEEFY commitments of this block and its descendants
+    /// must be signed by this new set.
+    AuthoritiesChange(BeefyValidatorSetRef<'a>),
+
+    /// Note that the validator with given index is disabled until the next change.
+    OnDisabled(u32),
+
+    /// Root of the Merkle Mountain Range of the chain after this block has been executed.
+    MmrRoot(&'a [u8; 32]),
+}
+
+impl<'a> BeefyConsensusLogRef<'a> {
+    /// Decodes a [`BeefyConsensusLogRef`] from a slice of bytes.
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        Ok(
+            nom::combinator::all_consuming(beefy_consensus_log_ref)(slice)
+                .map_err(|_: nom::Err<(&[u8], nom::error::ErrorKind)>| {
+                    Error::BeefyConsensusLogDecodeError
+                })?
+                .1,
+        )
+    }
+
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(
+        &self,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        let index = iter::once(match self {
+            BeefyConsensusLogRef::AuthoritiesChange(_) => [1],
+            BeefyConsensusLogRef::OnDisabled(_) => [2],
+            BeefyConsensusLogRef::MmrRoot(_) => [3],
+        });
+
+        let body = match self {
+            BeefyConsensusLogRef::AuthoritiesChange(set) => {
+                either::Left(set.scale_encoding().map(either::Left))
+            }
+            BeefyConsensusLogRef::OnDisabled(n) => {
+                either::Right(iter::once(either::Right(either::Left(n.to_le_bytes()))))
+            }
+            BeefyConsensusLogRef::MmrRoot(root) => {
+                either::Right(iter::once(either::Right(either::Right(&root[..]))))
+            }
+        };
+
+        index.map(either::Left).chain(body.map(either::Right))
+    }
+}
+
+impl<'a> From<&'a BeefyConsensusLog> for BeefyConsensusLogRef<'a> {
+    fn from(a: &'a BeefyConsensusLog) -> Self {
+        match a {
+            BeefyConsensusLog::AuthoritiesChange(v) => {
+                BeefyConsensusLogRef::AuthoritiesChange(v.into())
+            }
+            BeefyConsensusLog::OnDisabled(v) => BeefyConsensusLogRef::OnDisabled(*v),
+            BeefyConsensusLog::MmrRoot(v) => BeefyConsensusLogRef::MmrRoot(v),
+        }
+    }
+}
+
+/// A consensus log item for BEEFY.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BeefyConsensusLog {
+    /// The validator set has changed. The BEEFY commitments of this block and its descendants
+    /// must be signed by this new set.
+    AuthoritiesChange(BeefyValidatorSet),
+
+    /// Note that the validator with given index is disabled until the next change.
+    OnDisabled(u32),
+
+    /// Root of the Merkle Mountain Range of the chain after this block has been executed.
+    MmrRoot([u8; 32]),
+}
+
+impl<'a> From<BeefyConsensusLogRef<'a>> for BeefyConsensusLog {
+    fn from(a: BeefyConsensusLogRef<'a>) -> Self {
+        match a {
+            BeefyConsensusLogRef::AuthoritiesChange(v) => {
+                BeefyConsensusLog::AuthoritiesChange(v.into())
+            }
+            BeefyConsensusLogRef::OnDisabled(v) => BeefyConsensusLog::OnDisabled(v),
+            BeefyConsensusLogRef::MmrRoot(v) => BeefyConsensusLog::MmrRoot(*v),
+        }
+    }
+}
+
+/// Set of validators that are allowed to sign BEEFY commitments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeefyValidatorSetRef<'a> {
+    /// Compressed ECDSA public keys of the validators of the set.
+    pub validators: BeefyValidatorsIter<'a>,
+    /// Identifier of the set. Increased by one at each change.
+    pub id: u64,
+}
+
+impl<'a> BeefyValidatorSetRef<'a> {
+    /// Decodes a SCALE-encoded [`BeefyValidatorSetRef`].
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        Ok(
+            nom::combinator::all_consuming(beefy_validator_set_ref)(slice)
+                .map_err(|_: nom::Err<(&[u8], nom::error::ErrorKind)>| {
+                    Error::BeefyConsensusLogDecodeError
+                })?
+                .1,
+        )
+    }
+
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(
+        &self,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        let header = util::encode_scale_compact_usize(self.validators.len());
+        iter::once(either::Left(either::Left(header)))
+            .chain(self.validators.clone().map(|v| either::Right(&v[..])))
+            .chain(iter::once(either::Left(either::Right(
+                self.id.to_le_bytes(),
+            ))))
+    }
+}
+
+impl<'a> From<&'a BeefyValidatorSet> for BeefyValidatorSetRef<'a> {
+    fn from(set: &'a BeefyValidatorSet) -> Self {
+        BeefyValidatorSetRef {
+            validators: BeefyValidatorsIter(BeefyValidatorsIterInner::Decoded(
+                set.validators.iter(),
+            )),
+            id: set.id,
+        }
+    }
+}
+
+/// Set of validators that are allowed to sign BEEFY commitments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeefyValidatorSet {
+    /// Compressed ECDSA public keys of the validators of the set.
+    pub validators: Vec<[u8; 33]>,
+    /// Identifier of the set. Increased by one at each change.
+    pub id: u64,
+}
+
+impl<'a> From<BeefyValidatorSetRef<'a>> for BeefyValidatorSet {
+    fn from(set: BeefyValidatorSetRef<'a>) -> Self {
+        BeefyValidatorSet {
+            validators: set.validators.copied().collect(),
+            id: set.id,
+        }
+    }
+}
+
+/// List of validators in a BEEFY context.
+#[derive(Clone)]
+pub struct BeefyValidatorsIter<'a>(BeefyValidatorsIterInner<'a>);
+
+#[derive(Clone)]
+enum BeefyValidatorsIterInner<'a> {
+    Encoded(slice::Chunks<'a, u8>),
+    Decoded(slice::Iter<'a, [u8; 33]>),
+}
+
+impl<'a> BeefyValidatorsIter<'a> {
+    /// Returns an iterator corresponding to the given slice.
+    pub fn new(slice: &'a [[u8; 33]]) -> Self {
+        BeefyValidatorsIter(BeefyValidatorsIterInner::Decoded(slice.iter()))
+    }
+}
+
+impl<'a> Iterator for BeefyValidatorsIter<'a> {
+    type Item = &'a [u8; 33];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            BeefyValidatorsIterInner::Decoded(inner) => inner.next(),
+            BeefyValidatorsIterInner::Encoded(inner) => {
+                Some(<&[u8; 33]>::try_from(inner.next()?).unwrap())
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            BeefyValidatorsIterInner::Encoded(inner) => inner.size_hint(),
+            BeefyValidatorsIterInner::Decoded(inner) => inner.size_hint(),
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for BeefyValidatorsIter<'a> {}
+
+impl<'a> cmp::PartialEq<BeefyValidatorsIter<'a>> for BeefyValidatorsIter<'a> {
+    fn eq(&self, other: &BeefyValidatorsIter<'a>) -> bool {
+        self.clone().eq(other.clone())
+    }
+}
+
+impl<'a> cmp::Eq for BeefyValidatorsIter<'a> {}
+
+impl<'a> fmt::Debug for BeefyValidatorsIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.clone().map(hex::encode))
+            .finish()
+    }
+}
+
+fn beefy_consensus_log_ref<
+    'a,
+    E: nom::error::ParseError<&'a [u8]> + nom::error::ContextError<&'a [u8]>,
+>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], BeefyConsensusLogRef<'a>, E> {
+    nom::error::context(
+        "beefy_consensus_log_ref",
+        nom::branch::alt((
+            nom::combinator::map(
+                nom::sequence::preceded(nom::bytes::streaming::tag(&[1]), beefy_validator_set_ref),
+                BeefyConsensusLogRef::AuthoritiesChange,
+            ),
+            nom::combinator::map(
+                nom::sequence::preceded(
+                    nom::bytes::streaming::tag(&[2]),
+                    nom::number::streaming::le_u32,
+                ),
+                BeefyConsensusLogRef::OnDisabled,
+            ),
+            nom::combinator::map(
+                nom::sequence::preceded(
+                    nom::bytes::streaming::tag(&[3]),
+                    nom::bytes::streaming::take(32u32),
+                ),
+                |root| BeefyConsensusLogRef::MmrRoot(TryFrom::try_from(root).unwrap()),
+            ),
+        )),
+    )(bytes)
+}
+
+fn beefy_validator_set_ref<
+    'a,
+    E: nom::error::ParseError<&'a [u8]> + nom::error::ContextError<&'a [u8]>,
+>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], BeefyValidatorSetRef<'a>, E> {
+    nom::error::context(
+        "beefy_validator_set_ref",
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_validators| {
+                    nom::combinator::map(
+                        nom::bytes::streaming::take(num_validators.saturating_mul(33)),
+                        |bytes: &'a [u8]| {
+                            BeefyValidatorsIter(BeefyValidatorsIterInner::Encoded(bytes.chunks(33)))
+                        },
+                    )
+                }),
+                nom::number::streaming::le_u64,
+            )),
+            |(validators, id)| BeefyValidatorSetRef { validators, id },
+        ),
+    )(bytes)
+}
@@ -0,0 +1,452 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+use super::Error;
+use crate::util;
+
+use alloc::vec::Vec;
+use core::{cmp, fmt, iter, slice};
+
+/// A consensus log item for Sassafras.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SassafrasConsensusLogRef<'a> {
+    /// The epoch has changed. This provides information about the _next_ epoch. Information
+    /// about the _current_ epoch (i.e. the one we've just entered) should already be available
+    /// earlier in the chain.
+    NextEpochData(SassafrasNextEpochRef<'a>),
+    /// Disable the authority with given index.
+    OnDisabled(u32),
+}
+
+impl<'a> SassafrasConsensusLogRef<'a> {
+    /// Decodes a [`SassafrasConsensusLogRef`] from a slice of bytes.
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        Ok(
+            nom::combinator::all_consuming(sassafras_consensus_log_ref)(slice)
+                .map_err(|_: nom::Err<(&[u8], nom::error::ErrorKind)>| {
+                    Error::SassafrasConsensusLogDecodeError
+                })?
+                .1,
+        )
+    }
+
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(
+        &self,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        let index = iter::once(match self {
+            SassafrasConsensusLogRef::NextEpochData(_) => [1],
+            SassafrasConsensusLogRef::OnDisabled(_) => [2],
+        });
+
+        let body = match self {
+            SassafrasConsensusLogRef::NextEpochData(epoch) => {
+                either::Left(epoch.scale_encoding().map(either::Left))
+            }
+            SassafrasConsensusLogRef::OnDisabled(n) => {
+                either::Right(iter::once(either::Right(n.to_le_bytes())))
+            }
+        };
+
+        index.map(either::Left).chain(body.map(either::Right))
+    }
+}
+
+impl<'a> From<&'a SassafrasConsensusLog> for SassafrasConsensusLogRef<'a> {
+    fn from(a: &'a SassafrasConsensusLog) -> Self {
+        match a {
+            SassafrasConsensusLog::NextEpochData(v) => {
+                SassafrasConsensusLogRef::NextEpochData(v.into())
+            }
+            SassafrasConsensusLog::OnDisabled(v) => SassafrasConsensusLogRef::OnDisabled(*v),
+        }
+    }
+}
+
+/// A consensus log item for Sassafras.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SassafrasConsensusLog {
+    /// See [`SassafrasConsensusLogRef::NextEpochData`].
+    NextEpochData(SassafrasNextEpoch),
+    /// Disable the authority with given index.
+    OnDisabled(u32),
+}
+
+impl<'a> From<SassafrasConsensusLogRef<'a>> for SassafrasConsensusLog {
+    fn from(a: SassafrasConsensusLogRef<'a>) -> Self {
+        match a {
+            SassafrasConsensusLogRef::NextEpochData(v) => {
+                SassafrasConsensusLog::NextEpochData(v.into())
+            }
+            SassafrasConsensusLogRef::OnDisabled(v) => SassafrasConsensusLog::OnDisabled(v),
+        }
+    }
+}
+
+/// Information about the next epoch. This is broadcast in the first block of the epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SassafrasNextEpochRef<'a> {
+    /// The value of randomness to use for the tickets of the epoch.
+    pub randomness: &'a [u8; 32],
+
+    /// Bandersnatch public keys of the authorities of the epoch.
+    pub authorities: SassafrasAuthoritiesIter<'a>,
+
+    /// New tickets configuration, if it changes starting from this epoch.
+    pub config: Option<SassafrasEpochConfiguration>,
+}
+
+impl<'a> SassafrasNextEpochRef<'a> {
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(
+        &self,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        let config = match self.config {
+            Some(config) => {
+                let mut out = Vec::with_capacity(9);
+                out.push(1);
+                out.extend_from_slice(&config.redundancy_factor.to_le_bytes());
+                out.extend_from_slice(&config.attempts_number.to_le_bytes());
+                out
+            }
+            None => alloc::vec![0],
+        };
+
+        iter::once(either::Right(&self.randomness[..]))
+            .chain(iter::once(either::Left(either::Left(
+                util::encode_scale_compact_usize(self.authorities.len()),
+            ))))
+            .chain(self.authorities.clone().map(|a| either::Right(&a[..])))
+            .chain(iter::once(either::Left(either::Right(config))))
+    }
+}
+
+impl<'a> From<&'a SassafrasNextEpoch> for SassafrasNextEpochRef<'a> {
+    fn from(a: &'a SassafrasNextEpoch) -> Self {
+        SassafrasNextEpochRef {
+            randomness: &a.randomness,
+            authorities: SassafrasAuthoritiesIter::from_slice(&a.authorities),
+            config: a.config,
+        }
+    }
+}
+
+/// Information about the next epoch. This is broadcast in the first block of the epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SassafrasNextEpoch {
+    /// The value of randomness to use for the tickets of the epoch.
+    pub randomness: [u8; 32],
+
+    /// Bandersnatch public keys of the authorities of the epoch.
+    pub authorities: Vec<[u8; 32]>,
+
+    /// New tickets configuration, if it changes starting from this epoch.
+    pub config: Option<SassafrasEpochConfiguration>,
+}
+
+impl<'a> From<SassafrasNextEpochRef<'a>> for SassafrasNextEpoch {
+    fn from(a: SassafrasNextEpochRef<'a>) -> Self {
+        SassafrasNextEpoch {
+            randomness: *a.randomness,
+            authorities: a.authorities.copied().collect(),
+            config: a.config,
+        }
+    }
+}
+
+/// Configuration of the tickets of a Sassafras epoch.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SassafrasEpochConfiguration {
+    /// Number of tickets that are expected to be submitted for each slot of the epoch.
+    pub redundancy_factor: u32,
+    /// Maximum number of tickets that each authority is allowed to generate.
+    pub attempts_number: u32,
+}
+
+/// List of authorities in a Sassafras context.
+#[derive(Clone)]
+pub struct SassafrasAuthoritiesIter<'a>(SassafrasAuthoritiesIterInner<'a>);
+
+#[derive(Clone)]
+enum SassafrasAuthoritiesIterInner<'a> {
+    Encoded(slice::Chunks<'a, u8>),
+    Decoded(slice::Iter<'a, [u8; 32]>),
+}
+
+impl<'a> SassafrasAuthoritiesIter<'a> {
+    /// Builds a new [`SassafrasAuthoritiesIter`] iterating over the given slice.
+    pub fn from_slice(slice: &'a [[u8; 32]]) -> Self {
+        SassafrasAuthoritiesIter(SassafrasAuthoritiesIterInner::Decoded(slice.iter()))
+    }
+}
+
+impl<'a> Iterator for SassafrasAuthoritiesIter<'a> {
+    type Item = &'a [u8; 32];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match &mut self.0 {
+            SassafrasAuthoritiesIterInner::Decoded(inner) => inner.next(),
+            SassafrasAuthoritiesIterInner::Encoded(inner) => {
+                Some(<&[u8; 32]>::try_from(inner.next()?).unwrap())
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match &self.0 {
+            SassafrasAuthoritiesIterInner::Encoded(inner) => inner.size_hint(),
+            SassafrasAuthoritiesIterInner::Decoded(inner) => inner.size_hint(),
+        }
+    }
+}
+
+impl<'a> ExactSizeIterator for SassafrasAuthoritiesIter<'a> {}
+
+impl<'a> cmp::PartialEq<SassafrasAuthoritiesIter<'a>> for SassafrasAuthoritiesIter<'a> {
+    fn eq(&self, other: &SassafrasAuthoritiesIter<'a>) -> bool {
+        self.clone().eq(other.clone())
+    }
+}
+
+impl<'a> cmp::Eq for SassafrasAuthoritiesIter<'a> {}
+
+impl<'a> fmt::Debug for SassafrasAuthoritiesIter<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_list()
+            .entries(self.clone().map(hex::encode))
+            .finish()
+    }
+}
+
+/// A Sassafras pre-runtime digest, also known as slot claim. Contains the information necessary
+/// to know which authority has produced the block and at which slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SassafrasPreDigestRef<'a> {
+    /// Index of the authority that has produced the block, within the list of authorities of
+    /// the epoch.
+    pub authority_index: u32,
+
+    /// Slot number the block belongs to.
+    pub slot_number: u64,
+
+    /// Output of the VRF of the slot claim, before being turned into bytes.
+    ///
+    /// Slot claims always contain exactly one VRF pre-output, corresponding to the VRF input of
+    /// the slot claim.
+    pub vrf_pre_output: &'a [u8; 32],
+
+    /// Bandersnatch signature of the VRF.
+    pub vrf_signature: &'a [u8; 65],
+
+    /// If `Some`, the slot is claimed thanks to a ticket. Contains the signature made using the
+    /// ephemeral Ed25519 key that was committed to in the ticket.
+    ///
+    /// If `None`, the slot is claimed using the fallback mechanism that assigns slots to
+    /// authorities in a round-robin fashion.
+    pub ticket_claim: Option<&'a [u8; 64]>,
+}
+
+impl<'a> SassafrasPreDigestRef<'a> {
+    /// Decodes a [`SassafrasPreDigestRef`] from a slice of bytes.
+    pub fn from_slice(slice: &'a [u8]) -> Result<Self, Error> {
+        Ok(
+            nom::combinator::all_consuming(sassafras_pre_digest_ref)(slice)
+                .map_err(|_: nom::Err<(&[u8], nom::error::ErrorKind)>| {
+                    Error::SassafrasPreDigestDecodeError
+                })?
+                .1,
+        )
+    }
+
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(
+        &self,
+    ) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone + 'a {
+        let ticket_claim = match self.ticket_claim {
+            Some(claim) => either::Right(
+                iter::once(either::Left(&[1][..])).chain(iter::once(either::Left(&claim[..]))),
+            ),
+            None => either::Left(iter::once(either::Left(&[0][..]))),
+        };
+
+        iter::once(either::Right(either::Left(
+            self.authority_index.to_le_bytes(),
+        )))
+        .chain(iter::once(either::Right(either::Right(
+            self.slot_number.to_le_bytes(),
+        ))))
+        .chain(iter::once(either::Left(&[4][..])))
+        .chain(iter::once(either::Left(&self.vrf_pre_output[..])))
+        .chain(iter::once(either::Left(&self.vrf_signature[..])))
+        .chain(ticket_claim)
+    }
+}
+
+impl<'a> From<&'a SassafrasPreDigest> for SassafrasPreDigestRef<'a> {
+    fn from(a: &'a SassafrasPreDigest) -> Self {
+        SassafrasPreDigestRef {
+            authority_index: a.authority_index,
+            slot_number: a.slot_number,
+            vrf_pre_output: &a.vrf_pre_output,
+            vrf_signature: &a.vrf_signature,
+            ticket_claim: a.ticket_claim.as_ref(),
+        }
+    }
+}
+
+/// A Sassafras pre-runtime digest, also known as slot claim.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SassafrasPreDigest {
+    /// See [`SassafrasPreDigestRef::authority_index`].
+    pub authority_index: u32,
+    /// See [`SassafrasPreDigestRef::slot_number`].
+    pub slot_number: u64,
+    /// See [`SassafrasPreDigestRef::vrf_pre_output`].
+    pub vrf_pre_output: [u8; 32],
+    /// See [`SassafrasPreDigestRef::vrf_signature`].
+    pub vrf_signature: [u8; 65],
+    /// See [`SassafrasPreDigestRef::ticket_claim`].
+    pub ticket_claim: Option<[u8; 64]>,
+}
+
+impl<'a> From<SassafrasPreDigestRef<'a>> for SassafrasPreDigest {
+    fn from(a: SassafrasPreDigestRef<'a>) -> Self {
+        SassafrasPreDigest {
+            authority_index: a.authority_index,
+            slot_number: a.slot_number,
+            vrf_pre_output: *a.vrf_pre_output,
+            vrf_signature: *a.vrf_signature,
+            ticket_claim: a.ticket_claim.copied(),
+        }
+    }
+}
+
+fn sassafras_consensus_log_ref<
+    'a,
+    E: nom::error::ParseError<&'a [u8]> + nom::error::ContextError<&'a [u8]>,
+>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], SassafrasConsensusLogRef<'a>, E> {
+    nom::error::context(
+        "sassafras_consensus_log_ref",
+        nom::branch::alt((
+            nom::combinator::map(
+                nom::sequence::preceded(nom::bytes::streaming::tag(&[1]), sassafras_next_epoch_ref),
+                SassafrasConsensusLogRef::NextEpochData,
+            ),
+            nom::combinator::map(
+                nom::sequence::preceded(
+                    nom::bytes::streaming::tag(&[2]),
+                    nom::number::streaming::le_u32,
+                ),
+                SassafrasConsensusLogRef::OnDisabled,
+            ),
+        )),
+    )(bytes)
+}
+
+fn sassafras_next_epoch_ref<
+    'a,
+    E: nom::error::ParseError<&'a [u8]> + nom::error::ContextError<&'a [u8]>,
+>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], SassafrasNextEpochRef<'a>, E> {
+    nom::error::context(
+        "sassafras_next_epoch_ref",
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::bytes::streaming::take(32u32),
+                nom::combinator::flat_map(util::nom_scale_compact_usize, |num_authorities| {
+                    nom::combinator::map(
+                        nom::bytes::streaming::take(num_authorities.saturating_mul(32)),
+                        |bytes: &'a [u8]| {
+                            SassafrasAuthoritiesIter(SassafrasAuthoritiesIterInner::Encoded(
+                                bytes.chunks(32),
+                            ))
+                        },
+                    )
+                }),
+                nom::branch::alt((
+                    nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| None),
+                    nom::combinator::map(
+                        nom::sequence::preceded(
+                            nom::bytes::streaming::tag(&[1]),
+                            nom::sequence::tuple((
+                                nom::number::streaming::le_u32,
+                                nom::number::streaming::le_u32,
+                            )),
+                        ),
+                        |(redundancy_factor, attempts_number)| {
+                            Some(SassafrasEpochConfiguration {
+                                redundancy_factor,
+                                attempts_number,
+                            })
+                        },
+                    ),
+                )),
+            )),
+            |(randomness, authorities, config)| SassafrasNextEpochRef {
+                randomness: TryFrom::try_from(randomness).unwrap(),
+                authorities,
+                config,
+            },
+        ),
+    )(bytes)
+}
+
+fn sassafras_pre_digest_ref<
+    'a,
+    E: nom::error::ParseError<&'a [u8]> + nom::error::ContextError<&'a [u8]>,
+>(
+    bytes: &'a [u8],
+) -> nom::IResult<&'a [u8], SassafrasPreDigestRef<'a>, E> {
+    nom::error::context(
+        "sassafras_pre_digest_ref",
+        nom::combinator::map(
+            nom::sequence::tuple((
+                nom::number::streaming::le_u32,
+                nom::number::streaming::le_u64,
+                // Number of VRF pre-outputs, always equal to 1.
+                nom::bytes::streaming::tag(&[4]),
+                nom::bytes::streaming::take(32u32),
+                nom::bytes::streaming::take(65u32),
+                nom::branch::alt((
+                    nom::combinator::map(nom::bytes::streaming::tag(&[0]), |_| None),
+                    nom::combinator::map(
+                        nom::sequence::preceded(
+                            nom::bytes::streaming::tag(&[1]),
+                            nom::bytes::streaming::take(64u32),
+                        ),
+                        Some,
+                    ),
+                )),
+            )),
+            |(authority_index, slot_number, _, vrf_pre_output, vrf_signature, ticket_claim)| {
+                SassafrasPreDigestRef {
+                    authority_index,
+                    slot_number,
+                    vrf_pre_output: TryFrom::try_from(vrf_pre_output).unwrap(),
+                    vrf_signature: TryFrom::try_from(vrf_signature).unwrap(),
+                    ticket_claim: ticket_claim.map(|c: &[u8]| TryFrom::try_from(c).unwrap()),
+                }
+            },
+        ),
+    )(bytes)
+}
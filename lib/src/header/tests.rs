@@ -78,3 +78,69 @@ fn regression_2336() {
         4,
     );
 }
+
+#[test]
+fn beefy_authorities_change_reencode() {
+    let mut encoded = vec![4];
+    encoded.extend_from_slice(b"BEEF");
+    encoded.extend_from_slice(crate::util::encode_scale_compact_usize(1 + 1 + 2 * 33 + 8).as_ref());
+    encoded.push(1);
+    encoded.push(2 * 4);
+    encoded.extend_from_slice(&[2; 33]);
+    encoded.extend_from_slice(&[3; 33]);
+    encoded.extend_from_slice(&5u64.to_le_bytes());
+
+    let decoded = super::DigestItemRef::from_scale_encoded(&encoded, 4).unwrap();
+    match &decoded {
+        super::DigestItemRef::BeefyConsensus(super::BeefyConsensusLogRef::AuthoritiesChange(
+            set,
+        )) => {
+            assert_eq!(set.id, 5);
+            assert_eq!(
+                set.validators.clone().collect::<Vec<_>>(),
+                [&[2; 33], &[3; 33]]
+            );
+        }
+        _ => panic!(),
+    }
+
+    let reencoded = decoded.scale_encoding(4).fold(Vec::new(), |mut a, b| {
+        a.extend_from_slice(b.as_ref());
+        a
+    });
+    assert_eq!(reencoded, encoded);
+}
+
+#[test]
+fn sassafras_pre_digest_reencode() {
+    let mut encoded = vec![6];
+    encoded.extend_from_slice(b"SASS");
+    encoded.extend_from_slice(
+        crate::util::encode_scale_compact_usize(4 + 8 + 1 + 32 + 65 + 1 + 64).as_ref(),
+    );
+    encoded.extend_from_slice(&3u32.to_le_bytes());
+    encoded.extend_from_slice(&1234u64.to_le_bytes());
+    encoded.push(4);
+    encoded.extend_from_slice(&[7; 32]);
+    encoded.extend_from_slice(&[8; 65]);
+    encoded.push(1);
+    encoded.extend_from_slice(&[9; 64]);
+
+    let decoded = super::DigestItemRef::from_scale_encoded(&encoded, 4).unwrap();
+    match &decoded {
+        super::DigestItemRef::SassafrasPreDigest(pre_digest) => {
+            assert_eq!(pre_digest.authority_index, 3);
+            assert_eq!(pre_digest.slot_number, 1234);
+            assert_eq!(pre_digest.vrf_pre_output, &[7; 32]);
+            assert_eq!(pre_digest.vrf_signature, &[8; 65]);
+            assert_eq!(pre_digest.ticket_claim, Some(&[9; 64]));
+        }
+        _ => panic!(),
+    }
+
+    let reencoded = decoded.scale_encoding(4).fold(Vec::new(), |mut a, b| {
+        a.extend_from_slice(b.as_ref());
+        a
+    });
+    assert_eq!(reencoded, encoded);
+}
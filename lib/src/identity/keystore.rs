@@ -56,6 +56,8 @@ pub enum KeyNamespace {
     Aura,
     AuthorityDiscovery,
     Babe,
+    /// Key of a parachain collator, used to sign the collations sent to the relay chain.
+    Collator,
     Grandpa,
     ImOnline,
     // TODO: there exists other variants in Substrate but it's unclear whether they're in use (see https://github.com/paritytech/substrate/blob/cafe12e7785bf92e5dc04780c10e7f8330a15a4c/primitives/core/src/crypto.rs)
@@ -68,6 +70,7 @@ impl KeyNamespace {
             KeyNamespace::Aura,
             KeyNamespace::AuthorityDiscovery,
             KeyNamespace::Babe,
+            KeyNamespace::Collator,
             KeyNamespace::Grandpa,
             KeyNamespace::ImOnline,
         ]
@@ -79,6 +82,7 @@ impl KeyNamespace {
             "aura" => Some(KeyNamespace::Aura),
             "audi" => Some(KeyNamespace::AuthorityDiscovery),
             "babe" => Some(KeyNamespace::Babe),
+            "coll" => Some(KeyNamespace::Collator),
             "gran" => Some(KeyNamespace::Grandpa),
             "imon" => Some(KeyNamespace::ImOnline),
             _ => None,
@@ -90,6 +94,7 @@ impl KeyNamespace {
             KeyNamespace::Aura => "aura",
             KeyNamespace::AuthorityDiscovery => "audi",
             KeyNamespace::Babe => "babe",
+            KeyNamespace::Collator => "coll",
             KeyNamespace::Grandpa => "gran",
             KeyNamespace::ImOnline => "imon",
         }
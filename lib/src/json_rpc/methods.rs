@@ -23,6 +23,7 @@ use crate::{header, identity::ss58};
 use alloc::{
     borrow::Cow,
     boxed::Box,
+    collections::BTreeMap,
     format,
     string::{String, ToString as _},
     vec,
@@ -396,6 +397,8 @@ define_methods! {
     author_submitExtrinsic(transaction: HexString) -> HashHexString,
     author_unwatchExtrinsic(subscription: Cow<'a, str>) -> bool,
     babe_epochAuthorship() -> (), // TODO:
+    beefy_subscribeJustifications() -> Cow<'a, str>,
+    beefy_unsubscribeJustifications(subscription: Cow<'a, str>) -> bool,
     chain_getBlock(hash: Option<HashHexString>) -> Block,
     chain_getBlockHash(height: Option<u64>) -> HashHexString [chain_getHead],
     chain_getFinalizedHead() -> HashHexString [chain_getFinalisedHead],
@@ -430,10 +433,11 @@ define_methods! {
     state_queryStorageAt(keys: Vec<HexString>, at: Option<HashHexString>) -> Vec<StorageChangeSet>, // TODO:
     state_subscribeRuntimeVersion() -> Cow<'a, str> [chain_subscribeRuntimeVersion],
     state_subscribeStorage(list: Vec<HexString>) -> Cow<'a, str>,
+    state_traceBlock(block: HashHexString, targets: Option<Cow<'a, str>>, storage_keys: Option<Cow<'a, str>>, methods: Option<Cow<'a, str>>) -> TraceBlockResponse,
     state_unsubscribeRuntimeVersion(subscription: Cow<'a, str>) -> bool [chain_unsubscribeRuntimeVersion],
     state_unsubscribeStorage(subscription: Cow<'a, str>) -> bool,
     system_accountNextIndex(account: AccountId) -> u64,
-    system_addReservedPeer() -> (), // TODO:
+    system_addReservedPeer(peer: Cow<'a, str>) -> (),
     system_chain() -> Cow<'a, str>,
     system_chainType() -> Cow<'a, str>,
     system_dryRun() -> () [system_dryRunAt], // TODO:
@@ -447,7 +451,7 @@ define_methods! {
     system_nodeRoles() -> Cow<'a, [NodeRole]>,
     system_peers() -> Vec<SystemPeer>,
     system_properties() -> Box<serde_json::value::RawValue>,
-    system_removeReservedPeer() -> (), // TODO:
+    system_removeReservedPeer(peer_id: Cow<'a, str>) -> (),
     /// Returns, as an opaque string, the version of the client serving these JSON-RPC requests.
     system_version() -> Cow<'a, str>,
 
@@ -507,12 +511,18 @@ define_methods! {
     network_unstable_subscribeEvents() -> Cow<'a, str>,
     network_unstable_unsubscribeEvents(subscription: Cow<'a, str>) -> (),
     chainHead_unstable_finalizedDatabase(#[rename = "maxSizeBytes"] max_size_bytes: Option<u64>) -> Cow<'a, str>,
+
+    // These functions are compatible with the "manual seal" functions of Substrate, and are only
+    // meant to be used on development chains.
+    engine_createBlock(create_empty: bool, finalize: bool, parent_hash: Option<HashHexString>) -> CreatedBlock,
+    engine_finalizeBlock(hash: HashHexString, justification: Option<HexString>) -> bool,
 }
 
 define_methods! {
     ServerToClient,
     ServerToClientResponse, // TODO: unnecessary
     author_extrinsicUpdate(subscription: Cow<'a, str>, result: TransactionStatus) -> (),
+    beefy_justifications(subscription: Cow<'a, str>, result: HexString) -> (),
     chain_finalizedHead(subscription: Cow<'a, str>, result: Header) -> (),
     chain_newHead(subscription: Cow<'a, str>, result: Header) -> (),
     chain_allHead(subscription: Cow<'a, str>, result: Header) -> (),
@@ -1061,12 +1071,75 @@ pub enum DispatchClass {
     Mandatory,
 }
 
+/// Return value of [`MethodCall::engine_createBlock`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreatedBlock {
+    pub hash: HashHexString,
+    pub aux: CreatedBlockAux,
+}
+
+/// See [`CreatedBlock::aux`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CreatedBlockAux {
+    pub header_only: bool,
+    pub clear_justification_requests: bool,
+    pub needs_justification: bool,
+    pub bad_justification: bool,
+    pub is_new_best: bool,
+}
+
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct StorageChangeSet {
     pub block: HashHexString,
     pub changes: Vec<(HexString, Option<HexString>)>,
 }
 
+/// Return value of [`MethodCall::state_traceBlock`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub enum TraceBlockResponse {
+    #[serde(rename = "traceError")]
+    TraceError(TraceBlockError),
+    #[serde(rename = "blockTrace")]
+    BlockTrace(BlockTrace),
+}
+
+/// See [`TraceBlockResponse::TraceError`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraceBlockError {
+    pub error: String,
+}
+
+/// See [`TraceBlockResponse::BlockTrace`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct BlockTrace {
+    #[serde(rename = "blockHash")]
+    pub block_hash: HashHexString,
+    #[serde(rename = "parentHash")]
+    pub parent_hash: HashHexString,
+    #[serde(rename = "tracingTargets")]
+    pub tracing_targets: String,
+    #[serde(rename = "storageKeys")]
+    pub storage_keys: String,
+    pub methods: String,
+    /// List of events, in the order in which they happened during the execution of the block.
+    pub events: Vec<TraceBlockEvent>,
+}
+
+/// See [`BlockTrace::events`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraceBlockEvent {
+    /// Category of the event. For example `state` for storage accesses.
+    pub target: String,
+    pub data: TraceBlockEventData,
+}
+
+/// See [`TraceBlockEvent::data`].
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TraceBlockEventData {
+    #[serde(rename = "stringValues")]
+    pub string_values: BTreeMap<String, String>,
+}
+
 #[derive(Debug, Clone)]
 pub struct SystemHealth {
     pub is_syncing: bool,
@@ -421,6 +421,7 @@ impl ClientMainTask {
                 | methods::MethodCall::state_getStorageSize { .. }
                 | methods::MethodCall::state_queryStorage { .. }
                 | methods::MethodCall::state_queryStorageAt { .. }
+                | methods::MethodCall::state_traceBlock { .. }
                 | methods::MethodCall::system_accountNextIndex { .. }
                 | methods::MethodCall::system_addReservedPeer { .. }
                 | methods::MethodCall::system_chain { .. }
@@ -449,7 +450,9 @@ impl ClientMainTask {
                 | methods::MethodCall::chainHead_unstable_header { .. }
                 | methods::MethodCall::chainHead_unstable_stopOperation { .. }
                 | methods::MethodCall::chainHead_unstable_storage { .. }
-                | methods::MethodCall::chainHead_unstable_unpin { .. } => {
+                | methods::MethodCall::chainHead_unstable_unpin { .. }
+                | methods::MethodCall::engine_createBlock { .. }
+                | methods::MethodCall::engine_finalizeBlock { .. } => {
                     // Simple one-request-one-response.
                     return Event::HandleRequest {
                         request_process: RequestProcess {
@@ -465,6 +468,7 @@ impl ClientMainTask {
                 }
 
                 methods::MethodCall::author_submitAndWatchExtrinsic { .. }
+                | methods::MethodCall::beefy_subscribeJustifications { .. }
                 | methods::MethodCall::chain_subscribeAllHeads { .. }
                 | methods::MethodCall::chain_subscribeFinalizedHeads { .. }
                 | methods::MethodCall::chain_subscribeNewHeads { .. }
@@ -538,6 +542,7 @@ impl ClientMainTask {
                 }
 
                 methods::MethodCall::author_unwatchExtrinsic { subscription, .. }
+                | methods::MethodCall::beefy_unsubscribeJustifications { subscription, .. }
                 | methods::MethodCall::state_unsubscribeRuntimeVersion { subscription, .. }
                 | methods::MethodCall::state_unsubscribeStorage { subscription, .. }
                 | methods::MethodCall::transaction_unstable_unwatch { subscription, .. }
@@ -559,6 +564,9 @@ impl ClientMainTask {
                                     methods::MethodCall::author_unwatchExtrinsic { .. } => {
                                         methods::Response::author_unwatchExtrinsic(true)
                                     }
+                                    methods::MethodCall::beefy_unsubscribeJustifications {
+                                        ..
+                                    } => methods::Response::beefy_unsubscribeJustifications(true),
                                     methods::MethodCall::state_unsubscribeRuntimeVersion {
                                         ..
                                     } => methods::Response::state_unsubscribeRuntimeVersion(true),
@@ -588,6 +596,10 @@ impl ClientMainTask {
                                     methods::Response::author_unwatchExtrinsic(false)
                                         .to_json_response(request_id)
                                 }
+                                methods::MethodCall::beefy_unsubscribeJustifications { .. } => {
+                                    methods::Response::beefy_unsubscribeJustifications(false)
+                                        .to_json_response(request_id)
+                                }
                                 methods::MethodCall::state_unsubscribeRuntimeVersion { .. } => {
                                     methods::Response::state_unsubscribeRuntimeVersion(false)
                                         .to_json_response(request_id)
@@ -1151,6 +1163,11 @@ impl SubscriptionStartProcess {
             methods::MethodCall::chain_subscribeNewHeads { .. } => {
                 methods::Response::chain_subscribeNewHeads(Cow::Borrowed(&self.subscription_id))
             }
+            methods::MethodCall::beefy_subscribeJustifications { .. } => {
+                methods::Response::beefy_subscribeJustifications(Cow::Borrowed(
+                    &self.subscription_id,
+                ))
+            }
             methods::MethodCall::state_subscribeRuntimeVersion { .. } => {
                 methods::Response::state_subscribeRuntimeVersion(Cow::Borrowed(
                     &self.subscription_id,
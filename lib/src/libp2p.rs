@@ -88,6 +88,7 @@
 
 pub mod collection;
 pub mod connection;
+pub mod memory;
 pub mod multiaddr;
 pub mod multihash;
 pub mod peer_id;
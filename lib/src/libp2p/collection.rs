@@ -583,6 +583,24 @@ where
         }
     }
 
+    /// Returns `true` if the given substream corresponds to a request that has been started with
+    /// [`Network::start_request`] on the given connection and whose [`Event::Response`] hasn't
+    /// been yielded yet.
+    pub fn is_pending_outgoing_request(
+        &self,
+        connection_id: ConnectionId,
+        substream_id: SubstreamId,
+    ) -> bool {
+        self.outgoing_requests
+            .contains(&(connection_id, substream_id))
+    }
+
+    /// Returns `true` if the given substream corresponds to a request that has been yielded
+    /// with an [`Event::RequestIn`] and that hasn't been answered or cancelled yet.
+    pub fn is_pending_ingoing_request(&self, substream_id: SubstreamId) -> bool {
+        self.ingoing_requests.contains_key(&substream_id)
+    }
+
     /// Call after an [`Event::InboundNegotiated`] has been emitted in order to accept the protocol
     /// name and indicate the type of the protocol.
     ///
@@ -807,6 +825,39 @@ where
         ));
     }
 
+    /// Signals to the remote that an inbound notifications substream that was accepted using
+    /// [`Network::accept_in_notifications`] should be closed.
+    ///
+    /// Contrary to outbound substreams, it is not possible to forcefully close an inbound
+    /// substream. Notifications can continue to be received until the remote closes the
+    /// substream, at which point an [`Event::NotificationsInClose`] is generated as usual. The
+    /// [`SubstreamId`] remains valid until then.
+    ///
+    /// This function generates a message destined to the connection. Use
+    /// [`Network::pull_message_to_connection`] to process these messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] doesn't correspond to an accepted inbound notifications
+    /// substream.
+    ///
+    #[track_caller]
+    pub fn start_close_in_notifications(&mut self, substream_id: SubstreamId) {
+        let (connection_id, state, inner_substream_id) =
+            match self.ingoing_notification_substreams.get(&substream_id) {
+                Some(s) => s,
+                None => panic!(),
+            };
+        assert!(matches!(state, SubstreamState::Open));
+
+        self.messages_to_connections.push_back((
+            *connection_id,
+            CoordinatorToConnectionInner::CloseInNotifications {
+                substream_id: *inner_substream_id,
+            },
+        ));
+    }
+
     /// Adds a notification to the queue of notifications to send to the given peer.
     ///
     /// It is invalid to call this on a [`SubstreamId`] before a successful
@@ -1838,6 +1889,9 @@ enum CoordinatorToConnectionInner {
     RejectInNotifications {
         substream_id: established::SubstreamId,
     },
+    CloseInNotifications {
+        substream_id: established::SubstreamId,
+    },
 
     /// Answer an incoming request.
     ///
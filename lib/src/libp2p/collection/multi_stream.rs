@@ -537,6 +537,14 @@ where
                     );
                 }
             }
+            (
+                CoordinatorToConnectionInner::CloseInNotifications { substream_id },
+                MultiStreamConnectionTaskInner::Established { established, .. },
+            ) => {
+                // The remote might have closed the substream while the message was being
+                // delivered, in which case this does nothing.
+                established.close_in_notifications_substream(substream_id);
+            }
             (
                 CoordinatorToConnectionInner::RejectInNotifications { substream_id },
                 MultiStreamConnectionTaskInner::Established {
@@ -571,6 +579,7 @@ where
                 | CoordinatorToConnectionInner::RejectInbound { .. }
                 | CoordinatorToConnectionInner::AcceptInNotifications { .. }
                 | CoordinatorToConnectionInner::RejectInNotifications { .. }
+                | CoordinatorToConnectionInner::CloseInNotifications { .. }
                 | CoordinatorToConnectionInner::StartRequest { .. }
                 | CoordinatorToConnectionInner::AnswerRequest { .. }
                 | CoordinatorToConnectionInner::OpenOutNotifications { .. }
@@ -584,6 +593,7 @@ where
                 | CoordinatorToConnectionInner::RejectInbound { .. }
                 | CoordinatorToConnectionInner::AcceptInNotifications { .. }
                 | CoordinatorToConnectionInner::RejectInNotifications { .. }
+                | CoordinatorToConnectionInner::CloseInNotifications { .. }
                 | CoordinatorToConnectionInner::StartRequest { .. }
                 | CoordinatorToConnectionInner::AnswerRequest { .. }
                 | CoordinatorToConnectionInner::OpenOutNotifications { .. }
@@ -375,6 +375,14 @@ where
                     );
                 }
             }
+            (
+                CoordinatorToConnectionInner::CloseInNotifications { substream_id },
+                SingleStreamConnectionTaskInner::Established { established, .. },
+            ) => {
+                // The remote might have closed the substream while the message was being
+                // delivered, in which case this does nothing.
+                established.close_in_notifications_substream(substream_id);
+            }
             (
                 CoordinatorToConnectionInner::RejectInNotifications { substream_id },
                 SingleStreamConnectionTaskInner::Established {
@@ -411,6 +419,7 @@ where
                 | CoordinatorToConnectionInner::RejectInbound { .. }
                 | CoordinatorToConnectionInner::AcceptInNotifications { .. }
                 | CoordinatorToConnectionInner::RejectInNotifications { .. }
+                | CoordinatorToConnectionInner::CloseInNotifications { .. }
                 | CoordinatorToConnectionInner::StartRequest { .. }
                 | CoordinatorToConnectionInner::AnswerRequest { .. }
                 | CoordinatorToConnectionInner::OpenOutNotifications { .. }
@@ -424,6 +433,7 @@ where
                 | CoordinatorToConnectionInner::RejectInbound { .. }
                 | CoordinatorToConnectionInner::AcceptInNotifications { .. }
                 | CoordinatorToConnectionInner::RejectInNotifications { .. }
+                | CoordinatorToConnectionInner::CloseInNotifications { .. }
                 | CoordinatorToConnectionInner::StartRequest { .. }
                 | CoordinatorToConnectionInner::AnswerRequest { .. }
                 | CoordinatorToConnectionInner::OpenOutNotifications { .. }
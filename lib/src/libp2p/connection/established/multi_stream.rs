@@ -877,6 +877,35 @@ where
             .close_notifications_substream();
     }
 
+    /// Signals to the remote that an inbound notifications substream that was accepted using
+    /// [`MultiStream::accept_in_notifications_substream`] should be closed.
+    ///
+    /// Has no effect if the substream doesn't exist anymore or has already been closed by the
+    /// remote.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] doesn't belong to this type of connection.
+    ///
+    pub fn close_in_notifications_substream(&mut self, substream_id: SubstreamId) {
+        let substream_id = match substream_id.0 {
+            SubstreamIdInner::MultiStream(id) => id,
+            _ => panic!(),
+        };
+
+        let Some(inner_substream_id) = self.out_in_substreams_map.get(&substream_id) else {
+            return;
+        };
+
+        if let Some(substream) = self
+            .in_substreams
+            .get_mut(inner_substream_id)
+            .and_then(|s| s.inner.as_mut())
+        {
+            substream.close_in_notifications_substream();
+        }
+    }
+
     /// Responds to an incoming request. Must be called in response to a [`Event::RequestIn`].
     ///
     /// Returns an error if the [`SubstreamId`] is invalid.
@@ -240,32 +240,46 @@ where
                 // The Yamux state machine needs to process a substream.
 
                 // Temporarily extract the substream's fields to put them back later.
-                let (state_machine, mut substream_user_data) =
-                    substream_read_write.user_data_mut().take().unwrap();
-                let (state_machine_update, event) =
-                    state_machine.read_write(substream_read_write.read_write());
-
-                let event_to_yield = event.map(|ev| {
-                    Self::pass_through_substream_event(
-                        substream_read_write.substream_id(),
-                        &mut substream_user_data,
-                        ev,
-                    )
-                });
-
-                match state_machine_update {
-                    Some(s) => {
-                        *substream_read_write.user_data_mut() = Some((s, substream_user_data));
-                        self.inner.yamux = substream_read_write.finish();
-                    }
-                    None => {
-                        self.inner.yamux = substream_read_write.reset();
+                // The fields are missing if the substream state machine has finished after both
+                // sides have closed the substream, in which case the substream is only waiting
+                // for its remaining data to be flushed.
+                if let Some((state_machine, mut substream_user_data)) =
+                    substream_read_write.user_data_mut().take()
+                {
+                    let (state_machine_update, event) =
+                        state_machine.read_write(substream_read_write.read_write());
+
+                    let event_to_yield = event.map(|ev| {
+                        Self::pass_through_substream_event(
+                            substream_read_write.substream_id(),
+                            &mut substream_user_data,
+                            ev,
+                        )
+                    });
+
+                    match state_machine_update {
+                        Some(s) => {
+                            *substream_read_write.user_data_mut() = Some((s, substream_user_data));
+                            self.inner.yamux = substream_read_write.finish();
+                        }
+                        None if substream_read_write.read_write().is_dead() => {
+                            // The substream has been closed gracefully by both sides. Resetting
+                            // it would discard the data that hasn't been flushed yet, such as
+                            // the response to a request.
+                            self.inner.yamux = substream_read_write.finish();
+                        }
+                        None => {
+                            self.inner.yamux = substream_read_write.reset();
+                        }
                     }
-                }
 
-                if let Some(event_to_yield) = event_to_yield {
-                    drop(decrypted_read_write);
-                    return Ok((self, Some(event_to_yield)));
+                    if let Some(event_to_yield) = event_to_yield {
+                        drop(decrypted_read_write);
+                        return Ok((self, Some(event_to_yield)));
+                    }
+                } else {
+                    substream_read_write.read_write().discard_all_incoming();
+                    self.inner.yamux = substream_read_write.finish();
                 }
             }
             yamux::ReadWriteOutcome::StreamReset { yamux, .. } => {
@@ -700,6 +714,32 @@ where
         self.inner.yamux.mark_substream_write_ready(substream_id);
     }
 
+    /// Signals to the remote that an inbound notifications substream that was accepted using
+    /// [`SingleStream::accept_in_notifications_substream`] should be closed.
+    ///
+    /// Has no effect if the substream doesn't exist anymore or has already been closed by the
+    /// remote.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] doesn't belong to this type of connection.
+    ///
+    pub fn close_in_notifications_substream(&mut self, substream_id: SubstreamId) {
+        let substream_id = match substream_id.0 {
+            SubstreamIdInner::SingleStream(id) => id,
+            _ => panic!(),
+        };
+
+        if !self.inner.yamux.has_substream(substream_id) {
+            return;
+        }
+
+        if let Some((substream, _)) = self.inner.yamux[substream_id].as_mut() {
+            substream.close_in_notifications_substream();
+            self.inner.yamux.mark_substream_write_ready(substream_id);
+        }
+    }
+
     /// Responds to an incoming request. Must be called in response to a [`Event::RequestIn`].
     ///
     /// Passing an `Err` corresponds, on the other side, to a
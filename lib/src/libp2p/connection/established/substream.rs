@@ -1238,6 +1238,20 @@ where
         };
     }
 
+    /// Signals to the remote that an inbound notifications substream that was accepted using
+    /// [`Substream::accept_in_notifications_substream`] should be closed.
+    ///
+    /// Contrary to [`Substream::close_notifications_substream`], this function has no effect if
+    /// the substream isn't an open inbound notifications substream, for example because the
+    /// remote has closed it in the meantime.
+    ///
+    /// Notifications can continue to be received until the remote closes the substream.
+    pub fn close_in_notifications_substream(&mut self) {
+        if let SubstreamInner::NotificationsIn { close_desired, .. } = &mut self.inner {
+            *close_desired = true;
+        }
+    }
+
     /// Queues a ping on the given substream. Must be passed a randomly-generated payload of 32
     /// bytes, the time after which this ping is considered as failed.
     ///
@@ -964,6 +964,12 @@ where
                                 continue;
                             };
 
+                            // The substream might have been closed gracefully by both sides but
+                            // not removed yet. In that situation, the reset is ignored.
+                            if self.inner.dead_substreams.contains(&stream_id) {
+                                continue;
+                            }
+
                             let _was_inserted = self.inner.dead_substreams.insert(stream_id);
                             debug_assert!(_was_inserted);
 
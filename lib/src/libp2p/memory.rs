@@ -0,0 +1,376 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! In-process transport corresponding to the `/memory/<port>` multiaddresses.
+//!
+//! This module makes it possible to connect multiple nodes running within the same process
+//! without involving the operating system, which is mostly useful for testing purposes.
+//!
+//! Call [`listen`] in order to start listening on a certain port, then [`connect`] in order to
+//! connect to a port that is being listened on. Ports are shared by the entire process. The
+//! [`Stream`]s that are produced implement the `AsyncRead` and `AsyncWrite` traits, and can be
+//! wrapped around a [`super::with_buffers::WithBuffers`] like any other socket.
+
+#![cfg(feature = "std")]
+#![cfg_attr(docsrs, doc(cfg(feature = "std")))]
+
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    sync::Arc,
+};
+use core::{
+    cmp, fmt,
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+use futures_util::{AsyncRead, AsyncWrite};
+use std::{io, sync::Mutex};
+
+/// Maximum number of bytes that can be buffered in one direction of a [`Stream`] before writing
+/// blocks.
+const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// List of all the ports that are currently being listened on.
+static LISTENERS: Mutex<BTreeMap<u64, Arc<Mutex<ListenerQueue>>>> = Mutex::new(BTreeMap::new());
+
+/// Next port to try to assign to a [`listen`] call with a port of `0` and to outgoing
+/// connections.
+static NEXT_EPHEMERAL_PORT: Mutex<u64> = Mutex::new(1 << 32);
+
+/// Starts listening on the given port.
+///
+/// If `port` is `0`, an unused port is automatically picked. Use [`Listener::port`] to find out
+/// which port has been picked.
+///
+/// The port is no longer listened on once the returned [`Listener`] is destroyed.
+pub fn listen(port: u64) -> Result<Listener, ListenError> {
+    let mut listeners = LISTENERS.lock().unwrap();
+
+    let port = if port == 0 {
+        loop {
+            let port = next_ephemeral_port();
+            if !listeners.contains_key(&port) {
+                break port;
+            }
+        }
+    } else if listeners.contains_key(&port) {
+        return Err(ListenError::PortInUse);
+    } else {
+        port
+    };
+
+    let queue = Arc::new(Mutex::new(ListenerQueue {
+        pending: VecDeque::new(),
+        waker: None,
+    }));
+    listeners.insert(port, queue.clone());
+
+    Ok(Listener { port, queue })
+}
+
+/// Connects to the given port.
+///
+/// The connection is immediately added to the queue of the corresponding [`Listener`].
+pub fn connect(port: u64) -> Result<Stream, ConnectError> {
+    let queue = LISTENERS
+        .lock()
+        .unwrap()
+        .get(&port)
+        .cloned()
+        .ok_or(ConnectError::NoListener)?;
+
+    let local_to_remote = Arc::new(Mutex::new(Pipe::new()));
+    let remote_to_local = Arc::new(Mutex::new(Pipe::new()));
+
+    let local = Stream {
+        read: remote_to_local.clone(),
+        write: local_to_remote.clone(),
+        remote_port: port,
+    };
+
+    let remote = Stream {
+        read: local_to_remote,
+        write: remote_to_local,
+        remote_port: next_ephemeral_port(),
+    };
+
+    let mut queue = queue.lock().unwrap();
+    queue.pending.push_back(remote);
+    if let Some(waker) = queue.waker.take() {
+        waker.wake();
+    }
+
+    Ok(local)
+}
+
+/// Error potentially returned by [`listen`].
+#[derive(Debug, derive_more::Display, Clone, PartialEq, Eq)]
+pub enum ListenError {
+    /// The requested port is already being listened on.
+    PortInUse,
+}
+
+/// Error potentially returned by [`connect`].
+#[derive(Debug, derive_more::Display, Clone, PartialEq, Eq)]
+pub enum ConnectError {
+    /// Nothing is listening on the requested port.
+    NoListener,
+}
+
+/// Active listener. See [`listen`].
+pub struct Listener {
+    port: u64,
+    queue: Arc<Mutex<ListenerQueue>>,
+}
+
+impl Listener {
+    /// Returns the port being listened on.
+    pub fn port(&self) -> u64 {
+        self.port
+    }
+
+    /// Waits for an incoming connection.
+    pub fn accept(&self) -> impl Future<Output = Stream> + '_ {
+        core::future::poll_fn(move |cx| {
+            let mut queue = self.queue.lock().unwrap();
+            if let Some(stream) = queue.pending.pop_front() {
+                return Poll::Ready(stream);
+            }
+            queue.waker = Some(cx.waker().clone());
+            Poll::Pending
+        })
+    }
+}
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Listener").field(&self.port).finish()
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        LISTENERS.lock().unwrap().remove(&self.port);
+    }
+}
+
+struct ListenerQueue {
+    /// Connections that haven't been accepted yet.
+    pending: VecDeque<Stream>,
+    /// Waker of the latest call to [`Listener::accept`].
+    waker: Option<Waker>,
+}
+
+/// Bidirectional stream of data with another [`Stream`] of the same process.
+///
+/// Destroying the [`Stream`] closes it.
+pub struct Stream {
+    /// Data sent by the remote.
+    read: Arc<Mutex<Pipe>>,
+    /// Data sent to the remote.
+    write: Arc<Mutex<Pipe>>,
+    /// See [`Stream::remote_port`].
+    remote_port: u64,
+}
+
+impl Stream {
+    /// Returns the port of the remote side of the connection.
+    ///
+    /// For the side that has called [`connect`], this is the port that was passed to [`connect`].
+    /// For the side that has accepted the connection, this is a port that has been automatically
+    /// assigned to the remote and that can't be connected to.
+    pub fn remote_port(&self) -> u64 {
+        self.remote_port
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.read.lock().unwrap();
+
+        if !pipe.buffer.is_empty() {
+            let num_bytes = cmp::min(buf.len(), pipe.buffer.len());
+            for (dest, src) in buf.iter_mut().zip(pipe.buffer.drain(..num_bytes)) {
+                *dest = src;
+            }
+            if let Some(waker) = pipe.writer_waker.take() {
+                waker.wake();
+            }
+            return Poll::Ready(Ok(num_bytes));
+        }
+
+        if pipe.writer_closed {
+            return Poll::Ready(Ok(0));
+        }
+
+        pipe.reader_waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut pipe = self.write.lock().unwrap();
+
+        if pipe.reader_dropped {
+            return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+        }
+        if pipe.writer_closed {
+            return Poll::Ready(Err(io::ErrorKind::NotConnected.into()));
+        }
+
+        let num_bytes = cmp::min(buf.len(), PIPE_CAPACITY - pipe.buffer.len());
+        if num_bytes == 0 && !buf.is_empty() {
+            pipe.writer_waker = Some(cx.waker().clone());
+            return Poll::Pending;
+        }
+
+        pipe.buffer.extend(&buf[..num_bytes]);
+        if let Some(waker) = pipe.reader_waker.take() {
+            waker.wake();
+        }
+        Poll::Ready(Ok(num_bytes))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.write.lock().unwrap().close_writer();
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl fmt::Debug for Stream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Stream").field(&self.remote_port).finish()
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.write.lock().unwrap().close_writer();
+
+        let mut read = self.read.lock().unwrap();
+        read.reader_dropped = true;
+        read.buffer.clear();
+        if let Some(waker) = read.writer_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+/// One direction of a [`Stream`].
+struct Pipe {
+    /// Data written but not read yet. Never larger than [`PIPE_CAPACITY`].
+    buffer: VecDeque<u8>,
+    /// `true` if the writing side has been closed.
+    writer_closed: bool,
+    /// `true` if the reading side has been destroyed.
+    reader_dropped: bool,
+    /// Waker to wake up when data is written or the writing side is closed.
+    reader_waker: Option<Waker>,
+    /// Waker to wake up when data is read or the reading side is destroyed.
+    writer_waker: Option<Waker>,
+}
+
+impl Pipe {
+    fn new() -> Self {
+        Pipe {
+            buffer: VecDeque::new(),
+            writer_closed: false,
+            reader_dropped: false,
+            reader_waker: None,
+            writer_waker: None,
+        }
+    }
+
+    fn close_writer(&mut self) {
+        self.writer_closed = true;
+        if let Some(waker) = self.reader_waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+fn next_ephemeral_port() -> u64 {
+    let mut next = NEXT_EPHEMERAL_PORT.lock().unwrap();
+    let port = *next;
+    *next = next.checked_add(1).unwrap_or(1 << 32);
+    port
+}
+
+#[cfg(test)]
+mod tests {
+    use futures_util::{AsyncReadExt as _, AsyncWriteExt as _};
+
+    #[test]
+    fn connect_without_listener() {
+        assert_eq!(
+            super::connect(0xdead_beef).unwrap_err(),
+            super::ConnectError::NoListener
+        );
+    }
+
+    #[test]
+    fn port_in_use() {
+        let listener = super::listen(0).unwrap();
+        assert_eq!(
+            super::listen(listener.port()).unwrap_err(),
+            super::ListenError::PortInUse
+        );
+
+        let port = listener.port();
+        drop(listener);
+        assert!(super::connect(port).is_err());
+        assert!(super::listen(port).is_ok());
+    }
+
+    #[test]
+    fn send_receive_close() {
+        futures_executor::block_on(async move {
+            let listener = super::listen(0).unwrap();
+            let mut dialer = super::connect(listener.port()).unwrap();
+            assert_eq!(dialer.remote_port(), listener.port());
+            let mut listener_side = listener.accept().await;
+
+            dialer.write_all(b"hello").await.unwrap();
+            let mut received = [0; 5];
+            listener_side.read_exact(&mut received).await.unwrap();
+            assert_eq!(&received, b"hello");
+
+            listener_side.write_all(b"world").await.unwrap();
+            listener_side.close().await.unwrap();
+            let mut received = Vec::new();
+            dialer.read_to_end(&mut received).await.unwrap();
+            assert_eq!(received, b"world");
+
+            drop(listener_side);
+            assert!(dialer.write_all(b"foo").await.is_err());
+        });
+    }
+}
@@ -17,7 +17,9 @@
 
 //! High-level Polkadot/Substrate-specific networking.
 
+pub mod authority_discovery;
 pub mod basic_peering_strategy;
 pub mod codec;
 pub mod kademlia;
+pub mod observed_addresses;
 pub mod service;
@@ -0,0 +1,351 @@
+// Smoldot
+// Copyright (C) 2019-2022  Parity Technologies (UK) Ltd.
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Authority discovery.
+//!
+//! The authorities of a chain (for example the validators of a relay chain) publish in the
+//! Kademlia DHT of the chain the list of multiaddresses they can be reached at. This makes it
+//! possible for other authorities and for collators to directly connect to them.
+//!
+//! The list of the current authorities can be obtained by calling the
+//! [`AUTHORITIES_FUNCTION_NAME`] runtime function, whose output can be decoded with
+//! [`decode_authorities`]. Each authority is identified by its sr25519 authority discovery
+//! public key.
+//!
+//! The record of an authority is stored in the DHT under the key returned by
+//! [`authority_dht_key`]. It consists of the list of addresses of the node, signed both with the
+//! authority discovery key of the authority and with the libp2p key of the node.
+//!
+//! Use [`LocalRecord`] in order to build the record that the local node publishes, and
+//! [`decode_and_verify_record`] in order to verify the records found in the DHT.
+//!
+//! See also [the Substrate implementation](https://github.com/paritytech/polkadot-sdk/tree/master/substrate/client/authority-discovery)
+//! for more details.
+
+use crate::{
+    libp2p::{
+        multiaddr::{Multiaddr, ProtocolRef},
+        peer_id::{self, PeerId, PublicKey},
+    },
+    util::protobuf,
+};
+
+use alloc::{borrow::Cow, vec::Vec};
+use sha2::{Digest as _, Sha256};
+
+/// Name of the runtime function to call in order to obtain the list of authorities. Its
+/// output can be decoded with [`decode_authorities`].
+///
+/// This function doesn't accept any parameter.
+pub const AUTHORITIES_FUNCTION_NAME: &str = "AuthorityDiscoveryApi_authorities";
+
+/// Decodes the output of a call to [`AUTHORITIES_FUNCTION_NAME`] into the list of authority
+/// discovery public keys of the current and next authorities.
+pub fn decode_authorities(scale_encoded: &[u8]) -> Result<Vec<[u8; 32]>, DecodeAuthoritiesError> {
+    let result: nom::IResult<_, _> = nom::combinator::all_consuming(nom::combinator::complete(
+        nom::combinator::flat_map(crate::util::nom_scale_compact_usize, |num_elems| {
+            nom::multi::many_m_n(
+                num_elems,
+                num_elems,
+                nom::combinator::map(nom::bytes::streaming::take(32u32), |public_key| {
+                    <[u8; 32]>::try_from(public_key).unwrap()
+                }),
+            )
+        }),
+    ))(scale_encoded);
+
+    match result {
+        Ok((_, authorities)) => Ok(authorities),
+        Err(_) => Err(DecodeAuthoritiesError),
+    }
+}
+
+/// Error potentially returned by [`decode_authorities`].
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "Failed to decode the list of authorities")]
+pub struct DecodeAuthoritiesError;
+
+/// Returns the key in the Kademlia DHT under which the record of the given authority is stored.
+///
+/// The key is the SHA-256 multihash of the authority discovery public key.
+pub fn authority_dht_key(authority_public_key: &[u8; 32]) -> [u8; 34] {
+    let mut out = [0; 34];
+    // `0x12` is the multihash code of SHA-256, and `0x20` the length of the digest.
+    out[0] = 0x12;
+    out[1] = 0x20;
+    out[2..].copy_from_slice(&Sha256::digest(authority_public_key));
+    out
+}
+
+/// Record describing how to reach the local node, ready to be signed with the authority
+/// discovery keys of the local node.
+///
+/// The record doesn't depend on the authority discovery key, and can thus be shared between all
+/// the authority discovery keys of the local node.
+#[derive(Clone)]
+pub struct LocalRecord {
+    /// Protobuf-encoded `AuthorityRecord` message.
+    record: Vec<u8>,
+    /// Protobuf-encoded libp2p public key of the local node.
+    libp2p_public_key: Vec<u8>,
+    /// Signature of [`LocalRecord::record`] using the libp2p private key of the local node.
+    libp2p_signature: [u8; 64],
+}
+
+impl LocalRecord {
+    /// Builds a new [`LocalRecord`] containing the given addresses.
+    ///
+    /// The `/p2p` component of the addresses is automatically added. The addresses passed as
+    /// parameter must not contain any.
+    ///
+    /// The libp2p private key is only used to sign the record and isn't kept.
+    pub fn new(
+        libp2p_ed25519_private_key: &[u8; 32],
+        addresses: impl Iterator<Item = Multiaddr>,
+    ) -> Self {
+        let secret = ed25519_zebra::SigningKey::from(*libp2p_ed25519_private_key);
+        let libp2p_public_key =
+            PublicKey::Ed25519(ed25519_zebra::VerificationKey::from(&secret).into());
+        let local_peer_id = libp2p_public_key.clone().into_peer_id();
+
+        let mut record = Vec::with_capacity(128);
+        for mut address in addresses {
+            address.push(ProtocolRef::P2p(Cow::Borrowed(local_peer_id.as_bytes())));
+            for slice in protobuf::bytes_tag_encode(1, address.as_ref()) {
+                record.extend_from_slice(slice.as_ref());
+            }
+        }
+
+        let libp2p_signature = secret.sign(&record).into();
+
+        LocalRecord {
+            record,
+            libp2p_public_key: libp2p_public_key.to_protobuf_encoding(),
+            libp2p_signature,
+        }
+    }
+
+    /// Returns the payload that must be signed with the authority discovery key using the
+    /// sr25519 algorithm and the `substrate` signing context.
+    pub fn payload_to_sign(&self) -> &[u8] {
+        &self.record
+    }
+
+    /// Builds the value to publish in the Kademlia DHT under the key returned by
+    /// [`authority_dht_key`].
+    ///
+    /// Must be passed the signature of [`LocalRecord::payload_to_sign`] generated with the
+    /// authority discovery key.
+    pub fn build_signed_record(&self, authority_signature: &[u8; 64]) -> Vec<u8> {
+        // The capacity is arbitrary but large enough to avoid Vec reallocations.
+        let mut out = Vec::with_capacity(
+            32 + self.record.len() + authority_signature.len() + self.libp2p_public_key.len() + 64,
+        );
+        for slice in protobuf::bytes_tag_encode(1, &self.record) {
+            out.extend_from_slice(slice.as_ref());
+        }
+        for slice in protobuf::bytes_tag_encode(2, &authority_signature[..]) {
+            out.extend_from_slice(slice.as_ref());
+        }
+        for slice in protobuf::message_tag_encode(
+            3,
+            protobuf::bytes_tag_encode(1, &self.libp2p_signature[..])
+                .chain(protobuf::bytes_tag_encode(2, &self.libp2p_public_key[..])),
+        ) {
+            out.extend_from_slice(slice.as_ref());
+        }
+        out
+    }
+}
+
+/// Record of an authority found in the DHT and whose signatures have been verified.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedRecord {
+    /// Identity of the node of the authority.
+    pub peer_id: PeerId,
+    /// Addresses where the node can be reached, including their `/p2p` component.
+    pub addresses: Vec<Multiaddr>,
+}
+
+/// Decodes a record found in the DHT under the key returned by [`authority_dht_key`], and
+/// verifies that it has been signed by the given authority.
+///
+/// If the record contains a signature by the libp2p key of the node, it is verified as well.
+pub fn decode_and_verify_record(
+    signed_record: &[u8],
+    authority_public_key: &[u8; 32],
+) -> Result<VerifiedRecord, VerifyError> {
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[required] record = 1 => protobuf::bytes_tag_decode,
+            #[required] auth_signature = 2 => protobuf::bytes_tag_decode,
+            #[optional] peer_signature = 3 => protobuf::message_tag_decode(protobuf::message_decode!{
+                #[required] signature = 1 => protobuf::bytes_tag_decode,
+                #[required] public_key = 2 => protobuf::bytes_tag_decode,
+            }),
+        }),
+    );
+
+    let decoded = match nom::Finish::finish(parser(signed_record)) {
+        Ok((_, out)) => out,
+        Err(_) => return Err(VerifyError::ProtobufDecode),
+    };
+
+    // Verify the signature of the authority.
+    let authority_public_key = schnorrkel::PublicKey::from_bytes(authority_public_key)
+        .map_err(|_| VerifyError::BadAuthoritySignature)?;
+    let auth_signature = schnorrkel::Signature::from_bytes(decoded.auth_signature)
+        .map_err(|_| VerifyError::BadAuthoritySignature)?;
+    authority_public_key
+        .verify_simple(b"substrate", decoded.record, &auth_signature)
+        .map_err(|_| VerifyError::BadAuthoritySignature)?;
+
+    // Verify the signature of the node, if any.
+    let signer_peer_id = match decoded.peer_signature {
+        Some(peer_signature) => {
+            let public_key = PublicKey::from_protobuf_encoding(peer_signature.public_key)
+                .map_err(VerifyError::BadPeerPublicKey)?;
+            public_key
+                .verify(decoded.record, peer_signature.signature)
+                .map_err(|_| VerifyError::BadPeerSignature)?;
+            Some(public_key.into_peer_id())
+        }
+        None => None,
+    };
+
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[repeated(max = 1024)] addresses = 1 => protobuf::bytes_tag_decode,
+        }),
+    );
+
+    let addresses = match nom::Finish::finish(parser(decoded.record)) {
+        Ok((_, out)) => out.addresses,
+        Err(_) => return Err(VerifyError::ProtobufDecode),
+    };
+
+    // All the addresses must end with the same `/p2p` component, which must match the signer
+    // of the record if any.
+    let mut peer_id = signer_peer_id;
+    let mut out_addresses = Vec::with_capacity(addresses.len());
+    for address in addresses {
+        let address = Multiaddr::try_from(address.to_vec()).map_err(|_| VerifyError::BadAddress)?;
+        let address_peer_id = match address.iter().last() {
+            Some(ProtocolRef::P2p(p2p)) => PeerId::from_bytes(p2p.into_owned())
+                .map_err(|(err, _)| VerifyError::BadAddressPeerId(err))?,
+            _ => return Err(VerifyError::BadAddress),
+        };
+
+        match &peer_id {
+            Some(peer_id) if *peer_id != address_peer_id => {
+                return Err(VerifyError::PeerIdMismatch)
+            }
+            Some(_) => {}
+            None => peer_id = Some(address_peer_id),
+        }
+
+        out_addresses.push(address);
+    }
+
+    Ok(VerifiedRecord {
+        peer_id: peer_id.ok_or(VerifyError::NoAddress)?,
+        addresses: out_addresses,
+    })
+}
+
+/// Error potentially returned by [`decode_and_verify_record`].
+#[derive(Debug, derive_more::Display)]
+pub enum VerifyError {
+    /// Error while decoding the Protobuf encoding.
+    ProtobufDecode,
+    /// Signature of the record by the authority is invalid.
+    BadAuthoritySignature,
+    /// Failed to decode the libp2p public key of the node.
+    #[display(fmt = "Invalid libp2p public key: {_0}")]
+    BadPeerPublicKey(peer_id::FromProtobufEncodingError),
+    /// Signature of the record by the libp2p key of the node is invalid.
+    BadPeerSignature,
+    /// One of the addresses of the record is invalid or doesn't end with a `/p2p` component.
+    BadAddress,
+    /// Failed to decode the [`PeerId`] of one of the addresses of the record.
+    #[display(fmt = "Invalid PeerId in address: {_0}")]
+    BadAddressPeerId(peer_id::FromBytesError),
+    /// The addresses of the record don't all designate the node that has signed the record.
+    PeerIdMismatch,
+    /// Record doesn't contain any address.
+    NoAddress,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::libp2p::{multiaddr::Multiaddr, peer_id::PublicKey};
+
+    #[test]
+    fn decode_authorities() {
+        let mut encoded = vec![8];
+        encoded.extend_from_slice(&[1; 32]);
+        encoded.extend_from_slice(&[2; 32]);
+        assert_eq!(
+            super::decode_authorities(&encoded).unwrap(),
+            vec![[1; 32], [2; 32]]
+        );
+
+        assert!(super::decode_authorities(&encoded[..40]).is_err());
+    }
+
+    #[test]
+    fn sign_and_verify() {
+        let authority_key = schnorrkel::MiniSecretKey::from_bytes(&[3; 32])
+            .unwrap()
+            .expand_to_keypair(schnorrkel::ExpansionMode::Ed25519);
+        let authority_public_key = authority_key.public.to_bytes();
+
+        let libp2p_private_key = [4; 32];
+        let local_peer_id = PublicKey::Ed25519(
+            ed25519_zebra::VerificationKey::from(&ed25519_zebra::SigningKey::from(
+                libp2p_private_key,
+            ))
+            .into(),
+        )
+        .into_peer_id();
+
+        let record = super::LocalRecord::new(
+            &libp2p_private_key,
+            ["/ip4/1.2.3.4/tcp/30333".parse::<Multiaddr>().unwrap()].into_iter(),
+        );
+
+        let authority_signature = authority_key
+            .sign_simple(b"substrate", record.payload_to_sign())
+            .to_bytes();
+        let signed_record = record.build_signed_record(&authority_signature);
+
+        let verified =
+            super::decode_and_verify_record(&signed_record, &authority_public_key).unwrap();
+        assert_eq!(verified.peer_id, local_peer_id);
+        assert_eq!(
+            verified.addresses,
+            vec![format!("/ip4/1.2.3.4/tcp/30333/p2p/{local_peer_id}")
+                .parse::<Multiaddr>()
+                .unwrap()]
+        );
+
+        assert!(matches!(
+            super::decode_and_verify_record(&signed_record, &[5; 32]),
+            Err(super::VerifyError::BadAuthoritySignature)
+        ));
+    }
+}
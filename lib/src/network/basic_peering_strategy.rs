@@ -41,6 +41,21 @@
 //! more addresses. It is not possible to insert addresses to peers that aren't associated to at
 //! least one chain. Each address is either "connected" or "disconnected".
 //!
+//! Network identities that are associated with at least one chain can also be associated with
+//! the list of protocols that they support, as reported by the peers themselves. See
+//! [`BasicPeeringStrategy::set_peer_protocols`].
+//!
+//! Each network-identity-chain association also has a reputation, which is adjusted through
+//! [`BasicPeeringStrategy::report`] depending on the behaviour of the peer. Peers with a high
+//! reputation are preferred when assigning slots. If the reputation falls below
+//! [`BAN_THRESHOLD`], the peer is banned until its reputation has increased again. Reputations
+//! progressively go back to zero every time [`BasicPeeringStrategy::decay_reputations`] is
+//! called.
+//!
+//! Network-identity-chain associations can additionally be marked as "reserved" using
+//! [`BasicPeeringStrategy::insert_reserved_chain_peer`]. Reserved associations always have a
+//! slot, are never banned, and are never removed in order to make space for other peers.
+//!
 //! There exists a limit to the number of peers per chain and the number of addresses per peer,
 //! guaranteeing that the data structure only uses a bounded amount of memory. If these limits
 //! are reached, peers and addresses are removed randomly. Peers that have a slot and addresses
@@ -51,9 +66,10 @@ use crate::util;
 use alloc::{
     borrow::ToOwned as _,
     collections::{btree_map, BTreeMap, BTreeSet},
+    string::String,
     vec::Vec,
 };
-use core::{hash::Hash, iter, ops};
+use core::{cmp, hash::Hash, iter, ops};
 use rand::seq::IteratorRandom as _;
 use rand_chacha::{
     rand_core::{RngCore as _, SeedableRng as _},
@@ -74,6 +90,10 @@ pub struct BasicPeeringStrategy<TChainId, TInstant> {
     /// to be in a particular order.
     addresses: BTreeMap<(usize, Vec<u8>), AddressState>,
 
+    /// List of protocols supported by the peers, indexed by `peer_id_index`. Peers whose
+    /// protocols are unknown are absent.
+    protocols: BTreeMap<usize, Vec<String>>,
+
     /// List of all chains throughout the collection.
     ///
     /// > **Note**: In principle this field is completely unnecessary. In practice, however, we
@@ -94,6 +114,15 @@ pub struct BasicPeeringStrategy<TChainId, TInstant> {
     /// Entries are `(chain_id_index, state, peer_id_index)`.
     peers_chains_by_state: BTreeSet<(usize, PeerChainState<TInstant>, usize)>,
 
+    /// Reputation of the entries of [`BasicPeeringStrategy::peers_chains`]. Keys are
+    /// `(peer_id_index, chain_id_index)`. Entries whose reputation is 0 are absent.
+    reputations: BTreeMap<(usize, usize), i32>,
+
+    /// Subset of the entries of [`BasicPeeringStrategy::peers_chains`] that are reserved.
+    /// Entries are `(chain_id_index, peer_id_index)`. All the entries in this list are always in
+    /// the [`PeerChainState::Slot`] state.
+    reserved: BTreeSet<(usize, usize)>,
+
     /// Random number generator used to select peers to assign slots to and remove addresses/peers.
     randomness: ChaCha20Rng,
 }
@@ -107,10 +136,52 @@ enum AddressState {
 #[derive(Debug, Clone, PartialOrd, Ord, PartialEq, Eq)]
 enum PeerChainState<TInstant> {
     Assignable,
-    Banned { expires: TInstant },
+    Banned {
+        expires: TInstant,
+    },
+    /// Banned until the reputation goes back above [`BAN_THRESHOLD`].
+    ReputationBanned,
     Slot,
 }
 
+/// Reputation below which a peer is banned.
+pub const BAN_THRESHOLD: i32 = -2000;
+
+/// Minimum value of the reputation of a peer.
+pub const MIN_REPUTATION: i32 = -10000;
+
+/// Maximum value of the reputation of a peer.
+pub const MAX_REPUTATION: i32 = 10000;
+
+/// Event that modifies the reputation of a peer. See [`BasicPeeringStrategy::report`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ReputationChange {
+    /// The peer has answered a request with a valid response.
+    GoodResponse,
+    /// The peer hasn't answered a request in time.
+    Timeout,
+    /// The peer has answered a request with a response that couldn't be decoded, or with a
+    /// proof that is invalid.
+    BadResponse,
+    /// The peer has sent a justification that failed to verify.
+    InvalidJustification,
+    /// The peer has sent a call proof that is missing entries necessary to perform the call.
+    IncompleteCallProof,
+}
+
+impl ReputationChange {
+    /// Returns the value that is added to the reputation of the peer.
+    pub fn value(&self) -> i32 {
+        match self {
+            ReputationChange::GoodResponse => 20,
+            ReputationChange::Timeout => -300,
+            ReputationChange::BadResponse => -1500,
+            ReputationChange::InvalidJustification => -5000,
+            ReputationChange::IncompleteCallProof => -5000,
+        }
+    }
+}
+
 /// Configuration passed to [`BasicPeeringStrategy::new`].
 pub struct Config {
     /// Seed used for the randomness for choosing peers and addresses to connect to or remove.
@@ -146,6 +217,7 @@ where
                 }),
             ),
             addresses: BTreeMap::new(),
+            protocols: BTreeMap::new(),
             chains: slab::Slab::with_capacity(config.chains_capacity),
             chains_indices: hashbrown::HashMap::with_capacity_and_hasher(
                 config.chains_capacity,
@@ -157,6 +229,8 @@ where
             ),
             peers_chains: BTreeMap::new(),
             peers_chains_by_state: BTreeSet::new(),
+            reputations: BTreeMap::new(),
+            reserved: BTreeSet::new(),
             randomness,
         }
     }
@@ -223,6 +297,7 @@ where
                     self.peers_chains_by_state
                         .remove(&(chain_index, state, peer_to_remove));
                 debug_assert!(_was_removed);
+                self.reputations.remove(&(peer_to_remove, chain_index));
                 self.try_clean_up_peer_id(peer_to_remove);
                 Some(peer_id_to_remove)
             } else {
@@ -238,7 +313,8 @@ where
     /// Removes a peer-chain associated previously inserted with
     /// [`BasicPeeringStrategy::insert_chain_peer`].
     ///
-    /// Has no effect if the peer-chain association didn't exist.
+    /// Has no effect if the peer-chain association didn't exist or is reserved (see
+    /// [`BasicPeeringStrategy::insert_reserved_chain_peer`]).
     ///
     /// If the peer isn't assigned to any chain anymore, all of its addresses are also removed
     /// from the collection.
@@ -253,17 +329,124 @@ where
             return;
         };
 
+        if self.reserved.contains(&(chain_index, peer_id_index)) {
+            return;
+        }
+
         if let Some(state) = self.peers_chains.remove(&(peer_id_index, chain_index)) {
             let _was_removed =
                 self.peers_chains_by_state
                     .remove(&(chain_index, state, peer_id_index));
             debug_assert!(_was_removed);
+            self.reputations.remove(&(peer_id_index, chain_index));
 
             self.try_clean_up_peer_id(peer_id_index);
             self.try_clean_up_chain(chain_index);
         }
     }
 
+    /// Removes all the peer-chain associations of the given chain, including the reserved ones.
+    ///
+    /// Must be called when a chain is no longer relevant. Peers that aren't assigned to any chain
+    /// anymore are removed from the collection alongside with their addresses.
+    pub fn remove_chain_peers(&mut self, chain: &TChainId) {
+        let Some(&chain_index) = self.chains_indices.get(chain) else {
+            // If the `TChainId` is unknown, it means that it doesn't have any peer.
+            return;
+        };
+
+        let peers = self
+            .peers_chains_by_state
+            .range(
+                (chain_index, PeerChainState::Assignable, usize::min_value())
+                    ..=(chain_index, PeerChainState::Slot, usize::max_value()),
+            )
+            .cloned()
+            .collect::<Vec<_>>();
+
+        for (_, state, peer_id_index) in peers {
+            let _was_removed =
+                self.peers_chains_by_state
+                    .remove(&(chain_index, state.clone(), peer_id_index));
+            debug_assert!(_was_removed);
+            let _was_in = self.peers_chains.remove(&(peer_id_index, chain_index));
+            debug_assert!(_was_in == Some(state));
+            self.reputations.remove(&(peer_id_index, chain_index));
+            self.reserved.remove(&(chain_index, peer_id_index));
+            self.try_clean_up_peer_id(peer_id_index);
+        }
+
+        self.try_clean_up_chain(chain_index);
+    }
+
+    /// Inserts a chain-peer combination to the collection and marks it as reserved.
+    ///
+    /// Acts as an implicit call to [`BasicPeeringStrategy::assign_slot`]. Reserved peer-chain
+    /// associations keep their slot forever, are never banned, and are never removed in order to
+    /// make space for other peers. Calls to [`BasicPeeringStrategy::unassign_slot_and_ban`],
+    /// [`BasicPeeringStrategy::unassign_slots_and_ban`], and
+    /// [`BasicPeeringStrategy::unassign_slot_and_remove_chain_peer`] have no effect on them.
+    ///
+    /// Returns `false` if the peer was already reserved for the given chain.
+    pub fn insert_reserved_chain_peer(&mut self, chain: TChainId, peer_id: PeerId) -> bool {
+        self.assign_slot(&chain, &peer_id);
+        let peer_id_index = self.get_or_insert_peer_index(&peer_id);
+        let chain_index = self.get_or_insert_chain_index(&chain);
+        self.reserved.insert((chain_index, peer_id_index))
+    }
+
+    /// Removes the reserved mark of a peer-chain association previously inserted with
+    /// [`BasicPeeringStrategy::insert_reserved_chain_peer`].
+    ///
+    /// The peer-chain association is kept in the collection and its slot is still assigned.
+    /// Call [`BasicPeeringStrategy::unassign_slot_and_ban`] or
+    /// [`BasicPeeringStrategy::unassign_slot_and_remove_chain_peer`] afterwards if desired.
+    ///
+    /// Returns `false` if the peer wasn't reserved for the given chain.
+    pub fn remove_reserved_chain_peer(&mut self, chain: &TChainId, peer_id: &PeerId) -> bool {
+        let (Some(&peer_id_index), Some(&chain_index)) = (
+            self.peer_ids_indices.get(peer_id),
+            self.chains_indices.get(chain),
+        ) else {
+            return false;
+        };
+
+        self.reserved.remove(&(chain_index, peer_id_index))
+    }
+
+    /// Returns `true` if the given peer has been reserved for the given chain with
+    /// [`BasicPeeringStrategy::insert_reserved_chain_peer`].
+    pub fn is_reserved(&self, chain: &TChainId, peer_id: &PeerId) -> bool {
+        let (Some(&peer_id_index), Some(&chain_index)) = (
+            self.peer_ids_indices.get(peer_id),
+            self.chains_indices.get(chain),
+        ) else {
+            return false;
+        };
+
+        self.reserved.contains(&(chain_index, peer_id_index))
+    }
+
+    /// Returns the list of all peers that have been reserved for the given chain with
+    /// [`BasicPeeringStrategy::insert_reserved_chain_peer`].
+    ///
+    /// The order of the yielded elements is unspecified.
+    pub fn reserved_chain_peers(
+        &'_ self,
+        chain: &TChainId,
+    ) -> impl Iterator<Item = &'_ PeerId> + '_ {
+        let Some(&chain_index) = self.chains_indices.get(chain) else {
+            // If the `TChainId` is unknown, it means that it doesn't have any peer.
+            return either::Right(iter::empty());
+        };
+
+        either::Left(
+            self.reserved
+                .range((chain_index, usize::min_value())..=(chain_index, usize::max_value()))
+                .map(|(_, p)| &self.peer_ids[*p]),
+        )
+    }
+
     /// Returns the list of all peers that are known to belong to the given chain, in other
     /// words peers added through [`BasicPeeringStrategy::insert_chain_peer`].
     ///
@@ -425,13 +608,48 @@ where
         )
     }
 
+    /// Sets the list of protocols supported by the given peer, as reported by the peer itself.
+    /// Replaces the list previously set, if any.
+    ///
+    /// Similar to [`BasicPeeringStrategy::insert_address`], this function has no effect if the
+    /// peer doesn't belong to any chain, in which case `false` is returned.
+    ///
+    /// A maximum number of protocols that are maintained for this peer must be passed as
+    /// parameter. Protocols beyond this number are ignored.
+    pub fn set_peer_protocols(
+        &mut self,
+        peer_id: &PeerId,
+        protocols: impl Iterator<Item = String>,
+        max_protocols: usize,
+    ) -> bool {
+        let Some(&peer_id_index) = self.peer_ids_indices.get(peer_id) else {
+            return false;
+        };
+
+        self.protocols
+            .insert(peer_id_index, protocols.take(max_protocols).collect());
+        true
+    }
+
+    /// Returns the list of protocols supported by the given peer, as set with
+    /// [`BasicPeeringStrategy::set_peer_protocols`]. Empty if the protocols of this peer are
+    /// unknown.
+    pub fn peer_protocols(&'_ self, peer_id: &PeerId) -> impl Iterator<Item = &'_ str> + '_ {
+        self.peer_ids_indices
+            .get(peer_id)
+            .and_then(|peer_id_index| self.protocols.get(peer_id_index))
+            .into_iter()
+            .flat_map(|protocols| protocols.iter().map(|p| &p[..]))
+    }
+
     /// Chooses a [`PeerId`] that is known to belong to the given chain, that is not banned, and
     /// that doesn't have a slot assigned to it.
     ///
     /// A `TInstant` must be provided in order to determine whether past bans have expired.
     ///
-    /// If multiple peers can be assigned a slot, the one returned is chosen randomly. Calling
-    /// this function multiple times might return different peers.
+    /// If multiple peers can be assigned a slot, the one with the highest reputation is
+    /// returned. If multiple peers have the same highest reputation, the one returned is chosen
+    /// randomly. Calling this function multiple times might return different peers.
     /// For this reason, this function requires `&mut self`.
     ///
     /// Note that this function might return a peer for which no address is present. While this is
@@ -446,20 +664,31 @@ where
             return AssignablePeer::NoPeer;
         };
 
-        if let Some((_, _, peer_id_index)) = self
+        let assignable_range = (chain_index, PeerChainState::Assignable, usize::min_value())
+            ..=(
+                chain_index,
+                PeerChainState::Banned {
+                    expires: now.clone(),
+                },
+                usize::max_value(),
+            );
+
+        let reputations = &self.reputations;
+        let reputation_of =
+            |peer_id_index: usize| *reputations.get(&(peer_id_index, chain_index)).unwrap_or(&0);
+
+        if let Some(best_reputation) = self
             .peers_chains_by_state
-            .range(
-                (chain_index, PeerChainState::Assignable, usize::min_value())
-                    ..=(
-                        chain_index,
-                        PeerChainState::Banned {
-                            expires: now.clone(),
-                        },
-                        usize::max_value(),
-                    ),
-            )
-            .choose(&mut self.randomness)
+            .range(assignable_range.clone())
+            .map(|(_, _, peer_id_index)| reputation_of(*peer_id_index))
+            .max()
         {
+            let (_, _, peer_id_index) = self
+                .peers_chains_by_state
+                .range(assignable_range)
+                .filter(|(_, _, peer_id_index)| reputation_of(*peer_id_index) == best_reputation)
+                .choose(&mut self.randomness)
+                .unwrap_or_else(|| unreachable!());
             return AssignablePeer::Assignable(&self.peer_ids[*peer_id_index]);
         }
 
@@ -477,11 +706,14 @@ where
             ))
             .next()
         {
-            let PeerChainState::Banned { expires } = state else {
-                unreachable!()
-            };
-            AssignablePeer::AllPeersBanned {
-                next_unban: expires,
+            match state {
+                PeerChainState::Banned { expires } => AssignablePeer::AllPeersBanned {
+                    next_unban: expires,
+                },
+                // Peers banned because of their reputation are unbanned when
+                // `decay_reputations` is called, rather than at a specific instant.
+                PeerChainState::ReputationBanned => AssignablePeer::AllPeersReputationBanned,
+                PeerChainState::Assignable | PeerChainState::Slot => unreachable!(),
             }
         } else {
             AssignablePeer::NoPeer
@@ -522,7 +754,8 @@ where
     /// Unassign the slot that has been assigned to the given peer and bans the peer, preventing
     /// it from being assigned a slot on this chain for a certain amount of time.
     ///
-    /// Has no effect if the peer isn't assigned to the given chain.
+    /// Has no effect if the peer isn't assigned to the given chain, or if the peer is reserved
+    /// for the given chain (see [`BasicPeeringStrategy::insert_reserved_chain_peer`]).
     ///
     /// If the peer was already banned, the new ban expiration is `max(existing_ban, when_unban)`.
     pub fn unassign_slot_and_ban(
@@ -538,12 +771,21 @@ where
             return;
         };
 
+        if self.reserved.contains(&(chain_index, peer_id_index)) {
+            return;
+        }
+
         if let Some(state) = self.peers_chains.get_mut(&(peer_id_index, chain_index)) {
             if matches!(state, PeerChainState::Banned { expires } if *expires >= when_unban) {
                 // Ban is already long enough. Nothing to do.
                 return;
             }
 
+            if matches!(state, PeerChainState::ReputationBanned) {
+                // The peer will be unbanned when its reputation goes back up.
+                return;
+            }
+
             let _was_in =
                 self.peers_chains_by_state
                     .remove(&(chain_index, state.clone(), peer_id_index));
@@ -564,7 +806,7 @@ where
     /// preventing it from being assigned a slot for all of the chains it had a slot on for a
     /// certain amount of time.
     ///
-    /// Has no effect on chains the peer isn't assigned to.
+    /// Has no effect on chains the peer isn't assigned to or is reserved for.
     ///
     /// If the peer was already banned, the new ban expiration is `max(existing_ban, when_unban)`.
     ///
@@ -579,11 +821,20 @@ where
             .peers_chains
             .range_mut((peer_id_index, usize::min_value())..=(peer_id_index, usize::max_value()))
         {
+            if self.reserved.contains(&(*chain_index, peer_id_index)) {
+                continue;
+            }
+
             if matches!(state, PeerChainState::Banned { expires } if *expires >= when_unban) {
                 // Ban is already long enough. Nothing to do.
                 continue;
             }
 
+            if matches!(state, PeerChainState::ReputationBanned) {
+                // The peer will be unbanned when its reputation goes back up.
+                continue;
+            }
+
             let _was_in =
                 self.peers_chains_by_state
                     .remove(&(*chain_index, state.clone(), peer_id_index));
@@ -600,6 +851,143 @@ where
         }
     }
 
+    /// Adjusts the reputation of the given peer on the given chain.
+    ///
+    /// Has no effect if the peer isn't assigned to the given chain, in which case
+    /// [`ReportResult::UnknownPeerChain`] is returned.
+    ///
+    /// If the reputation falls below [`BAN_THRESHOLD`], the slot of the peer, if any, is
+    /// unassigned and the peer is banned until its reputation goes back above the threshold.
+    /// Peers reserved for this chain (see [`BasicPeeringStrategy::insert_reserved_chain_peer`])
+    /// are never banned.
+    pub fn report(
+        &mut self,
+        chain: &TChainId,
+        peer_id: &PeerId,
+        change: ReputationChange,
+    ) -> ReportResult {
+        let (Some(&peer_id_index), Some(&chain_index)) = (
+            self.peer_ids_indices.get(peer_id),
+            self.chains_indices.get(chain),
+        ) else {
+            return ReportResult::UnknownPeerChain;
+        };
+
+        let Some(state) = self.peers_chains.get_mut(&(peer_id_index, chain_index)) else {
+            return ReportResult::UnknownPeerChain;
+        };
+
+        let new_reputation = match self.reputations.entry((peer_id_index, chain_index)) {
+            btree_map::Entry::Occupied(mut entry) => {
+                let new_reputation = entry
+                    .get()
+                    .saturating_add(change.value())
+                    .clamp(MIN_REPUTATION, MAX_REPUTATION);
+                if new_reputation == 0 {
+                    entry.remove();
+                } else {
+                    *entry.get_mut() = new_reputation;
+                }
+                new_reputation
+            }
+            btree_map::Entry::Vacant(entry) => {
+                let new_reputation = change.value().clamp(MIN_REPUTATION, MAX_REPUTATION);
+                if new_reputation != 0 {
+                    entry.insert(new_reputation);
+                }
+                new_reputation
+            }
+        };
+
+        if new_reputation >= BAN_THRESHOLD
+            || matches!(state, PeerChainState::ReputationBanned)
+            || self.reserved.contains(&(chain_index, peer_id_index))
+        {
+            return ReportResult::Adjusted;
+        }
+
+        let had_slot = matches!(state, PeerChainState::Slot);
+
+        let _was_in =
+            self.peers_chains_by_state
+                .remove(&(chain_index, state.clone(), peer_id_index));
+        debug_assert!(_was_in);
+
+        *state = PeerChainState::ReputationBanned;
+
+        let _was_inserted = self.peers_chains_by_state.insert((
+            chain_index,
+            PeerChainState::ReputationBanned,
+            peer_id_index,
+        ));
+        debug_assert!(_was_inserted);
+
+        ReportResult::Banned { had_slot }
+    }
+
+    /// Returns the reputation of the given peer on the given chain.
+    ///
+    /// Returns 0 if the peer isn't assigned to the given chain.
+    pub fn reputation(&self, chain: &TChainId, peer_id: &PeerId) -> i32 {
+        let (Some(&peer_id_index), Some(&chain_index)) = (
+            self.peer_ids_indices.get(peer_id),
+            self.chains_indices.get(chain),
+        ) else {
+            return 0;
+        };
+
+        *self
+            .reputations
+            .get(&(peer_id_index, chain_index))
+            .unwrap_or(&0)
+    }
+
+    /// Brings the reputation of all the peers closer to zero, and unbans the peers whose
+    /// reputation goes back above [`BAN_THRESHOLD`].
+    ///
+    /// This function is meant to be called at a regular interval, typically every second. Each
+    /// call reduces the absolute value of each reputation by 2%.
+    pub fn decay_reputations(&mut self) {
+        let mut unbanned = Vec::new();
+
+        self.reputations
+            .retain(|&(peer_id_index, chain_index), reputation| {
+                let was_below_threshold = *reputation < BAN_THRESHOLD;
+                let decay = cmp::max(reputation.abs() / 50, 1);
+                *reputation -= reputation.signum() * decay;
+                if was_below_threshold && *reputation >= BAN_THRESHOLD {
+                    unbanned.push((peer_id_index, chain_index));
+                }
+                *reputation != 0
+            });
+
+        for (peer_id_index, chain_index) in unbanned {
+            let Some(state) = self.peers_chains.get_mut(&(peer_id_index, chain_index)) else {
+                unreachable!()
+            };
+
+            if !matches!(state, PeerChainState::ReputationBanned) {
+                continue;
+            }
+
+            let _was_in = self.peers_chains_by_state.remove(&(
+                chain_index,
+                PeerChainState::ReputationBanned,
+                peer_id_index,
+            ));
+            debug_assert!(_was_in);
+
+            *state = PeerChainState::Assignable;
+
+            let _was_inserted = self.peers_chains_by_state.insert((
+                chain_index,
+                PeerChainState::Assignable,
+                peer_id_index,
+            ));
+            debug_assert!(_was_inserted);
+        }
+    }
+
     /// Picks an address from the list whose state is "not connected", and switches it to
     /// "connected". Returns `None` if no such address is available.
     pub fn addr_to_connected(&mut self, peer_id: &PeerId) -> Option<&[u8]> {
@@ -724,6 +1112,7 @@ where
             let _was_removed = self.addresses.remove(&(peer_id_index, address));
             debug_assert!(_was_removed.is_some());
         }
+        self.protocols.remove(&peer_id_index);
     }
 }
 
@@ -745,10 +1134,28 @@ pub enum AssignablePeer<'a, TInstant> {
         /// Instant when the first peer will be unbanned.
         next_unban: &'a TInstant,
     },
+    /// No peer was found as all known un-assigned peers are currently banned because of their
+    /// reputation. They will be unbanned once their reputation has gone back up through
+    /// [`BasicPeeringStrategy::decay_reputations`].
+    AllPeersReputationBanned,
     /// No un-assigned peer was found.
     NoPeer,
 }
 
+/// See [`BasicPeeringStrategy::report`].
+#[derive(Debug)]
+pub enum ReportResult {
+    /// The reputation of the peer has been adjusted. The peer hasn't been newly banned.
+    Adjusted,
+    /// The reputation of the peer has fallen below [`BAN_THRESHOLD`] and the peer is now banned.
+    Banned {
+        /// `true` if the peer had a slot assigned to it. This slot has now been unassigned.
+        had_slot: bool,
+    },
+    /// The peer isn't associated to the given chain.
+    UnknownPeerChain,
+}
+
 /// See [`BasicPeeringStrategy::insert_chain_peer`].
 pub enum InsertChainPeerResult {
     /// Peer-chain association has been successfully inserted.
@@ -778,9 +1185,13 @@ pub enum InsertAddressResult {
 
 #[cfg(test)]
 mod tests {
-    use super::{BasicPeeringStrategy, Config, InsertAddressResult, InsertChainPeerResult};
+    use super::{
+        AssignablePeer, BasicPeeringStrategy, Config, InsertAddressResult, InsertChainPeerResult,
+        ReportResult, ReputationChange,
+    };
     use crate::network::service::{peer_id::PublicKey, PeerId};
-    use core::time::Duration;
+    use alloc::{borrow::ToOwned as _, vec, vec::Vec};
+    use core::{iter, time::Duration};
 
     #[test]
     fn peer_state_ordering() {
@@ -791,8 +1202,9 @@ mod tests {
         assert!(
             PeerChainState::Banned {
                 expires: u32::max_value()
-            } < PeerChainState::Slot
+            } < PeerChainState::ReputationBanned
         );
+        assert!(PeerChainState::<u32>::ReputationBanned < PeerChainState::Slot);
     }
 
     #[test]
@@ -822,5 +1234,186 @@ mod tests {
         assert_eq!(bps.peer_addresses(&peer_id).count(), 0);
     }
 
+    #[test]
+    fn peer_protocols() {
+        let mut bps = BasicPeeringStrategy::<u32, Duration>::new(Config {
+            randomness_seed: [0; 32],
+            peers_capacity: 0,
+            chains_capacity: 0,
+        });
+
+        let peer_id = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+
+        // Protocols of peers that don't belong to any chain aren't stored.
+        assert!(!bps.set_peer_protocols(&peer_id, iter::once("/foo".to_owned()), 8));
+        assert_eq!(bps.peer_protocols(&peer_id).count(), 0);
+
+        bps.insert_chain_peer(0, peer_id.clone(), usize::max_value());
+        assert!(bps.set_peer_protocols(
+            &peer_id,
+            ["/foo", "/bar", "/baz"].into_iter().map(|p| p.to_owned()),
+            2
+        ));
+        assert_eq!(
+            bps.peer_protocols(&peer_id).collect::<Vec<_>>(),
+            vec!["/foo", "/bar"]
+        );
+
+        // A new list replaces the previous one.
+        assert!(bps.set_peer_protocols(&peer_id, iter::once("/baz".to_owned()), 2));
+        assert_eq!(
+            bps.peer_protocols(&peer_id).collect::<Vec<_>>(),
+            vec!["/baz"]
+        );
+
+        // Protocols are forgotten at the same time as the peer.
+        bps.unassign_slot_and_remove_chain_peer(&0, &peer_id);
+        assert_eq!(bps.peer_protocols(&peer_id).count(), 0);
+        bps.insert_chain_peer(0, peer_id.clone(), usize::max_value());
+        assert_eq!(bps.peer_protocols(&peer_id).count(), 0);
+    }
+
+    #[test]
+    fn remove_chain_peers() {
+        let mut bps = BasicPeeringStrategy::<u32, Duration>::new(Config {
+            randomness_seed: [0; 32],
+            peers_capacity: 0,
+            chains_capacity: 0,
+        });
+
+        let shared = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+        let reserved = PeerId::from_public_key(&PublicKey::Ed25519([1; 32]));
+        let exclusive = PeerId::from_public_key(&PublicKey::Ed25519([2; 32]));
+
+        bps.insert_chain_peer(0, shared.clone(), usize::max_value());
+        bps.insert_chain_peer(1, shared.clone(), usize::max_value());
+        bps.insert_address(&shared, Vec::new(), usize::max_value());
+        bps.assign_slot(&0, &shared);
+        assert!(bps.insert_reserved_chain_peer(0, reserved.clone()));
+        bps.insert_chain_peer(0, exclusive.clone(), usize::max_value());
+        bps.insert_address(&exclusive, Vec::new(), usize::max_value());
+        bps.report(&0, &exclusive, ReputationChange::BadResponse);
+
+        bps.remove_chain_peers(&0);
+        assert_eq!(bps.chain_peers_unordered(&0).count(), 0);
+        assert_eq!(bps.reserved_chain_peers(&0).count(), 0);
+        assert!(!bps.is_reserved(&0, &reserved));
+
+        // Peers that belong to another chain are kept alongside with their addresses.
+        assert_eq!(
+            bps.chain_peers_unordered(&1).collect::<Vec<_>>(),
+            vec![&shared]
+        );
+        assert_eq!(bps.peer_addresses(&shared).count(), 1);
+        assert_eq!(bps.peer_addresses(&exclusive).count(), 0);
+
+        // Peers can be inserted again with a blank state.
+        bps.insert_chain_peer(0, exclusive.clone(), usize::max_value());
+        assert_eq!(bps.reputation(&0, &exclusive), 0);
+        assert!(matches!(
+            bps.pick_assignable_peer(&0, &Duration::from_secs(0)),
+            super::AssignablePeer::Assignable(p) if *p == exclusive
+        ));
+
+        // Removing a chain without any peer has no effect.
+        bps.remove_chain_peers(&2);
+    }
+
+    #[test]
+    fn reserved_peer_never_banned_nor_removed() {
+        let mut bps = BasicPeeringStrategy::<u32, Duration>::new(Config {
+            randomness_seed: [0; 32],
+            peers_capacity: 0,
+            chains_capacity: 0,
+        });
+
+        let reserved = PeerId::from_public_key(&PublicKey::Ed25519([0; 32]));
+        assert!(bps.insert_reserved_chain_peer(0, reserved.clone()));
+        assert!(!bps.insert_reserved_chain_peer(0, reserved.clone()));
+        assert!(bps.is_reserved(&0, &reserved));
+        assert!(!bps.is_reserved(&1, &reserved));
+
+        bps.unassign_slot_and_ban(&0, &reserved, Duration::from_secs(10));
+        bps.unassign_slots_and_ban(&reserved, Duration::from_secs(10));
+        bps.unassign_slot_and_remove_chain_peer(&0, &reserved);
+        assert_eq!(
+            bps.reserved_chain_peers(&0).collect::<Vec<_>>(),
+            vec![&reserved]
+        );
+
+        // Reaching the limit of peers never evicts the reserved peer.
+        for n in 1..8 {
+            let other = PeerId::from_public_key(&PublicKey::Ed25519([n; 32]));
+            bps.insert_chain_peer(0, other, 2);
+        }
+        assert!(bps.chain_peers_unordered(&0).any(|p| *p == reserved));
+        assert!(matches!(
+            bps.pick_assignable_peer(&0, &Duration::from_secs(0)),
+            super::AssignablePeer::Assignable(p) if *p != reserved
+        ));
+
+        // Once no longer reserved, the peer behaves normally again.
+        assert!(bps.remove_reserved_chain_peer(&0, &reserved));
+        assert!(!bps.remove_reserved_chain_peer(&0, &reserved));
+        bps.unassign_slot_and_remove_chain_peer(&0, &reserved);
+        assert!(!bps.chain_peers_unordered(&0).any(|p| *p == reserved));
+    }
+
+    #[test]
+    fn reputation_ban_and_decay() {
+        let mut bps = BasicPeeringStrategy::<u32, Duration>::new(Config {
+            randomness_seed: [0; 32],
+            peers_capacity: 0,
+            chains_capacity: 0,
+        });
+
+        let good = PeerId::from_public_key(&PublicKey::Ed25519([1; 32]));
+        let bad = PeerId::from_public_key(&PublicKey::Ed25519([2; 32]));
+        bps.insert_chain_peer(0, good.clone(), usize::max_value());
+        bps.assign_slot(&0, &bad);
+
+        // Peers with a higher reputation are always picked first.
+        assert!(matches!(
+            bps.report(&0, &good, ReputationChange::GoodResponse),
+            ReportResult::Adjusted
+        ));
+        assert!(bps.reputation(&0, &good) > 0);
+
+        assert!(matches!(
+            bps.report(&0, &bad, ReputationChange::BadResponse),
+            ReportResult::Adjusted
+        ));
+        assert!(matches!(
+            bps.report(&0, &bad, ReputationChange::BadResponse),
+            ReportResult::Banned { had_slot: true }
+        ));
+        assert!(matches!(
+            bps.pick_assignable_peer(&0, &Duration::from_secs(0)),
+            AssignablePeer::Assignable(p) if *p == good
+        ));
+        bps.assign_slot(&0, &good);
+        assert!(matches!(
+            bps.pick_assignable_peer(&0, &Duration::from_secs(0)),
+            AssignablePeer::AllPeersReputationBanned
+        ));
+
+        // The bad peer is unbanned after its reputation has decayed enough.
+        for _ in 0..25 {
+            bps.decay_reputations();
+        }
+        assert!(bps.reputation(&0, &bad) >= super::BAN_THRESHOLD);
+        assert!(matches!(
+            bps.pick_assignable_peer(&0, &Duration::from_secs(0)),
+            AssignablePeer::Assignable(p) if *p == bad
+        ));
+
+        // Reputations eventually go back to zero.
+        for _ in 0..1000 {
+            bps.decay_reputations();
+        }
+        assert_eq!(bps.reputation(&0, &bad), 0);
+        assert_eq!(bps.reputation(&0, &good), 0);
+    }
+
     // TODO: more tests
 }
@@ -27,6 +27,7 @@ use core::{fmt, iter};
 
 mod block_announces;
 mod block_request;
+mod collation;
 mod grandpa;
 mod grandpa_warp_sync;
 mod identify;
@@ -36,6 +37,7 @@ mod storage_call_proof;
 
 pub use self::block_announces::*;
 pub use self::block_request::*;
+pub use self::collation::*;
 pub use self::grandpa::*;
 pub use self::grandpa_warp_sync::*;
 pub use self::identify::*;
@@ -80,6 +82,14 @@ pub enum ProtocolName<'a> {
         genesis_hash: [u8; 32],
         fork_id: Option<&'a str>,
     },
+    Collation {
+        genesis_hash: [u8; 32],
+        fork_id: Option<&'a str>,
+    },
+    CollationFetching {
+        genesis_hash: [u8; 32],
+        fork_id: Option<&'a str>,
+    },
 }
 
 impl<'a> fmt::Debug for ProtocolName<'a> {
@@ -137,6 +147,14 @@ pub fn encode_protocol_name(
             genesis_hash,
             fork_id,
         } => (genesis_hash, fork_id, "state/2"),
+        ProtocolName::Collation {
+            genesis_hash,
+            fork_id,
+        } => (genesis_hash, fork_id, "collation/1"),
+        ProtocolName::CollationFetching {
+            genesis_hash,
+            fork_id,
+        } => (genesis_hash, fork_id, "req_collation/1"),
     };
 
     let genesis_hash = hex::encode(genesis_hash);
@@ -231,6 +249,8 @@ enum ProtocolTy {
     Kad,
     SyncWarp,
     State,
+    Collation,
+    CollationFetching,
 }
 
 fn protocol_ty(name: &str) -> nom::IResult<&str, ProtocolTy> {
@@ -251,6 +271,12 @@ fn protocol_ty(name: &str) -> nom::IResult<&str, ProtocolTy> {
             ProtocolTy::SyncWarp
         }),
         nom::combinator::map(nom::bytes::complete::tag("state/2"), |_| ProtocolTy::State),
+        nom::combinator::map(nom::bytes::complete::tag("collation/1"), |_| {
+            ProtocolTy::Collation
+        }),
+        nom::combinator::map(nom::bytes::complete::tag("req_collation/1"), |_| {
+            ProtocolTy::CollationFetching
+        }),
     ))(name)
 }
 
@@ -292,6 +318,14 @@ fn protocol_ty_to_real_protocol(
             genesis_hash,
             fork_id,
         },
+        ProtocolTy::Collation => ProtocolName::Collation {
+            genesis_hash,
+            fork_id,
+        },
+        ProtocolTy::CollationFetching => ProtocolName::CollationFetching {
+            genesis_hash,
+            fork_id,
+        },
     }
 }
 
@@ -0,0 +1,436 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Collation protocols.
+//!
+//! # Overview
+//!
+//! Collators are the nodes that produce blocks of a parachain. In order for these blocks to be
+//! included in the relay chain, they must be sent to the validators of the relay chain that are
+//! assigned to this parachain.
+//!
+//! This is done in two steps:
+//!
+//! - The collator opens a notifications substream with the validator using the collation
+//!   protocol, and sends a [`CollationProtocolMessageRef::Declare`] message indicating which
+//!   parachain it is a collator of. Then, whenever a new parachain block is available, the
+//!   collator sends a [`CollationProtocolMessageRef::AdvertiseCollation`] message indicating the
+//!   relay chain block the parachain block is built upon.
+//! - The validator then sends a collation fetching request (see
+//!   [`decode_collation_fetching_request`]) to the collator, which answers with the candidate
+//!   receipt and the Proof-of-Validity of the parachain block (see
+//!   [`build_collation_fetching_response`]).
+//!
+
+use crate::{libp2p::peer_id::PeerId, util};
+
+use alloc::vec::Vec;
+use core::iter;
+use nom::Finish as _;
+
+/// Message sent on a collation protocol substream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CollationProtocolMessageRef<'a> {
+    /// Sent by a collator at the beginning of the substream in order to indicate which parachain
+    /// it collates for.
+    Declare {
+        /// Sr25519 public key of the collator.
+        collator_id: &'a [u8; 32],
+        /// Identifier of the parachain the collator collates for.
+        para_id: u32,
+        /// Sr25519 signature made using [`CollationProtocolMessageRef::Declare::collator_id`] of
+        /// the payload returned by [`declare_signature_payload`].
+        signature: &'a [u8; 64],
+    },
+
+    /// Sent by a collator in order to advertise that a collation is available.
+    AdvertiseCollation {
+        /// Hash of the relay chain block the collation is built upon.
+        relay_parent: &'a [u8; 32],
+    },
+}
+
+/// Returns the payload that must be signed with the key of the collator in the
+/// [`CollationProtocolMessageRef::Declare`] message.
+///
+/// The `peer_id` must be the identity of the local node, in other words of the node that sends
+/// the message.
+pub fn declare_signature_payload(peer_id: &PeerId) -> Vec<u8> {
+    // Note that this matches what Polkadot does: the bytes of the `PeerId` followed with
+    // `b"COLL"`.
+    let mut out = Vec::with_capacity(peer_id.as_bytes().len() + 4);
+    out.extend_from_slice(peer_id.as_bytes());
+    out.extend_from_slice(b"COLL");
+    out
+}
+
+/// Builds the bytes corresponding to a collation protocol notification.
+pub fn build_collation_protocol_message<'a>(
+    message: &CollationProtocolMessageRef<'a>,
+) -> impl Iterator<Item = impl AsRef<[u8]> + 'a> + 'a {
+    // The first byte corresponds to the `CollatorProtocol` variant of the `CollationProtocol`
+    // enum, which is the only variant.
+    match *message {
+        CollationProtocolMessageRef::Declare {
+            collator_id,
+            para_id,
+            signature,
+        } => either::Left(
+            [
+                either::Left(either::Left([0u8, 0u8])),
+                either::Right(&collator_id[..]),
+                either::Left(either::Right(para_id.to_le_bytes())),
+                either::Right(&signature[..]),
+            ]
+            .into_iter(),
+        ),
+        CollationProtocolMessageRef::AdvertiseCollation { relay_parent } => either::Right(
+            [
+                either::Left(either::Left([0u8, 1u8])),
+                either::Right(&relay_parent[..]),
+            ]
+            .into_iter(),
+        ),
+    }
+}
+
+/// Decodes a collation protocol notification.
+pub fn decode_collation_protocol_message(
+    scale_encoded: &[u8],
+) -> Result<CollationProtocolMessageRef<'_>, DecodeCollationProtocolMessageError> {
+    match nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(nom::sequence::preceded(
+            nom::bytes::streaming::tag(&[0]),
+            nom::branch::alt((
+                nom::combinator::map(
+                    nom::sequence::tuple((
+                        nom::bytes::streaming::tag(&[0]),
+                        nom::bytes::streaming::take(32u32),
+                        nom::number::streaming::le_u32,
+                        nom::bytes::streaming::take(64u32),
+                    )),
+                    |(_, collator_id, para_id, signature)| CollationProtocolMessageRef::Declare {
+                        collator_id: <&[u8; 32]>::try_from(collator_id).unwrap(),
+                        para_id,
+                        signature: <&[u8; 64]>::try_from(signature).unwrap(),
+                    },
+                ),
+                nom::combinator::map(
+                    nom::sequence::preceded(
+                        nom::bytes::streaming::tag(&[1]),
+                        nom::bytes::streaming::take(32u32),
+                    ),
+                    |relay_parent| CollationProtocolMessageRef::AdvertiseCollation {
+                        relay_parent: <&[u8; 32]>::try_from(relay_parent).unwrap(),
+                    },
+                ),
+            )),
+        )),
+    )(scale_encoded)
+    .finish()
+    {
+        Ok((_, message)) => Ok(message),
+        Err(err) => Err(DecodeCollationProtocolMessageError(err.code)),
+    }
+}
+
+/// Error potentially returned by [`decode_collation_protocol_message`].
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "Failed to decode a collation protocol message")]
+pub struct DecodeCollationProtocolMessageError(nom::error::ErrorKind);
+
+/// Description of a collation fetching request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollationFetchingRequest {
+    /// Hash of the relay chain block the requested collation is built upon.
+    pub relay_parent: [u8; 32],
+    /// Identifier of the parachain whose collation is requested.
+    pub para_id: u32,
+}
+
+/// Builds the bytes corresponding to a collation fetching request.
+pub fn build_collation_fetching_request(
+    request: &CollationFetchingRequest,
+) -> impl Iterator<Item = impl AsRef<[u8]> + '_> + '_ {
+    [
+        either::Left(&request.relay_parent[..]),
+        either::Right(request.para_id.to_le_bytes()),
+    ]
+    .into_iter()
+}
+
+/// Decodes a collation fetching request.
+pub fn decode_collation_fetching_request(
+    request_bytes: &[u8],
+) -> Result<CollationFetchingRequest, DecodeCollationFetchingRequestError> {
+    if request_bytes.len() != 36 {
+        return Err(DecodeCollationFetchingRequestError::InvalidLength);
+    }
+
+    Ok(CollationFetchingRequest {
+        relay_parent: <[u8; 32]>::try_from(&request_bytes[..32]).unwrap(),
+        para_id: u32::from_le_bytes(<[u8; 4]>::try_from(&request_bytes[32..]).unwrap()),
+    })
+}
+
+/// Error potentially returned by [`decode_collation_fetching_request`].
+#[derive(Debug, derive_more::Display, Clone)]
+pub enum DecodeCollationFetchingRequestError {
+    /// The request doesn't have the expected length.
+    InvalidLength,
+}
+
+/// Candidate receipt of a parachain block. Sent alongside with the Proof-of-Validity in
+/// response to a collation fetching request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CandidateReceiptRef<'a> {
+    /// Identifier of the parachain the candidate belongs to.
+    pub para_id: u32,
+    /// Hash of the relay chain block the candidate is built upon.
+    pub relay_parent: &'a [u8; 32],
+    /// Sr25519 public key of the collator that has produced the candidate.
+    pub collator: &'a [u8; 32],
+    /// Blake2 hash of the SCALE-encoded persisted validation data of the parachain at the
+    /// relay parent.
+    pub persisted_validation_data_hash: &'a [u8; 32],
+    /// Blake2 hash of the SCALE-encoded Proof-of-Validity. See [`crate::author::pov::pov_hash`].
+    pub pov_hash: &'a [u8; 32],
+    /// Root of the erasure-coding Merkle tree of the available data of the candidate.
+    pub erasure_root: &'a [u8; 32],
+    /// Sr25519 signature made using [`CandidateReceiptRef::collator`] of the payload returned
+    /// by [`collator_signature_payload`].
+    pub signature: &'a [u8; 64],
+    /// Blake2 hash of the SCALE-encoded head data (i.e. header) of the parachain block.
+    pub para_head: &'a [u8; 32],
+    /// Blake2 hash of the Wasm validation code of the parachain.
+    pub validation_code_hash: &'a [u8; 32],
+    /// Blake2 hash of the SCALE-encoded commitments of the candidate.
+    pub commitments_hash: &'a [u8; 32],
+}
+
+impl<'a> CandidateReceiptRef<'a> {
+    /// Returns an iterator to list of buffers which, when concatenated, produces the SCALE
+    /// encoding of that object.
+    pub fn scale_encoding(&self) -> impl Iterator<Item = impl AsRef<[u8]> + Clone + 'a> + Clone {
+        [
+            either::Right(self.para_id.to_le_bytes()),
+            either::Left(&self.relay_parent[..]),
+            either::Left(&self.collator[..]),
+            either::Left(&self.persisted_validation_data_hash[..]),
+            either::Left(&self.pov_hash[..]),
+            either::Left(&self.erasure_root[..]),
+            either::Left(&self.signature[..]),
+            either::Left(&self.para_head[..]),
+            either::Left(&self.validation_code_hash[..]),
+            either::Left(&self.commitments_hash[..]),
+        ]
+        .into_iter()
+    }
+}
+
+/// Returns the payload that the collator must sign in order to produce
+/// [`CandidateReceiptRef::signature`].
+pub fn collator_signature_payload(
+    relay_parent: &[u8; 32],
+    para_id: u32,
+    persisted_validation_data_hash: &[u8; 32],
+    pov_hash: &[u8; 32],
+    validation_code_hash: &[u8; 32],
+) -> [u8; 132] {
+    let mut out = [0; 132];
+    out[..32].copy_from_slice(relay_parent);
+    out[32..36].copy_from_slice(&para_id.to_le_bytes());
+    out[36..68].copy_from_slice(persisted_validation_data_hash);
+    out[68..100].copy_from_slice(pov_hash);
+    out[100..132].copy_from_slice(validation_code_hash);
+    out
+}
+
+/// Response to a collation fetching request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CollationFetchingResponse<'a> {
+    /// Candidate receipt of the collation.
+    pub candidate_receipt: CandidateReceiptRef<'a>,
+    /// SCALE-encoded Proof-of-Validity of the collation. See [`crate::author::pov`].
+    pub scale_encoded_pov: &'a [u8],
+}
+
+/// Builds the bytes corresponding to a response to a collation fetching request.
+pub fn build_collation_fetching_response<'a>(
+    response: &CollationFetchingResponse<'a>,
+) -> impl Iterator<Item = impl AsRef<[u8]> + 'a> + 'a {
+    // The first byte corresponds to the `Collation` variant of the `CollationFetchingResponse`
+    // enum, which is the only variant.
+    iter::once(either::Left([0u8]))
+        .chain(
+            response
+                .candidate_receipt
+                .scale_encoding()
+                .map(either::Left)
+                .map(either::Right),
+        )
+        .chain(iter::once(either::Right(either::Right(
+            response.scale_encoded_pov,
+        ))))
+}
+
+/// Decodes a response to a collation fetching request.
+pub fn decode_collation_fetching_response(
+    response_bytes: &[u8],
+) -> Result<CollationFetchingResponse<'_>, DecodeCollationFetchingResponseError> {
+    let hash = |bytes| <&[u8; 32]>::try_from(bytes).unwrap();
+
+    match nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(nom::sequence::preceded(
+            nom::bytes::streaming::tag(&[0]),
+            nom::sequence::tuple((
+                nom::number::streaming::le_u32,
+                nom::multi::count(nom::bytes::streaming::take(32u32), 5),
+                nom::bytes::streaming::take(64u32),
+                nom::multi::count(nom::bytes::streaming::take(32u32), 3),
+                nom::combinator::recognize(util::nom_bytes_decode),
+            )),
+        )),
+    )(response_bytes)
+    .finish()
+    {
+        Ok((_, (para_id, before_signature, signature, after_signature, scale_encoded_pov))) => {
+            Ok(CollationFetchingResponse {
+                candidate_receipt: CandidateReceiptRef {
+                    para_id,
+                    relay_parent: hash(before_signature[0]),
+                    collator: hash(before_signature[1]),
+                    persisted_validation_data_hash: hash(before_signature[2]),
+                    pov_hash: hash(before_signature[3]),
+                    erasure_root: hash(before_signature[4]),
+                    signature: <&[u8; 64]>::try_from(signature).unwrap(),
+                    para_head: hash(after_signature[0]),
+                    validation_code_hash: hash(after_signature[1]),
+                    commitments_hash: hash(after_signature[2]),
+                },
+                scale_encoded_pov,
+            })
+        }
+        Err(err) => Err(DecodeCollationFetchingResponseError(err.code)),
+    }
+}
+
+/// Error potentially returned by [`decode_collation_fetching_response`].
+#[derive(Debug, derive_more::Display)]
+#[display(fmt = "Failed to decode a collation fetching response")]
+pub struct DecodeCollationFetchingResponseError(nom::error::ErrorKind);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn declare_encode_decode() {
+        let message = super::CollationProtocolMessageRef::Declare {
+            collator_id: &[1; 32],
+            para_id: 2000,
+            signature: &[2; 64],
+        };
+
+        let encoded =
+            super::build_collation_protocol_message(&message).fold(Vec::new(), |mut a, b| {
+                a.extend_from_slice(b.as_ref());
+                a
+            });
+        assert_eq!(encoded.len(), 2 + 32 + 4 + 64);
+        assert_eq!(&encoded[..2], &[0, 0]);
+
+        assert_eq!(
+            super::decode_collation_protocol_message(&encoded).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn declare_signature_payload_polkadot_compatible() {
+        // Payload as built by Polkadot for the `PeerId` of the Ed25519 public key `[1; 32]`.
+        let peer_id = crate::libp2p::peer_id::PublicKey::Ed25519([1; 32]).into_peer_id();
+        assert_eq!(
+            super::declare_signature_payload(&peer_id),
+            [
+                0x00, 0x24, 0x08, 0x01, 0x12, 0x20, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+                1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, b'C', b'O', b'L', b'L'
+            ]
+        );
+    }
+
+    #[test]
+    fn advertise_encode_decode() {
+        let message = super::CollationProtocolMessageRef::AdvertiseCollation {
+            relay_parent: &[5; 32],
+        };
+
+        let encoded =
+            super::build_collation_protocol_message(&message).fold(Vec::new(), |mut a, b| {
+                a.extend_from_slice(b.as_ref());
+                a
+            });
+        assert_eq!(&encoded[..2], &[0, 1]);
+
+        assert_eq!(
+            super::decode_collation_protocol_message(&encoded).unwrap(),
+            message
+        );
+    }
+
+    #[test]
+    fn fetching_response_encode_decode() {
+        let response = super::CollationFetchingResponse {
+            candidate_receipt: super::CandidateReceiptRef {
+                para_id: 1000,
+                relay_parent: &[1; 32],
+                collator: &[2; 32],
+                persisted_validation_data_hash: &[3; 32],
+                pov_hash: &[4; 32],
+                erasure_root: &[5; 32],
+                signature: &[6; 64],
+                para_head: &[7; 32],
+                validation_code_hash: &[8; 32],
+                commitments_hash: &[9; 32],
+            },
+            scale_encoded_pov: &[12, 1, 2, 3],
+        };
+
+        let encoded =
+            super::build_collation_fetching_response(&response).fold(Vec::new(), |mut a, b| {
+                a.extend_from_slice(b.as_ref());
+                a
+            });
+
+        assert_eq!(
+            super::decode_collation_fetching_response(&encoded).unwrap(),
+            response
+        );
+    }
+
+    #[test]
+    fn fetching_request_decode() {
+        let mut encoded = [3; 36];
+        encoded[32..].copy_from_slice(&2000u32.to_le_bytes());
+        assert_eq!(
+            super::decode_collation_fetching_request(&encoded).unwrap(),
+            super::CollationFetchingRequest {
+                relay_parent: [3; 32],
+                para_id: 2000,
+            }
+        );
+        assert!(super::decode_collation_fetching_request(&encoded[1..]).is_err());
+    }
+}
@@ -24,13 +24,18 @@ use alloc::vec::Vec;
 
 // See https://github.com/libp2p/specs/tree/master/kad-dht#rpc-messages for the protobuf format.
 
+// Values of the `type` field of the Kademlia messages.
+const MESSAGE_TY_PUT_VALUE: u64 = 0;
+const MESSAGE_TY_GET_VALUE: u64 = 1;
+const MESSAGE_TY_FIND_NODE: u64 = 4;
+
 /// Builds a wire message to send on the Kademlia request-response protocol to ask the target to
 /// return the nodes closest to the parameter.
 // TODO: parameter type?
 pub fn build_find_node_request(peer_id: &[u8]) -> Vec<u8> {
     // The capacity is arbitrary but large enough to avoid Vec reallocations.
     let mut out = Vec::with_capacity(64 + peer_id.len());
-    for slice in protobuf::enum_tag_encode(1, 4) {
+    for slice in protobuf::enum_tag_encode(1, MESSAGE_TY_FIND_NODE) {
         out.extend_from_slice(slice.as_ref());
     }
     for slice in protobuf::bytes_tag_encode(2, peer_id) {
@@ -55,7 +60,7 @@ pub fn decode_find_node_response(
     );
 
     let closer_peers = match nom::Finish::finish(parser(response_bytes)) {
-        Ok((_, out)) if out.response_ty.unwrap_or(0) == 4 => out.peers,
+        Ok((_, out)) if out.response_ty.unwrap_or(0) == MESSAGE_TY_FIND_NODE => out.peers,
         Ok((_, _)) => return Err(DecodeFindNodeResponseError::BadResponseTy),
         Err(_) => {
             return Err(DecodeFindNodeResponseError::ProtobufDecode(
@@ -96,6 +101,381 @@ pub enum DecodeFindNodeResponseError {
     BadMultiaddr(multiaddr::FromVecError),
 }
 
+/// Builds a wire message to send on the Kademlia request-response protocol to ask the target to
+/// return the record whose key is the parameter.
+pub fn build_get_value_request(key: &[u8]) -> Vec<u8> {
+    // The capacity is arbitrary but large enough to avoid Vec reallocations.
+    let mut out = Vec::with_capacity(64 + key.len());
+    for slice in protobuf::enum_tag_encode(1, MESSAGE_TY_GET_VALUE) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    for slice in protobuf::bytes_tag_encode(2, key) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    out
+}
+
+/// Decodes a response to a request built using [`build_get_value_request`].
+pub fn decode_get_value_response(
+    response_bytes: &[u8],
+) -> Result<GetValueResponse, DecodeGetValueResponseError> {
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[optional] response_ty = 1 => protobuf::enum_tag_decode,
+            #[optional] record = 3 => protobuf::message_tag_decode(protobuf::message_decode!{
+                #[optional] key = 1 => protobuf::bytes_tag_decode,
+                #[optional] value = 2 => protobuf::bytes_tag_decode,
+            }),
+            #[repeated(max = 1024)] peers = 8 => protobuf::message_tag_decode(protobuf::message_decode!{
+                #[required] peer_id = 1 => protobuf::bytes_tag_decode,
+                #[repeated(max = 1024)] addrs = 2 => protobuf::bytes_tag_decode,
+            }),
+        }),
+    );
+
+    let decoded = match nom::Finish::finish(parser(response_bytes)) {
+        Ok((_, out)) if out.response_ty.unwrap_or(0) == MESSAGE_TY_GET_VALUE => out,
+        Ok((_, _)) => return Err(DecodeGetValueResponseError::BadResponseTy),
+        Err(_) => {
+            return Err(DecodeGetValueResponseError::ProtobufDecode(
+                ProtobufDecodeError,
+            ))
+        }
+    };
+
+    let mut closer_peers = Vec::with_capacity(decoded.peers.len());
+    for peer in decoded.peers {
+        let peer_id = peer_id::PeerId::from_bytes(peer.peer_id.to_vec())
+            .map_err(|(err, _)| DecodeGetValueResponseError::BadPeerId(err))?;
+        let multiaddrs = peer.addrs.into_iter().map(|a| a.to_vec()).collect();
+        closer_peers.push((peer_id, multiaddrs));
+    }
+
+    Ok(GetValueResponse {
+        record: decoded.record.map(|record| KademliaRecord {
+            key: record.key.unwrap_or_default().to_vec(),
+            value: record.value.unwrap_or_default().to_vec(),
+        }),
+        closer_peers,
+    })
+}
+
+/// Decoded response to a request built using [`build_get_value_request`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetValueResponse {
+    /// Record found by the remote, if any.
+    pub record: Option<KademliaRecord>,
+    /// Nodes closest to the requested key that the remote knows about, and their addresses.
+    pub closer_peers: Vec<(peer_id::PeerId, Vec<Vec<u8>>)>,
+}
+
+/// Error potentially returned by [`decode_get_value_response`].
+#[derive(Debug, derive_more::Display)]
+pub enum DecodeGetValueResponseError {
+    /// Error while decoding the Protobuf encoding.
+    #[display(fmt = "Error decoding the response: {_0}")]
+    ProtobufDecode(ProtobufDecodeError),
+    /// Response isn't a response to a get value request.
+    BadResponseTy,
+    /// Error while parsing a [`peer_id::PeerId`] in the response.
+    #[display(fmt = "Invalid PeerId: {_0}")]
+    BadPeerId(peer_id::FromBytesError),
+}
+
+/// Builds a wire message to send on the Kademlia request-response protocol to ask the target to
+/// store the given record.
+pub fn build_put_value_request(key: &[u8], value: &[u8]) -> Vec<u8> {
+    // The capacity is arbitrary but large enough to avoid Vec reallocations.
+    let mut out = Vec::with_capacity(64 + key.len() * 2 + value.len());
+    for slice in protobuf::enum_tag_encode(1, MESSAGE_TY_PUT_VALUE) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    for slice in protobuf::bytes_tag_encode(2, key) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    for slice in protobuf::message_tag_encode(
+        3,
+        protobuf::bytes_tag_encode(1, key).chain(protobuf::bytes_tag_encode(2, value)),
+    ) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    out
+}
+
+/// Decodes a response to a request built using [`build_put_value_request`].
+///
+/// The remote is expected to echo the record that was sent, but its content isn't verified.
+pub fn decode_put_value_response(response_bytes: &[u8]) -> Result<(), DecodePutValueResponseError> {
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[optional] response_ty = 1 => protobuf::enum_tag_decode,
+        }),
+    );
+
+    match nom::Finish::finish(parser(response_bytes)) {
+        Ok((_, out)) if out.response_ty.unwrap_or(0) == MESSAGE_TY_PUT_VALUE => Ok(()),
+        Ok((_, _)) => Err(DecodePutValueResponseError::BadResponseTy),
+        Err(_) => Err(DecodePutValueResponseError::ProtobufDecode(
+            ProtobufDecodeError,
+        )),
+    }
+}
+
+/// Error potentially returned by [`decode_put_value_response`].
+#[derive(Debug, derive_more::Display)]
+pub enum DecodePutValueResponseError {
+    /// Error while decoding the Protobuf encoding.
+    #[display(fmt = "Error decoding the response: {_0}")]
+    ProtobufDecode(ProtobufDecodeError),
+    /// Response isn't a response to a put value request.
+    BadResponseTy,
+}
+
+/// Record stored in the Kademlia DHT.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KademliaRecord {
+    /// Key of the record.
+    pub key: Vec<u8>,
+    /// Value of the record.
+    pub value: Vec<u8>,
+}
+
+/// Request received on the Kademlia request-response protocol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KademliaRequest<'a> {
+    /// Remote asks for the nodes closest to the given key.
+    FindNode {
+        /// Key whose closest nodes must be returned.
+        key: &'a [u8],
+    },
+    /// Remote asks for the record whose key is the given key.
+    GetValue {
+        /// Key of the requested record.
+        key: &'a [u8],
+    },
+    /// Remote asks the local node to store a record.
+    PutValue {
+        /// Key of the record to store.
+        key: &'a [u8],
+        /// Value of the record to store.
+        value: &'a [u8],
+    },
+}
+
+/// Decodes a request received on the Kademlia request-response protocol.
+///
+/// Only `FIND_NODE`, `GET_VALUE` and `PUT_VALUE` requests are supported. Other requests, such
+/// as the ones related to providers, lead to [`DecodeKademliaRequestError::UnsupportedTy`].
+pub fn decode_kademlia_request(
+    request_bytes: &[u8],
+) -> Result<KademliaRequest<'_>, DecodeKademliaRequestError> {
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[optional] request_ty = 1 => protobuf::enum_tag_decode,
+            #[optional] key = 2 => protobuf::bytes_tag_decode,
+            #[optional] record = 3 => protobuf::message_tag_decode(protobuf::message_decode!{
+                #[optional] key = 1 => protobuf::bytes_tag_decode,
+                #[optional] value = 2 => protobuf::bytes_tag_decode,
+            }),
+        }),
+    );
+
+    let decoded = match nom::Finish::finish(parser(request_bytes)) {
+        Ok((_, out)) => out,
+        Err(_) => {
+            return Err(DecodeKademliaRequestError::ProtobufDecode(
+                ProtobufDecodeError,
+            ))
+        }
+    };
+
+    match decoded.request_ty.unwrap_or(0) {
+        MESSAGE_TY_FIND_NODE => Ok(KademliaRequest::FindNode {
+            key: decoded.key.ok_or(DecodeKademliaRequestError::MissingKey)?,
+        }),
+        MESSAGE_TY_GET_VALUE => Ok(KademliaRequest::GetValue {
+            key: decoded.key.ok_or(DecodeKademliaRequestError::MissingKey)?,
+        }),
+        MESSAGE_TY_PUT_VALUE => {
+            let record = decoded
+                .record
+                .ok_or(DecodeKademliaRequestError::MissingRecord)?;
+            Ok(KademliaRequest::PutValue {
+                key: record.key.ok_or(DecodeKademliaRequestError::MissingKey)?,
+                value: record.value.unwrap_or_default(),
+            })
+        }
+        ty => Err(DecodeKademliaRequestError::UnsupportedTy(ty)),
+    }
+}
+
+/// Error potentially returned by [`decode_kademlia_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum DecodeKademliaRequestError {
+    /// Error while decoding the Protobuf encoding.
+    #[display(fmt = "Error decoding the request: {_0}")]
+    ProtobufDecode(ProtobufDecodeError),
+    /// Type of request isn't supported.
+    #[display(fmt = "Unsupported request type: {_0}")]
+    UnsupportedTy(u64),
+    /// Request doesn't contain any key.
+    MissingKey,
+    /// `PUT_VALUE` request doesn't contain any record.
+    MissingRecord,
+}
+
+/// Builds the wire response to a `FIND_NODE` request.
+///
+/// `closer_peers` contains the list of nodes closest to the requested key, and their addresses.
+pub fn build_find_node_response(closer_peers: &[(peer_id::PeerId, Vec<Vec<u8>>)]) -> Vec<u8> {
+    // The capacity is arbitrary but large enough to avoid Vec reallocations.
+    let mut out = Vec::with_capacity(64 + closer_peers.len() * 128);
+    for slice in protobuf::enum_tag_encode(1, MESSAGE_TY_FIND_NODE) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    encode_closer_peers(&mut out, closer_peers);
+    out
+}
+
+/// Builds the wire response to a `GET_VALUE` request.
+///
+/// `value` must be `Some` if a record with the requested `key` is known locally. In addition,
+/// `closer_peers` contains the list of nodes closest to the requested key and their addresses.
+pub fn build_get_value_response(
+    key: &[u8],
+    value: Option<&[u8]>,
+    closer_peers: &[(peer_id::PeerId, Vec<Vec<u8>>)],
+) -> Vec<u8> {
+    // The capacity is arbitrary but large enough to avoid Vec reallocations.
+    let mut out = Vec::with_capacity(
+        64 + key.len() * 2 + value.map_or(0, |v| v.len()) + closer_peers.len() * 128,
+    );
+    for slice in protobuf::enum_tag_encode(1, MESSAGE_TY_GET_VALUE) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    for slice in protobuf::bytes_tag_encode(2, key) {
+        out.extend_from_slice(slice.as_ref());
+    }
+    if let Some(value) = value {
+        for slice in protobuf::message_tag_encode(
+            3,
+            protobuf::bytes_tag_encode(1, key).chain(protobuf::bytes_tag_encode(2, value)),
+        ) {
+            out.extend_from_slice(slice.as_ref());
+        }
+    }
+    encode_closer_peers(&mut out, closer_peers);
+    out
+}
+
+/// Builds the wire response to a `PUT_VALUE` request.
+///
+/// As defined by the libp2p specification, the response is identical to the request.
+pub fn build_put_value_response(key: &[u8], value: &[u8]) -> Vec<u8> {
+    build_put_value_request(key, value)
+}
+
+/// Appends to `out` the encoding of the `closerPeers` field of a Kademlia message.
+fn encode_closer_peers(out: &mut Vec<u8>, closer_peers: &[(peer_id::PeerId, Vec<Vec<u8>>)]) {
+    for (peer_id, addrs) in closer_peers {
+        let peer = protobuf::bytes_tag_encode(1, peer_id.as_bytes())
+            .map(either::Left)
+            .chain(
+                addrs
+                    .iter()
+                    .flat_map(|addr| protobuf::bytes_tag_encode(2, &addr[..]))
+                    .map(either::Right),
+            );
+
+        for slice in protobuf::message_tag_encode(8, peer) {
+            out.extend_from_slice(slice.as_ref());
+        }
+    }
+}
+
 /// Error while decoding the Protobuf encoding.
 #[derive(Debug, derive_more::Display)]
 pub struct ProtobufDecodeError;
+
+#[cfg(test)]
+mod tests {
+    use crate::libp2p::peer_id;
+
+    #[test]
+    fn find_node_request_decode() {
+        let key = [0xaa; 38];
+        let encoded = super::build_find_node_request(&key);
+        assert_eq!(
+            super::decode_kademlia_request(&encoded).unwrap(),
+            super::KademliaRequest::FindNode { key: &key }
+        );
+    }
+
+    #[test]
+    fn find_node_response_encode_decode() {
+        let peers = vec![
+            (
+                peer_id::PeerId::from_public_key(&peer_id::PublicKey::Ed25519([1; 32])),
+                vec![vec![4, 127, 0, 0, 1, 6, 0, 30]],
+            ),
+            (
+                peer_id::PeerId::from_public_key(&peer_id::PublicKey::Ed25519([2; 32])),
+                Vec::new(),
+            ),
+        ];
+
+        let encoded = super::build_find_node_response(&peers);
+        assert_eq!(super::decode_find_node_response(&encoded).unwrap(), peers);
+    }
+
+    #[test]
+    fn get_value_encode_decode() {
+        let encoded = super::build_get_value_request(b"foo");
+        assert_eq!(
+            super::decode_kademlia_request(&encoded).unwrap(),
+            super::KademliaRequest::GetValue { key: b"foo" }
+        );
+
+        let peers = vec![(
+            peer_id::PeerId::from_public_key(&peer_id::PublicKey::Ed25519([3; 32])),
+            vec![vec![4, 127, 0, 0, 1, 6, 0, 30]],
+        )];
+
+        let encoded = super::build_get_value_response(b"foo", Some(b"bar"), &peers);
+        assert_eq!(
+            super::decode_get_value_response(&encoded).unwrap(),
+            super::GetValueResponse {
+                record: Some(super::KademliaRecord {
+                    key: b"foo".to_vec(),
+                    value: b"bar".to_vec(),
+                }),
+                closer_peers: peers,
+            }
+        );
+
+        let encoded = super::build_get_value_response(b"foo", None, &[]);
+        assert_eq!(
+            super::decode_get_value_response(&encoded).unwrap(),
+            super::GetValueResponse {
+                record: None,
+                closer_peers: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn put_value_encode_decode() {
+        let encoded = super::build_put_value_request(b"foo", b"bar");
+        assert_eq!(
+            super::decode_kademlia_request(&encoded).unwrap(),
+            super::KademliaRequest::PutValue {
+                key: b"foo",
+                value: b"bar"
+            }
+        );
+
+        let encoded = super::build_put_value_response(b"foo", b"bar");
+        assert!(super::decode_put_value_response(&encoded).is_ok());
+        assert!(super::decode_get_value_response(&encoded).is_err());
+    }
+}
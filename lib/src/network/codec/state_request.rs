@@ -27,14 +27,13 @@
 //! a start key right after the last key of the response) in order to continue downloading the
 //! storage entries.
 //!
-//! The format of the response is a compact Merkle proof.
+//! The format of the response is normally a compact Merkle proof.
 //!
-//! > **Note**: The implementation in this module always requests a proof from the server.
-//! >           Substrate nodes also support a "no proof" mode where, instead of a proof, the list
-//! >           of entries are simply returned without any way to verify them. This alternative
-//! >           mode is supposed to be used only in situations where the peer the request is sent
-//! >           to is trusted. Because this "no proof" mode is very niche, the implementation in
-//! >           this module doesn't support it.
+//! Substrate nodes also support a "no proof" mode (see [`StateRequest::no_proof`]) where,
+//! instead of a proof, the list of entries are simply returned without any way to verify them
+//! individually. In this mode, the only way to verify the downloaded entries is to download the
+//! entire storage then calculate the state trie root hash and compare it with the value stored
+//! in the block's header.
 //!
 //! # About child tries
 //!
@@ -61,6 +60,10 @@ pub struct StateRequest<'a> {
     /// > **Note**: Because a response has a limited size, this field lets you send additional
     /// >           requests that start where the previous response has ended.
     pub start_key: StateRequestStart<'a>,
+
+    /// If `true`, the response contains the list of storage entries rather than a Merkle proof.
+    /// See [`StateResponse::entries`].
+    pub no_proof: bool,
 }
 
 /// See [`StateRequest::start_key`].
@@ -104,25 +107,153 @@ pub fn build_state_request(
         .map(either::Right)
         .map(either::Right)
         .chain(start.map(either::Left).map(either::Right))
-        .chain(protobuf::bool_tag_encode(3, false).map(either::Left))
+        .chain(protobuf::bool_tag_encode(3, config.no_proof).map(either::Left))
+}
+
+/// Decodes a state request.
+pub fn decode_state_request(request_bytes: &[u8]) -> Result<StateRequest, DecodeStateRequestError> {
+    let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
+        nom::combinator::complete(protobuf::message_decode! {
+            #[required] block_hash = 1 => protobuf::bytes_tag_decode,
+            #[repeated(max = 2)] start = 2 => protobuf::bytes_tag_decode,
+            #[optional] no_proof = 3 => protobuf::bool_tag_decode,
+        }),
+    );
+
+    let decoded = match nom::Finish::finish(parser(request_bytes)) {
+        Ok((_, rq)) => rq,
+        Err(_) => return Err(DecodeStateRequestError::ProtobufDecode),
+    };
+
+    Ok(StateRequest {
+        block_hash: <&[u8; 32]>::try_from(decoded.block_hash)
+            .map_err(|_| DecodeStateRequestError::InvalidBlockHashLength)?,
+        start_key: match &decoded.start[..] {
+            [] => StateRequestStart::MainTrie(&[]),
+            [key] => StateRequestStart::MainTrie(key),
+            [child_trie, key] => StateRequestStart::ChildTrieDefault {
+                child_trie: child_trie
+                    .strip_prefix(b":child_storage:default:")
+                    .ok_or(DecodeStateRequestError::InvalidChildTrie)?,
+                key,
+            },
+            _ => unreachable!(),
+        },
+        no_proof: decoded.no_proof.unwrap_or(false),
+    })
+}
+
+/// Error potentially returned by [`decode_state_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum DecodeStateRequestError {
+    /// Error while decoding the Protobuf encoding.
+    ProtobufDecode,
+    /// Hash of the block to make the request against isn't 32 bytes long.
+    InvalidBlockHashLength,
+    /// Start key of the request targets a child trie that isn't a default child trie.
+    InvalidChildTrie,
+}
+
+/// Decoded response to a state request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateResponse<'a> {
+    /// List of storage entries, grouped by trie. The main trie, if present, is always first.
+    ///
+    /// Expected to be empty if [`StateRequest::no_proof`] was `false`.
+    pub entries: Vec<StateResponseEntries<'a>>,
+
+    /// Compact Merkle proof containing the requested storage entries.
+    ///
+    /// Expected to be empty if [`StateRequest::no_proof`] was `true`.
+    pub proof: &'a [u8],
+}
+
+/// See [`StateResponse::entries`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateResponseEntries<'a> {
+    /// Empty for the main trie. For a child trie, contains the Merkle value of the root of the
+    /// child trie.
+    pub state_root: &'a [u8],
+
+    /// List of keys and their storage values, in lexicographic order of keys.
+    pub entries: Vec<(&'a [u8], &'a [u8])>,
+
+    /// `true` if no storage entry of this trie can be found after the last entry of
+    /// [`StateResponseEntries::entries`].
+    pub complete: bool,
+}
+
+/// Builds the bytes corresponding to a response to a state request.
+pub fn build_state_response(
+    response: StateResponse<'_>,
+) -> impl Iterator<Item = impl AsRef<[u8]> + '_> + '_ {
+    response
+        .entries
+        .into_iter()
+        .flat_map(|trie| {
+            protobuf::message_tag_encode(
+                1,
+                protobuf::bytes_tag_encode(1, trie.state_root)
+                    .map(either::Left)
+                    .chain(
+                        trie.entries
+                            .into_iter()
+                            .flat_map(|(key, value)| {
+                                protobuf::message_tag_encode(
+                                    2,
+                                    protobuf::bytes_tag_encode(1, key)
+                                        .chain(protobuf::bytes_tag_encode(2, value)),
+                                )
+                            })
+                            .map(either::Left)
+                            .chain(protobuf::bool_tag_encode(3, trie.complete).map(either::Right))
+                            .map(either::Right),
+                    ),
+            )
+        })
+        .map(either::Left)
+        .chain(protobuf::bytes_tag_encode(2, response.proof).map(either::Right))
 }
 
 /// Decodes a response to a state request.
-///
-/// On success, contains a Merkle proof.
-pub fn decode_state_response(response_bytes: &[u8]) -> Result<&[u8], DecodeStateResponseError> {
+pub fn decode_state_response(
+    response_bytes: &[u8],
+) -> Result<StateResponse, DecodeStateResponseError> {
     let mut parser = nom::combinator::all_consuming::<_, _, nom::error::Error<&[u8]>, _>(
         nom::combinator::complete(protobuf::message_decode! {
-            #[required] proof = 2 => protobuf::bytes_tag_decode,
+            #[repeated(max = usize::max_value())] entries = 1 => protobuf::message_tag_decode(protobuf::message_decode!{
+                #[optional] state_root = 1 => protobuf::bytes_tag_decode,
+                #[repeated(max = usize::max_value())] entries = 2 => protobuf::message_tag_decode(protobuf::message_decode!{
+                    #[optional] key = 1 => protobuf::bytes_tag_decode,
+                    #[optional] value = 2 => protobuf::bytes_tag_decode,
+                }),
+                #[optional] complete = 3 => protobuf::bool_tag_decode,
+            }),
+            #[optional] proof = 2 => protobuf::bytes_tag_decode,
         }),
     );
 
-    let proof = match nom::Finish::finish(parser(response_bytes)) {
-        Ok((_, proof)) => proof.proof,
+    let response = match nom::Finish::finish(parser(response_bytes)) {
+        Ok((_, response)) => response,
         Err(_) => return Err(DecodeStateResponseError::ProtobufDecode),
     };
 
-    Ok(proof)
+    Ok(StateResponse {
+        entries: response
+            .entries
+            .into_iter()
+            .map(|trie| StateResponseEntries {
+                state_root: trie.state_root.unwrap_or(&[]),
+                entries: trie
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry.key.unwrap_or(&[]), entry.value.unwrap_or(&[])))
+                    .collect(),
+                complete: trie.complete.unwrap_or(false),
+            })
+            .collect(),
+        proof: response.proof.unwrap_or(&[]),
+    })
 }
 
 /// Error potentially returned by [`decode_state_response`].
@@ -132,3 +263,75 @@ pub enum DecodeStateResponseError {
     /// Error while decoding the Protobuf encoding.
     ProtobufDecode,
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn request_encode_decode() {
+        for start_key in [
+            super::StateRequestStart::MainTrie(&[]),
+            super::StateRequestStart::MainTrie(&[1, 2, 3]),
+            super::StateRequestStart::ChildTrieDefault {
+                child_trie: &[4, 5],
+                key: &[6],
+            },
+        ] {
+            let request = super::StateRequest {
+                block_hash: &[0xaa; 32],
+                start_key,
+                no_proof: true,
+            };
+
+            let encoded =
+                super::build_state_request(request.clone()).fold(Vec::new(), |mut a, b| {
+                    a.extend_from_slice(b.as_ref());
+                    a
+                });
+
+            assert_eq!(super::decode_state_request(&encoded).unwrap(), request);
+        }
+    }
+
+    #[test]
+    fn response_encode_decode() {
+        let response = super::StateResponse {
+            entries: vec![
+                super::StateResponseEntries {
+                    state_root: &[],
+                    entries: vec![(&[1][..], &[2, 3][..]), (&[4][..], &[][..])],
+                    complete: false,
+                },
+                super::StateResponseEntries {
+                    state_root: &[0xbb; 32],
+                    entries: vec![(&[5][..], &[6][..])],
+                    complete: true,
+                },
+            ],
+            proof: &[],
+        };
+
+        let encoded = super::build_state_response(response.clone()).fold(Vec::new(), |mut a, b| {
+            a.extend_from_slice(b.as_ref());
+            a
+        });
+
+        assert_eq!(super::decode_state_response(&encoded).unwrap(), response);
+    }
+
+    #[test]
+    fn decode_entries_without_proof() {
+        let actual =
+            super::decode_state_response(&[10, 11, 18, 7, 10, 1, 1, 18, 2, 2, 3, 24, 1]).unwrap();
+
+        let expected = super::StateResponse {
+            entries: vec![super::StateResponseEntries {
+                state_root: &[],
+                entries: vec![(&[1][..], &[2, 3][..])],
+                complete: true,
+            }],
+            proof: &[],
+        };
+
+        assert_eq!(actual, expected);
+    }
+}
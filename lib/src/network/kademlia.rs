@@ -17,7 +17,9 @@
 
 // TODO: work in progress
 
+pub mod closest_peers_lookup;
 pub mod kbuckets;
+pub mod record_store;
 
 /// Data structure containing the k-buckets and the state of the current Kademlia queries.
 // TODO: unused
@@ -0,0 +1,348 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Iterative search for the peers of the network that are the closest to a key.
+//!
+//! The records of the Kademlia DHT are stored by the peers whose identity is the closest to the
+//! key of the record. No single node knows which peers these are. Instead, the local node
+//! starts by asking the peers of its k-buckets that are the closest to the key, which answer
+//! with the peers of their own k-buckets that are closer to the key, which are then asked in
+//! turn, and so on until no closer peer can be found.
+//!
+//! The [`ClosestPeersLookup`] doesn't perform any networking. It indicates which peers to send a
+//! request to, and must be informed of the outcome of these requests. The requests are
+//! typically `FIND_NODE` or `GET_VALUE` requests, as both return the peers closest to the key.
+//!
+//! # Usage
+//!
+//! Call [`ClosestPeersLookup::next_to_query`] in order to obtain the peers to send a request
+//! to, then call either [`ClosestPeersLookup::inject_response`] or
+//! [`ClosestPeersLookup::inject_failure`] once the request is finished. Repeat until
+//! [`ClosestPeersLookup::is_finished`] returns `true`, then use
+//! [`ClosestPeersLookup::closest_peers`].
+//!
+
+use alloc::collections::BTreeMap;
+use sha2::{Digest as _, Sha256};
+
+pub use crate::libp2p::PeerId;
+
+/// Configuration for a new [`ClosestPeersLookup`].
+#[derive(Debug, Clone)]
+pub struct Config<'a, TPeers> {
+    /// Key whose closest peers must be found.
+    pub key: &'a [u8],
+
+    /// Peers to start the search from, typically the peers of the k-buckets that are the closest
+    /// to the key.
+    pub initial_peers: TPeers,
+
+    /// Number of closest peers to find. Typically 20. Must be non-zero.
+    pub num_closest: usize,
+
+    /// Maximum number of requests that can be simultaneously in progress. Typically 3. Must be
+    /// non-zero.
+    pub parallelism: usize,
+}
+
+/// See the [module-level documentation](self).
+#[derive(Debug, Clone)]
+pub struct ClosestPeersLookup {
+    /// SHA-256 hash of the key whose closest peers must be found.
+    key_hashed: [u8; 32],
+
+    /// List of all the peers known by the search, indexed by the XOR distance between the
+    /// SHA-256 hash of their identity and [`ClosestPeersLookup::key_hashed`]. Peers whose request
+    /// has failed are kept in order to not query them again.
+    peers: BTreeMap<[u8; 32], (PeerId, PeerState)>,
+
+    /// Number of entries in [`ClosestPeersLookup::peers`] in the [`PeerState::InProgress`] state.
+    num_in_progress: usize,
+
+    /// See [`Config::num_closest`].
+    num_closest: usize,
+
+    /// See [`Config::parallelism`].
+    parallelism: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PeerState {
+    NotQueried,
+    InProgress,
+    Succeeded,
+    Failed,
+}
+
+impl ClosestPeersLookup {
+    /// Initializes a new search.
+    ///
+    /// # Panic
+    ///
+    /// Panics if [`Config::num_closest`] or [`Config::parallelism`] is 0.
+    ///
+    pub fn new(config: Config<impl Iterator<Item = PeerId>>) -> Self {
+        assert_ne!(config.num_closest, 0);
+        assert_ne!(config.parallelism, 0);
+
+        let mut lookup = ClosestPeersLookup {
+            key_hashed: Sha256::digest(config.key).into(),
+            peers: BTreeMap::new(),
+            num_in_progress: 0,
+            num_closest: config.num_closest,
+            parallelism: config.parallelism,
+        };
+
+        for peer_id in config.initial_peers {
+            lookup.insert_peer(peer_id);
+        }
+
+        lookup
+    }
+
+    /// Returns the next peer to send a request to, or `None` if no request should be started at
+    /// the moment. The peer is considered as having a request in progress until
+    /// [`ClosestPeersLookup::inject_response`] or [`ClosestPeersLookup::inject_failure`] is
+    /// called.
+    ///
+    /// `None` is returned if the maximum number of simultaneous requests is reached, or if all
+    /// the closest peers known so far have been or are being queried.
+    pub fn next_to_query(&mut self) -> Option<PeerId> {
+        if self.num_in_progress >= self.parallelism {
+            return None;
+        }
+
+        let (peer_id, state) = self
+            .peers
+            .values_mut()
+            .filter(|(_, state)| *state != PeerState::Failed)
+            .take(self.num_closest)
+            .find(|(_, state)| *state == PeerState::NotQueried)?;
+        *state = PeerState::InProgress;
+        self.num_in_progress += 1;
+        Some(peer_id.clone())
+    }
+
+    /// Reports the outcome of a successful request to a peer previously returned by
+    /// [`ClosestPeersLookup::next_to_query`]. `closer_peers` is the list of peers that the peer
+    /// has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if no request to this peer is in progress.
+    ///
+    pub fn inject_response(
+        &mut self,
+        peer_id: &PeerId,
+        closer_peers: impl Iterator<Item = PeerId>,
+    ) {
+        let distance = self.distance(peer_id);
+        let (_, state) = self.peers.get_mut(&distance).unwrap();
+        assert_eq!(*state, PeerState::InProgress);
+        *state = PeerState::Succeeded;
+        self.num_in_progress -= 1;
+
+        for peer_id in closer_peers {
+            self.insert_peer(peer_id);
+        }
+    }
+
+    /// Reports that a request to a peer previously returned by
+    /// [`ClosestPeersLookup::next_to_query`] has failed. The peer is no longer considered by the
+    /// search.
+    ///
+    /// # Panic
+    ///
+    /// Panics if no request to this peer is in progress.
+    ///
+    pub fn inject_failure(&mut self, peer_id: &PeerId) {
+        let distance = self.distance(peer_id);
+        let (_, state) = self.peers.get_mut(&distance).unwrap();
+        assert_eq!(*state, PeerState::InProgress);
+        *state = PeerState::Failed;
+        self.num_in_progress -= 1;
+    }
+
+    /// Returns `true` if the search is over, in other words if the closest peers known so far
+    /// have all successfully been queried.
+    pub fn is_finished(&self) -> bool {
+        self.num_in_progress == 0
+            && self
+                .peers
+                .values()
+                .filter(|(_, state)| *state != PeerState::Failed)
+                .take(self.num_closest)
+                .all(|(_, state)| *state == PeerState::Succeeded)
+    }
+
+    /// Returns the peers that have successfully been queried and that are the closest to the key,
+    /// ordered by increasing distance.
+    ///
+    /// The list is only final once [`ClosestPeersLookup::is_finished`] returns `true`.
+    pub fn closest_peers(&self) -> impl Iterator<Item = &PeerId> {
+        self.peers
+            .values()
+            .filter(|(_, state)| *state == PeerState::Succeeded)
+            .take(self.num_closest)
+            .map(|(peer_id, _)| peer_id)
+    }
+
+    /// Inserts a peer in the list of peers, if it isn't in the list yet.
+    fn insert_peer(&mut self, peer_id: PeerId) {
+        let distance = self.distance(&peer_id);
+        self.peers
+            .entry(distance)
+            .or_insert((peer_id, PeerState::NotQueried));
+    }
+
+    /// Returns the XOR distance between the given peer and the key.
+    fn distance(&self, peer_id: &PeerId) -> [u8; 32] {
+        let peer_hashed: [u8; 32] = Sha256::digest(peer_id.as_bytes()).into();
+        let mut distance = [0; 32];
+        for (n, byte) in distance.iter_mut().enumerate() {
+            *byte = peer_hashed[n] ^ self.key_hashed[n];
+        }
+        distance
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClosestPeersLookup, Config, PeerId};
+    use crate::libp2p::peer_id::PublicKey;
+    use alloc::collections::BTreeMap;
+    use sha2::{Digest as _, Sha256};
+
+    /// Simulated network where each peer knows a few peers at each distance from itself, like
+    /// in its k-buckets.
+    fn network(num_peers: u8) -> BTreeMap<PeerId, Vec<PeerId>> {
+        let peers = (0..num_peers)
+            .map(|n| PeerId::from_public_key(&PublicKey::Ed25519([n; 32])))
+            .collect::<Vec<_>>();
+
+        peers
+            .iter()
+            .map(|peer_id| {
+                let mut buckets = BTreeMap::<u32, Vec<PeerId>>::new();
+                for other in peers.iter().filter(|p| *p != peer_id) {
+                    let distance = xor(peer_id.as_bytes(), other.as_bytes());
+                    let bucket = distance.iter().position(|b| *b != 0).map_or(256, |n| {
+                        u32::try_from(n).unwrap() * 8 + distance[n].leading_zeros()
+                    });
+                    let bucket = buckets.entry(bucket).or_default();
+                    if bucket.len() < 3 {
+                        bucket.push(other.clone());
+                    }
+                }
+                (peer_id.clone(), buckets.into_values().flatten().collect())
+            })
+            .collect()
+    }
+
+    fn xor(a: &[u8], b: &[u8]) -> [u8; 32] {
+        let a: [u8; 32] = Sha256::digest(a).into();
+        let b: [u8; 32] = Sha256::digest(b).into();
+        let mut out = [0; 32];
+        for n in 0..32 {
+            out[n] = a[n] ^ b[n];
+        }
+        out
+    }
+
+    #[test]
+    fn finds_closest_peers() {
+        let network = network(200);
+        let key = b"some record key";
+
+        let mut expected = network.keys().cloned().collect::<Vec<_>>();
+        expected.sort_by_key(|p| xor(key, p.as_bytes()));
+        expected.truncate(4);
+
+        // Start from the peer that is the furthest away from the key.
+        let furthest = network
+            .keys()
+            .max_by_key(|p| xor(key, p.as_bytes()))
+            .unwrap()
+            .clone();
+        assert!(!expected.contains(&furthest));
+
+        let mut lookup = ClosestPeersLookup::new(Config {
+            key,
+            initial_peers: [furthest].into_iter(),
+            num_closest: 4,
+            parallelism: 3,
+        });
+
+        let mut num_requests = 0;
+        while !lookup.is_finished() {
+            let mut in_progress = Vec::new();
+            while let Some(peer_id) = lookup.next_to_query() {
+                in_progress.push(peer_id);
+            }
+            assert!(!in_progress.is_empty() && in_progress.len() <= 3);
+            for peer_id in in_progress {
+                num_requests += 1;
+                lookup.inject_response(&peer_id, network[&peer_id].iter().cloned());
+            }
+        }
+
+        assert_eq!(
+            lookup.closest_peers().cloned().collect::<Vec<_>>(),
+            expected
+        );
+        assert!(num_requests < 200);
+    }
+
+    #[test]
+    fn failed_peers_excluded() {
+        let network = network(50);
+        let key = b"foo";
+
+        let mut lookup = ClosestPeersLookup::new(Config {
+            key,
+            initial_peers: network.keys().cloned(),
+            num_closest: 3,
+            parallelism: 1,
+        });
+
+        // The closest peer fails to answer.
+        let closest = lookup.next_to_query().unwrap();
+        assert!(lookup.next_to_query().is_none());
+        lookup.inject_failure(&closest);
+
+        while let Some(peer_id) = lookup.next_to_query() {
+            lookup.inject_response(&peer_id, network[&peer_id].iter().cloned());
+        }
+
+        assert!(lookup.is_finished());
+        let found = lookup.closest_peers().cloned().collect::<Vec<_>>();
+        assert_eq!(found.len(), 3);
+        assert!(!found.contains(&closest));
+    }
+
+    #[test]
+    fn no_peer() {
+        let lookup = ClosestPeersLookup::new(Config {
+            key: b"foo",
+            initial_peers: core::iter::empty(),
+            num_closest: 20,
+            parallelism: 3,
+        });
+        assert!(lookup.is_finished());
+        assert_eq!(lookup.closest_peers().count(), 0);
+    }
+}
@@ -157,9 +157,12 @@ where
 
     /// Returns the list of entries in the k-buckets, ordered by increasing distance with the
     /// target.
-    pub fn closest_entries(&self, target: &K) -> impl Iterator<Item = (&K, &V)> {
+    ///
+    /// The target is passed in its bytes form, as it isn't necessarily a `K`. For example, the
+    /// key of a record stored in the Kademlia DHT can be any array of bytes.
+    pub fn closest_entries(&self, target: &[u8]) -> impl Iterator<Item = (&K, &V)> {
         // TODO: this is extremely unoptimized
-        let target_hashed = Key::new(target.as_ref());
+        let target_hashed = Key::new(target);
         let mut list = self.iter_ordered().collect::<Vec<_>>();
         list.sort_by_key(|(key, _)| {
             let key_hashed = Key::new(key.as_ref());
@@ -379,9 +382,12 @@ where
                 bucket.entries.insert(bucket.num_connected_entries, entry);
                 bucket.num_connected_entries += 1;
 
-                // If the peer we switch from disconnected to connected was the last one, reset
-                // the expiration.
-                if position == bucket.entries.capacity() - 1 {
+                // If the bucket now only contains connected entries, there is nothing to expire.
+                // Otherwise, if the peer we switch from disconnected to connected was the last
+                // one, reset the expiration.
+                if bucket.num_connected_entries == ENTRIES_PER_BUCKET {
+                    bucket.pending_entry = None;
+                } else if position == bucket.entries.capacity() - 1 {
                     debug_assert!(bucket.pending_entry.is_some());
                     bucket.pending_entry = Some(now.clone() + self.inner.pending_timeout);
                 }
@@ -601,5 +607,87 @@ mod tests {
         }
     }
 
+    #[test]
+    fn no_pending_entry_when_all_connected() {
+        let local_key = vec![0u8; 4];
+
+        // Iterator that generates random keys that are in the maximum size bucket.
+        let mut max_bucket_keys = {
+            let local_key_hash = Sha256::digest(&local_key);
+            (0..).map(move |_| loop {
+                let other_key: [u8; 32] = rand::random();
+                let other_key_hashed = Sha256::digest(other_key);
+                if ((local_key_hash[0] ^ other_key_hashed[0]) & 0x80) != 0 {
+                    break other_key.to_vec();
+                }
+            })
+        };
+
+        let mut buckets = super::KBuckets::<_, _, _, 4>::new(local_key, Duration::from_secs(1));
+
+        // Fill the bucket of maximum distance with disconnected nodes, then mark all of them as
+        // connected.
+        let keys = (0..4)
+            .map(|_| max_bucket_keys.next().unwrap())
+            .collect::<Vec<_>>();
+        for key in &keys {
+            match buckets.entry(key) {
+                super::Entry::Vacant(e) => {
+                    e.insert((), &Duration::new(0, 0), super::PeerState::Disconnected)
+                        .unwrap();
+                }
+                _ => panic!(),
+            }
+        }
+        for key in &keys {
+            buckets
+                .entry(key)
+                .into_occupied()
+                .unwrap()
+                .set_state(&Duration::new(0, 0), super::PeerState::Connected);
+        }
+
+        // The bucket only contains connected nodes. Inserting another node must fail, no matter
+        // the state of the new node and how much time has passed.
+        for state in [super::PeerState::Connected, super::PeerState::Disconnected] {
+            match buckets.entry(&max_bucket_keys.next().unwrap()) {
+                super::Entry::Vacant(e) => {
+                    assert!(matches!(
+                        e.insert((), &Duration::new(10, 0), state),
+                        Err(super::InsertError::Full)
+                    ));
+                }
+                _ => panic!(),
+            }
+        }
+    }
+
+    #[test]
+    fn closest_entries_arbitrary_target() {
+        let mut buckets = super::KBuckets::<_, _, _, 4>::new(vec![0u8; 4], Duration::from_secs(1));
+        for n in 1..=50u8 {
+            if let super::Entry::Vacant(e) = buckets.entry(&vec![n; 4]) {
+                let _ = e.insert((), &Duration::new(0, 0), super::PeerState::Disconnected);
+            }
+        }
+
+        // The target doesn't need to have the same format as the keys.
+        let target = b"some record key".to_vec();
+        let target_hashed = super::Key::new(&target);
+        let closest = buckets
+            .closest_entries(&target)
+            .map(|(key, _)| super::Key::new(key))
+            .collect::<Vec<_>>();
+
+        // Entries are ordered by the log2 of their distance to the target.
+        assert_eq!(closest.len(), buckets.iter_ordered().count());
+        for pair in closest.windows(2) {
+            assert!(
+                super::distance_log2(&pair[0], &target_hashed)
+                    <= super::distance_log2(&pair[1], &target_hashed)
+            );
+        }
+    }
+
     // TODO: a lot of tests
 }
@@ -0,0 +1,312 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Storage for the records of the Kademlia DHT.
+//!
+//! The [`RecordStore`] contains the records that remotes have asked the local node to store
+//! through `PUT_VALUE` requests, and that are served back through `GET_VALUE` requests.
+//!
+//! Each record expires after a configurable amount of time. Remotes are expected to periodically
+//! republish the records they care about.
+//!
+//! The number of records and the size of the key and value of each record are bounded,
+//! guaranteeing that the data structure only uses a bounded amount of memory. If the maximum number of records is reached,
+//! the record that is the closest to its expiration is removed in order to make space.
+//!
+
+use alloc::{
+    collections::{BTreeMap, BTreeSet},
+    vec::Vec,
+};
+use core::{ops::Add, time::Duration};
+
+/// Configuration passed to [`RecordStore::new`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Maximum number of records simultaneously stored.
+    pub max_records: usize,
+
+    /// Maximum size, in bytes, of the key of a record. Records whose key is larger are refused.
+    pub max_key_size: usize,
+
+    /// Maximum size, in bytes, of the value of a record. Records whose value is larger are
+    /// refused.
+    pub max_record_size: usize,
+
+    /// Duration after which a record is removed, unless it is inserted again.
+    pub record_ttl: Duration,
+}
+
+/// Bounded collection of Kademlia records.
+#[derive(Debug)]
+pub struct RecordStore<TNow> {
+    /// List of all records, indexed by key.
+    records: BTreeMap<Vec<u8>, Record<TNow>>,
+
+    /// Same entries as [`RecordStore::records`], ordered by expiration.
+    by_expiration: BTreeSet<(TNow, Vec<u8>)>,
+
+    /// See [`Config::max_records`].
+    max_records: usize,
+
+    /// See [`Config::max_key_size`].
+    max_key_size: usize,
+
+    /// See [`Config::max_record_size`].
+    max_record_size: usize,
+
+    /// See [`Config::record_ttl`].
+    record_ttl: Duration,
+}
+
+#[derive(Debug)]
+struct Record<TNow> {
+    value: Vec<u8>,
+    expiration: TNow,
+}
+
+impl<TNow> RecordStore<TNow>
+where
+    TNow: Clone + Add<Duration, Output = TNow> + Ord,
+{
+    /// Creates a new empty [`RecordStore`].
+    pub fn new(config: Config) -> Self {
+        RecordStore {
+            records: BTreeMap::new(),
+            by_expiration: BTreeSet::new(),
+            max_records: config.max_records,
+            max_key_size: config.max_key_size,
+            max_record_size: config.max_record_size,
+            record_ttl: config.record_ttl,
+        }
+    }
+
+    /// Returns the number of records in the store, including the ones that have expired but
+    /// haven't been purged yet.
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    /// Returns `true` if the store doesn't contain any record.
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// Inserts a record in the store, or updates the value of an existing record. The expiration
+    /// of the record is reset.
+    ///
+    /// If the store is full, the record that is the closest to its expiration is removed.
+    pub fn put(&mut self, key: Vec<u8>, value: Vec<u8>, now: &TNow) -> Result<(), PutError> {
+        if key.len() > self.max_key_size {
+            return Err(PutError::KeyTooLarge);
+        }
+
+        if value.len() > self.max_record_size {
+            return Err(PutError::RecordTooLarge);
+        }
+
+        if self.max_records == 0 {
+            return Err(PutError::StoreFull);
+        }
+
+        let expiration = now.clone() + self.record_ttl;
+
+        if let Some(record) = self.records.get_mut(&key) {
+            let _was_in = self
+                .by_expiration
+                .remove(&(record.expiration.clone(), key.clone()));
+            debug_assert!(_was_in);
+            record.value = value;
+            record.expiration = expiration.clone();
+            self.by_expiration.insert((expiration, key));
+            return Ok(());
+        }
+
+        if self.records.len() >= self.max_records {
+            let (_, key_to_remove) = self.by_expiration.pop_first().unwrap();
+            let _was_in = self.records.remove(&key_to_remove);
+            debug_assert!(_was_in.is_some());
+        }
+
+        self.by_expiration.insert((expiration.clone(), key.clone()));
+        self.records.insert(key, Record { value, expiration });
+        Ok(())
+    }
+
+    /// Returns the value of the record with the given key, or `None` if it isn't in the store or
+    /// has expired.
+    pub fn get(&self, key: &[u8], now: &TNow) -> Option<&[u8]> {
+        let record = self.records.get(key)?;
+        if record.expiration <= *now {
+            return None;
+        }
+        Some(&record.value)
+    }
+
+    /// Removes the record with the given key from the store. Returns its value, if any.
+    pub fn remove(&mut self, key: &[u8]) -> Option<Vec<u8>> {
+        let (key, record) = self.records.remove_entry(key)?;
+        let _was_in = self.by_expiration.remove(&(record.expiration, key));
+        debug_assert!(_was_in);
+        Some(record.value)
+    }
+
+    /// Removes from the store all the records that have expired.
+    pub fn purge_expired(&mut self, now: &TNow) {
+        while let Some((expiration, _)) = self.by_expiration.first() {
+            if *expiration > *now {
+                break;
+            }
+
+            let (_, key) = self.by_expiration.pop_first().unwrap();
+            let _was_in = self.records.remove(&key);
+            debug_assert!(_was_in.is_some());
+        }
+    }
+
+    /// Returns the list of all the records in the store that haven't expired.
+    pub fn iter<'a>(&'a self, now: &'a TNow) -> impl Iterator<Item = (&'a [u8], &'a [u8])> + 'a {
+        self.records
+            .iter()
+            .filter(move |(_, record)| record.expiration > *now)
+            .map(|(key, record)| (&key[..], &record.value[..]))
+    }
+}
+
+/// Error potentially returned by [`RecordStore::put`].
+#[derive(Debug, Clone, derive_more::Display)]
+pub enum PutError {
+    /// Key of the record is larger than [`Config::max_key_size`].
+    KeyTooLarge,
+    /// Value of the record is larger than [`Config::max_record_size`].
+    RecordTooLarge,
+    /// [`Config::max_records`] is zero.
+    StoreFull,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, PutError, RecordStore};
+    use core::time::Duration;
+
+    fn store(max_records: usize) -> RecordStore<Duration> {
+        RecordStore::new(Config {
+            max_records,
+            max_key_size: 4,
+            max_record_size: 8,
+            record_ttl: Duration::from_secs(10),
+        })
+    }
+
+    #[test]
+    fn put_get_remove() {
+        let mut store = store(4);
+        let now = Duration::from_secs(0);
+        store.put(b"foo".to_vec(), b"bar".to_vec(), &now).unwrap();
+        assert_eq!(store.get(b"foo", &now), Some(&b"bar"[..]));
+        assert_eq!(store.get(b"baz", &now), None);
+
+        store.put(b"foo".to_vec(), b"qux".to_vec(), &now).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get(b"foo", &now), Some(&b"qux"[..]));
+
+        assert_eq!(store.remove(b"foo"), Some(b"qux".to_vec()));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn record_too_large() {
+        let mut store = store(4);
+        assert!(matches!(
+            store.put(b"foo".to_vec(), vec![0; 9], &Duration::from_secs(0)),
+            Err(PutError::RecordTooLarge)
+        ));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn key_too_large() {
+        let mut store = store(4);
+        assert!(matches!(
+            store.put(vec![0; 5], b"bar".to_vec(), &Duration::from_secs(0)),
+            Err(PutError::KeyTooLarge)
+        ));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn store_full() {
+        let mut store = store(0);
+        assert!(matches!(
+            store.put(b"foo".to_vec(), b"bar".to_vec(), &Duration::from_secs(0)),
+            Err(PutError::StoreFull)
+        ));
+    }
+
+    #[test]
+    fn count_bounded() {
+        let mut store = store(3);
+        for n in 0..100u8 {
+            store
+                .put(vec![n], vec![n], &Duration::from_secs(u64::from(n)))
+                .unwrap();
+            assert!(store.len() <= 3);
+        }
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.by_expiration.len(), 3);
+    }
+
+    #[test]
+    fn expiration() {
+        let mut store = store(4);
+        store
+            .put(b"foo".to_vec(), b"bar".to_vec(), &Duration::from_secs(0))
+            .unwrap();
+        store
+            .put(b"baz".to_vec(), b"bar".to_vec(), &Duration::from_secs(5))
+            .unwrap();
+
+        assert!(store.get(b"foo", &Duration::from_secs(10)).is_none());
+        assert!(store.get(b"baz", &Duration::from_secs(10)).is_some());
+        assert_eq!(store.iter(&Duration::from_secs(10)).count(), 1);
+
+        store.purge_expired(&Duration::from_secs(10));
+        assert_eq!(store.len(), 1);
+        store.purge_expired(&Duration::from_secs(15));
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn full_store_evicts_oldest() {
+        let mut store = store(2);
+        store
+            .put(b"a".to_vec(), b"1".to_vec(), &Duration::from_secs(0))
+            .unwrap();
+        store
+            .put(b"b".to_vec(), b"2".to_vec(), &Duration::from_secs(1))
+            .unwrap();
+        store
+            .put(b"c".to_vec(), b"3".to_vec(), &Duration::from_secs(2))
+            .unwrap();
+
+        let now = Duration::from_secs(2);
+        assert_eq!(store.len(), 2);
+        assert!(store.get(b"a", &now).is_none());
+        assert!(store.get(b"b", &now).is_some());
+        assert!(store.get(b"c", &now).is_some());
+    }
+}
@@ -0,0 +1,211 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Aggregation of the addresses of the local node as observed by remotes.
+//!
+//! When answering an identify request, remotes indicate the address of the local node as seen
+//! from their side of the connection. This is the only way for a node located behind a NAT to
+//! learn about its public address.
+//!
+//! Since remotes can lie, a single report isn't enough to trust an address. The
+//! [`ObservedAddresses`] keeps track of the latest address reported by each remote, and
+//! considers an address as confirmed only once it has been reported by a certain number of
+//! distinct remotes.
+//!
+//! The number of remotes whose report is remembered is bounded. If the limit is reached, the
+//! oldest report is discarded.
+//!
+//! Addresses are represented as opaque bytes, and it is the responsibility of the API user to
+//! decode them.
+
+use alloc::{
+    collections::{btree_map, BTreeMap, VecDeque},
+    vec::Vec,
+};
+
+pub use crate::libp2p::PeerId;
+
+/// Configuration for a new [`ObservedAddresses`].
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Maximum number of remotes whose report is remembered.
+    pub max_reporters: usize,
+
+    /// Number of distinct remotes that must have reported an address for this address to be
+    /// considered as confirmed. Must be non-zero.
+    pub confirmation_threshold: usize,
+}
+
+/// See the [module-level documentation](self).
+#[derive(Debug, Clone)]
+pub struct ObservedAddresses {
+    /// List of reports, ordered from the oldest to the most recent. Each remote appears at most
+    /// once in this list.
+    reports: VecDeque<(PeerId, Vec<u8>)>,
+
+    /// For each address found in [`ObservedAddresses::reports`], the number of remotes that have
+    /// reported it. Never contains any zero.
+    num_reporters: BTreeMap<Vec<u8>, usize>,
+
+    /// See [`Config::max_reporters`].
+    max_reporters: usize,
+
+    /// See [`Config::confirmation_threshold`].
+    confirmation_threshold: usize,
+}
+
+impl ObservedAddresses {
+    /// Creates a new empty collection.
+    ///
+    /// # Panic
+    ///
+    /// Panics if [`Config::confirmation_threshold`] is 0.
+    ///
+    pub fn new(config: Config) -> Self {
+        assert_ne!(config.confirmation_threshold, 0);
+
+        ObservedAddresses {
+            reports: VecDeque::with_capacity(config.max_reporters),
+            num_reporters: BTreeMap::new(),
+            max_reporters: config.max_reporters,
+            confirmation_threshold: config.confirmation_threshold,
+        }
+    }
+
+    /// Records the fact that the given remote has observed the local node under the given
+    /// address. Replaces any address previously reported by this remote.
+    ///
+    /// Returns `true` if the address wasn't confirmed before this call and is confirmed after.
+    pub fn insert(&mut self, reporter: PeerId, address: Vec<u8>) -> bool {
+        if let Some(position) = self.reports.iter().position(|(p, _)| *p == reporter) {
+            let (_, previous_address) = self.reports.remove(position).unwrap();
+            self.decrease_num_reporters(previous_address);
+        }
+
+        if self.max_reporters == 0 {
+            return false;
+        }
+
+        while self.reports.len() >= self.max_reporters {
+            let (_, oldest_address) = self.reports.pop_front().unwrap();
+            self.decrease_num_reporters(oldest_address);
+        }
+
+        let num_reporters = self.num_reporters.entry(address.clone()).or_insert(0);
+        *num_reporters += 1;
+        let newly_confirmed = *num_reporters == self.confirmation_threshold;
+
+        self.reports.push_back((reporter, address));
+        newly_confirmed
+    }
+
+    /// Returns the list of addresses that have been reported by at least
+    /// [`Config::confirmation_threshold`] distinct remotes.
+    pub fn confirmed_addresses(&self) -> impl Iterator<Item = &[u8]> {
+        self.num_reporters
+            .iter()
+            .filter(|(_, n)| **n >= self.confirmation_threshold)
+            .map(|(addr, _)| &addr[..])
+    }
+
+    /// Returns `true` if the given address has been reported by at least
+    /// [`Config::confirmation_threshold`] distinct remotes.
+    pub fn is_confirmed(&self, address: &[u8]) -> bool {
+        self.num_reporters
+            .get(address)
+            .map_or(false, |n| *n >= self.confirmation_threshold)
+    }
+
+    fn decrease_num_reporters(&mut self, address: Vec<u8>) {
+        let btree_map::Entry::Occupied(mut entry) = self.num_reporters.entry(address) else {
+            unreachable!()
+        };
+        *entry.get_mut() -= 1;
+        if *entry.get() == 0 {
+            entry.remove();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ObservedAddresses, PeerId};
+    use crate::libp2p::peer_id::PublicKey;
+
+    fn peer(n: u8) -> PeerId {
+        PeerId::from_public_key(&PublicKey::Ed25519([n; 32]))
+    }
+
+    #[test]
+    fn confirmed_after_threshold() {
+        let mut addresses = ObservedAddresses::new(Config {
+            max_reporters: 16,
+            confirmation_threshold: 2,
+        });
+
+        assert!(!addresses.insert(peer(0), b"foo".to_vec()));
+        assert!(!addresses.is_confirmed(b"foo"));
+
+        // Reporting the same address twice from the same remote doesn't confirm it.
+        assert!(!addresses.insert(peer(0), b"foo".to_vec()));
+        assert!(!addresses.is_confirmed(b"foo"));
+
+        assert!(addresses.insert(peer(1), b"foo".to_vec()));
+        assert!(addresses.is_confirmed(b"foo"));
+        assert!(!addresses.insert(peer(2), b"foo".to_vec()));
+        assert_eq!(
+            addresses.confirmed_addresses().collect::<Vec<_>>(),
+            vec![&b"foo"[..]]
+        );
+    }
+
+    #[test]
+    fn new_report_replaces_previous() {
+        let mut addresses = ObservedAddresses::new(Config {
+            max_reporters: 16,
+            confirmation_threshold: 2,
+        });
+
+        addresses.insert(peer(0), b"foo".to_vec());
+        addresses.insert(peer(1), b"foo".to_vec());
+        assert!(addresses.is_confirmed(b"foo"));
+
+        addresses.insert(peer(1), b"bar".to_vec());
+        assert!(!addresses.is_confirmed(b"foo"));
+        assert!(!addresses.is_confirmed(b"bar"));
+        assert_eq!(addresses.confirmed_addresses().count(), 0);
+    }
+
+    #[test]
+    fn oldest_reports_discarded() {
+        let mut addresses = ObservedAddresses::new(Config {
+            max_reporters: 2,
+            confirmation_threshold: 2,
+        });
+
+        addresses.insert(peer(0), b"foo".to_vec());
+        addresses.insert(peer(1), b"foo".to_vec());
+        assert!(addresses.is_confirmed(b"foo"));
+
+        addresses.insert(peer(2), b"bar".to_vec());
+        assert!(!addresses.is_confirmed(b"foo"));
+
+        assert!(addresses.insert(peer(3), b"bar".to_vec()));
+        assert!(addresses.is_confirmed(b"bar"));
+        assert!(!addresses.is_confirmed(b"foo"));
+    }
+}
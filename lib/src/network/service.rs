@@ -80,11 +80,16 @@ use crate::libp2p::collection;
 use crate::network::codec;
 use crate::util::{self, SipHasherBuild};
 
-use alloc::{borrow::ToOwned as _, collections::BTreeSet, string::String, vec::Vec};
+use alloc::{
+    borrow::ToOwned as _,
+    collections::BTreeSet,
+    string::String,
+    vec::{self, Vec},
+};
 use core::{
     fmt,
     hash::Hash,
-    iter, mem,
+    mem,
     ops::{self, Add, Sub},
     time::Duration,
 };
@@ -103,6 +108,8 @@ pub use crate::libp2p::{
 
 pub use crate::network::codec::{BlockAnnouncesHandshakeDecodeError, Role};
 
+mod tests;
+
 /// Configuration for a [`ChainNetwork`].
 pub struct Config {
     /// Capacity to initially reserve to the list of connections.
@@ -151,6 +158,20 @@ pub struct ChainConfig<TChain> {
     /// `true` if incoming block requests are allowed.
     pub allow_inbound_block_requests: bool,
 
+    /// `true` if incoming state requests are allowed. See [`Event::StateRequestIn`].
+    pub allow_inbound_state_requests: bool,
+
+    /// `true` if incoming Kademlia requests are allowed. In other words, if the local node acts
+    /// as a server of the Kademlia DHT of this chain. See [`Event::KademliaFindNodeRequestIn`],
+    /// [`Event::KademliaGetValueRequestIn`] and [`Event::KademliaPutValueRequestIn`].
+    pub allow_inbound_kademlia_requests: bool,
+
+    /// `true` if the collation and collation fetching protocols are supported on this chain.
+    ///
+    /// This should be `true` only if the local node is a collator of a parachain and this chain
+    /// is its relay chain. See [`ChainNetwork::collation_open`].
+    pub allow_collation_protocols: bool,
+
     /// Hash of the best block according to the local node.
     pub best_hash: [u8; 32],
     /// Height of the best block according to the local node.
@@ -224,6 +245,12 @@ pub struct ChainNetwork<TChain, TNow> {
     // TODO: shrink to fit from time to time
     opened_gossip_undesired:
         hashbrown::HashSet<(ChainId, PeerId, GossipKind), util::SipHasherBuild>,
+
+    /// Substreams of [`ChainNetwork::inner`] that belonged to a chain that has been removed with
+    /// [`ChainNetwork::remove_chain`] and that couldn't be closed immediately. Events concerning
+    /// these substreams are silently discarded.
+    // TODO: shrink to fit from time to time
+    orphan_substreams: hashbrown::HashSet<SubstreamId, fnv::FnvBuildHasher>,
 }
 
 struct Chain<TChain> {
@@ -249,6 +276,15 @@ struct Chain<TChain> {
     /// See [`ChainConfig::allow_inbound_block_requests`].
     allow_inbound_block_requests: bool,
 
+    /// See [`ChainConfig::allow_inbound_state_requests`].
+    allow_inbound_state_requests: bool,
+
+    /// See [`ChainConfig::allow_inbound_kademlia_requests`].
+    allow_inbound_kademlia_requests: bool,
+
+    /// See [`ChainConfig::allow_collation_protocols`].
+    allow_collation_protocols: bool,
+
     /// See [`ChainConfig::user_data`].
     user_data: TChain,
 }
@@ -288,8 +324,12 @@ enum Protocol {
     LightStorage { chain_index: usize },
     LightCall { chain_index: usize },
     Kad { chain_index: usize },
+    KadGetValue { chain_index: usize },
+    KadPutValue { chain_index: usize },
     SyncWarp { chain_index: usize },
     State { chain_index: usize },
+    Collation { chain_index: usize },
+    CollationFetching { chain_index: usize },
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
@@ -297,6 +337,31 @@ enum NotificationsProtocol {
     BlockAnnounces { chain_index: usize },
     Transactions { chain_index: usize },
     Grandpa { chain_index: usize },
+    Collation { chain_index: usize },
+}
+
+impl Protocol {
+    /// Returns the index of the chain this protocol belongs to, or `None` if the protocol isn't
+    /// specific to a chain.
+    fn chain_index(&self) -> Option<usize> {
+        match *self {
+            Protocol::Identify | Protocol::Ping => None,
+            Protocol::BlockAnnounces { chain_index }
+            | Protocol::Transactions { chain_index }
+            | Protocol::Grandpa { chain_index }
+            | Protocol::Sync { chain_index }
+            | Protocol::LightUnknown { chain_index }
+            | Protocol::LightStorage { chain_index }
+            | Protocol::LightCall { chain_index }
+            | Protocol::Kad { chain_index }
+            | Protocol::KadGetValue { chain_index }
+            | Protocol::KadPutValue { chain_index }
+            | Protocol::SyncWarp { chain_index }
+            | Protocol::State { chain_index }
+            | Protocol::Collation { chain_index }
+            | Protocol::CollationFetching { chain_index } => Some(chain_index),
+        }
+    }
 }
 
 impl TryFrom<Protocol> for NotificationsProtocol {
@@ -311,6 +376,9 @@ impl TryFrom<Protocol> for NotificationsProtocol {
                 Ok(NotificationsProtocol::Transactions { chain_index })
             }
             Protocol::Grandpa { chain_index } => Ok(NotificationsProtocol::Grandpa { chain_index }),
+            Protocol::Collation { chain_index } => {
+                Ok(NotificationsProtocol::Collation { chain_index })
+            }
             Protocol::Identify => Err(()),
             Protocol::Ping => Err(()),
             Protocol::Sync { .. } => Err(()),
@@ -318,8 +386,11 @@ impl TryFrom<Protocol> for NotificationsProtocol {
             Protocol::LightStorage { .. } => Err(()),
             Protocol::LightCall { .. } => Err(()),
             Protocol::Kad { .. } => Err(()),
+            Protocol::KadGetValue { .. } => Err(()),
+            Protocol::KadPutValue { .. } => Err(()),
             Protocol::SyncWarp { .. } => Err(()),
             Protocol::State { .. } => Err(()),
+            Protocol::CollationFetching { .. } => Err(()),
         }
     }
 }
@@ -398,6 +469,7 @@ where
                     seed
                 }),
             ),
+            orphan_substreams: hashbrown::HashSet::with_capacity_and_hasher(0, Default::default()),
             chains: slab::Slab::with_capacity(config.chains_capacity),
             chains_by_protocol_info: hashbrown::HashMap::with_capacity_and_hasher(
                 config.chains_capacity,
@@ -436,6 +508,9 @@ where
             best_hash: config.best_hash,
             best_number: config.best_number,
             allow_inbound_block_requests: config.allow_inbound_block_requests,
+            allow_inbound_state_requests: config.allow_inbound_state_requests,
+            allow_inbound_kademlia_requests: config.allow_inbound_kademlia_requests,
+            allow_collation_protocols: config.allow_collation_protocols,
             grandpa_protocol_config: config.grandpa_protocol_config,
             user_data: config.user_data,
         });
@@ -443,7 +518,131 @@ where
         Ok(ChainId(chain_id))
     }
 
-    // TODO: add `fn remove_chain(&mut self, chain_id: ChainId)` but the behavior w.r.t. closing that chain's substreams is tricky
+    /// Removes a chain from the list of chains that is handled by the [`ChainNetwork`].
+    ///
+    /// All the peers are removed from the list of desired peers of this chain, and all the
+    /// notification substreams of this chain are closed. No event is generated concerning these
+    /// substreams. Connections are left untouched, as they might be used by other chains.
+    ///
+    /// The requests of this chain that have been started and whose response hasn't been yielded
+    /// yet are cancelled, and are returned in [`RemovedChain::cancelled_requests`]. No
+    /// [`Event::RequestResult`] will be generated for them.
+    ///
+    /// The incoming requests of this chain that have been yielded in an event and that haven't
+    /// been answered yet are answered with an error, and are returned in
+    /// [`RemovedChain::answered_requests_in`]. They must no longer be answered by the API user.
+    ///
+    /// The remote is asked to close the inbound notification substreams of this chain that are
+    /// open. Notifications received on these substreams are silently discarded until the remote
+    /// has closed them.
+    ///
+    /// This function might generate messages destined to connections. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process these messages after it has
+    /// returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn remove_chain(&mut self, chain_id: ChainId) -> RemovedChain<TChain> {
+        assert!(self.chains.contains(chain_id.0));
+
+        // Unmark all the desired peers of this chain.
+        let desired = self
+            .gossip_desired_peers_by_chain
+            .iter()
+            .filter(|(chain_index, _, _)| *chain_index == chain_id.0)
+            .map(|(_, kind, peer_id)| (*kind, peer_id.clone()))
+            .collect::<Vec<_>>();
+        for (kind, peer_id) in desired {
+            let _was_desired = self.gossip_remove_desired(chain_id, &peer_id, kind);
+            debug_assert!(_was_desired);
+        }
+
+        // Close all the notification substreams of this chain.
+        let notification_substreams = self
+            .notification_substreams_by_peer_id
+            .iter()
+            .filter(|(protocol, ..)| {
+                let chain_index = match *protocol {
+                    NotificationsProtocol::BlockAnnounces { chain_index } => chain_index,
+                    NotificationsProtocol::Transactions { chain_index } => chain_index,
+                    NotificationsProtocol::Grandpa { chain_index } => chain_index,
+                    NotificationsProtocol::Collation { chain_index } => chain_index,
+                };
+                chain_index == chain_id.0
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for entry in notification_substreams {
+            let (direction, state, substream_id) = (entry.2, entry.3, entry.4);
+            let _was_in = self.notification_substreams_by_peer_id.remove(&entry);
+            debug_assert!(_was_in);
+            let _was_in = self.substreams.remove(&substream_id);
+            debug_assert!(_was_in.is_some());
+
+            match (direction, state) {
+                (SubstreamDirection::Out, _) => self.inner.close_out_notifications(substream_id),
+                (SubstreamDirection::In, NotificationsSubstreamState::Pending) => {
+                    self.inner.reject_in_notifications(substream_id)
+                }
+                (SubstreamDirection::In, NotificationsSubstreamState::Open) => {
+                    // The substream is kept as orphan until the remote has closed it.
+                    self.inner.start_close_in_notifications(substream_id);
+                    let _was_inserted = self.orphan_substreams.insert(substream_id);
+                    debug_assert!(_was_inserted);
+                }
+            }
+        }
+
+        // The remaining substreams of this chain are requests, or inbound substreams whose
+        // protocol has been negotiated but that haven't been reported yet.
+        let other_substreams = self
+            .substreams
+            .iter()
+            .filter(|(_, info)| info.protocol.chain_index() == Some(chain_id.0))
+            .map(|(substream_id, info)| (*substream_id, info.connection_id))
+            .collect::<Vec<_>>();
+        let mut cancelled_requests = Vec::new();
+        let mut answered_requests_in = Vec::new();
+        for (substream_id, connection_id) in other_substreams {
+            let _was_in = self.substreams.remove(&substream_id);
+            debug_assert!(_was_in.is_some());
+
+            if self.inner.is_pending_ingoing_request(substream_id) {
+                self.inner.respond_in_request(substream_id, Err(()));
+                answered_requests_in.push(substream_id);
+                continue;
+            }
+
+            if self
+                .inner
+                .is_pending_outgoing_request(connection_id, substream_id)
+            {
+                cancelled_requests.push(substream_id);
+            }
+
+            let _was_inserted = self.orphan_substreams.insert(substream_id);
+            debug_assert!(_was_inserted);
+        }
+
+        self.connected_unopened_gossip_desired
+            .retain(|(_, c, _)| *c != chain_id);
+        self.opened_gossip_undesired
+            .retain(|(c, _, _)| *c != chain_id);
+
+        let chain = self.chains.remove(chain_id.0);
+        let _was_in = self
+            .chains_by_protocol_info
+            .remove(&(chain.genesis_hash, chain.fork_id));
+        debug_assert!(_was_in.is_some());
+
+        RemovedChain {
+            user_data: chain.user_data,
+            cancelled_requests,
+            answered_requests_in,
+        }
+    }
 
     /// Modifies the best block of the local node for the given chain. See
     /// [`ChainConfig::best_hash`] and [`ChainConfig::best_number`].
@@ -907,6 +1106,10 @@ where
                         peer_id_refmut @ None => {
                             self.unconnected_desired.remove(&actual_peer_id);
                             *peer_id_refmut = Some(actual_peer_id.clone());
+                            let _was_inserted = self
+                                .connections_by_peer_id
+                                .insert((actual_peer_id.clone(), id));
+                            debug_assert!(_was_inserted);
                         }
                         Some(peer_id_refmut) => {
                             // The actual PeerId doesn't match the expected PeerId.
@@ -1168,6 +1371,40 @@ where
                                     self.inner.reject_inbound(substream_id);
                                     continue;
                                 }
+                                Protocol::Collation { chain_index }
+                                    if self.chains[chain_index].allow_collation_protocols =>
+                                {
+                                    collection::InboundTy::Notifications {
+                                        max_handshake_size: 4,
+                                    }
+                                }
+                                Protocol::CollationFetching { chain_index }
+                                    if self.chains[chain_index].allow_collation_protocols =>
+                                {
+                                    collection::InboundTy::Request {
+                                        request_max_size: Some(36),
+                                    }
+                                }
+                                Protocol::Collation { .. } | Protocol::CollationFetching { .. } => {
+                                    self.inner.reject_inbound(substream_id);
+                                    continue;
+                                }
+                                Protocol::State { chain_index }
+                                    if self.chains[chain_index].allow_inbound_state_requests =>
+                                {
+                                    collection::InboundTy::Request {
+                                        request_max_size: Some(1024),
+                                    }
+                                }
+                                Protocol::Kad { chain_index }
+                                    if self.chains[chain_index].allow_inbound_kademlia_requests =>
+                                {
+                                    // Same value as the default maximum packet size of the
+                                    // libp2p Kademlia implementations.
+                                    collection::InboundTy::Request {
+                                        request_max_size: Some(16 * 1024),
+                                    }
+                                }
 
                                 // TODO: protocols that are not supported
                                 Protocol::LightUnknown { .. }
@@ -1178,9 +1415,10 @@ where
                                     continue;
                                 }
 
-                                Protocol::LightStorage { .. } | Protocol::LightCall { .. } => {
-                                    unreachable!()
-                                }
+                                Protocol::LightStorage { .. }
+                                | Protocol::LightCall { .. }
+                                | Protocol::KadGetValue { .. }
+                                | Protocol::KadPutValue { .. } => unreachable!(),
                             };
 
                             self.inner.accept_inbound(substream_id, inbound_type);
@@ -1207,6 +1445,13 @@ where
                     unreachable!()
                 }
 
+                collection::Event::InboundAcceptedCancel { substream_id }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    continue;
+                }
+
                 collection::Event::InboundAcceptedCancel { substream_id } => {
                     // An inbound substream has been aborted after having been accepted.
                     // Since we don't report any event to the API user when a substream is
@@ -1216,6 +1461,15 @@ where
                     continue;
                 }
 
+                collection::Event::Response { substream_id, .. }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    // The chain of this request has been removed. The API user has been notified
+                    // of the cancellation when removing the chain.
+                    self.orphan_substreams.remove(&substream_id);
+                    continue;
+                }
+
                 collection::Event::Response {
                     substream_id,
                     response,
@@ -1228,7 +1482,17 @@ where
 
                     // Decode/verify the response.
                     let response = match substream_info.protocol {
-                        Protocol::Identify => todo!(), // TODO: we don't send identify requests yet, so it's fine to leave this unimplemented
+                        Protocol::Identify => RequestResult::Identify(
+                            response
+                                .map_err(IdentifyRequestError::Request)
+                                .and_then(|payload| {
+                                    if let Err(err) = codec::decode_identify_response(&payload) {
+                                        Err(IdentifyRequestError::Decode(err))
+                                    } else {
+                                        Ok(EncodedIdentifyResponse(payload))
+                                    }
+                                }),
+                        ),
                         Protocol::Sync { .. } => RequestResult::Blocks(
                             response
                                 .map_err(BlocksRequestError::Request)
@@ -1284,6 +1548,22 @@ where
                                     }
                                 }),
                         ),
+                        Protocol::KadGetValue { .. } => RequestResult::KademliaGetValue(
+                            response
+                                .map_err(KademliaGetValueError::RequestFailed)
+                                .and_then(|payload| {
+                                    codec::decode_get_value_response(&payload)
+                                        .map_err(KademliaGetValueError::DecodeError)
+                                }),
+                        ),
+                        Protocol::KadPutValue { .. } => RequestResult::KademliaPutValue(
+                            response
+                                .map_err(KademliaPutValueError::RequestFailed)
+                                .and_then(|payload| {
+                                    codec::decode_put_value_response(&payload)
+                                        .map_err(KademliaPutValueError::DecodeError)
+                                }),
+                        ),
                         Protocol::SyncWarp { chain_index } => RequestResult::GrandpaWarpSync(
                             response
                                 .map_err(GrandpaWarpSyncRequestError::Request)
@@ -1318,7 +1598,11 @@ where
                         Protocol::Ping
                         | Protocol::BlockAnnounces { .. }
                         | Protocol::Transactions { .. }
-                        | Protocol::Grandpa { .. } => unreachable!(),
+                        | Protocol::Grandpa { .. }
+                        | Protocol::Collation { .. } => unreachable!(),
+
+                        // Collation fetching requests are never sent by the local node.
+                        Protocol::CollationFetching { .. } => unreachable!(),
                     };
 
                     return Some(Event::RequestResult {
@@ -1327,6 +1611,14 @@ where
                     });
                 }
 
+                collection::Event::RequestIn { substream_id, .. }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    self.inner.respond_in_request(substream_id, Err(()));
+                    continue;
+                }
+
                 collection::Event::RequestIn {
                     substream_id,
                     request_payload,
@@ -1385,11 +1677,108 @@ where
                                 }
                             }
                         }
+                        Protocol::CollationFetching { chain_index } => {
+                            match codec::decode_collation_fetching_request(&request_payload) {
+                                Ok(request) => {
+                                    return Some(Event::CollationFetchingRequestIn {
+                                        peer_id,
+                                        chain_id: ChainId(chain_index),
+                                        request,
+                                        substream_id,
+                                    })
+                                }
+                                Err(error) => {
+                                    let _ = self.substreams.remove(&substream_id);
+                                    self.inner.respond_in_request(substream_id, Err(()));
+                                    return Some(Event::ProtocolError {
+                                        peer_id,
+                                        error: ProtocolError::BadCollationFetchingRequest(error),
+                                    });
+                                }
+                            }
+                        }
+                        Protocol::State { chain_index } => {
+                            match codec::decode_state_request(&request_payload) {
+                                Ok(request) => {
+                                    let (child_trie, start_key) = match request.start_key {
+                                        codec::StateRequestStart::MainTrie(key) => {
+                                            (None, key.to_vec())
+                                        }
+                                        codec::StateRequestStart::ChildTrieDefault {
+                                            child_trie,
+                                            key,
+                                        } => (Some(child_trie.to_vec()), key.to_vec()),
+                                    };
+
+                                    return Some(Event::StateRequestIn {
+                                        peer_id,
+                                        chain_id: ChainId(chain_index),
+                                        block_hash: *request.block_hash,
+                                        child_trie,
+                                        start_key,
+                                        no_proof: request.no_proof,
+                                        substream_id,
+                                    });
+                                }
+                                Err(error) => {
+                                    let _ = self.substreams.remove(&substream_id);
+                                    self.inner.respond_in_request(substream_id, Err(()));
+                                    return Some(Event::ProtocolError {
+                                        peer_id,
+                                        error: ProtocolError::BadStateRequest(error),
+                                    });
+                                }
+                            }
+                        }
+                        Protocol::Kad { chain_index } => {
+                            match codec::decode_kademlia_request(&request_payload) {
+                                Ok(codec::KademliaRequest::FindNode { key }) => {
+                                    return Some(Event::KademliaFindNodeRequestIn {
+                                        peer_id,
+                                        chain_id: ChainId(chain_index),
+                                        key: key.to_vec(),
+                                        substream_id,
+                                    });
+                                }
+                                Ok(codec::KademliaRequest::GetValue { key }) => {
+                                    return Some(Event::KademliaGetValueRequestIn {
+                                        peer_id,
+                                        chain_id: ChainId(chain_index),
+                                        key: key.to_vec(),
+                                        substream_id,
+                                    });
+                                }
+                                Ok(codec::KademliaRequest::PutValue { key, value }) => {
+                                    return Some(Event::KademliaPutValueRequestIn {
+                                        peer_id,
+                                        chain_id: ChainId(chain_index),
+                                        key: key.to_vec(),
+                                        value: value.to_vec(),
+                                        substream_id,
+                                    });
+                                }
+                                Err(error) => {
+                                    let _ = self.substreams.remove(&substream_id);
+                                    self.inner.respond_in_request(substream_id, Err(()));
+                                    return Some(Event::ProtocolError {
+                                        peer_id,
+                                        error: ProtocolError::BadKademliaRequest(error),
+                                    });
+                                }
+                            }
+                        }
                         // Any other protocol is declined when the protocol is negotiated.
                         _ => unreachable!(),
                     }
                 }
 
+                collection::Event::RequestInCancel { substream_id }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    continue;
+                }
+
                 collection::Event::RequestInCancel { substream_id } => {
                     let _was_in = self.substreams.remove(&substream_id);
                     debug_assert!(_was_in.is_some());
@@ -1469,6 +1858,78 @@ where
                                         ));
                                     debug_assert!(_was_inserted);
 
+                                    // If the remote has a pending inbound block announces
+                                    // substream (reported through `GossipInDesired`), accept it
+                                    // now that the outbound substream is open.
+                                    if let Some(in_substream_id) = self
+                                        .notification_substreams_by_peer_id
+                                        .range(
+                                            (
+                                                NotificationsProtocol::BlockAnnounces {
+                                                    chain_index,
+                                                },
+                                                peer_id.clone(),
+                                                SubstreamDirection::In,
+                                                NotificationsSubstreamState::Pending,
+                                                SubstreamId::min_value(),
+                                            )
+                                                ..=(
+                                                    NotificationsProtocol::BlockAnnounces {
+                                                        chain_index,
+                                                    },
+                                                    peer_id.clone(),
+                                                    SubstreamDirection::In,
+                                                    NotificationsSubstreamState::Pending,
+                                                    SubstreamId::max_value(),
+                                                ),
+                                        )
+                                        .next()
+                                        .map(|(_, _, _, _, substream_id)| *substream_id)
+                                    {
+                                        let _was_removed =
+                                            self.notification_substreams_by_peer_id.remove(&(
+                                                NotificationsProtocol::BlockAnnounces {
+                                                    chain_index,
+                                                },
+                                                peer_id.clone(),
+                                                SubstreamDirection::In,
+                                                NotificationsSubstreamState::Pending,
+                                                in_substream_id,
+                                            ));
+                                        debug_assert!(_was_removed);
+                                        let _was_inserted =
+                                            self.notification_substreams_by_peer_id.insert((
+                                                NotificationsProtocol::BlockAnnounces {
+                                                    chain_index,
+                                                },
+                                                peer_id.clone(),
+                                                SubstreamDirection::In,
+                                                NotificationsSubstreamState::Open,
+                                                in_substream_id,
+                                            ));
+                                        debug_assert!(_was_inserted);
+
+                                        let handshake = codec::encode_block_announces_handshake(
+                                            codec::BlockAnnouncesHandshakeRef {
+                                                best_hash: &self.chains[chain_index].best_hash,
+                                                best_number: self.chains[chain_index].best_number,
+                                                role: self.chains[chain_index].role,
+                                                genesis_hash: &self.chains[chain_index]
+                                                    .genesis_hash,
+                                            },
+                                            self.chains[chain_index].block_number_bytes,
+                                        )
+                                        .fold(Vec::new(), |mut a, b| {
+                                            a.extend_from_slice(b.as_ref());
+                                            a
+                                        });
+                                        self.inner.accept_in_notifications(
+                                            in_substream_id,
+                                            handshake,
+                                            1024 * 1024, // TODO: arbitrary
+                                        );
+                                    }
+
                                     if self
                                         .notification_substreams_by_peer_id
                                         .range(
@@ -1846,6 +2307,31 @@ where
                             }
                         }
 
+                        // Collation substreams are independent from any other substream.
+                        Protocol::Collation { chain_index } => {
+                            if let Err(error) = result {
+                                return Some(Event::CollationOpenFailed {
+                                    peer_id,
+                                    chain_id: ChainId(chain_index),
+                                    error,
+                                });
+                            }
+
+                            let _was_inserted = self.notification_substreams_by_peer_id.insert((
+                                NotificationsProtocol::Collation { chain_index },
+                                peer_id.clone(),
+                                SubstreamDirection::Out,
+                                NotificationsSubstreamState::Open,
+                                substream_id,
+                            ));
+                            debug_assert!(_was_inserted);
+
+                            return Some(Event::CollationConnected {
+                                peer_id,
+                                chain_id: ChainId(chain_index),
+                            });
+                        }
+
                         // The other protocols aren't notification protocols.
                         Protocol::Identify
                         | Protocol::Ping
@@ -1854,8 +2340,11 @@ where
                         | Protocol::LightStorage { .. }
                         | Protocol::LightCall { .. }
                         | Protocol::Kad { .. }
+                        | Protocol::KadGetValue { .. }
+                        | Protocol::KadPutValue { .. }
                         | Protocol::SyncWarp { .. }
-                        | Protocol::State { .. } => unreachable!(),
+                        | Protocol::State { .. }
+                        | Protocol::CollationFetching { .. } => unreachable!(),
                     }
                 }
 
@@ -2054,10 +2543,24 @@ where
                                 new_substream_id,
                             ));
                         }
+                        Protocol::Collation { chain_index } => {
+                            return Some(Event::CollationClosed {
+                                peer_id,
+                                chain_id: ChainId(chain_index),
+                            });
+                        }
                         _ => unreachable!(),
                     }
                 }
 
+                collection::Event::NotificationsInOpen { substream_id, .. }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    self.inner.reject_in_notifications(substream_id);
+                    continue;
+                }
+
                 collection::Event::NotificationsInOpen { substream_id, .. } => {
                     // Remote would like to open a notifications substream with us.
 
@@ -2114,6 +2617,18 @@ where
                         continue;
                     }
 
+                    // Collation substreams opened by the remote are accepted immediately.
+                    // Inbound collation substreams are not tracked, as the messages received on
+                    // them are ignored.
+                    if let Protocol::Collation { chain_index } = substream_info.protocol {
+                        self.inner.accept_in_notifications(
+                            substream_id,
+                            self.chains[chain_index].role.scale_encoding().to_vec(),
+                            1024 * 1024, // TODO: arbitrary
+                        );
+                        continue;
+                    }
+
                     // Find the `chain_index`.
                     let (Protocol::BlockAnnounces { chain_index }
                     | Protocol::Transactions { chain_index }
@@ -2206,6 +2721,13 @@ where
                     });
                 }
 
+                collection::Event::NotificationsInOpenCancel { substream_id }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    continue;
+                }
+
                 collection::Event::NotificationsInOpenCancel { substream_id } => {
                     // Remote has cancelled a pending `NotificationsInOpen`.
 
@@ -2232,8 +2754,8 @@ where
                     let _was_in = self.notification_substreams_by_peer_id.remove(&(
                         NotificationsProtocol::BlockAnnounces { chain_index },
                         peer_id.clone(), // TODO: cloning overhead :-/
-                        SubstreamDirection::Out,
-                        NotificationsSubstreamState::Open,
+                        SubstreamDirection::In,
+                        NotificationsSubstreamState::Pending,
                         substream_id,
                     ));
                     debug_assert!(_was_in);
@@ -2246,6 +2768,13 @@ where
                     });
                 }
 
+                collection::Event::NotificationsIn { substream_id, .. }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    // The chain of this substream has been removed.
+                    continue;
+                }
+
                 collection::Event::NotificationsIn {
                     substream_id,
                     notification,
@@ -2259,6 +2788,8 @@ where
                         Protocol::BlockAnnounces { chain_index } => chain_index,
                         Protocol::Transactions { chain_index } => chain_index,
                         Protocol::Grandpa { chain_index } => chain_index,
+                        // TODO: the `CollationSeconded` messages sent by validators are currently ignored
+                        Protocol::Collation { .. } => continue,
                         // Other protocols are not notification protocols.
                         Protocol::Identify
                         | Protocol::Ping
@@ -2267,8 +2798,11 @@ where
                         | Protocol::LightStorage { .. }
                         | Protocol::LightCall { .. }
                         | Protocol::Kad { .. }
+                        | Protocol::KadGetValue { .. }
+                        | Protocol::KadPutValue { .. }
                         | Protocol::SyncWarp { .. }
-                        | Protocol::State { .. } => unreachable!(),
+                        | Protocol::State { .. }
+                        | Protocol::CollationFetching { .. } => unreachable!(),
                     };
                     let connection_info = &self.inner[substream_info.connection_id];
                     // Notification substreams can only happen on connections after their
@@ -2383,11 +2917,25 @@ where
                         | Protocol::LightStorage { .. }
                         | Protocol::LightCall { .. }
                         | Protocol::Kad { .. }
+                        | Protocol::KadGetValue { .. }
+                        | Protocol::KadPutValue { .. }
                         | Protocol::SyncWarp { .. }
                         | Protocol::State { .. } => unreachable!(),
+
+                        // Handled above.
+                        Protocol::Collation { .. } | Protocol::CollationFetching { .. } => {
+                            unreachable!()
+                        }
                     }
                 }
 
+                collection::Event::NotificationsInClose { substream_id, .. }
+                    if self.orphan_substreams.contains(&substream_id) =>
+                {
+                    self.orphan_substreams.remove(&substream_id);
+                    continue;
+                }
+
                 collection::Event::NotificationsInClose { substream_id, .. } => {
                     // An incoming notifications substream has been closed.
                     // Nothing to do except clean up the local state.
@@ -2435,7 +2983,7 @@ where
 
         self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::Sync {
                 chain_index: chain_id.0,
             },
@@ -2460,7 +3008,7 @@ where
 
         self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::SyncWarp {
                 chain_index: chain_id.0,
             },
@@ -2480,6 +3028,9 @@ where
     /// entire storage of the chain at once. Instead, call this function multiple times, each call
     /// passing a `start_key` that follows the last key of the previous response.
     ///
+    /// If `no_proof` is `true`, the response contains the list of storage entries instead of a
+    /// Merkle proof. See [`codec::StateRequest::no_proof`].
+    ///
     /// This function might generate a message destined a connection. Use
     /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
     ///
@@ -2493,11 +3044,13 @@ where
         chain_id: ChainId,
         block_hash: &[u8; 32],
         start_key: codec::StateRequestStart,
+        no_proof: bool,
         timeout: Duration,
     ) -> Result<SubstreamId, StartRequestError> {
         let request_data = codec::build_state_request(codec::StateRequest {
             block_hash,
             start_key,
+            no_proof,
         })
         .fold(Vec::new(), |mut a, b| {
             a.extend_from_slice(b.as_ref());
@@ -2506,7 +3059,7 @@ where
 
         self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::State {
                 chain_index: chain_id.0,
             },
@@ -2543,7 +3096,7 @@ where
 
         Ok(self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::LightStorage {
                 chain_index: chain_id.0,
             },
@@ -2587,7 +3140,7 @@ where
 
         Ok(self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::LightCall {
                 chain_index: chain_id.0,
             },
@@ -2595,6 +3148,23 @@ where
         )?)
     }
 
+    /// Sends an identify request to the given peer.
+    ///
+    /// The response contains, amongst other things, the addresses the peer is listening on, the
+    /// list of protocols it supports, and the address of the local node as seen by the peer.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    pub fn start_identify_request(
+        &mut self,
+        target: &PeerId,
+        timeout: Duration,
+    ) -> Result<SubstreamId, StartRequestError> {
+        // Contrary to the other request-response protocols, identify requests don't contain
+        // any data. The remote sends back its response as soon as the substream is opened.
+        self.start_request(target, None, Protocol::Identify, timeout)
+    }
+
     /// Sends a Kademlia find node request to the given peer.
     ///
     /// This function might generate a message destined a connection. Use
@@ -2619,7 +3189,7 @@ where
 
         self.start_request(
             target,
-            request_data,
+            Some(request_data),
             Protocol::Kad {
                 chain_index: chain_id.0,
             },
@@ -2627,18 +3197,78 @@ where
         )
     }
 
-    /// Underlying implementation of all the functions that start requests.
-    fn start_request(
+    /// Sends a Kademlia `GET_VALUE` request to the given peer, asking for the record whose key
+    /// is `key`.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn start_kademlia_get_value_request(
         &mut self,
         target: &PeerId,
-        request_data: Vec<u8>,
-        protocol: Protocol,
+        chain_id: ChainId,
+        key: &[u8],
         timeout: Duration,
     ) -> Result<SubstreamId, StartRequestError> {
+        let request_data = codec::build_get_value_request(key);
+
+        self.start_request(
+            target,
+            Some(request_data),
+            Protocol::KadGetValue {
+                chain_index: chain_id.0,
+            },
+            timeout,
+        )
+    }
+
+    /// Sends a Kademlia `PUT_VALUE` request to the given peer, asking it to store the given
+    /// record.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn start_kademlia_put_value_request(
+        &mut self,
+        target: &PeerId,
+        chain_id: ChainId,
+        key: &[u8],
+        value: &[u8],
+        timeout: Duration,
+    ) -> Result<SubstreamId, StartRequestError> {
+        let request_data = codec::build_put_value_request(key, value);
+
+        // TODO: check limit
+
+        self.start_request(
+            target,
+            Some(request_data),
+            Protocol::KadPutValue {
+                chain_index: chain_id.0,
+            },
+            timeout,
+        )
+    }
+
+    /// Returns `true` if there exists an established connection with the given peer, in which
+    /// case requests can be sent to it.
+    pub fn can_start_requests(&self, peer_id: &PeerId) -> bool {
+        self.request_connection(peer_id).is_some()
+    }
+
+    /// Returns a connection with the given peer that requests can be sent on, if any.
+    fn request_connection(&self, target: &PeerId) -> Option<collection::ConnectionId> {
         // TODO: cloning of `PeerId` overhead
         // TODO: this is O(n) but is it really a problem? you're only supposed to have max 1 or 2 connections per PeerId
-        let connection_id = self
-            .connections_by_peer_id
+        self.connections_by_peer_id
             .range(
                 (target.clone(), collection::ConnectionId::min_value())
                     ..=(target.clone(), collection::ConnectionId::max_value()),
@@ -2648,6 +3278,18 @@ where
                 let state = self.inner.connection_state(*connection_id);
                 state.established && !state.shutting_down
             })
+    }
+
+    /// Underlying implementation of all the functions that start requests.
+    fn start_request(
+        &mut self,
+        target: &PeerId,
+        request_data: Option<Vec<u8>>,
+        protocol: Protocol,
+        timeout: Duration,
+    ) -> Result<SubstreamId, StartRequestError> {
+        let connection_id = self
+            .request_connection(target)
             .ok_or(StartRequestError::NoConnection)?;
 
         let protocol_name = {
@@ -2703,7 +3345,9 @@ where
                         fork_id: chain_info.fork_id.as_deref(),
                     }
                 }
-                Protocol::Kad { chain_index } => {
+                Protocol::Kad { chain_index }
+                | Protocol::KadGetValue { chain_index }
+                | Protocol::KadPutValue { chain_index } => {
                     let chain_info = &self.chains[chain_index];
                     codec::ProtocolName::Kad {
                         genesis_hash: chain_info.genesis_hash,
@@ -2724,6 +3368,20 @@ where
                         fork_id: chain_info.fork_id.as_deref(),
                     }
                 }
+                Protocol::Collation { chain_index } => {
+                    let chain_info = &self.chains[chain_index];
+                    codec::ProtocolName::Collation {
+                        genesis_hash: chain_info.genesis_hash,
+                        fork_id: chain_info.fork_id.as_deref(),
+                    }
+                }
+                Protocol::CollationFetching { chain_index } => {
+                    let chain_info = &self.chains[chain_index];
+                    codec::ProtocolName::CollationFetching {
+                        genesis_hash: chain_info.genesis_hash,
+                        fork_id: chain_info.fork_id.as_deref(),
+                    }
+                }
             };
 
             codec::encode_protocol_name_string(protocol_name)
@@ -2732,7 +3390,7 @@ where
         let substream_id = self.inner.start_request(
             connection_id,
             protocol_name,
-            Some(request_data),
+            request_data,
             timeout,
             16 * 1024 * 1024,
         );
@@ -2752,8 +3410,8 @@ where
     /// Responds to an identify request. Call this function in response to
     /// a [`Event::IdentifyRequestIn`].
     ///
-    /// Only the `agent_version` needs to be specified. The other fields are automatically
-    /// filled by the [`ChainNetwork`].
+    /// Only the `agent_version` and the addresses the local node is reachable at need to be
+    /// specified. The other fields are automatically filled by the [`ChainNetwork`].
     ///
     /// This function might generate a message destined a connection. Use
     /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
@@ -2763,7 +3421,12 @@ where
     /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a blocks request or
     /// if the request has been cancelled with a [`Event::RequestInCancel`].
     ///
-    pub fn respond_identify(&mut self, substream_id: SubstreamId, agent_version: &str) {
+    pub fn respond_identify<'a>(
+        &mut self,
+        substream_id: SubstreamId,
+        agent_version: &str,
+        listen_addrs: impl Iterator<Item = &'a [u8]>,
+    ) {
         let substream_info = self.substreams.remove(&substream_id).unwrap();
         assert!(matches!(substream_info.protocol, Protocol::Identify { .. }));
 
@@ -2771,10 +3434,60 @@ where
             let observed_addr = &self.inner[substream_info.connection_id].address;
             let ed25519_public_key = &self.inner[substream_info.connection_id].ed25519_public_key;
 
-            // TODO: all protocols
-            let supported_protocols = [codec::ProtocolName::Ping].into_iter();
+            // List of protocols whose inbound substreams are accepted.
+            let mut supported_protocols =
+                vec![codec::ProtocolName::Identify, codec::ProtocolName::Ping];
+            for (_, chain) in &self.chains {
+                let genesis_hash = chain.genesis_hash;
+                let fork_id = chain.fork_id.as_deref();
+                supported_protocols.push(codec::ProtocolName::BlockAnnounces {
+                    genesis_hash,
+                    fork_id,
+                });
+                supported_protocols.push(codec::ProtocolName::Transactions {
+                    genesis_hash,
+                    fork_id,
+                });
+                if chain.grandpa_protocol_config.is_some() {
+                    supported_protocols.push(codec::ProtocolName::Grandpa {
+                        genesis_hash,
+                        fork_id,
+                    });
+                }
+                if chain.allow_inbound_block_requests {
+                    supported_protocols.push(codec::ProtocolName::Sync {
+                        genesis_hash,
+                        fork_id,
+                    });
+                }
+                if chain.allow_inbound_state_requests {
+                    supported_protocols.push(codec::ProtocolName::State {
+                        genesis_hash,
+                        fork_id,
+                    });
+                }
+                if chain.allow_inbound_kademlia_requests {
+                    supported_protocols.push(codec::ProtocolName::Kad {
+                        genesis_hash,
+                        fork_id,
+                    });
+                }
+                if chain.allow_collation_protocols {
+                    supported_protocols.push(codec::ProtocolName::Collation {
+                        genesis_hash,
+                        fork_id,
+                    });
+                    supported_protocols.push(codec::ProtocolName::CollationFetching {
+                        genesis_hash,
+                        fork_id,
+                    });
+                }
+            }
+
+            let listen_addrs = listen_addrs.collect::<Vec<_>>();
 
             let supported_protocols_names = supported_protocols
+                .into_iter()
                 .map(codec::encode_protocol_name_string)
                 .collect::<Vec<_>>();
 
@@ -2782,7 +3495,7 @@ where
                 protocol_version: "/substrate/1.0", // TODO: same value as in Substrate, see also https://github.com/paritytech/substrate/issues/14331
                 agent_version,
                 ed25519_public_key: *ed25519_public_key,
-                listen_addrs: iter::empty(), // TODO:
+                listen_addrs: listen_addrs.iter().map(|a| &a[..]),
                 observed_addr,
                 protocols: supported_protocols_names.iter().map(|p| &p[..]),
             })
@@ -2831,6 +3544,167 @@ where
         self.inner.respond_in_request(substream_id, response);
     }
 
+    /// Responds to a collation fetching request. Call this function in response to
+    /// a [`Event::CollationFetchingRequestIn`].
+    ///
+    /// Pass `None` in order to deny the request. Do this if no collation corresponding to the
+    /// request is available locally.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a collation fetching
+    /// request or if the request has been cancelled with a [`Event::RequestInCancel`].
+    ///
+    pub fn respond_collation_fetching(
+        &mut self,
+        substream_id: SubstreamId,
+        response: Option<codec::CollationFetchingResponse>,
+    ) {
+        let substream_info = self.substreams.remove(&substream_id).unwrap();
+        assert!(matches!(
+            substream_info.protocol,
+            Protocol::CollationFetching { .. }
+        ));
+
+        let response = if let Some(response) = response {
+            Ok(
+                codec::build_collation_fetching_response(&response).fold(Vec::new(), |mut a, b| {
+                    a.extend_from_slice(b.as_ref());
+                    a
+                }),
+            )
+        } else {
+            Err(())
+        };
+
+        self.inner.respond_in_request(substream_id, response);
+    }
+
+    /// Responds to a state request. Call this function in response to
+    /// a [`Event::StateRequestIn`].
+    ///
+    /// Pass `None` in order to deny the request. Do this if the storage of the requested block
+    /// isn't available locally.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a state request or
+    /// if the request has been cancelled with a [`Event::RequestInCancel`].
+    ///
+    pub fn respond_state(
+        &mut self,
+        substream_id: SubstreamId,
+        response: Option<codec::StateResponse>,
+    ) {
+        let substream_info = self.substreams.remove(&substream_id).unwrap();
+        assert!(matches!(substream_info.protocol, Protocol::State { .. }));
+
+        let response = if let Some(response) = response {
+            Ok(
+                codec::build_state_response(response).fold(Vec::new(), |mut a, b| {
+                    a.extend_from_slice(b.as_ref());
+                    a
+                }),
+            )
+        } else {
+            Err(())
+        };
+
+        self.inner.respond_in_request(substream_id, response);
+    }
+
+    /// Responds to a Kademlia `FIND_NODE` request. Call this function in response to
+    /// a [`Event::KademliaFindNodeRequestIn`].
+    ///
+    /// `closer_peers` must contain the nodes closest to the requested key that the local node
+    /// knows about, alongside with their addresses.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a Kademlia request or
+    /// if the request has been cancelled with a [`Event::RequestInCancel`].
+    ///
+    pub fn respond_kademlia_find_node(
+        &mut self,
+        substream_id: SubstreamId,
+        closer_peers: &[(PeerId, Vec<Vec<u8>>)],
+    ) {
+        let substream_info = self.substreams.remove(&substream_id).unwrap();
+        assert!(matches!(substream_info.protocol, Protocol::Kad { .. }));
+
+        let response = codec::build_find_node_response(closer_peers);
+        self.inner.respond_in_request(substream_id, Ok(response));
+    }
+
+    /// Responds to a Kademlia `GET_VALUE` request. Call this function in response to
+    /// a [`Event::KademliaGetValueRequestIn`].
+    ///
+    /// `value` must be `Some` if the record with the requested `key` is stored locally, and
+    /// `closer_peers` must contain the nodes closest to the requested key that the local node
+    /// knows about, alongside with their addresses.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a Kademlia request or
+    /// if the request has been cancelled with a [`Event::RequestInCancel`].
+    ///
+    pub fn respond_kademlia_get_value(
+        &mut self,
+        substream_id: SubstreamId,
+        key: &[u8],
+        value: Option<&[u8]>,
+        closer_peers: &[(PeerId, Vec<Vec<u8>>)],
+    ) {
+        let substream_info = self.substreams.remove(&substream_id).unwrap();
+        assert!(matches!(substream_info.protocol, Protocol::Kad { .. }));
+
+        let response = codec::build_get_value_response(key, value, closer_peers);
+        self.inner.respond_in_request(substream_id, Ok(response));
+    }
+
+    /// Responds to a Kademlia `PUT_VALUE` request. Call this function in response to
+    /// a [`Event::KademliaPutValueRequestIn`].
+    ///
+    /// Pass `None` in order to deny the request, for example if the record couldn't be stored.
+    /// Otherwise, pass the key and value of the record that has been stored.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`SubstreamId`] is invalid or doesn't correspond to a Kademlia request or
+    /// if the request has been cancelled with a [`Event::RequestInCancel`].
+    ///
+    pub fn respond_kademlia_put_value(
+        &mut self,
+        substream_id: SubstreamId,
+        record: Option<(&[u8], &[u8])>,
+    ) {
+        let substream_info = self.substreams.remove(&substream_id).unwrap();
+        assert!(matches!(substream_info.protocol, Protocol::Kad { .. }));
+
+        let response = if let Some((key, value)) = record {
+            Ok(codec::build_put_value_response(key, value))
+        } else {
+            Err(())
+        };
+
+        self.inner.respond_in_request(substream_id, response);
+    }
+
     /// Returns the list of all peers for a [`Event::GossipConnected`] event of the given kind has
     /// been emitted.
     /// It is possible to send gossip notifications to these peers.
@@ -3261,6 +4135,193 @@ where
         )
     }
 
+    /// Opens a collation substream with the given peer on the given chain.
+    ///
+    /// The chain must be a relay chain for which [`ChainConfig::allow_collation_protocols`] was
+    /// `true`, and the target is typically a validator assigned to the parachain of the local
+    /// collator.
+    ///
+    /// Either a [`Event::CollationConnected`] or [`Event::CollationOpenFailed`] is guaranteed to
+    /// later be generated, unless [`ChainNetwork::collation_close`] is called in the meanwhile.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    // TODO: proper error
+    pub fn collation_open(&mut self, chain_id: ChainId, target: &PeerId) -> Result<(), ()> {
+        let chain_info = &self.chains[chain_id.0];
+        if !chain_info.allow_collation_protocols {
+            return Err(());
+        }
+
+        // It is forbidden to open more than one collation substream with any given peer.
+        if self
+            .notification_substreams_by_peer_id
+            .range(
+                (
+                    NotificationsProtocol::Collation {
+                        chain_index: chain_id.0,
+                    },
+                    target.clone(),
+                    SubstreamDirection::Out,
+                    NotificationsSubstreamState::min_value(),
+                    SubstreamId::min_value(),
+                )
+                    ..=(
+                        NotificationsProtocol::Collation {
+                            chain_index: chain_id.0,
+                        },
+                        target.clone(),
+                        SubstreamDirection::Out,
+                        NotificationsSubstreamState::max_value(),
+                        SubstreamId::max_value(),
+                    ),
+            )
+            .next()
+            .is_some()
+        {
+            return Err(());
+        }
+
+        // TODO: cloning of `PeerId` overhead
+        let connection_id = self
+            .connections_by_peer_id
+            .range(
+                (target.clone(), collection::ConnectionId::min_value())
+                    ..=(target.clone(), collection::ConnectionId::max_value()),
+            )
+            .map(|(_, connection_id)| *connection_id)
+            .find(|connection_id| {
+                let state = self.inner.connection_state(*connection_id);
+                state.established && !state.shutting_down
+            })
+            .ok_or(())?;
+
+        let substream_id = self.inner.open_out_notifications(
+            connection_id,
+            codec::encode_protocol_name_string(codec::ProtocolName::Collation {
+                genesis_hash: chain_info.genesis_hash,
+                fork_id: chain_info.fork_id.as_deref(),
+            }),
+            Duration::from_secs(10), // TODO: arbitrary
+            chain_info.role.scale_encoding().to_vec(),
+            1024 * 1024, // TODO: arbitrary
+        );
+
+        let _prev_value = self.substreams.insert(
+            substream_id,
+            SubstreamInfo {
+                connection_id,
+                protocol: Protocol::Collation {
+                    chain_index: chain_id.0,
+                },
+            },
+        );
+        debug_assert!(_prev_value.is_none());
+
+        let _was_inserted = self.notification_substreams_by_peer_id.insert((
+            NotificationsProtocol::Collation {
+                chain_index: chain_id.0,
+            },
+            target.clone(),
+            SubstreamDirection::Out,
+            NotificationsSubstreamState::Pending,
+            substream_id,
+        ));
+        debug_assert!(_was_inserted);
+
+        Ok(())
+    }
+
+    /// Closes the collation substream with the given peer, either fully open or in the process
+    /// of being opened. No event is generated.
+    ///
+    /// Returns an error if no such substream exists.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn collation_close(&mut self, chain_id: ChainId, target: &PeerId) -> Result<(), ()> {
+        assert!(self.chains.contains(chain_id.0));
+
+        let Some((_, _, _, state, substream_id)) = self
+            .notification_substreams_by_peer_id
+            .range(
+                (
+                    NotificationsProtocol::Collation {
+                        chain_index: chain_id.0,
+                    },
+                    target.clone(),
+                    SubstreamDirection::Out,
+                    NotificationsSubstreamState::min_value(),
+                    SubstreamId::min_value(),
+                )
+                    ..=(
+                        NotificationsProtocol::Collation {
+                            chain_index: chain_id.0,
+                        },
+                        target.clone(),
+                        SubstreamDirection::Out,
+                        NotificationsSubstreamState::max_value(),
+                        SubstreamId::max_value(),
+                    ),
+            )
+            .next()
+            .cloned()
+        else {
+            return Err(());
+        };
+
+        self.inner.close_out_notifications(substream_id);
+        self.substreams.remove(&substream_id).unwrap();
+        let _was_in = self.notification_substreams_by_peer_id.remove(&(
+            NotificationsProtocol::Collation {
+                chain_index: chain_id.0,
+            },
+            target.clone(),
+            SubstreamDirection::Out,
+            state,
+            substream_id,
+        ));
+        debug_assert!(_was_in);
+
+        Ok(())
+    }
+
+    /// Sends a message on the collation substream with the given peer.
+    ///
+    /// Must be called only after a [`Event::CollationConnected`] has been generated.
+    ///
+    /// This function might generate a message destined a connection. Use
+    /// [`ChainNetwork::pull_message_to_connection`] to process messages after it has returned.
+    ///
+    /// # Panic
+    ///
+    /// Panics if the [`ChainId`] is invalid.
+    ///
+    pub fn collation_send(
+        &mut self,
+        target: &PeerId,
+        chain_id: ChainId,
+        message: codec::CollationProtocolMessageRef,
+    ) -> Result<(), QueueNotificationError> {
+        let notification =
+            codec::build_collation_protocol_message(&message).fold(Vec::new(), |mut a, b| {
+                a.extend_from_slice(b.as_ref());
+                a
+            });
+
+        self.queue_notification(
+            target,
+            NotificationsProtocol::Collation {
+                chain_index: chain_id.0,
+            },
+            notification,
+        )
+    }
+
     /// Inner implementation for all the notifications sends.
     fn queue_notification(
         &mut self,
@@ -3272,10 +4333,34 @@ where
             NotificationsProtocol::BlockAnnounces { chain_index } => chain_index,
             NotificationsProtocol::Transactions { chain_index } => chain_index,
             NotificationsProtocol::Grandpa { chain_index } => chain_index,
+            NotificationsProtocol::Collation { chain_index } => chain_index,
         };
 
         assert!(self.chains.contains(chain_index));
 
+        // Collation substreams aren't tied to the block announces substream.
+        if let NotificationsProtocol::Collation { .. } = protocol {
+            // TODO: O(n) ; optimize this by using range()
+            let substream_id = self
+                .notification_substreams_by_peer_id
+                .iter()
+                .find(move |(p, id, d, s, _)| {
+                    *p == protocol
+                        && id == target
+                        && *d == SubstreamDirection::Out
+                        && *s == NotificationsSubstreamState::Open
+                })
+                .map(|(_, _, _, _, substream_id)| *substream_id)
+                .ok_or(QueueNotificationError::NoConnection)?;
+
+            return match self.inner.queue_notification(substream_id, notification) {
+                Ok(()) => Ok(()),
+                Err(collection::QueueNotificationError::QueueFull) => {
+                    Err(QueueNotificationError::QueueFull)
+                }
+            };
+        }
+
         // We first find a block announces substream for that peer.
         // TODO: only relevant for GossipKind::ConsensusTransactions
         // If none is found, then we are not considered "gossip-connected", and return an error
@@ -3399,6 +4484,24 @@ where
                     .get(&(genesis_hash, fork_id.map(|fork_id| fork_id.to_owned())))
                     .ok_or(())?,
             },
+            codec::ProtocolName::Collation {
+                genesis_hash,
+                fork_id,
+            } => Protocol::Collation {
+                chain_index: *self
+                    .chains_by_protocol_info
+                    .get(&(genesis_hash, fork_id.map(|fork_id| fork_id.to_owned())))
+                    .ok_or(())?,
+            },
+            codec::ProtocolName::CollationFetching {
+                genesis_hash,
+                fork_id,
+            } => Protocol::CollationFetching {
+                chain_index: *self
+                    .chains_by_protocol_info
+                    .get(&(genesis_hash, fork_id.map(|fork_id| fork_id.to_owned())))
+                    .ok_or(())?,
+            },
         })
     }
 }
@@ -3422,6 +4525,20 @@ pub enum GossipKind {
     ConsensusTransactions,
 }
 
+/// Information about a chain removed with [`ChainNetwork::remove_chain`].
+#[derive(Debug)]
+pub struct RemovedChain<TChain> {
+    /// See [`ChainConfig::user_data`].
+    pub user_data: TChain,
+
+    /// List of requests of this chain that were in progress and that have been cancelled.
+    pub cancelled_requests: Vec<SubstreamId>,
+
+    /// List of incoming requests of this chain that had been yielded in an event, had not been
+    /// answered yet, and that have now been answered with an error.
+    pub answered_requests_in: Vec<SubstreamId>,
+}
+
 /// Error returned by [`ChainNetwork::add_chain`].
 #[derive(Debug, derive_more::Display, Clone)]
 pub enum AddChainError {
@@ -3620,6 +4737,130 @@ pub enum Event {
         substream_id: SubstreamId,
     },
 
+    /// A remote has sent a request for the storage of a block.
+    ///
+    /// Can only happen for chains where [`ChainConfig::allow_inbound_state_requests`] is `true`.
+    ///
+    /// You are strongly encouraged to call [`ChainNetwork::respond_state`].
+    StateRequestIn {
+        /// Remote that has sent the request.
+        peer_id: PeerId,
+        /// Index of the chain concerned by the request.
+        chain_id: ChainId,
+        /// Hash of the block whose storage is requested.
+        block_hash: [u8; 32],
+        /// If `Some`, the storage entries of the given default child trie are requested rather
+        /// than the ones of the main trie.
+        child_trie: Option<Vec<u8>>,
+        /// Storage entries are requested starting at this key (excluded).
+        start_key: Vec<u8>,
+        /// If `true`, the response should contain the list of storage entries rather than a
+        /// Merkle proof. See [`codec::StateRequest::no_proof`].
+        no_proof: bool,
+        /// Identifier of the request. Necessary to send back the answer.
+        substream_id: SubstreamId,
+    },
+
+    /// A remote has sent a Kademlia request for the nodes closest to a certain key.
+    ///
+    /// Can only happen for chains where [`ChainConfig::allow_inbound_kademlia_requests`] is
+    /// `true`.
+    ///
+    /// You are strongly encouraged to call [`ChainNetwork::respond_kademlia_find_node`].
+    KademliaFindNodeRequestIn {
+        /// Remote that has sent the request.
+        peer_id: PeerId,
+        /// Index of the chain concerned by the request.
+        chain_id: ChainId,
+        /// Key whose closest nodes are requested. Typically a [`PeerId`] in its bytes form.
+        key: Vec<u8>,
+        /// Identifier of the request. Necessary to send back the answer.
+        substream_id: SubstreamId,
+    },
+
+    /// A remote has sent a Kademlia request for a record.
+    ///
+    /// Can only happen for chains where [`ChainConfig::allow_inbound_kademlia_requests`] is
+    /// `true`.
+    ///
+    /// You are strongly encouraged to call [`ChainNetwork::respond_kademlia_get_value`].
+    KademliaGetValueRequestIn {
+        /// Remote that has sent the request.
+        peer_id: PeerId,
+        /// Index of the chain concerned by the request.
+        chain_id: ChainId,
+        /// Key of the requested record.
+        key: Vec<u8>,
+        /// Identifier of the request. Necessary to send back the answer.
+        substream_id: SubstreamId,
+    },
+
+    /// A remote has sent a Kademlia request asking the local node to store a record.
+    ///
+    /// Can only happen for chains where [`ChainConfig::allow_inbound_kademlia_requests`] is
+    /// `true`.
+    ///
+    /// You are strongly encouraged to call [`ChainNetwork::respond_kademlia_put_value`].
+    KademliaPutValueRequestIn {
+        /// Remote that has sent the request.
+        peer_id: PeerId,
+        /// Index of the chain concerned by the request.
+        chain_id: ChainId,
+        /// Key of the record to store.
+        key: Vec<u8>,
+        /// Value of the record to store.
+        value: Vec<u8>,
+        /// Identifier of the request. Necessary to send back the answer.
+        substream_id: SubstreamId,
+    },
+
+    /// A remote has sent a request for a collation.
+    ///
+    /// Can only happen for chains where [`ChainConfig::allow_collation_protocols`] is `true`.
+    ///
+    /// You are strongly encouraged to call [`ChainNetwork::respond_collation_fetching`].
+    CollationFetchingRequestIn {
+        /// Remote that has sent the request.
+        peer_id: PeerId,
+        /// Index of the chain concerned by the request.
+        chain_id: ChainId,
+        /// Information about the request.
+        request: codec::CollationFetchingRequest,
+        /// Identifier of the request. Necessary to send back the answer.
+        substream_id: SubstreamId,
+    },
+
+    /// Now connected to the given peer through the collation protocol.
+    ///
+    /// This event can only happen as a result of a call to [`ChainNetwork::collation_open`].
+    CollationConnected {
+        /// Peer we are now connected to.
+        peer_id: PeerId,
+        /// Chain of the collation substream.
+        chain_id: ChainId,
+    },
+
+    /// An attempt has been made to open a collation substream, but something wrong happened.
+    ///
+    /// This event can only happen as a result of a call to [`ChainNetwork::collation_open`].
+    CollationOpenFailed {
+        /// Peer concerned by the event.
+        peer_id: PeerId,
+        /// Chain of the collation substream.
+        chain_id: ChainId,
+        /// Problem that happened.
+        error: NotificationsOutErr,
+    },
+
+    /// A collation substream that was reported through [`Event::CollationConnected`] has been
+    /// closed by the remote.
+    CollationClosed {
+        /// Peer concerned by the event.
+        peer_id: PeerId,
+        /// Chain of the collation substream.
+        chain_id: ChainId,
+    },
+
     /// A remote is no longer interested in the response to a request.
     ///
     /// Calling [`ChainNetwork::respond_identify`], [`ChainNetwork::respond_blocks`], or similar
@@ -3657,6 +4898,15 @@ pub enum ProtocolError {
     /// Error while decoding a received blocks request.
     #[display(fmt = "Error while decoding a received blocks request: {_0}")]
     BadBlocksRequest(codec::DecodeBlockRequestError),
+    /// Error while decoding a received collation fetching request.
+    #[display(fmt = "Error while decoding a received collation fetching request: {_0}")]
+    BadCollationFetchingRequest(codec::DecodeCollationFetchingRequestError),
+    /// Error while decoding a received state request.
+    #[display(fmt = "Error while decoding a received state request: {_0}")]
+    BadStateRequest(codec::DecodeStateRequestError),
+    /// Error while decoding a received Kademlia request.
+    #[display(fmt = "Error while decoding a received Kademlia request: {_0}")]
+    BadKademliaRequest(codec::DecodeKademliaRequestError),
 }
 
 /// Error potentially returned when starting a request.
@@ -3688,12 +4938,26 @@ impl From<StartRequestError> for StartRequestMaybeTooLargeError {
 /// See [`Event::RequestResult`̀].
 #[derive(Debug)]
 pub enum RequestResult {
+    Identify(Result<EncodedIdentifyResponse, IdentifyRequestError>),
     Blocks(Result<Vec<codec::BlockData>, BlocksRequestError>),
     GrandpaWarpSync(Result<EncodedGrandpaWarpSyncResponse, GrandpaWarpSyncRequestError>),
     State(Result<EncodedStateResponse, StateRequestError>),
     StorageProof(Result<EncodedMerkleProof, StorageProofRequestError>),
     CallProof(Result<EncodedMerkleProof, CallProofRequestError>),
     KademliaFindNode(Result<Vec<(peer_id::PeerId, Vec<Vec<u8>>)>, KademliaFindNodeError>),
+    KademliaGetValue(Result<codec::GetValueResponse, KademliaGetValueError>),
+    KademliaPutValue(Result<(), KademliaPutValueError>),
+}
+
+/// Error returned by [`ChainNetwork::start_identify_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum IdentifyRequestError {
+    /// Error while waiting for the response from the peer.
+    #[display(fmt = "{_0}")]
+    Request(RequestError),
+    /// Error while decoding the response returned by the peer.
+    #[display(fmt = "Response decoding error: {_0}")]
+    Decode(codec::DecodeIdentifyResponseError),
 }
 
 /// Error returned by [`ChainNetwork::start_blocks_request`].
@@ -3770,6 +5034,28 @@ pub enum KademliaFindNodeError {
     DecodeError(codec::DecodeFindNodeResponseError),
 }
 
+/// Error during [`ChainNetwork::start_kademlia_get_value_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum KademliaGetValueError {
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    RequestFailed(RequestError),
+    /// Failed to decode the response.
+    #[display(fmt = "Response decoding error: {_0}")]
+    DecodeError(codec::DecodeGetValueResponseError),
+}
+
+/// Error during [`ChainNetwork::start_kademlia_put_value_request`].
+#[derive(Debug, derive_more::Display)]
+pub enum KademliaPutValueError {
+    /// Error during the request.
+    #[display(fmt = "{_0}")]
+    RequestFailed(RequestError),
+    /// Failed to decode the response.
+    #[display(fmt = "Response decoding error: {_0}")]
+    DecodeError(codec::DecodePutValueResponseError),
+}
+
 /// Error potentially returned when queueing a notification.
 #[derive(Debug, derive_more::Display)]
 pub enum QueueNotificationError {
@@ -3846,14 +5132,36 @@ impl fmt::Debug for EncodedGrandpaWarpSyncResponse {
     }
 }
 
+/// Undecoded but valid identify response.
+#[derive(Clone)]
+pub struct EncodedIdentifyResponse(Vec<u8>);
+
+impl EncodedIdentifyResponse {
+    /// Returns the decoded identify response.
+    pub fn decode(
+        &self,
+    ) -> codec::IdentifyResponse<'_, vec::IntoIter<&'_ [u8]>, vec::IntoIter<&'_ str>> {
+        match codec::decode_identify_response(&self.0) {
+            Ok(r) => r,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl fmt::Debug for EncodedIdentifyResponse {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.decode(), f)
+    }
+}
+
 /// Undecoded but valid state response.
 // TODO: merge with EncodedMerkleProof?
 #[derive(Clone)]
 pub struct EncodedStateResponse(Vec<u8>);
 
 impl EncodedStateResponse {
-    /// Returns the Merkle proof of the state response.
-    pub fn decode(&self) -> &[u8] {
+    /// Returns the decoded state response.
+    pub fn decode(&self) -> codec::StateResponse {
         match codec::decode_state_response(&self.0) {
             Ok(r) => r,
             Err(_) => unreachable!(),
@@ -0,0 +1,421 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#![cfg(test)]
+
+use super::{
+    codec, ChainConfig, ChainId, ChainNetwork, Config, ConnectionId, Event, GossipKind, NoiseKey,
+    PeerId, ReadWrite, RequestResult, SingleStreamConnectionTask, SingleStreamHandshakeKind,
+    SubstreamId,
+};
+use crate::libp2p::peer_id::PublicKey;
+use core::{mem, num::NonZeroU32, time::Duration};
+
+/// Two [`ChainNetwork`]s connected to each other through a single connection.
+struct TwoNodes {
+    alice: Node,
+    bob: Node,
+
+    /// Time that has elapsed since an unspecified epoch.
+    now: Duration,
+}
+
+struct Node {
+    network: ChainNetwork<(), Duration>,
+    peer_id: PeerId,
+    connection_id: ConnectionId,
+    connection: Option<SingleStreamConnectionTask<Duration>>,
+    /// Data written by this node and that the other node hasn't read yet.
+    outgoing_buffer: Vec<u8>,
+    /// Next time the connection needs to be processed.
+    wake_up_after: Option<Duration>,
+}
+
+impl TwoNodes {
+    /// Builds two nodes that both have the chains with the given genesis hashes, and connects
+    /// them to each other.
+    fn new(genesis_hashes: &[[u8; 32]]) -> (Self, Vec<ChainId>) {
+        let alice_key = NoiseKey::new(&rand::random(), &rand::random());
+        let bob_key = NoiseKey::new(&rand::random(), &rand::random());
+        let alice_peer_id =
+            PublicKey::Ed25519(*alice_key.libp2p_public_ed25519_key()).into_peer_id();
+        let bob_peer_id = PublicKey::Ed25519(*bob_key.libp2p_public_ed25519_key()).into_peer_id();
+
+        let mut chain_ids = Vec::new();
+        let mut new_network = || {
+            let mut network = ChainNetwork::new(Config {
+                connections_capacity: 1,
+                chains_capacity: genesis_hashes.len(),
+                randomness_seed: rand::random(),
+                handshake_timeout: Duration::from_secs(8),
+            });
+
+            chain_ids.clear();
+            for genesis_hash in genesis_hashes {
+                chain_ids.push(
+                    network
+                        .add_chain(ChainConfig {
+                            user_data: (),
+                            genesis_hash: *genesis_hash,
+                            fork_id: None,
+                            block_number_bytes: 4,
+                            best_hash: *genesis_hash,
+                            best_number: 0,
+                            role: codec::Role::Full,
+                            grandpa_protocol_config: None,
+                            allow_inbound_block_requests: true,
+                            allow_inbound_state_requests: false,
+                            allow_inbound_kademlia_requests: false,
+                            allow_collation_protocols: false,
+                        })
+                        .unwrap(),
+                );
+            }
+
+            network
+        };
+
+        let mut alice_network = new_network();
+        let mut bob_network = new_network();
+
+        let (alice_connection_id, alice_connection) = alice_network.add_single_stream_connection(
+            Duration::new(0, 0),
+            SingleStreamHandshakeKind::MultistreamSelectNoiseYamux {
+                is_initiator: true,
+                noise_key: &alice_key,
+            },
+            Vec::new(),
+            Some(bob_peer_id.clone()),
+        );
+        let (bob_connection_id, bob_connection) = bob_network.add_single_stream_connection(
+            Duration::new(0, 0),
+            SingleStreamHandshakeKind::MultistreamSelectNoiseYamux {
+                is_initiator: false,
+                noise_key: &bob_key,
+            },
+            Vec::new(),
+            None,
+        );
+
+        let mut nodes = TwoNodes {
+            alice: Node {
+                network: alice_network,
+                peer_id: alice_peer_id,
+                connection_id: alice_connection_id,
+                connection: Some(alice_connection),
+                outgoing_buffer: Vec::new(),
+                wake_up_after: None,
+            },
+            bob: Node {
+                network: bob_network,
+                peer_id: bob_peer_id,
+                connection_id: bob_connection_id,
+                connection: Some(bob_connection),
+                outgoing_buffer: Vec::new(),
+                wake_up_after: None,
+            },
+            now: Duration::new(0, 0),
+        };
+
+        let mut handshakes_finished = 0;
+        while handshakes_finished != 2 {
+            match nodes.run_until_event() {
+                Some((_, Event::HandshakeFinished { .. })) => handshakes_finished += 1,
+                _ev => unreachable!("{:?}", _ev),
+            }
+        }
+
+        (nodes, chain_ids)
+    }
+
+    /// Transfers data between the two nodes and advances time until one of them generates an
+    /// event. The first element of the returned tuple is `true` for Alice and `false` for Bob.
+    ///
+    /// Returns `None` if nothing happens within the next 30 seconds.
+    fn run_until_event(&mut self) -> Option<(bool, Event)> {
+        let deadline = self.now + Duration::from_secs(30);
+
+        loop {
+            if let Some(event) = self.alice.network.next_event() {
+                return Some((true, event));
+            }
+            if let Some(event) = self.bob.network.next_event() {
+                return Some((false, event));
+            }
+
+            let alice_progress = self.alice.process(self.now, &mut self.bob.outgoing_buffer);
+            let bob_progress = self.bob.process(self.now, &mut self.alice.outgoing_buffer);
+            if alice_progress || bob_progress {
+                continue;
+            }
+
+            // Nothing more will happen immediately. Advance time before looping again.
+            match (self.alice.wake_up_after, self.bob.wake_up_after) {
+                (Some(a), Some(b)) if a.min(b) <= deadline => self.now = a.min(b),
+                (Some(w), None) | (None, Some(w)) if w <= deadline => self.now = w,
+                _ => return None,
+            }
+        }
+    }
+
+    /// Opens a block announces substream in both directions between the two nodes.
+    fn open_gossip(&mut self, chain_id: ChainId) {
+        self.alice.network.gossip_insert_desired(
+            chain_id,
+            self.bob.peer_id.clone(),
+            GossipKind::ConsensusTransactions,
+        );
+        self.alice
+            .network
+            .gossip_open(
+                chain_id,
+                &self.bob.peer_id,
+                GossipKind::ConsensusTransactions,
+            )
+            .unwrap();
+
+        let mut gossip_connected = 0;
+        while gossip_connected != 2 {
+            match self.run_until_event() {
+                Some((false, Event::GossipInDesired { chain_id: c, .. })) if c == chain_id => {
+                    self.bob
+                        .network
+                        .gossip_open(
+                            chain_id,
+                            &self.alice.peer_id,
+                            GossipKind::ConsensusTransactions,
+                        )
+                        .unwrap();
+                }
+                Some((_, Event::GossipConnected { chain_id: c, .. })) if c == chain_id => {
+                    gossip_connected += 1
+                }
+                _ev => unreachable!("{:?}", _ev),
+            }
+        }
+    }
+}
+
+impl Node {
+    /// Delivers the pending messages between the network and the connection, and reads and
+    /// writes data on the connection. Returns `false` if nothing has happened.
+    fn process(&mut self, now: Duration, incoming_buffer: &mut Vec<u8>) -> bool {
+        let mut progress = false;
+
+        while let Some((connection_id, message)) = self.network.pull_message_to_connection() {
+            assert_eq!(connection_id, self.connection_id);
+            self.connection
+                .as_mut()
+                .unwrap()
+                .inject_coordinator_message(&now, message);
+            progress = true;
+        }
+
+        let mut read_write = ReadWrite {
+            now,
+            incoming_buffer: mem::take(incoming_buffer),
+            expected_incoming_bytes: Some(0),
+            read_bytes: 0,
+            write_buffers: Vec::new(),
+            write_bytes_queued: 0,
+            write_bytes_queueable: Some(64 * 1024),
+            wake_up_after: None,
+        };
+        self.connection
+            .as_mut()
+            .unwrap()
+            .read_write(&mut read_write);
+        *incoming_buffer = read_write.incoming_buffer;
+        if read_write.read_bytes != 0
+            || read_write.write_bytes_queued != 0
+            || read_write.wake_up_after.map_or(false, |when| when <= now)
+        {
+            progress = true;
+        }
+        self.wake_up_after = read_write.wake_up_after;
+        self.outgoing_buffer
+            .extend(read_write.write_buffers.into_iter().flatten());
+
+        loop {
+            let (connection, message) = self
+                .connection
+                .take()
+                .unwrap()
+                .pull_message_to_coordinator();
+            self.connection = connection;
+            let Some(message) = message else { break };
+            self.network
+                .inject_connection_message(self.connection_id, message);
+            progress = true;
+        }
+
+        progress
+    }
+}
+
+fn blocks_request_config() -> codec::BlocksRequestConfig {
+    codec::BlocksRequestConfig {
+        start: codec::BlocksRequestConfigStart::Number(0),
+        desired_count: NonZeroU32::new(1).unwrap(),
+        direction: codec::BlocksRequestDirection::Ascending,
+        fields: codec::BlocksRequestFields {
+            header: true,
+            body: false,
+            justifications: false,
+        },
+    }
+}
+
+#[test]
+fn remove_chain_without_activity() {
+    let (mut nodes, chain_ids) = TwoNodes::new(&[[0; 32], [1; 32]]);
+
+    let removed = nodes.alice.network.remove_chain(chain_ids[0]);
+    assert!(removed.cancelled_requests.is_empty());
+    assert!(removed.answered_requests_in.is_empty());
+    assert_eq!(
+        nodes.alice.network.chains().collect::<Vec<_>>(),
+        vec![chain_ids[1]]
+    );
+    assert!(nodes.run_until_event().is_none());
+}
+
+#[test]
+fn remove_chain_cancels_requests_and_closes_substreams() {
+    let (mut nodes, chain_ids) = TwoNodes::new(&[[0; 32], [1; 32]]);
+    let (removed_chain, other_chain) = (chain_ids[0], chain_ids[1]);
+
+    nodes.open_gossip(removed_chain);
+
+    // Alice sends a request to Bob, and Bob sends a request to Alice. Neither answers.
+    let alice_request = nodes
+        .alice
+        .network
+        .start_blocks_request(
+            &nodes.bob.peer_id,
+            removed_chain,
+            blocks_request_config(),
+            Duration::from_secs(20),
+        )
+        .unwrap();
+    let bob_request = nodes
+        .bob
+        .network
+        .start_blocks_request(
+            &nodes.alice.peer_id,
+            removed_chain,
+            blocks_request_config(),
+            Duration::from_secs(20),
+        )
+        .unwrap();
+
+    let (mut alice_request_in, mut bob_request_in) = (None::<SubstreamId>, None::<SubstreamId>);
+    while alice_request_in.is_none() || bob_request_in.is_none() {
+        match nodes.run_until_event() {
+            Some((true, Event::BlocksRequestIn { substream_id, .. })) => {
+                alice_request_in = Some(substream_id)
+            }
+            Some((false, Event::BlocksRequestIn { substream_id, .. })) => {
+                bob_request_in = Some(substream_id)
+            }
+            _ev => unreachable!("{:?}", _ev),
+        }
+    }
+
+    let removed = nodes.alice.network.remove_chain(removed_chain);
+    assert_eq!(removed.cancelled_requests, vec![alice_request]);
+    assert_eq!(
+        removed.answered_requests_in,
+        vec![alice_request_in.unwrap()]
+    );
+
+    // Bob answers the request of Alice, which Alice must silently discard.
+    nodes
+        .bob
+        .network
+        .respond_blocks(bob_request_in.unwrap(), Some(Vec::new()));
+
+    // Bob is notified that his request has failed and that both gossip substreams have been
+    // closed. Alice doesn't generate any event concerning the removed chain.
+    let (mut bob_request_failed, mut bob_gossip_closed) = (false, false);
+    while let Some(event) = nodes.run_until_event() {
+        match event {
+            (
+                false,
+                Event::RequestResult {
+                    substream_id,
+                    response: RequestResult::Blocks(Err(_)),
+                },
+            ) if substream_id == bob_request => {
+                assert!(!bob_request_failed);
+                bob_request_failed = true;
+            }
+            (
+                false,
+                Event::GossipDisconnected {
+                    chain_id,
+                    kind: GossipKind::ConsensusTransactions,
+                    ..
+                },
+            ) if chain_id == removed_chain => {
+                assert!(!bob_gossip_closed);
+                bob_gossip_closed = true;
+            }
+            _ev => unreachable!("{:?}", _ev),
+        }
+    }
+    assert!(bob_request_failed);
+    assert!(bob_gossip_closed);
+
+    // The connection is still usable for the other chain.
+    let alice_request = nodes
+        .alice
+        .network
+        .start_blocks_request(
+            &nodes.bob.peer_id,
+            other_chain,
+            blocks_request_config(),
+            Duration::from_secs(20),
+        )
+        .unwrap();
+    match nodes.run_until_event() {
+        Some((
+            false,
+            Event::BlocksRequestIn {
+                substream_id,
+                chain_id,
+                ..
+            },
+        )) if chain_id == other_chain => {
+            nodes
+                .bob
+                .network
+                .respond_blocks(substream_id, Some(Vec::new()));
+        }
+        _ev => unreachable!("{:?}", _ev),
+    }
+    match nodes.run_until_event() {
+        Some((
+            true,
+            Event::RequestResult {
+                substream_id,
+                response: RequestResult::Blocks(Ok(_)),
+            },
+        )) if substream_id == alice_request => {}
+        _ev => unreachable!("{:?}", _ev),
+    }
+}
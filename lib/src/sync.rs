@@ -72,4 +72,5 @@ pub mod all;
 pub mod all_forks;
 pub mod optimistic;
 pub mod para;
+pub mod state_download;
 pub mod warp_sync;
@@ -49,6 +49,7 @@ use core::{
 };
 
 pub use crate::executor::vm::ExecHint;
+pub use optimistic::{HeadersVerifiedAhead, HeadersVerifyAhead};
 pub use warp_sync::{
     BuildChainInformationError as WarpSyncBuildChainInformationError,
     BuildRuntimeError as WarpSyncBuildRuntimeError, ConfigCodeTrieNodeHint, VerifyFragmentError,
@@ -116,6 +117,16 @@ pub struct Config {
     // TODO: change this now that we don't verify block bodies here
     pub full_mode: bool,
 
+    /// If `true` and [`Config::full_mode`] is `true`, the state machine starts by warp syncing
+    /// to the head of the finalized chain, then continues with the block-by-block full
+    /// synchronization. Has no effect if [`Config::full_mode`] is `false`, as warp syncing is
+    /// then always performed.
+    ///
+    /// > **Note**: Warp syncing doesn't download the storage of the finalized block. It is the
+    /// >           responsibility of the API user to download it, for example through state
+    /// >           requests, once [`ProcessOne::WarpSyncFinished`] is returned.
+    pub full_mode_warp_sync: bool,
+
     /// Known valid Merkle value and storage value combination for the `:code` key.
     ///
     /// If provided, the warp syncing algorithm will first fetch the Merkle value of `:code`, and
@@ -181,7 +192,7 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
     /// Initializes a new state machine.
     pub fn new(config: Config) -> Self {
         AllSync {
-            inner: if config.full_mode {
+            inner: if config.full_mode && !config.full_mode_warp_sync {
                 AllSyncInner::Optimistic {
                     inner: optimistic::OptimisticSync::new(optimistic::Config {
                         chain_information: config.chain_information,
@@ -222,7 +233,7 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
                                 sources_capacity: config.sources_capacity,
                                 blocks_capacity: config.blocks_capacity,
                                 download_ahead_blocks: config.download_ahead_blocks,
-                                download_bodies: false,
+                                download_bodies: config.full_mode,
                             }),
                         }
                     }
@@ -236,6 +247,7 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
                 blocks_capacity: config.blocks_capacity,
                 max_disjoint_headers: config.max_disjoint_headers,
                 max_requests_per_block: config.max_requests_per_block,
+                download_ahead_blocks: config.download_ahead_blocks,
                 block_number_bytes: config.block_number_bytes,
                 allow_unknown_consensus_engines: config.allow_unknown_consensus_engines,
             },
@@ -393,6 +405,66 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
         }
     }
 
+    /// Sets the given non-finalized block as the new finalized block.
+    ///
+    /// This is meant to be used on chains whose finality is
+    /// [`chain_information::ChainInformationFinality::Outsourced`], where the API user decides
+    /// which blocks are finalized.
+    ///
+    /// On success, always returns [`FinalityProofVerifyOutcome::NewFinalized`].
+    ///
+    /// Returns an error if the block isn't in the list of non-finalized blocks. This is always
+    /// the case while warp syncing.
+    pub fn set_finalized_block(
+        &mut self,
+        block_hash: &[u8; 32],
+    ) -> Result<FinalityProofVerifyOutcome<TBl>, blocks_tree::SetFinalizedError> {
+        match &mut self.inner {
+            AllSyncInner::AllForks(sync) => match sync.set_finalized_block(block_hash)? {
+                all_forks::FinalityProofVerifyOutcome::NewFinalized {
+                    finalized_blocks_newest_to_oldest,
+                    pruned_blocks,
+                    updates_best_block,
+                } => Ok(FinalityProofVerifyOutcome::NewFinalized {
+                    finalized_blocks_newest_to_oldest: finalized_blocks_newest_to_oldest
+                        .into_iter()
+                        .map(|b| Block {
+                            full: None, // TODO: wrong
+                            header: b.0,
+                            justifications: Vec::new(),
+                            user_data: b.1.unwrap(),
+                        })
+                        .collect(),
+                    pruned_blocks: pruned_blocks
+                        .into_iter()
+                        .map(|b| b.0.hash(self.shared.block_number_bytes))
+                        .collect(),
+                    updates_best_block,
+                }),
+                _ => unreachable!(),
+            },
+            AllSyncInner::Optimistic { inner } => {
+                let (finalized_blocks_newest_to_oldest, pruned_blocks, updates_best_block) =
+                    inner.set_finalized_block(block_hash)?;
+                Ok(FinalityProofVerifyOutcome::NewFinalized {
+                    finalized_blocks_newest_to_oldest: finalized_blocks_newest_to_oldest
+                        .into_iter()
+                        .map(|b| Block {
+                            header: b.header,
+                            justifications: b.justifications,
+                            user_data: b.user_data,
+                            full: b.full.map(|b| BlockFull { body: b.body }),
+                        })
+                        .collect(),
+                    pruned_blocks,
+                    updates_best_block,
+                })
+            }
+            AllSyncInner::WarpSync { .. } => Err(blocks_tree::SetFinalizedError::UnknownBlock),
+            AllSyncInner::Poisoned => unreachable!(),
+        }
+    }
+
     /// Returns true if it is believed that we are near the head of the chain.
     ///
     /// The way this method is implemented is opaque and cannot be relied on. The return value
@@ -1141,6 +1213,32 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
         }
     }
 
+    /// Builds a [`HeadersVerifyAhead`] that verifies the headers of up to `max_headers` blocks
+    /// that are queued for verification, independently of [`AllSync::process_one`].
+    ///
+    /// Returns `None` if there is nothing to verify ahead. This is always the case if the
+    /// blocks aren't verified in order, which happens when the chain is near its head.
+    ///
+    /// See [`optimistic::OptimisticSync::headers_verify_ahead`].
+    pub fn headers_verify_ahead(&self, max_headers: usize) -> Option<HeadersVerifyAhead> {
+        match &self.inner {
+            AllSyncInner::Optimistic { inner } => inner.headers_verify_ahead(max_headers),
+            AllSyncInner::AllForks(_) | AllSyncInner::WarpSync { .. } => None,
+            AllSyncInner::Poisoned => unreachable!(),
+        }
+    }
+
+    /// Injects the outcome of [`HeadersVerifyAhead::perform`].
+    ///
+    /// The outcome is silently discarded if it is no longer relevant.
+    pub fn inject_headers_verified_ahead(&mut self, verified: HeadersVerifiedAhead) {
+        match &mut self.inner {
+            AllSyncInner::Optimistic { inner } => inner.inject_headers_verified_ahead(verified),
+            AllSyncInner::AllForks(_) | AllSyncInner::WarpSync { .. } => {}
+            AllSyncInner::Poisoned => unreachable!(),
+        }
+    }
+
     /// Process the next block in the queue of verification.
     ///
     /// This method takes ownership of the [`AllSync`] and starts a verification process. The
@@ -1186,17 +1284,24 @@ impl<TRq, TSrc, TBl> AllSync<TRq, TSrc, TBl> {
                 inner,
                 ready_to_transition: Some(ready_to_transition),
             } => {
-                let (
-                    new_inner,
-                    finalized_block_runtime,
-                    finalized_storage_code,
-                    finalized_storage_heap_pages,
-                    finalized_storage_code_merkle_value,
-                    finalized_storage_code_closest_ancestor_excluding,
-                ) = self
-                    .shared
-                    .transition_warp_sync_all_forks(inner, ready_to_transition);
-                self.inner = AllSyncInner::AllForks(new_inner);
+                let finalized_block_runtime = ready_to_transition.finalized_runtime;
+                let finalized_storage_code = ready_to_transition.finalized_storage_code;
+                let finalized_storage_heap_pages = ready_to_transition.finalized_storage_heap_pages;
+                let finalized_storage_code_merkle_value =
+                    ready_to_transition.finalized_storage_code_merkle_value;
+                let finalized_storage_code_closest_ancestor_excluding =
+                    ready_to_transition.finalized_storage_code_closest_ancestor_excluding;
+
+                // In full mode, the block bodies have to be downloaded and verified, which only
+                // the optimistic strategy supports.
+                self.inner = if self.shared.full_mode {
+                    AllSyncInner::Optimistic {
+                        inner: self.shared.transition_warp_sync_optimistic(inner),
+                    }
+                } else {
+                    AllSyncInner::AllForks(self.shared.transition_warp_sync_all_forks(inner))
+                };
+
                 ProcessOne::WarpSyncFinished {
                     sync: self,
                     finalized_block_runtime,
@@ -2770,6 +2875,8 @@ struct Shared<TRq> {
     max_disjoint_headers: usize,
     /// Value passed through [`Config::max_requests_per_block`].
     max_requests_per_block: NonZeroU32,
+    /// Value passed through [`Config::download_ahead_blocks`].
+    download_ahead_blocks: NonZeroU32,
     /// Value passed through [`Config::block_number_bytes`].
     block_number_bytes: usize,
     /// Value passed through [`Config::allow_unknown_consensus_engines`].
@@ -2782,15 +2889,8 @@ impl<TRq> Shared<TRq> {
     fn transition_warp_sync_all_forks<TSrc, TBl>(
         &mut self,
         warp_sync: warp_sync::WarpSync<WarpSyncSourceExtra<TSrc>, WarpSyncRequestExtra<TRq>>,
-        ready_to_transition: warp_sync::RuntimeInformation,
-    ) -> (
-        all_forks::AllForksSync<Option<TBl>, AllForksRequestExtra<TRq>, AllForksSourceExtra<TSrc>>,
-        host::HostVmPrototype,
-        Option<Vec<u8>>,
-        Option<Vec<u8>>,
-        Option<Vec<u8>>,
-        Option<Vec<Nibble>>,
-    ) {
+    ) -> all_forks::AllForksSync<Option<TBl>, AllForksRequestExtra<TRq>, AllForksSourceExtra<TSrc>>
+    {
         let warp_sync = warp_sync.deconstruct();
 
         let mut all_forks = all_forks::AllForksSync::new(all_forks::Config {
@@ -2801,7 +2901,7 @@ impl<TRq> Shared<TRq> {
             max_disjoint_headers: self.max_disjoint_headers,
             max_requests_per_block: self.max_requests_per_block,
             allow_unknown_consensus_engines: self.allow_unknown_consensus_engines,
-            full: false,
+            full: self.full_mode,
         });
 
         debug_assert!(self
@@ -2819,40 +2919,7 @@ impl<TRq> Shared<TRq> {
             detail,
         ) in warp_sync.in_progress_requests
         {
-            // TODO: DRY
-            let detail = match detail {
-                warp_sync::RequestDetail::WarpSyncRequest { block_hash } => {
-                    RequestDetail::WarpSync {
-                        sync_start_block_hash: block_hash,
-                    }
-                }
-                warp_sync::RequestDetail::StorageGetMerkleProof { block_hash, keys } => {
-                    RequestDetail::StorageGet { block_hash, keys }
-                }
-                warp_sync::RequestDetail::RuntimeCallMerkleProof {
-                    block_hash,
-                    function_name,
-                    parameter_vectored,
-                } => RequestDetail::RuntimeCallMerkleProof {
-                    block_hash,
-                    function_name,
-                    parameter_vectored,
-                },
-            };
-
-            // TODO: O(n2)
-            let (source_id, _) = self
-                .sources
-                .iter()
-                .find(|(_, s)| {
-                    matches!(s,
-                        SourceMapping::WarpSync(s) if *s == source_id
-                    )
-                })
-                .unwrap();
-
-            self.requests[outer_request_id.0] =
-                RequestMapping::Inline(SourceId(source_id), detail, user_data);
+            self.transition_warp_sync_request(source_id, outer_request_id, user_data, detail);
         }
 
         for (_, finalized_block_height, source) in warp_sync.sources_ordered {
@@ -2890,14 +2957,110 @@ impl<TRq> Shared<TRq> {
             .iter()
             .all(|(_, s)| matches!(s, RequestMapping::AllForks(..) | RequestMapping::Inline(..))));
 
-        (
-            all_forks,
-            ready_to_transition.finalized_runtime,
-            ready_to_transition.finalized_storage_code,
-            ready_to_transition.finalized_storage_heap_pages,
-            ready_to_transition.finalized_storage_code_merkle_value,
-            ready_to_transition.finalized_storage_code_closest_ancestor_excluding,
-        )
+        all_forks
+    }
+
+    /// Transitions the sync state machine from the warp sync strategy to the "optimistic"
+    /// strategy.
+    fn transition_warp_sync_optimistic<TSrc, TBl>(
+        &mut self,
+        warp_sync: warp_sync::WarpSync<WarpSyncSourceExtra<TSrc>, WarpSyncRequestExtra<TRq>>,
+    ) -> optimistic::OptimisticSync<OptimisticRequestExtra<TRq>, OptimisticSourceExtra<TSrc>, TBl>
+    {
+        let warp_sync = warp_sync.deconstruct();
+
+        let mut optimistic = optimistic::OptimisticSync::new(optimistic::Config {
+            chain_information: warp_sync.chain_information,
+            block_number_bytes: self.block_number_bytes,
+            sources_capacity: self.sources_capacity,
+            blocks_capacity: self.blocks_capacity,
+            download_ahead_blocks: self.download_ahead_blocks,
+            download_bodies: self.full_mode,
+        });
+
+        debug_assert!(self
+            .sources
+            .iter()
+            .all(|(_, s)| matches!(s, SourceMapping::WarpSync(_))));
+
+        for (
+            source_id,
+            _,
+            WarpSyncRequestExtra {
+                outer_request_id,
+                user_data,
+            },
+            detail,
+        ) in warp_sync.in_progress_requests
+        {
+            self.transition_warp_sync_request(source_id, outer_request_id, user_data, detail);
+        }
+
+        for (_, _, source) in warp_sync.sources_ordered {
+            let updated_source_id = optimistic.add_source(
+                OptimisticSourceExtra {
+                    user_data: source.user_data,
+                    best_block_hash: source.best_block_hash,
+                    outer_source_id: source.outer_source_id,
+                },
+                source.best_block_number,
+            );
+
+            self.sources[source.outer_source_id.0] = SourceMapping::Optimistic(updated_source_id);
+        }
+
+        debug_assert!(self
+            .sources
+            .iter()
+            .all(|(_, s)| matches!(s, SourceMapping::Optimistic(_))));
+        debug_assert!(self
+            .requests
+            .iter()
+            .all(|(_, s)| matches!(s, RequestMapping::Inline(..))));
+
+        optimistic
+    }
+
+    /// Turns a request that the warp sync strategy has started into a request that is no longer
+    /// tracked by any strategy.
+    fn transition_warp_sync_request(
+        &mut self,
+        source_id: warp_sync::SourceId,
+        outer_request_id: RequestId,
+        user_data: TRq,
+        detail: warp_sync::RequestDetail,
+    ) {
+        let detail = match detail {
+            warp_sync::RequestDetail::WarpSyncRequest { block_hash } => RequestDetail::WarpSync {
+                sync_start_block_hash: block_hash,
+            },
+            warp_sync::RequestDetail::StorageGetMerkleProof { block_hash, keys } => {
+                RequestDetail::StorageGet { block_hash, keys }
+            }
+            warp_sync::RequestDetail::RuntimeCallMerkleProof {
+                block_hash,
+                function_name,
+                parameter_vectored,
+            } => RequestDetail::RuntimeCallMerkleProof {
+                block_hash,
+                function_name,
+                parameter_vectored,
+            },
+        };
+
+        // TODO: O(n2)
+        let (source_id, _) = self
+            .sources
+            .iter()
+            .find(|(_, s)| {
+                matches!(s,
+                    SourceMapping::WarpSync(s) if *s == source_id
+                )
+            })
+            .unwrap();
+
+        self.requests[outer_request_id.0] =
+            RequestMapping::Inline(SourceId(source_id), detail, user_data);
     }
 }
 
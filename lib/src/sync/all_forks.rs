@@ -507,6 +507,46 @@ impl<TBl, TRq, TSrc> AllForksSync<TBl, TRq, TSrc> {
         self.chain.iter_ancestry_order()
     }
 
+    /// Sets the given non-finalized block as the new finalized block.
+    ///
+    /// This is meant to be used on chains whose finality is
+    /// [`chain_information::ChainInformationFinality::Outsourced`], where the API user decides
+    /// which blocks are finalized.
+    ///
+    /// On success, the returned value is the same as for a successful finality proof
+    /// verification. See [`FinalityProofVerifyOutcome::NewFinalized`].
+    pub fn set_finalized_block(
+        &mut self,
+        block_hash: &[u8; 32],
+    ) -> Result<FinalityProofVerifyOutcome<TBl>, blocks_tree::SetFinalizedError> {
+        let block_number_bytes = self.chain.block_number_bytes();
+
+        let finalized_blocks_iter = self.chain.set_finalized_block(block_hash)?;
+        let updates_best_block = finalized_blocks_iter.updates_best_block();
+        let mut finalized_blocks = Vec::new();
+        let mut pruned_blocks = Vec::new();
+        for block in finalized_blocks_iter {
+            let header = header::Header::from(
+                header::decode(&block.scale_encoded_header, block_number_bytes).unwrap(),
+            );
+            if matches!(block.ty, blocks_tree::RemovedBlockType::Finalized) {
+                finalized_blocks.push((header, block.user_data));
+            } else {
+                pruned_blocks.push((header, block.user_data));
+            }
+        }
+        let _finalized_blocks = self
+            .inner
+            .blocks
+            .set_finalized_block_height(finalized_blocks.last().unwrap().0.number);
+
+        Ok(FinalityProofVerifyOutcome::NewFinalized {
+            finalized_blocks_newest_to_oldest: finalized_blocks,
+            pruned_blocks,
+            updates_best_block,
+        })
+    }
+
     /// Gives access to the user data stored for a block of the data structure.
     ///
     /// # Panic
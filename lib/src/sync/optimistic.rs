@@ -169,6 +169,10 @@ struct OptimisticSyncInner<TRq, TSrc, TBl> {
 
     /// Same as [`OptimisticSyncInner::obsolete_requests`], but ordered differently.
     obsolete_requests_by_source: BTreeSet<(SourceId, RequestId)>,
+
+    /// Headers of blocks of [`OptimisticSyncInner::verification_queue`] that have been verified
+    /// ahead of time through a [`HeadersVerifyAhead`], indexed by their hash.
+    headers_verified_ahead: HashMap<[u8; 32], blocks_tree::VerifiedHeader, fnv::FnvBuildHasher>,
 }
 
 impl<TRq, TSrc, TBl> OptimisticSyncInner<TRq, TSrc, TBl> {
@@ -178,6 +182,8 @@ impl<TRq, TSrc, TBl> OptimisticSyncInner<TRq, TSrc, TBl> {
             verification_queue::VerificationQueue::new(chain.best_block_header().number + 1),
         );
 
+        self.headers_verified_ahead.clear();
+
         for ((request_id, user_data), source) in former_queue.into_requests() {
             let _was_in = self
                 .obsolete_requests
@@ -276,6 +282,7 @@ impl<TRq, TSrc, TBl> OptimisticSync<TRq, TSrc, TBl> {
                 next_request_id: RequestId(0),
                 obsolete_requests: HashMap::with_capacity_and_hasher(0, Default::default()),
                 obsolete_requests_by_source: BTreeSet::new(),
+                headers_verified_ahead: HashMap::with_capacity_and_hasher(0, Default::default()),
             }),
         }
     }
@@ -347,6 +354,42 @@ impl<TRq, TSrc, TBl> OptimisticSync<TRq, TSrc, TBl> {
         self.chain.iter_ancestry_order()
     }
 
+    /// Sets the given non-finalized block as the new finalized block.
+    ///
+    /// This is meant to be used on chains whose finality is
+    /// [`chain_information::ChainInformationFinality::Outsourced`], where the API user decides
+    /// which blocks are finalized.
+    ///
+    /// On success, returns the blocks that have been finalized, in decreasing block number, the
+    /// hashes of the blocks that are no longer descendant of the finalized block, and whether the
+    /// best block has been modified.
+    pub fn set_finalized_block(
+        &mut self,
+        block_hash: &[u8; 32],
+    ) -> Result<(Vec<Block<TBl>>, Vec<[u8; 32]>, bool), blocks_tree::SetFinalizedError> {
+        let finalized_blocks_iter = self.chain.set_finalized_block(block_hash)?;
+        let updates_best_block = finalized_blocks_iter.updates_best_block();
+        let mut finalized_blocks_newest_to_oldest = Vec::new();
+        let mut pruned_blocks = Vec::new();
+        for block in finalized_blocks_iter {
+            match block.ty {
+                blocks_tree::RemovedBlockType::Finalized => {
+                    finalized_blocks_newest_to_oldest.push(block.user_data)
+                }
+                blocks_tree::RemovedBlockType::Pruned => pruned_blocks.push(block.block_hash),
+            }
+        }
+
+        self.inner.finalized_chain_information.chain_information =
+            self.chain.as_chain_information().into();
+
+        Ok((
+            finalized_blocks_newest_to_oldest,
+            pruned_blocks,
+            updates_best_block,
+        ))
+    }
+
     /// Disassembles the state machine into its raw components.
     pub fn disassemble(self) -> Disassemble<TRq, TSrc> {
         Disassemble {
@@ -669,6 +712,65 @@ impl<TRq, TSrc, TBl> OptimisticSync<TRq, TSrc, TBl> {
         user_data
     }
 
+    /// Builds a [`HeadersVerifyAhead`] that verifies the headers of up to `max_headers` blocks
+    /// that are queued for verification.
+    ///
+    /// Verifying the header of a block doesn't require executing the block. While the blocks are
+    /// verified one by one with [`OptimisticSync::process_one`], the headers of the blocks that
+    /// follow can thus be verified in parallel, for example on a different thread. The outcome
+    /// must then be passed to [`OptimisticSync::inject_headers_verified_ahead`], after which
+    /// [`BlockVerify::verify_header`] no longer verifies these headers again.
+    ///
+    /// Returns `None` if there isn't any queued block whose header hasn't been verified ahead
+    /// yet.
+    pub fn headers_verify_ahead(&self, max_headers: usize) -> Option<HeadersVerifyAhead> {
+        let headers = self
+            .inner
+            .verification_queue
+            .ready_blocks()
+            .take(max_headers)
+            .map(|block| {
+                let hash = header::hash_from_scale_encoded_header(&block.scale_encoded_header);
+                let verified = self.inner.headers_verified_ahead.get(&hash).cloned();
+                (block.scale_encoded_header.clone(), verified)
+            })
+            .collect::<Vec<_>>();
+
+        if headers.iter().all(|(_, verified)| verified.is_some()) {
+            return None;
+        }
+
+        Some(HeadersVerifyAhead {
+            chain: self.chain.clone_without_user_data(),
+            headers,
+        })
+    }
+
+    /// Injects the outcome of [`HeadersVerifyAhead::perform`].
+    ///
+    /// Headers that no longer correspond to a block queued for verification, for example
+    /// because the chain has been reset in the meanwhile, are silently discarded.
+    pub fn inject_headers_verified_ahead(&mut self, verified: HeadersVerifiedAhead) {
+        let queued_hashes = self
+            .inner
+            .verification_queue
+            .ready_blocks()
+            .map(|block| header::hash_from_scale_encoded_header(&block.scale_encoded_header))
+            .collect::<hashbrown::HashSet<_, fnv::FnvBuildHasher>>();
+
+        for verified_header in verified.verified_headers {
+            if queued_hashes.contains(verified_header.hash()) {
+                self.inner
+                    .headers_verified_ahead
+                    .insert(*verified_header.hash(), verified_header);
+            }
+        }
+
+        self.inner
+            .headers_verified_ahead
+            .retain(|hash, _| queued_hashes.contains(hash));
+    }
+
     /// Process the next block in the queue of verification.
     ///
     /// This method takes ownership of the [`OptimisticSync`]. The [`OptimisticSync`] is yielded
@@ -818,10 +920,25 @@ impl<TRq, TSrc, TBl> BlockVerify<TRq, TSrc, TBl> {
             .collect::<Vec<_>>()
             .into_iter();
 
-        let outcome = match self
-            .chain
-            .verify_header(block.scale_encoded_header, now_from_unix_epoch)
-        {
+        // If the header has been verified ahead of time, the verification isn't performed again.
+        let verified_ahead = self
+            .inner
+            .headers_verified_ahead
+            .remove(&header::hash_from_scale_encoded_header(
+                &block.scale_encoded_header,
+            ))
+            .and_then(|verified_header| {
+                self.chain.revalidate_verified_header(verified_header).ok()
+            });
+
+        let verify_result = match verified_ahead {
+            Some(success) => Ok(success),
+            None => self
+                .chain
+                .verify_header(block.scale_encoded_header, now_from_unix_epoch),
+        };
+
+        let outcome = match verify_result {
             Ok(blocks_tree::HeaderVerifySuccess::Verified {
                 verified_header,
                 is_new_best: true,
@@ -1034,6 +1151,91 @@ impl<TRq, TSrc, TBl> BlockVerifySuccess<TRq, TSrc, TBl> {
     }
 }
 
+/// Verification of the headers of blocks queued for verification, ahead of the blocks
+/// themselves. See [`OptimisticSync::headers_verify_ahead`].
+///
+/// This object doesn't borrow the [`OptimisticSync`] and can be sent to a different thread.
+pub struct HeadersVerifyAhead {
+    /// Copy of [`OptimisticSync::chain`] the headers are verified against.
+    chain: blocks_tree::NonFinalizedTree<()>,
+    /// SCALE-encoded headers to verify, in order, and their verified version if they have
+    /// already been verified ahead.
+    headers: Vec<(Vec<u8>, Option<blocks_tree::VerifiedHeader>)>,
+}
+
+impl HeadersVerifyAhead {
+    /// Verify the headers.
+    ///
+    /// Must be passed the current UNIX time in order to verify that the blocks don't pretend to
+    /// come from the future.
+    ///
+    /// The verification stops at the first header that fails to verify. This failure isn't
+    /// reported, and will instead be reported again when the block is verified through
+    /// [`OptimisticSync::process_one`].
+    pub fn perform(mut self, now_from_unix_epoch: Duration) -> HeadersVerifiedAhead {
+        let mut verified_headers = Vec::with_capacity(self.headers.len());
+
+        for (scale_encoded_header, already_verified) in self.headers {
+            let already_verified = already_verified.and_then(|verified_header| {
+                match self.chain.revalidate_verified_header(verified_header) {
+                    Ok(blocks_tree::HeaderVerifySuccess::Verified {
+                        verified_header, ..
+                    }) => Some(verified_header),
+                    _ => None,
+                }
+            });
+
+            let verified_header = match already_verified {
+                Some(verified_header) => verified_header,
+                None => match self
+                    .chain
+                    .verify_header(scale_encoded_header, now_from_unix_epoch)
+                {
+                    Ok(blocks_tree::HeaderVerifySuccess::Verified {
+                        verified_header, ..
+                    }) => {
+                        verified_headers.push(verified_header.clone());
+                        verified_header
+                    }
+                    Ok(blocks_tree::HeaderVerifySuccess::Duplicate) | Err(_) => break,
+                },
+            };
+
+            self.chain.insert_verified_header(verified_header, ());
+        }
+
+        HeadersVerifiedAhead { verified_headers }
+    }
+}
+
+impl fmt::Debug for HeadersVerifyAhead {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeadersVerifyAhead")
+            .field("num_headers", &self.headers.len())
+            .finish()
+    }
+}
+
+/// Outcome of [`HeadersVerifyAhead::perform`]. Must be passed to
+/// [`OptimisticSync::inject_headers_verified_ahead`].
+#[derive(Debug)]
+pub struct HeadersVerifiedAhead {
+    /// Headers that have been successfully verified.
+    verified_headers: Vec<blocks_tree::VerifiedHeader>,
+}
+
+impl HeadersVerifiedAhead {
+    /// Returns the number of headers that have been successfully verified.
+    pub fn len(&self) -> usize {
+        self.verified_headers.len()
+    }
+
+    /// Returns `true` if no header has been successfully verified.
+    pub fn is_empty(&self) -> bool {
+        self.verified_headers.is_empty()
+    }
+}
+
 /// Start the processing of a justification verification.
 pub struct JustificationVerify<TRq, TSrc, TBl> {
     inner: Box<OptimisticSyncInner<TRq, TSrc, TBl>>,
@@ -70,6 +70,21 @@ impl<TRq, TBl> VerificationQueue<TRq, TBl> {
         }
     }
 
+    /// Returns the list of blocks that are ready, in the order in which they are going to be
+    /// returned by [`VerificationQueue::pop_first_block`].
+    ///
+    /// The first element of the iterator is the block returned by
+    /// [`VerificationQueue::first_block`].
+    pub fn ready_blocks(&'_ self) -> impl Iterator<Item = &'_ TBl> + '_ {
+        self.verification_queue
+            .iter()
+            .map_while(|entry| match &entry.ty {
+                VerificationQueueEntryTy::Queued { blocks, .. } => Some(blocks.iter()),
+                _ => None,
+            })
+            .flatten()
+    }
+
     /// If the queue starts with ready blocks, returns the first block that is ready and removes
     /// it.
     ///
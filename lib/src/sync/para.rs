@@ -32,6 +32,27 @@
 //! See the [`persisted_validation_data_parameters`] to obtain the input to pass to the runtime
 //! function. The first parameter is a `para_id` found in the chain specification of the
 //! parachain of parathread.
+//!
+//! # Authoring parachain blocks
+//!
+//! In order to author a block of a parachain, the block author must provide to the runtime a
+//! [`crate::verify::inherents::ParachainInherentData`]. This data is assembled from the relay
+//! chain as follows:
+//!
+//! - The persisted validation data is obtained as explained above.
+//! - The downward messages are obtained by calling the [`DMQ_CONTENTS_FUNCTION_NAME`] runtime
+//!   function. See [`dmq_contents_parameters`] and [`decode_dmq_contents_return_value`].
+//! - The horizontal messages are obtained by calling the
+//!   [`INBOUND_HRMP_CHANNELS_CONTENTS_FUNCTION_NAME`] runtime function. See
+//!   [`inbound_hrmp_channels_contents_parameters`] and
+//!   [`decode_inbound_hrmp_channels_contents_return_value`].
+//! - The relay chain state proof is a Merkle proof of the storage of the relay parent block
+//!   containing the keys returned by [`relay_chain_state_proof_keys`] and [`hrmp_channel_key`].
+
+use crate::verify::inherents;
+
+use alloc::vec::Vec;
+use core::{hash::Hasher as _, iter};
 
 /// Produces the input to pass to the `ParachainHost_persisted_validation_data` runtime call.
 pub fn persisted_validation_data_parameters(
@@ -48,6 +69,39 @@ pub fn persisted_validation_data_parameters(
 /// Name of the runtime function to call in order to obtain the parachain heads.
 pub const PERSISTED_VALIDATION_FUNCTION_NAME: &str = "ParachainHost_persisted_validation_data";
 
+/// Name of the runtime function to call in order to obtain the messages sent by the relay chain
+/// to a parachain.
+pub const DMQ_CONTENTS_FUNCTION_NAME: &str = "ParachainHost_dmq_contents";
+
+/// Produces the input to pass to the `ParachainHost_dmq_contents` runtime call.
+pub fn dmq_contents_parameters(para_id: u32) -> impl Iterator<Item = impl AsRef<[u8]>> + Clone {
+    iter::once(para_id.to_le_bytes())
+}
+
+/// Name of the runtime function to call in order to obtain the messages sent by other parachains
+/// to a parachain.
+pub const INBOUND_HRMP_CHANNELS_CONTENTS_FUNCTION_NAME: &str =
+    "ParachainHost_inbound_hrmp_channels_contents";
+
+/// Produces the input to pass to the `ParachainHost_inbound_hrmp_channels_contents` runtime call.
+pub fn inbound_hrmp_channels_contents_parameters(
+    para_id: u32,
+) -> impl Iterator<Item = impl AsRef<[u8]>> + Clone {
+    iter::once(para_id.to_le_bytes())
+}
+
+/// Name of the runtime function to call in order to obtain the hash of the validation code
+/// (i.e. the Wasm runtime) of a parachain.
+pub const VALIDATION_CODE_HASH_FUNCTION_NAME: &str = "ParachainHost_validation_code_hash";
+
+/// Produces the input to pass to the `ParachainHost_validation_code_hash` runtime call.
+pub fn validation_code_hash_parameters(
+    para_id: u32,
+    assumption: OccupiedCoreAssumption,
+) -> impl Iterator<Item = impl AsRef<[u8]>> + Clone {
+    persisted_validation_data_parameters(para_id, assumption)
+}
+
 /// An assumption being made about the state of an occupied core.
 // TODO: what does that mean?
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
@@ -87,6 +141,25 @@ pub fn decode_persisted_validation_data_return_value(
     }
 }
 
+/// Attempt to decode the return value of the `ParachainHost_validation_code_hash` runtime call.
+///
+/// Returns `None` if the relay chain doesn't know about the parachain.
+pub fn decode_validation_code_hash_return_value(
+    scale_encoded: &[u8],
+) -> Result<Option<&[u8; 32]>, Error> {
+    let res: Result<_, nom::Err<nom::error::Error<_>>> = nom::combinator::all_consuming(
+        nom::combinator::complete(crate::util::nom_option_decode(nom::combinator::map(
+            nom::bytes::streaming::take(32u32),
+            |hash| <&[u8; 32]>::try_from(hash).unwrap(),
+        ))),
+    )(scale_encoded);
+    match res {
+        Ok((_, data)) => Ok(data),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(Error(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
 /// Error that can happen during the decoding.
 #[derive(Debug, derive_more::Display)]
 #[display(fmt = "Error decoding persisted validation data")]
@@ -108,6 +181,177 @@ pub struct PersistedValidationDataRef<'a> {
     pub max_pov_size: u32,
 }
 
+/// Attempt to decode the return value of the `ParachainHost_dmq_contents` runtime call.
+///
+/// The messages are returned in the order in which they have been queued.
+pub fn decode_dmq_contents_return_value(
+    scale_encoded: &[u8],
+    block_number_bytes: usize,
+) -> Result<Vec<InboundMessageRef<'_>>, Error> {
+    let res: Result<_, nom::Err<nom::error::Error<_>>> =
+        nom::combinator::all_consuming(inbound_messages(block_number_bytes))(scale_encoded);
+    match res {
+        Ok((_, data)) => Ok(data),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(Error(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Attempt to decode the return value of the `ParachainHost_inbound_hrmp_channels_contents`
+/// runtime call.
+///
+/// Returns a list of messages for each sender parachain id, ordered by sender parachain id.
+pub fn decode_inbound_hrmp_channels_contents_return_value(
+    scale_encoded: &[u8],
+    block_number_bytes: usize,
+) -> Result<Vec<(u32, Vec<InboundMessageRef<'_>>)>, Error> {
+    let res: Result<_, nom::Err<nom::error::Error<_>>> = nom::combinator::all_consuming(
+        nom::combinator::flat_map(crate::util::nom_scale_compact_usize, move |num_elems| {
+            nom::multi::many_m_n(
+                num_elems,
+                num_elems,
+                nom::sequence::tuple((
+                    nom::number::streaming::le_u32,
+                    inbound_messages(block_number_bytes),
+                )),
+            )
+        }),
+    )(scale_encoded);
+    match res {
+        Ok((_, data)) => Ok(data),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(Error(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Attempt to decode the storage value found at [`hrmp_ingress_channels_index_key`] or
+/// [`hrmp_egress_channels_index_key`], in other words a list of parachain ids.
+pub fn decode_hrmp_channels_index(scale_encoded: &[u8]) -> Result<Vec<u32>, Error> {
+    let res: Result<_, nom::Err<nom::error::Error<_>>> =
+        nom::combinator::all_consuming(nom::combinator::complete(nom::combinator::flat_map(
+            crate::util::nom_scale_compact_usize,
+            |num_elems| nom::multi::many_m_n(num_elems, num_elems, nom::number::streaming::le_u32),
+        )))(scale_encoded);
+    match res {
+        Ok((_, data)) => Ok(data),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => Err(Error(err.code)),
+        Err(_) => unreachable!(),
+    }
+}
+
+/// Message sent to a parachain, either by the relay chain or by another parachain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct InboundMessageRef<'a> {
+    /// Number of the relay chain block where the message has been enqueued.
+    pub sent_at: u64,
+    /// Opaque content of the message.
+    pub data: &'a [u8],
+}
+
+impl<'a> From<InboundMessageRef<'a>> for inherents::InboundMessage {
+    fn from(message: InboundMessageRef<'a>) -> inherents::InboundMessage {
+        inherents::InboundMessage {
+            sent_at: message.sent_at,
+            data: message.data.to_vec(),
+        }
+    }
+}
+
+/// Returns the list of relay chain storage keys whose values must be included in the relay chain
+/// state proof of the [`inherents::ParachainInherentData`] of the given parachain.
+///
+/// In addition to these keys, the proof must also include the [`hrmp_channel_key`] of each
+/// channel found in the values of the ingress and egress channel indices.
+// TODO: the randomness-related keys of BABE are missing, as they are only needed by a few parachains
+pub fn relay_chain_state_proof_keys(para_id: u32) -> impl Iterator<Item = Vec<u8>> + Clone {
+    let para_id = para_id.to_le_bytes();
+    [
+        storage_value_key(b"Babe", b"CurrentSlot"),
+        storage_value_key(b"Configuration", b"ActiveConfig"),
+        storage_map_twox64_concat_key(b"Dmp", b"DownwardMessageQueueHeads", &para_id),
+        hrmp_ingress_channels_index_key(u32::from_le_bytes(para_id)),
+        hrmp_egress_channels_index_key(u32::from_le_bytes(para_id)),
+        storage_map_twox64_concat_key(b"Paras", b"UpgradeGoAheadSignal", &para_id),
+        storage_map_twox64_concat_key(b"Paras", b"UpgradeRestrictionSignal", &para_id),
+        storage_map_twox64_concat_key(b"Paras", b"Heads", &para_id),
+    ]
+    .into_iter()
+}
+
+/// Returns the relay chain storage key containing the list of parachains that have an open HRMP
+/// channel towards the given parachain. See [`decode_hrmp_channels_index`].
+pub fn hrmp_ingress_channels_index_key(para_id: u32) -> Vec<u8> {
+    storage_map_twox64_concat_key(b"Hrmp", b"HrmpIngressChannelsIndex", &para_id.to_le_bytes())
+}
+
+/// Returns the relay chain storage key containing the list of parachains towards which the
+/// given parachain has an open HRMP channel. See [`decode_hrmp_channels_index`].
+pub fn hrmp_egress_channels_index_key(para_id: u32) -> Vec<u8> {
+    storage_map_twox64_concat_key(b"Hrmp", b"HrmpEgressChannelsIndex", &para_id.to_le_bytes())
+}
+
+/// Returns the relay chain storage key containing the description of the HRMP channel from
+/// `sender` to `recipient`.
+pub fn hrmp_channel_key(sender: u32, recipient: u32) -> Vec<u8> {
+    let mut channel_id = [0; 8];
+    channel_id[..4].copy_from_slice(&sender.to_le_bytes());
+    channel_id[4..].copy_from_slice(&recipient.to_le_bytes());
+    storage_map_twox64_concat_key(b"Hrmp", b"HrmpChannels", &channel_id)
+}
+
+/// Builds the storage key of a storage value of a FRAME pallet.
+fn storage_value_key(pallet: &[u8], item: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(32);
+    out.extend_from_slice(&twox_128(pallet));
+    out.extend_from_slice(&twox_128(item));
+    out
+}
+
+/// Builds the storage key of an entry of a storage map of a FRAME pallet using the
+/// `Twox64Concat` hasher.
+fn storage_map_twox64_concat_key(pallet: &[u8], item: &[u8], map_key: &[u8]) -> Vec<u8> {
+    let mut out = storage_value_key(pallet, item);
+    out.extend_from_slice(&twox_64(map_key));
+    out.extend_from_slice(map_key);
+    out
+}
+
+fn twox_64(data: &[u8]) -> [u8; 8] {
+    let mut h0 = twox_hash::XxHash::with_seed(0);
+    h0.write(data);
+    h0.finish().to_le_bytes()
+}
+
+fn twox_128(data: &[u8]) -> [u8; 16] {
+    let mut h0 = twox_hash::XxHash::with_seed(0);
+    let mut h1 = twox_hash::XxHash::with_seed(1);
+    h0.write(data);
+    h1.write(data);
+    let mut out = [0; 16];
+    out[..8].copy_from_slice(&h0.finish().to_le_bytes());
+    out[8..].copy_from_slice(&h1.finish().to_le_bytes());
+    out
+}
+
+/// `Nom` combinator that parses a list of [`InboundMessageRef`].
+fn inbound_messages<'a, E: nom::error::ParseError<&'a [u8]>>(
+    block_number_bytes: usize,
+) -> impl FnMut(&'a [u8]) -> nom::IResult<&[u8], Vec<InboundMessageRef>, E> {
+    nom::combinator::flat_map(crate::util::nom_scale_compact_usize, move |num_elems| {
+        nom::multi::many_m_n(
+            num_elems,
+            num_elems,
+            nom::combinator::map(
+                nom::sequence::tuple((
+                    crate::util::nom_varsize_number_decode_u64(block_number_bytes),
+                    crate::util::nom_bytes_decode,
+                )),
+                |(sent_at, data)| InboundMessageRef { sent_at, data },
+            ),
+        )
+    })
+}
+
 /// `Nom` combinator that parses a [`PersistedValidationDataRef`].
 fn persisted_validation_data<'a, E: nom::error::ParseError<&'a [u8]>>(
     block_number_bytes: usize,
@@ -133,6 +377,77 @@ fn persisted_validation_data<'a, E: nom::error::ParseError<&'a [u8]>>(
 
 #[cfg(test)]
 mod tests {
+    #[test]
+    fn dmq_contents_decode() {
+        let encoded = [8, 5, 0, 0, 0, 8, 1, 2, 7, 0, 0, 0, 0];
+        assert_eq!(
+            super::decode_dmq_contents_return_value(&encoded, 4).unwrap(),
+            vec![
+                super::InboundMessageRef {
+                    sent_at: 5,
+                    data: &[1, 2]
+                },
+                super::InboundMessageRef {
+                    sent_at: 7,
+                    data: &[]
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn inbound_hrmp_decode() {
+        let encoded = [4, 0xe8, 0x3, 0, 0, 4, 9, 0, 0, 0, 4, 0xff];
+        assert_eq!(
+            super::decode_inbound_hrmp_channels_contents_return_value(&encoded, 4).unwrap(),
+            vec![(
+                1000,
+                vec![super::InboundMessageRef {
+                    sent_at: 9,
+                    data: &[0xff]
+                }]
+            )]
+        );
+    }
+
+    #[test]
+    fn hrmp_channels_index_decode() {
+        let encoded = [8, 0xe8, 0x3, 0, 0, 0xd0, 0x7, 0, 0];
+        assert_eq!(
+            super::decode_hrmp_channels_index(&encoded).unwrap(),
+            vec![1000, 2000]
+        );
+        assert!(super::decode_hrmp_channels_index(&encoded[..8]).is_err());
+    }
+
+    #[test]
+    fn validation_code_hash_decode() {
+        let mut encoded = vec![1];
+        encoded.extend_from_slice(&[0xab; 32]);
+        assert_eq!(
+            super::decode_validation_code_hash_return_value(&encoded).unwrap(),
+            Some(&[0xab; 32])
+        );
+        assert_eq!(
+            super::decode_validation_code_hash_return_value(&[0]).unwrap(),
+            None
+        );
+        assert!(super::decode_validation_code_hash_return_value(&encoded[..20]).is_err());
+    }
+
+    #[test]
+    fn well_known_keys() {
+        // `twox128("Babe") ++ twox128("CurrentSlot")`
+        assert_eq!(
+            super::relay_chain_state_proof_keys(1000).next().unwrap(),
+            [
+                0x1c, 0xb6, 0xf3, 0x6e, 0x02, 0x7a, 0xbb, 0x20, 0x91, 0xcf, 0xb5, 0x11, 0x0a, 0xb5,
+                0x08, 0x7f, 0x06, 0x15, 0x5b, 0x3c, 0xd9, 0xa8, 0xc9, 0xe5, 0xe9, 0xa2, 0x3f, 0xd5,
+                0xdc, 0x13, 0xa5, 0xed
+            ]
+        );
+    }
+
     #[test]
     fn basic_decode() {
         let encoded = [
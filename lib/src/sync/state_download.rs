@@ -0,0 +1,359 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Download of the entire storage of a block, including its child tries, through state requests.
+//!
+//! This is typically used after a warp sync, by nodes that need to know the entire storage of
+//! the block they have warp synced to.
+//!
+//! The storage entries are received ordered by key, and the nodes of the tries are yielded as
+//! soon as they are complete. Only the ancestors of the latest downloaded entries are kept in
+//! memory, meaning that the memory usage doesn't depend on the size of the storage.
+//!
+//! # Usage
+//!
+//! Call [`StateDownload::request_start`] in order to determine the start key of the next state
+//! request, send the request to a peer, then pass the response to
+//! [`StateDownload::process_response`]. Repeat until [`Progress::Finished`] is returned, then
+//! compare [`Progress::Finished::state_root`] with the state trie root found in the header of
+//! the block.
+//!
+//! The storage entries are not verified until the download is finished. If the state trie root
+//! doesn't match, all the trie nodes that have been yielded must be discarded.
+
+use crate::{
+    network::codec::{StateRequestStart, StateResponse},
+    trie::{self, ordered_builder},
+};
+
+use alloc::vec::Vec;
+use core::mem;
+
+/// Prefix of the keys of the main trie that contain the root of a default child trie.
+const CHILD_TRIE_PREFIX: &[u8] = b":child_storage:default:";
+
+/// See [the module-level documentation](..).
+pub struct StateDownload {
+    /// Version of the trie entries of the block whose storage is downloaded.
+    trie_entries_version: trie::TrieEntryVersion,
+
+    /// Builder of the main trie.
+    main_trie: ordered_builder::OrderedTrieBuilder,
+
+    /// Key of the latest entry of the main trie that has been downloaded.
+    main_trie_last_key: Option<Vec<u8>>,
+
+    /// Child trie whose download is in progress. The download of a child trie is always
+    /// finished before any entry of the main trie that follows the child trie is downloaded.
+    child_trie: Option<ChildTrieDownload>,
+
+    /// Number of storage entries, including the ones of the child tries, downloaded so far.
+    num_entries: u64,
+}
+
+/// See [`StateDownload::child_trie`].
+struct ChildTrieDownload {
+    /// Key of the child trie, without the `:child_storage:default:` prefix.
+    child_trie: Vec<u8>,
+
+    /// Merkle value of the root of the child trie, as found in the main trie.
+    root: Vec<u8>,
+
+    /// Builder of the child trie.
+    builder: ordered_builder::OrderedTrieBuilder,
+
+    /// Key of the latest entry of the child trie that has been downloaded.
+    last_key: Option<Vec<u8>>,
+}
+
+impl StateDownload {
+    /// Initializes a new download.
+    pub fn new(trie_entries_version: trie::TrieEntryVersion) -> Self {
+        StateDownload {
+            trie_entries_version,
+            main_trie: ordered_builder::OrderedTrieBuilder::new(
+                trie::HashFunction::Blake2,
+                trie_entries_version,
+            ),
+            main_trie_last_key: None,
+            child_trie: None,
+            num_entries: 0,
+        }
+    }
+
+    /// Returns the number of storage entries, including the ones of the child tries, that have
+    /// been downloaded so far.
+    pub fn num_entries(&self) -> u64 {
+        self.num_entries
+    }
+
+    /// Returns the start key to put in the next state request.
+    ///
+    /// If the download of a child trie is in progress, it is continued. Otherwise, the download
+    /// of the main trie continues after its latest downloaded key.
+    pub fn request_start(&self) -> StateRequestStart<'_> {
+        match &self.child_trie {
+            Some(child) => StateRequestStart::ChildTrieDefault {
+                child_trie: &child.child_trie,
+                key: child.last_key.as_deref().unwrap_or(&[]),
+            },
+            None => StateRequestStart::MainTrie(self.main_trie_last_key.as_deref().unwrap_or(&[])),
+        }
+    }
+
+    /// Updates the download with the content of a response to a state request whose start key
+    /// was obtained through [`StateDownload::request_start`].
+    ///
+    /// Returns the list of trie nodes that are now complete. Nodes are always returned after all
+    /// of their descendants, and child trie nodes are always returned before the nodes of the
+    /// main trie that reference them.
+    ///
+    /// On error, the state of the download is unspecified and it must be started again from
+    /// scratch.
+    pub fn process_response(
+        &mut self,
+        response: StateResponse,
+    ) -> Result<(Vec<TrieNode>, Progress), Error> {
+        if response
+            .entries
+            .iter()
+            .all(|group| group.entries.is_empty())
+            && !response.entries.iter().any(|group| group.complete)
+        {
+            return Err(Error::EmptyResponse);
+        }
+
+        let mut nodes = Vec::new();
+
+        let main_trie = response
+            .entries
+            .iter()
+            .find(|group| group.state_root.is_empty());
+
+        // Child tries whose root is found in the entries of the main trie of this response and
+        // that haven't been downloaded yet, in order.
+        let mut new_child_tries = main_trie
+            .into_iter()
+            .flat_map(|group| group.entries.iter())
+            .filter(|(key, _)| {
+                self.main_trie_last_key
+                    .as_ref()
+                    .map_or(true, |last_key| *key > &last_key[..])
+            })
+            .filter_map(|(key, value)| Some((key.strip_prefix(CHILD_TRIE_PREFIX)?, *value)))
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        // Child tries are processed first, so that their nodes are yielded before the nodes of
+        // the main trie that reference them.
+        for group in response
+            .entries
+            .iter()
+            .filter(|group| !group.state_root.is_empty())
+        {
+            if self
+                .child_trie
+                .as_ref()
+                .map_or(true, |child| child.root != group.state_root)
+            {
+                if self.child_trie.is_some() {
+                    return Err(Error::ChildTrieNotFinished);
+                }
+
+                match new_child_tries.next() {
+                    Some((child_trie, root)) if root == group.state_root => {
+                        self.child_trie = Some(ChildTrieDownload {
+                            child_trie: child_trie.to_vec(),
+                            root: root.to_vec(),
+                            builder: ordered_builder::OrderedTrieBuilder::new(
+                                trie::HashFunction::Blake2,
+                                self.trie_entries_version,
+                            ),
+                            last_key: None,
+                        });
+                    }
+                    _ => return Err(Error::UnexpectedChildTrie),
+                }
+            }
+
+            let child = self.child_trie.as_mut().unwrap();
+            for (key, value) in &group.entries {
+                // The start key of the request might be included in the response.
+                if child.last_key.as_deref() == Some(*key) {
+                    continue;
+                }
+                let new_nodes = child
+                    .builder
+                    .push(key, value)
+                    .map_err(|_| Error::NotOrdered)?;
+                nodes.extend(new_nodes.into_iter().map(|node| TrieNode {
+                    node,
+                    references_child_trie: false,
+                }));
+                child.last_key = Some(key.to_vec());
+                self.num_entries += 1;
+            }
+
+            if group.complete {
+                let child = self.child_trie.take().unwrap();
+                let (new_nodes, root) = child.builder.finish();
+                if root[..] != child.root[..] {
+                    return Err(Error::ChildTrieRootMismatch);
+                }
+                nodes.extend(new_nodes.into_iter().map(|node| TrieNode {
+                    node,
+                    references_child_trie: false,
+                }));
+            }
+        }
+
+        let Some(main_trie) = main_trie else {
+            return Ok((nodes, Progress::InProgress));
+        };
+
+        for (key, value) in &main_trie.entries {
+            // The start key of the request might be included in the response.
+            if self.main_trie_last_key.as_deref() == Some(*key) {
+                continue;
+            }
+
+            if key.starts_with(b":child_storage:") && !key.starts_with(CHILD_TRIE_PREFIX) {
+                return Err(Error::InvalidChildTrieKey);
+            }
+
+            // No entry of the main trie can follow a child trie whose download isn't finished.
+            if let Some(child) = &self.child_trie {
+                if **key > *[CHILD_TRIE_PREFIX, &child.child_trie].concat() {
+                    return Err(Error::ChildTrieNotFinished);
+                }
+            }
+
+            let new_nodes = self
+                .main_trie
+                .push(key, value)
+                .map_err(|_| Error::NotOrdered)?;
+            nodes.extend(new_nodes.into_iter().map(main_trie_node));
+            self.main_trie_last_key = Some(key.to_vec());
+            self.num_entries += 1;
+        }
+
+        // The response might have been truncated right after the root of a child trie. In that
+        // situation, the child trie is downloaded in the next requests.
+        if let Some((child_trie, root)) = new_child_tries.next() {
+            let is_last_key = self
+                .main_trie_last_key
+                .as_ref()
+                .and_then(|key| key.strip_prefix(CHILD_TRIE_PREFIX))
+                .map_or(false, |last| last == child_trie);
+            if !is_last_key
+                || new_child_tries.next().is_some()
+                || self.child_trie.is_some()
+                || main_trie.complete
+            {
+                return Err(Error::MissingChildTrie);
+            }
+
+            self.child_trie = Some(ChildTrieDownload {
+                child_trie: child_trie.to_vec(),
+                root: root.to_vec(),
+                builder: ordered_builder::OrderedTrieBuilder::new(
+                    trie::HashFunction::Blake2,
+                    self.trie_entries_version,
+                ),
+                last_key: None,
+            });
+        }
+
+        if !main_trie.complete {
+            return Ok((nodes, Progress::InProgress));
+        }
+
+        if self.child_trie.is_some() {
+            return Err(Error::ChildTrieNotFinished);
+        }
+
+        let builder = mem::replace(
+            &mut self.main_trie,
+            ordered_builder::OrderedTrieBuilder::new(
+                trie::HashFunction::Blake2,
+                self.trie_entries_version,
+            ),
+        );
+        let (new_nodes, state_root) = builder.finish();
+        nodes.extend(new_nodes.into_iter().map(main_trie_node));
+
+        Ok((nodes, Progress::Finished { state_root }))
+    }
+}
+
+/// Builds a [`TrieNode`] from a node of the main trie.
+fn main_trie_node(node: ordered_builder::Node) -> TrieNode {
+    let references_child_trie = node.storage_value.is_some()
+        && node.key.starts_with(
+            &trie::bytes_to_nibbles(b":child_storage:".iter().copied()).collect::<Vec<_>>(),
+        );
+    TrieNode {
+        node,
+        references_child_trie,
+    }
+}
+
+/// Node of a trie yielded by [`StateDownload::process_response`].
+#[derive(Debug, Clone)]
+pub struct TrieNode {
+    /// The node itself.
+    pub node: ordered_builder::Node,
+    /// `true` if the node belongs to the main trie and its storage value is the Merkle value of
+    /// the root of a child trie.
+    pub references_child_trie: bool,
+}
+
+/// Outcome of [`StateDownload::process_response`].
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// More storage entries must be downloaded.
+    InProgress,
+    /// All the storage entries have been downloaded.
+    Finished {
+        /// Merkle value of the root of the main trie that has been downloaded. Must be compared
+        /// with the state trie root found in the header of the block.
+        state_root: [u8; 32],
+    },
+}
+
+/// Error potentially returned by [`StateDownload::process_response`].
+#[derive(Debug, Clone, derive_more::Display)]
+pub enum Error {
+    /// Storage entries of a trie aren't ordered by key.
+    NotOrdered,
+    /// Response contains no storage entry.
+    EmptyResponse,
+    /// Response contains a child trie that doesn't match any child trie root of the main trie.
+    UnexpectedChildTrie,
+    /// Main trie contains the root of a child trie whose content is missing from the response.
+    MissingChildTrie,
+    /// Main trie contains an entry whose key starts with `:child_storage:` but that isn't a
+    /// default child trie.
+    InvalidChildTrieKey,
+    /// Response contains entries of the main trie that follow a child trie that isn't
+    /// complete.
+    ChildTrieNotFinished,
+    /// Merkle value of the root of a child trie doesn't match its content.
+    ChildTrieRootMismatch,
+}
+
+mod tests;
@@ -0,0 +1,334 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#![cfg(test)]
+
+use super::{Error, Progress, StateDownload, CHILD_TRIE_PREFIX};
+use crate::{
+    network::codec,
+    trie::{ordered_builder, HashFunction, TrieEntryVersion},
+};
+
+use alloc::{collections::BTreeMap, vec, vec::Vec};
+use core::ops::Bound;
+
+/// Storage of a block: the main trie and the default child tries, indexed by child trie key
+/// without prefix.
+struct Storage {
+    main_trie: BTreeMap<Vec<u8>, Vec<u8>>,
+    child_tries: BTreeMap<Vec<u8>, BTreeMap<Vec<u8>, Vec<u8>>>,
+}
+
+impl Storage {
+    /// Builds a storage whose main trie contains the given entries plus the roots of the given
+    /// child tries.
+    fn new(
+        main_trie: impl IntoIterator<Item = (Vec<u8>, Vec<u8>)>,
+        child_tries: impl IntoIterator<Item = (Vec<u8>, BTreeMap<Vec<u8>, Vec<u8>>)>,
+    ) -> Self {
+        let mut main_trie = main_trie.into_iter().collect::<BTreeMap<_, _>>();
+        let child_tries = child_tries.into_iter().collect::<BTreeMap<_, _>>();
+        for (child_trie, entries) in &child_tries {
+            main_trie.insert(
+                [CHILD_TRIE_PREFIX, child_trie].concat(),
+                trie_root(entries).to_vec(),
+            );
+        }
+        Storage {
+            main_trie,
+            child_tries,
+        }
+    }
+
+    /// Builds the response that a full node would send back to a state request, containing at
+    /// most `max_entries` storage entries.
+    fn serve(&self, start: codec::StateRequestStart, max_entries: usize) -> Vec<u8> {
+        let mut groups = vec![(Vec::new(), Vec::new(), false)];
+        let mut budget = max_entries;
+
+        let main_start = match start {
+            codec::StateRequestStart::MainTrie(key) => start_bound(key),
+            codec::StateRequestStart::ChildTrieDefault { child_trie, key } => {
+                if !self.serve_child(&mut groups, child_trie, key, &mut budget) {
+                    return encode(&groups);
+                }
+                Bound::Excluded([CHILD_TRIE_PREFIX, child_trie].concat())
+            }
+        };
+
+        for (key, value) in self.main_trie.range((main_start, Bound::Unbounded)) {
+            if budget == 0 {
+                return encode(&groups);
+            }
+            budget -= 1;
+            groups[0].1.push((key.clone(), value.clone()));
+
+            if let Some(child_trie) = key.strip_prefix(CHILD_TRIE_PREFIX) {
+                if !self.serve_child(&mut groups, child_trie, &[], &mut budget) {
+                    return encode(&groups);
+                }
+            }
+        }
+
+        groups[0].2 = true;
+        encode(&groups)
+    }
+
+    /// Adds the entries of a child trie to the response. Returns `true` if the child trie is
+    /// complete.
+    fn serve_child(
+        &self,
+        groups: &mut Vec<(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>, bool)>,
+        child_trie: &[u8],
+        start: &[u8],
+        budget: &mut usize,
+    ) -> bool {
+        if *budget == 0 {
+            return false;
+        }
+
+        let entries = &self.child_tries[child_trie];
+        let mut group = (trie_root(entries).to_vec(), Vec::new(), false);
+        for (key, value) in entries.range((start_bound(start), Bound::Unbounded)) {
+            if *budget == 0 {
+                groups.push(group);
+                return false;
+            }
+            *budget -= 1;
+            group.1.push((key.clone(), value.clone()));
+        }
+
+        group.2 = true;
+        groups.push(group);
+        true
+    }
+
+    /// Returns the total number of storage entries, including the child tries.
+    fn num_entries(&self) -> u64 {
+        u64::try_from(
+            self.main_trie.len() + self.child_tries.values().map(|c| c.len()).sum::<usize>(),
+        )
+        .unwrap()
+    }
+}
+
+/// Like Substrate, the entries of the response start strictly after the start key of the
+/// request, unless it is empty.
+fn start_bound(key: &[u8]) -> Bound<Vec<u8>> {
+    if key.is_empty() {
+        Bound::Included(Vec::new())
+    } else {
+        Bound::Excluded(key.to_vec())
+    }
+}
+
+/// Encodes a state response, in order to test the decoding as well.
+fn encode(groups: &[(Vec<u8>, Vec<(Vec<u8>, Vec<u8>)>, bool)]) -> Vec<u8> {
+    codec::build_state_response(codec::StateResponse {
+        entries: groups
+            .iter()
+            .map(
+                |(state_root, entries, complete)| codec::StateResponseEntries {
+                    state_root,
+                    entries: entries.iter().map(|(k, v)| (&k[..], &v[..])).collect(),
+                    complete: *complete,
+                },
+            )
+            .collect(),
+        proof: &[],
+    })
+    .fold(Vec::new(), |mut a, b| {
+        a.extend_from_slice(b.as_ref());
+        a
+    })
+}
+
+/// Calculates the Merkle value of the root of a trie.
+fn trie_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>) -> [u8; 32] {
+    let mut builder =
+        ordered_builder::OrderedTrieBuilder::new(HashFunction::Blake2, TrieEntryVersion::V1);
+    for (key, value) in entries {
+        builder.push(key, value).unwrap();
+    }
+    builder.finish().1
+}
+
+/// Downloads the storage, and returns the root of the downloaded main trie and all the nodes.
+fn download(
+    storage: &Storage,
+    max_entries: usize,
+) -> Result<([u8; 32], Vec<super::TrieNode>, u64), Error> {
+    let mut download = StateDownload::new(TrieEntryVersion::V1);
+    let mut all_nodes = Vec::new();
+
+    for _ in 0..10000 {
+        let response = storage.serve(download.request_start(), max_entries);
+        let (nodes, progress) =
+            download.process_response(codec::decode_state_response(&response).unwrap())?;
+        all_nodes.extend(nodes);
+        if let Progress::Finished { state_root } = progress {
+            return Ok((state_root, all_nodes, download.num_entries()));
+        }
+    }
+
+    panic!("download never finishes")
+}
+
+fn entries(prefix: &[u8], num: u8) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    (0..num)
+        .map(|n| ([prefix, &[n]].concat(), vec![n; usize::from(n % 40) + 1]))
+        .collect()
+}
+
+#[test]
+fn downloads_storage_with_child_tries() {
+    let storage = Storage::new(
+        entries(b"a", 30)
+            .into_iter()
+            .chain(entries(b":code", 2))
+            .chain(entries(b"z", 10)),
+        [
+            (b"child1".to_vec(), entries(b"c", 25)),
+            (b"child2".to_vec(), entries(b"", 1)),
+            (b"child3".to_vec(), entries(b"foo", 60)),
+        ],
+    );
+    let expected_root = trie_root(&storage.main_trie);
+
+    // Different sizes of responses cause the responses to be truncated at different places,
+    // including right after the root of a child trie and in the middle of a child trie.
+    for max_entries in (1..20).chain([1000]) {
+        let (state_root, nodes, num_entries) = download(&storage, max_entries).unwrap();
+        assert_eq!(state_root, expected_root);
+        assert_eq!(num_entries, storage.num_entries());
+        assert_eq!(
+            nodes.last().unwrap().node.merkle_value.as_ref(),
+            &expected_root[..]
+        );
+        assert_eq!(
+            nodes.iter().filter(|n| n.references_child_trie).count(),
+            storage.child_tries.len()
+        );
+    }
+}
+
+#[test]
+fn unexpected_child_trie_root() {
+    let mut storage = Storage::new(entries(b"a", 10), [(b"child".to_vec(), entries(b"c", 10))]);
+    storage
+        .child_tries
+        .get_mut(&b"child"[..])
+        .unwrap()
+        .insert(b"extra".to_vec(), b"value".to_vec());
+
+    // The server sends the root of the modified child trie, which doesn't match the main trie.
+    assert!(matches!(
+        download(&storage, 1000),
+        Err(Error::UnexpectedChildTrie)
+    ));
+}
+
+#[test]
+fn child_trie_content_mismatch() {
+    let mut storage = Storage::new(entries(b"a", 10), [(b"child".to_vec(), entries(b"c", 10))]);
+    let child_root = trie_root(&storage.child_tries[&b"child"[..]]);
+    storage
+        .child_tries
+        .get_mut(&b"child"[..])
+        .unwrap()
+        .insert(b"extra".to_vec(), b"value".to_vec());
+
+    // Server that sends the correct root but the content of the modified child trie.
+    let mut download = StateDownload::new(TrieEntryVersion::V1);
+    let mut groups = vec![(
+        Vec::new(),
+        storage
+            .main_trie
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        true,
+    )];
+    groups.push((
+        child_root.to_vec(),
+        storage.child_tries[&b"child"[..]]
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        true,
+    ));
+    let response = encode(&groups);
+    assert!(matches!(
+        download.process_response(codec::decode_state_response(&response).unwrap()),
+        Err(Error::ChildTrieRootMismatch)
+    ));
+}
+
+#[test]
+fn missing_child_trie() {
+    let storage = Storage::new(entries(b"a", 10), [(b"child".to_vec(), entries(b"c", 10))]);
+
+    let mut download = StateDownload::new(TrieEntryVersion::V1);
+    let response = encode(&[(
+        Vec::new(),
+        storage
+            .main_trie
+            .iter()
+            .map(|(k, v)| (k.clone(), v.clone()))
+            .collect(),
+        true,
+    )]);
+    assert!(matches!(
+        download.process_response(codec::decode_state_response(&response).unwrap()),
+        Err(Error::MissingChildTrie)
+    ));
+}
+
+#[test]
+fn not_ordered() {
+    let mut download = StateDownload::new(TrieEntryVersion::V1);
+    let response = encode(&[(
+        Vec::new(),
+        vec![
+            (b"b".to_vec(), b"1".to_vec()),
+            (b"a".to_vec(), b"2".to_vec()),
+        ],
+        true,
+    )]);
+    assert!(matches!(
+        download.process_response(codec::decode_state_response(&response).unwrap()),
+        Err(Error::NotOrdered)
+    ));
+}
+
+#[test]
+fn empty_response() {
+    let mut download = StateDownload::new(TrieEntryVersion::V1);
+    let response = encode(&[(Vec::new(), Vec::new(), false)]);
+    assert!(matches!(
+        download.process_response(codec::decode_state_response(&response).unwrap()),
+        Err(Error::EmptyResponse)
+    ));
+}
+
+#[test]
+fn empty_storage() {
+    let storage = Storage::new([], []);
+    let (state_root, _, num_entries) = download(&storage, 10).unwrap();
+    assert_eq!(state_root, trie_root(&BTreeMap::new()));
+    assert_eq!(num_entries, 0);
+}
@@ -196,7 +196,9 @@ pub fn start_warp_sync<TSrc, TRq>(
     }
 
     match config.start_chain_information.as_ref().consensus {
-        ChainInformationConsensusRef::Babe { .. } | ChainInformationConsensusRef::Aura { .. } => {}
+        ChainInformationConsensusRef::Babe { .. }
+        | ChainInformationConsensusRef::Aura { .. }
+        | ChainInformationConsensusRef::Sassafras { .. } => {}
         ChainInformationConsensusRef::Unknown => {
             return Err((
                 config.start_chain_information,
@@ -475,6 +477,16 @@ fn runtime_calls_default_value(
                 CallProof::NotStarted,
             );
         }
+        ChainInformationConsensusRef::Sassafras { .. } => {
+            list.insert(
+                chain_information::build::RuntimeCall::SassafrasApiCurrentEpoch,
+                CallProof::NotStarted,
+            );
+            list.insert(
+                chain_information::build::RuntimeCall::SassafrasApiNextEpoch,
+                CallProof::NotStarted,
+            );
+        }
         ChainInformationConsensusRef::Unknown => {}
     }
     list
@@ -1858,6 +1870,7 @@ impl<TSrc, TRq> BuildRuntime<TSrc, TRq> {
             heap_pages: decoded_heap_pages,
             exec_hint,
             allow_unresolved_imports,
+            fuel_budget: None,
         }) {
             Ok(runtime) => runtime,
             Err(err) => {
@@ -335,6 +335,7 @@ pub fn validate_transaction(
                 storage_main_trie_changes: storage_diff::TrieDiff::empty(),
                 max_log_level: config.max_log_level,
                 calculate_trie_changes: false,
+                trace: false,
             });
 
             // Information used later, after `Core_initialize_block` is done.
@@ -372,6 +373,7 @@ pub fn validate_transaction(
                 storage_main_trie_changes: storage_diff::TrieDiff::empty(),
                 max_log_level: config.max_log_level,
                 calculate_trie_changes: false,
+                trace: false,
             });
 
             match vm {
@@ -461,6 +463,7 @@ impl Query {
                         storage_main_trie_changes: success.storage_changes.into_main_trie_diff(),
                         max_log_level: info.max_log_level,
                         calculate_trie_changes: false,
+                        trace: false,
                     });
 
                     match vm {
@@ -30,6 +30,7 @@ fn validate_from_proof() {
         module: hex::decode(&test.runtime_code).unwrap(),
         heap_pages: executor::DEFAULT_HEAP_PAGES,
         allow_unresolved_imports: true,
+        fuel_budget: None,
         exec_hint: executor::vm::ExecHint::Oneshot,
     })
     .unwrap();
@@ -0,0 +1,398 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Builds the nodes of a trie from its storage entries, provided one by one in increasing order
+//! of keys.
+//!
+//! Contrary to [`super::trie_structure`], the entries of the trie don't need to all be kept in
+//! memory at the same time. As soon as it is known that a node can't have any new descendant,
+//! its Merkle value is calculated and the node is yielded back. Only the nodes that are
+//! ancestors of the latest entry are kept in memory.
+//!
+//! This is typically used when downloading the entire storage of a block from the network, in
+//! which case the storage entries are received ordered by key.
+//!
+//! Nodes are always yielded back after all of their descendants.
+//!
+//! # Example
+//!
+//! ```
+//! use smoldot::trie::{HashFunction, TrieEntryVersion, ordered_builder};
+//!
+//! let mut builder = ordered_builder::OrderedTrieBuilder::new(
+//!     HashFunction::Blake2,
+//!     TrieEntryVersion::V1,
+//! );
+//!
+//! let mut nodes = Vec::new();
+//! nodes.extend(builder.push(b"foo", b"bar").unwrap());
+//! nodes.extend(builder.push(b"fuu", b"baz").unwrap());
+//! let (last_nodes, trie_root) = builder.finish();
+//! nodes.extend(last_nodes);
+//!
+//! // The trie contains the two storage entries and the branch node that joins them.
+//! assert_eq!(nodes.len(), 3);
+//! assert_eq!(nodes.last().unwrap().merkle_value.as_ref(), &trie_root[..]);
+//! ```
+
+use super::{nibble, trie_node, HashFunction, Nibble, TrieEntryVersion};
+
+use alloc::vec::Vec;
+
+/// See [the module-level documentation](..).
+pub struct OrderedTrieBuilder {
+    /// Hash function used by the trie.
+    hash_function: HashFunction,
+
+    /// Version of all the storage entries of the trie.
+    version: TrieEntryVersion,
+
+    /// Nodes whose Merkle value can't be calculated yet because they might still get new
+    /// children. Each node is an ancestor of the one that follows. The last node is always the
+    /// latest entry that has been pushed.
+    stack: Vec<PendingNode>,
+}
+
+struct PendingNode {
+    /// Full key of the node, in nibbles.
+    key: Vec<Nibble>,
+    /// Storage value of the node, if any. Always `Some` for the nodes that have been pushed,
+    /// and `None` for branch nodes.
+    storage_value: Option<Vec<u8>>,
+    /// Merkle values of the children of the node found so far.
+    children: [Option<trie_node::MerkleValueOutput>; 16],
+}
+
+impl OrderedTrieBuilder {
+    /// Initializes a new builder for an empty trie.
+    pub fn new(hash_function: HashFunction, version: TrieEntryVersion) -> Self {
+        OrderedTrieBuilder {
+            hash_function,
+            version,
+            stack: Vec::with_capacity(16),
+        }
+    }
+
+    /// Adds a storage entry to the trie.
+    ///
+    /// Returns the list of nodes that are now complete, in other words whose Merkle value can be
+    /// calculated. Nodes are returned in an order where children always come before their
+    /// parent.
+    ///
+    /// Returns an error if the key isn't strictly superior to the previous key that has been
+    /// pushed, in which case the state of the builder is unchanged.
+    pub fn push(&mut self, key: &[u8], value: &[u8]) -> Result<Vec<Node>, PushError> {
+        let key = nibble::bytes_to_nibbles(key.iter().copied()).collect::<Vec<_>>();
+        let mut out = Vec::new();
+
+        if let Some(previous) = self.stack.last() {
+            if key <= previous.key {
+                return Err(PushError::NotOrdered);
+            }
+
+            // Length of the common prefix between the new key and the previous key. All the
+            // nodes whose key is longer than that can't have any new descendant.
+            let common_prefix = key
+                .iter()
+                .zip(previous.key.iter())
+                .take_while(|(a, b)| a == b)
+                .count();
+
+            while self
+                .stack
+                .last()
+                .map_or(false, |node| node.key.len() > common_prefix)
+            {
+                let node = self.stack.pop().unwrap();
+
+                // If the parent of the node isn't on the stack, it is a branch node located
+                // where the previous key and the new key diverge.
+                if self
+                    .stack
+                    .last()
+                    .map_or(true, |parent| parent.key.len() < common_prefix)
+                {
+                    self.stack.push(PendingNode {
+                        key: node.key[..common_prefix].to_vec(),
+                        storage_value: None,
+                        children: Default::default(),
+                    });
+                }
+
+                let parent = self.stack.last_mut().unwrap();
+                let child_index = usize::from(u8::from(node.key[parent.key.len()]));
+                let node = Self::complete_node(
+                    self.hash_function,
+                    self.version,
+                    node,
+                    Some(parent.key.len()),
+                );
+                parent.children[child_index] = Some(node.merkle_value.clone());
+                out.push(node);
+            }
+        }
+
+        self.stack.push(PendingNode {
+            key,
+            storage_value: Some(value.to_vec()),
+            children: Default::default(),
+        });
+
+        Ok(out)
+    }
+
+    /// Indicates that all the storage entries have been pushed.
+    ///
+    /// Returns the list of nodes that weren't complete yet, in an order where children always
+    /// come before their parent, and the Merkle value of the root of the trie. If the trie isn't
+    /// empty, the last node of the list is the root node.
+    pub fn finish(mut self) -> (Vec<Node>, [u8; 32]) {
+        let mut out = Vec::with_capacity(self.stack.len());
+
+        while let Some(node) = self.stack.pop() {
+            let parent_key_len = self.stack.last().map(|parent| parent.key.len());
+            let child_index = parent_key_len.map(|len| usize::from(u8::from(node.key[len])));
+            let node = Self::complete_node(self.hash_function, self.version, node, parent_key_len);
+            if let Some(child_index) = child_index {
+                self.stack.last_mut().unwrap().children[child_index] =
+                    Some(node.merkle_value.clone());
+            }
+            out.push(node);
+        }
+
+        let root = match (out.last(), self.hash_function) {
+            (Some(root), _) => <[u8; 32]>::try_from(root.merkle_value.clone()).unwrap(),
+            (None, HashFunction::Blake2) => super::EMPTY_BLAKE2_TRIE_MERKLE_VALUE,
+            (None, HashFunction::Keccak256) => super::EMPTY_KECCAK256_TRIE_MERKLE_VALUE,
+        };
+
+        (out, root)
+    }
+
+    /// Calculates the Merkle value of the given node, whose parent has a key of length
+    /// `parent_key_len`, or that is the root node if `None`.
+    fn complete_node(
+        hash_function: HashFunction,
+        version: TrieEntryVersion,
+        node: PendingNode,
+        parent_key_len: Option<usize>,
+    ) -> Node {
+        let partial_key_start = parent_key_len.map_or(0, |len| len + 1);
+
+        let storage_value_hash = match (&node.storage_value, version) {
+            (Some(value), TrieEntryVersion::V1) if value.len() >= 33 => Some(match hash_function {
+                HashFunction::Blake2 => {
+                    <[u8; 32]>::try_from(blake2_rfc::blake2b::blake2b(32, &[], value).as_bytes())
+                        .unwrap()
+                }
+                HashFunction::Keccak256 => <sha3::Keccak256 as sha3::Digest>::digest(value).into(),
+            }),
+            _ => None,
+        };
+
+        let merkle_value = trie_node::calculate_merkle_value(
+            trie_node::Decoded {
+                children: core::array::from_fn::<_, 16, _>(|n| {
+                    node.children[n].as_ref().map(|child| child.as_ref())
+                }),
+                partial_key: node.key[partial_key_start..].iter().copied(),
+                storage_value: match (&node.storage_value, &storage_value_hash) {
+                    (_, Some(hash)) => trie_node::StorageValue::Hashed(hash),
+                    (Some(value), None) => trie_node::StorageValue::Unhashed(value),
+                    (None, None) => trie_node::StorageValue::None,
+                },
+            },
+            hash_function,
+            parent_key_len.is_none(),
+        )
+        // Only branch nodes don't have a storage value, and they always have at least two
+        // children.
+        .unwrap_or_else(|_| unreachable!());
+
+        Node {
+            partial_key: node.key[partial_key_start..].to_vec(),
+            key: node.key,
+            children_merkle_values: node.children,
+            storage_value: node.storage_value,
+            merkle_value,
+        }
+    }
+}
+
+/// Node of the trie yielded by the [`OrderedTrieBuilder`].
+#[derive(Debug, Clone)]
+pub struct Node {
+    /// Full key of the node, in nibbles.
+    pub key: Vec<Nibble>,
+    /// Partial key of the node, in nibbles. Always a suffix of [`Node::key`].
+    pub partial_key: Vec<Nibble>,
+    /// Merkle values of the children of the node, indexed by nibble.
+    pub children_merkle_values: [Option<trie_node::MerkleValueOutput>; 16],
+    /// Storage value of the node, if any.
+    pub storage_value: Option<Vec<u8>>,
+    /// Merkle value of the node. Always 32 bytes for the root node.
+    pub merkle_value: trie_node::MerkleValueOutput,
+}
+
+/// Error potentially returned by [`OrderedTrieBuilder::push`].
+#[derive(Debug, Clone, derive_more::Display)]
+pub enum PushError {
+    /// Key isn't strictly superior to the previous key.
+    NotOrdered,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OrderedTrieBuilder, PushError};
+    use crate::trie::{calculate_root, trie_structure, HashFunction, TrieEntryVersion};
+    use alloc::{collections::BTreeMap, vec::Vec};
+    use core::ops::Bound;
+    use rand::{distributions::Uniform, Rng as _};
+
+    /// Builds the trie using [`OrderedTrieBuilder`], and returns the Merkle value of the root
+    /// and all the nodes.
+    fn build(
+        entries: &BTreeMap<Vec<u8>, Vec<u8>>,
+        version: TrieEntryVersion,
+    ) -> (Vec<super::Node>, [u8; 32]) {
+        let mut builder = OrderedTrieBuilder::new(HashFunction::Blake2, version);
+        let mut nodes = Vec::new();
+        for (key, value) in entries {
+            nodes.extend(builder.push(key, value).unwrap());
+        }
+        let (last_nodes, root) = builder.finish();
+        nodes.extend(last_nodes);
+        (nodes, root)
+    }
+
+    /// Calculates the Merkle value of the root using [`calculate_root`].
+    fn reference_root(entries: &BTreeMap<Vec<u8>, Vec<u8>>, version: TrieEntryVersion) -> [u8; 32] {
+        let mut calculation = calculate_root::root_merkle_value(HashFunction::Blake2);
+
+        loop {
+            match calculation {
+                calculate_root::RootMerkleValueCalculation::Finished { hash } => return hash,
+                calculate_root::RootMerkleValueCalculation::NextKey(next_key) => {
+                    let lower_bound = if next_key.or_equal() {
+                        Bound::Included(next_key.key_before().collect::<Vec<_>>())
+                    } else {
+                        Bound::Excluded(next_key.key_before().collect::<Vec<_>>())
+                    };
+
+                    let key = entries
+                        .range((lower_bound, Bound::Unbounded))
+                        .next()
+                        .filter(|(k, _)| {
+                            k.iter()
+                                .copied()
+                                .zip(next_key.prefix())
+                                .all(|(a, b)| a == b)
+                        })
+                        .map(|(k, _)| k);
+
+                    calculation = next_key.inject_key(key.map(|k| k.iter().copied()));
+                }
+                calculate_root::RootMerkleValueCalculation::StorageValue(value) => {
+                    let key = value.key().collect::<Vec<u8>>();
+                    calculation = value.inject(entries.get(&key).map(|v| (v, version)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn empty_trie() {
+        let (nodes, root) = build(&BTreeMap::new(), TrieEntryVersion::V1);
+        assert!(nodes.is_empty());
+        assert_eq!(root, crate::trie::EMPTY_BLAKE2_TRIE_MERKLE_VALUE);
+    }
+
+    #[test]
+    fn not_ordered() {
+        let mut builder = OrderedTrieBuilder::new(HashFunction::Blake2, TrieEntryVersion::V1);
+        builder.push(b"abc", b"1").unwrap();
+        assert!(matches!(
+            builder.push(b"abc", b"2"),
+            Err(PushError::NotOrdered)
+        ));
+        assert!(matches!(
+            builder.push(b"ab", b"2"),
+            Err(PushError::NotOrdered)
+        ));
+        assert!(matches!(
+            builder.push(b"aaa", b"2"),
+            Err(PushError::NotOrdered)
+        ));
+        builder.push(b"abcd", b"2").unwrap();
+    }
+
+    #[test]
+    fn matches_reference() {
+        let mut rng = rand::thread_rng();
+
+        for _ in 0..500 {
+            let version = if rng.gen() {
+                TrieEntryVersion::V0
+            } else {
+                TrieEntryVersion::V1
+            };
+
+            // Use a small alphabet and short keys in order to create lots of shared prefixes.
+            let mut entries = BTreeMap::new();
+            for _ in 0..rng.gen_range(0..64) {
+                let key_len = rng.gen_range(0..6);
+                let key = (&mut rng)
+                    .sample_iter(Uniform::new_inclusive(0u8, 3))
+                    .map(|b| b * 0x11)
+                    .take(key_len)
+                    .collect::<Vec<_>>();
+                let value_len = if rng.gen() { 2 } else { 40 };
+                let value = (&mut rng)
+                    .sample_iter(Uniform::new_inclusive(0u8, 255))
+                    .take(value_len)
+                    .collect::<Vec<_>>();
+                entries.insert(key, value);
+            }
+
+            let (nodes, root) = build(&entries, version);
+            assert_eq!(root, reference_root(&entries, version));
+
+            // The number of nodes must match the number of nodes of the trie.
+            let mut structure = trie_structure::TrieStructure::<()>::new();
+            for key in entries.keys() {
+                let _ = structure
+                    .node(crate::trie::bytes_to_nibbles(key.iter().copied()))
+                    .into_vacant()
+                    .unwrap()
+                    .insert_storage_value()
+                    .insert((), ());
+            }
+            assert_eq!(nodes.len(), structure.len());
+
+            // All the nodes that are referenced as children must have been yielded before their
+            // parent.
+            for (index, node) in nodes.iter().enumerate() {
+                assert!(node.key.ends_with(&node.partial_key));
+                for child in node.children_merkle_values.iter().flatten() {
+                    assert!(nodes[..index]
+                        .iter()
+                        .any(|n| n.merkle_value.as_ref() == child.as_ref()));
+                }
+            }
+        }
+    }
+}
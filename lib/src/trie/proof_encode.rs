@@ -433,6 +433,98 @@ impl ProofBuilder {
             a
         })
     }
+
+    /// Builds the Merkle proof in the so-called "compact" format.
+    ///
+    /// In this format, the node values are ordered lexicographically by key, and the hash of a
+    /// child is omitted (replaced with an empty child) if the node value of this child is also
+    /// part of the proof. Similarly, the hash of a storage value is omitted if the storage value
+    /// is part of the proof, in which case the node value is prefixed with the byte `1` and is
+    /// directly followed with the storage value. The decoder can then re-calculate all the
+    /// omitted hashes.
+    ///
+    /// This format is notably used for the storage proof found in the Proof-of-Validity of
+    /// parachain blocks. See [`crate::author::pov`].
+    ///
+    /// Contrary to [`ProofBuilder::build`], the node values found in the proof builder must be
+    /// coherent. Call [`ProofBuilder::make_coherent`] beforehand if necessary.
+    ///
+    /// This function returns an iterator of buffers. The actual Merkle proof consists in the
+    /// concatenation of all the buffers.
+    pub fn build_compact(mut self) -> impl Iterator<Item = impl AsRef<[u8]> + Clone> {
+        // TODO: we need to collect the indices into a Vec due to the API of trie_structure not allowing non-mutable access to nodes
+        let node_indices = self.trie_structure.iter_ordered().collect::<Vec<_>>();
+
+        let mut entries = Vec::with_capacity(node_indices.len());
+        for node_index in node_indices {
+            let mut node = self.trie_structure.node_by_index(node_index).unwrap();
+            let is_root_node = node.is_root_node();
+
+            // Children whose node value is also in the proof are replaced with an empty child.
+            // Nodes of length < 32 are inlined within their parent and are thus left untouched.
+            let omitted_children: [bool; 16] = array::from_fn(|nibble| {
+                let nibble = nibble::Nibble::try_from(u8::try_from(nibble).unwrap()).unwrap();
+                matches!(node.child_user_data(nibble), Some(Some(child)) if child.node_value.len() >= 32)
+            });
+
+            // Ignore nodes whose value is missing.
+            let Some(node_info) = node.user_data().take() else {
+                continue;
+            };
+
+            // Nodes of length < 32 should have been inlined within their parent or ancestor.
+            // We thus skip them, unless they're the root node.
+            if !is_root_node && node_info.node_value.len() < 32 {
+                debug_assert!(node_info.storage_value_node.is_none());
+                continue;
+            }
+
+            // We already make sure that node values are valid when inserting them. As such,
+            // it is ok to `unwrap()` here.
+            let mut decoded_node_value = trie_node::decode(&node_info.node_value).unwrap();
+            for (child, omitted) in decoded_node_value.children.iter_mut().zip(omitted_children) {
+                if omitted {
+                    *child = Some(&[][..]);
+                }
+            }
+
+            // If the storage value is part of the proof, it is replaced with an empty inline
+            // storage value, and the node value is prefixed with an escape byte.
+            let mut entry = Vec::with_capacity(node_info.node_value.len() + 1);
+            if node_info.storage_value_node.is_some() {
+                entry.push(1);
+                decoded_node_value.storage_value = trie_node::StorageValue::Unhashed(&[]);
+            }
+
+            // `encode` can return an error only if there's no children and no storage value,
+            // which can't happen as the node value was valid when we decoded it.
+            for buffer in trie_node::encode(decoded_node_value).unwrap() {
+                entry.extend_from_slice(buffer.as_ref());
+            }
+
+            entries.push(entry);
+            entries.extend(node_info.storage_value_node);
+        }
+
+        // The proof is the SCALE encoding of the list of entries.
+        let num_entries_encoded = crate::util::encode_scale_compact_usize(entries.len());
+        let entries = entries.into_iter().flat_map(|entry| {
+            let len = crate::util::encode_scale_compact_usize(entry.len());
+            [either::Left(len), either::Right(entry)].into_iter()
+        });
+
+        iter::once(either::Left(num_entries_encoded)).chain(entries.map(either::Right))
+    }
+
+    /// Similar to [`ProofBuilder::build_compact`], but returns a `Vec`.
+    ///
+    /// This is a convenience wrapper around [`ProofBuilder::build_compact`].
+    pub fn build_compact_to_vec(self) -> Vec<u8> {
+        self.build_compact().fold(Vec::new(), |mut a, b| {
+            a.extend_from_slice(b.as_ref());
+            a
+        })
+    }
 }
 
 impl Default for ProofBuilder {
@@ -613,6 +705,192 @@ mod tests {
         }
     }
 
+    #[test]
+    fn build_random_compact_proof() {
+        // This test builds a compact proof of a randomly-generated trie, then decodes it by
+        // re-calculating the hashes that have been omitted, and checks whether the trie root hash
+        // matches.
+
+        // Decodes the node at the head of `entries`, and returns its node value.
+        fn decode_compact(entries: &mut impl Iterator<Item = Vec<u8>>) -> Vec<u8> {
+            let entry = entries.next().unwrap();
+            let (has_separate_value, node_value) = match entry.split_first() {
+                Some((1, rest)) => (true, rest),
+                _ => (false, &entry[..]),
+            };
+            let decoded = trie_node::decode(node_value).unwrap();
+
+            let storage_value_hash =
+                has_separate_value.then(|| super::blake2_hash(&entries.next().unwrap()));
+
+            let children: [Option<Vec<u8>>; 16] =
+                array::from_fn(|nibble| match decoded.children[nibble] {
+                    Some(&[]) => {
+                        let child = decode_compact(entries);
+                        debug_assert!(child.len() >= 32);
+                        Some(super::blake2_hash(&child).to_vec())
+                    }
+                    Some(child) => Some(child.to_vec()),
+                    None => None,
+                });
+
+            trie_node::encode_to_vec(trie_node::Decoded {
+                children: array::from_fn(|n| children[n].as_deref()),
+                partial_key: decoded.partial_key,
+                storage_value: match &storage_value_hash {
+                    Some(hash) => trie_node::StorageValue::Hashed(hash),
+                    None => decoded.storage_value,
+                },
+            })
+            .unwrap()
+        }
+
+        // We repeat the test many times due to its random factor.
+        for _ in 0..1500 {
+            // Build a trie with entries with randomly generated keys.
+            let mut trie = trie_structure::TrieStructure::new();
+            for _ in 0..Uniform::new_inclusive(1, 32).sample(&mut rand::thread_rng()) {
+                let mut key = Vec::new();
+                for _ in 0..Uniform::new_inclusive(0, 12).sample(&mut rand::thread_rng()) {
+                    key.push(
+                        nibble::Nibble::try_from(
+                            Uniform::new_inclusive(0, 15).sample(&mut rand::thread_rng()),
+                        )
+                        .unwrap(),
+                    );
+                }
+
+                match trie.node(key.into_iter()) {
+                    trie_structure::Entry::Vacant(e) => {
+                        e.insert_storage_value().insert((), ());
+                    }
+                    trie_structure::Entry::Occupied(trie_structure::NodeAccess::Branch(e)) => {
+                        e.insert_storage_value();
+                    }
+                    trie_structure::Entry::Occupied(trie_structure::NodeAccess::Storage(_)) => {}
+                }
+            }
+
+            // Put the content of the trie into the proof builder. Storage values are randomly
+            // hashed, in which case their unhashed version is included in the proof.
+            let mut proof_builder = super::ProofBuilder::new();
+            for node_index in trie.iter_unordered().collect::<Vec<_>>() {
+                let key = trie
+                    .node_full_key_by_index(node_index)
+                    .unwrap()
+                    .collect::<Vec<_>>();
+
+                let mut storage_value = Vec::new();
+                for _ in 0..Uniform::new_inclusive(0, 64).sample(&mut rand::thread_rng()) {
+                    storage_value
+                        .push(Uniform::new_inclusive(0, 255).sample(&mut rand::thread_rng()));
+                }
+                let storage_value_hash = super::blake2_hash(&storage_value);
+                let hashed = rand::random::<bool>();
+
+                let has_storage_value = trie.node_by_index(node_index).unwrap().has_storage_value();
+                let node_value = trie_node::encode_to_vec(trie_node::Decoded {
+                    children: array::from_fn(|nibble| {
+                        let nibble =
+                            nibble::Nibble::try_from(u8::try_from(nibble).unwrap()).unwrap();
+                        if trie
+                            .node_by_index(node_index)
+                            .unwrap()
+                            .child_user_data(nibble)
+                            .is_some()
+                        {
+                            Some(&[][..])
+                        } else {
+                            None
+                        }
+                    }),
+                    partial_key: trie
+                        .node_by_index(node_index)
+                        .unwrap()
+                        .partial_key()
+                        .collect::<Vec<_>>()
+                        .into_iter(),
+                    storage_value: match (has_storage_value, hashed) {
+                        (true, true) => trie_node::StorageValue::Hashed(&storage_value_hash),
+                        (true, false) => trie_node::StorageValue::Unhashed(&storage_value),
+                        (false, _) => trie_node::StorageValue::None,
+                    },
+                })
+                .unwrap();
+
+                let unhashed_storage_value = if has_storage_value && hashed {
+                    Some(&storage_value[..])
+                } else {
+                    None
+                };
+                proof_builder.set_node_value(&key, &node_value, unhashed_storage_value);
+            }
+
+            // Generate the proof.
+            assert!(proof_builder.missing_node_values().next().is_none());
+            proof_builder.make_coherent();
+            let trie_root_hash = proof_builder.trie_root_hash().unwrap();
+            let proof = proof_builder.build_compact_to_vec();
+
+            // Decode the list of entries.
+            let (_, entries) = nom::combinator::all_consuming(nom::multi::length_count(
+                crate::util::nom_scale_compact_usize,
+                crate::util::nom_bytes_decode,
+            ))(&proof[..])
+            .map_err(|_: nom::Err<nom::error::Error<&[u8]>>| ())
+            .unwrap();
+
+            // Re-calculate the root hash.
+            let mut entries = entries.into_iter().map(|e| e.to_vec());
+            let root_node_value = decode_compact(&mut entries);
+            assert!(entries.next().is_none());
+            assert_eq!(super::blake2_hash(&root_node_value), trie_root_hash);
+        }
+    }
+
+    #[test]
+    fn compact_proof_omits_hashes() {
+        let mut proof_builder = super::ProofBuilder::new();
+
+        // Root node with a single child at nibble 0, and a hashed storage value whose unhashed
+        // version is provided.
+        let child_node_value = [
+            &[65, 0, 128][..],
+            &[0xaa; 32][..], // Unhashed storage value, to make the node value >= 32 bytes.
+        ]
+        .concat();
+        let child_hash = super::blake2_hash(&child_node_value);
+        let storage_value = [0xbb; 40];
+        let storage_value_hash = super::blake2_hash(&storage_value);
+        let root_node_value = trie_node::encode_to_vec(trie_node::Decoded {
+            children: array::from_fn(|n| if n == 0 { Some(&child_hash[..]) } else { None }),
+            partial_key: core::iter::empty(),
+            storage_value: trie_node::StorageValue::Hashed(&storage_value_hash),
+        })
+        .unwrap();
+
+        proof_builder.set_node_value(&[], &root_node_value, Some(&storage_value));
+        proof_builder.set_node_value(
+            &nibble::bytes_to_nibbles([0x00].into_iter()).collect::<Vec<_>>(),
+            &child_node_value,
+            None,
+        );
+        assert!(proof_builder.missing_node_values().next().is_none());
+
+        // Three entries.
+        let mut expected = vec![12];
+        // Root node, escaped, with an empty inline storage value and an empty child.
+        expected.extend_from_slice(&[4 * 6, 1, 0b1100_0000, 1, 0, 0, 0]);
+        // Storage value of the root node.
+        expected.push(4 * 40);
+        expected.extend_from_slice(&storage_value);
+        // Child node, as is.
+        expected.push(u8::try_from(child_node_value.len() * 4).unwrap());
+        expected.extend_from_slice(&child_node_value);
+
+        assert_eq!(proof_builder.build_compact_to_vec(), expected);
+    }
+
     #[test]
     fn identical_nodes_deduplicated() {
         let mut proof_builder = super::ProofBuilder::new();
@@ -71,3 +71,4 @@ pub mod babe;
 pub mod body_only;
 pub mod header_only;
 pub mod inherents;
+pub mod sassafras;
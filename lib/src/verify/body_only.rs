@@ -199,6 +199,31 @@ pub enum Error {
     ForbiddenHostCall,
 }
 
+/// Builds the SCALE-encoded parameter of the `Core_execute_block` runtime function, in other
+/// words the header of the block, without its seal, followed with its body.
+pub fn execute_block_parameter(
+    block_header: header::HeaderRef<'_>,
+    block_number_bytes: usize,
+    block_body: impl ExactSizeIterator<Item = impl AsRef<[u8]>>,
+) -> Vec<u8> {
+    // Consensus engines add a seal at the end of the digest logs. This seal is guaranteed to
+    // be the last item. We need to remove it before we can verify the unsealed header.
+    let mut unsealed_header = block_header;
+    let _seal_log = unsealed_header.digest.pop_seal();
+
+    let encoded_body_len = util::encode_scale_compact_usize(block_body.len());
+    unsealed_header
+        .scale_encoding(block_number_bytes)
+        .map(|b| either::Right(either::Left(b)))
+        .chain(iter::once(either::Right(either::Right(encoded_body_len))))
+        .chain(block_body.map(either::Left))
+        .fold(Vec::with_capacity(8192), |mut a, b| {
+            // TODO: better capacity ^ ?
+            a.extend_from_slice(AsRef::<[u8]>::as_ref(&b));
+            a
+        })
+}
+
 /// Verifies whether a block body is valid.
 pub fn verify(
     config: Config<impl ExactSizeIterator<Item = impl AsRef<[u8]> + Clone> + Clone>,
@@ -213,24 +238,11 @@ pub fn verify(
     // The first parameter of these two runtime functions is the same: a SCALE-encoded
     // `(header, body)` where `body` is a `Vec<Extrinsic>`. We perform the encoding ahead of time
     // in order to re-use it later for the second call.
-    let execute_block_parameters = {
-        // Consensus engines add a seal at the end of the digest logs. This seal is guaranteed to
-        // be the last item. We need to remove it before we can verify the unsealed header.
-        let mut unsealed_header = config.block_header.clone();
-        let _seal_log = unsealed_header.digest.pop_seal();
-
-        let encoded_body_len = util::encode_scale_compact_usize(config.block_body.len());
-        unsealed_header
-            .scale_encoding(config.block_number_bytes)
-            .map(|b| either::Right(either::Left(b)))
-            .chain(iter::once(either::Right(either::Right(encoded_body_len))))
-            .chain(config.block_body.map(either::Left))
-            .fold(Vec::with_capacity(8192), |mut a, b| {
-                // TODO: better capacity ^ ?
-                a.extend_from_slice(AsRef::<[u8]>::as_ref(&b));
-                a
-            })
-    };
+    let execute_block_parameters = execute_block_parameter(
+        config.block_header.clone(),
+        config.block_number_bytes,
+        config.block_body,
+    );
 
     // Start the virtual machine with `BlockBuilder_check_inherents`.
     let check_inherents_process = {
@@ -239,6 +251,8 @@ pub fn verify(
         let inherent_data = inherents::InherentData {
             timestamp: u64::try_from(config.now_from_unix_epoch.as_millis())
                 .unwrap_or(u64::max_value()),
+            consensus: None,
+            parachain: None,
         };
 
         let vm = runtime_host::run(runtime_host::Config {
@@ -268,6 +282,7 @@ pub fn verify(
             max_log_level: config.max_log_level,
             // Calculating the trie changes is done at the next step.
             calculate_trie_changes: false,
+            trace: false,
         });
 
         match vm {
@@ -367,6 +382,7 @@ impl VerifyInner {
                                 .into_main_trie_diff(),
                             max_log_level: 0,
                             calculate_trie_changes: self.calculate_trie_changes,
+                            trace: false,
                         });
 
                         match vm {
@@ -778,6 +794,7 @@ impl RuntimeCompilation {
             heap_pages: self.heap_pages,
             exec_hint: vm::ExecHint::CompileAheadOfTime,
             allow_unresolved_imports: false,
+            fuel_budget: None,
         }) {
             Ok(vm) => vm,
             Err(err) => {
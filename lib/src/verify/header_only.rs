@@ -18,7 +18,7 @@
 use crate::{
     chain::chain_information,
     header,
-    verify::{aura, babe},
+    verify::{aura, babe, sassafras},
 };
 
 use alloc::vec::Vec;
@@ -58,6 +58,10 @@ pub struct Config<'a> {
     ///
     /// However, since a recognized consensus engine must always be present, both `true` and
     /// `false` guarantee that the number of authorable blocks over the network is bounded.
+    ///
+    /// Because the signatures of Sassafras can't be verified (see [`sassafras`]), blocks of
+    /// chains using Sassafras are treated as using an unknown consensus engine and are only
+    /// accepted if this field is `true`.
     pub allow_unknown_consensus_engines: bool,
 }
 
@@ -96,6 +100,19 @@ pub enum ConfigConsensus<'a> {
         /// 00:00:00 UTC on 1 January 1970), ignoring leap seconds.
         now_from_unix_epoch: Duration,
     },
+
+    /// Chain is using the Sassafras consensus engine.
+    Sassafras {
+        /// Number of slots per epoch in the Sassafras configuration.
+        slots_per_epoch: NonZeroU64,
+
+        /// Epoch the parent block belongs to. Must be `None` if and only if the parent block's
+        /// number is 0, as block #0 doesn't belong to any epoch.
+        parent_block_epoch: Option<chain_information::SassafrasEpochInformationRef<'a>>,
+
+        /// Epoch that follows the epoch the parent block belongs to.
+        parent_block_next_epoch: chain_information::SassafrasEpochInformationRef<'a>,
+    },
 }
 
 /// Extra items of [`Config`] that are dependant on the finality engine of the chain.
@@ -138,6 +155,23 @@ pub enum Success {
         /// passed as [`ConfigConsensus::Babe::parent_block_epoch`].
         epoch_transition_target: Option<chain_information::BabeEpochInformation>,
     },
+
+    /// Chain is using the Sassafras consensus engine.
+    Sassafras {
+        /// Slot number the block belongs to.
+        ///
+        /// > **Note**: This is a simple reminder. The value can also be found in the header of the
+        /// >           block.
+        slot_number: u64,
+
+        /// `true` if the slot has been claimed using a ticket. `false` if it has been claimed
+        /// using the fallback mechanism.
+        is_ticket_claim: bool,
+
+        /// If `Some`, the verified block contains an epoch transition describing the new
+        /// "next epoch". See [`Success::Babe::epoch_transition_target`].
+        epoch_transition_target: Option<chain_information::SassafrasEpochInformation>,
+    },
 }
 
 /// Error that can happen during the verification.
@@ -160,6 +194,9 @@ pub enum Error {
     /// Failed to verify the authenticity of the block with the BABE algorithm.
     #[display(fmt = "{_0}")]
     BabeVerification(babe::VerifyError),
+    /// Failed to verify the authenticity of the block with the Sassafras algorithm.
+    #[display(fmt = "{_0}")]
+    SassafrasVerification(sassafras::VerifyError),
     /// Block schedules a Grandpa authorities change while another change is still in progress.
     GrandpaChangesOverlap,
 }
@@ -222,7 +259,9 @@ pub fn verify(config: Config) -> Result<Success, Error> {
             slot_duration,
             now_from_unix_epoch,
         } => {
-            if config.block_header.digest.has_any_babe() {
+            if config.block_header.digest.has_any_babe()
+                || config.block_header.digest.has_any_sassafras()
+            {
                 return Err(Error::MultipleConsensusEngines);
             }
 
@@ -248,7 +287,9 @@ pub fn verify(config: Config) -> Result<Success, Error> {
             slots_per_epoch,
             now_from_unix_epoch,
         } => {
-            if config.block_header.digest.has_any_aura() {
+            if config.block_header.digest.has_any_aura()
+                || config.block_header.digest.has_any_sassafras()
+            {
                 return Err(Error::MultipleConsensusEngines);
             }
 
@@ -271,5 +312,39 @@ pub fn verify(config: Config) -> Result<Success, Error> {
                 Err(err) => Err(Error::BabeVerification(err)),
             }
         }
+        ConfigConsensus::Sassafras {
+            parent_block_epoch,
+            parent_block_next_epoch,
+            slots_per_epoch,
+        } => {
+            if config.block_header.digest.has_any_aura()
+                || config.block_header.digest.has_any_babe()
+            {
+                return Err(Error::MultipleConsensusEngines);
+            }
+
+            // The seal, VRF output and ticket claim can't be verified. Only accept the block if
+            // the API user is fine with consensus engines that aren't fully verified.
+            if !config.allow_unknown_consensus_engines {
+                return Err(Error::UnknownConsensusEngine { engine: *b"SASS" });
+            }
+
+            let result = sassafras::verify_header(sassafras::VerifyConfig {
+                header: config.block_header.clone(),
+                parent_block_header: config.parent_block_header,
+                parent_block_epoch,
+                parent_block_next_epoch,
+                slots_per_epoch,
+            });
+
+            match result {
+                Ok(s) => Ok(Success::Sassafras {
+                    epoch_transition_target: s.epoch_transition_target,
+                    is_ticket_claim: s.is_ticket_claim,
+                    slot_number: s.slot_number,
+                }),
+                Err(err) => Err(Error::SassafrasVerification(err)),
+            }
+        }
     }
 }
@@ -28,6 +28,11 @@
 //! When a block is later verified, the inherents are verified by calling a runtime function and
 //! passing as parameter an encoded [`InherentData`] as well.
 
+use crate::util;
+
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{cmp, iter};
+
 /// Values of the inherents to pass to the runtime.
 ///
 /// Historically, the inherent data included an Aura or Babe slot number, using the identifiers
@@ -37,14 +42,92 @@
 /// (calling `BlockBuilder_check_inherents`) of blocks that are using older runtime versions will
 /// lead to errors concerning the Aura or Babe modules that should simply be ignored. Authoring
 /// blocks using older runtime versions is not supported anymore.
-#[derive(Debug)]
+///
+/// The slot number can nonetheless be passed through [`InherentData::consensus`], as some
+/// runtimes (in particular parachain runtimes built with Cumulus) still make use of it.
+#[derive(Debug, Clone)]
 pub struct InherentData {
     /// Number of milliseconds since the UNIX epoch when the block is generated, ignoring leap
     /// seconds.
     ///
     /// Its identifier passed to the runtime is: `timstap0`.
     pub timestamp: u64,
-    // TODO: parachain-related inherents are missing
+
+    /// Consensus-related inherents. `None` if no slot number should be passed to the runtime.
+    pub consensus: Option<InherentDataConsensus>,
+
+    /// Parachain-related inherents. Must be `Some` when authoring a block of a parachain, and
+    /// `None` otherwise.
+    ///
+    /// Its identifier passed to the runtime is: `sysi1337`.
+    pub parachain: Option<ParachainInherentData>,
+}
+
+/// See [`InherentData::consensus`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InherentDataConsensus {
+    /// Chain is using the Aura consensus algorithm.
+    Aura {
+        /// Number of the slot the block belongs to.
+        ///
+        /// Its identifier passed to the runtime is: `auraslot`.
+        slot_number: u64,
+    },
+    /// Chain is using the Babe consensus algorithm.
+    Babe {
+        /// Number of the slot the block belongs to.
+        ///
+        /// Its identifier passed to the runtime is: `babeslot`.
+        slot_number: u64,
+    },
+}
+
+/// Inherents that parachains built with Cumulus expect in each of their blocks.
+///
+/// All these information come from the relay chain. See the [`crate::sync::para`] module for
+/// how to obtain them.
+#[derive(Debug, Clone)]
+pub struct ParachainInherentData {
+    /// Number of bytes used to encode block numbers of the relay chain.
+    pub relay_chain_block_number_bytes: usize,
+
+    /// SCALE-encoded header of the parent of the parachain block being generated, as found in
+    /// the persisted validation data.
+    ///
+    /// See [`crate::sync::para::PersistedValidationDataRef::parent_head`].
+    pub parent_head: Vec<u8>,
+
+    /// Number of the relay chain block that the parachain block is built upon.
+    pub relay_parent_number: u64,
+
+    /// Storage trie root of the relay chain block that the parachain block is built upon.
+    pub relay_parent_storage_root: [u8; 32],
+
+    /// Maximum legal size of a POV block, in bytes.
+    pub max_pov_size: u32,
+
+    /// List of trie nodes of the storage of the relay parent block, proving the value of the
+    /// relay chain storage items that the parachain runtime reads.
+    ///
+    /// The order of the nodes is irrelevant. See [`crate::sync::para::relay_chain_state_proof_keys`]
+    /// for the list of keys whose proof must be included.
+    pub relay_chain_state: Vec<Vec<u8>>,
+
+    /// Messages sent from the relay chain to the parachain, in the order in which they have been
+    /// queued.
+    pub downward_messages: Vec<InboundMessage>,
+
+    /// Messages sent from other parachains to the parachain, indexed by sender parachain id.
+    pub horizontal_messages: BTreeMap<u32, Vec<InboundMessage>>,
+}
+
+/// Message received by a parachain. See [`ParachainInherentData`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InboundMessage {
+    /// Number of the relay chain block where the message has been enqueued.
+    pub sent_at: u64,
+    /// Opaque content of the message.
+    pub data: Vec<u8>,
 }
 
 impl InherentData {
@@ -52,6 +135,142 @@ impl InherentData {
     pub fn as_raw_list(
         &'_ self,
     ) -> impl ExactSizeIterator<Item = ([u8; 8], impl AsRef<[u8]> + Clone + '_)> + Clone + '_ {
-        [(*b"timstap0", self.timestamp.to_le_bytes())].into_iter()
+        let consensus = self.consensus.map(|consensus| match consensus {
+            InherentDataConsensus::Aura { slot_number } => (*b"auraslot", slot_number),
+            InherentDataConsensus::Babe { slot_number } => (*b"babeslot", slot_number),
+        });
+
+        let parachain = self
+            .parachain
+            .as_ref()
+            .map(|parachain| (*b"sysi1337", parachain.scale_encoding_vec()));
+
+        iter::once((*b"timstap0", either::Left(self.timestamp.to_le_bytes())))
+            .chain(consensus.map(|(id, slot_number)| (id, either::Left(slot_number.to_le_bytes()))))
+            .chain(parachain.map(|(id, encoded)| (id, either::Right(encoded))))
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+impl ParachainInherentData {
+    /// Returns the SCALE encoding of this data, as expected by the `parachain-system` pallet of
+    /// Cumulus.
+    pub fn scale_encoding_vec(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(
+            1024 + self
+                .relay_chain_state
+                .iter()
+                .fold(0, |sum, node| sum + node.len() + 4),
+        );
+
+        let encode_number = |out: &mut Vec<u8>, number: u64| {
+            let number_bytes = number.to_le_bytes();
+            let num_bytes = self.relay_chain_block_number_bytes;
+            out.extend_from_slice(&number_bytes[..cmp::min(num_bytes, number_bytes.len())]);
+            // Block numbers larger than 64 bits are padded with zeroes.
+            out.resize(out.len() + num_bytes.saturating_sub(number_bytes.len()), 0);
+        };
+
+        let encode_messages = |out: &mut Vec<u8>, messages: &[InboundMessage]| {
+            out.extend_from_slice(util::encode_scale_compact_usize(messages.len()).as_ref());
+            for message in messages {
+                encode_number(out, message.sent_at);
+                out.extend_from_slice(
+                    util::encode_scale_compact_usize(message.data.len()).as_ref(),
+                );
+                out.extend_from_slice(&message.data);
+            }
+        };
+
+        // Persisted validation data.
+        out.extend_from_slice(util::encode_scale_compact_usize(self.parent_head.len()).as_ref());
+        out.extend_from_slice(&self.parent_head);
+        encode_number(&mut out, self.relay_parent_number);
+        out.extend_from_slice(&self.relay_parent_storage_root);
+        out.extend_from_slice(&self.max_pov_size.to_le_bytes());
+
+        // The relay chain state proof is a `BTreeSet` on the Cumulus side. Nodes must be sorted
+        // and de-duplicated in order to match the encoding of this type.
+        let mut relay_chain_state = self
+            .relay_chain_state
+            .iter()
+            .map(|node| &node[..])
+            .collect::<Vec<_>>();
+        relay_chain_state.sort_unstable();
+        relay_chain_state.dedup();
+        out.extend_from_slice(util::encode_scale_compact_usize(relay_chain_state.len()).as_ref());
+        for node in relay_chain_state {
+            out.extend_from_slice(util::encode_scale_compact_usize(node.len()).as_ref());
+            out.extend_from_slice(node);
+        }
+
+        encode_messages(&mut out, &self.downward_messages);
+
+        out.extend_from_slice(
+            util::encode_scale_compact_usize(self.horizontal_messages.len()).as_ref(),
+        );
+        for (sender, messages) in &self.horizontal_messages {
+            out.extend_from_slice(&sender.to_le_bytes());
+            encode_messages(&mut out, messages);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::collections::BTreeMap;
+
+    #[test]
+    fn timestamp_only() {
+        let data = super::InherentData {
+            timestamp: 0x0102030405060708,
+            consensus: None,
+            parachain: None,
+        };
+
+        let list = data
+            .as_raw_list()
+            .map(|(id, value)| (id, value.as_ref().to_vec()))
+            .collect::<Vec<_>>();
+        assert_eq!(list, vec![(*b"timstap0", vec![8, 7, 6, 5, 4, 3, 2, 1])]);
+    }
+
+    #[test]
+    fn parachain_encoding() {
+        let data = super::ParachainInherentData {
+            relay_chain_block_number_bytes: 4,
+            parent_head: vec![0xaa, 0xbb],
+            relay_parent_number: 5,
+            relay_parent_storage_root: [0x11; 32],
+            max_pov_size: 0x500000,
+            relay_chain_state: vec![vec![2], vec![1], vec![2]],
+            downward_messages: vec![super::InboundMessage {
+                sent_at: 3,
+                data: vec![0xcc],
+            }],
+            horizontal_messages: {
+                let mut list = BTreeMap::new();
+                list.insert(
+                    1000,
+                    vec![super::InboundMessage {
+                        sent_at: 4,
+                        data: vec![],
+                    }],
+                );
+                list
+            },
+        };
+
+        let mut expected = vec![8, 0xaa, 0xbb, 5, 0, 0, 0];
+        expected.extend_from_slice(&[0x11; 32]);
+        expected.extend_from_slice(&[0, 0, 0x50, 0]);
+        expected.extend_from_slice(&[8, 4, 1, 4, 2]);
+        expected.extend_from_slice(&[4, 3, 0, 0, 0, 4, 0xcc]);
+        expected.extend_from_slice(&[4, 0xe8, 0x3, 0, 0, 4, 4, 0, 0, 0, 0]);
+
+        assert_eq!(data.scale_encoding_vec(), expected);
     }
 }
@@ -0,0 +1,288 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Sassafras consensus.
+//!
+//! Sassafras, for Semi Anonymous Sortition of Staked Assignees For Fixed-time Rhythmic
+//! Assignment of Slots, is a consensus algorithm meant to replace BABE.
+//!
+//! # Overview of Sassafras
+//!
+//! Similarly to BABE (see [`crate::verify::babe`]), time is divided into non-overlapping
+//! **epochs**, themselves divided into **slots**. The first block of each epoch contains, in its
+//! header, the list of authorities and the randomness value of the epoch that follows.
+//!
+//! Contrary to BABE, each slot is assigned to exactly one authority. During an epoch, authorities
+//! submit anonymous *tickets* to the runtime. The runtime then sorts these tickets and assigns
+//! each slot of the next epoch to one of them. An authority that owns the ticket of a slot
+//! proves its ownership in the header of the block it produces.
+//!
+//! Slots for which no ticket exists are assigned to the authority at index
+//! `slot_number % authorities.len()`. This is called the *fallback* mechanism.
+//!
+//! Every block must contain, in its header, a pre-runtime digest indicating the slot and the
+//! authority that has produced it, alongside with a VRF signature. The header must also be
+//! sealed with a signature of the authority.
+//!
+//! # Usage
+//!
+//! The handling of epochs is identical to the one of [`crate::verify::babe`]. See the
+//! documentation of this module for more information.
+//!
+//! > **Note**: The signatures of Sassafras use the Bandersnatch elliptic curve, which smoldot
+//! >           doesn't support yet. As such, the seal, VRF signature, and ticket claim aren't
+//! >           verified. Only the slot number, the author index, and the epoch transitions are.
+//! >           For this reason, [`crate::verify::header_only`] only accepts Sassafras blocks if
+//! >           unknown consensus engines are allowed.
+//!
+//! Disabled authorities (indicated by the `OnDisabled` consensus log) are tracked by the
+//! runtime. Header verification only makes sure that the disabled authority exists.
+
+use crate::{chain::chain_information, header};
+
+use core::num::NonZeroU64;
+
+/// Configuration for [`verify_header`].
+pub struct VerifyConfig<'a> {
+    /// Header of the block to verify.
+    pub header: header::HeaderRef<'a>,
+
+    /// Header of the parent of the block to verify.
+    ///
+    /// [`verify_header`] assumes that this block has been successfully verified before.
+    ///
+    /// The hash of this header must be the one referenced in [`VerifyConfig::header`].
+    pub parent_block_header: header::HeaderRef<'a>,
+
+    /// Number of slots per epoch in the Sassafras configuration.
+    pub slots_per_epoch: NonZeroU64,
+
+    /// Epoch the parent block belongs to. Must be `None` if and only if the parent block's number
+    /// is 0, as block #0 doesn't belong to any epoch.
+    ///
+    /// If `Some`, then the [`chain_information::SassafrasEpochInformationRef::start_slot_number`]
+    /// must be `Some`.
+    pub parent_block_epoch: Option<chain_information::SassafrasEpochInformationRef<'a>>,
+
+    /// Epoch that follows the epoch the parent block belongs to.
+    ///
+    /// The [`chain_information::SassafrasEpochInformationRef::start_slot_number`] must be `None`
+    /// if and only if the [`chain_information::SassafrasEpochInformationRef::epoch_index`] is
+    /// `0`.
+    pub parent_block_next_epoch: chain_information::SassafrasEpochInformationRef<'a>,
+}
+
+/// Information yielded back after successfully verifying a block.
+#[derive(Debug)]
+pub struct VerifySuccess {
+    /// Slot number the block belongs to.
+    ///
+    /// > **Note**: This is a simple reminder. The value can also be found in the header of the
+    /// >           block.
+    pub slot_number: u64,
+
+    /// `true` if the slot has been claimed using a ticket. `false` if it has been claimed using
+    /// the fallback mechanism.
+    pub is_ticket_claim: bool,
+
+    /// If `Some`, the verified block contains an epoch transition describing the new "next epoch".
+    /// When verifying blocks that are children of this one, the value in this field must be
+    /// provided as [`VerifyConfig::parent_block_next_epoch`], and the value previously in
+    /// [`VerifyConfig::parent_block_next_epoch`] must instead be passed as
+    /// [`VerifyConfig::parent_block_epoch`].
+    pub epoch_transition_target: Option<chain_information::SassafrasEpochInformation>,
+}
+
+/// Failure to verify a block.
+#[derive(Debug, derive_more::Display)]
+pub enum VerifyError {
+    /// The seal (containing the signature of the authority) is missing from the header.
+    MissingSeal,
+    /// No pre-runtime digest in the block header.
+    MissingPreRuntimeDigest,
+    /// Parent block doesn't contain any Sassafras information.
+    ParentIsntSassafrasConsensus,
+    /// Slot number must be strictly increasing between a parent and its child.
+    SlotNumberNotIncreasing,
+    /// Block contains an epoch change digest log, but no epoch change is to be performed.
+    UnexpectedEpochChangeLog,
+    /// Block is the first block after a new epoch, but it is missing an epoch change digest log.
+    MissingEpochChangeLog,
+    /// Authority index stored within block is out of range.
+    InvalidAuthorityIndex,
+    /// Block is a fallback slot claim and its author is not the expected author.
+    BadFallbackSlotAuthor,
+    /// Block disables an authority whose index is out of range.
+    InvalidDisabledAuthorityIndex,
+}
+
+/// Verifies whether a block header provides a correct proof of the legitimacy of the authorship.
+///
+/// # Panic
+///
+/// Panics if `config.parent_block_header` is invalid.
+/// Panics if `config.parent_block_epoch` is `None` and `config.parent_header.number` is not 0.
+/// Panics if `config.header.number` is not `config.parent_block_header.number + 1`.
+///
+pub fn verify_header(config: VerifyConfig) -> Result<VerifySuccess, VerifyError> {
+    let pre_digest = config
+        .header
+        .digest
+        .sassafras_pre_runtime()
+        .ok_or(VerifyError::MissingPreRuntimeDigest)?;
+    let slot_number = pre_digest.slot_number;
+
+    // Make sure that the slot of the block is increasing compared to its parent's.
+    let parent_slot_number = if config.parent_block_header.number != 0 {
+        let parent_slot_number = match config.parent_block_header.digest.sassafras_pre_runtime() {
+            Some(pr) => pr.slot_number,
+            None => return Err(VerifyError::ParentIsntSassafrasConsensus),
+        };
+
+        if slot_number <= parent_slot_number {
+            return Err(VerifyError::SlotNumberNotIncreasing);
+        }
+
+        Some(parent_slot_number)
+    } else {
+        None
+    };
+
+    // Verify consistency of the configuration.
+    if let Some(curr) = &config.parent_block_epoch {
+        assert!(curr.start_slot_number.is_some());
+        assert!(curr.start_slot_number <= parent_slot_number);
+    } else {
+        assert_eq!(config.parent_block_next_epoch.epoch_index, 0);
+    }
+    assert_eq!(
+        config.parent_block_next_epoch.epoch_index == 0,
+        config.parent_block_next_epoch.start_slot_number.is_none()
+    );
+
+    // Verify the epoch transition of the block.
+    // `block_epoch_info` contains the epoch the block belongs to.
+    let block_epoch_info = match (
+        &config.parent_block_epoch,
+        config.header.digest.sassafras_epoch_information().is_some(),
+    ) {
+        (Some(parent_epoch), false) => parent_epoch,
+        (None, false) => {
+            assert_eq!(config.parent_block_header.number, 0);
+            return Err(VerifyError::MissingEpochChangeLog);
+        }
+        (Some(_), true)
+            if config
+                .parent_block_next_epoch
+                .start_slot_number
+                .is_none_or(|n| n <= slot_number) =>
+        {
+            &config.parent_block_next_epoch
+        }
+        (Some(_), true) => {
+            return Err(VerifyError::UnexpectedEpochChangeLog);
+        }
+        (None, true) => {
+            assert_eq!(config.header.number, 1);
+            &config.parent_block_next_epoch
+        }
+    };
+
+    // Check if the current slot number indicates that entire epochs have been skipped.
+    let skipped_epochs = block_epoch_info
+        .start_slot_number
+        .map_or(0, |start_slot_number| {
+            (slot_number - start_slot_number) / config.slots_per_epoch
+        });
+
+    // Calculate the epoch index of the epoch of the block.
+    let block_epoch_index = block_epoch_info.epoch_index + skipped_epochs;
+
+    // The seal can't be verified, but its presence is mandatory.
+    if config.header.digest.sassafras_seal().is_none() {
+        return Err(VerifyError::MissingSeal);
+    }
+
+    // Make sure that the author exists.
+    let num_authorities = block_epoch_info.authorities.len();
+    if usize::try_from(pre_digest.authority_index).map_or(true, |idx| idx >= num_authorities) {
+        return Err(VerifyError::InvalidAuthorityIndex);
+    }
+
+    // The list of disabled authorities is stored in the runtime and can't be known from the
+    // headers alone. Only the existence of the disabled authority can be checked here.
+    for log in config.header.digest.logs() {
+        if let header::DigestItemRef::SassafrasConsensus(
+            header::SassafrasConsensusLogRef::OnDisabled(index),
+        ) = log
+        {
+            if usize::try_from(index).map_or(true, |idx| idx >= num_authorities) {
+                return Err(VerifyError::InvalidDisabledAuthorityIndex);
+            }
+        }
+    }
+
+    // Slots that are claimed without a ticket are assigned in a round-robin fashion.
+    let is_ticket_claim = pre_digest.ticket_claim.is_some();
+    if !is_ticket_claim {
+        // `num_authorities` can't be 0, as the authority index has been checked above.
+        let expected_authority_index =
+            slot_number % u64::try_from(num_authorities).unwrap_or(u64::MAX);
+        if expected_authority_index != u64::from(pre_digest.authority_index) {
+            return Err(VerifyError::BadFallbackSlotAuthor);
+        }
+    }
+
+    // TODO: verify the seal, the VRF signature, and the ticket claim; this requires Bandersnatch
+    //       and ring-VRF support
+
+    // If the block contains an epoch transition, build the information about the new epoch.
+    let epoch_transition_target = config
+        .header
+        .digest
+        .sassafras_epoch_information()
+        .map(|info| {
+            let start_slot_number = Some(
+                block_epoch_info
+                    .start_slot_number
+                    .unwrap_or(slot_number)
+                    .checked_add(config.slots_per_epoch.get())
+                    .unwrap()
+                    // If some epochs have been skipped, we need to adjust the starting slot of
+                    // the next epoch.
+                    .checked_add(
+                        skipped_epochs
+                            .checked_mul(config.slots_per_epoch.get())
+                            .unwrap(),
+                    )
+                    .unwrap(),
+            );
+            chain_information::SassafrasEpochInformation {
+                epoch_index: block_epoch_index.checked_add(1).unwrap(),
+                start_slot_number,
+                authorities: info.authorities.copied().collect(),
+                randomness: *info.randomness,
+            }
+        });
+
+    // Success! 🚀
+    Ok(VerifySuccess {
+        slot_number,
+        is_ticket_claim,
+        epoch_transition_target,
+    })
+}
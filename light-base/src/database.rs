@@ -62,6 +62,13 @@ pub struct DatabaseContent {
     /// Does **not** necessarily match the finalized block found in
     /// [`DatabaseContent::chain_information`].
     pub runtime_code_hint: Option<DatabaseContentRuntimeCodeHint>,
+
+    /// List of storage entries that have been verified in the past, ordered from the most
+    /// recently used to the least recently used. Empty if the database doesn't contain any.
+    ///
+    /// Does **not** necessarily match the finalized block found in
+    /// [`DatabaseContent::chain_information`].
+    pub storage_cache_hint: Vec<DatabaseContentStorageCacheEntry>,
 }
 
 /// See [`DatabaseContent::runtime_code_hint`].
@@ -77,6 +84,20 @@ pub struct DatabaseContentRuntimeCodeHint {
     pub closest_ancestor_excluding: Vec<Nibble>,
 }
 
+/// See [`DatabaseContent::storage_cache_hint`].
+#[derive(Debug, Clone)]
+pub struct DatabaseContentStorageCacheEntry {
+    /// Storage key of the entry.
+    pub key: Vec<u8>,
+    /// Storage value associated with [`DatabaseContentStorageCacheEntry::key`].
+    pub storage_value: Vec<u8>,
+    /// Merkle value of the trie node of [`DatabaseContentStorageCacheEntry::key`] in the
+    /// storage main trie.
+    pub merkle_value: Vec<u8>,
+    /// Closest ancestor of [`DatabaseContentStorageCacheEntry::key`] except for the key itself.
+    pub closest_ancestor_excluding: Vec<Nibble>,
+}
+
 /// Serializes the finalized state of the chain, using the given services.
 ///
 /// The returned string is guaranteed to not exceed `max_size` bytes. A truncated or invalid
@@ -117,11 +138,22 @@ pub async fn encode_database<TPlat: platform::PlatformRef>(
         code_storage_value: code_storage_value.map(|data| {
             base64::Engine::encode(&base64::engine::general_purpose::STANDARD_NO_PAD, data)
         }),
-        code_closest_ancestor_excluding: code_closest_ancestor_excluding.map(|key| {
-            key.iter()
-                .map(|nibble| format!("{:x}", nibble))
-                .collect::<String>()
-        }),
+        code_closest_ancestor_excluding: code_closest_ancestor_excluding
+            .map(|key| encode_nibbles(&key)),
+        storage_cache: sync_service
+            .storage_cache_entries()
+            .await
+            .into_iter()
+            .map(|entry| SerdeStorageCacheEntry {
+                key: hex::encode(entry.key),
+                value: base64::Engine::encode(
+                    &base64::engine::general_purpose::STANDARD_NO_PAD,
+                    entry.storage_value,
+                ),
+                merkle_value: hex::encode(entry.merkle_value),
+                closest_ancestor: encode_nibbles(&entry.closest_ancestor_excluding),
+            })
+            .collect(),
     };
 
     // Cap the database length to the maximum size.
@@ -132,6 +164,14 @@ pub async fn encode_database<TPlat: platform::PlatformRef>(
             return serialized;
         }
 
+        // Remove the least recently used half of the storage cache, as it is the least
+        // important item.
+        if !database_draft.storage_cache.is_empty() {
+            let new_len = database_draft.storage_cache.len() / 2;
+            database_draft.storage_cache.truncate(new_len);
+            continue;
+        }
+
         // Scrap the code, as it is the biggest item.
         if database_draft.code_merkle_value.is_some() || database_draft.code_storage_value.is_some()
         {
@@ -217,25 +257,57 @@ pub fn decode_database(encoded: &str, block_number_bytes: usize) -> Result<Datab
             code: base64::Engine::decode(&base64::engine::general_purpose::STANDARD_NO_PAD, sv)
                 .map_err(|_| ())?,
             code_merkle_value: hex::decode(mv).map_err(|_| ())?,
-            closest_ancestor_excluding: an
-                .as_bytes()
-                .iter()
-                .map(|char| Nibble::from_ascii_hex_digit(*char).ok_or(()))
-                .collect::<Result<Vec<Nibble>, ()>>()?,
+            closest_ancestor_excluding: decode_nibbles(&an)?,
         }),
         // A combination of `Some` and `None` is technically invalid, but we simply ignore this
         // situation.
         _ => None,
     };
 
+    // Similarly to nodes, storage cache entries that fail to decode are simply ignored.
+    let storage_cache_hint = decoded
+        .storage_cache
+        .iter()
+        .filter_map(|entry| {
+            Some(DatabaseContentStorageCacheEntry {
+                key: hex::decode(&entry.key).ok()?,
+                storage_value: base64::Engine::decode(
+                    &base64::engine::general_purpose::STANDARD_NO_PAD,
+                    &entry.value,
+                )
+                .ok()?,
+                merkle_value: hex::decode(&entry.merkle_value).ok()?,
+                closest_ancestor_excluding: decode_nibbles(&entry.closest_ancestor).ok()?,
+            })
+        })
+        .collect::<Vec<_>>();
+
     Ok(DatabaseContent {
         genesis_block_hash,
         chain_information,
         known_nodes,
         runtime_code_hint,
+        storage_cache_hint,
     })
 }
 
+/// Encodes a list of nibbles as a string of hexadecimal digits, one per nibble.
+fn encode_nibbles(nibbles: &[Nibble]) -> String {
+    nibbles
+        .iter()
+        .map(|nibble| format!("{:x}", nibble))
+        .collect::<String>()
+}
+
+/// Opposite of [`encode_nibbles`].
+fn decode_nibbles(encoded: &str) -> Result<Vec<Nibble>, ()> {
+    encoded
+        .as_bytes()
+        .iter()
+        .map(|char| Nibble::from_ascii_hex_digit(*char).ok_or(()))
+        .collect::<Result<Vec<Nibble>, ()>>()
+}
+
 #[derive(serde::Serialize, serde::Deserialize)]
 struct SerdeDatabase {
     /// Hexadecimal-encoded hash of the genesis block header. Has no `0x` prefix.
@@ -262,4 +334,25 @@ struct SerdeDatabase {
         skip_serializing_if = "Option::is_none"
     )]
     code_closest_ancestor_excluding: Option<String>,
+    #[serde(
+        rename = "storageCache",
+        default = "Default::default",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    storage_cache: Vec<SerdeStorageCacheEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct SerdeStorageCacheEntry {
+    /// Hexadecimal-encoded storage key. Has no `0x` prefix.
+    key: String,
+    /// Base64-encoded storage value.
+    value: String,
+    /// Hexadecimal-encoded Merkle value. Has no `0x` prefix.
+    #[serde(rename = "merkleValue")]
+    merkle_value: String,
+    /// Closest ancestor of the key, excluding the key itself, with one hexadecimal digit per
+    /// nibble.
+    #[serde(rename = "closestAncestor")]
+    closest_ancestor: String,
 }
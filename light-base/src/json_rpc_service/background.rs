@@ -44,6 +44,7 @@ use smoldot::{
     libp2p::{multiaddr, PeerId},
 };
 
+mod beefy;
 mod chain_head;
 mod getters;
 mod legacy_state_sub;
@@ -274,6 +275,8 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::author_submitExtrinsic { .. }
             | methods::MethodCall::author_unwatchExtrinsic { .. }
             | methods::MethodCall::babe_epochAuthorship { .. }
+            | methods::MethodCall::beefy_subscribeJustifications { .. }
+            | methods::MethodCall::beefy_unsubscribeJustifications { .. }
             | methods::MethodCall::chain_getBlock { .. }
             | methods::MethodCall::chain_getBlockHash { .. }
             | methods::MethodCall::chain_getFinalizedHead { .. }
@@ -306,6 +309,7 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::state_queryStorageAt { .. }
             | methods::MethodCall::state_subscribeRuntimeVersion { .. }
             | methods::MethodCall::state_subscribeStorage { .. }
+            | methods::MethodCall::state_traceBlock { .. }
             | methods::MethodCall::state_unsubscribeRuntimeVersion { .. }
             | methods::MethodCall::state_unsubscribeStorage { .. }
             | methods::MethodCall::system_accountNextIndex { .. }
@@ -359,7 +363,9 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::transaction_unstable_unwatch { .. }
             | methods::MethodCall::network_unstable_subscribeEvents { .. }
             | methods::MethodCall::network_unstable_unsubscribeEvents { .. }
-            | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. } => {}
+            | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. }
+            | methods::MethodCall::engine_createBlock { .. }
+            | methods::MethodCall::engine_finalizeBlock { .. } => {}
         }
 
         // Each call is handled in a separate method.
@@ -499,11 +505,14 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::state_getStorageHash { .. }
             | methods::MethodCall::state_getStorageSize { .. }
             | methods::MethodCall::state_queryStorage { .. }
+            | methods::MethodCall::state_traceBlock { .. }
             | methods::MethodCall::system_addReservedPeer { .. }
             | methods::MethodCall::system_dryRun { .. }
             | methods::MethodCall::system_localPeerId { .. }
             | methods::MethodCall::system_networkState { .. }
-            | methods::MethodCall::system_removeReservedPeer { .. }) => {
+            | methods::MethodCall::system_removeReservedPeer { .. }
+            | methods::MethodCall::engine_createBlock { .. }
+            | methods::MethodCall::engine_finalizeBlock { .. }) => {
                 // TODO: implement the ones that make sense to implement ^
                 log::error!(target: &self.log_target, "JSON-RPC call not supported yet: {:?}", _method);
                 request.fail(json_rpc::parse::ErrorResponse::ServerError(
@@ -542,6 +551,8 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::author_submitExtrinsic { .. }
             | methods::MethodCall::author_unwatchExtrinsic { .. }
             | methods::MethodCall::babe_epochAuthorship { .. }
+            | methods::MethodCall::beefy_subscribeJustifications { .. }
+            | methods::MethodCall::beefy_unsubscribeJustifications { .. }
             | methods::MethodCall::chain_getBlock { .. }
             | methods::MethodCall::chain_getBlockHash { .. }
             | methods::MethodCall::chain_getFinalizedHead { .. }
@@ -574,6 +585,7 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::state_queryStorageAt { .. }
             | methods::MethodCall::state_subscribeRuntimeVersion { .. }
             | methods::MethodCall::state_subscribeStorage { .. }
+            | methods::MethodCall::state_traceBlock { .. }
             | methods::MethodCall::state_unsubscribeRuntimeVersion { .. }
             | methods::MethodCall::state_unsubscribeStorage { .. }
             | methods::MethodCall::system_accountNextIndex { .. }
@@ -627,7 +639,9 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             | methods::MethodCall::transaction_unstable_unwatch { .. }
             | methods::MethodCall::network_unstable_subscribeEvents { .. }
             | methods::MethodCall::network_unstable_unsubscribeEvents { .. }
-            | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. } => {}
+            | methods::MethodCall::chainHead_unstable_finalizedDatabase { .. }
+            | methods::MethodCall::engine_createBlock { .. }
+            | methods::MethodCall::engine_finalizeBlock { .. } => {}
         }
 
         // Each call is handled in a separate method.
@@ -635,6 +649,9 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             methods::MethodCall::author_submitAndWatchExtrinsic { .. } => {
                 self.submit_and_watch_transaction(request).await
             }
+            methods::MethodCall::beefy_subscribeJustifications {} => {
+                self.beefy_subscribe_justifications(request).await
+            }
             methods::MethodCall::chain_subscribeAllHeads {}
             | methods::MethodCall::chain_subscribeFinalizedHeads {}
             | methods::MethodCall::chain_subscribeNewHeads {}
@@ -1003,7 +1020,7 @@ impl<TPlat: PlatformRef> Background<TPlat> {
         // then performing the actual call. The first step is the longest and most difficult.
         let precall = self.runtime_access(block_hash).await?;
 
-        let (runtime_call_lock, virtual_machine) = precall
+        let (mut runtime_call_lock, virtual_machine) = precall
             .start(
                 function_to_call,
                 call_parameters.clone(),
@@ -1048,6 +1065,7 @@ impl<TPlat: PlatformRef> Background<TPlat> {
             storage_main_trie_changes: Default::default(),
             max_log_level: 0,
             calculate_trie_changes: false,
+            trace: false,
         }) {
             Ok(vm) => vm,
             Err((err, prototype)) => {
@@ -1070,10 +1088,12 @@ impl<TPlat: PlatformRef> Background<TPlat> {
                 runtime_host::RuntimeHostVm::StorageGet(get) => {
                     let storage_value = {
                         let child_trie = get.child_trie();
-                        runtime_call_lock.storage_entry(
-                            child_trie.as_ref().map(|c| c.as_ref()),
-                            get.key().as_ref(),
-                        )
+                        runtime_call_lock
+                            .storage_entry(
+                                child_trie.as_ref().map(|c| c.as_ref()),
+                                get.key().as_ref(),
+                            )
+                            .await
                     };
                     let storage_value = match storage_value {
                         Ok(v) => v,
@@ -1090,10 +1110,12 @@ impl<TPlat: PlatformRef> Background<TPlat> {
                 runtime_host::RuntimeHostVm::ClosestDescendantMerkleValue(mv) => {
                     let merkle_value = {
                         let child_trie = mv.child_trie();
-                        runtime_call_lock.closest_descendant_merkle_value(
-                            child_trie.as_ref().map(|c| c.as_ref()),
-                            &mv.key().collect::<Vec<_>>(),
-                        )
+                        runtime_call_lock
+                            .closest_descendant_merkle_value(
+                                child_trie.as_ref().map(|c| c.as_ref()),
+                                &mv.key().collect::<Vec<_>>(),
+                            )
+                            .await
                     };
                     let merkle_value = match merkle_value {
                         Ok(v) => v,
@@ -1110,13 +1132,15 @@ impl<TPlat: PlatformRef> Background<TPlat> {
                 runtime_host::RuntimeHostVm::NextKey(nk) => {
                     let next_key = {
                         let child_trie = nk.child_trie();
-                        runtime_call_lock.next_key(
-                            child_trie.as_ref().map(|c| c.as_ref()),
-                            &nk.key().collect::<Vec<_>>(),
-                            nk.or_equal(),
-                            &nk.prefix().collect::<Vec<_>>(),
-                            nk.branch_nodes(),
-                        )
+                        runtime_call_lock
+                            .next_key(
+                                child_trie.as_ref().map(|c| c.as_ref()),
+                                &nk.key().collect::<Vec<_>>(),
+                                nk.or_equal(),
+                                &nk.prefix().collect::<Vec<_>>(),
+                                nk.branch_nodes(),
+                            )
+                            .await
                     };
                     let next_key = match next_key {
                         Ok(v) => v,
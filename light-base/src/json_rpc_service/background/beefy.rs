@@ -0,0 +1,177 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! All JSON-RPC method handlers that relate to BEEFY.
+
+use super::{Background, PlatformRef};
+
+use alloc::{borrow::ToOwned as _, format, sync::Arc};
+use core::{num::NonZeroU32, pin, time::Duration};
+use futures_lite::future;
+use futures_util::StreamExt as _;
+use smoldot::{
+    finality::beefy,
+    header,
+    json_rpc::{methods, service},
+};
+
+impl<TPlat: PlatformRef> Background<TPlat> {
+    /// Handles a call to [`methods::MethodCall::beefy_subscribeJustifications`].
+    pub(super) async fn beefy_subscribe_justifications(
+        self: &Arc<Self>,
+        request: service::SubscriptionStartProcess,
+    ) {
+        let methods::MethodCall::beefy_subscribeJustifications {} = request.request() else {
+            unreachable!()
+        };
+
+        let justifications = self.sync_service.subscribe_beefy_justifications(16).await;
+
+        self.platform
+            .spawn_task(format!("{}-beefy-justifications", self.log_target).into(), {
+                let me = self.clone();
+                async move {
+                    let mut justifications = pin::pin!(justifications);
+
+                    let mut subscription = request.accept();
+                    let subscription_id = subscription.subscription_id().to_owned();
+
+                    // Validator set used to verify the latest justification. Kept in order to
+                    // avoid performing a runtime call for every justification.
+                    let mut validator_set: Option<header::BeefyValidatorSet> = None;
+
+                    loop {
+                        let justification = match future::or(
+                            async { Some(justifications.next().await) },
+                            async {
+                                subscription.wait_until_stale().await;
+                                None
+                            },
+                        )
+                        .await
+                        {
+                            Some(Some(justification)) => justification,
+                            Some(None) => {
+                                // Channel from the sync service has been closed. There is
+                                // nothing more that can be done except hope that the client
+                                // understands that no new notification is expected and
+                                // unsubscribes.
+                                subscription.wait_until_stale().await;
+                                break;
+                            }
+                            None => break,
+                        };
+
+                        let block_number_bytes = me.sync_service.block_number_bytes();
+                        let Ok(signed_commitment) = beefy::commitment::decode_signed_commitment(
+                            &justification.scale_encoded_justification,
+                            block_number_bytes,
+                        ) else {
+                            log::debug!(
+                                target: &me.log_target,
+                                "BeefyJustification(block={}) => DecodeError",
+                                justification.block_number
+                            );
+                            continue;
+                        };
+
+                        if signed_commitment.commitment.block_number != justification.block_number
+                        {
+                            log::debug!(
+                                target: &me.log_target,
+                                "BeefyJustification(block={}) => BlockNumberMismatch",
+                                justification.block_number
+                            );
+                            continue;
+                        }
+
+                        // Fetch the validator set from the runtime if the one that has signed
+                        // the commitment isn't known.
+                        if validator_set.as_ref().map(|set| set.id)
+                            != Some(signed_commitment.commitment.validator_set_id)
+                        {
+                            let result = me
+                                .runtime_call_no_api_check(
+                                    &justification.block_hash,
+                                    beefy::VALIDATOR_SET_FUNCTION_NAME,
+                                    beefy::validator_set_parameters(),
+                                    3,
+                                    Duration::from_secs(10),
+                                    NonZeroU32::new(3).unwrap(),
+                                )
+                                .await;
+                            match result.as_ref().map(|output| {
+                                beefy::decode_validator_set_return_value(output)
+                            }) {
+                                Ok(Ok(Some(set))) => validator_set = Some(set.into()),
+                                Ok(Ok(None)) => {
+                                    log::debug!(
+                                        target: &me.log_target,
+                                        "BeefyJustification(block={}) => BeefyDisabled",
+                                        justification.block_number
+                                    );
+                                    continue;
+                                }
+                                Ok(Err(error)) => {
+                                    log::debug!(
+                                        target: &me.log_target,
+                                        "BeefyJustification(block={}) => ValidatorSetDecodeError({})",
+                                        justification.block_number, error
+                                    );
+                                    continue;
+                                }
+                                Err(error) => {
+                                    log::debug!(
+                                        target: &me.log_target,
+                                        "BeefyJustification(block={}) => ValidatorSetCallError({})",
+                                        justification.block_number, error
+                                    );
+                                    continue;
+                                }
+                            }
+                        }
+
+                        let validator_set = validator_set.as_ref().unwrap();
+                        if let Err(error) =
+                            beefy::commitment::verify(beefy::commitment::Config {
+                                signed_commitment,
+                                block_number_bytes,
+                                validator_set_id: validator_set.id,
+                                validators: validator_set.validators.iter(),
+                            })
+                        {
+                            log::debug!(
+                                target: &me.log_target,
+                                "BeefyJustification(block={}) => VerifyError({})",
+                                justification.block_number, error
+                            );
+                            continue;
+                        }
+
+                        subscription
+                            .send_notification(methods::ServerToClient::beefy_justifications {
+                                subscription: (&subscription_id).into(),
+                                result: methods::HexString(
+                                    justification.scale_encoded_justification,
+                                ),
+                            })
+                            .await;
+                    }
+                }
+            });
+    }
+}
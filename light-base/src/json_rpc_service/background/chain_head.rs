@@ -1355,7 +1355,7 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                 };
 
                 match pre_runtime_call {
-                    Ok((runtime_call_lock, virtual_machine)) => {
+                    Ok((mut runtime_call_lock, virtual_machine)) => {
                         match runtime_host::run(runtime_host::Config {
                             virtual_machine,
                             function_to_call: &function_to_call,
@@ -1363,6 +1363,7 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                             storage_main_trie_changes: Default::default(),
                             max_log_level: 0,
                             calculate_trie_changes: false,
+                            trace: false,
                         }) {
                             Err((error, prototype)) => {
                                 runtime_call_lock.unlock(prototype);
@@ -1406,7 +1407,7 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                                             // TODO: what if the remote lied to us?
                                             let storage_value = {
                                                 let child_trie = get.child_trie();
-                                                runtime_call_lock.storage_entry(child_trie.as_ref().map(|c| c.as_ref()), get.key().as_ref())
+                                                runtime_call_lock.storage_entry(child_trie.as_ref().map(|c| c.as_ref()), get.key().as_ref()).await
                                             };
                                             let storage_value = match storage_value {
                                                 Ok(v) => v,
@@ -1436,7 +1437,7 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                                             let merkle_value = {
                                                 let child_trie = mv.child_trie();
                                                 runtime_call_lock
-                                                    .closest_descendant_merkle_value(child_trie.as_ref().map(|c| c.as_ref()), &mv.key().collect::<Vec<_>>())
+                                                    .closest_descendant_merkle_value(child_trie.as_ref().map(|c| c.as_ref()), &mv.key().collect::<Vec<_>>()).await
                                             };
                                             let merkle_value = match merkle_value {
                                                 Ok(v) => v,
@@ -1469,7 +1470,7 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                                                     nk.or_equal(),
                                                     &nk.prefix().collect::<Vec<_>>(),
                                                     nk.branch_nodes(),
-                                                )
+                                                ).await
                                             };
                                             let next_key = match next_key {
                                                 Ok(v) => v,
@@ -1529,26 +1530,6 @@ impl<TPlat: PlatformRef> ChainHeadFollowTask<TPlat> {
                             }
                         }).await;
                     }
-                    Err(runtime_service::RuntimeCallError::StorageRetrieval(error)) => {
-                        let _ = to_main_task.send(OperationEvent {
-                            operation_id: operation_id.clone(),
-                            is_done: true,
-                            notification: methods::FollowEvent::OperationError {
-                                operation_id: operation_id.clone().into(),
-                                error: error.to_string().into(),
-                            }
-                        }).await;
-                    }
-                    Err(runtime_service::RuntimeCallError::MissingProofEntry(_error)) => {
-                        let _ = to_main_task.send(OperationEvent {
-                            operation_id: operation_id.clone(),
-                            is_done: true,
-                            notification: methods::FollowEvent::OperationError {
-                                operation_id: operation_id.clone().into(),
-                                error: "incomplete call proof".into(),
-                            }
-                        }).await;
-                    }
                     Err(runtime_service::RuntimeCallError::InvalidChildTrieRoot) => {
                         let _ = to_main_task.send(OperationEvent {
                             operation_id: operation_id.clone(),
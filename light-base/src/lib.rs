@@ -445,9 +445,15 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
         // Load the information about the chain. If a light sync state (also known as a checkpoint)
         // is present in the chain spec, it is possible to start syncing at the finalized block
         // it describes.
-        // At the same time, we deconstruct the database into `known_nodes`
-        // and `runtime_code_hint`.
-        let (chain_information, used_database_chain_information, known_nodes, runtime_code_hint) = {
+        // At the same time, we deconstruct the database into `known_nodes`,
+        // `runtime_code_hint`, and `storage_cache_hint`.
+        let (
+            chain_information,
+            used_database_chain_information,
+            known_nodes,
+            runtime_code_hint,
+            storage_cache_hint,
+        ) = {
             let checkpoint = chain_spec
                 .light_sync_state()
                 .map(|s| s.to_chain_information());
@@ -462,12 +468,19 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                         chain_information: Some(db_ci),
                         known_nodes,
                         runtime_code_hint,
+                        storage_cache_hint,
                         ..
                     }),
                 ) if db_ci.as_ref().finalized_block_header.number
                     >= checkpoint.as_ref().finalized_block_header.number =>
                 {
-                    (Some(db_ci), true, known_nodes, runtime_code_hint)
+                    (
+                        Some(db_ci),
+                        true,
+                        known_nodes,
+                        runtime_code_hint,
+                        storage_cache_hint,
+                    )
                 }
 
                 // Otherwise, use the chain spec checkpoint.
@@ -477,10 +490,19 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                     Some(database::DatabaseContent {
                         known_nodes,
                         runtime_code_hint,
+                        storage_cache_hint,
                         ..
                     }),
-                ) => (Some(checkpoint), false, known_nodes, runtime_code_hint),
-                (_, Some(Ok(checkpoint)), None) => (Some(checkpoint), false, Vec::new(), None),
+                ) => (
+                    Some(checkpoint),
+                    false,
+                    known_nodes,
+                    runtime_code_hint,
+                    storage_cache_hint,
+                ),
+                (_, Some(Ok(checkpoint)), None) => {
+                    (Some(checkpoint), false, Vec::new(), None, Vec::new())
+                }
 
                 // If neither the genesis chain information nor the checkpoint chain information
                 // is available, we could in principle use the database, but for API reasons we
@@ -492,10 +514,17 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                     Some(database::DatabaseContent {
                         known_nodes,
                         runtime_code_hint,
+                        storage_cache_hint,
                         ..
                     }),
-                ) => (None, false, known_nodes, runtime_code_hint),
-                (None, None, None) => (None, false, Vec::new(), None),
+                ) => (
+                    None,
+                    false,
+                    known_nodes,
+                    runtime_code_hint,
+                    storage_cache_hint,
+                ),
+                (None, None, None) => (None, false, Vec::new(), None, Vec::new()),
 
                 // Use the genesis block if no checkpoint is available.
                 (
@@ -507,9 +536,16 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                     Some(database::DatabaseContent {
                         known_nodes,
                         runtime_code_hint,
+                        storage_cache_hint,
                         ..
                     }),
-                ) => (Some(genesis_ci), false, known_nodes, runtime_code_hint),
+                ) => (
+                    Some(genesis_ci),
+                    false,
+                    known_nodes,
+                    runtime_code_hint,
+                    storage_cache_hint,
+                ),
                 (
                     Some(genesis_ci),
                     None
@@ -517,7 +553,7 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                         chain_spec::CheckpointToChainInformationError::GenesisBlockCheckpoint,
                     )),
                     None,
-                ) => (Some(genesis_ci), false, Vec::new(), None),
+                ) => (Some(genesis_ci), false, Vec::new(), None, Vec::new()),
 
                 // If the checkpoint format is invalid, we return an error no matter whether the
                 // genesis chain information could be used.
@@ -723,6 +759,7 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
                         log_name.clone(),
                         &self.platform,
                         runtime_code_hint,
+                        storage_cache_hint,
                         genesis_block_header,
                         usize::from(chain_spec.block_number_bytes()),
                         chain_spec.fork_id().map(|f| f.to_owned()),
@@ -965,7 +1002,24 @@ impl<TPlat: platform::PlatformRef, TChain> Client<TPlat, TChain> {
         let running_chain = chains_by_key.get_mut(&removed_chain.key).unwrap();
         if running_chain.num_references.get() == 1 {
             log::info!(target: "smoldot", "Shutting down chain {}", running_chain.log_name);
-            chains_by_key.remove(&removed_chain.key);
+            let running_chain = chains_by_key.remove(&removed_chain.key).unwrap();
+
+            // The services of this chain shut down once all the references to them are dropped.
+            // In order to not wait for this to happen, the chain is immediately removed from
+            // the network service, unless it is the relay chain of a parachain that is still
+            // running, in which case the parachain continues to use it.
+            if !chains_by_key.keys().any(|key| {
+                key.relay_chain
+                    .as_ref()
+                    .map_or(false, |(relay_chain, _)| **relay_chain == removed_chain.key)
+            }) {
+                let network_service = running_chain.services.network_service;
+                let chain_id = running_chain.services.network_service_chain_id;
+                self.platform
+                    .spawn_task("network-service-remove-chain".into(), async move {
+                        network_service.remove_chain(chain_id).await
+                    });
+            }
         } else {
             running_chain.num_references =
                 NonZeroU32::new(running_chain.num_references.get() - 1).unwrap();
@@ -1078,6 +1132,10 @@ enum StartServicesChainTy<'a, TPlat: platform::PlatformRef> {
     },
 }
 
+/// Maximum size, in bytes, of the storage entries that each chain keeps in memory in order to
+/// avoid downloading them again. See [`sync_service::Config::storage_cache_max_size`].
+const STORAGE_CACHE_MAX_SIZE: usize = 256 * 1024;
+
 /// Starts all the services of the client.
 ///
 /// Returns some of the services that have been started. If these service get shut down, all the
@@ -1086,6 +1144,7 @@ fn start_services<TPlat: platform::PlatformRef>(
     log_name: String,
     platform: &TPlat,
     runtime_code_hint: Option<database::DatabaseContentRuntimeCodeHint>,
+    storage_cache_hint: Vec<database::DatabaseContentStorageCacheEntry>,
     genesis_block_scale_encoded_header: Vec<u8>,
     block_number_bytes: usize,
     fork_id: Option<String>,
@@ -1156,6 +1215,16 @@ fn start_services<TPlat: platform::PlatformRef>(
 
     let network_service_chain_id = network_service_chain_ids.into_iter().next().unwrap();
 
+    let storage_cache_hint = storage_cache_hint
+        .into_iter()
+        .map(|entry| sync_service::StorageCacheEntry {
+            key: entry.key,
+            storage_value: entry.storage_value,
+            merkle_value: entry.merkle_value,
+            closest_ancestor_excluding: entry.closest_ancestor_excluding,
+        })
+        .collect::<Vec<_>>();
+
     let (sync_service, runtime_service) = match config {
         StartServicesChainTy::Parachain {
             relay_chain,
@@ -1174,6 +1243,8 @@ fn start_services<TPlat: platform::PlatformRef>(
                 block_number_bytes,
                 network_service: (network_service.clone(), network_service_chain_id),
                 network_events_receiver: network_event_receivers.pop().unwrap(),
+                storage_cache_hint,
+                storage_cache_max_size: STORAGE_CACHE_MAX_SIZE,
                 chain_type: sync_service::ConfigChainType::Parachain(
                     sync_service::ConfigParachain {
                         finalized_block_header,
@@ -1211,6 +1282,8 @@ fn start_services<TPlat: platform::PlatformRef>(
                 platform: platform.clone(),
                 network_service: (network_service.clone(), network_service_chain_id),
                 network_events_receiver: network_event_receivers.pop().unwrap(),
+                storage_cache_hint,
+                storage_cache_max_size: STORAGE_CACHE_MAX_SIZE,
                 chain_type: sync_service::ConfigChainType::RelayChain(
                     sync_service::ConfigRelayChain {
                         chain_information: chain_information.clone(),
@@ -46,7 +46,7 @@ use alloc::{
     sync::Arc,
     vec::{self, Vec},
 };
-use core::{cmp, mem, pin::Pin, task::Poll, time::Duration};
+use core::{cmp, iter, mem, pin::Pin, task::Poll, time::Duration};
 use futures_channel::oneshot;
 use futures_lite::FutureExt as _;
 use futures_util::{future, stream, StreamExt as _};
@@ -64,6 +64,7 @@ use smoldot::{
     network::{basic_peering_strategy, codec, service},
 };
 
+pub use basic_peering_strategy::ReputationChange;
 pub use service::{ChainId, EncodedMerkleProof, QueueNotificationError};
 
 mod tasks;
@@ -184,9 +185,13 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
                     genesis_hash: chain.genesis_block_hash,
                     role: codec::Role::Light,
                     allow_inbound_block_requests: false,
+                    allow_inbound_state_requests: false,
+                    allow_inbound_kademlia_requests: false,
+                    allow_collation_protocols: false,
                     user_data: Chain {
                         log_name: chain.log_name.clone(),
                         num_out_slots: chain.num_out_slots,
+                        all_peers_reputation_banned: false,
                     },
                 })
                 .unwrap();
@@ -229,6 +234,29 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             }),
         );
 
+        // Spawn a task that makes the reputations of the peers decay at a periodic interval.
+        config.platform.spawn_task(
+            "network-reputations-decay".into(),
+            Box::pin({
+                let platform = config.platform.clone();
+                let messages_tx = messages_tx.clone();
+                async move {
+                    loop {
+                        platform.sleep(Duration::from_secs(1)).await;
+
+                        if messages_tx
+                            .send(ToBackground::DecayReputations)
+                            .await
+                            .is_err()
+                        {
+                            break;
+                        }
+                    }
+                }
+                .or(on_service_killed.listen())
+            }),
+        );
+
         // Spawn main task that processes the network service.
         let task = Box::pin(
             background_task(BackgroundTask {
@@ -353,6 +381,22 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             }
         }
 
+        self.report_request_outcome(
+            chain_id,
+            target.clone(),
+            match &result {
+                Ok(_) => Some(ReputationChange::GoodResponse),
+                Err(BlocksRequestError::NoConnection) => None,
+                Err(BlocksRequestError::Request(service::BlocksRequestError::Request(err))) => {
+                    request_error_reputation_change(err)
+                }
+                Err(BlocksRequestError::Request(service::BlocksRequestError::Decode(_))) => {
+                    Some(ReputationChange::BadResponse)
+                }
+            },
+        )
+        .await;
+
         if !log::log_enabled!(log::Level::Debug) {
             match &result {
                 Ok(_) | Err(BlocksRequestError::NoConnection) => {}
@@ -420,6 +464,22 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             }
         }
 
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(ReputationChange::GoodResponse),
+                Err(WarpSyncRequestError::NoConnection) => None,
+                Err(WarpSyncRequestError::Request(
+                    service::GrandpaWarpSyncRequestError::Request(err),
+                )) => request_error_reputation_change(err),
+                Err(WarpSyncRequestError::Request(
+                    service::GrandpaWarpSyncRequestError::Decode(_),
+                )) => Some(ReputationChange::BadResponse),
+            },
+        )
+        .await;
+
         result
     }
 
@@ -506,6 +566,26 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             }
         }
 
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(ReputationChange::GoodResponse),
+                Err(StorageProofRequestError::NoConnection)
+                | Err(StorageProofRequestError::RequestTooLarge)
+                | Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::RemoteCouldntAnswer,
+                )) => None,
+                Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::Request(err),
+                )) => request_error_reputation_change(err),
+                Err(StorageProofRequestError::Request(
+                    service::StorageProofRequestError::Decode(_),
+                )) => Some(ReputationChange::BadResponse),
+            },
+        )
+        .await;
+
         result
     }
 
@@ -565,9 +645,58 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             }
         }
 
+        self.report_request_outcome(
+            chain_id,
+            target,
+            match &result {
+                Ok(_) => Some(ReputationChange::GoodResponse),
+                Err(CallProofRequestError::NoConnection)
+                | Err(CallProofRequestError::RequestTooLarge)
+                | Err(CallProofRequestError::Request(
+                    service::CallProofRequestError::RemoteCouldntAnswer,
+                )) => None,
+                Err(CallProofRequestError::Request(service::CallProofRequestError::Request(
+                    err,
+                ))) => request_error_reputation_change(err),
+                Err(CallProofRequestError::Request(service::CallProofRequestError::Decode(_))) => {
+                    Some(ReputationChange::BadResponse)
+                }
+            },
+        )
+        .await;
+
         result
     }
 
+    /// Adjusts the reputation of the given peer on the given chain.
+    ///
+    /// Must be used to report misbehaviours detected outside of the networking, such as invalid
+    /// justifications. Peers whose reputation is too low are banned. Request failures and
+    /// successes are already reported automatically.
+    pub async fn report_peer(&self, chain_id: ChainId, peer_id: PeerId, change: ReputationChange) {
+        self.messages_tx
+            .send(ToBackground::ReportPeer {
+                chain_id,
+                peer_id,
+                change,
+            })
+            .await
+            .unwrap();
+    }
+
+    /// Reports the outcome of a request to the background task, if the outcome has an effect on
+    /// the reputation of the peer.
+    async fn report_request_outcome(
+        &self,
+        chain_id: ChainId,
+        peer_id: PeerId,
+        change: Option<ReputationChange>,
+    ) {
+        if let Some(change) = change {
+            self.report_peer(chain_id, peer_id, change).await;
+        }
+    }
+
     /// Announces transaction to the peers we are connected to.
     ///
     /// Returns a list of peers that we have sent the transaction to. Can return an empty `Vec`
@@ -687,6 +816,20 @@ impl<TPlat: PlatformRef> NetworkService<TPlat> {
             .unwrap();
         rx.await.unwrap().into_iter()
     }
+
+    /// Removes the given chain from the network service.
+    ///
+    /// The gossip substreams of this chain are closed, the requests of this chain that are in
+    /// progress fail, and the peers that were discovered for this chain are forgotten.
+    ///
+    /// Afterwards, the requests concerning this chain fail as if no connection was established,
+    /// and the other messages concerning this chain are ignored.
+    pub async fn remove_chain(&self, chain_id: ChainId) {
+        self.messages_tx
+            .send(ToBackground::RemoveChain { chain_id })
+            .await
+            .unwrap();
+    }
 }
 
 impl<TPlat: PlatformRef> Drop for NetworkService<TPlat> {
@@ -855,7 +998,40 @@ enum ToBackground {
         chain_id: ChainId,
         result: oneshot::Sender<Vec<PeerId>>,
     },
+    ReportPeer {
+        chain_id: ChainId,
+        peer_id: PeerId,
+        change: ReputationChange,
+    },
+    RemoveChain {
+        chain_id: ChainId,
+    },
     StartDiscovery,
+    DecayReputations,
+}
+
+impl ToBackground {
+    /// Returns the chain this message concerns, if any.
+    fn chain_id(&self) -> Option<ChainId> {
+        match self {
+            ToBackground::StartBlocksRequest { chain_id, .. }
+            | ToBackground::StartWarpSyncRequest { chain_id, .. }
+            | ToBackground::StartStorageProofRequest { chain_id, .. }
+            | ToBackground::StartCallProofRequest { chain_id, .. }
+            | ToBackground::SetLocalBestBlock { chain_id, .. }
+            | ToBackground::SetLocalGrandpaState { chain_id, .. }
+            | ToBackground::AnnounceTransaction { chain_id, .. }
+            | ToBackground::SendBlockAnnounce { chain_id, .. }
+            | ToBackground::Discover { chain_id, .. }
+            | ToBackground::DiscoveredNodes { chain_id, .. }
+            | ToBackground::PeersList { chain_id, .. }
+            | ToBackground::ReportPeer { chain_id, .. }
+            | ToBackground::RemoveChain { chain_id } => Some(*chain_id),
+            ToBackground::ConnectionMessage { .. }
+            | ToBackground::StartDiscovery
+            | ToBackground::DecayReputations => None,
+        }
+    }
 }
 
 struct BackgroundTask<TPlat: PlatformRef> {
@@ -934,6 +1110,11 @@ struct Chain {
 
     /// See [`ConfigChain::num_out_slots`].
     num_out_slots: usize,
+
+    /// `true` if all the un-assigned peers of this chain were banned because of their reputation
+    /// the last time a slot assignment was attempted. Used in order to avoid printing the same
+    /// log message repeatedly.
+    all_peers_reputation_banned: bool,
 }
 
 async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
@@ -1001,6 +1182,7 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
                                 .pick_assignable_peer(&chain_id, &task.platform.now())
                             {
                                 basic_peering_strategy::AssignablePeer::Assignable(peer_id) => {
+                                    task.network[chain_id].all_peers_reputation_banned = false;
                                     break 'search WhatHappened::CanAssignSlot(
                                         peer_id.clone(),
                                         chain_id,
@@ -1013,7 +1195,23 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
                                         earlier_unban = Some(next_unban.clone());
                                     }
                                 }
-                                basic_peering_strategy::AssignablePeer::NoPeer => continue,
+                                basic_peering_strategy::AssignablePeer::AllPeersReputationBanned => {
+                                    // Printed only once, as this code is reached every time the
+                                    // background task wakes up.
+                                    if !task.network[chain_id].all_peers_reputation_banned {
+                                        task.network[chain_id].all_peers_reputation_banned = true;
+                                        log::debug!(
+                                            target: "network",
+                                            "Slots({}) => NoAssignablePeer(reason=reputation)",
+                                            &task.network[chain_id].log_name,
+                                        );
+                                    }
+                                    continue;
+                                }
+                                basic_peering_strategy::AssignablePeer::NoPeer => {
+                                    task.network[chain_id].all_peers_reputation_banned = false;
+                                    continue;
+                                }
                             }
                         }
 
@@ -1072,6 +1270,67 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
                     }));
                 }
             }
+            WhatHappened::Message(message)
+                if message.chain_id().map_or(false, |chain_id| {
+                    !task.network.chains().any(|c| c == chain_id)
+                }) =>
+            {
+                // The chain has been removed with `NetworkService::remove_chain`. The services
+                // of this chain might still be running for a little while.
+                match message {
+                    ToBackground::StartBlocksRequest { result, .. } => {
+                        let _ = result.send(Err(BlocksRequestError::NoConnection));
+                    }
+                    ToBackground::StartWarpSyncRequest { result, .. } => {
+                        let _ = result.send(Err(WarpSyncRequestError::NoConnection));
+                    }
+                    ToBackground::StartStorageProofRequest { result, .. } => {
+                        let _ = result.send(Err(StorageProofRequestError::NoConnection));
+                    }
+                    ToBackground::StartCallProofRequest { result, .. } => {
+                        let _ = result.send(Err(CallProofRequestError::NoConnection));
+                    }
+                    ToBackground::AnnounceTransaction { result, .. } => {
+                        let _ = result.send(Vec::new());
+                    }
+                    ToBackground::SendBlockAnnounce { result, .. } => {
+                        let _ = result.send(Err(QueueNotificationError::NoConnection));
+                    }
+                    ToBackground::DiscoveredNodes { result, .. } => {
+                        let _ = result.send(Vec::new());
+                    }
+                    ToBackground::PeersList { result, .. } => {
+                        let _ = result.send(Vec::new());
+                    }
+                    _ => {}
+                }
+            }
+            WhatHappened::Message(ToBackground::RemoveChain { chain_id }) => {
+                let removed = task.network.remove_chain(chain_id);
+                log::debug!(target: "network", "Chain({}) <= RemoveChain", removed.user_data.log_name);
+
+                // The light client never accepts incoming requests.
+                debug_assert!(removed.answered_requests_in.is_empty());
+
+                for substream_id in removed.cancelled_requests {
+                    if let Some(result) = task.blocks_requests.remove(&substream_id) {
+                        let _ = result.send(Err(BlocksRequestError::NoConnection));
+                    } else if let Some(result) =
+                        task.grandpa_warp_sync_requests.remove(&substream_id)
+                    {
+                        let _ = result.send(Err(WarpSyncRequestError::NoConnection));
+                    } else if let Some(result) = task.storage_proof_requests.remove(&substream_id) {
+                        let _ = result.send(Err(StorageProofRequestError::NoConnection));
+                    } else if let Some(result) = task.call_proof_requests.remove(&substream_id) {
+                        let _ = result.send(Err(CallProofRequestError::NoConnection));
+                    } else {
+                        let _was_in = task.kademlia_find_node_requests.remove(&substream_id);
+                        debug_assert!(_was_in.is_some());
+                    }
+                }
+
+                task.peering_strategy.remove_chain_peers(&chain_id);
+            }
             WhatHappened::Message(ToBackground::ConnectionMessage {
                 connection_id,
                 message,
@@ -1339,6 +1598,31 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
                         .collect(),
                 );
             }
+            WhatHappened::Message(ToBackground::ReportPeer {
+                chain_id,
+                peer_id,
+                change,
+            }) => match task.peering_strategy.report(&chain_id, &peer_id, change) {
+                basic_peering_strategy::ReportResult::Banned { had_slot } => {
+                    log::debug!(
+                        target: "network",
+                        "Slots({}) ∌ {} (reason=reputation, had_slot={:?})",
+                        &task.network[chain_id].log_name,
+                        peer_id,
+                        had_slot
+                    );
+                    task.network.gossip_remove_desired(
+                        chain_id,
+                        &peer_id,
+                        service::GossipKind::ConsensusTransactions,
+                    );
+                }
+                basic_peering_strategy::ReportResult::Adjusted
+                | basic_peering_strategy::ReportResult::UnknownPeerChain => {}
+            },
+            WhatHappened::Message(ToBackground::DecayReputations) => {
+                task.peering_strategy.decay_reputations();
+            }
             WhatHappened::Message(ToBackground::StartDiscovery) => {
                 for chain_id in task.network.chains().collect::<Vec<_>>() {
                     let random_peer_id = {
@@ -1790,10 +2074,31 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
                     "Connections({}) => IdentifyRequest",
                     peer_id,
                 );
-                task.network
-                    .respond_identify(substream_id, &task.identify_agent_version);
+                // The light client isn't reachable by other nodes, and thus doesn't report any
+                // listen address.
+                task.network.respond_identify(
+                    substream_id,
+                    &task.identify_agent_version,
+                    iter::empty(),
+                );
+            }
+            WhatHappened::NetworkEvent(
+                service::Event::BlocksRequestIn { .. }
+                | service::Event::StateRequestIn { .. }
+                | service::Event::KademliaFindNodeRequestIn { .. }
+                | service::Event::KademliaGetValueRequestIn { .. }
+                | service::Event::KademliaPutValueRequestIn { .. },
+            ) => unreachable!(),
+            WhatHappened::NetworkEvent(
+                service::Event::CollationFetchingRequestIn { .. }
+                | service::Event::CollationConnected { .. }
+                | service::Event::CollationOpenFailed { .. }
+                | service::Event::CollationClosed { .. },
+            ) => {
+                // Light clients never act as collators, and thus never enable the collation
+                // protocols.
+                unreachable!()
             }
-            WhatHappened::NetworkEvent(service::Event::BlocksRequestIn { .. }) => unreachable!(),
             WhatHappened::NetworkEvent(service::Event::RequestInCancel { .. }) => {
                 // All incoming requests are immediately answered.
                 unreachable!()
@@ -2070,3 +2375,15 @@ async fn background_task<TPlat: PlatformRef>(mut task: BackgroundTask<TPlat>) {
         }
     }
 }
+
+/// Returns the reputation change to apply to a peer that has answered a request with the given
+/// error, or `None` if the error isn't the fault of the peer.
+fn request_error_reputation_change(error: &service::RequestError) -> Option<ReputationChange> {
+    match error {
+        service::RequestError::Substream(connection::established::RequestError::Timeout) => {
+            Some(ReputationChange::Timeout)
+        }
+        err if err.is_protocol_error() => Some(ReputationChange::BadResponse),
+        _ => None,
+    }
+}
@@ -28,11 +28,16 @@ pub use smoldot::libp2p::with_buffers;
 // TODO: this module should probably not be public?
 pub mod address_parse;
 pub mod default;
+pub mod memory;
 
 #[cfg(feature = "std")]
 #[cfg_attr(docsrs, doc(cfg(feature = "std")))]
 pub use default::DefaultPlatform;
 
+#[cfg(feature = "std")]
+#[cfg_attr(docsrs, doc(cfg(feature = "std")))]
+pub use memory::MemoryPlatform;
+
 /// Access to a platform's capabilities.
 ///
 /// Implementations of this trait are expected to be cheaply-clonable "handles". All clones of the
@@ -306,6 +311,12 @@ pub enum ConnectionType {
     WebRtcIpv4,
     /// Libp2p-specific WebRTC flavour.
     WebRtcIpv6,
+    /// In-process connection, corresponding to a `/memory/<port>` multiaddress.
+    ///
+    /// > **Note**: This variant has been added in version 0.12.0. Implementations of
+    /// >           [`PlatformRef`] that don't support in-process connections should return
+    /// >           `false` from [`PlatformRef::supports_connection_type`].
+    Memory,
 }
 
 impl<'a> From<&'a Address<'a>> for ConnectionType {
@@ -334,6 +345,7 @@ impl<'a> From<&'a Address<'a>> for ConnectionType {
                 secure: *secure,
                 remote_is_localhost: hostname.eq_ignore_ascii_case("localhost"),
             },
+            Address::Memory { .. } => ConnectionType::Memory,
         }
     }
 }
@@ -402,6 +414,16 @@ pub enum Address<'a> {
         /// `true` for WebSocket secure connections.
         secure: bool,
     },
+
+    /// In-process connection, corresponding to a `/memory/<port>` multiaddress.
+    ///
+    /// > **Note**: This variant has been added in version 0.12.0. It is only ever passed to
+    /// >           [`PlatformRef::connect_stream`] if [`PlatformRef::supports_connection_type`]
+    /// >           returns `true` for [`ConnectionType::Memory`].
+    Memory {
+        /// Port to connect to.
+        port: u64,
+    },
 }
 
 /// Address passed to [`PlatformRef::connect_multistream`].
@@ -30,7 +30,7 @@ pub fn multiaddr_to_address(multiaddr: &Multiaddr) -> Result<AddressOrMultiStrea
     let mut iter = multiaddr.iter().fuse();
 
     let proto1 = iter.next().ok_or(Error::UnknownCombination)?;
-    let proto2 = iter.next().ok_or(Error::UnknownCombination)?;
+    let proto2 = iter.next();
     let proto3 = iter.next();
     let proto4 = iter.next();
 
@@ -38,6 +38,14 @@ pub fn multiaddr_to_address(multiaddr: &Multiaddr) -> Result<AddressOrMultiStrea
         return Err(Error::UnknownCombination);
     }
 
+    if let (ProtocolRef::Memory(port), None) = (&proto1, &proto2) {
+        return Ok(AddressOrMultiStreamAddress::Address(Address::Memory {
+            port: *port,
+        }));
+    }
+
+    let proto2 = proto2.ok_or(Error::UnknownCombination)?;
+
     Ok(match (proto1, proto2, proto3, proto4) {
         (ProtocolRef::Ip4(ip), ProtocolRef::Tcp(port), None, None) => {
             AddressOrMultiStreamAddress::Address(Address::TcpIp {
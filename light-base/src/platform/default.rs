@@ -70,25 +70,50 @@ impl DefaultPlatform {
     /// Panics if it wasn't possible to spawn background threads.
     ///
     pub fn new(client_name: String, client_version: String) -> Arc<Self> {
+        let mut n = 0;
+        Self::with_thread_spawner(client_name, client_version, move |run| {
+            let spawn_result = thread::Builder::new()
+                .name(format!("smoldot-light-{}", n))
+                .spawn(run);
+            n += 1;
+
+            if let Err(err) = spawn_result {
+                panic!("Failed to spawn execution thread: {err}");
+            }
+        })
+    }
+
+    /// Creates a new [`DefaultPlatform`], leaving the spawning of the threads that execute the
+    /// background tasks up to the caller.
+    ///
+    /// `spawn_thread` is called multiple times before this function returns. Each call passes a
+    /// function that must be run on a new thread. This function only returns after the
+    /// [`DefaultPlatform`] has been destroyed.
+    ///
+    /// This is useful in environments where threads need to be created in a specific way, for
+    /// example in order to be attached to a virtual machine.
+    ///
+    /// See [`DefaultPlatform::new`] for the meaning of the other parameters.
+    pub fn with_thread_spawner(
+        client_name: String,
+        client_version: String,
+        mut spawn_thread: impl FnMut(Box<dyn FnOnce() + Send>),
+    ) -> Arc<Self> {
         let tasks_executor = Arc::new(smol::Executor::new());
         let shutdown_notify = event_listener::Event::new();
 
-        for n in 0..thread::available_parallelism()
+        for _ in 0..thread::available_parallelism()
             .map(|n| n.get())
             .unwrap_or(4)
         {
             // Note that `listen()` must be called here (and not in the thread being spawned), as
-            // it might be notified as soon as `DefaultPlatform::new` returns.
+            // it might be notified as soon as this function returns.
             let on_shutdown = shutdown_notify.listen();
             let tasks_executor = tasks_executor.clone();
 
-            let spawn_result = thread::Builder::new()
-                .name(format!("smoldot-light-{}", n))
-                .spawn(move || smol::block_on(tasks_executor.run(on_shutdown)));
-
-            if let Err(err) = spawn_result {
-                panic!("Failed to spawn execution thread: {err}");
-            }
+            spawn_thread(Box::new(move || {
+                smol::block_on(tasks_executor.run(on_shutdown))
+            }));
         }
 
         Arc::new(DefaultPlatform {
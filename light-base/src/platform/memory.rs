@@ -0,0 +1,315 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+#![cfg(feature = "std")]
+#![cfg_attr(docsrs, doc(cfg(feature = "std")))]
+
+//! Implementation of the [`PlatformRef`] trait that only supports in-process connections and
+//! whose clock is manually controlled.
+//!
+//! This module contains the [`MemoryPlatform`] struct, which implements [`PlatformRef`]. It is
+//! meant to be used in tests, where multiple nodes run within the same process and connect to
+//! each other through `/memory/<port>` multiaddresses. See [`smoldot::libp2p::memory`].
+//!
+//! The time reported by the [`MemoryPlatform`] never advances on its own. Call
+//! [`MemoryPlatform::advance_time`] in order to make it advance.
+//!
+//! # Example
+//!
+//! ```rust
+//! use core::time::Duration;
+//! use smoldot_light::{Client, platform::MemoryPlatform};
+//! let platform = MemoryPlatform::new("test".into(), "1.0".into(), Duration::from_secs(1_700_000_000));
+//! let client = Client::new(platform.clone());
+//! platform.advance_time(Duration::from_secs(6));
+//! # let _: Client<_, ()> = client;  // Used in this example to infer the generic parameters of the Client
+//! ```
+//!
+
+use super::{
+    with_buffers, Address, ConnectionType, MultiStreamAddress, MultiStreamWebRtcConnection,
+    PlatformRef, SubstreamDirection,
+};
+
+use alloc::{borrow::Cow, collections::BTreeMap, sync::Arc};
+use core::{
+    future::Future,
+    pin::Pin,
+    sync::atomic::{AtomicU64, Ordering},
+    task::{Context, Poll, Waker},
+    time::Duration,
+};
+use futures_util::future;
+use smoldot::libp2p::memory;
+use std::{io, sync::Mutex, thread};
+
+/// Implementation of the [`PlatformRef`] trait that only supports in-process connections and
+/// whose clock is manually controlled.
+pub struct MemoryPlatform {
+    client_name: String,
+    client_version: String,
+    /// Value returned by [`PlatformRef::now_from_unix_epoch`] when the clock is at zero.
+    unix_time_start: Duration,
+    clock: Arc<Clock>,
+    tasks_executor: Arc<smol::Executor<'static>>,
+    shutdown_notify: event_listener::Event,
+}
+
+impl MemoryPlatform {
+    /// Creates a new [`MemoryPlatform`].
+    ///
+    /// This function spawns threads in order to execute the background tasks that will later be
+    /// spawned.
+    ///
+    /// Must be passed as "client name" and "client version" that are used in various places
+    /// such as to answer some JSON-RPC requests, and the value of the UNIX time that the clock
+    /// starts at.
+    ///
+    /// # Panic
+    ///
+    /// Panics if it wasn't possible to spawn background threads.
+    ///
+    pub fn new(
+        client_name: String,
+        client_version: String,
+        unix_time_start: Duration,
+    ) -> Arc<Self> {
+        let tasks_executor = Arc::new(smol::Executor::new());
+        let shutdown_notify = event_listener::Event::new();
+
+        for n in 0..thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(4)
+        {
+            // Note that `listen()` must be called here (and not in the thread being spawned), as
+            // it might be notified as soon as `MemoryPlatform::new` returns.
+            let on_shutdown = shutdown_notify.listen();
+            let tasks_executor = tasks_executor.clone();
+
+            let spawn_result = thread::Builder::new()
+                .name(format!("smoldot-light-memory-{}", n))
+                .spawn(move || smol::block_on(tasks_executor.run(on_shutdown)));
+
+            if let Err(err) = spawn_result {
+                panic!("Failed to spawn execution thread: {err}");
+            }
+        }
+
+        Arc::new(MemoryPlatform {
+            client_name,
+            client_version,
+            unix_time_start,
+            clock: Arc::new(Clock {
+                state: Mutex::new(ClockState {
+                    now: Duration::new(0, 0),
+                    sleeps: BTreeMap::new(),
+                }),
+                next_sleep_id: AtomicU64::new(0),
+            }),
+            tasks_executor,
+            shutdown_notify,
+        })
+    }
+
+    /// Advances the clock by the given duration, and wakes up all the timers that have elapsed.
+    pub fn advance_time(&self, duration: Duration) {
+        let elapsed = {
+            let mut state = self.clock.state.lock().unwrap();
+            state.now += duration;
+            let split_key = (state.now + Duration::from_nanos(1), 0);
+            let still_pending = state.sleeps.split_off(&split_key);
+            core::mem::replace(&mut state.sleeps, still_pending)
+        };
+
+        for (_, waker) in elapsed {
+            waker.wake();
+        }
+    }
+}
+
+impl PlatformRef for Arc<MemoryPlatform> {
+    type Delay = Delay;
+    /// Time elapsed since the [`MemoryPlatform`] has been created, according to its manually
+    /// controlled clock.
+    type Instant = Duration;
+    type MultiStream = std::convert::Infallible; // TODO: replace with `!` once stable: https://github.com/rust-lang/rust/issues/35121
+    type Stream = Stream;
+    type StreamConnectFuture = future::Ready<Self::Stream>;
+    type MultiStreamConnectFuture = future::Pending<MultiStreamWebRtcConnection<Self::MultiStream>>;
+    type ReadWriteAccess<'a> = with_buffers::ReadWriteAccess<'a, Duration>;
+    type StreamUpdateFuture<'a> = future::BoxFuture<'a, ()>;
+    type StreamErrorRef<'a> = &'a io::Error;
+    type NextSubstreamFuture<'a> = future::Pending<Option<(Self::Stream, SubstreamDirection)>>;
+
+    fn now_from_unix_epoch(&self) -> Duration {
+        self.unix_time_start + self.now()
+    }
+
+    fn now(&self) -> Self::Instant {
+        self.clock.state.lock().unwrap().now
+    }
+
+    fn fill_random_bytes(&self, buffer: &mut [u8]) {
+        rand::RngCore::fill_bytes(&mut rand::thread_rng(), buffer);
+    }
+
+    fn sleep(&self, duration: Duration) -> Self::Delay {
+        self.sleep_until(self.now() + duration)
+    }
+
+    fn sleep_until(&self, when: Self::Instant) -> Self::Delay {
+        Delay {
+            clock: self.clock.clone(),
+            when,
+            id: self.clock.next_sleep_id.fetch_add(1, Ordering::Relaxed),
+        }
+    }
+
+    fn spawn_task(&self, _task_name: Cow<str>, task: impl Future<Output = ()> + Send + 'static) {
+        self.tasks_executor.spawn(task).detach();
+    }
+
+    fn client_name(&self) -> Cow<str> {
+        Cow::Borrowed(&self.client_name)
+    }
+
+    fn client_version(&self) -> Cow<str> {
+        Cow::Borrowed(&self.client_version)
+    }
+
+    fn supports_connection_type(&self, connection_type: ConnectionType) -> bool {
+        matches!(connection_type, ConnectionType::Memory)
+    }
+
+    fn connect_stream(&self, address: Address) -> Self::StreamConnectFuture {
+        let Address::Memory { port } = address else {
+            // The API user of the `PlatformRef` trait is never supposed to open connections of
+            // a type that isn't supported.
+            unreachable!()
+        };
+
+        let socket = memory::connect(port)
+            .map_err(|err| io::Error::new(io::ErrorKind::ConnectionRefused, err.to_string()));
+        future::ready(Stream(with_buffers::WithBuffers::new(future::ready(
+            socket,
+        ))))
+    }
+
+    fn connect_multistream(&self, _address: MultiStreamAddress) -> Self::MultiStreamConnectFuture {
+        panic!()
+    }
+
+    fn open_out_substream(&self, c: &mut Self::MultiStream) {
+        // This function can only be called with so-called "multi-stream" connections. We never
+        // open such connection.
+        match *c {}
+    }
+
+    fn next_substream(&self, c: &'_ mut Self::MultiStream) -> Self::NextSubstreamFuture<'_> {
+        // This function can only be called with so-called "multi-stream" connections. We never
+        // open such connection.
+        match *c {}
+    }
+
+    fn read_write_access<'a>(
+        &self,
+        stream: Pin<&'a mut Self::Stream>,
+    ) -> Result<Self::ReadWriteAccess<'a>, &'a io::Error> {
+        let now = self.now();
+        let stream = stream.project();
+        stream.0.read_write_access(now)
+    }
+
+    fn wait_read_write_again<'a>(
+        &self,
+        stream: Pin<&'a mut Self::Stream>,
+    ) -> Self::StreamUpdateFuture<'a> {
+        let stream = stream.project();
+        let platform = self.clone();
+        Box::pin(
+            stream
+                .0
+                .wait_read_write_again(move |when| platform.sleep_until(when)),
+        )
+    }
+}
+
+impl Drop for MemoryPlatform {
+    fn drop(&mut self) {
+        self.shutdown_notify.notify(usize::max_value());
+    }
+}
+
+/// Implementation detail of [`MemoryPlatform`].
+#[pin_project::pin_project]
+pub struct Stream(
+    #[pin]
+    with_buffers::WithBuffers<
+        future::Ready<Result<memory::Stream, io::Error>>,
+        memory::Stream,
+        Duration,
+    >,
+);
+
+/// Implementation detail of [`MemoryPlatform`].
+pub struct Delay {
+    clock: Arc<Clock>,
+    when: Duration,
+    /// Identifier of this delay, used as a key in [`ClockState::sleeps`].
+    id: u64,
+}
+
+impl Future for Delay {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let mut state = self.clock.state.lock().unwrap();
+        if state.now >= self.when {
+            return Poll::Ready(());
+        }
+
+        state
+            .sleeps
+            .insert((self.when, self.id), cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+impl Drop for Delay {
+    fn drop(&mut self) {
+        self.clock
+            .state
+            .lock()
+            .unwrap()
+            .sleeps
+            .remove(&(self.when, self.id));
+    }
+}
+
+/// Manually controlled clock shared between a [`MemoryPlatform`] and its [`Delay`]s.
+struct Clock {
+    state: Mutex<ClockState>,
+    /// Identifier to assign to the next [`Delay`].
+    next_sleep_id: AtomicU64,
+}
+
+struct ClockState {
+    /// Time elapsed since the creation of the [`MemoryPlatform`].
+    now: Duration,
+    /// List of [`Delay`]s that are waiting, indexed by when they finish and their identifier.
+    sleeps: BTreeMap<(Duration, u64), Waker>,
+}
@@ -79,6 +79,7 @@ use smoldot::{
     chain::async_tree,
     executor, header,
     informant::{BytesDisplay, HashDisplay},
+    libp2p::PeerId,
     network::codec,
     trie::{self, proof_decode, Nibble, TrieEntryVersion},
 };
@@ -790,6 +791,13 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
         }
     }
 
+    /// Downloads a call proof for the given runtime call from the network, then locks the
+    /// runtime of the block.
+    ///
+    /// The call proof is downloaded from up to `total_attempts` peers. If it later turns out,
+    /// while performing the call, that the proof is missing some entries, the peer that has
+    /// sent it is banned and a new proof is downloaded from a different peer. This is done up
+    /// to `total_attempts` times.
     pub async fn start<'b>(
         &'b self,
         method: &'b str,
@@ -797,12 +805,17 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
         total_attempts: u32,
         timeout_per_request: Duration,
         max_parallel: NonZeroU32,
-    ) -> Result<(RuntimeCall<'b>, executor::host::HostVmPrototype), RuntimeCallError> {
+    ) -> Result<(RuntimeCall<'b, TPlat>, executor::host::HostVmPrototype), RuntimeCallError> {
         // TODO: DRY :-/ this whole thing is messy
 
+        // The parameter is stored in order to be able to download other call proofs later.
+        let parameter = parameter_vectored.fold(Vec::new(), |mut parameter, chunk| {
+            parameter.extend_from_slice(chunk.as_ref());
+            parameter
+        });
+
         // Perform the call proof request.
         // Note that `guarded` is not locked.
-        // TODO: there's no way to verify that the call proof is actually correct; we have to ban the peer and restart the whole call process if it turns out that it's not
         // TODO: also, an empty proof will be reported as an error right now, which is weird
         let call_proof = self
             .sync_service
@@ -812,8 +825,9 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
                 codec::CallProofRequestConfig {
                     block_hash: self.hash,
                     method: method.into(),
-                    parameter_vectored: parameter_vectored.clone(),
+                    parameter_vectored: iter::once(&parameter),
                 },
+                &[],
                 total_attempts,
                 timeout_per_request,
                 max_parallel,
@@ -821,13 +835,6 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
             .await
             .map_err(RuntimeCallError::CallProof);
 
-        let call_proof = call_proof.and_then(|call_proof| {
-            proof_decode::decode_and_verify_proof(proof_decode::Config {
-                proof: call_proof.decode().to_owned(), // TODO: to_owned() inefficiency, need some help from the networking to obtain the owned data
-            })
-            .map_err(RuntimeCallError::StorageRetrieval)
-        });
-
         let (guarded, virtual_machine) = match self.runtime.runtime.as_ref() {
             Ok(r) => {
                 let mut lock = r.virtual_machine.lock().await;
@@ -841,8 +848,17 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
 
         let lock = RuntimeCall {
             guarded,
+            sync_service: &self.sync_service,
+            block_number: self.block_number,
+            block_hash: self.hash,
             block_state_root_hash: self.block_state_root_hash,
+            method,
+            parameter,
+            total_attempts,
+            timeout_per_request,
+            max_parallel,
             call_proof,
+            incomplete_proofs_peers: Vec::new(),
         };
 
         Ok((lock, virtual_machine))
@@ -850,46 +866,62 @@ impl<TPlat: PlatformRef> RuntimeAccess<TPlat> {
 }
 
 /// See [`RuntimeService::pinned_block_runtime_access`].
+///
+/// The storage of the block is accessed through a call proof downloaded from the network. If the
+/// call proof turns out to be missing entries, the peer that has sent it is banned and a new
+/// call proof is transparently downloaded from a different peer.
+///
+/// Because the call proof is verified against the state trie root of the block, the storage
+/// entries that have been found in a previous call proof are guaranteed to also be the same
+/// in a new call proof. It is therefore not necessary to restart the runtime call from scratch
+/// when a new call proof is downloaded.
 #[must_use]
-pub struct RuntimeCall<'a> {
+pub struct RuntimeCall<'a, TPlat: PlatformRef> {
     guarded: MutexGuard<'a, Option<executor::host::HostVmPrototype>>,
+    sync_service: &'a Arc<sync_service::SyncService<TPlat>>,
+    block_number: u64,
+    block_hash: [u8; 32],
     block_state_root_hash: [u8; 32],
-    call_proof: Result<trie::proof_decode::DecodedTrieProof<Vec<u8>>, RuntimeCallError>,
+    /// Name of the runtime function being called.
+    method: &'a str,
+    /// Parameter passed to the runtime function.
+    parameter: Vec<u8>,
+    /// Value passed to [`RuntimeAccess::start`].
+    total_attempts: u32,
+    /// Value passed to [`RuntimeAccess::start`].
+    timeout_per_request: Duration,
+    /// Value passed to [`RuntimeAccess::start`].
+    max_parallel: NonZeroU32,
+    /// Call proof currently in use, or error if no call proof could be obtained.
+    call_proof: Result<sync_service::CallProof, RuntimeCallError>,
+    /// List of peers that have sent a call proof that was missing entries. Never contains more
+    /// than [`RuntimeCall::total_attempts`] elements.
+    incomplete_proofs_peers: Vec<PeerId>,
 }
 
-impl<'a> RuntimeCall<'a> {
+impl<'a, TPlat: PlatformRef> RuntimeCall<'a, TPlat> {
     /// Finds the given key in the call proof and returns the associated storage value.
     ///
     /// If `child_trie` is `Some`, look for the key in the given child trie. If it is `None`, look
     /// for the key in the main trie.
     ///
-    /// Returns an error if the key couldn't be found in the proof, meaning that the proof is
-    /// invalid.
-    // TODO: if proof is invalid, we should give the option to fetch another call proof
-    pub fn storage_entry(
-        &self,
+    /// If the key couldn't be found in the call proof, a new call proof is downloaded from a
+    /// different peer. An error is returned if no valid call proof could be obtained.
+    pub async fn storage_entry(
+        &mut self,
         child_trie: Option<&[u8]>,
         requested_key: &[u8],
     ) -> Result<Option<(&[u8], TrieEntryVersion)>, RuntimeCallError> {
-        let call_proof = match &self.call_proof {
-            Ok(p) => p,
-            Err(err) => return Err(err.clone()),
-        };
-
-        let trie_root = match child_trie {
-            Some(child_trie) => {
-                match Self::child_trie_root(call_proof, &self.block_state_root_hash, child_trie)? {
-                    Some(h) => h,
-                    None => return Ok(None),
-                }
-            }
-            None => self.block_state_root_hash,
-        };
-
-        match call_proof.storage_value(&trie_root, requested_key) {
-            Ok(v) => Ok(v),
-            Err(err) => Err(RuntimeCallError::MissingProofEntry(err)),
+        // Download new call proofs until the entry can be found.
+        while matches!(
+            self.storage_entry_in_proof(child_trie, requested_key),
+            Err(ProofLookupError::MissingProofEntry)
+        ) {
+            self.download_new_call_proof().await?;
         }
+
+        self.storage_entry_in_proof(child_trie, requested_key)
+            .map_err(ProofLookupError::into_call_error)
     }
 
     /// Find in the proof the trie node that follows `key_before` in lexicographic order.
@@ -905,19 +937,129 @@ impl<'a> RuntimeCall<'a> {
     ///
     /// If `branch_nodes` is `false`, then trie nodes that don't have a storage value are skipped.
     ///
-    /// Returns an error if the proof doesn't contain enough information, meaning that the proof is
-    /// invalid.
-    pub fn next_key(
-        &'_ self,
+    /// If the proof doesn't contain enough information, a new call proof is downloaded from a
+    /// different peer. An error is returned if no valid call proof could be obtained.
+    pub async fn next_key(
+        &'_ mut self,
         child_trie: Option<&[u8]>,
         key_before: &[trie::Nibble],
         or_equal: bool,
         prefix: &[trie::Nibble],
         branch_nodes: bool,
     ) -> Result<Option<&'_ [trie::Nibble]>, RuntimeCallError> {
+        // Download new call proofs until the entry can be found.
+        while matches!(
+            self.next_key_in_proof(child_trie, key_before, or_equal, prefix, branch_nodes),
+            Err(ProofLookupError::MissingProofEntry)
+        ) {
+            self.download_new_call_proof().await?;
+        }
+
+        self.next_key_in_proof(child_trie, key_before, or_equal, prefix, branch_nodes)
+            .map_err(ProofLookupError::into_call_error)
+    }
+
+    /// Find in the proof the closest trie node that descends from `key` and returns its Merkle
+    /// value.
+    ///
+    /// If `child_trie` is `Some`, look for the key in the given child trie. If it is `None`, look
+    /// for the key in the main trie.
+    ///
+    /// If the proof doesn't contain enough information, a new call proof is downloaded from a
+    /// different peer. An error is returned if no valid call proof could be obtained.
+    ///
+    /// Returns `Ok(None)` if the child trie is known to not exist or if it is known that there is
+    /// no descendant.
+    pub async fn closest_descendant_merkle_value(
+        &'_ mut self,
+        child_trie: Option<&[u8]>,
+        key: &[trie::Nibble],
+    ) -> Result<Option<&'_ [u8]>, RuntimeCallError> {
+        // Download new call proofs until the entry can be found.
+        while matches!(
+            self.closest_descendant_merkle_value_in_proof(child_trie, key),
+            Err(ProofLookupError::MissingProofEntry)
+        ) {
+            self.download_new_call_proof().await?;
+        }
+
+        self.closest_descendant_merkle_value_in_proof(child_trie, key)
+            .map_err(ProofLookupError::into_call_error)
+    }
+
+    /// End the runtime call.
+    ///
+    /// This method **must** be called.
+    pub fn unlock(mut self, vm: executor::host::HostVmPrototype) {
+        debug_assert!(self.guarded.is_none());
+        *self.guarded = Some(vm);
+    }
+
+    /// Bans the peer that has sent the current call proof, and downloads a new call proof from a
+    /// different peer.
+    ///
+    /// Must only be called if the current call proof is missing an entry.
+    async fn download_new_call_proof(&mut self) -> Result<(), RuntimeCallError> {
+        let Ok(call_proof) = &self.call_proof else {
+            unreachable!()
+        };
+
+        let peer_id = call_proof.peer_id.clone();
+        self.sync_service
+            .report_incomplete_call_proof(peer_id.clone())
+            .await;
+        self.incomplete_proofs_peers.push(peer_id);
+
+        let previous_errors =
+            iter::repeat(sync_service::CallProofQueryErrorDetail::MissingProofEntry)
+                .take(self.incomplete_proofs_peers.len());
+
+        if self.incomplete_proofs_peers.len()
+            >= usize::try_from(self.total_attempts).unwrap_or(usize::max_value())
+        {
+            self.call_proof = Err(RuntimeCallError::CallProof(
+                sync_service::CallProofQueryError {
+                    errors: previous_errors.collect(),
+                },
+            ));
+        } else {
+            self.call_proof = self
+                .sync_service
+                .clone()
+                .call_proof_query(
+                    self.block_number,
+                    codec::CallProofRequestConfig {
+                        block_hash: self.block_hash,
+                        method: self.method.into(),
+                        parameter_vectored: iter::once(&self.parameter),
+                    },
+                    &self.incomplete_proofs_peers,
+                    self.total_attempts,
+                    self.timeout_per_request,
+                    self.max_parallel,
+                )
+                .await
+                .map_err(|err| {
+                    RuntimeCallError::CallProof(sync_service::CallProofQueryError {
+                        errors: previous_errors.chain(err.errors).collect(),
+                    })
+                });
+        }
+
+        match &self.call_proof {
+            Ok(_) => Ok(()),
+            Err(err) => Err(err.clone()),
+        }
+    }
+
+    fn storage_entry_in_proof(
+        &self,
+        child_trie: Option<&[u8]>,
+        requested_key: &[u8],
+    ) -> Result<Option<(&[u8], TrieEntryVersion)>, ProofLookupError> {
         let call_proof = match &self.call_proof {
-            Ok(p) => p,
-            Err(err) => return Err(err.clone()),
+            Ok(p) => &p.decoded_proof,
+            Err(err) => return Err(ProofLookupError::Call(err.clone())),
         };
 
         let trie_root = match child_trie {
@@ -930,31 +1072,22 @@ impl<'a> RuntimeCall<'a> {
             None => self.block_state_root_hash,
         };
 
-        match call_proof.next_key(&trie_root, key_before, or_equal, prefix, branch_nodes) {
-            Ok(v) => Ok(v),
-            Err(err) => Err(RuntimeCallError::MissingProofEntry(err)),
-        }
+        call_proof
+            .storage_value(&trie_root, requested_key)
+            .map_err(|_| ProofLookupError::MissingProofEntry)
     }
 
-    /// Find in the proof the closest trie node that descends from `key` and returns its Merkle
-    /// value.
-    ///
-    /// If `child_trie` is `Some`, look for the key in the given child trie. If it is `None`, look
-    /// for the key in the main trie.
-    ///
-    /// Returns an error if the proof doesn't contain enough information, meaning that the proof is
-    /// invalid.
-    ///
-    /// Returns `Ok(None)` if the child trie is known to not exist or if it is known that there is
-    /// no descendant.
-    pub fn closest_descendant_merkle_value(
+    fn next_key_in_proof(
         &'_ self,
         child_trie: Option<&[u8]>,
-        key: &[trie::Nibble],
-    ) -> Result<Option<&'_ [u8]>, RuntimeCallError> {
+        key_before: &[trie::Nibble],
+        or_equal: bool,
+        prefix: &[trie::Nibble],
+        branch_nodes: bool,
+    ) -> Result<Option<&'_ [trie::Nibble]>, ProofLookupError> {
         let call_proof = match &self.call_proof {
-            Ok(p) => p,
-            Err(err) => return Err(err.clone()),
+            Ok(p) => &p.decoded_proof,
+            Err(err) => return Err(ProofLookupError::Call(err.clone())),
         };
 
         let trie_root = match child_trie {
@@ -968,23 +1101,40 @@ impl<'a> RuntimeCall<'a> {
         };
 
         call_proof
-            .closest_descendant_merkle_value(&trie_root, key)
-            .map_err(RuntimeCallError::MissingProofEntry)
+            .next_key(&trie_root, key_before, or_equal, prefix, branch_nodes)
+            .map_err(|_| ProofLookupError::MissingProofEntry)
     }
 
-    /// End the runtime call.
-    ///
-    /// This method **must** be called.
-    pub fn unlock(mut self, vm: executor::host::HostVmPrototype) {
-        debug_assert!(self.guarded.is_none());
-        *self.guarded = Some(vm);
+    fn closest_descendant_merkle_value_in_proof(
+        &'_ self,
+        child_trie: Option<&[u8]>,
+        key: &[trie::Nibble],
+    ) -> Result<Option<&'_ [u8]>, ProofLookupError> {
+        let call_proof = match &self.call_proof {
+            Ok(p) => &p.decoded_proof,
+            Err(err) => return Err(ProofLookupError::Call(err.clone())),
+        };
+
+        let trie_root = match child_trie {
+            Some(child_trie) => {
+                match Self::child_trie_root(call_proof, &self.block_state_root_hash, child_trie)? {
+                    Some(h) => h,
+                    None => return Ok(None),
+                }
+            }
+            None => self.block_state_root_hash,
+        };
+
+        call_proof
+            .closest_descendant_merkle_value(&trie_root, key)
+            .map_err(|_| ProofLookupError::MissingProofEntry)
     }
 
     fn child_trie_root(
         proof: &proof_decode::DecodedTrieProof<Vec<u8>>,
         main_trie_root: &[u8; 32],
         child_trie: &[u8],
-    ) -> Result<Option<[u8; 32]>, RuntimeCallError> {
+    ) -> Result<Option<[u8; 32]>, ProofLookupError> {
         // TODO: allocation here, but probably not problematic
         const PREFIX: &[u8] = b":child_storage:default:";
         let mut key = Vec::with_capacity(PREFIX.len() + child_trie.as_ref().len());
@@ -992,17 +1142,38 @@ impl<'a> RuntimeCall<'a> {
         key.extend_from_slice(child_trie.as_ref());
 
         match proof.storage_value(main_trie_root, &key) {
-            Err(err) => Err(RuntimeCallError::MissingProofEntry(err)),
+            Err(_) => Err(ProofLookupError::MissingProofEntry),
             Ok(None) => Ok(None),
             Ok(Some((value, _))) => match <[u8; 32]>::try_from(value) {
                 Ok(hash) => Ok(Some(hash)),
-                Err(_) => Err(RuntimeCallError::InvalidChildTrieRoot),
+                Err(_) => Err(ProofLookupError::Call(
+                    RuntimeCallError::InvalidChildTrieRoot,
+                )),
             },
         }
     }
 }
 
-impl<'a> Drop for RuntimeCall<'a> {
+/// Error while looking up an entry in a call proof. See [`RuntimeCall`].
+enum ProofLookupError {
+    /// The call proof doesn't contain the requested entry.
+    MissingProofEntry,
+    /// Any other error.
+    Call(RuntimeCallError),
+}
+
+impl ProofLookupError {
+    fn into_call_error(self) -> RuntimeCallError {
+        match self {
+            // The lookup is only ever performed again after having made sure that the entry is
+            // in the proof.
+            ProofLookupError::MissingProofEntry => unreachable!(),
+            ProofLookupError::Call(err) => err,
+        }
+    }
+}
+
+impl<'a, TPlat: PlatformRef> Drop for RuntimeCall<'a, TPlat> {
     fn drop(&mut self) {
         if self.guarded.is_none() {
             // The [`RuntimeCall`] has been destroyed without being properly unlocked.
@@ -1018,13 +1189,6 @@ pub enum RuntimeCallError {
     /// Runtime of the block isn't valid.
     #[display(fmt = "Runtime of the block isn't valid: {_0}")]
     InvalidRuntime(RuntimeError),
-    /// Error while retrieving the storage item from other nodes.
-    // TODO: change error type?
-    #[display(fmt = "Error in call proof: {_0}")]
-    StorageRetrieval(proof_decode::Error),
-    /// One or more entries are missing from the call proof.
-    #[display(fmt = "One or more entries are missing from the call proof")]
-    MissingProofEntry(proof_decode::IncompleteProofError),
     /// Call proof contains a reference to a child trie whose hash isn't 32 bytes.
     InvalidChildTrieRoot,
     /// Error while retrieving the call proof from the network.
@@ -1041,8 +1205,6 @@ impl RuntimeCallError {
     pub fn is_network_problem(&self) -> bool {
         match self {
             RuntimeCallError::InvalidRuntime(_) => false,
-            RuntimeCallError::StorageRetrieval(_) => false,
-            RuntimeCallError::MissingProofEntry(_) => false,
             RuntimeCallError::InvalidChildTrieRoot => false,
             RuntimeCallError::CallProof(err) => err.is_network_problem(),
             RuntimeCallError::StorageQuery(err) => err.is_network_problem(),
@@ -2305,6 +2467,7 @@ impl SuccessfulRuntime {
             heap_pages,
             exec_hint,
             allow_unresolved_imports: false,
+            fuel_budget: None,
         }) {
             Ok(vm) => {
                 return Ok(SuccessfulRuntime {
@@ -2323,6 +2486,7 @@ impl SuccessfulRuntime {
                     heap_pages,
                     exec_hint,
                     allow_unresolved_imports: true,
+                    fuel_budget: None,
                 }) {
                     Ok(vm) => {
                         log::warn!(
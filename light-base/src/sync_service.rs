@@ -29,6 +29,7 @@
 use crate::{network_service, platform::PlatformRef, runtime_service};
 
 use alloc::{borrow::ToOwned as _, boxed::Box, format, string::String, sync::Arc, vec::Vec};
+use async_lock::Mutex;
 use core::{cmp, fmt, future::Future, mem, num::NonZeroU32, pin::Pin, time::Duration};
 use futures_channel::oneshot;
 use futures_lite::stream;
@@ -44,6 +45,7 @@ use smoldot::{
 
 mod parachain;
 mod standalone;
+mod storage_cache;
 
 /// Configuration for a [`SyncService`].
 pub struct Config<TPlat: PlatformRef> {
@@ -72,6 +74,35 @@ pub struct Config<TPlat: PlatformRef> {
 
     /// Extra fields depending on whether the chain is a relay chain or a parachain.
     pub chain_type: ConfigChainType<TPlat>,
+
+    /// Storage entries that are known to have been valid at some point in the past, for example
+    /// because they were found in a database.
+    ///
+    /// Whenever one of these keys is requested through [`SyncService::storage_query`], the
+    /// Merkle value of its trie node is downloaded first. If it matches the Merkle value of the
+    /// entry, the storage value of the entry is used instead of being downloaded. If it doesn't
+    /// match, an extra round-trip will be needed.
+    ///
+    /// Entries whose total size exceed [`Config::storage_cache_max_size`] are ignored.
+    pub storage_cache_hint: Vec<StorageCacheEntry>,
+
+    /// Maximum size, in bytes, of the storage entries that are kept in memory in order to be
+    /// reused by later calls to [`SyncService::storage_query`]. Can be `0` in order to disable
+    /// the cache.
+    pub storage_cache_max_size: usize,
+}
+
+/// See [`Config::storage_cache_hint`] and [`SyncService::storage_cache_entries`].
+#[derive(Debug, Clone)]
+pub struct StorageCacheEntry {
+    /// Storage key of the entry.
+    pub key: Vec<u8>,
+    /// Storage value associated with [`StorageCacheEntry::key`].
+    pub storage_value: Vec<u8>,
+    /// Merkle value of the trie node of [`StorageCacheEntry::key`] in the storage main trie.
+    pub merkle_value: Vec<u8>,
+    /// Closest ancestor of [`StorageCacheEntry::key`] except for the key itself.
+    pub closest_ancestor_excluding: Vec<Nibble>,
 }
 
 /// See [`Config::chain_type`].
@@ -147,6 +178,9 @@ pub struct SyncService<TPlat: PlatformRef> {
     network_chain_id: network_service::ChainId,
     /// See [`Config::block_number_bytes`].
     block_number_bytes: usize,
+
+    /// Storage entries verified in the past. See [`Config::storage_cache_hint`].
+    storage_cache: Mutex<storage_cache::StorageCache>,
 }
 
 impl<TPlat: PlatformRef> SyncService<TPlat> {
@@ -192,12 +226,27 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                 log::debug!(target: &log_target, "Shutdown");
             });
 
+        let mut storage_cache = storage_cache::StorageCache::new(config.storage_cache_max_size);
+        // The hint is inserted in reverse order so that the first entries of the hint end up
+        // being the most recently used.
+        for entry in config.storage_cache_hint.into_iter().rev() {
+            storage_cache.insert(
+                entry.key,
+                storage_cache::Entry {
+                    storage_value: entry.storage_value,
+                    merkle_value: entry.merkle_value,
+                    closest_ancestor_excluding: entry.closest_ancestor_excluding,
+                },
+            );
+        }
+
         SyncService {
             to_background,
             platform: config.platform,
             network_service: config.network_service.0,
             network_chain_id: config.network_service.1,
             block_number_bytes: config.block_number_bytes,
+            storage_cache: Mutex::new(storage_cache),
         }
     }
 
@@ -206,6 +255,25 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
         self.block_number_bytes
     }
 
+    /// Returns the list of storage entries that have been verified in the past and that are kept
+    /// in cache, from the most recently used to the least recently used.
+    ///
+    /// There is no guarantee that these entries match the storage of any specific block. See
+    /// [`Config::storage_cache_hint`].
+    pub async fn storage_cache_entries(&self) -> Vec<StorageCacheEntry> {
+        self.storage_cache
+            .lock()
+            .await
+            .iter()
+            .map(|(key, entry)| StorageCacheEntry {
+                key: key.to_vec(),
+                storage_value: entry.storage_value.clone(),
+                merkle_value: entry.merkle_value.clone(),
+                closest_ancestor_excluding: entry.closest_ancestor_excluding.clone(),
+            })
+            .collect()
+    }
+
     /// Returns the state of the finalized block of the chain, after passing it through
     /// [`smoldot::database::finalized_serialize::encode_chain`].
     ///
@@ -256,6 +324,33 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
         rx.await.unwrap()
     }
 
+    /// Subscribes to the BEEFY justifications found in the blocks downloaded from the network.
+    ///
+    /// Only up to `buffer_size` justifications are buffered in the channel. If the channel is
+    /// full when a new justification is attempted to be pushed, the channel gets closed.
+    ///
+    /// > **Important**: The justifications are reported as received from the network and
+    /// >                haven't been verified. It is the responsibility of the receiver to
+    /// >                verify them.
+    ///
+    /// Parachains never report any BEEFY justification.
+    pub async fn subscribe_beefy_justifications(
+        &self,
+        buffer_size: usize,
+    ) -> async_channel::Receiver<BeefyJustification> {
+        let (send_back, rx) = oneshot::channel();
+
+        self.to_background
+            .send(ToBackground::SubscribeBeefyJustifications {
+                send_back,
+                buffer_size,
+            })
+            .await
+            .unwrap();
+
+        rx.await.unwrap()
+    }
+
     /// Returns true if it is believed that we are near the head of the chain.
     ///
     /// The way this method is implemented is opaque and cannot be relied on. The return value
@@ -448,11 +543,20 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                 key: Vec<u8>,
                 hash: bool,
             },
+            /// Same as [`RequestImpl::ValueOrHash`], but the storage value might be found in
+            /// the storage cache. Only the Merkle value of the key is requested in order to
+            /// verify that the cache entry is still valid.
+            ValueOrHashFromCache {
+                key: Vec<u8>,
+                hash: bool,
+                cached: storage_cache::Entry,
+            },
             ClosestDescendantMerkleValue {
                 key: Vec<u8>,
             },
         }
 
+        let mut storage_cache = self.storage_cache.lock().await;
         let mut requests_remaining = requests
             .map(|request| match request.ty {
                 StorageRequestItemTy::DescendantsHashes
@@ -467,19 +571,26 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                     }),
                     requested_key: request.key,
                 },
-                StorageRequestItemTy::Value => RequestImpl::ValueOrHash {
-                    key: request.key,
-                    hash: false,
-                },
-                StorageRequestItemTy::Hash => RequestImpl::ValueOrHash {
-                    key: request.key,
-                    hash: true,
-                },
+                StorageRequestItemTy::Value | StorageRequestItemTy::Hash => {
+                    let hash = matches!(request.ty, StorageRequestItemTy::Hash);
+                    match storage_cache.get(&request.key) {
+                        Some(cached) => RequestImpl::ValueOrHashFromCache {
+                            cached: cached.clone(),
+                            key: request.key,
+                            hash,
+                        },
+                        None => RequestImpl::ValueOrHash {
+                            key: request.key,
+                            hash,
+                        },
+                    }
+                }
                 StorageRequestItemTy::ClosestDescendantMerkleValue => {
                     RequestImpl::ClosestDescendantMerkleValue { key: request.key }
                 }
             })
             .collect::<Vec<_>>();
+        drop(storage_cache);
 
         let total_attempts = usize::try_from(total_attempts).unwrap_or(usize::max_value());
         let mut outcome_errors = Vec::with_capacity(total_attempts);
@@ -559,7 +670,8 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                                 max_reponse_nodes += key.len() * 2;
                             }
                         }
-                        RequestImpl::ClosestDescendantMerkleValue { key } => {
+                        RequestImpl::ClosestDescendantMerkleValue { key }
+                        | RequestImpl::ValueOrHashFromCache { key, .. } => {
                             // We query the parent of `key`.
                             if key.is_empty() {
                                 if keys.insert(Vec::new()) {
@@ -580,7 +692,7 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                 .clone()
                 .storage_proof_request(
                     self.network_chain_id,
-                    target,
+                    target.clone(),
                     codec::StorageProofRequestConfig {
                         block_hash: *block_hash,
                         keys: keys_to_request.into_iter(),
@@ -623,6 +735,13 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
             }) {
                 Ok(d) => d,
                 Err(err) => {
+                    self.network_service
+                        .report_peer(
+                            self.network_chain_id,
+                            target,
+                            network_service::ReputationChange::BadResponse,
+                        )
+                        .await;
                     outcome_errors.push(StorageQueryErrorDetail::ProofVerification(err));
                     continue;
                 }
@@ -630,6 +749,10 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
 
             let mut proof_has_advanced_verification = false;
 
+            // Storage values found in the proof, or confirmed to still be valid, that should
+            // be put in the storage cache.
+            let mut storage_cache_updates = Vec::new();
+
             for request in mem::take(&mut requests_remaining) {
                 match request {
                     RequestImpl::PrefixScan {
@@ -720,6 +843,14 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                                 }
                                 proof_decode::StorageValue::Known { value, .. } => {
                                     proof_has_advanced_verification = true;
+                                    if let Some(entry) = storage_cache_entry_from_proof(
+                                        &decoded_proof,
+                                        main_trie_root_hash,
+                                        &key,
+                                        value,
+                                    ) {
+                                        storage_cache_updates.push((key.clone(), entry));
+                                    }
                                     if hash {
                                         let hashed_value =
                                             blake2_rfc::blake2b::blake2b(32, &[], value);
@@ -753,6 +884,67 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                             }
                         }
                     }
+                    RequestImpl::ValueOrHashFromCache { key, hash, cached } => {
+                        let key_nibbles =
+                            &trie::bytes_to_nibbles(key.iter().copied()).collect::<Vec<_>>();
+
+                        let Ok(merkle_value) = decoded_proof
+                            .closest_descendant_merkle_value(main_trie_root_hash, key_nibbles)
+                        else {
+                            requests_remaining.push(RequestImpl::ValueOrHashFromCache {
+                                key,
+                                hash,
+                                cached,
+                            });
+                            continue;
+                        };
+
+                        let Ok(closest_ancestor_excluding) = decoded_proof
+                            .closest_ancestor_in_proof(
+                                main_trie_root_hash,
+                                &key_nibbles[..key_nibbles.len() - 1],
+                            )
+                        else {
+                            requests_remaining.push(RequestImpl::ValueOrHashFromCache {
+                                key,
+                                hash,
+                                cached,
+                            });
+                            continue;
+                        };
+
+                        proof_has_advanced_verification = true;
+
+                        // If the Merkle value and the closest ancestor are the same as in the
+                        // cache entry, then the trie node of `key` is at the same location and
+                        // is the same as when the entry was inserted, and thus so is its
+                        // storage value. Otherwise, the storage value needs to be downloaded.
+                        if merkle_value != Some(&cached.merkle_value[..])
+                            || closest_ancestor_excluding
+                                != Some(&cached.closest_ancestor_excluding[..])
+                        {
+                            requests_remaining.push(RequestImpl::ValueOrHash { key, hash });
+                            continue;
+                        }
+
+                        if hash {
+                            let hashed_value =
+                                blake2_rfc::blake2b::blake2b(32, &[], &cached.storage_value);
+                            final_results.push(StorageResultItem::Hash {
+                                key: key.clone(),
+                                hash: Some(
+                                    *<&[u8; 32]>::try_from(hashed_value.as_bytes()).unwrap(),
+                                ),
+                            });
+                        } else {
+                            final_results.push(StorageResultItem::Value {
+                                key: key.clone(),
+                                value: Some(cached.storage_value.clone()),
+                            });
+                        }
+
+                        storage_cache_updates.push((key, cached));
+                    }
                     RequestImpl::ClosestDescendantMerkleValue { key } => {
                         let key_nibbles =
                             &trie::bytes_to_nibbles(key.iter().copied()).collect::<Vec<_>>();
@@ -792,6 +984,13 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                 }
             }
 
+            if !storage_cache_updates.is_empty() {
+                let mut storage_cache = self.storage_cache.lock().await;
+                for (key, entry) in storage_cache_updates {
+                    storage_cache.insert(key, entry);
+                }
+            }
+
             // If the proof doesn't contain any item that reduces the number of things to request,
             // then we push an error.
             if !proof_has_advanced_verification {
@@ -800,16 +999,38 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
         }
     }
 
-    // TODO: documentation
-    // TODO: there's no proof that the call proof is actually correct
+    /// Reports that a call proof returned by [`SyncService::call_proof_query`] is missing
+    /// entries that are necessary in order to perform the call. The peer that has sent it gets
+    /// banned.
+    pub async fn report_incomplete_call_proof(&self, peer_id: PeerId) {
+        self.network_service
+            .report_peer(
+                self.network_chain_id,
+                peer_id,
+                network_service::ReputationChange::IncompleteCallProof,
+            )
+            .await;
+    }
+
+    /// Downloads a call proof of the given runtime call from one of the peers that are assumed to
+    /// know the given block, and verifies that it is well-formed.
+    ///
+    /// Peers in `peers_to_skip` are never queried. The peers are tried one after the other until
+    /// one of them returns a well-formed proof or `total_attempts` peers have been tried. Peers
+    /// that return a malformed proof are reported to the network service.
+    ///
+    /// > **Note**: There is no way to verify whether a call proof contains all the entries
+    /// >           necessary to perform the call other than actually performing the call.
+    /// >           See also [`CallProofQueryErrorDetail::MissingProofEntry`].
     pub async fn call_proof_query(
         self: Arc<Self>,
         block_number: u64,
         config: codec::CallProofRequestConfig<'_, impl Iterator<Item = impl AsRef<[u8]>> + Clone>,
+        peers_to_skip: &[PeerId],
         total_attempts: u32,
         timeout_per_request: Duration,
         _max_parallel: NonZeroU32,
-    ) -> Result<network_service::EncodedMerkleProof, CallProofQueryError> {
+    ) -> Result<CallProof, CallProofQueryError> {
         let mut outcome_errors =
             Vec::with_capacity(usize::try_from(total_attempts).unwrap_or(usize::max_value()));
 
@@ -818,6 +1039,7 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
         for target in self
             .peers_assumed_know_blocks(block_number, &config.block_hash)
             .await
+            .filter(|peer_id| !peers_to_skip.contains(peer_id))
             .take(usize::try_from(total_attempts).unwrap_or(usize::max_value()))
         {
             let result = self
@@ -825,24 +1047,43 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
                 .clone()
                 .call_proof_request(
                     self.network_chain_id,
-                    target,
+                    target.clone(),
                     config.clone(),
                     timeout_per_request,
                 )
                 .await;
 
-            match result {
-                Ok(value) if !value.decode().is_empty() => return Ok(value),
+            let proof = match result {
+                Ok(value) if !value.decode().is_empty() => value,
                 // TODO: this check of emptiness is a bit of a hack; it is necessary because Substrate responds to requests about blocks it doesn't know with an empty proof
-                Ok(_) => outcome_errors.push(network_service::CallProofRequestError::Request(
-                    service::CallProofRequestError::Request(
-                        smoldot::network::service::RequestError::Substream(
-                            smoldot::libp2p::connection::established::RequestError::SubstreamClosed,
-                        ),
-                    ),
-                )),
+                Ok(_) => {
+                    outcome_errors.push(CallProofQueryErrorDetail::EmptyProof);
+                    continue;
+                }
                 Err(err) => {
-                    outcome_errors.push(err);
+                    outcome_errors.push(CallProofQueryErrorDetail::Network(err));
+                    continue;
+                }
+            };
+
+            match proof_decode::decode_and_verify_proof(proof_decode::Config {
+                proof: proof.decode().to_owned(), // TODO: to_owned() inefficiency, need some help from the networking to obtain the owned data
+            }) {
+                Ok(decoded_proof) => {
+                    return Ok(CallProof {
+                        peer_id: target,
+                        decoded_proof,
+                    })
+                }
+                Err(err) => {
+                    self.network_service
+                        .report_peer(
+                            self.network_chain_id,
+                            target,
+                            network_service::ReputationChange::BadResponse,
+                        )
+                        .await;
+                    outcome_errors.push(CallProofQueryErrorDetail::ProofVerification(err));
                 }
             }
         }
@@ -853,6 +1094,33 @@ impl<TPlat: PlatformRef> SyncService<TPlat> {
     }
 }
 
+/// Builds a [`storage_cache::Entry`] from a proof that contains the storage value of the given
+/// key. Returns `None` if the proof doesn't contain enough information.
+fn storage_cache_entry_from_proof<T: AsRef<[u8]>>(
+    decoded_proof: &proof_decode::DecodedTrieProof<T>,
+    main_trie_root_hash: &[u8; 32],
+    key: &[u8],
+    storage_value: &[u8],
+) -> Option<storage_cache::Entry> {
+    if key.is_empty() {
+        return None;
+    }
+
+    let key_nibbles = trie::bytes_to_nibbles(key.iter().copied()).collect::<Vec<_>>();
+    let merkle_value = decoded_proof
+        .closest_descendant_merkle_value(main_trie_root_hash, &key_nibbles)
+        .ok()??;
+    let closest_ancestor_excluding = decoded_proof
+        .closest_ancestor_in_proof(main_trie_root_hash, &key_nibbles[..key_nibbles.len() - 1])
+        .ok()??;
+
+    Some(storage_cache::Entry {
+        storage_value: storage_value.to_vec(),
+        merkle_value: merkle_value.to_vec(),
+        closest_ancestor_excluding: closest_ancestor_excluding.to_vec(),
+    })
+}
+
 /// An item requested with [`SyncService::storage_query`].
 #[derive(Debug, Clone)]
 pub struct StorageRequestItem {
@@ -1010,19 +1278,32 @@ pub enum StorageQueryErrorDetail {
     MissingProofEntry,
 }
 
+/// Call proof successfully returned by [`SyncService::call_proof_query`].
+pub struct CallProof {
+    /// Peer that has sent the proof.
+    pub peer_id: PeerId,
+    /// The proof itself.
+    pub decoded_proof: proof_decode::DecodedTrieProof<Vec<u8>>,
+}
+
 /// Error that can happen when calling [`SyncService::call_proof_query`].
 #[derive(Debug, Clone)]
 pub struct CallProofQueryError {
-    /// Contains one error per peer that has been contacted. If this list is empty, then we
+    /// Contains one error per attempt, in chronological order. If this list is empty, then we
     /// aren't connected to any node.
-    pub errors: Vec<network_service::CallProofRequestError>,
+    pub errors: Vec<CallProofQueryErrorDetail>,
 }
 
 impl CallProofQueryError {
     /// Returns `true` if this is caused by networking issues, as opposed to a consensus-related
     /// issue.
     pub fn is_network_problem(&self) -> bool {
-        self.errors.iter().all(|err| err.is_network_problem())
+        self.errors.iter().all(|err| match err {
+            CallProofQueryErrorDetail::Network(err) => err.is_network_problem(),
+            CallProofQueryErrorDetail::EmptyProof => true,
+            CallProofQueryErrorDetail::ProofVerification(_)
+            | CallProofQueryErrorDetail::MissingProofEntry => false,
+        })
     }
 }
 
@@ -1040,6 +1321,26 @@ impl fmt::Display for CallProofQueryError {
     }
 }
 
+/// See [`CallProofQueryError`].
+#[derive(Debug, derive_more::Display, Clone)]
+pub enum CallProofQueryErrorDetail {
+    /// Error during the network request.
+    #[display(fmt = "{_0}")]
+    Network(network_service::CallProofRequestError),
+    /// The peer has answered with an empty proof, which typically indicates that it doesn't
+    /// know the requested block.
+    #[display(fmt = "Empty call proof")]
+    EmptyProof,
+    /// Error verifying the proof.
+    #[display(fmt = "{_0}")]
+    ProofVerification(proof_decode::Error),
+    /// The proof is missing one or more entries that are necessary in order to perform the
+    /// call. Never returned by [`SyncService::call_proof_query`], as this can only be detected
+    /// while performing the call. See [`runtime_service::RuntimeCall`].
+    #[display(fmt = "Call proof is missing entries necessary to perform the call")]
+    MissingProofEntry,
+}
+
 /// Return value of [`SyncService::subscribe_all`].
 pub struct SubscribeAll {
     /// SCALE-encoded header of the finalized block at the time of the subscription.
@@ -1158,6 +1459,17 @@ pub struct BlockNotification {
     pub parent_hash: [u8; 32],
 }
 
+/// See [`SyncService::subscribe_beefy_justifications`].
+#[derive(Debug, Clone)]
+pub struct BeefyJustification {
+    /// Height of the block the justification has been found in.
+    pub block_number: u64,
+    /// Hash of the block the justification has been found in.
+    pub block_hash: [u8; 32],
+    /// SCALE-encoded BEEFY justification, as found in the block. Hasn't been verified.
+    pub scale_encoded_justification: Vec<u8>,
+}
+
 enum ToBackground {
     /// See [`SyncService::is_near_head_of_chain_heuristic`].
     IsNearHeadOfChainHeuristic { send_back: oneshot::Sender<bool> },
@@ -1167,6 +1479,11 @@ enum ToBackground {
         buffer_size: usize,
         runtime_interest: bool,
     },
+    /// See [`SyncService::subscribe_beefy_justifications`].
+    SubscribeBeefyJustifications {
+        send_back: oneshot::Sender<async_channel::Receiver<BeefyJustification>>,
+        buffer_size: usize,
+    },
     /// See [`SyncService::peers_assumed_know_blocks`].
     PeersAssumedKnowBlock {
         send_back: oneshot::Sender<Vec<PeerId>>,
@@ -612,6 +612,18 @@ impl<TPlat: PlatformRef> ParachainBackgroundTask<TPlat> {
             (ToBackground::SerializeChainInformation { send_back }, _) => {
                 let _ = send_back.send(None);
             }
+            (
+                ToBackground::SubscribeBeefyJustifications {
+                    send_back,
+                    buffer_size,
+                },
+                _,
+            ) => {
+                // Parachains don't use BEEFY. The sender is immediately dropped, meaning that
+                // the channel is closed without any item.
+                let (_, rx) = async_channel::bounded(buffer_size.max(1));
+                let _ = send_back.send(rx);
+            }
         }
     }
 
@@ -1231,7 +1243,7 @@ async fn parahead<TPlat: PlatformRef>(
         }
     };
 
-    let (runtime_call_lock, virtual_machine) = precall
+    let (mut runtime_call_lock, virtual_machine) = precall
         .start(
             para::PERSISTED_VALIDATION_FUNCTION_NAME,
             para::persisted_validation_data_parameters(
@@ -1257,6 +1269,7 @@ async fn parahead<TPlat: PlatformRef>(
         max_log_level: 0,
         storage_main_trie_changes: Default::default(),
         calculate_trie_changes: false,
+        trace: false,
     }) {
         Ok(vm) => vm,
         Err((err, prototype)) => {
@@ -1281,6 +1294,7 @@ async fn parahead<TPlat: PlatformRef>(
                     let child_trie = get.child_trie();
                     runtime_call_lock
                         .storage_entry(child_trie.as_ref().map(|c| c.as_ref()), get.key().as_ref())
+                        .await
                 };
                 let storage_value = match storage_value {
                     Ok(v) => v,
@@ -1296,13 +1310,15 @@ async fn parahead<TPlat: PlatformRef>(
             runtime_host::RuntimeHostVm::NextKey(nk) => {
                 let next_key = {
                     let child_trie = nk.child_trie();
-                    runtime_call_lock.next_key(
-                        child_trie.as_ref().map(|c| c.as_ref()),
-                        &nk.key().collect::<Vec<_>>(),
-                        nk.or_equal(),
-                        &nk.prefix().collect::<Vec<_>>(),
-                        nk.branch_nodes(),
-                    )
+                    runtime_call_lock
+                        .next_key(
+                            child_trie.as_ref().map(|c| c.as_ref()),
+                            &nk.key().collect::<Vec<_>>(),
+                            nk.or_equal(),
+                            &nk.prefix().collect::<Vec<_>>(),
+                            nk.branch_nodes(),
+                        )
+                        .await
                 };
                 let next_key = match next_key {
                     Ok(v) => v,
@@ -1317,10 +1333,12 @@ async fn parahead<TPlat: PlatformRef>(
             runtime_host::RuntimeHostVm::ClosestDescendantMerkleValue(mv) => {
                 let merkle_value = {
                     let child_trie = mv.child_trie();
-                    runtime_call_lock.closest_descendant_merkle_value(
-                        child_trie.as_ref().map(|c| c.as_ref()),
-                        &mv.key().collect::<Vec<_>>(),
-                    )
+                    runtime_call_lock
+                        .closest_descendant_merkle_value(
+                            child_trie.as_ref().map(|c| c.as_ref()),
+                            &mv.key().collect::<Vec<_>>(),
+                        )
+                        .await
                 };
                 let merkle_value = match merkle_value {
                     Ok(v) => v,
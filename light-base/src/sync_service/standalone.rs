@@ -93,6 +93,7 @@ pub(super) async fn start_standalone_chain<TPlat: PlatformRef>(
                 NonZeroU32::new(5000).unwrap()
             },
             full_mode: false,
+            full_mode_warp_sync: false,
             code_trie_node_hint: runtime_code_hint.map(|hint| all::ConfigCodeTrieNodeHint {
                 merkle_value: hint.merkle_value,
                 storage_value: hint.storage_value,
@@ -108,6 +109,7 @@ pub(super) async fn start_standalone_chain<TPlat: PlatformRef>(
         ))
         .fuse(),
         all_notifications: Vec::<async_channel::Sender<Notification>>::new(),
+        beefy_justifications_notifications: Vec::new(),
         log_target,
         network_service,
         network_chain_id,
@@ -292,6 +294,7 @@ pub(super) async fn start_standalone_chain<TPlat: PlatformRef>(
                 // Inject the result of the request into the sync state machine.
                 match result {
                     RequestOutcome::Block(Ok(v)) => {
+                        task.dispatch_beefy_justifications(&v);
                         task.sync
                             .blocks_request_response(
                                 request_id,
@@ -437,6 +440,9 @@ struct Task<TPlat: PlatformRef> {
     /// All event subscribers that are interested in events about the chain.
     all_notifications: Vec<async_channel::Sender<Notification>>,
 
+    /// All subscribers that are interested in the BEEFY justifications found in blocks.
+    beefy_justifications_notifications: Vec<async_channel::Sender<super::BeefyJustification>>,
+
     /// Contains a `Delay` after which we print a warning about GrandPa warp sync taking a long
     /// time. Set to `Pending` after the warp sync has finished, so that future remains pending
     /// forever.
@@ -758,6 +764,9 @@ impl<TPlat: PlatformRef> Task<TPlat> {
                     .proof_sender()
                     .map(|(_, (peer_id, _))| Cow::Owned(peer_id.to_string())) // TODO: unnecessary cloning most of the time
                     .unwrap_or(Cow::Borrowed("<disconnected>"));
+                let sender = verify
+                    .proof_sender()
+                    .map(|(_, (peer_id, _))| peer_id.clone());
 
                 let (sync, result) = verify.perform({
                     let mut seed = [0; 32];
@@ -777,7 +786,16 @@ impl<TPlat: PlatformRef> Task<TPlat> {
                         );
                     }
                     Err(err) => {
-                        // TODO: should disconnect peer
+                        if let Some(sender) = sender {
+                            self.network_service
+                                .report_peer(
+                                    self.network_chain_id,
+                                    sender,
+                                    network_service::ReputationChange::InvalidJustification,
+                                )
+                                .await;
+                        }
+
                         let maybe_forced_change =
                             matches!(err, all::VerifyFragmentError::JustificationVerify(_));
                         log::warn!(
@@ -1082,6 +1100,15 @@ impl<TPlat: PlatformRef> Task<TPlat> {
                 });
             }
 
+            ToBackground::SubscribeBeefyJustifications {
+                send_back,
+                buffer_size,
+            } => {
+                let (tx, rx) = async_channel::bounded(buffer_size.max(1));
+                self.beefy_justifications_notifications.push(tx);
+                let _ = send_back.send(rx);
+            }
+
             ToBackground::PeersAssumedKnowBlock {
                 send_back,
                 block_number,
@@ -1315,4 +1342,49 @@ impl<TPlat: PlatformRef> Task<TPlat> {
             self.all_notifications.push(subscription);
         }
     }
+
+    /// Sends the BEEFY justifications found in the given response to a blocks request to all
+    /// the BEEFY justifications subscribers.
+    fn dispatch_beefy_justifications(&mut self, blocks: &[codec::BlockData]) {
+        if self.beefy_justifications_notifications.is_empty() {
+            return;
+        }
+
+        for block in blocks {
+            let (Some(scale_encoded_header), Some(justifications)) =
+                (&block.header, &block.justifications)
+            else {
+                continue;
+            };
+
+            let Ok(decoded_header) =
+                header::decode(scale_encoded_header, self.sync.block_number_bytes())
+            else {
+                continue;
+            };
+
+            for justification in justifications {
+                if justification.engine_id != *b"BEEF" {
+                    continue;
+                }
+
+                let notification = super::BeefyJustification {
+                    block_number: decoded_header.number,
+                    block_hash: decoded_header.hash(self.sync.block_number_bytes()),
+                    scale_encoded_justification: justification.justification.clone(),
+                };
+
+                // Elements are removed one by one and inserted back if the channel is still
+                // open.
+                for index in (0..self.beefy_justifications_notifications.len()).rev() {
+                    let subscription = self.beefy_justifications_notifications.swap_remove(index);
+                    if subscription.try_send(notification.clone()).is_err() {
+                        continue;
+                    }
+
+                    self.beefy_justifications_notifications.push(subscription);
+                }
+            }
+        }
+    }
 }
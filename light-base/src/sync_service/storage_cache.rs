@@ -0,0 +1,108 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Cache of storage entries that have been verified in the past.
+//!
+//! Each entry of the cache contains a storage value alongside with the Merkle value of the trie
+//! node of its key and the key of the closest ancestor of this trie node. If, in a more recent
+//! block, the trie node of the key has the same Merkle value and the same closest ancestor, then
+//! the storage value is guaranteed to be the same. Proving this only requires the parent of the
+//! trie node, which is typically much smaller than the storage value itself.
+//!
+//! Entries are not tied to any specific block, and the cache doesn't by itself guarantee that
+//! its content is still up-to-date. It is the responsibility of the user of the cache to verify
+//! that the Merkle value is unchanged before using a storage value.
+
+use alloc::vec::Vec;
+use smoldot::trie::Nibble;
+
+/// See [the module-level documentation](..).
+pub(super) struct StorageCache {
+    /// List of entries, indexed by storage key.
+    entries: lru::LruCache<Vec<u8>, Entry, fnv::FnvBuildHasher>,
+
+    /// Sum of the sizes of all the keys and entries in [`StorageCache::entries`].
+    total_size: usize,
+
+    /// Maximum value of [`StorageCache::total_size`].
+    max_size: usize,
+}
+
+/// Entry in a [`StorageCache`].
+#[derive(Debug, Clone)]
+pub(super) struct Entry {
+    /// Storage value of the key.
+    pub storage_value: Vec<u8>,
+    /// Merkle value of the trie node of the key.
+    pub merkle_value: Vec<u8>,
+    /// Closest ancestor of the trie node of the key, excluding the key itself.
+    pub closest_ancestor_excluding: Vec<Nibble>,
+}
+
+impl StorageCache {
+    /// Creates a new empty cache whose total size will never exceed `max_size` bytes.
+    pub fn new(max_size: usize) -> Self {
+        StorageCache {
+            entries: lru::LruCache::unbounded_with_hasher(Default::default()),
+            total_size: 0,
+            max_size,
+        }
+    }
+
+    /// Returns the entry corresponding to the given key, if any, and marks it as recently used.
+    pub fn get(&mut self, key: &[u8]) -> Option<&Entry> {
+        self.entries.get(key)
+    }
+
+    /// Inserts an entry in the cache, replacing the existing one if any. Least recently used
+    /// entries are removed if the cache exceeds its maximum size.
+    ///
+    /// The entry is ignored if it is bigger than the maximum size of the cache, or if `key` is
+    /// empty, as the empty key can't have any ancestor.
+    pub fn insert(&mut self, key: Vec<u8>, entry: Entry) {
+        let size = entry_size(&key, &entry);
+        if size > self.max_size || key.is_empty() {
+            return;
+        }
+
+        if let Some(previous) = self.entries.pop(&key) {
+            self.total_size -= entry_size(&key, &previous);
+        }
+
+        while self.total_size + size > self.max_size {
+            let (removed_key, removed) = self.entries.pop_lru().unwrap();
+            self.total_size -= entry_size(&removed_key, &removed);
+        }
+
+        self.total_size += size;
+        self.entries.put(key, entry);
+    }
+
+    /// Returns the list of entries in the cache, from the most recently used to the least
+    /// recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &Entry)> {
+        self.entries.iter().map(|(key, entry)| (&key[..], entry))
+    }
+}
+
+/// Returns the size that an entry occupies in a [`StorageCache`].
+fn entry_size(key: &[u8], entry: &Entry) -> usize {
+    key.len()
+        + entry.storage_value.len()
+        + entry.merkle_value.len()
+        + entry.closest_ancestor_excluding.len()
+}
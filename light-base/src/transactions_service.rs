@@ -1302,7 +1302,7 @@ async fn validate_transaction<TPlat: PlatformRef>(
     );
 
     let block_hash = *runtime_lock.block_hash();
-    let (runtime_call_lock, runtime) = runtime_lock
+    let (mut runtime_call_lock, runtime) = runtime_lock
         .start(
             validate::VALIDATION_FUNCTION_NAME,
             // TODO: don't hardcode v3 but determine parameters dynamically from the runtime
@@ -1361,6 +1361,7 @@ async fn validate_transaction<TPlat: PlatformRef>(
                     let child_trie = get.child_trie();
                     runtime_call_lock
                         .storage_entry(child_trie.as_ref().map(|c| c.as_ref()), get.key().as_ref())
+                        .await
                 };
                 let storage_value = match storage_value {
                     Ok(v) => v,
@@ -1377,10 +1378,12 @@ async fn validate_transaction<TPlat: PlatformRef>(
             validate::Query::ClosestDescendantMerkleValue(mv) => {
                 let merkle_value = {
                     let child_trie = mv.child_trie();
-                    runtime_call_lock.closest_descendant_merkle_value(
-                        child_trie.as_ref().map(|c| c.as_ref()),
-                        &mv.key().collect::<Vec<_>>(),
-                    )
+                    runtime_call_lock
+                        .closest_descendant_merkle_value(
+                            child_trie.as_ref().map(|c| c.as_ref()),
+                            &mv.key().collect::<Vec<_>>(),
+                        )
+                        .await
                 };
                 let merkle_value = match merkle_value {
                     Ok(v) => v,
@@ -1398,13 +1401,15 @@ async fn validate_transaction<TPlat: PlatformRef>(
             validate::Query::NextKey(nk) => {
                 let next_key = {
                     let child_trie = nk.child_trie();
-                    runtime_call_lock.next_key(
-                        child_trie.as_ref().map(|c| c.as_ref()),
-                        &nk.key().collect::<Vec<_>>(),
-                        nk.or_equal(),
-                        &nk.prefix().collect::<Vec<_>>(),
-                        nk.branch_nodes(),
-                    )
+                    runtime_call_lock
+                        .next_key(
+                            child_trie.as_ref().map(|c| c.as_ref()),
+                            &nk.key().collect::<Vec<_>>(),
+                            nk.or_equal(),
+                            &nk.prefix().collect::<Vec<_>>(),
+                            nk.branch_nodes(),
+                        )
+                        .await
                 };
                 let next_key = match next_key {
                     Ok(v) => v,
@@ -0,0 +1,316 @@
+#ifndef SMOLDOT_LIGHT_H
+#define SMOLDOT_LIGHT_H
+
+/* Warning, this file is autogenerated by cbindgen. Don't modify this manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * Light client and its chains. A pointer to this struct is passed over the FFI layer.
+ */
+typedef struct SmoldotClient SmoldotClient;
+
+/**
+ * Function that executes background tasks of the client. Only returns after the client has
+ * been destroyed.
+ *
+ * See [`SmoldotClientConfig::spawn_executor_thread`].
+ */
+typedef void (*SmoldotRunExecutorThread)(void *run_data);
+
+/**
+ * Function that must spawn a new thread and call `run(run_data)` on it.
+ *
+ * See [`SmoldotClientConfig::spawn_executor_thread`].
+ */
+typedef void (*SmoldotSpawnExecutorThread)(void *user_data,
+                                           SmoldotRunExecutorThread run,
+                                           void *run_data);
+
+/**
+ * Function called when the queue of JSON-RPC responses of the given chain is no longer empty.
+ *
+ * See [`SmoldotClientConfig::json_rpc_responses_non_empty`].
+ */
+typedef void (*SmoldotJsonRpcResponsesNonEmpty)(void *user_data, uint32_t chain_id);
+
+/**
+ * Configuration passed to [`smoldot_client_new`].
+ */
+typedef struct SmoldotClientConfig {
+  /**
+   * Function called by [`smoldot_client_new`] once per thread that the client wants to use
+   * in order to execute its background tasks. Can be used to create threads in a specific
+   * way, for example in order to attach them to a virtual machine.
+   *
+   * If `NULL`, the client spawns these threads itself.
+   */
+  SmoldotSpawnExecutorThread spawn_executor_thread;
+  /**
+   * Opaque pointer passed as first parameter to [`SmoldotClientConfig::spawn_executor_thread`].
+   */
+  void *spawn_executor_thread_user_data;
+  /**
+   * Function called when the queue of JSON-RPC responses of a chain is no longer empty.
+   *
+   * This function is only ever called after [`smoldot_json_rpc_responses_peek`] has returned
+   * a `len` of 0.
+   *
+   * This function might be called spuriously, however this behavior must not be relied upon.
+   *
+   * Can be `NULL`, in which case the user must poll the responses by calling
+   * [`smoldot_json_rpc_responses_peek`] regularly.
+   */
+  SmoldotJsonRpcResponsesNonEmpty json_rpc_responses_non_empty;
+  /**
+   * Opaque pointer passed as first parameter to
+   * [`SmoldotClientConfig::json_rpc_responses_non_empty`].
+   */
+  void *json_rpc_responses_non_empty_user_data;
+} SmoldotClientConfig;
+
+/**
+ * See [`smoldot_json_rpc_responses_peek`].
+ */
+typedef struct SmoldotJsonRpcResponseInfo {
+  /**
+   * Pointer in memory where the JSON-RPC response can be found. The response is *not*
+   * nul-terminated.
+   */
+  const char *ptr;
+  /**
+   * Length of the JSON-RPC response in bytes. If 0, indicates that the queue is empty.
+   */
+  size_t len;
+} SmoldotJsonRpcResponseInfo;
+
+/**
+ * Function called when the client emits a log entry.
+ *
+ * Each log entry is made of a log level (`1 = Error, 2 = Warn, 3 = Info, 4 = Debug,
+ * 5 = Trace`), a log target (e.g. "network"), and a log message.
+ *
+ * The log target and message are UTF-8 strings that are *not* nul-terminated, and whose
+ * length is passed alongside with the pointer. They are only valid for the duration of the
+ * call.
+ */
+typedef void (*SmoldotLogCallback)(void *user_data,
+                                   uint32_t level,
+                                   const char *target,
+                                   size_t target_len,
+                                   const char *message,
+                                   size_t message_len);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Registers a function that receives the logs emitted by all the clients.
+ *
+ * Log entries are passed to `callback` provided that their log level is inferior or equal to
+ * the value of `max_log_level` (`0 = Off, 1 = Error, 2 = Warn, 3 = Info, 4 = Debug,
+ * 5 = Trace`).
+ *
+ * Only the first call to this function has an effect. Subsequent calls are ignored.
+ *
+ * # Safety
+ *
+ * `user_data` must be usable from any thread.
+ */
+void smoldot_init_logger(uint32_t max_log_level, SmoldotLogCallback callback, void *user_data);
+
+/**
+ * Creates a new client. Must later be destroyed with [`smoldot_client_destroy`].
+ *
+ * # Safety
+ *
+ * The user data pointers found in `config` must be usable from any thread.
+ */
+SmoldotClient *smoldot_client_new(SmoldotClientConfig config);
+
+/**
+ * Destroys a client previously created with [`smoldot_client_new`], and all of its chains.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`], and must not be used anymore
+ * after this function returns.
+ */
+void smoldot_client_destroy(SmoldotClient *client);
+
+/**
+ * Adds a chain to the client. The client will try to stay connected and synchronize this chain.
+ *
+ * The chain specification and database content must be UTF-8 strings, and their length must be
+ * passed alongside with their pointer. They don't need to be nul-terminated.
+ *
+ * > **Note**: The database content is an opaque string that can be obtained by calling
+ * >           the `chainHead_unstable_finalizedDatabase` JSON-RPC function.
+ *
+ * The list of potential relay chains is a list of chain ids. If the chain specification refer
+ * to a parachain, these chain ids are the ones that will be looked up to find the corresponding
+ * relay chain.
+ *
+ * `json_rpc_max_pending_requests` indicates the size of the queue of JSON-RPC requests that
+ * haven't been answered yet.
+ * If `json_rpc_max_pending_requests` is 0, then no JSON-RPC service will be started and it is
+ * forbidden to send JSON-RPC requests targeting this chain. This can be used to save up
+ * resources.
+ * If `json_rpc_max_pending_requests` is 0, then the value of `json_rpc_max_subscriptions` is
+ * ignored.
+ *
+ * If an error happens during the creation of the chain, a chain id will be allocated
+ * nonetheless, and must later be de-allocated by calling [`smoldot_remove_chain`]. This
+ * allocated chain, however, will be in an erroneous state. Use [`smoldot_chain_is_ok`] to
+ * determine whether this function was successful. If not, use [`smoldot_chain_error_len`] and
+ * [`smoldot_chain_error_ptr`] to obtain the error message.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`]. The pointers must point to
+ * buffers of at least the given length.
+ */
+uint32_t smoldot_add_chain(const SmoldotClient *client,
+                           const char *chain_spec,
+                           size_t chain_spec_len,
+                           const char *database_content,
+                           size_t database_content_len,
+                           uint32_t json_rpc_max_pending_requests,
+                           uint32_t json_rpc_max_subscriptions,
+                           const uint32_t *potential_relay_chains,
+                           size_t potential_relay_chains_len);
+
+/**
+ * Removes a chain previously added using [`smoldot_add_chain`]. Instantly unsubscribes all the
+ * JSON-RPC subscriptions and cancels all in-progress requests corresponding to that chain.
+ *
+ * If the removed chain was an erroneous chain, calling this function will invalidate the pointer
+ * returned by [`smoldot_chain_error_ptr`].
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+void smoldot_remove_chain(const SmoldotClient *client, uint32_t chain_id);
+
+/**
+ * Returns `1` if creating this chain was successful. Otherwise, returns `0`.
+ *
+ * If `0` is returned, use [`smoldot_chain_error_len`] and [`smoldot_chain_error_ptr`] to obtain
+ * an error message.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+uint32_t smoldot_chain_is_ok(const SmoldotClient *client, uint32_t chain_id);
+
+/**
+ * Returns the length of the error message stored for this chain.
+ *
+ * Must only be called on an erroneous chain. Use [`smoldot_chain_is_ok`] to determine whether
+ * a chain is in an erroneous state. Returns `0` if the chain isn't erroneous.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+size_t smoldot_chain_error_len(const SmoldotClient *client, uint32_t chain_id);
+
+/**
+ * Returns a pointer to the error message stored for this chain. The error message is a UTF-8
+ * string that is *not* nul-terminated, and whose length can be determined by calling
+ * [`smoldot_chain_error_len`].
+ *
+ * Must only be called on an erroneous chain. Use [`smoldot_chain_is_ok`] to determine whether
+ * a chain is in an erroneous state. Returns `NULL` if the chain isn't erroneous.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+const char *smoldot_chain_error_ptr(const SmoldotClient *client, uint32_t chain_id);
+
+/**
+ * Emit a JSON-RPC request or notification towards the given chain previously added using
+ * [`smoldot_add_chain`].
+ *
+ * A UTF-8 JSON-RPC request or notification must be passed as parameter, alongside with its
+ * length. It doesn't need to be nul-terminated. The format of the JSON-RPC requests and
+ * notifications is described in
+ * [the standard JSON-RPC 2.0 specification](https://www.jsonrpc.org/specification).
+ *
+ * If the buffer isn't a valid JSON-RPC request, then an error JSON-RPC response with an `id`
+ * equal to `null` is generated, in accordance with the JSON-RPC 2.0 specification.
+ *
+ * Responses and notifications are notified using
+ * [`SmoldotClientConfig::json_rpc_responses_non_empty`], and can be read with
+ * [`smoldot_json_rpc_responses_peek`].
+ *
+ * It is forbidden to call this function on an erroneous chain or a chain that was created with
+ * `json_rpc_max_pending_requests` equal to 0.
+ *
+ * This function returns:
+ * - 0 on success.
+ * - 1 if the chain has too many pending JSON-RPC requests and refuses to queue another one.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`]. `text` must point to a buffer of
+ * at least `text_len` bytes.
+ */
+uint32_t smoldot_json_rpc_send(const SmoldotClient *client,
+                               const char *text,
+                               size_t text_len,
+                               uint32_t chain_id);
+
+/**
+ * Obtains information about the first response in the queue of JSON-RPC responses.
+ *
+ * The pointer found in the returned [`SmoldotJsonRpcResponseInfo`] remains valid until
+ * [`smoldot_json_rpc_responses_pop`] or [`smoldot_remove_chain`] is called with the same
+ * `chain_id`.
+ *
+ * If `len` is equal to 0, this indicates that the queue of JSON-RPC responses is empty.
+ * When a `len` of 0 is returned, [`SmoldotClientConfig::json_rpc_responses_non_empty`] will
+ * later be called to indicate that it is no longer empty.
+ *
+ * After having read the response or notification, use [`smoldot_json_rpc_responses_pop`] to
+ * remove it from the queue. You can then call [`smoldot_json_rpc_responses_peek`] again to read
+ * the next response.
+ *
+ * It is forbidden to call this function on an erroneous chain or a chain that was created with
+ * `json_rpc_max_pending_requests` equal to 0.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+SmoldotJsonRpcResponseInfo smoldot_json_rpc_responses_peek(const SmoldotClient *client,
+                                                           uint32_t chain_id);
+
+/**
+ * Removes the first response from the queue of JSON-RPC responses. This is the response whose
+ * information can be retrieved using [`smoldot_json_rpc_responses_peek`].
+ *
+ * Calling this function invalidates the pointer previously returned by a call to
+ * [`smoldot_json_rpc_responses_peek`] with the same `chain_id`.
+ *
+ * It is forbidden to call this function on an erroneous chain or a chain that was created with
+ * `json_rpc_max_pending_requests` equal to 0.
+ *
+ * # Safety
+ *
+ * `client` must have been returned by [`smoldot_client_new`].
+ */
+void smoldot_json_rpc_responses_pop(const SmoldotClient *client, uint32_t chain_id);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* SMOLDOT_LIGHT_H */
@@ -0,0 +1,369 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Functions and types exported through the C ABI.
+//!
+//! The `include/smoldot_light.h` header file is generated from this module using `cbindgen`.
+//! It must be regenerated after any modification, by running the following command from the
+//! directory of this crate:
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/smoldot_light.h
+//! ```
+//!
+//! These functions mirror the ones that the WebAssembly light client exports to JavaScript.
+//! Contrary to the WebAssembly light client, the background tasks are executed by threads,
+//! which means that there is no equivalent to its `advance_execution` function.
+//!
+//! # Threads
+//!
+//! All the functions can be called from any thread, and are thread safe. The callbacks passed
+//! to the library are called from arbitrary threads.
+//!
+//! As a rule, none of the callbacks is allowed to call a function of this library. This avoids
+//! deadlocks and tricky re-entrency situations.
+//!
+//! # Panics
+//!
+//! Calling a function in a way that this documentation forbids leads to a panic, which aborts
+//! the process.
+
+use crate::SmoldotClient;
+use core::{ffi, slice};
+
+/// Function called when the client emits a log entry.
+///
+/// Each log entry is made of a log level (`1 = Error, 2 = Warn, 3 = Info, 4 = Debug,
+/// 5 = Trace`), a log target (e.g. "network"), and a log message.
+///
+/// The log target and message are UTF-8 strings that are *not* nul-terminated, and whose
+/// length is passed alongside with the pointer. They are only valid for the duration of the
+/// call.
+pub type SmoldotLogCallback = unsafe extern "C" fn(
+    user_data: *mut ffi::c_void,
+    level: u32,
+    target: *const ffi::c_char,
+    target_len: usize,
+    message: *const ffi::c_char,
+    message_len: usize,
+);
+
+/// Function that executes background tasks of the client. Only returns after the client has
+/// been destroyed.
+///
+/// See [`SmoldotClientConfig::spawn_executor_thread`].
+pub type SmoldotRunExecutorThread = unsafe extern "C" fn(run_data: *mut ffi::c_void);
+
+/// Function that must spawn a new thread and call `run(run_data)` on it.
+///
+/// See [`SmoldotClientConfig::spawn_executor_thread`].
+pub type SmoldotSpawnExecutorThread = unsafe extern "C" fn(
+    user_data: *mut ffi::c_void,
+    run: SmoldotRunExecutorThread,
+    run_data: *mut ffi::c_void,
+);
+
+/// Function called when the queue of JSON-RPC responses of the given chain is no longer empty.
+///
+/// See [`SmoldotClientConfig::json_rpc_responses_non_empty`].
+pub type SmoldotJsonRpcResponsesNonEmpty =
+    unsafe extern "C" fn(user_data: *mut ffi::c_void, chain_id: u32);
+
+/// Configuration passed to [`smoldot_client_new`].
+#[repr(C)]
+pub struct SmoldotClientConfig {
+    /// Function called by [`smoldot_client_new`] once per thread that the client wants to use
+    /// in order to execute its background tasks. Can be used to create threads in a specific
+    /// way, for example in order to attach them to a virtual machine.
+    ///
+    /// If `NULL`, the client spawns these threads itself.
+    pub spawn_executor_thread: Option<SmoldotSpawnExecutorThread>,
+    /// Opaque pointer passed as first parameter to [`SmoldotClientConfig::spawn_executor_thread`].
+    pub spawn_executor_thread_user_data: *mut ffi::c_void,
+    /// Function called when the queue of JSON-RPC responses of a chain is no longer empty.
+    ///
+    /// This function is only ever called after [`smoldot_json_rpc_responses_peek`] has returned
+    /// a `len` of 0.
+    ///
+    /// This function might be called spuriously, however this behavior must not be relied upon.
+    ///
+    /// Can be `NULL`, in which case the user must poll the responses by calling
+    /// [`smoldot_json_rpc_responses_peek`] regularly.
+    pub json_rpc_responses_non_empty: Option<SmoldotJsonRpcResponsesNonEmpty>,
+    /// Opaque pointer passed as first parameter to
+    /// [`SmoldotClientConfig::json_rpc_responses_non_empty`].
+    pub json_rpc_responses_non_empty_user_data: *mut ffi::c_void,
+}
+
+/// See [`smoldot_json_rpc_responses_peek`].
+#[repr(C)]
+pub struct SmoldotJsonRpcResponseInfo {
+    /// Pointer in memory where the JSON-RPC response can be found. The response is *not*
+    /// nul-terminated.
+    pub ptr: *const ffi::c_char,
+    /// Length of the JSON-RPC response in bytes. If 0, indicates that the queue is empty.
+    pub len: usize,
+}
+
+/// Registers a function that receives the logs emitted by all the clients.
+///
+/// Log entries are passed to `callback` provided that their log level is inferior or equal to
+/// the value of `max_log_level` (`0 = Off, 1 = Error, 2 = Warn, 3 = Info, 4 = Debug,
+/// 5 = Trace`).
+///
+/// Only the first call to this function has an effect. Subsequent calls are ignored.
+///
+/// # Safety
+///
+/// `user_data` must be usable from any thread.
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_init_logger(
+    max_log_level: u32,
+    callback: SmoldotLogCallback,
+    user_data: *mut ffi::c_void,
+) {
+    super::init_logger(max_log_level, callback, user_data)
+}
+
+/// Creates a new client. Must later be destroyed with [`smoldot_client_destroy`].
+///
+/// # Safety
+///
+/// The user data pointers found in `config` must be usable from any thread.
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_client_new(config: SmoldotClientConfig) -> *mut SmoldotClient {
+    Box::into_raw(super::client_new(config))
+}
+
+/// Destroys a client previously created with [`smoldot_client_new`], and all of its chains.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`], and must not be used anymore
+/// after this function returns.
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_client_destroy(client: *mut SmoldotClient) {
+    super::client_destroy(Box::from_raw(client))
+}
+
+/// Adds a chain to the client. The client will try to stay connected and synchronize this chain.
+///
+/// The chain specification and database content must be UTF-8 strings, and their length must be
+/// passed alongside with their pointer. They don't need to be nul-terminated.
+///
+/// > **Note**: The database content is an opaque string that can be obtained by calling
+/// >           the `chainHead_unstable_finalizedDatabase` JSON-RPC function.
+///
+/// The list of potential relay chains is a list of chain ids. If the chain specification refer
+/// to a parachain, these chain ids are the ones that will be looked up to find the corresponding
+/// relay chain.
+///
+/// `json_rpc_max_pending_requests` indicates the size of the queue of JSON-RPC requests that
+/// haven't been answered yet.
+/// If `json_rpc_max_pending_requests` is 0, then no JSON-RPC service will be started and it is
+/// forbidden to send JSON-RPC requests targeting this chain. This can be used to save up
+/// resources.
+/// If `json_rpc_max_pending_requests` is 0, then the value of `json_rpc_max_subscriptions` is
+/// ignored.
+///
+/// If an error happens during the creation of the chain, a chain id will be allocated
+/// nonetheless, and must later be de-allocated by calling [`smoldot_remove_chain`]. This
+/// allocated chain, however, will be in an erroneous state. Use [`smoldot_chain_is_ok`] to
+/// determine whether this function was successful. If not, use [`smoldot_chain_error_len`] and
+/// [`smoldot_chain_error_ptr`] to obtain the error message.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`]. The pointers must point to
+/// buffers of at least the given length.
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_add_chain(
+    client: *const SmoldotClient,
+    chain_spec: *const ffi::c_char,
+    chain_spec_len: usize,
+    database_content: *const ffi::c_char,
+    database_content_len: usize,
+    json_rpc_max_pending_requests: u32,
+    json_rpc_max_subscriptions: u32,
+    potential_relay_chains: *const u32,
+    potential_relay_chains_len: usize,
+) -> u32 {
+    super::add_chain(
+        &*client,
+        buffer(chain_spec as *const u8, chain_spec_len),
+        buffer(database_content as *const u8, database_content_len),
+        json_rpc_max_pending_requests,
+        json_rpc_max_subscriptions,
+        buffer(potential_relay_chains, potential_relay_chains_len),
+    )
+}
+
+/// Removes a chain previously added using [`smoldot_add_chain`]. Instantly unsubscribes all the
+/// JSON-RPC subscriptions and cancels all in-progress requests corresponding to that chain.
+///
+/// If the removed chain was an erroneous chain, calling this function will invalidate the pointer
+/// returned by [`smoldot_chain_error_ptr`].
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_remove_chain(client: *const SmoldotClient, chain_id: u32) {
+    super::remove_chain(&*client, chain_id);
+}
+
+/// Returns `1` if creating this chain was successful. Otherwise, returns `0`.
+///
+/// If `0` is returned, use [`smoldot_chain_error_len`] and [`smoldot_chain_error_ptr`] to obtain
+/// an error message.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_chain_is_ok(client: *const SmoldotClient, chain_id: u32) -> u32 {
+    super::chain_is_ok(&*client, chain_id)
+}
+
+/// Returns the length of the error message stored for this chain.
+///
+/// Must only be called on an erroneous chain. Use [`smoldot_chain_is_ok`] to determine whether
+/// a chain is in an erroneous state. Returns `0` if the chain isn't erroneous.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_chain_error_len(
+    client: *const SmoldotClient,
+    chain_id: u32,
+) -> usize {
+    super::chain_error_len(&*client, chain_id)
+}
+
+/// Returns a pointer to the error message stored for this chain. The error message is a UTF-8
+/// string that is *not* nul-terminated, and whose length can be determined by calling
+/// [`smoldot_chain_error_len`].
+///
+/// Must only be called on an erroneous chain. Use [`smoldot_chain_is_ok`] to determine whether
+/// a chain is in an erroneous state. Returns `NULL` if the chain isn't erroneous.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_chain_error_ptr(
+    client: *const SmoldotClient,
+    chain_id: u32,
+) -> *const ffi::c_char {
+    super::chain_error_ptr(&*client, chain_id)
+}
+
+/// Emit a JSON-RPC request or notification towards the given chain previously added using
+/// [`smoldot_add_chain`].
+///
+/// A UTF-8 JSON-RPC request or notification must be passed as parameter, alongside with its
+/// length. It doesn't need to be nul-terminated. The format of the JSON-RPC requests and
+/// notifications is described in
+/// [the standard JSON-RPC 2.0 specification](https://www.jsonrpc.org/specification).
+///
+/// If the buffer isn't a valid JSON-RPC request, then an error JSON-RPC response with an `id`
+/// equal to `null` is generated, in accordance with the JSON-RPC 2.0 specification.
+///
+/// Responses and notifications are notified using
+/// [`SmoldotClientConfig::json_rpc_responses_non_empty`], and can be read with
+/// [`smoldot_json_rpc_responses_peek`].
+///
+/// It is forbidden to call this function on an erroneous chain or a chain that was created with
+/// `json_rpc_max_pending_requests` equal to 0.
+///
+/// This function returns:
+/// - 0 on success.
+/// - 1 if the chain has too many pending JSON-RPC requests and refuses to queue another one.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`]. `text` must point to a buffer of
+/// at least `text_len` bytes.
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_json_rpc_send(
+    client: *const SmoldotClient,
+    text: *const ffi::c_char,
+    text_len: usize,
+    chain_id: u32,
+) -> u32 {
+    super::json_rpc_send(&*client, buffer(text as *const u8, text_len), chain_id)
+}
+
+/// Obtains information about the first response in the queue of JSON-RPC responses.
+///
+/// The pointer found in the returned [`SmoldotJsonRpcResponseInfo`] remains valid until
+/// [`smoldot_json_rpc_responses_pop`] or [`smoldot_remove_chain`] is called with the same
+/// `chain_id`.
+///
+/// If `len` is equal to 0, this indicates that the queue of JSON-RPC responses is empty.
+/// When a `len` of 0 is returned, [`SmoldotClientConfig::json_rpc_responses_non_empty`] will
+/// later be called to indicate that it is no longer empty.
+///
+/// After having read the response or notification, use [`smoldot_json_rpc_responses_pop`] to
+/// remove it from the queue. You can then call [`smoldot_json_rpc_responses_peek`] again to read
+/// the next response.
+///
+/// It is forbidden to call this function on an erroneous chain or a chain that was created with
+/// `json_rpc_max_pending_requests` equal to 0.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_json_rpc_responses_peek(
+    client: *const SmoldotClient,
+    chain_id: u32,
+) -> SmoldotJsonRpcResponseInfo {
+    super::json_rpc_responses_peek(&*client, chain_id)
+}
+
+/// Removes the first response from the queue of JSON-RPC responses. This is the response whose
+/// information can be retrieved using [`smoldot_json_rpc_responses_peek`].
+///
+/// Calling this function invalidates the pointer previously returned by a call to
+/// [`smoldot_json_rpc_responses_peek`] with the same `chain_id`.
+///
+/// It is forbidden to call this function on an erroneous chain or a chain that was created with
+/// `json_rpc_max_pending_requests` equal to 0.
+///
+/// # Safety
+///
+/// `client` must have been returned by [`smoldot_client_new`].
+#[no_mangle]
+pub unsafe extern "C" fn smoldot_json_rpc_responses_pop(
+    client: *const SmoldotClient,
+    chain_id: u32,
+) {
+    super::json_rpc_responses_pop(&*client, chain_id);
+}
+
+/// Builds a slice from a pointer and a length passed through the FFI layer. The pointer is
+/// allowed to be `NULL` if the length is 0.
+unsafe fn buffer<'a, T>(ptr: *const T, len: usize) -> &'a [T] {
+    if len == 0 {
+        &[]
+    } else {
+        debug_assert!(!ptr.is_null());
+        slice::from_raw_parts(ptr, len)
+    }
+}
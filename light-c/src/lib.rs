@@ -0,0 +1,449 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Contains a light client implementation usable through a C ABI.
+//!
+//! See the [`bindings`] module for the list of functions that are exported.
+
+#![deny(rustdoc::broken_intra_doc_links)]
+#![deny(unused_crate_dependencies)]
+
+use core::{ffi, num::NonZeroU32, pin::Pin, str};
+use futures_util::{stream, Stream as _, StreamExt as _};
+use smoldot_light::{platform::DefaultPlatform, HandleRpcError};
+use std::{
+    sync::{Arc, Mutex},
+    task,
+};
+
+pub mod bindings;
+
+/// Light client and its chains. A pointer to this struct is passed over the FFI layer.
+pub struct SmoldotClient {
+    inner: Mutex<Client>,
+
+    /// Function called when the queue of JSON-RPC responses of a chain is no longer empty.
+    json_rpc_responses_non_empty: Arc<JsonRpcResponsesNonEmptyCallback>,
+}
+
+struct Client {
+    smoldot: smoldot_light::Client<Arc<DefaultPlatform>>,
+
+    /// List of all chains that have been added by the user.
+    chains: slab::Slab<Chain>,
+}
+
+enum Chain {
+    Healthy {
+        smoldot_chain_id: smoldot_light::ChainId,
+
+        /// JSON-RPC responses that is at the front of the queue according to the API. If `Some`,
+        /// a pointer to the string has been returned by [`json_rpc_responses_peek`].
+        json_rpc_response: Option<String>,
+
+        /// Receiver for JSON-RPC responses sent by the client. `None` if JSON-RPC requests are
+        /// disabled on this chain.
+        /// While this could in principle be a [`smoldot_light::JsonRpcResponses`], we wrap it
+        /// within a [`futures_util::Stream`] in order to guarantee that the `waker` that we
+        /// register doesn't get cleaned up.
+        json_rpc_responses_rx: Option<stream::BoxStream<'static, String>>,
+    },
+    Erroneous {
+        error: String,
+    },
+}
+
+/// Opaque pointer provided by the user alongside with a callback, and passed back when the
+/// callback is called.
+#[derive(Copy, Clone)]
+struct UserData(*mut ffi::c_void);
+
+// The user is responsible for making sure that the user data can be used from any thread, as
+// documented in the bindings.
+unsafe impl Send for UserData {}
+unsafe impl Sync for UserData {}
+
+/// See [`bindings::SmoldotClientConfig::json_rpc_responses_non_empty`].
+struct JsonRpcResponsesNonEmptyCallback {
+    callback: Option<bindings::SmoldotJsonRpcResponsesNonEmpty>,
+    user_data: UserData,
+}
+
+fn client_new(config: bindings::SmoldotClientConfig) -> Box<SmoldotClient> {
+    let client_name = env!("CARGO_PKG_NAME").to_owned();
+    let client_version = env!("CARGO_PKG_VERSION").to_owned();
+
+    let platform = match config.spawn_executor_thread {
+        Some(spawn_executor_thread) => {
+            let user_data = UserData(config.spawn_executor_thread_user_data);
+            DefaultPlatform::with_thread_spawner(client_name, client_version, move |run| {
+                let run_data = Box::into_raw(Box::new(run)) as *mut ffi::c_void;
+                unsafe { spawn_executor_thread(user_data.0, run_executor_thread, run_data) }
+            })
+        }
+        None => DefaultPlatform::new(client_name, client_version),
+    };
+
+    Box::new(SmoldotClient {
+        inner: Mutex::new(Client {
+            smoldot: smoldot_light::Client::new(platform),
+            chains: slab::Slab::new(),
+        }),
+        json_rpc_responses_non_empty: Arc::new(JsonRpcResponsesNonEmptyCallback {
+            callback: config.json_rpc_responses_non_empty,
+            user_data: UserData(config.json_rpc_responses_non_empty_user_data),
+        }),
+    })
+}
+
+/// Function passed to [`bindings::SmoldotClientConfig::spawn_executor_thread`].
+unsafe extern "C" fn run_executor_thread(run_data: *mut ffi::c_void) {
+    let run = Box::from_raw(run_data as *mut Box<dyn FnOnce() + Send>);
+    run()
+}
+
+fn client_destroy(client: Box<SmoldotClient>) {
+    let mut client_lock = client.inner.lock().unwrap();
+    for (_, chain) in client_lock.chains.iter_mut() {
+        if let Chain::Healthy {
+            json_rpc_responses_rx: Some(json_rpc_responses_rx),
+            ..
+        } = chain
+        {
+            erase_waker(json_rpc_responses_rx);
+        }
+    }
+}
+
+fn add_chain(
+    client: &SmoldotClient,
+    chain_spec: &[u8],
+    database_content: &[u8],
+    json_rpc_max_pending_requests: u32,
+    json_rpc_max_subscriptions: u32,
+    potential_relay_chains: &[u32],
+) -> u32 {
+    let mut client_lock = client.inner.lock().unwrap();
+
+    // Retrieve the potential relay chains parameter passed through the FFI layer.
+    let potential_relay_chains: Vec<_> = potential_relay_chains
+        .iter()
+        .filter_map(|c| {
+            if let Some(Chain::Healthy {
+                smoldot_chain_id, ..
+            }) = client_lock.chains.get(usize::try_from(*c).ok()?)
+            {
+                Some(*smoldot_chain_id)
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    // Insert the chain in the client.
+    let smoldot_light::AddChainSuccess {
+        chain_id: smoldot_chain_id,
+        json_rpc_responses,
+    } = match client_lock
+        .smoldot
+        .add_chain(smoldot_light::AddChainConfig {
+            user_data: (),
+            specification: str::from_utf8(chain_spec)
+                .unwrap_or_else(|_| panic!("non-utf8 chain spec")),
+            database_content: str::from_utf8(database_content)
+                .unwrap_or_else(|_| panic!("non-utf8 database content")),
+            json_rpc: if let Some(json_rpc_max_pending_requests) =
+                NonZeroU32::new(json_rpc_max_pending_requests)
+            {
+                smoldot_light::AddChainConfigJsonRpc::Enabled {
+                    max_pending_requests: json_rpc_max_pending_requests,
+                    max_subscriptions: json_rpc_max_subscriptions,
+                }
+            } else {
+                smoldot_light::AddChainConfigJsonRpc::Disabled
+            },
+            potential_relay_chains: potential_relay_chains.into_iter(),
+        }) {
+        Ok(c) => c,
+        Err(error) => {
+            let chain_id = client_lock.chains.insert(Chain::Erroneous {
+                error: error.to_string(),
+            });
+
+            return u32::try_from(chain_id).unwrap();
+        }
+    };
+
+    // We wrap the JSON-RPC responses stream into a proper stream in order to be able to guarantee
+    // that `poll_next()` always operates on the same future.
+    let json_rpc_responses_rx = json_rpc_responses.map(|json_rpc_responses| {
+        stream::unfold(json_rpc_responses, |mut json_rpc_responses| async {
+            // The stream ends when we remove the chain. Once the chain is removed, the user
+            // cannot poll the stream anymore. Therefore it is safe to unwrap the result here.
+            let msg = json_rpc_responses.next().await.unwrap();
+            Some((msg, json_rpc_responses))
+        })
+        .boxed()
+    });
+
+    let chain_id = client_lock.chains.insert(Chain::Healthy {
+        smoldot_chain_id,
+        json_rpc_response: None,
+        json_rpc_responses_rx,
+    });
+
+    u32::try_from(chain_id).unwrap()
+}
+
+fn remove_chain(client: &SmoldotClient, chain_id: u32) {
+    let mut client_lock = client.inner.lock().unwrap();
+
+    match client_lock
+        .chains
+        .remove(usize::try_from(chain_id).unwrap())
+    {
+        Chain::Healthy {
+            smoldot_chain_id,
+            json_rpc_responses_rx,
+            ..
+        } => {
+            if let Some(mut json_rpc_responses_rx) = json_rpc_responses_rx {
+                erase_waker(&mut json_rpc_responses_rx);
+            }
+
+            let () = client_lock.smoldot.remove_chain(smoldot_chain_id);
+        }
+        Chain::Erroneous { .. } => {}
+    }
+}
+
+/// We've polled the JSON-RPC receiver with a waker that calls the user's callback. Once the
+/// sender is destroyed, this waker will be called in order to inform of the destruction. We
+/// don't want that to happen. Therefore, we poll the receiver again with a dummy "no-op" waker
+/// for the sole purpose of erasing the previously-registered waker.
+fn erase_waker(json_rpc_responses_rx: &mut stream::BoxStream<'static, String>) {
+    let _ = Pin::new(json_rpc_responses_rx).poll_next(&mut task::Context::from_waker(
+        futures_util::task::noop_waker_ref(),
+    ));
+}
+
+fn chain_is_ok(client: &SmoldotClient, chain_id: u32) -> u32 {
+    let client_lock = client.inner.lock().unwrap();
+    if matches!(
+        client_lock
+            .chains
+            .get(usize::try_from(chain_id).unwrap())
+            .unwrap(),
+        Chain::Healthy { .. }
+    ) {
+        1
+    } else {
+        0
+    }
+}
+
+fn chain_error_len(client: &SmoldotClient, chain_id: u32) -> usize {
+    let client_lock = client.inner.lock().unwrap();
+    match client_lock
+        .chains
+        .get(usize::try_from(chain_id).unwrap())
+        .unwrap()
+    {
+        Chain::Healthy { .. } => 0,
+        Chain::Erroneous { error } => error.as_bytes().len(),
+    }
+}
+
+fn chain_error_ptr(client: &SmoldotClient, chain_id: u32) -> *const ffi::c_char {
+    let client_lock = client.inner.lock().unwrap();
+    match client_lock
+        .chains
+        .get(usize::try_from(chain_id).unwrap())
+        .unwrap()
+    {
+        Chain::Healthy { .. } => core::ptr::null(),
+        Chain::Erroneous { error } => error.as_bytes().as_ptr() as *const ffi::c_char,
+    }
+}
+
+fn json_rpc_send(client: &SmoldotClient, json_rpc_request: &[u8], chain_id: u32) -> u32 {
+    // As mentioned in the documentation, the bytes *must* be valid UTF-8.
+    let json_rpc_request: String = String::from_utf8(json_rpc_request.to_vec())
+        .unwrap_or_else(|_| panic!("non-UTF-8 JSON-RPC request"));
+
+    let mut client_lock = client.inner.lock().unwrap();
+    let client_chain_id = match client_lock
+        .chains
+        .get(usize::try_from(chain_id).unwrap())
+        .unwrap()
+    {
+        Chain::Healthy {
+            smoldot_chain_id, ..
+        } => *smoldot_chain_id,
+        Chain::Erroneous { .. } => panic!(),
+    };
+
+    match client_lock
+        .smoldot
+        .json_rpc_request(json_rpc_request, client_chain_id)
+    {
+        Ok(()) => 0,
+        Err(HandleRpcError::TooManyPendingRequests { .. }) => 1,
+    }
+}
+
+fn json_rpc_responses_peek(
+    client: &SmoldotClient,
+    chain_id: u32,
+) -> bindings::SmoldotJsonRpcResponseInfo {
+    let mut client_lock = client.inner.lock().unwrap();
+    match client_lock
+        .chains
+        .get_mut(usize::try_from(chain_id).unwrap())
+        .unwrap()
+    {
+        Chain::Healthy {
+            json_rpc_response,
+            json_rpc_responses_rx,
+            ..
+        } => {
+            if json_rpc_response.is_none() {
+                if let Some(json_rpc_responses_rx) = json_rpc_responses_rx.as_mut() {
+                    loop {
+                        match Pin::new(&mut *json_rpc_responses_rx).poll_next(
+                            &mut task::Context::from_waker(
+                                &Arc::new(JsonRpcResponsesNonEmptyWaker {
+                                    chain_id,
+                                    callback: client.json_rpc_responses_non_empty.clone(),
+                                })
+                                .into(),
+                            ),
+                        ) {
+                            task::Poll::Ready(Some(response)) if response.is_empty() => {
+                                // The API of `json_rpc_responses_peek` says that a length of 0
+                                // indicates that the queue is empty. For this reason, we skip
+                                // this response.
+                                // This is a pretty niche situation, but at least we handle it
+                                // properly.
+                            }
+                            task::Poll::Ready(Some(response)) => {
+                                debug_assert!(!response.is_empty());
+                                *json_rpc_response = Some(response);
+                                break;
+                            }
+                            task::Poll::Ready(None) => unreachable!(),
+                            task::Poll::Pending => break,
+                        }
+                    }
+                }
+            }
+
+            match &json_rpc_response {
+                Some(rp) => {
+                    debug_assert!(!rp.is_empty());
+                    bindings::SmoldotJsonRpcResponseInfo {
+                        ptr: rp.as_bytes().as_ptr() as *const ffi::c_char,
+                        len: rp.as_bytes().len(),
+                    }
+                }
+                None => bindings::SmoldotJsonRpcResponseInfo {
+                    ptr: core::ptr::null(),
+                    len: 0,
+                },
+            }
+        }
+        _ => panic!(),
+    }
+}
+
+fn json_rpc_responses_pop(client: &SmoldotClient, chain_id: u32) {
+    let mut client_lock = client.inner.lock().unwrap();
+    match client_lock
+        .chains
+        .get_mut(usize::try_from(chain_id).unwrap())
+        .unwrap()
+    {
+        Chain::Healthy {
+            json_rpc_response, ..
+        } => *json_rpc_response = None,
+        _ => panic!(),
+    }
+}
+
+struct JsonRpcResponsesNonEmptyWaker {
+    chain_id: u32,
+    callback: Arc<JsonRpcResponsesNonEmptyCallback>,
+}
+
+impl task::Wake for JsonRpcResponsesNonEmptyWaker {
+    fn wake(self: Arc<Self>) {
+        if let Some(callback) = self.callback.callback {
+            unsafe { callback(self.callback.user_data.0, self.chain_id) }
+        }
+    }
+}
+
+fn init_logger(
+    max_log_level: u32,
+    callback: bindings::SmoldotLogCallback,
+    user_data: *mut ffi::c_void,
+) {
+    let _ = log::set_boxed_logger(Box::new(Logger {
+        callback,
+        user_data: UserData(user_data),
+    }))
+    .map(|()| {
+        log::set_max_level(match max_log_level {
+            0 => log::LevelFilter::Off,
+            1 => log::LevelFilter::Error,
+            2 => log::LevelFilter::Warn,
+            3 => log::LevelFilter::Info,
+            4 => log::LevelFilter::Debug,
+            _ => log::LevelFilter::Trace,
+        })
+    });
+}
+
+/// Implementation of [`log::Log`] that sends out logs to the FFI.
+struct Logger {
+    callback: bindings::SmoldotLogCallback,
+    user_data: UserData,
+}
+
+impl log::Log for Logger {
+    fn enabled(&self, _: &log::Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &log::Record) {
+        let target = record.target();
+        let message = format!("{}", record.args());
+
+        unsafe {
+            (self.callback)(
+                self.user_data.0,
+                record.level() as usize as u32,
+                target.as_bytes().as_ptr() as *const ffi::c_char,
+                target.as_bytes().len(),
+                message.as_bytes().as_ptr() as *const ffi::c_char,
+                message.as_bytes().len(),
+            )
+        }
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,172 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+// Small program that uses the C bindings of the light client.
+//
+// Must be passed as parameter the path to a chain specification. Exits with a non-zero status
+// code if anything goes wrong.
+
+#define _GNU_SOURCE  // For `memmem`.
+
+#include <pthread.h>
+#include <stdio.h>
+#include <string.h>
+#include <time.h>
+
+#include "smoldot_light.h"
+
+#define CHECK(cond)                                                                \
+  do {                                                                             \
+    if (!(cond)) {                                                                 \
+      fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__, __LINE__, #cond);      \
+      exit(1);                                                                     \
+    }                                                                              \
+  } while (0)
+
+static pthread_mutex_t mutex = PTHREAD_MUTEX_INITIALIZER;
+static pthread_cond_t cond = PTHREAD_COND_INITIALIZER;
+static int responses_non_empty = 0;
+static int num_executor_threads = 0;
+
+struct executor_thread {
+  SmoldotRunExecutorThread run;
+  void *run_data;
+};
+
+static void *executor_thread_main(void *arg) {
+  struct executor_thread *thread = arg;
+  thread->run(thread->run_data);
+  free(thread);
+  return NULL;
+}
+
+static void spawn_executor_thread(void *user_data, SmoldotRunExecutorThread run,
+                                  void *run_data) {
+  (void)user_data;
+
+  struct executor_thread *thread = malloc(sizeof(struct executor_thread));
+  CHECK(thread != NULL);
+  thread->run = run;
+  thread->run_data = run_data;
+
+  pthread_t handle;
+  CHECK(pthread_create(&handle, NULL, executor_thread_main, thread) == 0);
+  CHECK(pthread_detach(handle) == 0);
+  num_executor_threads++;
+}
+
+static void json_rpc_responses_non_empty(void *user_data, uint32_t chain_id) {
+  (void)user_data;
+  (void)chain_id;
+
+  pthread_mutex_lock(&mutex);
+  responses_non_empty = 1;
+  pthread_cond_signal(&cond);
+  pthread_mutex_unlock(&mutex);
+}
+
+static void log_callback(void *user_data, uint32_t level, const char *target,
+                         size_t target_len, const char *message, size_t message_len) {
+  (void)user_data;
+  fprintf(stderr, "[%u] [%.*s] %.*s\n", level, (int)target_len, target, (int)message_len,
+          message);
+}
+
+static char *read_file(const char *path, size_t *len) {
+  FILE *file = fopen(path, "rb");
+  CHECK(file != NULL);
+  CHECK(fseek(file, 0, SEEK_END) == 0);
+  long size = ftell(file);
+  CHECK(size >= 0);
+  CHECK(fseek(file, 0, SEEK_SET) == 0);
+
+  char *content = malloc((size_t)size);
+  CHECK(content != NULL);
+  CHECK(fread(content, 1, (size_t)size, file) == (size_t)size);
+  fclose(file);
+
+  *len = (size_t)size;
+  return content;
+}
+
+// Waits until a JSON-RPC response is available on the given chain, and returns it.
+static SmoldotJsonRpcResponseInfo wait_response(const SmoldotClient *client, uint32_t chain_id) {
+  struct timespec deadline;
+  clock_gettime(CLOCK_REALTIME, &deadline);
+  deadline.tv_sec += 60;
+
+  for (;;) {
+    pthread_mutex_lock(&mutex);
+    responses_non_empty = 0;
+    pthread_mutex_unlock(&mutex);
+
+    SmoldotJsonRpcResponseInfo response = smoldot_json_rpc_responses_peek(client, chain_id);
+    if (response.len != 0)
+      return response;
+
+    pthread_mutex_lock(&mutex);
+    while (!responses_non_empty)
+      CHECK(pthread_cond_timedwait(&cond, &mutex, &deadline) == 0);
+    pthread_mutex_unlock(&mutex);
+  }
+}
+
+int main(int argc, char **argv) {
+  CHECK(argc == 2);
+
+  smoldot_init_logger(3, log_callback, NULL);
+
+  SmoldotClientConfig config = {
+      .spawn_executor_thread = spawn_executor_thread,
+      .spawn_executor_thread_user_data = NULL,
+      .json_rpc_responses_non_empty = json_rpc_responses_non_empty,
+      .json_rpc_responses_non_empty_user_data = NULL,
+  };
+  SmoldotClient *client = smoldot_client_new(config);
+  CHECK(client != NULL);
+  CHECK(num_executor_threads > 0);
+
+  // Invalid chain specifications lead to an erroneous chain.
+  const char *invalid_spec = "not a chain spec";
+  uint32_t erroneous_chain =
+      smoldot_add_chain(client, invalid_spec, strlen(invalid_spec), NULL, 0, 0, 0, NULL, 0);
+  CHECK(smoldot_chain_is_ok(client, erroneous_chain) == 0);
+  CHECK(smoldot_chain_error_len(client, erroneous_chain) != 0);
+  CHECK(smoldot_chain_error_ptr(client, erroneous_chain) != NULL);
+  smoldot_remove_chain(client, erroneous_chain);
+
+  // Add a valid chain and send a JSON-RPC request to it.
+  size_t chain_spec_len;
+  char *chain_spec = read_file(argv[1], &chain_spec_len);
+  uint32_t chain = smoldot_add_chain(client, chain_spec, chain_spec_len, NULL, 0, 16, 16, NULL, 0);
+  free(chain_spec);
+  CHECK(smoldot_chain_is_ok(client, chain) == 1);
+  CHECK(smoldot_chain_error_len(client, chain) == 0);
+
+  const char *request = "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"system_name\",\"params\":[]}";
+  CHECK(smoldot_json_rpc_send(client, request, strlen(request), chain) == 0);
+
+  SmoldotJsonRpcResponseInfo response = wait_response(client, chain);
+  printf("%.*s\n", (int)response.len, response.ptr);
+  CHECK(memmem(response.ptr, response.len, "\"id\":1", 6) != NULL);
+  CHECK(memmem(response.ptr, response.len, "\"result\"", 8) != NULL);
+  smoldot_json_rpc_responses_pop(client, chain);
+
+  smoldot_remove_chain(client, chain);
+  smoldot_client_destroy(client);
+  return 0;
+}
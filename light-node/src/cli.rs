@@ -0,0 +1,54 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Provides the [`CliOptions`] struct that contains all the CLI options that can be passed to the
+//! binary.
+//!
+//! See the documentation of the [`clap`] crate in order to learn more.
+
+use std::{net::SocketAddr, num::NonZeroU32, path::PathBuf};
+
+// Note: the doc-comments applied to this struct and its field are visible when the binary is
+// started with `--help`.
+
+#[derive(Debug, clap::Parser)]
+#[command(about, author, version, long_about = None)]
+pub struct CliOptions {
+    /// Path to a file containing the specification of a chain to connect to. Can be passed
+    /// multiple times. The relay chain of a parachain must also be passed.
+    #[arg(long = "chain", required = true)]
+    pub chains: Vec<PathBuf>,
+    /// Bind point of the JSON-RPC server of the first chain (`<ip>:<port>`). Each following chain
+    /// is served on the following port, in the order in which `--chain` is passed.
+    #[arg(long, default_value = "127.0.0.1:9944")]
+    pub json_rpc_address: SocketAddr,
+    /// Maximum number of JSON-RPC clients that can be connected simultaneously to each chain.
+    #[arg(long, default_value = "64")]
+    pub json_rpc_max_clients: u32,
+    /// Maximum number of JSON-RPC requests of each client that are waiting to be processed.
+    #[arg(long, default_value = "128")]
+    pub json_rpc_max_pending_requests: NonZeroU32,
+    /// Maximum number of active JSON-RPC subscriptions of each client.
+    #[arg(long, default_value = "1024")]
+    pub json_rpc_max_subscriptions: u32,
+    /// Level of logging: off, error, warn, info, debug, trace.
+    #[arg(long, default_value = "info")]
+    pub log_level: log::LevelFilter,
+    /// Do not load or store anything on disk.
+    #[arg(long)]
+    pub tmp: bool,
+}
@@ -0,0 +1,324 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! WebSocket server that serves the JSON-RPC interface of one chain of the light client.
+//!
+//! Every incoming connection adds the chain to the [`smoldot_light::Client`] again, with the
+//! JSON-RPC interface enabled, and removes it when the connection closes. Because the client
+//! deduplicates identical chains, all the connections of a chain share the same networking and
+//! synchronization services, while each connection gets its own JSON-RPC subscriptions.
+
+use futures_lite::future;
+use futures_util::FutureExt as _;
+use smol::net::{TcpListener, TcpStream};
+use smoldot_light::{AddChainConfig, AddChainConfigJsonRpc, ChainId, HandleRpcError};
+use std::{
+    fmt::{self, Write as _},
+    io, mem,
+    net::SocketAddr,
+    num::NonZeroU32,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex,
+    },
+    time::Duration,
+};
+
+/// Configuration for a JSON-RPC server.
+pub struct Config {
+    /// Client the chain is added to.
+    pub client: Arc<Mutex<crate::Client>>,
+
+    /// Name of the chain, used in the logs.
+    pub log_name: String,
+
+    /// Specification of the chain to serve.
+    pub specification: Arc<str>,
+
+    /// Relay chain of the chain to serve, if it is a parachain.
+    pub relay_chain: Option<ChainId>,
+
+    /// Where to bind the server.
+    pub address: SocketAddr,
+
+    /// Maximum number of JSON-RPC clients connected at the same time.
+    pub max_clients: u32,
+
+    /// Maximum number of requests of each client that are waiting to be processed.
+    pub max_pending_requests: NonZeroU32,
+
+    /// Maximum number of active subscriptions of each client.
+    pub max_subscriptions: u32,
+}
+
+/// Binds the server and spawns a background task that accepts incoming connections.
+///
+/// Returns the address the server is actually listening on.
+pub async fn start(config: Config) -> Result<SocketAddr, io::Error> {
+    let tcp_listener = TcpListener::bind(config.address).await?;
+    let listen_address = tcp_listener.local_addr()?;
+
+    smol::spawn(run(Arc::new(config), tcp_listener)).detach();
+
+    Ok(listen_address)
+}
+
+async fn run(config: Arc<Config>, tcp_listener: TcpListener) {
+    let num_clients = Arc::new(AtomicU32::new(0));
+
+    loop {
+        let (tcp_socket, address) = match tcp_listener.accept().await {
+            Ok(v) => v,
+            Err(error) => {
+                // Failing to accept an incoming TCP connection generally happens due to
+                // the limit of file descriptors being reached.
+                // Sleep a little bit and try again.
+                log::warn!(
+                    "json-rpc-tcp-listener-error; chain={}, error={error}",
+                    config.log_name
+                );
+                smol::Timer::after(Duration::from_millis(50)).await;
+                continue;
+            }
+        };
+
+        // Try to increase `num_clients`. Fails if the maximum is reached.
+        if num_clients
+            .fetch_update(Ordering::SeqCst, Ordering::Relaxed, |old_value| {
+                if old_value < config.max_clients {
+                    // Considering that `old_value < max`, and `max` fits in a `u32` by
+                    // definition, then `old_value + 1` also always fits in a `u32`. QED.
+                    // There's no risk of overflow.
+                    Some(old_value + 1)
+                } else {
+                    None
+                }
+            })
+            .is_err()
+        {
+            // Reject the socket without sending back anything. Sending back a status
+            // code would require allocating resources for that socket, which we
+            // specifically don't want to do.
+            log::debug!(
+                "json-rpc-incoming-connection-rejected; chain={}, address={address}",
+                config.log_name
+            );
+            smol::Timer::after(Duration::from_millis(50)).await;
+            continue;
+        }
+
+        log::debug!(
+            "json-rpc-incoming-connection; chain={}, address={address}",
+            config.log_name
+        );
+
+        let config = config.clone();
+        let num_clients = num_clients.clone();
+        smol::spawn(async move {
+            run_client(&config, tcp_socket, address).await;
+            num_clients.fetch_sub(1, Ordering::Release);
+        })
+        .detach();
+    }
+}
+
+async fn run_client(config: &Config, tcp_socket: TcpStream, socket_address: SocketAddr) {
+    // Perform the WebSocket handshake.
+    let (mut ws_sender, mut ws_receiver) = {
+        let mut ws_server = soketto::handshake::Server::new(tcp_socket);
+
+        let key = match ws_server.receive_request().await {
+            Ok(req) => req.key(),
+            Err(error) => {
+                log::debug!("json-rpc-connection-error; address={socket_address}, error={error}");
+                return;
+            }
+        };
+
+        let accept = soketto::handshake::server::Response::Accept {
+            key,
+            protocol: None,
+        };
+
+        match ws_server.send_response(&accept).await {
+            Ok(()) => {}
+            Err(error) => {
+                log::debug!("json-rpc-connection-error; address={socket_address}, error={error}");
+                return;
+            }
+        }
+
+        ws_server.into_builder().finish()
+    };
+
+    // Add the chain to the client. Since a chain with the same specification is already
+    // present, the client reuses its services instead of starting new ones.
+    let (chain_id, mut json_rpc_responses) = {
+        let mut client = config.client.lock().unwrap();
+        match client.add_chain(AddChainConfig {
+            user_data: (),
+            specification: &config.specification,
+            database_content: "",
+            potential_relay_chains: config.relay_chain.into_iter(),
+            json_rpc: AddChainConfigJsonRpc::Enabled {
+                max_pending_requests: config.max_pending_requests,
+                max_subscriptions: config.max_subscriptions,
+            },
+        }) {
+            Ok(success) => (success.chain_id, success.json_rpc_responses.unwrap()),
+            Err(error) => {
+                log::warn!(
+                    "json-rpc-add-chain-error; chain={}, error={error}",
+                    config.log_name
+                );
+                return;
+            }
+        }
+    };
+
+    // Create a future responsible for pulling responses and sending them back.
+    let sending_future = async {
+        let mut must_flush_asap = false;
+
+        loop {
+            // If `must_flush_asap`, we simply peek for the next response but without awaiting.
+            // If `!must_flush_asap`, we wait for as long as necessary.
+            let maybe_response = if must_flush_asap {
+                json_rpc_responses.next().now_or_never()
+            } else {
+                Some(json_rpc_responses.next().await)
+            };
+
+            match maybe_response {
+                None => {
+                    if let Err(err) = ws_sender.flush().await {
+                        break Err(err.to_string());
+                    }
+                    must_flush_asap = false;
+                }
+                Some(Some(response)) => {
+                    log::debug!(
+                        "json-rpc-response; address={}; response={}",
+                        socket_address,
+                        truncated_str(response.chars().filter(|c| !c.is_control()), 128)
+                    );
+
+                    if let Err(err) = ws_sender.send_text_owned(response).await {
+                        break Err(err.to_string());
+                    }
+                    must_flush_asap = true;
+                }
+                Some(None) => {
+                    // The chain is only ever removed after this future has finished.
+                    unreachable!()
+                }
+            };
+        }
+    };
+
+    // Create a future responsible for pulling messages from the socket and sending them to
+    // the client.
+    let receiving_future = async {
+        let mut message = Vec::new();
+        loop {
+            message.clear();
+
+            match ws_receiver.receive_data(&mut message).await {
+                Ok(soketto::Data::Binary(_)) => {
+                    break Err("Unexpected binary frame".to_string());
+                }
+                Ok(soketto::Data::Text(_)) => {} // Handled below.
+                Err(soketto::connection::Error::Closed) => break Ok(()),
+                Err(err) => {
+                    break Err(err.to_string());
+                }
+            }
+
+            let mut request = match String::from_utf8(mem::take(&mut message)) {
+                Ok(r) => r,
+                Err(error) => {
+                    break Err(format!("Non-UTF8 text frame: {error}"));
+                }
+            };
+
+            log::debug!(
+                "json-rpc-request; address={}; request={}",
+                socket_address,
+                truncated_str(request.chars().filter(|c| !c.is_control()), 128)
+            );
+
+            // If the queue of requests is full, we stop reading from the socket until some
+            // space is available. The client doesn't provide any way to be notified when that
+            // is the case, so we simply try again after a small delay.
+            loop {
+                let result = config
+                    .client
+                    .lock()
+                    .unwrap()
+                    .json_rpc_request(request, chain_id);
+                match result {
+                    Ok(()) => break,
+                    Err(HandleRpcError::TooManyPendingRequests { json_rpc_request }) => {
+                        request = json_rpc_request;
+                        smol::Timer::after(Duration::from_millis(50)).await;
+                    }
+                }
+            }
+        }
+    };
+
+    // Run these two futures until completion.
+    match future::or(sending_future, receiving_future).await {
+        Ok(()) => {
+            log::debug!("json-rpc-connection-closed; address={socket_address}");
+        }
+        Err(error) => {
+            log::debug!("json-rpc-connection-error; address={socket_address}, error={error}");
+        }
+    }
+
+    config.client.lock().unwrap().remove_chain(chain_id);
+}
+
+/// Returns an opaque object implementing the `fmt::Display` trait. Truncates the given `char`
+/// yielding iterator to the given number of elements, and if the limit is reached adds a `…` at
+/// the end.
+fn truncated_str<'a>(
+    input: impl Iterator<Item = char> + Clone + 'a,
+    limit: usize,
+) -> impl fmt::Display + 'a {
+    struct Iter<I>(I, usize);
+
+    impl<I: Iterator<Item = char> + Clone> fmt::Display for Iter<I> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut counter = 0;
+            for c in self.0.clone() {
+                f.write_char(c)?;
+
+                counter += 1;
+                if counter >= self.1 {
+                    f.write_char('…')?;
+                    break;
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    Iter(input, limit)
+}
@@ -0,0 +1,295 @@
+// Smoldot
+// Copyright (C) 2023  Pierre Krieger
+// SPDX-License-Identifier: GPL-3.0-or-later WITH Classpath-exception-2.0
+
+// This program is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// This program is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with this program.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Standalone light client.
+//!
+//! Connects to one or more chains using [`smoldot_light`] and serves the JSON-RPC interface of
+//! each chain through a WebSocket server. The database of each chain is regularly saved on disk
+//! in order to speed up the next start.
+
+#![deny(rustdoc::broken_intra_doc_links)]
+#![deny(unused_crate_dependencies)]
+
+use futures_lite::future;
+use smoldot_light::{
+    platform::default::DefaultPlatform, AddChainConfig, AddChainConfigJsonRpc, ChainId,
+    JsonRpcResponses,
+};
+use std::{
+    fs, io,
+    net::SocketAddr,
+    num::NonZeroU32,
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+mod cli;
+mod json_rpc_server;
+
+/// Type of the light client shared between the JSON-RPC servers.
+type Client = smoldot_light::Client<Arc<DefaultPlatform>>;
+
+/// Interval between two saves of the databases of the chains.
+const DATABASE_SAVE_INTERVAL: Duration = Duration::from_secs(60);
+
+fn main() {
+    smol::block_on(async_main())
+}
+
+async fn async_main() {
+    let cli_options = <cli::CliOptions as clap::Parser>::parse();
+
+    // The `smoldot_light` library emits logs through the `log` crate.
+    env_logger::Builder::new()
+        .filter_level(cli_options.log_level)
+        .init();
+
+    // Directory where the databases of the chains are stored.
+    let base_storage_directory = if cli_options.tmp {
+        None
+    } else if let Some(base) = directories::ProjectDirs::from("io", "smoldot", "smoldot-light") {
+        Some(base.data_dir().to_owned())
+    } else {
+        log::warn!(
+            "Failed to fetch $HOME directory. Falling back to storing everything in memory, \
+            meaning that everything will be lost when the node stops. If this is intended, \
+            please make this explicit by passing the `--tmp` flag instead."
+        );
+        None
+    };
+
+    // Load all the chain specifications and assign a JSON-RPC address to each of them.
+    let mut chains = Vec::with_capacity(cli_options.chains.len());
+    for (index, path) in cli_options.chains.iter().enumerate() {
+        let specification = fs::read_to_string(path).expect("Failed to read chain specification");
+        let parsed_specification = smoldot::chain_spec::ChainSpec::from_json_bytes(&specification)
+            .expect("Failed to decode chain specification");
+
+        let json_rpc_address = {
+            let mut address = cli_options.json_rpc_address;
+            let port = u16::try_from(index)
+                .ok()
+                .and_then(|index| address.port().checked_add(index))
+                .expect("Too many chains for the JSON-RPC port range");
+            address.set_port(port);
+            address
+        };
+
+        let database_path = base_storage_directory
+            .as_ref()
+            .map(|dir| dir.join(parsed_specification.id()).join("database.json"));
+
+        chains.push(ChainToAdd {
+            log_name: parsed_specification.id().to_owned(),
+            relay_chain_name: parsed_specification
+                .relay_chain()
+                .map(|(name, _)| name.to_owned()),
+            specification: Arc::from(specification),
+            json_rpc_address,
+            database_path,
+        });
+    }
+
+    // Relay chains must be added before their parachains.
+    chains.sort_by_key(|chain| chain.relay_chain_name.is_some());
+
+    let client = Arc::new(Mutex::new(Client::new(DefaultPlatform::new(
+        env!("CARGO_PKG_NAME").into(),
+        env!("CARGO_PKG_VERSION").into(),
+    ))));
+
+    // Add each chain a first time. This instance of the chain keeps the chain alive when no
+    // JSON-RPC client is connected, and is used to save the database of the chain. The
+    // JSON-RPC servers then add the chain again for each of their clients.
+    let mut relay_chains = Vec::<(String, ChainId)>::new();
+    let mut saved_chains = Vec::new();
+    for chain in chains {
+        let database_content = chain
+            .database_path
+            .as_ref()
+            .map(|path| match fs::read_to_string(path) {
+                Ok(content) => content,
+                Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+                Err(err) => {
+                    log::warn!("database-load-error; chain={}, error={err}", chain.log_name);
+                    String::new()
+                }
+            })
+            .unwrap_or_default();
+
+        // The client searches for the relay chain of a parachain by its name within the list
+        // we pass. Restricting this list ahead of time lets us know which chain it picked.
+        let potential_relay_chains = relay_chains
+            .iter()
+            .filter(|(name, _)| Some(name) == chain.relay_chain_name.as_ref())
+            .map(|(_, chain_id)| *chain_id)
+            .collect::<Vec<_>>();
+
+        let success = client
+            .lock()
+            .unwrap()
+            .add_chain(AddChainConfig {
+                user_data: (),
+                specification: &chain.specification,
+                database_content: &database_content,
+                potential_relay_chains: potential_relay_chains.iter().copied(),
+                json_rpc: if chain.database_path.is_some() {
+                    AddChainConfigJsonRpc::Enabled {
+                        max_pending_requests: NonZeroU32::new(1).unwrap(),
+                        max_subscriptions: 0,
+                    }
+                } else {
+                    AddChainConfigJsonRpc::Disabled
+                },
+            })
+            .unwrap_or_else(|err| panic!("Failed to add chain {}: {err}", chain.log_name));
+
+        // If `add_chain` succeeded, then there is exactly one potential relay chain.
+        let relay_chain = if chain.relay_chain_name.is_some() {
+            Some(potential_relay_chains[0])
+        } else {
+            relay_chains.push((chain.log_name.clone(), success.chain_id));
+            None
+        };
+
+        let listen_address = json_rpc_server::start(json_rpc_server::Config {
+            client: client.clone(),
+            log_name: chain.log_name.clone(),
+            specification: chain.specification.clone(),
+            relay_chain,
+            address: chain.json_rpc_address,
+            max_clients: cli_options.json_rpc_max_clients,
+            max_pending_requests: cli_options.json_rpc_max_pending_requests,
+            max_subscriptions: cli_options.json_rpc_max_subscriptions,
+        })
+        .await
+        .unwrap_or_else(|err| panic!("Failed to start JSON-RPC server: {err}"));
+        log::info!(
+            "json-rpc-server-started; chain={}, address={listen_address}",
+            chain.log_name
+        );
+
+        if let Some(database_path) = chain.database_path {
+            fs::create_dir_all(database_path.parent().unwrap()).unwrap();
+            saved_chains.push(SavedChain {
+                log_name: chain.log_name,
+                chain_id: success.chain_id,
+                json_rpc_responses: success.json_rpc_responses.unwrap(),
+                database_path,
+            });
+        }
+    }
+
+    // This is the point where we might start catching Ctrl+C.
+    let (ctrlc_tx, ctrlc_rx) = smol::channel::bounded(1);
+    if let Err(err) = ctrlc::set_handler(move || {
+        let _ = ctrlc_tx.try_send(());
+    }) {
+        // It is not critical to fail to setup the Ctrl-C handler.
+        log::warn!("ctrlc-handler-setup-fail; err={err}");
+    }
+
+    // Save the databases at a regular interval, and one last time before exiting.
+    loop {
+        let ctrlc_detected = future::or(
+            async {
+                let _ = ctrlc_rx.recv().await;
+                true
+            },
+            async {
+                smol::Timer::after(DATABASE_SAVE_INTERVAL).await;
+                false
+            },
+        )
+        .await;
+
+        for chain in &mut saved_chains {
+            save_database(&client, chain).await;
+        }
+
+        if ctrlc_detected {
+            break;
+        }
+    }
+}
+
+/// Queries the database of the given chain from the client and writes it to disk.
+async fn save_database(client: &Mutex<Client>, chain: &mut SavedChain) {
+    match client.lock().unwrap().json_rpc_request(
+        r#"{"jsonrpc":"2.0","id":"1","method":"chainHead_unstable_finalizedDatabase","params":[]}"#,
+        chain.chain_id,
+    ) {
+        Ok(()) => {}
+        Err(smoldot_light::HandleRpcError::TooManyPendingRequests { .. }) => {
+            // Requests are only ever sent by this function, which waits for the response.
+            unreachable!()
+        }
+    }
+
+    // The chain is never removed, and thus `next` never returns `None`.
+    let response = chain.json_rpc_responses.next().await.unwrap();
+    let Some(database) = smoldot::json_rpc::parse::parse_response(&response)
+        .ok()
+        .and_then(|response| response.into_success())
+        .and_then(|(_, result_json)| serde_json::from_str::<String>(result_json).ok())
+    else {
+        log::warn!(
+            "database-save-error; chain={}, error=unexpected JSON-RPC response",
+            chain.log_name
+        );
+        return;
+    };
+
+    // Write to a temporary file first, so that the existing database isn't lost if the node
+    // stops in the middle of the write.
+    let temporary_path = chain.database_path.with_extension("json.tmp");
+    if let Err(err) = fs::write(&temporary_path, database)
+        .and_then(|()| fs::rename(&temporary_path, &chain.database_path))
+    {
+        log::warn!("database-save-error; chain={}, error={err}", chain.log_name);
+        return;
+    }
+
+    log::debug!("database-saved; chain={}", chain.log_name);
+}
+
+/// Chain passed on the command line, before it is added to the client.
+struct ChainToAdd {
+    /// Name of the chain, used in the logs.
+    log_name: String,
+    /// Name of the relay chain found in the specification, if the chain is a parachain.
+    relay_chain_name: Option<String>,
+    /// Specification of the chain.
+    specification: Arc<str>,
+    /// Where the JSON-RPC server of this chain is bound.
+    json_rpc_address: SocketAddr,
+    /// Path to the file containing the database of the chain. `None` if `--tmp`.
+    database_path: Option<PathBuf>,
+}
+
+/// Chain whose database is saved on disk.
+struct SavedChain {
+    /// Name of the chain, used in the logs.
+    log_name: String,
+    /// Identifier of the chain within the client.
+    chain_id: ChainId,
+    /// Responses to the requests sent by [`save_database`].
+    json_rpc_responses: JsonRpcResponses,
+    /// Path to the file containing the database of the chain.
+    database_path: PathBuf,
+}
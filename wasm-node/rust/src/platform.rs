@@ -171,6 +171,8 @@ impl smoldot_light::platform::PlatformRef for PlatformRef {
             smoldot_light::platform::ConnectionType::WebSocketDns { secure: true, .. } => 14,
             smoldot_light::platform::ConnectionType::WebRtcIpv4 => 16,
             smoldot_light::platform::ConnectionType::WebRtcIpv6 => 17,
+            // In-process connections are never supported by the JavaScript side.
+            smoldot_light::platform::ConnectionType::Memory => return false,
         };
 
         unsafe { bindings::connection_type_supported(ty) != 0 }
@@ -234,6 +236,11 @@ impl smoldot_light::platform::PlatformRef for PlatformRef {
                 .chain(port.to_be_bytes())
                 .chain(hostname.as_bytes().iter().copied())
                 .collect(),
+            smoldot_light::platform::Address::Memory { .. } => {
+                // The API user of the `PlatformRef` trait is never supposed to open connections
+                // of a type that isn't supported.
+                unreachable!()
+            }
         };
 
         let write_closable = match address {
@@ -241,6 +248,7 @@ impl smoldot_light::platform::PlatformRef for PlatformRef {
             | smoldot_light::platform::Address::TcpDns { .. } => true,
             smoldot_light::platform::Address::WebSocketIp { .. }
             | smoldot_light::platform::Address::WebSocketDns { .. } => false,
+            smoldot_light::platform::Address::Memory { .. } => unreachable!(),
         };
 
         unsafe {